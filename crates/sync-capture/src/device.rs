@@ -0,0 +1,177 @@
+// Identification of V4L2 camera devices through sysfs.
+//
+// USB cameras expose their serial number and bus path on the USB device node,
+// which is the parent of the video interface that `/sys/class/video4linux/videoN/device`
+// points to. Non-Linux platforms and non-USB devices simply report `None`.
+
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+const VIDEO4LINUX_SYSFS: &str = "/sys/class/video4linux";
+
+fn usb_device_dir(video_index: u32) -> Option<PathBuf> {
+    let interface = fs::canonicalize(format!("{}/video{}/device", VIDEO4LINUX_SYSFS, video_index)).ok()?;
+    interface.parent().map(|dir| dir.to_path_buf())
+}
+
+fn read_attribute(video_index: u32, attribute: &str) -> Option<String> {
+    let value = fs::read_to_string(usb_device_dir(video_index)?.join(attribute)).ok()?;
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+/// USB serial number of `/dev/video<index>`, if the device reports one.
+pub fn usb_serial(video_index: u32) -> Option<String> {
+    read_attribute(video_index, "serial")
+}
+
+/// USB bus path (e.g. `1-2.3`) of `/dev/video<index>`, stable as long as the cable stays in the same port.
+pub fn usb_bus_path(video_index: u32) -> Option<String> {
+    let dir = usb_device_dir(video_index)?;
+    // Only USB device nodes carry a bus number
+    if !dir.join("busnum").exists() {
+        return None;
+    }
+    dir.file_name().map(|name| name.to_string_lossy().into_owned())
+}
+
+/// How a camera is identified in the configuration.
+///
+/// Indices change across reboots and re-plugging, so rigs should prefer
+/// `serial:<usb serial>` or `bus:<usb bus path>`; both are resolved to the
+/// current `/dev/videoN` node at startup.
+/// Serialized as its string form, e.g. `"serial:ABC123"`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "String", try_from = "String")
+)]
+pub enum CameraSelector {
+    Index(u32),
+    Serial(String),
+    BusPath(String),
+}
+
+impl Default for CameraSelector {
+    fn default() -> Self {
+        CameraSelector::Index(0)
+    }
+}
+
+impl FromStr for CameraSelector {
+    type Err = String;
+
+    /// Accepts `2`, `index:2`, `/dev/video2`, `serial:ABC123` and `bus:1-2.3`.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let parse_index = |index: &str| {
+            index
+                .parse::<u32>()
+                .map(CameraSelector::Index)
+                .map_err(|_| format!("invalid camera index '{}'", index))
+        };
+        if let Some(serial) = text.strip_prefix("serial:") {
+            Ok(CameraSelector::Serial(serial.to_string()))
+        } else if let Some(bus_path) = text.strip_prefix("bus:") {
+            Ok(CameraSelector::BusPath(bus_path.to_string()))
+        } else if let Some(index) = text.strip_prefix("index:") {
+            parse_index(index)
+        } else if let Some(index) = text.strip_prefix("/dev/video") {
+            parse_index(index)
+        } else {
+            parse_index(text)
+        }
+    }
+}
+
+impl fmt::Display for CameraSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CameraSelector::Index(index) => write!(f, "index:{}", index),
+            CameraSelector::Serial(serial) => write!(f, "serial:{}", serial),
+            CameraSelector::BusPath(bus_path) => write!(f, "bus:{}", bus_path),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<CameraSelector> for String {
+    fn from(selector: CameraSelector) -> Self {
+        selector.to_string()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<String> for CameraSelector {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        text.parse()
+    }
+}
+
+impl CameraSelector {
+    /// Resolves the selector to the current video device index.
+    pub fn resolve(&self) -> Result<u32, String> {
+        let matches = |index: u32| match self {
+            CameraSelector::Index(_) => false,
+            CameraSelector::Serial(serial) => usb_serial(index).as_deref() == Some(serial.as_str()),
+            CameraSelector::BusPath(bus_path) => usb_bus_path(index).as_deref() == Some(bus_path.as_str()),
+        };
+        match self {
+            CameraSelector::Index(index) => Ok(*index),
+            _ => capture_nodes()
+                .into_iter()
+                .find(|index| matches(*index))
+                .ok_or_else(|| format!("no camera found for {}", self)),
+        }
+    }
+}
+
+/// Indices of the primary video node of each device, sorted. UVC cameras also
+/// create metadata nodes, which report a non-zero `index` attribute.
+fn capture_nodes() -> Vec<u32> {
+    let Ok(entries) = fs::read_dir(VIDEO4LINUX_SYSFS) else {
+        return Vec::new();
+    };
+    let mut nodes: Vec<u32> = entries
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.strip_prefix("video")?.parse().ok())
+        .filter(|index| {
+            fs::read_to_string(format!("{}/video{}/index", VIDEO4LINUX_SYSFS, index))
+                .map(|node| node.trim() == "0")
+                .unwrap_or(true)
+        })
+        .collect();
+    nodes.sort_unstable();
+    nodes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selectors_parse_from_every_form_and_print_canonically() {
+        for text in ["2", "index:2", "/dev/video2"] {
+            assert_eq!(text.parse::<CameraSelector>(), Ok(CameraSelector::Index(2)));
+        }
+        assert_eq!("serial:ABC123".parse::<CameraSelector>(), Ok(CameraSelector::Serial("ABC123".to_string())));
+        assert_eq!("bus:1-2.3".parse::<CameraSelector>(), Ok(CameraSelector::BusPath("1-2.3".to_string())));
+        assert_eq!("front".parse::<CameraSelector>(), Err("invalid camera index 'front'".to_string()));
+        assert_eq!("index:-1".parse::<CameraSelector>(), Err("invalid camera index '-1'".to_string()));
+
+        for text in ["index:2", "serial:ABC123", "bus:1-2.3"] {
+            assert_eq!(text.parse::<CameraSelector>().unwrap().to_string(), text);
+        }
+        assert_eq!(CameraSelector::default().to_string(), "index:0");
+    }
+
+    #[test]
+    fn indices_resolve_as_given_and_unknown_identities_fail() {
+        assert_eq!(CameraSelector::Index(5).resolve(), Ok(5));
+        let missing = CameraSelector::Serial("no-such-camera-serial".to_string());
+        assert_eq!(missing.resolve(), Err("no camera found for serial:no-such-camera-serial".to_string()));
+    }
+}
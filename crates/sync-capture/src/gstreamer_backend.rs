@@ -0,0 +1,214 @@
+// Capture from an arbitrary GStreamer pipeline.
+//
+// For cameras only reachable through GStreamer, e.g. a GMSL deserializer
+// behind `v4l2src ! nvvidconv` or `nvarguscamerasrc`. The pipeline description
+// ends where the frames should be taken; the backend appends
+// `videoconvert ! video/x-raw,format=RGB ! appsink` and pulls the converted
+// frames from the appsink.
+//
+// The pipeline is forced onto GStreamer's system clock, which runs on
+// CLOCK_MONOTONIC, so a buffer's PTS plus the pipeline's base time is the
+// monotonic time the source stamped it (v4l2src takes the driver's buffer
+// timestamp). It is reported as the frame's driver timestamp, and
+// `match_on_capture_time` tells the capture app to match on it instead of
+// the dequeue time: conversion and scaling in the pipeline then add no
+// jitter to the association. Buffer offsets are the sequence numbers of
+// sources that count frames (v4l2src), and buffers flagged corrupted count
+// as driver errors.
+
+use std::io;
+use std::time::Duration;
+
+use gstreamer as gst;
+use gstreamer::prelude::*;
+use gstreamer_app::AppSink;
+use gstreamer_video::VideoInfo;
+
+use super::{CaptureBackend, CapturedFrame, FramePool, PoolStats};
+
+/// Name of the appsink appended to the pipeline.
+const SINK_NAME: &str = "sync_capture_sink";
+
+/// How long the pipeline may take to deliver its first frame.
+const START_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Frames the appsink holds before the pipeline blocks; sources drop frames then.
+const MAX_QUEUED: u32 = 4;
+
+/// Frames pulled from the appsink at the end of a GStreamer pipeline.
+pub struct GstreamerBackend {
+    pipeline: gst::Pipeline,
+    sink: AppSink,
+    description: String,
+    width: u32,
+    height: u32,
+    timeout: Option<Duration>,
+    // Pulled while starting, delivered by the first `frame`
+    first: Option<gst::Sample>,
+    pool: FramePool,
+}
+
+impl GstreamerBackend {
+    /// Starts `description` (`gst-launch-1.0` syntax, without a sink) and waits until it negotiated its format.
+    pub fn open(description: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        gst::init()?;
+        let launch = format!(
+            "{} ! videoconvert ! video/x-raw,format=RGB ! appsink name={} max-buffers={} drop=false sync=false",
+            description, SINK_NAME, MAX_QUEUED
+        );
+        let pipeline = gst::parse::launch(&launch)?
+            .downcast::<gst::Pipeline>()
+            .map_err(|_| format!("'{}' is not a GStreamer pipeline", description))?;
+        let sink = pipeline
+            .by_name(SINK_NAME)
+            .and_then(|element| element.downcast::<AppSink>().ok())
+            .ok_or("GStreamer pipeline without its appsink")?;
+        // PTS plus base time on CLOCK_MONOTONIC, even if an element offers its own clock
+        pipeline.use_clock(Some(&gst::SystemClock::obtain()));
+
+        pipeline.set_state(gst::State::Playing)?;
+        let mut backend = Self {
+            pipeline,
+            sink,
+            description: description.to_string(),
+            width: 0,
+            height: 0,
+            timeout: None,
+            first: None,
+            pool: FramePool::default(),
+        };
+        // Live sources do not preroll: the first frame tells the negotiated format
+        let Some(first) = backend.sink.try_pull_sample(gst::ClockTime::from_nseconds(START_TIMEOUT.as_nanos() as u64)) else {
+            backend.check_bus()?;
+            return Err(format!("GStreamer pipeline delivered no frame within {:?}", START_TIMEOUT).into());
+        };
+        let info = VideoInfo::from_caps(first.caps().ok_or("GStreamer sample without caps")?)?;
+        (backend.width, backend.height) = (info.width(), info.height());
+        backend.first = Some(first);
+        Ok(backend)
+    }
+
+    // Turns an error or end of stream posted by the pipeline into an error
+    fn check_bus(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(bus) = self.pipeline.bus() else {
+            return Ok(());
+        };
+        while let Some(message) = bus.pop_filtered(&[gst::MessageType::Error, gst::MessageType::Eos]) {
+            match message.view() {
+                gst::MessageView::Error(error) => {
+                    let source = error.src().map(|src| src.path_string().to_string()).unwrap_or_default();
+                    return Err(format!("GStreamer error from {}: {} ({})", source, error.error(), error.debug().unwrap_or_default()).into());
+                }
+                gst::MessageView::Eos(_) => return Err("GStreamer pipeline reached the end of the stream".into()),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+// Rows are padded to 4 bytes, the frame is packed
+fn pack_rows(data: &[u8], stride: usize, width: u32, height: u32, rgb: &mut Vec<u8>) {
+    let row_len = width as usize * 3;
+    rgb.reserve(row_len * height as usize);
+    for row in data.chunks(stride).take(height as usize) {
+        rgb.extend_from_slice(&row[..row_len.min(row.len())]);
+    }
+}
+
+// The source element of a pipeline description, e.g. `v4l2src device=/dev/video2`
+fn source_element(description: &str) -> &str {
+    description.split('!').next().unwrap_or_default().trim()
+}
+
+impl Drop for GstreamerBackend {
+    fn drop(&mut self) {
+        let _ = self.pipeline.set_state(gst::State::Null);
+    }
+}
+
+impl CaptureBackend for GstreamerBackend {
+    fn frame(&mut self) -> Result<CapturedFrame, Box<dyn std::error::Error>> {
+        let sample = match (self.first.take(), self.timeout) {
+            (Some(first), _) => Some(first),
+            (None, Some(timeout)) => self.sink.try_pull_sample(gst::ClockTime::from_nseconds(timeout.as_nanos() as u64)),
+            (None, None) => self.sink.pull_sample().ok(),
+        };
+        let Some(sample) = sample else {
+            self.check_bus()?;
+            if self.sink.is_eos() {
+                return Err("GStreamer pipeline reached the end of the stream".into());
+            }
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "no frame from the GStreamer pipeline within the frame timeout").into());
+        };
+        let buffer = sample.buffer().ok_or("GStreamer sample without a buffer")?;
+        let info = VideoInfo::from_caps(sample.caps().ok_or("GStreamer sample without caps")?)?;
+        let (width, height, stride) = (info.width(), info.height(), info.stride()[0] as usize);
+        let map = buffer.map_readable()?;
+
+        let mut rgb = self.pool.take();
+        pack_rows(&map, stride, width, height, &mut rgb);
+        self.width = width;
+        self.height = height;
+
+        let capture_ns = buffer.pts().zip(self.pipeline.base_time()).map(|(pts, base_time)| (pts + base_time).nseconds());
+        Ok(CapturedFrame {
+            width,
+            height,
+            rgb: self.pool.share(rgb),
+            raw_len: map.size(),
+            driver_timestamp_ns: capture_ns,
+            sequence: (buffer.offset() != gst::BUFFER_OFFSET_NONE).then_some(buffer.offset() as u32),
+            driver_error: buffer.flags().contains(gst::BufferFlags::CORRUPTED),
+        })
+    }
+
+    fn device_name(&self) -> String {
+        source_element(&self.description).to_string()
+    }
+
+    fn resolution(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn describe(&self) -> String {
+        format!("gstreamer, RGB {}x{}, {}", self.width, self.height, self.description)
+    }
+
+    fn set_frame_timeout(&mut self, timeout: Duration) {
+        self.timeout = Some(timeout);
+    }
+
+    fn match_on_capture_time(&self) -> bool {
+        true
+    }
+
+    fn pool_stats(&mut self) -> Option<PoolStats> {
+        Some(self.pool.stats())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn padded_rows_are_packed() {
+        // 2x2 RGB with rows padded from 6 to 8 bytes
+        let data = [1, 2, 3, 4, 5, 6, 0, 0, 7, 8, 9, 10, 11, 12, 0, 0];
+        let mut rgb = vec![];
+        pack_rows(&data, 8, 2, 2, &mut rgb);
+        assert_eq!(rgb, [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]);
+
+        // A short last row is taken as far as it goes
+        let mut rgb = vec![];
+        pack_rows(&data[..12], 8, 2, 2, &mut rgb);
+        assert_eq!(rgb.len(), 10);
+    }
+
+    #[test]
+    fn devices_are_named_by_the_source_element() {
+        assert_eq!(source_element("v4l2src device=/dev/video2 ! nvvidconv ! video/x-raw"), "v4l2src device=/dev/video2");
+        assert_eq!(source_element(" nvarguscamerasrc sensor-id=1 "), "nvarguscamerasrc sensor-id=1");
+    }
+}
@@ -0,0 +1,191 @@
+// Camera capture backends.
+//
+// `nokhwa` is the portable default. On Linux the `v4l2` backend talks to the
+// driver directly, which allows choosing the queued buffer count and memory
+// type (see `tuning`) and exposes the driver's buffer metadata. The
+// `gstreamer` backend takes frames from any GStreamer pipeline, for cameras
+// behind vendor elements, and matches them on their PTS. Each backend has its
+// own feature; the frame type, tuning, camera identity (`device`) and
+// the synthetic `test_pattern` camera for end-to-end tests are always available.
+// On Linux, `loopback` writes the synchronized frames to a v4l2loopback device;
+// with the `rtsp` feature they can also be served over the network (`rtsp`).
+// Backends convert into recycled buffers that all consumers of a frame share
+// without copies (`pool`).
+
+pub mod device;
+#[cfg(feature = "gstreamer")]
+mod gstreamer_backend;
+#[cfg(target_os = "linux")]
+pub mod loopback;
+pub mod pool;
+#[cfg(feature = "rtsp")]
+pub mod rtsp;
+#[cfg(feature = "nokhwa")]
+mod nokhwa_backend;
+pub mod test_pattern;
+pub mod tuning;
+#[cfg(all(target_os = "linux", feature = "v4l2"))]
+mod v4l2_backend;
+
+use std::time::Duration;
+
+#[cfg(feature = "nokhwa")]
+pub use nokhwa_backend::NokhwaBackend;
+#[cfg(feature = "gstreamer")]
+pub use gstreamer_backend::GstreamerBackend;
+pub use pool::{FrameBuffer, FramePool, PoolStats};
+pub use test_pattern::TestPatternBackend;
+#[cfg(all(target_os = "linux", feature = "v4l2"))]
+pub use v4l2_backend::V4l2Backend;
+
+/// A captured frame converted to RGB for processing and display; clones share the pixels.
+#[derive(Clone)]
+pub struct CapturedFrame {
+    pub width: u32,
+    pub height: u32,
+    /// Packed RGB, 3 bytes per pixel, shared with every consumer of the frame.
+    pub rgb: FrameBuffer,
+    /// Size of the buffer as delivered by the driver (before conversion).
+    pub raw_len: usize,
+    /// Driver buffer timestamp (CLOCK_MONOTONIC ns), if the backend exposes it and the driver stamps on that clock.
+    pub driver_timestamp_ns: Option<u64>,
+    /// Driver frame sequence number, if the backend exposes it.
+    pub sequence: Option<u32>,
+    /// The driver flagged the buffer as corrupted (V4L2_BUF_FLAG_ERROR, e.g. UVC payload errors).
+    pub driver_error: bool,
+}
+
+pub trait CaptureBackend {
+    /// Blocks until the next frame is available.
+    fn frame(&mut self) -> Result<CapturedFrame, Box<dyn std::error::Error>>;
+
+    /// Human readable device name for logs and the session manifest.
+    fn device_name(&self) -> String;
+
+    /// Negotiated resolution.
+    fn resolution(&self) -> (u32, u32);
+
+    /// Short description of the stream configuration (backend, buffers, format).
+    fn describe(&self) -> String;
+
+    /// Makes `frame` give up with a `TimedOut` I/O error after `timeout` without a frame, so
+    /// the caller gets to notice a stalled stream. Backends that cannot time out ignore it.
+    fn set_frame_timeout(&mut self, _timeout: Duration) {}
+
+    /// Buffers queued to the driver, the most frames that can wait for the capture loop.
+    fn queued_buffers(&self) -> Option<u32> {
+        None
+    }
+
+    /// Whether the capture app matches frames on `driver_timestamp_ns`, the time the frame was
+    /// captured, instead of the time it was dequeued, which adds the varying delay of the queue,
+    /// the pipeline and the capture loop. Frames without a driver timestamp use the dequeue time.
+    fn match_on_capture_time(&self) -> bool {
+        false
+    }
+
+    /// Buffer counts of the pool the frames are converted into, if the backend has one.
+    fn pool_stats(&mut self) -> Option<PoolStats> {
+        None
+    }
+}
+
+/// Whether a `frame` error is the frame timeout rather than a failure.
+pub fn is_timeout(error: &(dyn std::error::Error + 'static)) -> bool {
+    error.downcast_ref::<std::io::Error>().is_some_and(|e| e.kind() == std::io::ErrorKind::TimedOut)
+}
+
+/// Converts packed YUYV (YUV 4:2:2) to RGB using BT.601 coefficients.
+pub fn yuyv_to_rgb(yuyv: &[u8]) -> Vec<u8> {
+    let mut rgb = Vec::new();
+    yuyv_to_rgb_into(yuyv, &mut rgb);
+    rgb
+}
+
+/// `yuyv_to_rgb` appending to `rgb`, e.g. a recycled buffer of a `FramePool`.
+pub fn yuyv_to_rgb_into(yuyv: &[u8], rgb: &mut Vec<u8>) {
+    let clamp = |v: i32| v.clamp(0, 255) as u8;
+    rgb.reserve(yuyv.len() / 2 * 3);
+    for chunk in yuyv.chunks_exact(4) {
+        let (y0, u, y1, v) = (chunk[0] as i32, chunk[1] as i32 - 128, chunk[2] as i32, chunk[3] as i32 - 128);
+        for y in [y0, y1] {
+            let c = 298 * (y - 16);
+            rgb.push(clamp((c + 409 * v + 128) >> 8));
+            rgb.push(clamp((c - 100 * u - 208 * v + 128) >> 8));
+            rgb.push(clamp((c + 516 * u + 128) >> 8));
+        }
+    }
+}
+
+/// Converts packed RGB to YUYV (YUV 4:2:2, BT.601 limited range), the inverse of `yuyv_to_rgb`.
+/// Each pixel pair shares the average chroma; a trailing odd pixel is dropped.
+pub fn rgb_to_yuyv(rgb: &[u8]) -> Vec<u8> {
+    let luma = |p: &[u8]| ((66 * p[0] as i32 + 129 * p[1] as i32 + 25 * p[2] as i32 + 128) >> 8) + 16;
+    let mut yuyv = Vec::with_capacity(rgb.len() / 3 * 2);
+    for pair in rgb.chunks_exact(6) {
+        let (r, g, b) = (pair[0] as i32 + pair[3] as i32, pair[1] as i32 + pair[4] as i32, pair[2] as i32 + pair[5] as i32);
+        let u = ((-38 * r - 74 * g + 112 * b + 256) >> 9) + 128;
+        let v = ((112 * r - 94 * g - 18 * b + 256) >> 9) + 128;
+        yuyv.extend_from_slice(&[luma(&pair[..3]) as u8, u.clamp(0, 255) as u8, luma(&pair[3..]) as u8, v.clamp(0, 255) as u8]);
+    }
+    yuyv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A backend of its own needs none of the backend features, only the frame type
+    struct StillCamera;
+
+    impl CaptureBackend for StillCamera {
+        fn frame(&mut self) -> Result<CapturedFrame, Box<dyn std::error::Error>> {
+            Ok(CapturedFrame {
+                width: 2,
+                height: 1,
+                rgb: vec![255; 6].into(),
+                raw_len: 4,
+                driver_timestamp_ns: None,
+                sequence: None,
+                driver_error: false,
+            })
+        }
+
+        fn device_name(&self) -> String {
+            "still".to_string()
+        }
+
+        fn resolution(&self) -> (u32, u32) {
+            (2, 1)
+        }
+
+        fn describe(&self) -> String {
+            "still, 2x1".to_string()
+        }
+    }
+
+    #[test]
+    fn custom_backends_build_without_the_backend_features() {
+        let mut camera: Box<dyn CaptureBackend> = Box::new(StillCamera);
+        let frame = camera.frame().unwrap();
+        assert_eq!((frame.width, frame.height), camera.resolution());
+        assert_eq!(frame.rgb.len(), 3 * 2);
+        assert_eq!(camera.describe(), "still, 2x1");
+        assert_eq!(camera.queued_buffers(), None);
+        assert!(!camera.match_on_capture_time());
+        assert!(camera.pool_stats().is_none());
+    }
+
+    #[test]
+    fn yuyv_round_trips_through_rgb() {
+        // Gray pairs and a saturated pair, plus a trailing odd pixel that is dropped
+        let rgb = [128, 128, 128, 128, 128, 128, 200, 30, 30, 200, 30, 30, 9, 9, 9];
+        let yuyv = rgb_to_yuyv(&rgb);
+        assert_eq!(yuyv.len(), 8);
+        let back = yuyv_to_rgb(&yuyv);
+        assert_eq!(back.len(), 12);
+        for (a, b) in rgb.iter().zip(&back) {
+            assert!(a.abs_diff(*b) <= 3, "{:?} -> {:?}", rgb, back);
+        }
+    }
+}
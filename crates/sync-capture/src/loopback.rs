@@ -0,0 +1,343 @@
+// Output of synchronized frames to a v4l2loopback device.
+//
+// Any V4L2 application (browsers, OpenCV, GStreamer's v4l2src) can then read
+// the matched frames as if they came from a camera, with the buffer timestamp
+// set to the exposure time of the matched trigger instead of the time the
+// frame reached the capture app. v4l2loopback passes the timestamp of an
+// output buffer through to its readers unchanged
+// (`V4L2_BUF_FLAG_TIMESTAMP_COPY`); it is given in CLOCK_MONOTONIC like the
+// timestamps of a real camera.
+//
+// Frames are written as YUYV through memory-mapped output buffers. The device
+// is opened non-blocking: when every buffer is still queued (a reader is not
+// keeping up), the frame is dropped and counted instead of stalling the
+// capture thread. Create the device first, e.g.
+// `modprobe v4l2loopback video_nr=10 card_label=synced exclusive_caps=1`.
+
+use std::fs::File;
+use std::io;
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::OpenOptionsExt;
+
+use super::rgb_to_yuyv;
+use uapi::*;
+
+/// Output buffers queued to the device.
+const BUFFER_COUNT: u32 = 4;
+
+/// Layout of `<linux/videodev2.h>` on 64-bit targets; most fields are only read by the kernel.
+#[allow(dead_code)]
+mod uapi {
+    pub const V4L2_BUF_TYPE_VIDEO_OUTPUT: u32 = 2;
+    pub const V4L2_MEMORY_MMAP: u32 = 1;
+    pub const V4L2_FIELD_NONE: u32 = 1;
+    pub const V4L2_COLORSPACE_SMPTE170M: u32 = 1;
+    pub const V4L2_BUF_FLAG_TIMESTAMP_COPY: u32 = 0x4000;
+    /// `v4l2_fourcc('Y', 'U', 'Y', 'V')`
+    pub const V4L2_PIX_FMT_YUYV: u32 = 0x5659_5559;
+
+    /// `_IOWR('V', 5, struct v4l2_format)`
+    pub const VIDIOC_S_FMT: u64 = 0xC0D0_5605;
+    /// `_IOWR('V', 8, struct v4l2_requestbuffers)`
+    pub const VIDIOC_REQBUFS: u64 = 0xC014_5608;
+    /// `_IOWR('V', 9, struct v4l2_buffer)`
+    pub const VIDIOC_QUERYBUF: u64 = 0xC058_5609;
+    /// `_IOWR('V', 15, struct v4l2_buffer)`
+    pub const VIDIOC_QBUF: u64 = 0xC058_560F;
+    /// `_IOWR('V', 17, struct v4l2_buffer)`
+    pub const VIDIOC_DQBUF: u64 = 0xC058_5611;
+    /// `_IOW('V', 18, int)`
+    pub const VIDIOC_STREAMON: u64 = 0x4004_5612;
+    /// `_IOW('V', 19, int)`
+    pub const VIDIOC_STREAMOFF: u64 = 0x4004_5613;
+
+    #[repr(C)]
+    #[derive(Default)]
+    pub struct PixFormat {
+        pub width: u32,
+        pub height: u32,
+        pub pixelformat: u32,
+        pub field: u32,
+        pub bytesperline: u32,
+        pub sizeimage: u32,
+        pub colorspace: u32,
+        pub private: u32,
+        pub flags: u32,
+        pub ycbcr_enc: u32,
+        pub quantization: u32,
+        pub xfer_func: u32,
+    }
+
+    /// `struct v4l2_format` with the `pix` member of its 200 byte union.
+    #[repr(C)]
+    pub struct Format {
+        pub kind: u32,
+        pub padding: u32,
+        pub pix: PixFormat,
+        pub rest: [u8; 152],
+    }
+
+    #[repr(C)]
+    #[derive(Default)]
+    pub struct RequestBuffers {
+        pub count: u32,
+        pub kind: u32,
+        pub memory: u32,
+        pub capabilities: u32,
+        pub flags: u8,
+        pub reserved: [u8; 3],
+    }
+
+    #[repr(C)]
+    #[derive(Default)]
+    pub struct Timeval {
+        pub sec: i64,
+        pub usec: i64,
+    }
+
+    /// `struct v4l2_buffer`; `m` holds the mmap offset in its low 32 bits.
+    #[repr(C)]
+    #[derive(Default)]
+    pub struct Buffer {
+        pub index: u32,
+        pub kind: u32,
+        pub bytesused: u32,
+        pub flags: u32,
+        pub field: u32,
+        pub timestamp: Timeval,
+        pub timecode: [u32; 4],
+        pub sequence: u32,
+        pub memory: u32,
+        pub m: u64,
+        pub length: u32,
+        pub reserved2: u32,
+        pub request_fd: u32,
+    }
+}
+
+fn ioctl<T>(device: &File, request: u64, argument: &mut T) -> io::Result<()> {
+    // SAFETY: every request is paired with the struct its number encodes
+    if unsafe { libc::ioctl(device.as_raw_fd(), request as _, argument as *mut T) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// A memory-mapped output buffer
+struct Mapping {
+    ptr: *mut u8,
+    len: usize,
+}
+
+/// Writes frames to a v4l2loopback device, each stamped with its corrected timestamp.
+pub struct LoopbackOutput {
+    device: File,
+    path: String,
+    width: u32,
+    height: u32,
+    buffers: Vec<Mapping>,
+    // Buffers not queued to the device, ready to be filled
+    free: Vec<u32>,
+    streaming: bool,
+    written: u64,
+    dropped: u64,
+}
+
+impl LoopbackOutput {
+    /// Opens the v4l2loopback device at `path` for YUYV frames of `width`x`height` (`width` even).
+    pub fn open(path: &str, width: u32, height: u32) -> io::Result<Self> {
+        if !width.is_multiple_of(2) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("YUYV needs an even width, got {}", width)));
+        }
+        let device = File::options().read(true).write(true).custom_flags(libc::O_NONBLOCK).open(path)?;
+        let mut format = Format {
+            kind: V4L2_BUF_TYPE_VIDEO_OUTPUT,
+            padding: 0,
+            pix: PixFormat {
+                width,
+                height,
+                pixelformat: V4L2_PIX_FMT_YUYV,
+                field: V4L2_FIELD_NONE,
+                bytesperline: width * 2,
+                sizeimage: width * height * 2,
+                colorspace: V4L2_COLORSPACE_SMPTE170M,
+                ..Default::default()
+            },
+            rest: [0; 152],
+        };
+        ioctl(&device, VIDIOC_S_FMT, &mut format)?;
+        if (format.pix.width, format.pix.height, format.pix.pixelformat) != (width, height, V4L2_PIX_FMT_YUYV) {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("{} does not accept YUYV {}x{} (got {}x{})", path, width, height, format.pix.width, format.pix.height),
+            ));
+        }
+
+        let mut request = RequestBuffers {
+            count: BUFFER_COUNT,
+            kind: V4L2_BUF_TYPE_VIDEO_OUTPUT,
+            memory: V4L2_MEMORY_MMAP,
+            ..Default::default()
+        };
+        ioctl(&device, VIDIOC_REQBUFS, &mut request)?;
+        let mut output = Self {
+            device,
+            path: path.to_string(),
+            width,
+            height,
+            buffers: Vec::new(),
+            free: Vec::new(),
+            streaming: false,
+            written: 0,
+            dropped: 0,
+        };
+        for index in 0..request.count {
+            let mut buffer = Buffer {
+                index,
+                kind: V4L2_BUF_TYPE_VIDEO_OUTPUT,
+                memory: V4L2_MEMORY_MMAP,
+                ..Default::default()
+            };
+            ioctl(&output.device, VIDIOC_QUERYBUF, &mut buffer)?;
+            let len = buffer.length as usize;
+            // SAFETY: maps the buffer the driver just described; unmapped on drop
+            let ptr = unsafe {
+                libc::mmap(
+                    std::ptr::null_mut(),
+                    len,
+                    libc::PROT_READ | libc::PROT_WRITE,
+                    libc::MAP_SHARED,
+                    output.device.as_raw_fd(),
+                    buffer.m as u32 as libc::off_t,
+                )
+            };
+            if ptr == libc::MAP_FAILED {
+                return Err(io::Error::last_os_error());
+            }
+            output.buffers.push(Mapping { ptr: ptr as *mut u8, len });
+            output.free.push(index);
+        }
+        Ok(output)
+    }
+
+    // Takes back the buffers the device is done with
+    fn reclaim(&mut self) -> io::Result<()> {
+        while self.streaming {
+            let mut buffer = Buffer {
+                kind: V4L2_BUF_TYPE_VIDEO_OUTPUT,
+                memory: V4L2_MEMORY_MMAP,
+                ..Default::default()
+            };
+            match ioctl(&self.device, VIDIOC_DQBUF, &mut buffer) {
+                Ok(()) => self.free.push(buffer.index),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Queues a packed RGB frame with the CLOCK_MONOTONIC `timestamp_ns`. Returns `false`
+    /// if the frame was dropped because every buffer is still queued.
+    pub fn write(&mut self, rgb: &[u8], timestamp_ns: u64) -> io::Result<bool> {
+        if rgb.len() != self.width as usize * self.height as usize * 3 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("frame of {} bytes does not fit {}x{} RGB", rgb.len(), self.width, self.height),
+            ));
+        }
+        self.reclaim()?;
+        let Some(index) = self.free.pop() else {
+            self.dropped += 1;
+            return Ok(false);
+        };
+        let yuyv = rgb_to_yuyv(rgb);
+        let mapping = &self.buffers[index as usize];
+        let len = yuyv.len().min(mapping.len);
+        // SAFETY: the buffer is mapped for `mapping.len` bytes and not queued, so the driver does not touch it
+        unsafe { std::ptr::copy_nonoverlapping(yuyv.as_ptr(), mapping.ptr, len) };
+        let mut buffer = Buffer {
+            index,
+            kind: V4L2_BUF_TYPE_VIDEO_OUTPUT,
+            bytesused: len as u32,
+            flags: V4L2_BUF_FLAG_TIMESTAMP_COPY,
+            field: V4L2_FIELD_NONE,
+            timestamp: Timeval {
+                sec: (timestamp_ns / 1_000_000_000) as i64,
+                usec: (timestamp_ns % 1_000_000_000 / 1_000) as i64,
+            },
+            memory: V4L2_MEMORY_MMAP,
+            ..Default::default()
+        };
+        if let Err(e) = ioctl(&self.device, VIDIOC_QBUF, &mut buffer) {
+            self.free.push(index);
+            return Err(e);
+        }
+        if !self.streaming {
+            let mut kind = V4L2_BUF_TYPE_VIDEO_OUTPUT as libc::c_int;
+            ioctl(&self.device, VIDIOC_STREAMON, &mut kind)?;
+            self.streaming = true;
+        }
+        self.written += 1;
+        Ok(true)
+    }
+
+    /// Frames queued to the device so far.
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+
+    /// Frames dropped because no buffer was free.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    pub fn describe(&self) -> String {
+        format!("{}, YUYV {}x{}, {} buffers", self.path, self.width, self.height, self.buffers.len())
+    }
+}
+
+impl Drop for LoopbackOutput {
+    fn drop(&mut self) {
+        if self.streaming {
+            let mut kind = V4L2_BUF_TYPE_VIDEO_OUTPUT as libc::c_int;
+            let _ = ioctl(&self.device, VIDIOC_STREAMOFF, &mut kind);
+        }
+        for mapping in &self.buffers {
+            // SAFETY: each mapping was created in `open` with this length and is not used after
+            unsafe { libc::munmap(mapping.ptr as *mut libc::c_void, mapping.len) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Argument size encoded in an ioctl request number
+    fn size_of_request(request: u64) -> usize {
+        ((request >> 16) & 0x3fff) as usize
+    }
+
+    #[test]
+    fn uapi_structs_match_the_ioctl_sizes() {
+        assert_eq!(size_of_request(VIDIOC_S_FMT), std::mem::size_of::<Format>());
+        assert_eq!(size_of_request(VIDIOC_REQBUFS), std::mem::size_of::<RequestBuffers>());
+        for request in [VIDIOC_QUERYBUF, VIDIOC_QBUF, VIDIOC_DQBUF] {
+            assert_eq!(size_of_request(request), std::mem::size_of::<Buffer>());
+        }
+        assert_eq!(size_of_request(VIDIOC_STREAMON), std::mem::size_of::<libc::c_int>());
+    }
+
+    #[test]
+    fn open_rejects_odd_widths_and_non_v4l2_files() {
+        let odd = LoopbackOutput::open("/dev/null", 641, 480).err().unwrap();
+        assert_eq!(odd.kind(), io::ErrorKind::InvalidInput);
+
+        let path = std::env::temp_dir().join(format!("camera_sync_loopback_{}", std::process::id()));
+        std::fs::write(&path, b"").unwrap();
+        let not_a_device = LoopbackOutput::open(path.to_str().unwrap(), 640, 480).err().unwrap();
+        assert_eq!(not_a_device.raw_os_error(), Some(libc::ENOTTY));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,64 @@
+use nokhwa::pixel_format::RgbFormat;
+use nokhwa::utils::{CameraIndex, RequestedFormat, RequestedFormatType, Resolution};
+use nokhwa::Camera;
+
+use super::{CaptureBackend, CapturedFrame, FramePool, PoolStats};
+
+pub struct NokhwaBackend {
+    camera: Camera,
+    pool: FramePool,
+}
+
+impl NokhwaBackend {
+    pub fn open(index: u32, width: u32, height: u32) -> Result<Self, Box<dyn std::error::Error>> {
+        let requested_format = RequestedFormat::new::<RgbFormat>(RequestedFormatType::AbsoluteHighestFrameRate);
+        let mut camera = Camera::new(CameraIndex::Index(index), requested_format)?;
+
+        // Try to set resolution, but don't fail if it's not supported
+        if let Err(e) = camera.set_resolution(Resolution::new(width, height)) {
+            println!("Warning: Could not set resolution {}x{}: {}. Using camera default.", width, height, e);
+        }
+
+        camera.open_stream()?;
+        Ok(Self {
+            camera,
+            pool: FramePool::default(),
+        })
+    }
+}
+
+impl CaptureBackend for NokhwaBackend {
+    fn frame(&mut self) -> Result<CapturedFrame, Box<dyn std::error::Error>> {
+        let frame = self.camera.frame()?;
+        let resolution = frame.resolution();
+        let mut rgb = self.pool.take();
+        rgb.resize(resolution.width_x as usize * resolution.height_y as usize * 3, 0);
+        frame.decode_image_to_buffer::<RgbFormat>(&mut rgb)?;
+        Ok(CapturedFrame {
+            width: resolution.width_x,
+            height: resolution.height_y,
+            raw_len: frame.buffer().len(),
+            rgb: self.pool.share(rgb),
+            driver_timestamp_ns: None,
+            sequence: None,
+            driver_error: false,
+        })
+    }
+
+    fn device_name(&self) -> String {
+        self.camera.info().human_name()
+    }
+
+    fn resolution(&self) -> (u32, u32) {
+        let resolution = self.camera.resolution();
+        (resolution.width_x, resolution.height_y)
+    }
+
+    fn describe(&self) -> String {
+        format!("nokhwa, {}", self.camera.camera_format())
+    }
+
+    fn pool_stats(&mut self) -> Option<PoolStats> {
+        Some(self.pool.stats())
+    }
+}
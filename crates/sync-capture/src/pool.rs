@@ -0,0 +1,222 @@
+// Pooled, reference-counted frame buffers.
+//
+// A captured frame goes to several consumers at once: matching on the capture
+// thread, the preview (held by the UI thread until a newer frame replaces it),
+// the session recorder, the iceoryx2 frame publisher and the RTSP pipeline.
+// `FrameBuffer` shares one buffer between all of them; cloning it only counts
+// a reference. When the last clone is dropped, on whichever thread, the buffer
+// goes back to the `FramePool` it came from, so a backend converts into a few
+// recycled buffers instead of allocating one per frame. Returning never waits:
+// buffers travel back over a channel the pool drains when it hands out the
+// next one.
+//
+// The pool keeps up to `capacity` free buffers. A consumer holding frames for
+// longer (a recorder behind on a slow disk) makes the pool allocate more;
+// these are freed again when they come back to a full pool. `PoolStats` shows
+// how many buffers exist and how many consumers still hold.
+
+use std::fmt;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+
+/// Free buffers a pool keeps by default: the V4L2 queue is not part of it, so
+/// this covers the frame being matched, the preview and a few queued consumers.
+pub const DEFAULT_CAPACITY: usize = 8;
+
+/// Buffer counts of a pool.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    pub capacity: usize,
+    /// Buffers currently held by frames.
+    pub in_use: usize,
+    /// Free buffers waiting for the next frame.
+    pub free: usize,
+    /// Buffers allocated so far; beyond `capacity` consumers hold frames too long.
+    pub allocated: u64,
+    /// Frames that got a recycled buffer.
+    pub reused: u64,
+}
+
+impl fmt::Display for PoolStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} buffers in use, {} free (capacity {}), {} allocated, {} reused",
+            self.in_use, self.free, self.capacity, self.allocated, self.reused
+        )
+    }
+}
+
+/// Recycles the buffers of the frames one backend delivers.
+#[derive(Debug)]
+pub struct FramePool {
+    free: Vec<Vec<u8>>,
+    returned: Receiver<Vec<u8>>,
+    recycle: Sender<Vec<u8>>,
+    in_use: Arc<AtomicUsize>,
+    capacity: usize,
+    allocated: u64,
+    reused: u64,
+}
+
+impl Default for FramePool {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl FramePool {
+    pub fn new(capacity: usize) -> Self {
+        let (recycle, returned) = mpsc::channel();
+        Self {
+            free: Vec::new(),
+            returned,
+            recycle,
+            in_use: Arc::new(AtomicUsize::new(0)),
+            capacity,
+            allocated: 0,
+            reused: 0,
+        }
+    }
+
+    /// An empty buffer to fill with the next frame, recycled if one came back.
+    pub fn take(&mut self) -> Vec<u8> {
+        self.collect();
+        match self.free.pop() {
+            Some(mut buffer) => {
+                buffer.clear();
+                self.reused += 1;
+                buffer
+            }
+            None => {
+                self.allocated += 1;
+                Vec::new()
+            }
+        }
+    }
+
+    /// Shares a filled buffer (from `take`); it returns to this pool when the last clone is dropped.
+    pub fn share(&self, data: Vec<u8>) -> FrameBuffer {
+        self.in_use.fetch_add(1, Ordering::Relaxed);
+        FrameBuffer(Arc::new(Slot {
+            data,
+            home: Some((self.recycle.clone(), self.in_use.clone())),
+        }))
+    }
+
+    pub fn stats(&mut self) -> PoolStats {
+        self.collect();
+        PoolStats {
+            capacity: self.capacity,
+            in_use: self.in_use.load(Ordering::Relaxed),
+            free: self.free.len(),
+            allocated: self.allocated,
+            reused: self.reused,
+        }
+    }
+
+    // Takes back the buffers dropped since, freeing the ones beyond the capacity
+    fn collect(&mut self) {
+        for buffer in self.returned.try_iter() {
+            if self.free.len() < self.capacity {
+                self.free.push(buffer);
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Slot {
+    data: Vec<u8>,
+    // Pool channel and its in-use count; `None` for buffers outside a pool
+    home: Option<(Sender<Vec<u8>>, Arc<AtomicUsize>)>,
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        if let Some((recycle, in_use)) = self.home.take() {
+            in_use.fetch_sub(1, Ordering::Relaxed);
+            // A dropped pool (camera closed) frees the buffer instead
+            let _ = recycle.send(std::mem::take(&mut self.data));
+        }
+    }
+}
+
+/// Shared, immutable frame bytes; clones refer to the same buffer.
+#[derive(Debug, Clone)]
+pub struct FrameBuffer(Arc<Slot>);
+
+impl FrameBuffer {
+    /// Consumers holding this buffer, including this one.
+    pub fn holders(&self) -> usize {
+        Arc::strong_count(&self.0)
+    }
+}
+
+/// A buffer outside any pool, freed when the last clone is dropped.
+impl From<Vec<u8>> for FrameBuffer {
+    fn from(data: Vec<u8>) -> Self {
+        Self(Arc::new(Slot { data, home: None }))
+    }
+}
+
+impl Deref for FrameBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0.data
+    }
+}
+
+impl AsRef<[u8]> for FrameBuffer {
+    fn as_ref(&self) -> &[u8] {
+        &self.0.data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shared_buffers_return_to_the_pool() {
+        let mut pool = FramePool::new(2);
+        let mut data = pool.take();
+        data.extend_from_slice(&[1, 2, 3]);
+        let address = data.as_ptr();
+        let frame = pool.share(data);
+        let preview = frame.clone();
+        assert_eq!(frame.holders(), 2);
+
+        // Still held by the preview after the recorder thread is done
+        std::thread::spawn(move || assert_eq!(&frame[..], &[1, 2, 3])).join().unwrap();
+        assert_eq!((pool.stats().in_use, pool.stats().free), (1, 0));
+        drop(preview);
+        let stats = pool.stats();
+        assert_eq!((stats.in_use, stats.free, stats.allocated), (0, 1, 1));
+
+        // The next frame reuses the buffer, emptied
+        let data = pool.take();
+        assert!(data.is_empty() && data.as_ptr() == address);
+        assert_eq!(pool.stats().reused, 1);
+
+        // Consumers holding three frames make the pool allocate beyond its capacity of 2
+        let mut frames = vec![pool.share(data)];
+        for _ in 0..2 {
+            let data = pool.take();
+            frames.push(pool.share(data));
+        }
+        assert_eq!(pool.stats().in_use, 3);
+        drop(frames);
+        let stats = pool.stats();
+        assert_eq!((stats.in_use, stats.free, stats.allocated), (0, 2, 3));
+
+        // Buffers outside a pool are freed with their last clone
+        let unpooled = FrameBuffer::from(vec![7; 4]);
+        assert_eq!(unpooled.len(), 4);
+        drop(unpooled);
+        assert_eq!(pool.stats().free, 2);
+    }
+}
@@ -0,0 +1,159 @@
+// RTSP output of synchronized frames for remote monitoring.
+//
+// A GStreamer RTSP server serves the matched frames as H.264 at
+// `rtsp://<host>:<port><mount>`, so a control room can watch a rig over the
+// network with VLC, ffplay or any NVR. Every frame carries the SMPTE timecode
+// of its trigger as `GstVideoTimeCodeMeta`, which `h264parse` writes into the
+// pic timing SEI of the stream; players that show timecode (and tools that
+// align streams by it) see the exposure time, not the time the frame was
+// served. All clients share one encoder. Without clients, frames are discarded
+// before encoding, so an idle server costs nothing but the listening socket.
+// The GStreamer buffers wrap the shared frame buffers (see `pool`), no frame
+// is copied for the encoder.
+//
+// Needs the GStreamer base, good and ugly (x264enc) plugins and the RTSP
+// server library at runtime.
+
+use std::sync::mpsc::{self, Receiver};
+
+use gstreamer as gst;
+use gstreamer::glib;
+use gstreamer::prelude::*;
+use gstreamer_app::AppSrc;
+use gstreamer_rtsp_server::prelude::*;
+use gstreamer_rtsp_server::{RTSPMediaFactory, RTSPServer};
+use gstreamer_video::{ValidVideoTimeCode, VideoFormat, VideoInfo, VideoTimeCode, VideoTimeCodeFlags, VideoTimeCodeMeta};
+
+use super::FrameBuffer;
+
+// RGB in, low-latency H.264 out; the SEI timecode is updated from the buffer metadata
+const PIPELINE: &str = "( appsrc name=src is-live=true format=time do-timestamp=true \
+    ! videoconvert ! x264enc tune=zerolatency speed-preset=ultrafast \
+    ! h264parse config-interval=-1 update-timecode=true ! rtph264pay name=pay0 pt=96 )";
+
+/// Serves the synchronized stream over RTSP, stamped with trigger timecodes.
+pub struct RtspOutput {
+    // Source of the shared media, while a client is connected; the server's callbacks send its changes
+    source: Option<AppSrc>,
+    source_changes: Receiver<Option<AppSrc>>,
+    main_loop: glib::MainLoop,
+    url: String,
+    width: u32,
+    height: u32,
+    fps: u32,
+    // Rate the timecodes count in: (numerator, denominator, drop-frame)
+    timecode_rate: (u32, u32, bool),
+    served: u64,
+}
+
+impl RtspOutput {
+    /// Listens on `port` and serves RGB frames of `width`x`height` at `fps` under `mount` (e.g. `/sync`).
+    pub fn start(port: u16, mount: &str, width: u32, height: u32, fps: u32) -> Result<Self, Box<dyn std::error::Error>> {
+        gst::init()?;
+        let fps = fps.max(1);
+        let caps = VideoInfo::builder(VideoFormat::Rgb, width, height).fps(gst::Fraction::new(fps as i32, 1)).build()?.to_caps()?;
+
+        let server = RTSPServer::new();
+        server.set_service(&port.to_string());
+        let factory = RTSPMediaFactory::new();
+        factory.set_launch(PIPELINE);
+        factory.set_shared(true);
+        let (configured, source_changes) = mpsc::channel();
+        let unprepared = configured.clone();
+        factory.connect_media_configure(move |_, media| {
+            let Some(src) = media
+                .element()
+                .downcast::<gst::Bin>()
+                .ok()
+                .and_then(|bin| bin.by_name_recurse_up("src"))
+                .and_then(|src| src.downcast::<AppSrc>().ok())
+            else {
+                return;
+            };
+            src.set_caps(Some(&caps));
+            let _ = configured.send(Some(src));
+        });
+        // The last client leaving tears the media down; frames are discarded until the next one connects
+        factory.connect_media_constructed(move |_, media| {
+            let unprepared = unprepared.clone();
+            media.connect_unprepared(move |_| {
+                let _ = unprepared.send(None);
+            });
+        });
+        server.mount_points().ok_or("RTSP server has no mount points")?.add_factory(mount, factory);
+        server.attach(None)?;
+
+        let main_loop = glib::MainLoop::new(None, false);
+        let running = main_loop.clone();
+        std::thread::Builder::new().name("rtsp".to_string()).spawn(move || running.run())?;
+        Ok(Self {
+            source: None,
+            source_changes,
+            main_loop,
+            url: format!("rtsp://0.0.0.0:{}{}", port, mount),
+            width,
+            height,
+            fps,
+            timecode_rate: (fps, 1, false),
+            served: 0,
+        })
+    }
+
+    /// Rate the pushed timecodes count in, e.g. `(30000, 1001, true)` for 29.97 drop-frame;
+    /// whole frames of the output fps by default.
+    pub fn set_timecode_rate(&mut self, numerator: u32, denominator: u32, drop_frame: bool) {
+        self.timecode_rate = (numerator.max(1), denominator.max(1), drop_frame);
+    }
+
+    /// Serves a packed RGB frame with the timecode `(hours, minutes, seconds, frames)` of its
+    /// trigger, without copying it. Returns `false` if no client is connected.
+    pub fn push(&mut self, rgb: &FrameBuffer, timecode: (u32, u32, u32, u32)) -> Result<bool, Box<dyn std::error::Error>> {
+        while let Ok(change) = self.source_changes.try_recv() {
+            self.source = change;
+        }
+        let Some(source) = &self.source else {
+            return Ok(false);
+        };
+        if rgb.len() != self.width as usize * self.height as usize * 3 {
+            return Err(format!("frame of {} bytes does not fit {}x{} RGB", rgb.len(), self.width, self.height).into());
+        }
+        let (hours, minutes, seconds, frames) = timecode;
+        let (numerator, denominator, drop_frame) = self.timecode_rate;
+        let flags = if drop_frame { VideoTimeCodeFlags::DROP_FRAME } else { VideoTimeCodeFlags::empty() };
+        let timecode = VideoTimeCode::new(
+            gst::Fraction::new(numerator as i32, denominator as i32),
+            None,
+            flags,
+            hours,
+            minutes,
+            seconds,
+            frames,
+            0,
+        );
+        let timecode = ValidVideoTimeCode::try_from(timecode).map_err(|_| "invalid timecode")?;
+        let mut buffer = gst::Buffer::from_slice(rgb.clone());
+        {
+            let buffer = buffer.get_mut().ok_or("buffer not writable")?;
+            buffer.set_duration(gst::ClockTime::from_nseconds(1_000_000_000 / self.fps as u64));
+            VideoTimeCodeMeta::add(buffer, &timecode);
+        }
+        source.push_buffer(buffer)?;
+        self.served += 1;
+        Ok(true)
+    }
+
+    /// Frames served to clients so far.
+    pub fn served(&self) -> u64 {
+        self.served
+    }
+
+    pub fn describe(&self) -> String {
+        format!("{}, H.264 {}x{}@{}fps with SMPTE timecode", self.url, self.width, self.height, self.fps)
+    }
+}
+
+impl Drop for RtspOutput {
+    fn drop(&mut self) {
+        self.main_loop.quit();
+    }
+}
@@ -0,0 +1,263 @@
+// Synthetic camera rendering the trigger it was exposed for into its pixels.
+//
+// For end-to-end tests without a camera: the backend takes the triggers the
+// publisher sends, waits a simulated pipeline delay (plus optional jitter)
+// after each trigger's hardware timestamp and then delivers a frame that
+// encodes that trigger as black and white bars across the image:
+//
+//   16 bits  marker 0xA5C3
+//   64 bits  trigger id
+//   64 bits  hardware timestamp (ns)
+//   16 bits  checksum over id and timestamp
+//
+// Bits are most significant first, one full-height bar each, so the image
+// survives scaling and mild compression. `decode` reads the bars back; the
+// capture app compares the decoded trigger with the one the matcher chose for
+// the frame, which makes every association checkable. Frames are delivered in
+// trigger order, like a camera in external trigger mode: `frame` blocks until a
+// trigger arrives, or until the frame timeout if one is set.
+
+use std::collections::VecDeque;
+use std::io;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use super::{CaptureBackend, CapturedFrame, FramePool, PoolStats};
+
+const MARKER: u16 = 0xA5C3;
+
+/// Bars across the image, one per bit.
+pub const BITS: u32 = 16 + 64 + 64 + 16;
+
+/// How often `frame` polls the trigger source while waiting.
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Polled for the next `(trigger_id, hw_ts)`, `None` if nothing arrived yet.
+pub type TriggerSource = Box<dyn FnMut() -> Result<Option<(u64, u64)>, Box<dyn std::error::Error>>>;
+
+fn checksum(trigger_id: u64, hw_ts: u64) -> u16 {
+    let folded = trigger_id ^ hw_ts.rotate_left(32);
+    (folded ^ (folded >> 16) ^ (folded >> 32) ^ (folded >> 48)) as u16
+}
+
+fn bits(trigger_id: u64, hw_ts: u64) -> impl Iterator<Item = bool> {
+    let field = |value: u64, width: u32| (0..width).rev().map(move |bit| (value >> bit) & 1 == 1);
+    field(MARKER as u64, 16)
+        .chain(field(trigger_id, 64))
+        .chain(field(hw_ts, 64))
+        .chain(field(checksum(trigger_id, hw_ts) as u64, 16))
+}
+
+/// Packed RGB image of `width`x`height` encoding the trigger; `width` must be at least `BITS`.
+/// Pixels right of the last bar are gray.
+pub fn encode(trigger_id: u64, hw_ts: u64, width: u32, height: u32) -> Vec<u8> {
+    let mut rgb = Vec::new();
+    encode_into(trigger_id, hw_ts, width, height, &mut rgb);
+    rgb
+}
+
+/// `encode` appending to `rgb`, e.g. a recycled buffer of a `FramePool`.
+pub fn encode_into(trigger_id: u64, hw_ts: u64, width: u32, height: u32, rgb: &mut Vec<u8>) {
+    let bar_width = (width / BITS).max(1) as usize;
+    let mut row = vec![128u8; width as usize * 3];
+    for (index, bit) in bits(trigger_id, hw_ts).enumerate() {
+        let start = index * bar_width * 3;
+        let end = (start + bar_width * 3).min(row.len());
+        if start < end {
+            row[start..end].fill(if bit { 255 } else { 0 });
+        }
+    }
+    rgb.reserve(row.len() * height as usize);
+    for _ in 0..height {
+        rgb.extend_from_slice(&row);
+    }
+}
+
+/// The `(trigger_id, hw_ts)` encoded in the image, `None` if the marker or checksum do not match.
+pub fn decode(rgb: &[u8], width: u32, height: u32) -> Option<(u64, u64)> {
+    let bar_width = width / BITS;
+    if bar_width == 0 || rgb.len() < width as usize * height as usize * 3 {
+        return None;
+    }
+    // Center of every bar on the middle row
+    let row = (height / 2) as usize * width as usize * 3;
+    let mut bars = (0..BITS).map(|bar| {
+        let pixel = row + (bar * bar_width + bar_width / 2) as usize * 3;
+        let luma = (rgb[pixel] as u32 * 77 + rgb[pixel + 1] as u32 * 150 + rgb[pixel + 2] as u32 * 29) >> 8;
+        luma >= 128
+    });
+    let mut field = |width: u32| bars.by_ref().take(width as usize).fold(0u64, |value, bit| (value << 1) | bit as u64);
+    let (marker, trigger_id, hw_ts, sum) = (field(16), field(64), field(64), field(16));
+    (marker == MARKER as u64 && sum == checksum(trigger_id, hw_ts) as u64).then_some((trigger_id, hw_ts))
+}
+
+fn realtime_now_ns() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_nanos() as u64)
+}
+
+/// Virtual camera delivering one test-pattern frame per trigger after a simulated pipeline delay.
+pub struct TestPatternBackend {
+    source: TriggerSource,
+    width: u32,
+    height: u32,
+    delay: Duration,
+    jitter: Duration,
+    // Triggers waiting for their frame: (trigger_id, hw_ts, delivery time ns)
+    pending: VecDeque<(u64, u64, u64)>,
+    sequence: u32,
+    // xorshift state for the jitter
+    random: u64,
+    timeout: Option<Duration>,
+    pool: FramePool,
+}
+
+impl TestPatternBackend {
+    /// Frames arrive `delay` after each trigger's hardware timestamp, uniformly
+    /// varied by up to `jitter` either way. `width` is raised to `BITS` if smaller.
+    pub fn new(source: TriggerSource, width: u32, height: u32, delay: Duration, jitter: Duration) -> Self {
+        Self {
+            source,
+            width: width.max(BITS),
+            height: height.max(1),
+            delay,
+            jitter,
+            pending: VecDeque::new(),
+            sequence: 0,
+            random: realtime_now_ns() | 1,
+            timeout: None,
+            pool: FramePool::default(),
+        }
+    }
+
+    // Uniform in [-jitter, +jitter]
+    fn jitter_ns(&mut self) -> i64 {
+        let jitter_ns = self.jitter.as_nanos() as u64;
+        if jitter_ns == 0 {
+            return 0;
+        }
+        self.random ^= self.random << 13;
+        self.random ^= self.random >> 7;
+        self.random ^= self.random << 17;
+        (self.random % (2 * jitter_ns + 1)) as i64 - jitter_ns as i64
+    }
+
+    fn receive_triggers(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        while let Some((trigger_id, hw_ts)) = (self.source)()? {
+            let due_ns = (hw_ts + self.delay.as_nanos() as u64).saturating_add_signed(self.jitter_ns());
+            // The pipeline is first in, first out: jitter never reorders frames
+            let due_ns = self.pending.back().map_or(due_ns, |&(_, _, last_ns)| due_ns.max(last_ns));
+            self.pending.push_back((trigger_id, hw_ts, due_ns));
+        }
+        Ok(())
+    }
+}
+
+impl CaptureBackend for TestPatternBackend {
+    fn frame(&mut self) -> Result<CapturedFrame, Box<dyn std::error::Error>> {
+        let started = Instant::now();
+        loop {
+            if self.timeout.is_some_and(|timeout| started.elapsed() >= timeout) {
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "no test-pattern frame within the frame timeout").into());
+            }
+            self.receive_triggers()?;
+            let now_ns = realtime_now_ns();
+            match self.pending.front() {
+                Some(&(trigger_id, hw_ts, due_ns)) if due_ns <= now_ns => {
+                    self.pending.pop_front();
+                    self.sequence = self.sequence.wrapping_add(1);
+                    let mut rgb = self.pool.take();
+                    encode_into(trigger_id, hw_ts, self.width, self.height, &mut rgb);
+                    return Ok(CapturedFrame {
+                        width: self.width,
+                        height: self.height,
+                        raw_len: rgb.len(),
+                        rgb: self.pool.share(rgb),
+                        driver_timestamp_ns: None,
+                        sequence: Some(self.sequence),
+                        driver_error: false,
+                    });
+                }
+                Some(&(_, _, due_ns)) => std::thread::sleep(Duration::from_nanos(due_ns - now_ns).min(POLL_INTERVAL)),
+                None => std::thread::sleep(POLL_INTERVAL),
+            }
+        }
+    }
+
+    fn device_name(&self) -> String {
+        "test pattern".to_string()
+    }
+
+    fn resolution(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn describe(&self) -> String {
+        format!(
+            "test pattern {}x{}, delay {:.1}ms, jitter ±{:.1}ms",
+            self.width,
+            self.height,
+            self.delay.as_secs_f64() * 1000.0,
+            self.jitter.as_secs_f64() * 1000.0
+        )
+    }
+
+    fn set_frame_timeout(&mut self, timeout: Duration) {
+        self.timeout = Some(timeout);
+    }
+
+    fn pool_stats(&mut self) -> Option<PoolStats> {
+        Some(self.pool.stats())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+    use crate::CaptureBackend;
+
+    const HW_TS: u64 = 1_700_000_000_123_456_789;
+
+    #[test]
+    fn round_trip() {
+        for (width, height) in [(640, 480), (160, 1), (333, 7), (1920, 1080)] {
+            let rgb = encode(u64::MAX - 3, HW_TS, width, height);
+            assert_eq!(rgb.len(), (width * height * 3) as usize);
+            assert_eq!(decode(&rgb, width, height), Some((u64::MAX - 3, HW_TS)), "{}x{}", width, height);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_patterns() {
+        let (width, height) = (640, 480);
+        assert_eq!(decode(&vec![128; width * height * 3], width as u32, height as u32), None);
+        // Too narrow for one pixel per bar
+        assert_eq!(decode(&encode(1, 2, 100, 10), 100, 10), None);
+
+        // One flipped bar in the trigger id fails the checksum
+        let mut rgb = encode(42, HW_TS, width as u32, height as u32);
+        let bar_width = width / BITS as usize;
+        let pixel = (height / 2 * width + 40 * bar_width + bar_width / 2) * 3;
+        for byte in &mut rgb[pixel..pixel + 3] {
+            *byte ^= 0xff;
+        }
+        assert_eq!(decode(&rgb, width as u32, height as u32), None);
+    }
+
+    #[test]
+    fn frames_follow_triggers_after_the_delay() {
+        let now_ns = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos() as u64;
+        let mut triggers: Vec<(u64, u64)> = (0..5).map(|id| (id, now_ns + id * 2_000_000)).rev().collect();
+        let source: TriggerSource = Box::new(move || Ok(triggers.pop()));
+        let delay = Duration::from_millis(5);
+        let mut camera = TestPatternBackend::new(source, 320, 240, delay, Duration::from_millis(1));
+        for expected in 0..5 {
+            let frame = camera.frame().unwrap();
+            let delivered_ns = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos() as u64;
+            let (trigger_id, hw_ts) = decode(&frame.rgb, frame.width, frame.height).unwrap();
+            assert_eq!(trigger_id, expected);
+            assert_eq!(frame.sequence, Some(expected as u32 + 1));
+            // Jitter shifts the delivery by at most 1ms either way
+            assert!(delivered_ns >= hw_ts + 4_000_000, "frame {} delivered {}ns after its trigger", expected, delivered_ns - hw_ts);
+        }
+    }
+}
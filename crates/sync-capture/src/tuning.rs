@@ -0,0 +1,128 @@
+// Choice of the V4L2 queued buffer count and memory type.
+//
+// Every buffer queued beyond the one the driver is filling can hold a finished
+// frame waiting to be dequeued, so the worst-case queuing delay between
+// exposure and dequeue grows by one frame interval per extra buffer. The
+// planner picks the largest count that stays within the target latency while
+// keeping at least two buffers (one filling, one ready) for continuous streaming.
+
+use std::fmt;
+use std::str::FromStr;
+
+pub const MIN_BUFFERS: u32 = 2;
+pub const MAX_BUFFERS: u32 = 32;
+/// Default target: at most two frames of queuing delay.
+pub const DEFAULT_TARGET_FRAMES: f64 = 2.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "lowercase"))]
+pub enum MemoryType {
+    Mmap,
+    Userptr,
+}
+
+impl FromStr for MemoryType {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text.to_ascii_lowercase().as_str() {
+            "mmap" => Ok(MemoryType::Mmap),
+            "userptr" => Ok(MemoryType::Userptr),
+            other => Err(format!("unknown V4L2 memory type '{}' (expected mmap or userptr)", other)),
+        }
+    }
+}
+
+impl fmt::Display for MemoryType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MemoryType::Mmap => write!(f, "MMAP"),
+            MemoryType::Userptr => write!(f, "USERPTR"),
+        }
+    }
+}
+
+/// Buffer configuration chosen for a stream, with the reasoning behind it.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BufferTuning {
+    pub buffer_count: u32,
+    pub memory: MemoryType,
+    pub frame_interval_ms: f64,
+    /// Worst-case delay added by frames waiting in the queue.
+    pub max_queue_delay_ms: f64,
+    pub reason: String,
+}
+
+impl BufferTuning {
+    /// `requested_count` / `requested_memory` override the automatic choice; `None` means auto.
+    pub fn plan(
+        camera_fps: u32,
+        target_latency_ms: Option<f64>,
+        requested_count: Option<u32>,
+        requested_memory: Option<MemoryType>,
+    ) -> Self {
+        let frame_interval_ms = 1000.0 / camera_fps.max(1) as f64;
+
+        let (buffer_count, reason) = match requested_count {
+            Some(count) => (count.clamp(MIN_BUFFERS, MAX_BUFFERS), "requested".to_string()),
+            None => {
+                let target_ms = target_latency_ms.unwrap_or(DEFAULT_TARGET_FRAMES * frame_interval_ms);
+                // One buffer is always being filled; the rest may queue up finished frames
+                let queued = (target_ms / frame_interval_ms).floor() as u32;
+                let count = (queued + 1).clamp(MIN_BUFFERS, MAX_BUFFERS);
+                (count, format!("auto for {:.1}ms target at {}fps", target_ms, camera_fps))
+            }
+        };
+
+        // MMAP works with every capture driver and needs no copies; USERPTR only on request
+        let memory = requested_memory.unwrap_or(MemoryType::Mmap);
+
+        Self {
+            buffer_count,
+            memory,
+            frame_interval_ms,
+            max_queue_delay_ms: (buffer_count - 1) as f64 * frame_interval_ms,
+            reason,
+        }
+    }
+}
+
+impl fmt::Display for BufferTuning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} buffers, {} memory, max queue delay {:.1}ms ({})",
+            self.buffer_count, self.memory, self.max_queue_delay_ms, self.reason
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn auto_plan_keeps_the_queue_within_the_target() {
+        // Two frames of delay at 30fps: two queued plus the one being filled
+        let tuning = BufferTuning::plan(30, None, None, None);
+        assert_eq!((tuning.buffer_count, tuning.memory), (3, MemoryType::Mmap));
+        assert!(tuning.max_queue_delay_ms <= DEFAULT_TARGET_FRAMES * tuning.frame_interval_ms + 1e-9);
+
+        assert_eq!(BufferTuning::plan(30, Some(100.0), None, None).buffer_count, 4);
+        // Streaming needs at least two buffers, however tight the target
+        assert_eq!(BufferTuning::plan(30, Some(1.0), None, None).buffer_count, MIN_BUFFERS);
+        assert_eq!(BufferTuning::plan(1000, Some(1000.0), None, None).buffer_count, MAX_BUFFERS);
+    }
+
+    #[test]
+    fn requested_values_override_the_plan_within_limits() {
+        let tuning = BufferTuning::plan(60, Some(1.0), Some(8), Some(MemoryType::Userptr));
+        assert_eq!((tuning.buffer_count, tuning.memory, tuning.reason.as_str()), (8, MemoryType::Userptr, "requested"));
+        assert_eq!(BufferTuning::plan(60, None, Some(1), None).buffer_count, MIN_BUFFERS);
+        assert_eq!(BufferTuning::plan(60, None, Some(100), None).buffer_count, MAX_BUFFERS);
+
+        assert_eq!("USERPTR".parse::<MemoryType>(), Ok(MemoryType::Userptr));
+        assert!("dmabuf".parse::<MemoryType>().is_err());
+    }
+}
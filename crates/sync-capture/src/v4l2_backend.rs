@@ -0,0 +1,150 @@
+use std::time::Duration;
+
+use v4l::buffer::{Flags, Type};
+use v4l::io::mmap::Stream as MmapStream;
+use v4l::io::traits::CaptureStream;
+use v4l::io::userptr::Stream as UserptrStream;
+use v4l::video::capture::Parameters;
+use v4l::video::Capture;
+use v4l::{Device, FourCC};
+
+use super::tuning::{BufferTuning, MemoryType};
+use super::{yuyv_to_rgb_into, CaptureBackend, CapturedFrame, FramePool, PoolStats};
+
+// Clock of the buffer timestamp (V4L2_BUF_FLAG_TIMESTAMP_*): only CLOCK_MONOTONIC can be
+// converted to the trigger clock, output-to-capture copies and unknown clocks cannot
+const TIMESTAMP_MASK: u32 = 0x0000_e000;
+const TIMESTAMP_MONOTONIC: u32 = 0x0000_2000;
+
+enum Stream {
+    Mmap(MmapStream<'static>),
+    Userptr(UserptrStream),
+}
+
+fn open_stream(device: &Device, memory: MemoryType, buffer_count: u32) -> std::io::Result<Stream> {
+    Ok(match memory {
+        MemoryType::Mmap => Stream::Mmap(MmapStream::with_buffers(device, Type::VideoCapture, buffer_count)?),
+        MemoryType::Userptr => Stream::Userptr(UserptrStream::with_buffers(device, Type::VideoCapture, buffer_count)?),
+    })
+}
+
+/// Direct V4L2 capture (YUYV) with an explicit buffer queue configuration.
+pub struct V4l2Backend {
+    // Keeps the device open for the lifetime of the stream
+    _device: Device,
+    stream: Stream,
+    device_name: String,
+    width: u32,
+    height: u32,
+    fps: u32,
+    tuning: BufferTuning,
+    pool: FramePool,
+}
+
+impl V4l2Backend {
+    pub fn open(index: u32, width: u32, height: u32, fps: u32, mut tuning: BufferTuning) -> Result<Self, Box<dyn std::error::Error>> {
+        let device = Device::new(index as usize)?;
+        let device_name = device.query_caps()?.card;
+
+        let yuyv = FourCC::new(b"YUYV");
+        let mut format = device.format()?;
+        format.width = width;
+        format.height = height;
+        format.fourcc = yuyv;
+        let format = device.set_format(&format)?;
+        if format.fourcc != yuyv {
+            return Err(format!("/dev/video{} does not support YUYV (got {})", index, format.fourcc).into());
+        }
+
+        // Stream parameters: frame interval matching the configured camera rate
+        let params = device.set_params(&Parameters::with_fps(fps))?;
+        let fps = params.interval.denominator / params.interval.numerator.max(1);
+
+        // Fall back to the other memory type if the driver rejects the planned one
+        let stream = match open_stream(&device, tuning.memory, tuning.buffer_count) {
+            Ok(stream) => stream,
+            Err(e) => {
+                let fallback = match tuning.memory {
+                    MemoryType::Mmap => MemoryType::Userptr,
+                    MemoryType::Userptr => MemoryType::Mmap,
+                };
+                let stream = open_stream(&device, fallback, tuning.buffer_count)?;
+                tuning.reason = format!("{}; {} unsupported: {}", tuning.reason, tuning.memory, e);
+                tuning.memory = fallback;
+                stream
+            }
+        };
+
+        Ok(Self {
+            _device: device,
+            stream,
+            device_name,
+            width: format.width,
+            height: format.height,
+            fps,
+            tuning,
+            pool: FramePool::default(),
+        })
+    }
+
+    pub fn tuning(&self) -> &BufferTuning {
+        &self.tuning
+    }
+}
+
+impl CaptureBackend for V4l2Backend {
+    fn frame(&mut self) -> Result<CapturedFrame, Box<dyn std::error::Error>> {
+        let (data, meta) = match &mut self.stream {
+            Stream::Mmap(stream) => stream.next()?,
+            Stream::Userptr(stream) => stream.next()?,
+        };
+        let bytes = &data[..(meta.bytesused as usize).min(data.len())];
+        let monotonic = meta.flags.bits() & TIMESTAMP_MASK == TIMESTAMP_MONOTONIC;
+        let timestamp_ns = monotonic.then(|| meta.timestamp.sec as u64 * 1_000_000_000 + meta.timestamp.usec as u64 * 1_000);
+        let mut rgb = self.pool.take();
+        yuyv_to_rgb_into(bytes, &mut rgb);
+
+        Ok(CapturedFrame {
+            width: self.width,
+            height: self.height,
+            rgb: self.pool.share(rgb),
+            raw_len: bytes.len(),
+            driver_timestamp_ns: timestamp_ns,
+            sequence: Some(meta.sequence),
+            driver_error: meta.flags.contains(Flags::ERROR),
+        })
+    }
+
+    fn device_name(&self) -> String {
+        self.device_name.clone()
+    }
+
+    fn resolution(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn describe(&self) -> String {
+        format!("v4l2, YUYV {}x{}@{}fps, {}", self.width, self.height, self.fps, self.tuning)
+    }
+
+    fn set_frame_timeout(&mut self, timeout: Duration) {
+        match &mut self.stream {
+            Stream::Mmap(stream) => stream.set_timeout(timeout),
+            Stream::Userptr(stream) => stream.set_timeout(timeout),
+        }
+    }
+
+    fn queued_buffers(&self) -> Option<u32> {
+        Some(self.tuning.buffer_count)
+    }
+
+    // The driver stamps the buffer when the frame was captured; the dequeue time adds the
+    // queueing and scheduling delay of the capture loop
+    fn match_on_capture_time(&self) -> bool {
+        true
+    }
+
+    fn pool_stats(&mut self) -> Option<PoolStats> {
+        Some(self.pool.stats())
+    }
+}
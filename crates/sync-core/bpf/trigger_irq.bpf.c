@@ -0,0 +1,72 @@
+// Stamps the trigger interrupt in kernel context for the publisher's `irq`
+// timestamp source (see `sync_core::irq`).
+//
+// Attached to the irq:irq_handler_entry tracepoint, the program submits the
+// CLOCK_MONOTONIC time of every entry of the IRQ armed in `irq_config` to the
+// `irq_events` ring buffer. The publisher writes the config when it starts.
+// Build, then load, attach and pin the maps where the publisher looks:
+//
+//   clang -O2 -g -target bpf -mcpu=v3 -c trigger_irq.bpf.c -o trigger_irq.bpf.o
+//   bpftool prog loadall trigger_irq.bpf.o /sys/fs/bpf/camera_sync/prog \
+//       pinmaps /sys/fs/bpf/camera_sync autoattach
+
+#include <linux/bpf.h>
+#include <linux/types.h>
+#include <bpf/bpf_helpers.h>
+
+// `sync_core::irq::IrqEvent`
+struct trigger_irq_event {
+    __u64 timestamp_ns;
+    __u32 irq;
+    __u32 seqno;
+};
+
+// `sync_core::irq::uapi::IrqConfig`
+struct trigger_irq_config {
+    __u32 irq;
+    __u32 armed;
+    // Counts every entry, also those that found the ring full
+    __u32 seqno;
+};
+
+// Fields of /sys/kernel/tracing/events/irq/irq_handler_entry/format
+struct irq_handler_entry_args {
+    __u64 common;
+    int irq;
+    __u32 name;
+};
+
+struct {
+    __uint(type, BPF_MAP_TYPE_RINGBUF);
+    // Room for a few seconds of a kHz trigger if the publisher stalls
+    __uint(max_entries, 64 * 1024);
+} irq_events SEC(".maps");
+
+struct {
+    __uint(type, BPF_MAP_TYPE_ARRAY);
+    __uint(max_entries, 1);
+    __type(key, __u32);
+    __type(value, struct trigger_irq_config);
+} irq_config SEC(".maps");
+
+SEC("tracepoint/irq/irq_handler_entry")
+int trigger_irq_entry(struct irq_handler_entry_args *args)
+{
+    // Taken first, before anything that could delay it
+    __u64 now = bpf_ktime_get_ns();
+    __u32 key = 0;
+    struct trigger_irq_config *config = bpf_map_lookup_elem(&irq_config, &key);
+    if (!config || !config->armed || args->irq != (int)config->irq)
+        return 0;
+    __u32 seqno = __sync_fetch_and_add(&config->seqno, 1);
+    struct trigger_irq_event *event = bpf_ringbuf_reserve(&irq_events, sizeof(*event), 0);
+    if (!event)
+        return 0;
+    event->timestamp_ns = now;
+    event->irq = args->irq;
+    event->seqno = seqno;
+    bpf_ringbuf_submit(event, 0);
+    return 0;
+}
+
+char LICENSE[] SEC("license") = "GPL";
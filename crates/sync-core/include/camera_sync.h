@@ -0,0 +1,96 @@
+/* Shared-memory payloads of the camera trigger sync services.
+ * Generated by `cargo run --bin c_header` from sync_core::layout, do not edit. */
+
+#ifndef CAMERA_SYNC_H
+#define CAMERA_SYNC_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+/* Trigger ids with this bit set are canaries (see sync_core::canary), never match them. */
+#define CAMERA_SYNC_CANARY_BIT UINT64_C(0x8000000000000000)
+
+/* camera_trigger.sequence_flags of canary triggers; the other bits are reserved and zero. */
+#define CAMERA_SYNC_SEQUENCE_CANARY UINT32_C(0x1)
+
+/* Layout version in camera_trigger.schema_version, and the iceoryx2 type names of the payloads. */
+#define CAMERA_SYNC_SCHEMA_VERSION UINT32_C(5)
+#define CAMERA_SYNC_TRIGGER_TYPE_NAME "camera_trigger_v5"
+#define CAMERA_SYNC_BATCH_TYPE_NAME "camera_trigger_batch_v5"
+
+/* trigger_signature.clock_domain: the clock both timestamps count in (see sync_core::clock). */
+#define CAMERA_SYNC_CLOCK_REALTIME UINT8_C(0)
+#define CAMERA_SYNC_CLOCK_MONOTONIC UINT8_C(1)
+#define CAMERA_SYNC_CLOCK_TAI UINT8_C(2)
+#define CAMERA_SYNC_CLOCK_PTP UINT8_C(3)
+
+/* trigger_signature.clock_policy: what consumers do while the publisher's clock is downgraded (see sync_core::clock_quality). */
+#define CAMERA_SYNC_CLOCK_POLICY_NONE UINT8_C(0)
+#define CAMERA_SYNC_CLOCK_POLICY_FLAG UINT8_C(1)
+#define CAMERA_SYNC_CLOCK_POLICY_WIDEN UINT8_C(2)
+#define CAMERA_SYNC_CLOCK_POLICY_PAUSE UINT8_C(3)
+
+/* Payload of the trigger service (`Camera/Sync`); timestamps in ns of the header's clock_domain, schema_version is CAMERA_SYNC_SCHEMA_VERSION. */
+typedef struct camera_trigger {
+    uint64_t trigger_id;
+    uint64_t hw_timestamp_ns;
+    uint64_t publish_timestamp_ns;
+    uint32_t sequence_flags;
+    uint32_t schema_version;
+} camera_trigger;
+
+_Static_assert(sizeof(camera_trigger) == 32, "camera_trigger size");
+_Static_assert(offsetof(camera_trigger, trigger_id) == 0, "camera_trigger.trigger_id offset");
+_Static_assert(offsetof(camera_trigger, hw_timestamp_ns) == 8, "camera_trigger.hw_timestamp_ns offset");
+_Static_assert(offsetof(camera_trigger, publish_timestamp_ns) == 16, "camera_trigger.publish_timestamp_ns offset");
+_Static_assert(offsetof(camera_trigger, sequence_flags) == 24, "camera_trigger.sequence_flags offset");
+_Static_assert(offsetof(camera_trigger, schema_version) == 28, "camera_trigger.schema_version offset");
+
+/* User header of the trigger service: ed25519 signature, timestamp source, clock domain, clock policy, trace origin and PHC offset. */
+typedef struct trigger_signature {
+    uint64_t key_id;
+    bool is_signed;
+    uint8_t timestamp_source;
+    uint8_t signature[64];
+    uint8_t clock_domain;
+    uint8_t clock_policy;
+    uint32_t trace_origin;
+    int64_t phc_offset_ns;
+} trigger_signature;
+
+_Static_assert(sizeof(trigger_signature) == 88, "trigger_signature size");
+_Static_assert(offsetof(trigger_signature, key_id) == 0, "trigger_signature.key_id offset");
+_Static_assert(offsetof(trigger_signature, is_signed) == 8, "trigger_signature.is_signed offset");
+_Static_assert(offsetof(trigger_signature, timestamp_source) == 9, "trigger_signature.timestamp_source offset");
+_Static_assert(offsetof(trigger_signature, signature) == 10, "trigger_signature.signature offset");
+_Static_assert(offsetof(trigger_signature, clock_domain) == 74, "trigger_signature.clock_domain offset");
+_Static_assert(offsetof(trigger_signature, clock_policy) == 75, "trigger_signature.clock_policy offset");
+_Static_assert(offsetof(trigger_signature, trace_origin) == 76, "trigger_signature.trace_origin offset");
+_Static_assert(offsetof(trigger_signature, phc_offset_ns) == 80, "trigger_signature.phc_offset_ns offset");
+
+/* A batched trigger as the difference to the one before it. */
+typedef struct camera_trigger_delta {
+    uint32_t trigger_id;
+    uint32_t hw_timestamp_ns;
+    uint32_t publish_timestamp_ns;
+} camera_trigger_delta;
+
+_Static_assert(sizeof(camera_trigger_delta) == 12, "camera_trigger_delta size");
+_Static_assert(offsetof(camera_trigger_delta, trigger_id) == 0, "camera_trigger_delta.trigger_id offset");
+_Static_assert(offsetof(camera_trigger_delta, hw_timestamp_ns) == 4, "camera_trigger_delta.hw_timestamp_ns offset");
+_Static_assert(offsetof(camera_trigger_delta, publish_timestamp_ns) == 8, "camera_trigger_delta.publish_timestamp_ns offset");
+
+/* Payload of the batched trigger service: `count` triggers, the first in full; deltas valid up to `count - 1`. */
+typedef struct camera_trigger_batch {
+    camera_trigger first;
+    uint32_t count;
+    camera_trigger_delta deltas[63];
+} camera_trigger_batch;
+
+_Static_assert(sizeof(camera_trigger_batch) == 792, "camera_trigger_batch size");
+_Static_assert(offsetof(camera_trigger_batch, first) == 0, "camera_trigger_batch.first offset");
+_Static_assert(offsetof(camera_trigger_batch, count) == 32, "camera_trigger_batch.count offset");
+_Static_assert(offsetof(camera_trigger_batch, deltas) == 36, "camera_trigger_batch.deltas offset");
+
+#endif /* CAMERA_SYNC_H */
@@ -0,0 +1,556 @@
+// Routine post-processing of recorded sessions (`analyze` binary).
+//
+// Works on the `records.csv` of one or more sessions, one per camera:
+//
+//   latency   histogram of the trigger-to-frame latency of every session
+//   drops     triggers without a recorded frame over time
+//   skew      per-camera latency against the other cameras, per trigger
+//   csv       one row per frame with latency and skew, for spreadsheets
+//
+// A session records matched frames only, so dropped triggers are gaps in the
+// recorded trigger ids. Cameras triggered on every Nth pulse leave regular
+// gaps: the most common step between recorded ids is the session's stride,
+// and only steps beyond it count as drops, spread evenly over the time of the
+// gap. A camera's skew on a trigger is its latency minus the median latency
+// of all sessions that recorded that trigger; with two cameras each shows
+// half of their difference. Recorded latencies are compared as they are, so
+// sessions of several hosts should have been recorded against the same
+// trigger clock domain.
+//
+// Charts render as SVG (title, axes, legend) or, for a `.png` path, as a
+// plain raster of the same plot area without any text.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::merge::HostSession;
+use crate::png;
+use crate::skew;
+use crate::stats::StatsSummary;
+use crate::CameraTrigger;
+
+/// Histogram bins (`--bins`).
+pub const DEFAULT_BINS: usize = 40;
+
+/// Width of the drop timeline buckets (`--bucket-s`).
+pub const DEFAULT_BUCKET_NS: u64 = 1_000_000_000;
+
+const CSV_HEADER: &str = "session,trigger_id,hw_timestamp_ns,v4l2_timestamp_ns,latency_ms,score_ms,skew_ms";
+
+/// Chart size in pixels.
+const WIDTH: u32 = 960;
+const HEIGHT: u32 = 480;
+/// Room for tick labels around the plot area.
+const MARGIN: f64 = 64.0;
+/// Series colors, in series order.
+const PALETTE: [(u8, u8, u8); 6] = [(0x1f, 0x77, 0xb4), (0xd6, 0x27, 0x28), (0x2c, 0xa0, 0x2c), (0xff, 0x7f, 0x0e), (0x94, 0x67, 0xbd), (0x8c, 0x56, 0x4b)];
+
+/// Count, mean, spread and exact percentiles of `values`.
+pub fn summarize(values: &[f64]) -> StatsSummary {
+    if values.is_empty() {
+        return StatsSummary::default();
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let percentile = |p: f64| sorted[((p / 100.0) * (sorted.len() - 1) as f64).round() as usize];
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    let variance = values.iter().map(|value| (value - mean).powi(2)).sum::<f64>() / values.len() as f64;
+    StatsSummary {
+        count: values.len() as u64,
+        mean_ms: mean,
+        std_ms: variance.sqrt(),
+        p50_ms: percentile(50.0),
+        p95_ms: percentile(95.0),
+        min_ms: sorted[0],
+        max_ms: sorted[sorted.len() - 1],
+    }
+}
+
+/// One shape of a chart series, in data coordinates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mark {
+    /// From `y = 0` to `y`, between `x0` and `x1`.
+    Bar { x0: f64, x1: f64, y: f64 },
+    Point { x: f64, y: f64 },
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Series {
+    pub name: String,
+    pub marks: Vec<Mark>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Chart {
+    pub title: String,
+    pub x_label: String,
+    pub y_label: String,
+    pub series: Vec<Series>,
+}
+
+impl Chart {
+    // Data range (x0, x1, y0, y1) of all marks, including y = 0
+    fn range(&self) -> (f64, f64, f64, f64) {
+        let (mut x0, mut x1, mut y0, mut y1) = (f64::INFINITY, f64::NEG_INFINITY, 0.0f64, 0.0f64);
+        for mark in self.series.iter().flat_map(|series| &series.marks) {
+            let (left, right, y) = match *mark {
+                Mark::Bar { x0, x1, y } => (x0, x1, y),
+                Mark::Point { x, y } => (x, x, y),
+            };
+            (x0, x1, y0, y1) = (x0.min(left), x1.max(right), y0.min(y), y1.max(y));
+        }
+        if !x0.is_finite() {
+            return (0.0, 1.0, 0.0, 1.0);
+        }
+        let x1 = if x1 > x0 { x1 } else { x0 + 1.0 };
+        let pad = if y1 > y0 { (y1 - y0) * 0.05 } else { 1.0 };
+        (x0, x1, if y0 < 0.0 { y0 - pad } else { y0 }, y1 + pad)
+    }
+
+    // Pixel position of a data point
+    fn scale(&self) -> impl Fn(f64, f64) -> (f64, f64) {
+        let (x0, x1, y0, y1) = self.range();
+        move |x, y| {
+            (
+                MARGIN + (x - x0) / (x1 - x0) * (WIDTH as f64 - 2.0 * MARGIN),
+                HEIGHT as f64 - MARGIN - (y - y0) / (y1 - y0) * (HEIGHT as f64 - 2.0 * MARGIN),
+            )
+        }
+    }
+
+    pub fn svg(&self) -> String {
+        let (x0, x1, y0, y1) = self.range();
+        let to_px = self.scale();
+        let (left, right, top, bottom) = (MARGIN, WIDTH as f64 - MARGIN, MARGIN, HEIGHT as f64 - MARGIN);
+        let mut svg = String::new();
+        let _ = writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" font-family="monospace" font-size="12">"#,
+            WIDTH, HEIGHT
+        );
+        let _ = writeln!(svg, r#"<rect width="100%" height="100%" fill="white"/>"#);
+        let _ = writeln!(svg, r#"<text x="{}" y="{}" font-size="14">{}</text>"#, left, top - 24.0, escape(&self.title));
+        // Axes with five ticks each
+        let _ = writeln!(svg, r##"<path d="M{0} {1} V{2} H{3}" fill="none" stroke="#424242"/>"##, left, top, bottom, right);
+        for tick in 0..=4 {
+            let fraction = tick as f64 / 4.0;
+            let (x, y) = (x0 + (x1 - x0) * fraction, y0 + (y1 - y0) * fraction);
+            let (px, _) = to_px(x, y0);
+            let (_, py) = to_px(x0, y);
+            let _ = writeln!(svg, r#"<text x="{:.1}" y="{}" text-anchor="middle">{}</text>"#, px, bottom + 16.0, tick_label(x));
+            let _ = writeln!(svg, r#"<text x="{}" y="{:.1}" text-anchor="end">{}</text>"#, left - 6.0, py + 4.0, tick_label(y));
+        }
+        if y0 < 0.0 {
+            let (_, zero) = to_px(x0, 0.0);
+            let _ = writeln!(svg, r##"<line x1="{0}" y1="{1:.1}" x2="{2}" y2="{1:.1}" stroke="#9e9e9e" stroke-dasharray="4 3"/>"##, left, zero, right);
+        }
+        let _ = writeln!(svg, r#"<text x="{}" y="{}" text-anchor="middle">{}</text>"#, (left + right) / 2.0, bottom + 36.0, escape(&self.x_label));
+        let _ = writeln!(
+            svg,
+            r#"<text x="16" y="{0}" text-anchor="middle" transform="rotate(-90 16 {0})">{1}</text>"#,
+            (top + bottom) / 2.0,
+            escape(&self.y_label)
+        );
+
+        for (index, series) in self.series.iter().enumerate() {
+            let (r, g, b) = PALETTE[index % PALETTE.len()];
+            let color = format!("#{:02x}{:02x}{:02x}", r, g, b);
+            let _ = writeln!(svg, r#"<text x="{}" y="{}" fill="{}">{}</text>"#, right - 160.0, top + 16.0 * index as f64, color, escape(&series.name));
+            for mark in &series.marks {
+                match *mark {
+                    Mark::Bar { x0, x1, y } => {
+                        let ((left, base), (right, end)) = (to_px(x0, 0.0), to_px(x1, y));
+                        let _ = writeln!(
+                            svg,
+                            r#"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" fill="{}"/>"#,
+                            left,
+                            base.min(end),
+                            (right - left).max(1.0),
+                            (base - end).abs(),
+                            color
+                        );
+                    }
+                    Mark::Point { x, y } => {
+                        let (x, y) = to_px(x, y);
+                        let _ = writeln!(svg, r#"<circle cx="{:.1}" cy="{:.1}" r="1.5" fill="{}"/>"#, x, y, color);
+                    }
+                }
+            }
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    /// The plot area as packed RGB of `WIDTH` x `HEIGHT` pixels, without text.
+    pub fn rgb(&self) -> Vec<u8> {
+        let (x0, _, y0, _) = self.range();
+        let to_px = self.scale();
+        let mut rgb = vec![255u8; (WIDTH * HEIGHT * 3) as usize];
+        let (left, right, top, bottom) = (MARGIN, WIDTH as f64 - MARGIN, MARGIN, HEIGHT as f64 - MARGIN);
+        fill_rect(&mut rgb, left - 1.0, top, left, bottom, (0x42, 0x42, 0x42));
+        fill_rect(&mut rgb, left, bottom, right, bottom + 1.0, (0x42, 0x42, 0x42));
+        if y0 < 0.0 {
+            let (_, zero) = to_px(x0, 0.0);
+            fill_rect(&mut rgb, left, zero, right, zero + 1.0, (0x9e, 0x9e, 0x9e));
+        }
+        for (index, series) in self.series.iter().enumerate() {
+            let color = PALETTE[index % PALETTE.len()];
+            for mark in &series.marks {
+                match *mark {
+                    Mark::Bar { x0, x1, y } => {
+                        let ((left, base), (right, end)) = (to_px(x0, 0.0), to_px(x1, y));
+                        fill_rect(&mut rgb, left, base.min(end), right.max(left + 1.0), base.max(end), color);
+                    }
+                    Mark::Point { x, y } => {
+                        let (x, y) = to_px(x, y);
+                        fill_rect(&mut rgb, x - 1.0, y - 1.0, x + 2.0, y + 2.0, color);
+                    }
+                }
+            }
+        }
+        rgb
+    }
+
+    /// Writes the chart as PNG for a `.png` path, as SVG otherwise.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("png")) {
+            png::write_rgb(path, WIDTH, HEIGHT, &self.rgb(), None, None)
+        } else {
+            fs::write(path, self.svg())
+        }
+    }
+}
+
+fn fill_rect(rgb: &mut [u8], x0: f64, y0: f64, x1: f64, y1: f64, (r, g, b): (u8, u8, u8)) {
+    let clamp = |value: f64, max: u32| (value.round().max(0.0) as u32).min(max);
+    for y in clamp(y0, HEIGHT)..clamp(y1, HEIGHT) {
+        for x in clamp(x0, WIDTH)..clamp(x1, WIDTH) {
+            let pixel = ((y * WIDTH + x) * 3) as usize;
+            rgb[pixel..pixel + 3].copy_from_slice(&[r, g, b]);
+        }
+    }
+}
+
+fn tick_label(value: f64) -> String {
+    match value.abs() {
+        magnitude if magnitude >= 100.0 || magnitude == 0.0 => format!("{:.0}", value),
+        magnitude if magnitude >= 1.0 => format!("{:.1}", value),
+        _ => format!("{:.3}", value),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Latency histogram with the sessions' bars side by side in every bin.
+pub fn latency_histogram(sessions: &[HostSession], bins: usize) -> Chart {
+    let bins = bins.max(1);
+    let latencies = || sessions.iter().flat_map(|session| session.records.iter().map(|record| record.total_latency_ms));
+    let (min, max) = latencies().fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), latency| (min.min(latency), max.max(latency)));
+    let width = if max > min { (max - min) / bins as f64 } else { 1.0 };
+    let series = sessions
+        .iter()
+        .enumerate()
+        .map(|(index, session)| {
+            let mut counts = vec![0u64; bins];
+            for record in &session.records {
+                counts[(((record.total_latency_ms - min) / width) as usize).min(bins - 1)] += 1;
+            }
+            let share = width / sessions.len() as f64;
+            let marks = counts
+                .iter()
+                .enumerate()
+                .filter(|(_, count)| **count > 0)
+                .map(|(bin, count)| {
+                    let x0 = min + bin as f64 * width + index as f64 * share;
+                    Mark::Bar { x0, x1: x0 + share, y: *count as f64 }
+                })
+                .collect();
+            Series { name: session.name.clone(), marks }
+        })
+        .collect();
+    Chart {
+        title: "Trigger-to-frame latency".to_string(),
+        x_label: "latency [ms]".to_string(),
+        y_label: "frames".to_string(),
+        series,
+    }
+}
+
+/// Triggers of one time bucket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DropBucket {
+    /// Trigger hw_ts where the bucket starts, ns.
+    pub start_ns: u64,
+    pub recorded: u64,
+    pub dropped: u64,
+}
+
+/// Triggers without a recorded frame over the time of a session.
+#[derive(Debug, Clone, Default)]
+pub struct DropTimeline {
+    pub name: String,
+    /// Most common step between recorded trigger ids (1 for a camera on every trigger).
+    pub stride: u64,
+    pub recorded: u64,
+    pub dropped: u64,
+    /// Steps beyond the stride, and the triggers dropped in the longest one.
+    pub gaps: u64,
+    pub longest_gap: u64,
+    /// hw_ts of the last recorded trigger before the longest gap, ns.
+    pub longest_gap_ns: u64,
+    pub buckets: Vec<DropBucket>,
+}
+
+pub fn drop_timeline(session: &HostSession, bucket_ns: u64) -> DropTimeline {
+    let bucket_ns = bucket_ns.max(1);
+    let mut triggers: Vec<(u64, u64)> = session.records.iter().map(|record| (record.trigger.frame_id, record.trigger.hw_timestamp_ns)).collect();
+    triggers.sort_unstable();
+    triggers.dedup_by_key(|(id, _)| *id);
+    let mut timeline = DropTimeline {
+        name: session.name.clone(),
+        stride: 1,
+        recorded: triggers.len() as u64,
+        ..DropTimeline::default()
+    };
+    let Some(&(_, start_ns)) = triggers.iter().min_by_key(|(_, hw_ts)| *hw_ts) else {
+        return timeline;
+    };
+
+    let mut steps = BTreeMap::<u64, u64>::new();
+    for pair in triggers.windows(2) {
+        *steps.entry(pair[1].0 - pair[0].0).or_default() += 1;
+    }
+    // The smallest of equally common steps
+    timeline.stride = steps.iter().max_by_key(|(step, count)| (**count, std::cmp::Reverse(**step))).map_or(1, |(step, _)| *step);
+
+    let mut buckets = BTreeMap::<u64, DropBucket>::new();
+    for (index, &(id, hw_ts)) in triggers.iter().enumerate() {
+        bucket_at(&mut buckets, start_ns, bucket_ns, hw_ts).recorded += 1;
+        let Some(&(previous_id, previous_ns)) = index.checked_sub(1).map(|previous| &triggers[previous]) else {
+            continue;
+        };
+        let missing = ((id - previous_id) / timeline.stride).saturating_sub(1);
+        if missing == 0 {
+            continue;
+        }
+        timeline.gaps += 1;
+        timeline.dropped += missing;
+        if missing > timeline.longest_gap {
+            (timeline.longest_gap, timeline.longest_gap_ns) = (missing, previous_ns);
+        }
+        // Spread evenly over the gap
+        for k in 1..=missing {
+            let at = previous_ns + (hw_ts.saturating_sub(previous_ns) as u128 * k as u128 / (missing + 1) as u128) as u64;
+            bucket_at(&mut buckets, start_ns, bucket_ns, at).dropped += 1;
+        }
+    }
+    timeline.buckets = buckets.into_values().collect();
+    timeline
+}
+
+fn bucket_at(buckets: &mut BTreeMap<u64, DropBucket>, start_ns: u64, bucket_ns: u64, hw_ts: u64) -> &mut DropBucket {
+    let index = hw_ts.saturating_sub(start_ns) / bucket_ns;
+    buckets.entry(index).or_insert(DropBucket {
+        start_ns: start_ns + index * bucket_ns,
+        recorded: 0,
+        dropped: 0,
+    })
+}
+
+/// Dropped triggers per bucket, the sessions' bars side by side; seconds since the earliest bucket.
+pub fn drops_chart(timelines: &[DropTimeline], bucket_ns: u64) -> Chart {
+    let start_ns = timelines.iter().filter_map(|timeline| timeline.buckets.first()).map(|bucket| bucket.start_ns).min().unwrap_or(0);
+    let (bucket_s, share) = (bucket_ns as f64 / 1e9, 1.0 / timelines.len().max(1) as f64);
+    let series = timelines
+        .iter()
+        .enumerate()
+        .map(|(index, timeline)| Series {
+            name: timeline.name.clone(),
+            marks: timeline
+                .buckets
+                .iter()
+                .filter(|bucket| bucket.dropped > 0)
+                .map(|bucket| {
+                    let x0 = (bucket.start_ns - start_ns) as f64 / 1e9 + index as f64 * share * bucket_s;
+                    Mark::Bar { x0, x1: x0 + share * bucket_s, y: bucket.dropped as f64 }
+                })
+                .collect(),
+        })
+        .collect();
+    Chart {
+        title: "Dropped triggers".to_string(),
+        x_label: "time [s]".to_string(),
+        y_label: format!("triggers per {}s", bucket_s),
+        series,
+    }
+}
+
+/// Latency of one session against the other sessions on their shared triggers.
+#[derive(Debug, Clone, Default)]
+pub struct SessionSkew {
+    pub name: String,
+    /// (trigger hw_ts ns, skew ms) in trigger order.
+    pub samples: Vec<(u64, f64)>,
+    pub summary: StatsSummary,
+}
+
+// Skew per session, keyed by trigger id, for triggers recorded by at least two sessions
+fn skew_by_trigger(sessions: &[HostSession]) -> Vec<BTreeMap<u64, f64>> {
+    let mut latencies = BTreeMap::<u64, Vec<(usize, f64)>>::new();
+    for (index, session) in sessions.iter().enumerate() {
+        for record in &session.records {
+            latencies.entry(record.trigger.frame_id).or_default().push((index, record.total_latency_ms));
+        }
+    }
+    let mut skews = vec![BTreeMap::new(); sessions.len()];
+    for (id, latencies) in latencies {
+        if latencies.len() < 2 {
+            continue;
+        }
+        let Some(median) = skew::median(latencies.iter().map(|(_, latency)| *latency)) else {
+            continue;
+        };
+        for (index, latency) in latencies {
+            skews[index].insert(id, latency - median);
+        }
+    }
+    skews
+}
+
+/// Skew of every session; empty samples for sessions sharing no trigger with another.
+pub fn session_skew(sessions: &[HostSession]) -> Vec<SessionSkew> {
+    sessions
+        .iter()
+        .zip(skew_by_trigger(sessions))
+        .map(|(session, skews)| {
+            let mut samples: Vec<(u64, f64)> = session
+                .records
+                .iter()
+                .filter_map(|record| skews.get(&record.trigger.frame_id).map(|skew| (record.trigger.hw_timestamp_ns, *skew)))
+                .collect();
+            samples.sort_by_key(|(hw_ts, _)| *hw_ts);
+            samples.dedup_by_key(|(hw_ts, _)| *hw_ts);
+            let values: Vec<f64> = samples.iter().map(|(_, skew)| *skew).collect();
+            SessionSkew {
+                name: session.name.clone(),
+                summary: summarize(&values),
+                samples,
+            }
+        })
+        .collect()
+}
+
+/// Skew of every session over time, seconds since the earliest shared trigger.
+pub fn skew_chart(skews: &[SessionSkew]) -> Chart {
+    let start_ns = skews.iter().filter_map(|skew| skew.samples.first()).map(|(hw_ts, _)| *hw_ts).min().unwrap_or(0);
+    Chart {
+        title: "Latency skew against the other cameras".to_string(),
+        x_label: "time [s]".to_string(),
+        y_label: "skew [ms]".to_string(),
+        series: skews
+            .iter()
+            .map(|skew| Series {
+                name: skew.name.clone(),
+                marks: skew
+                    .samples
+                    .iter()
+                    .map(|(hw_ts, skew)| Mark::Point { x: (hw_ts - start_ns) as f64 / 1e9, y: *skew })
+                    .collect(),
+            })
+            .collect(),
+    }
+}
+
+/// Writes one row per recorded frame of all sessions; returns the number of rows.
+pub fn write_csv(sessions: &[HostSession], out: &mut impl Write) -> io::Result<u64> {
+    writeln!(out, "{}", CSV_HEADER)?;
+    let mut rows = 0;
+    for (session, skews) in sessions.iter().zip(skew_by_trigger(sessions)) {
+        for record in &session.records {
+            let CameraTrigger { frame_id: id, hw_timestamp_ns: hw_ts, .. } = record.trigger;
+            let skew = skews.get(&id).map(|skew| format!("{:.3}", skew)).unwrap_or_default();
+            writeln!(
+                out,
+                "{},{},{},{},{:.3},{:.3},{}",
+                session.name, id, hw_ts, record.v4l2_timestamp_ns, record.total_latency_ms, record.score_ms, skew
+            )?;
+            rows += 1;
+        }
+    }
+    out.flush()?;
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::MatchRecord;
+    use crate::signing::{SignatureStatus, TriggerSignature};
+
+    const MS: u64 = 1_000_000;
+
+    fn session(name: &str, ids: impl IntoIterator<Item = u64>, latency_ms: f64) -> HostSession {
+        let records = ids
+            .into_iter()
+            .map(|id| MatchRecord {
+                trigger: CameraTrigger::new(id, 1_700_000_000_000 * MS + id * 10 * MS, 0),
+                v4l2_timestamp_ns: 1_700_000_000_000 * MS + id * 10 * MS + (latency_ms * 1e6) as u64,
+                total_latency_ms: latency_ms,
+                score_ms: 0.0,
+                signature_status: SignatureStatus::Unsigned,
+                signature: TriggerSignature::default(),
+                metadata: Vec::new(),
+            })
+            .collect();
+        HostSession {
+            name: name.to_string(),
+            records,
+            mappings: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn drops_beyond_the_stride_are_counted_where_they_happened() {
+        // Every second trigger, with 20, 22 and 24 missing
+        let cam = session("cam0", (0..=40).step_by(2).filter(|id| !(20..=24).contains(id)), 12.0);
+        let timeline = drop_timeline(&cam, 100 * MS);
+        assert_eq!((timeline.stride, timeline.recorded, timeline.dropped), (2, 18, 3));
+        assert_eq!((timeline.gaps, timeline.longest_gap), (1, 3));
+        assert_eq!(timeline.longest_gap_ns, cam.records[9].trigger.hw_timestamp_ns);
+        // 10ms per trigger id: ids 20..=24 fall into the bucket starting at 200ms
+        let dropped: Vec<u64> = timeline.buckets.iter().map(|bucket| bucket.dropped).collect();
+        assert_eq!(dropped, [0, 0, 3, 0, 0]);
+
+        let chart = drops_chart(&[timeline], 100 * MS);
+        let [Mark::Bar { x0, y, .. }] = chart.series[0].marks[..] else {
+            panic!("one bar expected: {:?}", chart.series[0].marks);
+        };
+        assert_eq!(((x0 * 1e3).round(), y), (200.0, 3.0));
+    }
+
+    #[test]
+    fn skew_points_at_the_slower_camera() {
+        let sessions = [session("left", 0..50, 10.0), session("right", 0..50, 10.0), session("hub", 10..60, 12.5)];
+        let skews = session_skew(&sessions);
+        // Only the hub recorded triggers 50..60, they have no skew
+        assert_eq!(skews.iter().map(|skew| skew.summary.count).collect::<Vec<_>>(), [50, 50, 40]);
+        assert_eq!((skews[0].summary.max_ms, skews[2].summary.p50_ms), (0.0, 2.5));
+
+        let mut csv = Vec::new();
+        assert_eq!(write_csv(&sessions, &mut csv).unwrap(), 150);
+        let csv = String::from_utf8(csv).unwrap();
+        assert!(csv.lines().any(|line| line.starts_with("hub,10,") && line.ends_with(",2.500")));
+        assert!(csv.lines().any(|line| line.starts_with("hub,59,") && line.ends_with(",0.000,")));
+
+        let chart = skew_chart(&skews);
+        assert!(chart.svg().contains(">hub</text>"));
+        let histogram = latency_histogram(&sessions, DEFAULT_BINS);
+        assert_eq!(histogram.series[2].marks.len(), 1);
+        assert_eq!(histogram.rgb().len(), 960 * 480 * 3);
+    }
+}
@@ -0,0 +1,335 @@
+// Diff of two frame-to-trigger association traces.
+//
+// An association trace lists, for every frame of a session, the trigger it
+// was matched to. Two traces of the same session, e.g. the recorded one and a
+// replay through a changed matcher, are compared frame by frame (frames are
+// identified by their timestamp, so both traces must share a time base):
+//
+//   unchanged   same trigger, or no match in both
+//   reassigned  matched to a different trigger
+//   gained      matched only in the second trace
+//   lost        matched only in the first trace
+//
+// A frame missing from one trace counts as unmatched there. Traces come from a
+// session directory (`records.csv`), a fixture's expected pairs, or a fixture
+// replayed through the current matcher (`replay:<file>`, see `fixture`). The
+// diff renders as an ASCII timeline strip or as an SVG timeline with a line
+// from every frame to its trigger in both traces (`assoc_diff` binary).
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use crate::fixture::Fixture;
+use crate::session::{self, MatchRecord};
+
+#[derive(Debug, Clone, Default)]
+pub struct AssociationTrace {
+    pub name: String,
+    /// `(frame_ns, trigger_id)` in frame order.
+    pub frames: Vec<(u64, Option<u64>)>,
+    /// Hardware timestamp per trigger id, where known.
+    pub triggers: BTreeMap<u64, u64>,
+}
+
+impl AssociationTrace {
+    pub fn from_records(name: &str, records: &[MatchRecord]) -> Self {
+        let mut frames: Vec<_> = records.iter().map(|record| (record.v4l2_timestamp_ns, Some(record.trigger.frame_id))).collect();
+        frames.sort();
+        Self {
+            name: name.to_string(),
+            frames,
+            triggers: records.iter().map(|record| (record.trigger.frame_id, record.trigger.hw_timestamp_ns)).collect(),
+        }
+    }
+
+    /// The fixture's expected pairs, or with `replay` the pairs the current matcher produces.
+    pub fn from_fixture(name: &str, fixture: &Fixture, replay: bool) -> Self {
+        let frames = if replay {
+            fixture.frames.iter().zip(fixture.replay()).map(|(&(frame_ns, _), id)| (frame_ns, id)).collect()
+        } else {
+            fixture.frames.clone()
+        };
+        Self {
+            name: name.to_string(),
+            frames,
+            triggers: fixture.triggers.iter().map(|&(id, hw_ts, _)| (id, hw_ts)).collect(),
+        }
+    }
+
+    /// A session directory, a fixture file, or `replay:<fixture file>`.
+    pub fn load(spec: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        if let Some(path) = spec.strip_prefix("replay:") {
+            let fixture = Fixture::parse(&fs::read_to_string(path)?).map_err(|e| format!("{}: {}", path, e))?;
+            return Ok(Self::from_fixture(spec, &fixture, true));
+        }
+        let path = Path::new(spec);
+        if path.is_dir() {
+            return Ok(Self::from_records(spec, &session::read_records(path)?));
+        }
+        let fixture = Fixture::parse(&fs::read_to_string(path)?).map_err(|e| format!("{}: {}", spec, e))?;
+        Ok(Self::from_fixture(spec, &fixture, false))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Unchanged,
+    Reassigned,
+    Gained,
+    Lost,
+}
+
+impl Change {
+    pub const ALL: [Change; 4] = [Change::Unchanged, Change::Reassigned, Change::Gained, Change::Lost];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Change::Unchanged => "unchanged",
+            Change::Reassigned => "reassigned",
+            Change::Gained => "gained",
+            Change::Lost => "lost",
+        }
+    }
+
+    /// Timeline strip character; a bucket shows its most severe change.
+    fn symbol(self) -> char {
+        match self {
+            Change::Unchanged => '.',
+            Change::Reassigned => 'x',
+            Change::Gained => '+',
+            Change::Lost => '-',
+        }
+    }
+
+    fn color(self) -> &'static str {
+        match self {
+            Change::Unchanged => "#9e9e9e",
+            Change::Reassigned => "#e65100",
+            Change::Gained => "#2e7d32",
+            Change::Lost => "#c62828",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameDiff {
+    pub frame_ns: u64,
+    pub before: Option<u64>,
+    pub after: Option<u64>,
+    pub change: Change,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AssociationDiff {
+    /// Every frame of either trace, in frame order.
+    pub frames: Vec<FrameDiff>,
+}
+
+impl AssociationDiff {
+    pub fn new(before: &AssociationTrace, after: &AssociationTrace) -> Self {
+        let mut merged: BTreeMap<u64, (Option<u64>, Option<u64>)> = BTreeMap::new();
+        for &(frame_ns, id) in &before.frames {
+            merged.entry(frame_ns).or_default().0 = id;
+        }
+        for &(frame_ns, id) in &after.frames {
+            merged.entry(frame_ns).or_default().1 = id;
+        }
+        let frames = merged
+            .into_iter()
+            .map(|(frame_ns, (before, after))| FrameDiff {
+                frame_ns,
+                before,
+                after,
+                change: match (before, after) {
+                    (Some(a), Some(b)) if a != b => Change::Reassigned,
+                    (None, Some(_)) => Change::Gained,
+                    (Some(_), None) => Change::Lost,
+                    _ => Change::Unchanged,
+                },
+            })
+            .collect();
+        Self { frames }
+    }
+
+    pub fn count(&self, change: Change) -> usize {
+        self.frames.iter().filter(|frame| frame.change == change).count()
+    }
+
+    /// Frames whose association differs.
+    pub fn changed(&self) -> impl Iterator<Item = &FrameDiff> {
+        self.frames.iter().filter(|frame| frame.change != Change::Unchanged)
+    }
+
+    pub fn summary(&self) -> String {
+        let counts: Vec<String> = Change::ALL.iter().map(|&change| format!("{} {}", self.count(change), change.name())).collect();
+        format!("{} frames: {}", self.frames.len(), counts.join(", "))
+    }
+
+    /// The session as `width` characters, each showing the most severe change of its frames
+    /// (`x` reassigned, `-` lost, `+` gained, `.` unchanged, blank without frames).
+    pub fn timeline(&self, width: usize) -> String {
+        let (Some(first), Some(last)) = (self.frames.first(), self.frames.last()) else {
+            return String::new();
+        };
+        let width = width.max(1);
+        let span = (last.frame_ns - first.frame_ns).max(1);
+        let mut buckets: Vec<Option<Change>> = vec![None; width];
+        for frame in &self.frames {
+            let bucket = (((frame.frame_ns - first.frame_ns) as u128 * (width - 1) as u128) / span as u128) as usize;
+            let severity = |change: Option<Change>| match change {
+                None => 0,
+                Some(Change::Unchanged) => 1,
+                Some(Change::Gained) => 2,
+                Some(Change::Lost) => 3,
+                Some(Change::Reassigned) => 4,
+            };
+            if severity(Some(frame.change)) > severity(buckets[bucket]) {
+                buckets[bucket] = Some(frame.change);
+            }
+        }
+        buckets.into_iter().map(|change| change.map_or(' ', Change::symbol)).collect()
+    }
+
+    /// SVG timeline: triggers on top, frames below, a line from every frame to its trigger
+    /// in `before` (dashed) and `after` (solid), colored by change.
+    pub fn svg(&self, before: &AssociationTrace, after: &AssociationTrace) -> String {
+        const WIDTH: f64 = 1600.0;
+        const MARGIN: f64 = 40.0;
+        const TRIGGER_Y: f64 = 60.0;
+        const FRAME_Y: f64 = 160.0;
+
+        let trigger_ns = |id: u64| after.triggers.get(&id).or_else(|| before.triggers.get(&id)).copied();
+        let times = self
+            .frames
+            .iter()
+            .map(|frame| frame.frame_ns)
+            .chain(before.triggers.values().copied())
+            .chain(after.triggers.values().copied());
+        let (start, end) = times.fold((u64::MAX, 0), |(start, end), t| (start.min(t), end.max(t)));
+        let span = end.saturating_sub(start).max(1) as f64;
+        let x = |t: u64| MARGIN + t.saturating_sub(start) as f64 / span * (WIDTH - 2.0 * MARGIN);
+
+        let mut svg = String::new();
+        let _ = writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="240" font-family="monospace" font-size="12">"#,
+            WIDTH
+        );
+        let _ = writeln!(svg, r#"<rect width="100%" height="100%" fill="white"/>"#);
+        let _ = writeln!(svg, r#"<text x="{}" y="{}">triggers</text>"#, MARGIN, TRIGGER_Y - 16.0);
+        let _ = writeln!(svg, r#"<text x="{}" y="{}">frames</text>"#, MARGIN, FRAME_Y + 24.0);
+        let mut trigger_times: Vec<u64> = before.triggers.values().chain(after.triggers.values()).copied().collect();
+        trigger_times.sort_unstable();
+        trigger_times.dedup();
+        for t in trigger_times {
+            let _ = writeln!(svg, r##"<line x1="{0:.1}" y1="{1}" x2="{0:.1}" y2="{2}" stroke="#424242"/>"##, x(t), TRIGGER_Y - 8.0, TRIGGER_Y);
+        }
+        for frame in &self.frames {
+            let frame_x = x(frame.frame_ns);
+            let color = frame.change.color();
+            let mut link = |id: Option<u64>, dashed: bool| {
+                if let Some(t) = id.and_then(trigger_ns) {
+                    let dash = if dashed { r#" stroke-dasharray="4 3""# } else { "" };
+                    let _ = writeln!(
+                        svg,
+                        r#"<line x1="{:.1}" y1="{}" x2="{:.1}" y2="{}" stroke="{}"{}/>"#,
+                        frame_x, FRAME_Y, x(t), TRIGGER_Y, color, dash
+                    );
+                }
+            };
+            // Unchanged pairs draw a single line
+            if frame.change != Change::Unchanged {
+                link(frame.before, true);
+            }
+            link(frame.after, false);
+            let id = |id: Option<u64>| id.map_or("-".to_string(), |id| id.to_string());
+            let _ = writeln!(
+                svg,
+                r#"<circle cx="{:.1}" cy="{}" r="3" fill="{}"><title>frame {}ns: {} -&gt; {} ({})</title></circle>"#,
+                frame_x,
+                FRAME_Y,
+                color,
+                frame.frame_ns,
+                id(frame.before),
+                id(frame.after),
+                frame.change.name()
+            );
+        }
+        let mut legend_x = MARGIN;
+        for change in Change::ALL {
+            let _ = writeln!(svg, r#"<circle cx="{:.1}" cy="212" r="4" fill="{}"/>"#, legend_x, change.color());
+            let _ = writeln!(svg, r#"<text x="{:.1}" y="216">{} {}</text>"#, legend_x + 8.0, change.name(), self.count(change));
+            legend_x += 140.0;
+        }
+        let _ = writeln!(
+            svg,
+            r#"<text x="{:.1}" y="216">dashed: {}, solid: {}</text>"#,
+            legend_x,
+            escape(&before.name),
+            escape(&after.name)
+        );
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trace(name: &str, frames: &[(u64, Option<u64>)]) -> AssociationTrace {
+        AssociationTrace {
+            name: name.to_string(),
+            frames: frames.to_vec(),
+            triggers: frames.iter().filter_map(|&(_, id)| id).map(|id| (id, id * 100)).collect(),
+        }
+    }
+
+    #[test]
+    fn frames_are_classified_by_their_trigger_in_both_traces() {
+        let before = trace("recorded", &[(100, Some(1)), (200, Some(2)), (300, Some(3)), (400, None), (500, Some(5))]);
+        let after = trace("replay", &[(100, Some(1)), (200, Some(3)), (300, None), (400, Some(4)), (600, None)]);
+        let diff = AssociationDiff::new(&before, &after);
+
+        let changes: Vec<_> = diff.frames.iter().map(|frame| (frame.frame_ns, frame.change)).collect();
+        assert_eq!(
+            changes,
+            [
+                (100, Change::Unchanged),
+                (200, Change::Reassigned),
+                (300, Change::Lost),
+                (400, Change::Gained),
+                // Missing from the replay: unmatched there
+                (500, Change::Lost),
+                (600, Change::Unchanged),
+            ]
+        );
+        assert_eq!(diff.changed().count(), 4);
+        assert_eq!(diff.summary(), "6 frames: 2 unchanged, 1 reassigned, 1 gained, 2 lost");
+        // 100..600 over 6 characters: one frame per bucket
+        assert_eq!(diff.timeline(6), ".x-+-.");
+        // A bucket shows its most severe change
+        assert_eq!(diff.timeline(3), "x-.");
+        assert_eq!(AssociationDiff::default().timeline(10), "");
+    }
+
+    #[test]
+    fn svg_links_frames_to_their_triggers() {
+        let before = trace("a<b", &[(100, Some(1)), (200, Some(2))]);
+        let after = trace("c&d", &[(100, Some(1)), (200, Some(1))]);
+        let svg = AssociationDiff::new(&before, &after).svg(&before, &after);
+
+        assert!(svg.starts_with("<svg") && svg.ends_with("</svg>\n"));
+        // Unchanged: one solid line; reassigned: dashed before and solid after
+        assert_eq!(svg.matches("stroke-dasharray").count(), 1);
+        assert_eq!(svg.matches("<line").count() - svg.matches(r##"stroke="#424242""##).count(), 3);
+        assert!(svg.contains("frame 200ns: 2 -&gt; 1 (reassigned)"));
+        assert!(svg.contains("dashed: a&lt;b, solid: c&amp;d"));
+    }
+}
@@ -0,0 +1,218 @@
+// Fixed-duration batch runs for scripted hardware acceptance tests.
+//
+// With `--duration 60s` a binary stops by itself, writes a final JSON report
+// (to `--report <file>`, or as the last line on stdout) with the match counts,
+// drops, deadline misses and latency statistics, and exits with a code a
+// script can act on:
+//
+//   0  all configured thresholds passed
+//   1  at least one threshold failed (listed in the report's `failures`)
+//   2  bad usage
+//
+// Thresholds are opt-in: `--min-match-pct <pct>`, `--max-p95-ms <ms>` (total
+// trigger->match latency), `--max-drops <n>` (dropped and missing triggers
+// plus frames lost in the driver) and `--max-deadline-misses <n>`. Frames of
+// the test-pattern camera matched to another trigger than the one they show
+// always fail a run.
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crate::cli::Args;
+use crate::latency::LatencyBreakdown;
+use crate::status::{json_array, json_object, Value};
+
+pub const EXIT_PASS: i32 = 0;
+pub const EXIT_FAIL: i32 = 1;
+pub const EXIT_USAGE: i32 = 2;
+
+/// Parses `500ms`, `60s`, `5m`, `1h` or a plain number of seconds.
+pub fn parse_duration(text: &str) -> Result<Duration, String> {
+    let text = text.trim();
+    let split = text.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number = number.parse::<f64>().map_err(|_| format!("invalid duration '{}'", text))?;
+    let seconds = match unit {
+        "ms" => number / 1000.0,
+        "" | "s" => number,
+        "m" => number * 60.0,
+        "h" => number * 3600.0,
+        _ => return Err(format!("invalid duration unit in '{}' (expected ms, s, m or h)", text)),
+    };
+    Ok(Duration::from_secs_f64(seconds))
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Thresholds {
+    pub min_match_pct: Option<f64>,
+    pub max_p95_ms: Option<f64>,
+    pub max_drops: Option<u64>,
+    pub max_deadline_misses: Option<u64>,
+}
+
+impl Thresholds {
+    pub fn from_args(args: &Args) -> Self {
+        Self {
+            min_match_pct: args.value_as("min-match-pct"),
+            max_p95_ms: args.value_as("max-p95-ms"),
+            max_drops: args.value_as("max-drops"),
+            max_deadline_misses: args.value_as("max-deadline-misses"),
+        }
+    }
+}
+
+/// Counters of a finished run.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BatchResults {
+    /// Triggers published or received.
+    pub triggers: u64,
+    /// Frames that went through matching.
+    pub frames: u64,
+    pub matched: u64,
+    /// Dropped and missing triggers plus frames lost in the driver.
+    pub drops: u64,
+    pub deadline_misses: u64,
+    /// Test-pattern frames matched to another trigger than the one they show.
+    pub pattern_mismatches: u64,
+}
+
+impl BatchResults {
+    pub fn match_pct(&self) -> Option<f64> {
+        (self.frames > 0).then(|| self.matched as f64 / self.frames as f64 * 100.0)
+    }
+}
+
+pub struct BatchRun {
+    program: String,
+    duration: Duration,
+    started: Instant,
+    thresholds: Thresholds,
+    report: Option<PathBuf>,
+}
+
+impl BatchRun {
+    /// Reads `--duration`, `--report` and the thresholds; `None` without `--duration`.
+    /// An invalid duration is an error, bad usage (`EXIT_USAGE`) for the binary.
+    pub fn from_args(args: &Args, program: &str) -> Result<Option<Self>, String> {
+        let Some(duration) = args.value("duration") else {
+            return Ok(None);
+        };
+        Ok(Some(Self {
+            program: program.to_string(),
+            duration: parse_duration(duration)?,
+            started: Instant::now(),
+            thresholds: Thresholds::from_args(args),
+            report: args.value("report").map(PathBuf::from),
+        }))
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.started.elapsed() >= self.duration
+    }
+
+    pub fn remaining(&self) -> Duration {
+        self.duration.saturating_sub(self.started.elapsed())
+    }
+
+    /// Threshold violations, empty if the run passed.
+    pub fn failures(&self, results: &BatchResults, latency: &LatencyBreakdown) -> Vec<String> {
+        let mut failures = Vec::new();
+        if let Some(min_pct) = self.thresholds.min_match_pct {
+            match results.match_pct() {
+                Some(pct) if pct >= min_pct => {}
+                Some(pct) => failures.push(format!("match rate {:.1}% below {:.1}%", pct, min_pct)),
+                None => failures.push("no frames processed".to_string()),
+            }
+        }
+        if let Some(max_ms) = self.thresholds.max_p95_ms {
+            let total = latency.snapshot().into_iter().find(|(name, _)| *name == "total");
+            match total {
+                Some((_, stats)) if stats.p95_ms <= max_ms => {}
+                Some((_, stats)) => failures.push(format!("total latency p95 {:.2}ms above {:.2}ms", stats.p95_ms, max_ms)),
+                None => failures.push("no latency samples".to_string()),
+            }
+        }
+        if let Some(max_drops) = self.thresholds.max_drops.filter(|max| results.drops > *max) {
+            failures.push(format!("{} drops above {}", results.drops, max_drops));
+        }
+        if let Some(max_misses) = self.thresholds.max_deadline_misses.filter(|max| results.deadline_misses > *max) {
+            failures.push(format!("{} deadline misses above {}", results.deadline_misses, max_misses));
+        }
+        if results.pattern_mismatches > 0 {
+            failures.push(format!("{} frames matched to another trigger than their test pattern shows", results.pattern_mismatches));
+        }
+        failures
+    }
+
+    /// Writes the final report and returns the exit code.
+    pub fn finish(&self, results: &BatchResults, latency: &LatencyBreakdown) -> std::io::Result<i32> {
+        let failures = self.failures(results, latency);
+        let stages: Vec<(&str, Value)> = latency
+            .snapshot()
+            .into_iter()
+            .map(|(name, stats)| {
+                let stats = json_object(&[
+                    ("count", stats.count.into()),
+                    ("mean_ms", stats.mean_ms.into()),
+                    ("std_ms", stats.std_ms.into()),
+                    ("p50_ms", stats.p50_ms.into()),
+                    ("p95_ms", stats.p95_ms.into()),
+                    ("min_ms", stats.min_ms.into()),
+                    ("max_ms", stats.max_ms.into()),
+                ]);
+                (name, Value::Json(stats))
+            })
+            .collect();
+        let thresholds = json_object(&[
+            ("min_match_pct", self.thresholds.min_match_pct.into()),
+            ("max_p95_ms", self.thresholds.max_p95_ms.into()),
+            ("max_drops", self.thresholds.max_drops.into()),
+            ("max_deadline_misses", self.thresholds.max_deadline_misses.into()),
+        ]);
+        let failure_list: Vec<Value> = failures.iter().map(|failure| failure.as_str().into()).collect();
+        let report = json_object(&[
+            ("program", self.program.as_str().into()),
+            ("passed", failures.is_empty().into()),
+            ("duration_s", self.duration.as_secs_f64().into()),
+            ("elapsed_s", self.started.elapsed().as_secs_f64().into()),
+            ("triggers", results.triggers.into()),
+            ("frames", results.frames.into()),
+            ("matched", results.matched.into()),
+            ("unmatched", results.frames.saturating_sub(results.matched).into()),
+            ("match_pct", results.match_pct().into()),
+            ("drops", results.drops.into()),
+            ("deadline_misses", results.deadline_misses.into()),
+            ("pattern_mismatches", results.pattern_mismatches.into()),
+            ("latency", Value::Json(json_object(&stages))),
+            ("thresholds", Value::Json(thresholds)),
+            ("failures", Value::Json(json_array(&failure_list))),
+        ]);
+        match &self.report {
+            Some(path) => fs::write(path, format!("{}\n", report))?,
+            None => println!("{}", report),
+        }
+        Ok(if failures.is_empty() { EXIT_PASS } else { EXIT_FAIL })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_args(line: &str) -> Result<Option<BatchRun>, String> {
+        BatchRun::from_args(&Args::parse(line.split_whitespace().map(str::to_string), &[]), "subscriber")
+    }
+
+    #[test]
+    fn duration_is_optional_and_an_invalid_one_is_an_error() {
+        assert!(from_args("subscriber").unwrap().is_none());
+
+        let batch = from_args("subscriber --duration 500ms --max-drops 3").unwrap().unwrap();
+        assert_eq!(batch.duration, Duration::from_millis(500));
+        assert_eq!(batch.thresholds.max_drops, Some(3));
+        assert!(!batch.is_over());
+
+        assert_eq!(from_args("subscriber --duration 5d").err(), Some("invalid duration unit in '5d' (expected ms, s, m or h)".to_string()));
+    }
+}
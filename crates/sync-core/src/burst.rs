@@ -0,0 +1,90 @@
+// Trigger bursts for multi-exposure captures.
+//
+// Active illumination and structured light take K closely spaced exposures
+// per logical capture. The publisher then fires K pulses per trigger interval
+// (`--burst-size K`), each with its own trigger id, so the burst id and the
+// index within the burst follow from the id: ids start at 1 and every burst
+// takes K consecutive ids. Consumers configured with the same burst size
+// associate their frames with (burst, index) pairs.
+//
+// Pulses of one burst are too close to be told apart reliably by timestamp
+// alone, so once a frame matched a pulse, the following frames take the
+// following pulses of that burst in order.
+
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BurstPattern {
+    /// Pulses per logical capture, 1 for plain triggers.
+    pub size: u32,
+}
+
+impl Default for BurstPattern {
+    fn default() -> Self {
+        Self { size: 1 }
+    }
+}
+
+/// Position of a trigger within the burst pattern.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BurstPosition {
+    pub burst_id: u64,
+    pub index: u32,
+}
+
+impl fmt::Display for BurstPosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.burst_id, self.index)
+    }
+}
+
+impl BurstPattern {
+    pub fn new(size: u32) -> Self {
+        Self { size: size.max(1) }
+    }
+
+    pub fn is_burst(&self) -> bool {
+        self.size > 1
+    }
+
+    pub fn position(&self, trigger_id: u64) -> BurstPosition {
+        let size = self.size.max(1) as u64;
+        let sequence = trigger_id.saturating_sub(1);
+        BurstPosition {
+            burst_id: sequence / size + 1,
+            index: (sequence % size) as u32,
+        }
+    }
+
+    /// The trigger id the next frame should take after `last_matched`, if that burst has pulses left.
+    pub fn next_in_burst(&self, last_matched: u64) -> Option<u64> {
+        if self.is_burst() && self.position(last_matched).index + 1 < self.size {
+            Some(last_matched + 1)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trigger_ids_map_to_burst_positions() {
+        let pattern = BurstPattern::new(3);
+        let positions: Vec<String> = (1..=7).map(|id| pattern.position(id).to_string()).collect();
+        assert_eq!(positions, ["1.0", "1.1", "1.2", "2.0", "2.1", "2.2", "3.0"]);
+        assert_eq!(BurstPattern::new(0), BurstPattern::default());
+        assert_eq!(BurstPattern::default().position(5), BurstPosition { burst_id: 5, index: 0 });
+    }
+
+    #[test]
+    fn frames_follow_the_remaining_pulses_of_a_burst() {
+        let pattern = BurstPattern::new(3);
+        assert_eq!(pattern.next_in_burst(4), Some(5));
+        assert_eq!(pattern.next_in_burst(5), Some(6));
+        assert_eq!(pattern.next_in_burst(6), None);
+        assert_eq!(BurstPattern::default().next_in_burst(4), None);
+    }
+}
@@ -0,0 +1,208 @@
+// Canary triggers: liveness of the full trigger chain.
+//
+// A heartbeat shows that a process runs, not that triggers get through to it.
+// With `--canary-interval-ms`, the publisher injects a canary trigger every
+// interval on `Camera/Sync` and the sensor fan-outs, signed like any trigger:
+// its id has `CANARY_BIT` set and its timestamps are the injection time. Every
+// consumer that receives a canary acknowledges it with its receive-time
+// feedback on `Camera/Feedback` (see `feedback`) instead of matching it. The
+// publisher's `CanaryMonitor` collects the acknowledgements and reports each
+// canary once its deadline passed: which consumers acknowledged it with what
+// latency (injection to receipt, both on the publisher host's realtime clock)
+// and which known consumers did not. A consumer is known once it acknowledged
+// a canary or was named with `--canary-consumers`.
+
+use std::fmt;
+
+use crate::feedback::ReceiveFeedback;
+use crate::CameraTrigger;
+
+/// Set in the trigger id of canaries; real trigger ids never get near it.
+pub const CANARY_BIT: u64 = 1 << 63;
+
+pub fn is_canary(trigger_id: u64) -> bool {
+    trigger_id & CANARY_BIT != 0
+}
+
+/// Sequence number of a canary trigger id.
+pub fn sequence(trigger_id: u64) -> u64 {
+    trigger_id & !CANARY_BIT
+}
+
+/// Outcome of one canary, reported once its deadline passed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CanaryReport {
+    pub sequence: u64,
+    pub sent_ns: u64,
+    /// `(consumer, latency ns)` in the order the acknowledgements arrived.
+    pub acknowledged: Vec<(String, u64)>,
+    /// Known consumers that did not acknowledge within the deadline.
+    pub missing: Vec<String>,
+}
+
+impl CanaryReport {
+    /// Every known consumer acknowledged, and there was at least one.
+    pub fn is_healthy(&self) -> bool {
+        self.missing.is_empty() && !self.acknowledged.is_empty()
+    }
+
+    pub fn max_latency_ns(&self) -> Option<u64> {
+        self.acknowledged.iter().map(|(_, latency_ns)| *latency_ns).max()
+    }
+
+    /// `name latency, ...` of the acknowledgements.
+    pub fn acknowledged_list(&self) -> String {
+        let acknowledged: Vec<String> = self.acknowledged.iter().map(|(consumer, latency_ns)| format!("{} {:.2}ms", consumer, *latency_ns as f64 / 1e6)).collect();
+        acknowledged.join(", ")
+    }
+}
+
+impl fmt::Display for CanaryReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "canary {}: ", self.sequence)?;
+        if self.acknowledged.is_empty() {
+            write!(f, "no acknowledgement")?;
+        } else {
+            write!(f, "acknowledged by {}", self.acknowledged_list())?;
+        }
+        if !self.missing.is_empty() {
+            write!(f, "; missing {}", self.missing.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct Outstanding {
+    sequence: u64,
+    sent_ns: u64,
+    acknowledged: Vec<(String, u64)>,
+}
+
+/// Publisher side: schedules the canaries and collects their acknowledgements.
+#[derive(Debug)]
+pub struct CanaryMonitor {
+    interval_ns: u64,
+    deadline_ns: u64,
+    next_sequence: u64,
+    next_due_ns: Option<u64>,
+    outstanding: Vec<Outstanding>,
+    consumers: Vec<String>,
+}
+
+impl CanaryMonitor {
+    /// A canary every `interval_ns`, acknowledged within `deadline_ns` by at least the `expected` consumers.
+    pub fn new(interval_ns: u64, deadline_ns: u64, expected: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            interval_ns: interval_ns.max(1),
+            deadline_ns,
+            next_sequence: 0,
+            next_due_ns: None,
+            outstanding: Vec::new(),
+            consumers: expected.into_iter().collect(),
+        }
+    }
+
+    /// The canary to inject at `now_ns`, if one is due; the first is due right away.
+    pub fn due(&mut self, now_ns: u64) -> Option<CameraTrigger> {
+        if self.next_due_ns.is_some_and(|due_ns| now_ns < due_ns) {
+            return None;
+        }
+        self.next_due_ns = Some(now_ns + self.interval_ns);
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        self.outstanding.push(Outstanding {
+            sequence,
+            sent_ns: now_ns,
+            acknowledged: Vec::new(),
+        });
+        Some(CameraTrigger::new(CANARY_BIT | sequence, now_ns, now_ns))
+    }
+
+    /// Records an acknowledgement; late ones (after the report) and repeated ones are ignored.
+    pub fn on_ack(&mut self, ack: &ReceiveFeedback) {
+        let consumer = ack.consumer_str();
+        if !self.consumers.contains(&consumer) {
+            self.consumers.push(consumer.clone());
+        }
+        let sequence = sequence(ack.trigger_id);
+        let Some(canary) = self.outstanding.iter_mut().find(|canary| canary.sequence == sequence) else {
+            return;
+        };
+        if !canary.acknowledged.iter().any(|(acknowledged, _)| *acknowledged == consumer) {
+            canary.acknowledged.push((consumer, ack.received_ns.saturating_sub(canary.sent_ns)));
+        }
+    }
+
+    /// Reports of the canaries whose deadline passed at `now_ns`, oldest first.
+    pub fn expired(&mut self, now_ns: u64) -> Vec<CanaryReport> {
+        let deadline_ns = self.deadline_ns;
+        let (expired, outstanding) = std::mem::take(&mut self.outstanding)
+            .into_iter()
+            .partition::<Vec<_>, _>(|canary| now_ns >= canary.sent_ns + deadline_ns);
+        self.outstanding = outstanding;
+        expired
+            .into_iter()
+            .map(|canary| CanaryReport {
+                missing: self
+                    .consumers
+                    .iter()
+                    .filter(|consumer| !canary.acknowledged.iter().any(|(acknowledged, _)| acknowledged == *consumer))
+                    .cloned()
+                    .collect(),
+                sequence: canary.sequence,
+                sent_ns: canary.sent_ns,
+                acknowledged: canary.acknowledged,
+            })
+            .collect()
+    }
+
+    /// Consumers expected to acknowledge every canary.
+    pub fn consumers(&self) -> &[String] {
+        &self.consumers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START_NS: u64 = 1_700_000_000_000_000_000;
+    const INTERVAL_NS: u64 = 1_000_000_000;
+    const DEADLINE_NS: u64 = 500_000_000;
+
+    #[test]
+    fn reports_acknowledgements_and_missing_consumers() {
+        let mut monitor = CanaryMonitor::new(INTERVAL_NS, DEADLINE_NS, ["radar0".to_string()]);
+        let first = monitor.due(START_NS).unwrap();
+        assert!(is_canary(first.frame_id));
+        assert!(!is_canary(first.frame_id & !CANARY_BIT));
+        assert_eq!(sequence(first.frame_id), 0);
+        assert_eq!(monitor.due(START_NS + INTERVAL_NS - 1), None);
+
+        monitor.on_ack(&ReceiveFeedback::new("subscriber", &first, START_NS + 400_000));
+        monitor.on_ack(&ReceiveFeedback::new("camera serial:ABC123", &first, START_NS + 1_200_000));
+        // Repeated acknowledgements keep the first latency
+        monitor.on_ack(&ReceiveFeedback::new("subscriber", &first, START_NS + 9_000_000));
+        assert!(monitor.expired(START_NS + DEADLINE_NS - 1).is_empty());
+
+        let reports = monitor.expired(START_NS + DEADLINE_NS);
+        assert_eq!(reports.len(), 1);
+        let report = &reports[0];
+        assert_eq!(report.acknowledged, [("subscriber".to_string(), 400_000), ("camera serial:ABC123".to_string(), 1_200_000)]);
+        assert_eq!(report.missing, ["radar0"]);
+        assert!(!report.is_healthy());
+        assert_eq!(report.max_latency_ns(), Some(1_200_000));
+        assert_eq!(report.to_string(), "canary 0: acknowledged by subscriber 0.40ms, camera serial:ABC123 1.20ms; missing radar0");
+
+        // Consumers seen once are expected for every later canary; late acknowledgements are dropped
+        let second = monitor.due(START_NS + INTERVAL_NS).unwrap();
+        assert_eq!(sequence(second.frame_id), 1);
+        monitor.on_ack(&ReceiveFeedback::new("radar0", &first, START_NS + INTERVAL_NS + 100_000));
+        monitor.on_ack(&ReceiveFeedback::new("radar0", &second, START_NS + INTERVAL_NS + 300_000));
+        monitor.on_ack(&ReceiveFeedback::new("subscriber", &second, START_NS + INTERVAL_NS + 200_000));
+        let report = monitor.expired(START_NS + INTERVAL_NS + DEADLINE_NS).remove(0);
+        assert_eq!(report.missing, ["camera serial:ABC123"]);
+        assert_eq!(report.acknowledged.len(), 2);
+    }
+}
@@ -0,0 +1,165 @@
+// Independent trigger channels of one publisher.
+//
+// Camera groups that run at different rates (group A at 30 Hz on the main
+// trigger line, group B at 10 Hz on its own) get one trigger channel each
+// from the same publisher process. Unlike a sensor fan-out (see
+// `sync_core::sensor`), a channel is not derived from the master triggers: it
+// has its own schedule on the realtime grid `k * interval + offset` and its
+// own trigger id space starting at 1, and is published on its own service.
+// Consumers started with the same channel subscribe to that service and match
+// against the channel's rate.
+//
+// Channels are given as comma separated `key=value` pairs:
+//
+//   name=groupb,interval_ms=100,offset_ms=5
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::clock;
+use crate::CameraTrigger;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TriggerChannel {
+    /// Also names the channel's trigger service.
+    pub name: String,
+    pub interval_ns: u64,
+    /// Phase of the channel's grid, so channels with a common interval can interleave.
+    pub offset_ns: i64,
+}
+
+impl fmt::Display for TriggerChannel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "name={},interval_ms={},offset_ms={}", self.name, self.interval_ns as f64 / 1e6, self.offset_ns as f64 / 1e6)
+    }
+}
+
+impl FromStr for TriggerChannel {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut name = None;
+        let mut interval_ns = None;
+        let mut offset_ns = 0;
+        for pair in text.split(',') {
+            let (key, value) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("invalid trigger channel entry '{}' (expected key=value)", pair))?;
+            let invalid = || format!("invalid trigger channel value {}='{}'", key, value);
+            match key.trim() {
+                "name" => name = Some(value.trim().to_string()),
+                "interval_ms" => interval_ns = Some(value.trim().parse::<f64>().ok().filter(|ms| *ms >= 1e-6).ok_or_else(invalid)? * 1e6),
+                "offset_ms" => offset_ns = (value.trim().parse::<f64>().map_err(|_| invalid())? * 1e6) as i64,
+                other => return Err(format!("unknown trigger channel key '{}'", other)),
+            }
+        }
+        let name = name
+            .filter(|name| !name.is_empty() && !name.contains('/'))
+            .ok_or("trigger channel needs a name without '/'")?;
+        let interval_ns = interval_ns.ok_or_else(|| format!("trigger channel {} needs an interval_ms", name))?;
+        Ok(Self {
+            name,
+            interval_ns: interval_ns.round() as u64,
+            offset_ns,
+        })
+    }
+}
+
+impl TriggerChannel {
+    /// Trigger rate of the channel, at least 1.
+    pub fn rate(&self) -> u32 {
+        (1e9 / self.interval_ns as f64).round().max(1.0) as u32
+    }
+}
+
+/// When each channel fires next, and the trigger ids they gave out.
+#[derive(Debug, Clone)]
+pub struct ChannelSchedule {
+    channels: Vec<TriggerChannel>,
+    next_ns: Vec<u64>,
+    last_ids: Vec<u64>,
+}
+
+impl ChannelSchedule {
+    /// Every channel starts at its first grid point after `now_ns`.
+    pub fn new(channels: Vec<TriggerChannel>, now_ns: u64) -> Self {
+        let next_ns = channels.iter().map(|channel| clock::next_grid_ns(now_ns, channel.interval_ns, channel.offset_ns)).collect();
+        let last_ids = vec![0; channels.len()];
+        Self { channels, next_ns, last_ids }
+    }
+
+    pub fn channels(&self) -> &[TriggerChannel] {
+        &self.channels
+    }
+
+    /// Index and grid point of the channel that fires next; channels on the same grid point fire in
+    /// the order they were given. `None` without channels.
+    pub fn next(&self) -> Option<(usize, u64)> {
+        self.next_ns.iter().copied().enumerate().min_by_key(|&(_, due_ns)| due_ns)
+    }
+
+    /// Fires channel `index` with the trigger timestamp `hw_ts`: the trigger gets the channel's next
+    /// id, and the channel's next grid point is the first one after `hw_ts` (grid points missed by
+    /// a late wake-up are skipped, not fired in a burst).
+    pub fn fire(&mut self, index: usize, hw_ts: u64, pub_ts: u64) -> CameraTrigger {
+        let channel = &self.channels[index];
+        self.last_ids[index] += 1;
+        self.next_ns[index] = clock::next_grid_ns(hw_ts.max(self.next_ns[index]), channel.interval_ns, channel.offset_ns);
+        CameraTrigger::new(self.last_ids[index], hw_ts, pub_ts)
+    }
+
+    /// Triggers fired on channel `index` so far.
+    pub fn fired(&self, index: usize) -> u64 {
+        self.last_ids[index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: u64 = 1_000_000;
+
+    // A multiple of both channel intervals
+    const START_NS: u64 = 1_700_000_000_000 * MS;
+
+    #[test]
+    fn channels_parse_and_print() {
+        let channel: TriggerChannel = "name=groupb, interval_ms=100, offset_ms=5".parse().unwrap();
+        assert_eq!((channel.name.as_str(), channel.interval_ns, channel.offset_ns, channel.rate()), ("groupb", 100 * MS, 5_000_000, 10));
+        assert_eq!(channel.to_string().parse::<TriggerChannel>(), Ok(channel));
+        assert_eq!("name=fast,interval_ms=0.5".parse::<TriggerChannel>().map(|channel| channel.rate()), Ok(2000));
+
+        assert!("interval_ms=100".parse::<TriggerChannel>().is_err());
+        assert!("name=a/b,interval_ms=100".parse::<TriggerChannel>().is_err());
+        assert!("name=groupb".parse::<TriggerChannel>().is_err());
+        assert!("name=groupb,interval_ms=0".parse::<TriggerChannel>().is_err());
+        assert!("name=groupb,interval_ms=100,divider=2".parse::<TriggerChannel>().is_err());
+    }
+
+    #[test]
+    fn channels_fire_on_their_own_grids_and_ids() {
+        let channels = ["name=a,interval_ms=100", "name=b,interval_ms=250,offset_ms=5"].map(|spec| spec.parse::<TriggerChannel>().unwrap());
+        let mut schedule = ChannelSchedule::new(channels.to_vec(), START_NS);
+
+        // 0.1ms wake-up latency on every grid point
+        let mut fired = Vec::new();
+        while fired.len() < 6 {
+            let (index, due_ns) = schedule.next().unwrap();
+            let trigger = schedule.fire(index, due_ns + MS / 10, due_ns + MS / 5);
+            fired.push((schedule.channels()[index].name.clone(), trigger.frame_id, (trigger.hw_timestamp_ns - START_NS) / (MS / 10)));
+        }
+        let expected = [("b", 1, 51), ("a", 1, 1001), ("a", 2, 2001), ("b", 2, 2551), ("a", 3, 3001), ("a", 4, 4001)];
+        assert_eq!(fired, expected.map(|(name, id, tenth_ms)| (name.to_string(), id, tenth_ms)));
+        assert_eq!((schedule.fired(0), schedule.fired(1)), (4, 2));
+
+        // Woken 180ms late: the missed grid points are skipped, not fired in a burst
+        assert_eq!(schedule.next(), Some((0, START_NS + 500 * MS)));
+        assert_eq!(schedule.fire(0, START_NS + 680 * MS, START_NS + 680 * MS), CameraTrigger::new(5, START_NS + 680 * MS, START_NS + 680 * MS));
+        assert_eq!(schedule.next(), Some((1, START_NS + 505 * MS)));
+        assert_eq!(schedule.fire(1, START_NS + 681 * MS, START_NS + 681 * MS).frame_id, 3);
+        assert_eq!(schedule.next(), Some((0, START_NS + 700 * MS)));
+        schedule.fire(0, START_NS + 700 * MS, START_NS + 700 * MS);
+        assert_eq!(schedule.next(), Some((1, START_NS + 755 * MS)));
+    }
+}
@@ -0,0 +1,132 @@
+// Minimal command line handling shared by the binaries.
+//
+// The binaries historically take positional arguments (`publisher 33`,
+// `subscriber 110 10`, ...). Optional features are enabled with `--name value`
+// options, which may appear anywhere without shifting the positional indices.
+//
+// `--config rig.toml` loads top-level TOML keys as defaults for the options of
+// the same name (`camera = "serial:ABC123"` acts like `--camera serial:ABC123`).
+// Options given on the command line take precedence over the config file.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+#[derive(Debug, Clone)]
+pub struct Args {
+    program: String,
+    positional: Vec<String>,
+    options: Vec<(String, Option<String>)>,
+}
+
+impl Args {
+    /// Parses `std::env::args()`. `switches` lists the options that never take a value.
+    /// Exits with an error message if `--config` names an unreadable file.
+    pub fn from_env(switches: &[&str]) -> Self {
+        let mut args = Self::parse(env::args(), switches);
+        if let Some(path) = args.value("config").map(str::to_string) {
+            if let Err(e) = args.load_config(Path::new(&path)) {
+                eprintln!("Error: could not load config {}: {}", path, e);
+                std::process::exit(2);
+            }
+        }
+        args
+    }
+
+    /// Adds the top-level keys of a TOML file as option defaults. Arrays become
+    /// repeated options, `true` enables a switch and `false` leaves it off.
+    pub fn load_config(&mut self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let table: toml::Table = fs::read_to_string(path)?.parse()?;
+        let mut defaults = Vec::new();
+        for (key, value) in table {
+            let items = match value {
+                toml::Value::Array(items) => items,
+                value => vec![value],
+            };
+            for item in items {
+                match item {
+                    toml::Value::String(text) => defaults.push((key.clone(), Some(text))),
+                    toml::Value::Boolean(true) => defaults.push((key.clone(), None)),
+                    toml::Value::Boolean(false) => {}
+                    other => defaults.push((key.clone(), Some(other.to_string()))),
+                }
+            }
+        }
+        // Config entries go first so that command line options win ("last occurrence wins")
+        defaults.append(&mut self.options);
+        self.options = defaults;
+        Ok(())
+    }
+
+    pub fn parse<I: IntoIterator<Item = String>>(args: I, switches: &[&str]) -> Self {
+        let mut args = args.into_iter();
+        let program = args.next().unwrap_or_default();
+        let mut positional = Vec::new();
+        let mut options = Vec::new();
+
+        while let Some(arg) = args.next() {
+            match arg.strip_prefix("--") {
+                Some(option) => {
+                    if let Some((name, value)) = option.split_once('=') {
+                        options.push((name.to_string(), Some(value.to_string())));
+                    } else if switches.contains(&option) {
+                        options.push((option.to_string(), None));
+                    } else {
+                        options.push((option.to_string(), args.next()));
+                    }
+                }
+                None => positional.push(arg),
+            }
+        }
+
+        Self { program, positional, options }
+    }
+
+    pub fn program(&self) -> &str {
+        &self.program
+    }
+
+    /// Returns the positional argument at `index` if present and parseable.
+    pub fn positional<T: FromStr>(&self, index: usize) -> Option<T> {
+        self.positional.get(index).and_then(|v| v.parse().ok())
+    }
+
+    /// Returns the value of `--name value` (or `--name=value`), last occurrence wins.
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .rev()
+            .find(|(n, _)| n == name)
+            .and_then(|(_, v)| v.as_deref())
+    }
+
+    /// Returns the value of `--name` parsed as `T`, `None` if missing or unparseable.
+    pub fn value_as<T: FromStr>(&self, name: &str) -> Option<T> {
+        self.value(name).and_then(|v| v.parse().ok())
+    }
+
+    /// Returns the values of every occurrence of a repeatable option, in order.
+    pub fn values(&self, name: &str) -> Vec<&str> {
+        self.options
+            .iter()
+            .filter(|(n, _)| n == name)
+            .filter_map(|(_, v)| v.as_deref())
+            .collect()
+    }
+
+    pub fn flag(&self, name: &str) -> bool {
+        self.options.iter().any(|(n, _)| n == name)
+    }
+
+    /// Names of the options given, each once, in order of first occurrence.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = Vec::new();
+        for (name, _) in &self.options {
+            if !names.contains(&name.as_str()) {
+                names.push(name);
+            }
+        }
+        names
+    }
+}
@@ -0,0 +1,412 @@
+// Clock readings used to relate timestamps from different clock domains.
+//
+// Triggers carry CLOCK_REALTIME nanoseconds by default, while V4L2 stamps
+// buffers with CLOCK_MONOTONIC. Reading both clocks back to back gives the
+// offset needed to express a driver timestamp in the trigger clock domain.
+// Frames are matched on that timestamp (`--frame-timestamp driver`) unless
+// the driver left it unset or it went backwards; the dequeue time stands in
+// then (`FrameTimestamp`).
+//
+// A publisher can also stamp its triggers in another `ClockDomain`
+// (`--clock-domain`), e.g. CLOCK_MONOTONIC so that an NTP step mid-run does
+// not move them, or TAI/PTP time. The domain travels in the trigger header
+// (`TriggerSignature::clock_domain`), and consumers convert received triggers
+// to CLOCK_REALTIME, the domain they match frames in, once the signature is
+// checked (`trigger_to_realtime`). Conversions go through CLOCK_REALTIME: the
+// monotonic clock with a freshly sampled offset, TAI and PTP with the leap
+// second table (PTP hardware clocks count TAI since the PTP epoch, see
+// `leap`). Monotonic timestamps only mean something on the host that took
+// them.
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::leap::Tai;
+use crate::signing::TriggerSignature;
+use crate::units::Nanos;
+use crate::CameraTrigger;
+
+pub fn realtime_now_ns() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(unix)]
+pub fn monotonic_now_ns() -> u64 {
+    let mut ts = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    // SAFETY: `ts` is a valid timespec and CLOCK_MONOTONIC is always available
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+    ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64
+}
+
+#[cfg(not(unix))]
+pub fn monotonic_now_ns() -> u64 {
+    use std::sync::OnceLock;
+    use std::time::Instant;
+    static START: OnceLock<Instant> = OnceLock::new();
+    START.get_or_init(Instant::now).elapsed().as_nanos() as u64
+}
+
+/// `CLOCK_REALTIME - CLOCK_MONOTONIC` in nanoseconds, sampled now.
+pub fn realtime_minus_monotonic_ns() -> i64 {
+    let before = monotonic_now_ns();
+    let realtime = realtime_now_ns();
+    let after = monotonic_now_ns();
+    // Use the midpoint of the two monotonic reads to halve the sampling error
+    realtime as i64 - (before / 2 + after / 2) as i64
+}
+
+/// Converts a CLOCK_MONOTONIC timestamp into CLOCK_REALTIME using a sampled offset.
+pub fn monotonic_to_realtime_ns(monotonic_ns: u64, offset_ns: i64) -> u64 {
+    (monotonic_ns as i64 + offset_ns) as u64
+}
+
+/// First instant strictly after `now_ns` on the grid `offset_ns + k * period_ns`,
+/// with `k` counted from the clock epoch. Rigs sharing a clock and a period can
+/// interleave their triggers by choosing different offsets.
+pub fn next_grid_ns(now_ns: u64, period_ns: u64, offset_ns: i64) -> u64 {
+    let offset = offset_ns.rem_euclid(period_ns as i64) as u64;
+    let since_grid = (now_ns + period_ns - offset) % period_ns;
+    now_ns + period_ns - since_grid
+}
+
+/// Sleeps until CLOCK_REALTIME reaches `target_ns`. The absolute timer does not
+/// add up the errors of relative sleeps and follows clock adjustments (PTP
+/// slewing or steps) while waiting.
+#[cfg(target_os = "linux")]
+pub fn sleep_until_realtime_ns(target_ns: u64) {
+    let ts = libc::timespec {
+        tv_sec: (target_ns / 1_000_000_000) as libc::time_t,
+        tv_nsec: (target_ns % 1_000_000_000) as libc::c_long,
+    };
+    // SAFETY: `ts` is a valid timespec and the remaining time is not requested for absolute sleeps
+    while unsafe { libc::clock_nanosleep(libc::CLOCK_REALTIME, libc::TIMER_ABSTIME, &ts, std::ptr::null_mut()) } == libc::EINTR {}
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn sleep_until_realtime_ns(target_ns: u64) {
+    let now = realtime_now_ns();
+    if target_ns > now {
+        std::thread::sleep(std::time::Duration::from_nanos(target_ns - now));
+    }
+}
+
+/// Clock a trigger's timestamps count in. The discriminant is the value carried in the trigger header.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ClockDomain {
+    #[default]
+    Realtime = 0,
+    Monotonic = 1,
+    Tai = 2,
+    Ptp = 3,
+}
+
+impl ClockDomain {
+    /// Decodes the header value; unknown values (newer publishers) are `None`.
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Self::Realtime),
+            1 => Some(Self::Monotonic),
+            2 => Some(Self::Tai),
+            3 => Some(Self::Ptp),
+            _ => None,
+        }
+    }
+
+    /// Name of a header value, also for values unknown to this build.
+    pub fn label(value: u8) -> String {
+        Self::from_u8(value).map_or_else(|| format!("unknown-{}", value), |domain| domain.to_string())
+    }
+
+    /// The clock as named in logs, e.g. `CLOCK_MONOTONIC`.
+    pub fn describe(self) -> &'static str {
+        match self {
+            Self::Realtime => "CLOCK_REALTIME",
+            Self::Monotonic => "CLOCK_MONOTONIC",
+            Self::Tai => "TAI",
+            Self::Ptp => "PTP time (TAI)",
+        }
+    }
+
+    /// Timestamps of this domain are meaningless on other hosts.
+    pub fn is_host_local(self) -> bool {
+        self == Self::Monotonic
+    }
+}
+
+impl fmt::Display for ClockDomain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Realtime => "realtime",
+            Self::Monotonic => "monotonic",
+            Self::Tai => "tai",
+            Self::Ptp => "ptp",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for ClockDomain {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "realtime" => Ok(Self::Realtime),
+            "monotonic" => Ok(Self::Monotonic),
+            "tai" => Ok(Self::Tai),
+            "ptp" => Ok(Self::Ptp),
+            other => Err(format!("unknown clock domain '{}' (expected realtime, monotonic, tai or ptp)", other)),
+        }
+    }
+}
+
+/// Converts timestamps between clock domains with one sample of `CLOCK_REALTIME - CLOCK_MONOTONIC`.
+/// Sample a new one for every batch of timestamps, so that clock steps are followed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DomainConverter {
+    realtime_minus_monotonic_ns: i64,
+}
+
+impl DomainConverter {
+    /// With the offset of the clocks now.
+    pub fn sample() -> Self {
+        Self::with_offset(realtime_minus_monotonic_ns())
+    }
+
+    pub fn with_offset(realtime_minus_monotonic_ns: i64) -> Self {
+        Self { realtime_minus_monotonic_ns }
+    }
+
+    pub fn to_realtime_ns(&self, ns: u64, from: ClockDomain) -> u64 {
+        match from {
+            ClockDomain::Realtime => ns,
+            ClockDomain::Monotonic => monotonic_to_realtime_ns(ns, self.realtime_minus_monotonic_ns),
+            ClockDomain::Tai | ClockDomain::Ptp => Tai(ns).to_utc().0,
+        }
+    }
+
+    pub fn from_realtime_ns(&self, realtime_ns: u64, to: ClockDomain) -> u64 {
+        match to {
+            ClockDomain::Realtime => realtime_ns,
+            ClockDomain::Monotonic => (realtime_ns as i64 - self.realtime_minus_monotonic_ns).max(0) as u64,
+            ClockDomain::Tai | ClockDomain::Ptp => Tai::from_utc(Nanos(realtime_ns)).0,
+        }
+    }
+
+    pub fn convert_ns(&self, ns: u64, from: ClockDomain, to: ClockDomain) -> u64 {
+        if from == to {
+            return ns;
+        }
+        self.from_realtime_ns(self.to_realtime_ns(ns, from), to)
+    }
+
+    /// Both timestamps of a trigger; the id stays.
+    pub fn convert_trigger(&self, trigger: CameraTrigger, from: ClockDomain, to: ClockDomain) -> CameraTrigger {
+        CameraTrigger {
+            hw_timestamp_ns: self.convert_ns(trigger.hw_timestamp_ns, from, to),
+            publish_timestamp_ns: self.convert_ns(trigger.publish_timestamp_ns, from, to),
+            ..trigger
+        }
+    }
+}
+
+/// What captured frames are matched on (`--frame-timestamp`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FrameTimestamp {
+    /// The driver's buffer timestamp where the backend has one.
+    #[default]
+    Driver,
+    /// The time the capture loop dequeued the frame.
+    Dequeue,
+}
+
+impl FrameTimestamp {
+    /// The CLOCK_REALTIME time a frame dequeued at `dequeue_ns` is matched on. `driver_ns` is its
+    /// CLOCK_MONOTONIC buffer timestamp, converted with `realtime_minus_monotonic_ns`; it is not
+    /// used if unset (0) or not after the previous frame's `previous_driver_ns`.
+    pub fn match_ns(self, driver_ns: Option<u64>, previous_driver_ns: Option<u64>, dequeue_ns: u64, realtime_minus_monotonic_ns: i64) -> u64 {
+        match self {
+            Self::Driver => driver_to_realtime_ns(driver_ns, previous_driver_ns, realtime_minus_monotonic_ns).unwrap_or(dequeue_ns),
+            Self::Dequeue => dequeue_ns,
+        }
+    }
+}
+
+impl fmt::Display for FrameTimestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Driver => write!(f, "driver"),
+            Self::Dequeue => write!(f, "dequeue"),
+        }
+    }
+}
+
+impl FromStr for FrameTimestamp {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "driver" => Ok(Self::Driver),
+            "dequeue" => Ok(Self::Dequeue),
+            other => Err(format!("unknown frame timestamp '{}' (expected driver or dequeue)", other)),
+        }
+    }
+}
+
+/// A driver's CLOCK_MONOTONIC buffer timestamp `driver_ns` in CLOCK_REALTIME; `None` if the driver
+/// left it unset (0) or it is not after the previous frame's `previous_driver_ns`.
+pub fn driver_to_realtime_ns(driver_ns: Option<u64>, previous_driver_ns: Option<u64>, realtime_minus_monotonic_ns: i64) -> Option<u64> {
+    let driver_ns = driver_ns.filter(|&ns| ns != 0 && previous_driver_ns.is_none_or(|previous| ns > previous))?;
+    Some(monotonic_to_realtime_ns(driver_ns, realtime_minus_monotonic_ns))
+}
+
+/// A received trigger in CLOCK_REALTIME, with its header marked accordingly. Check the signature
+/// before: it covers the timestamps as published. Fails for a domain unknown to this build.
+pub fn trigger_to_realtime(trigger: CameraTrigger, header: TriggerSignature) -> Result<(CameraTrigger, TriggerSignature), String> {
+    let domain = ClockDomain::from_u8(header.clock_domain)
+        .ok_or_else(|| format!("trigger {} in clock domain {}, rebuild publisher and subscribers from the same release", trigger.frame_id, ClockDomain::label(header.clock_domain)))?;
+    if domain == ClockDomain::Realtime {
+        return Ok((trigger, header));
+    }
+    let converted = DomainConverter::sample().convert_trigger(trigger, domain, ClockDomain::Realtime);
+    Ok((
+        converted,
+        TriggerSignature {
+            clock_domain: ClockDomain::Realtime as u8,
+            ..header
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::TriggerHistory;
+    use crate::wire::TriggerMessage;
+
+    const NS_PER_SECOND: u64 = 1_000_000_000;
+
+    // 2023-11-14 22:13:20 UTC, TAI-UTC 37s
+    const REALTIME_NS: u64 = 1_700_000_000 * NS_PER_SECOND;
+
+    const PERIOD: u64 = 33_333_333;
+
+    #[test]
+    fn grid_instants_follow_the_phase_offset() {
+        assert_eq!(next_grid_ns(10 * PERIOD, PERIOD, 0), 11 * PERIOD);
+        assert_eq!(next_grid_ns(10 * PERIOD + 1, PERIOD, 0), 11 * PERIOD);
+        assert_eq!(next_grid_ns(10 * PERIOD, PERIOD, 1_000_000), 10 * PERIOD + 1_000_000);
+        assert_eq!(next_grid_ns(10 * PERIOD + 2_000_000, PERIOD, 1_000_000), 11 * PERIOD + 1_000_000);
+        // Offsets wrap around the period, negative ones included
+        assert_eq!(next_grid_ns(10 * PERIOD, PERIOD, PERIOD as i64 + 5), 10 * PERIOD + 5);
+        assert_eq!(next_grid_ns(10 * PERIOD, PERIOD, -5), 11 * PERIOD - 5);
+    }
+
+    #[test]
+    fn aligned_sleeps_wake_on_the_grid_point() {
+        let target = next_grid_ns(realtime_now_ns() + 5_000_000, 10_000_000, 0);
+        sleep_until_realtime_ns(target);
+        let woke = realtime_now_ns();
+        assert!(woke >= target && woke - target < 50_000_000, "woke {}ns after {}", woke as i64 - target as i64, target);
+        // A target in the past returns right away
+        sleep_until_realtime_ns(target - 1_000_000_000);
+    }
+
+    #[test]
+    fn frames_fall_back_to_the_dequeue_time_without_a_usable_driver_timestamp() {
+        const OFFSET: i64 = 1_700_000_000_000_000_000;
+        let dequeue_ns = OFFSET as u64 + 5_000_000;
+        assert_eq!(driver_to_realtime_ns(Some(2_000_000), Some(1_000_000), OFFSET), Some(OFFSET as u64 + 2_000_000));
+        assert_eq!(FrameTimestamp::Driver.match_ns(Some(2_000_000), None, dequeue_ns, OFFSET), OFFSET as u64 + 2_000_000);
+
+        // Unset, repeated or going backwards
+        for (driver_ns, previous_ns) in [(None, None), (Some(0), None), (Some(2_000_000), Some(2_000_000)), (Some(1_000_000), Some(2_000_000))] {
+            assert_eq!(driver_to_realtime_ns(driver_ns, previous_ns, OFFSET), None);
+            assert_eq!(FrameTimestamp::Driver.match_ns(driver_ns, previous_ns, dequeue_ns, OFFSET), dequeue_ns);
+        }
+        assert_eq!(FrameTimestamp::Dequeue.match_ns(Some(2_000_000), None, dequeue_ns, OFFSET), dequeue_ns);
+
+        // `--frame-timestamp` takes the names, the driver time unless told otherwise
+        assert_eq!(FrameTimestamp::default(), FrameTimestamp::Driver);
+        assert_eq!("dequeue".parse(), Ok(FrameTimestamp::Dequeue));
+        assert!("pts".parse::<FrameTimestamp>().unwrap_err().contains("'pts'"));
+    }
+
+    #[test]
+    fn triggers_convert_between_clock_domains() {
+        for domain in [ClockDomain::Realtime, ClockDomain::Monotonic, ClockDomain::Tai, ClockDomain::Ptp] {
+            assert_eq!(domain.to_string().parse::<ClockDomain>(), Ok(domain));
+            assert_eq!(ClockDomain::from_u8(domain as u8), Some(domain));
+        }
+        assert!("boottime".parse::<ClockDomain>().is_err());
+        assert_eq!(ClockDomain::label(9), "unknown-9");
+
+        // Monotonic 5000s after boot is REALTIME_NS
+        let converter = DomainConverter::with_offset((REALTIME_NS - 5_000 * NS_PER_SECOND) as i64);
+        let monotonic = CameraTrigger::new(7, 5_000 * NS_PER_SECOND, 5_000 * NS_PER_SECOND + 40_000);
+        let realtime = converter.convert_trigger(monotonic, ClockDomain::Monotonic, ClockDomain::Realtime);
+        assert_eq!(realtime, CameraTrigger::new(7, REALTIME_NS, REALTIME_NS + 40_000));
+        let tai = converter.convert_trigger(monotonic, ClockDomain::Monotonic, ClockDomain::Tai);
+        assert_eq!(tai, CameraTrigger::new(7, REALTIME_NS + 37 * NS_PER_SECOND, REALTIME_NS + 37 * NS_PER_SECOND + 40_000));
+        assert_eq!(converter.convert_trigger(tai, ClockDomain::Ptp, ClockDomain::Monotonic), monotonic);
+
+        // Received triggers are matched in realtime, with the header saying so
+        let header = TriggerSignature {
+            clock_domain: ClockDomain::Tai as u8,
+            trace_origin: 5,
+            ..TriggerSignature::default()
+        };
+        let (trigger, converted) = trigger_to_realtime(tai, header).unwrap();
+        assert_eq!(trigger, realtime);
+        assert_eq!((converted.clock_domain, converted.trace_origin), (ClockDomain::Realtime as u8, 5));
+        assert_eq!(trigger_to_realtime(realtime, converted).unwrap().0, realtime);
+        let newer = TriggerSignature {
+            clock_domain: 9,
+            ..TriggerSignature::default()
+        };
+        assert!(trigger_to_realtime(realtime, newer).is_err());
+    }
+
+    #[test]
+    fn clock_domain_survives_the_wire() {
+        let message = TriggerMessage {
+            trigger: CameraTrigger::new(7, REALTIME_NS, REALTIME_NS + 40_000),
+            signature: TriggerSignature {
+                clock_domain: ClockDomain::Ptp as u8,
+                ..TriggerSignature::default()
+            },
+            link_tx: None,
+        };
+        let decoded = TriggerMessage::decode(&message.encode()).unwrap();
+        assert_eq!(decoded.signature.clock_domain, ClockDomain::Ptp as u8);
+
+        // Realtime triggers encode as before
+        let realtime = TriggerMessage {
+            signature: TriggerSignature::default(),
+            ..message
+        };
+        assert_eq!(realtime.encode().len() + 2, message.encode().len());
+        assert_eq!(TriggerMessage::decode(&realtime.encode()).unwrap().signature.clock_domain, 0);
+    }
+
+    #[test]
+    fn history_queries_stay_in_realtime() {
+        let header = TriggerSignature {
+            clock_domain: ClockDomain::Monotonic as u8,
+            ..TriggerSignature::default()
+        };
+        let now_monotonic = monotonic_now_ns();
+        let mut history = TriggerHistory::new(10);
+        for (trigger_id, age_s) in [(1, 3), (2, 2), (3, 0)] {
+            let hw_ts = now_monotonic - age_s * NS_PER_SECOND;
+            history.push(CameraTrigger::new(trigger_id, hw_ts, hw_ts + 40_000), header);
+        }
+        let since = Nanos(realtime_now_ns() - 2 * NS_PER_SECOND - NS_PER_SECOND / 2);
+        let found: Vec<u64> = history.since(since).map(|entry| entry.trigger.frame_id).collect();
+        assert_eq!(found, [2, 3]);
+    }
+}
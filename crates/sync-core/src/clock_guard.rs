@@ -0,0 +1,267 @@
+// Sanity guards for the clocks a process relies on.
+//
+// Latencies and the matcher's drift model are differences of timestamps from
+// different clocks. When one of them steps (an NTP step, a PTP servo that
+// gives up slewing, a trigger source that restarts), the differences turn
+// into garbage: negative latencies, offsets of seconds, a drift model that
+// learns the step. A `ClockGuard` watches one clock against a reference that
+// must advance with it, e.g. CLOCK_REALTIME against CLOCK_MONOTONIC, or the
+// trigger hardware timestamps against their publish times. A reading that
+// goes back in time or whose offset from the reference moves by more than
+// `--clock-max-step-ms` is a clock event. The clock stays disturbed until
+// `--clock-settle-ms` passed on the reference without a further event; then
+// the guard reports the end of the disturbance. While a clock is disturbed,
+// the consumers freeze the matcher's learning (see `pipeline`), keep the
+// latency samples out of their statistics and mark the recorded frames.
+
+use std::fmt;
+
+use crate::cli::Args;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockGuardConfig {
+    /// Largest change of the offset from the reference that is not a step.
+    pub max_step_ns: u64,
+    /// Reference time without events that ends a disturbance.
+    pub settle_ns: u64,
+}
+
+impl Default for ClockGuardConfig {
+    fn default() -> Self {
+        Self {
+            max_step_ns: 20_000_000,
+            settle_ns: 2_000_000_000,
+        }
+    }
+}
+
+impl ClockGuardConfig {
+    /// `--clock-max-step-ms` and `--clock-settle-ms`.
+    pub fn from_args(args: &Args) -> Self {
+        let defaults = Self::default();
+        Self {
+            max_step_ns: args.value_as::<f64>("clock-max-step-ms").map_or(defaults.max_step_ns, |ms| (ms * 1e6) as u64),
+            settle_ns: args.value_as::<f64>("clock-settle-ms").map_or(defaults.settle_ns, |ms| (ms * 1e6) as u64),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockEventKind {
+    /// A reading before the previous one, or the offset moved back by more than the allowed step.
+    SteppedBack,
+    /// The offset moved forward by more than the allowed step.
+    JumpedForward,
+    /// The clock was quiet for the settle time after its last step.
+    Settled,
+}
+
+impl ClockEventKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ClockEventKind::SteppedBack => "stepped_back",
+            ClockEventKind::JumpedForward => "jumped_forward",
+            ClockEventKind::Settled => "settled",
+        }
+    }
+}
+
+impl fmt::Display for ClockEventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClockEvent {
+    pub clock: &'static str,
+    pub kind: ClockEventKind,
+    /// Change of the offset from the reference (steps), 0 when settled.
+    pub step_ns: i64,
+    /// Reading of the clock that triggered the event.
+    pub clock_ns: u64,
+    /// Since the disturbance began (settled), 0 for steps.
+    pub disturbed_ns: u64,
+    /// Steps of this clock so far, including this one.
+    pub steps: u64,
+}
+
+impl ClockEvent {
+    pub fn is_step(&self) -> bool {
+        self.kind != ClockEventKind::Settled
+    }
+}
+
+impl fmt::Display for ClockEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            ClockEventKind::SteppedBack => write!(f, "CLOCK EVENT: {} clock stepped back by {:.3}ms", self.clock, self.step_ns.unsigned_abs() as f64 / 1e6)?,
+            ClockEventKind::JumpedForward => write!(f, "CLOCK EVENT: {} clock jumped forward by {:.3}ms", self.clock, self.step_ns as f64 / 1e6)?,
+            ClockEventKind::Settled => return write!(f, "CLOCK EVENT: {} clock settled after {:.3}s of disturbance", self.clock, self.disturbed_ns as f64 / 1e9),
+        }
+        write!(f, " (step {})", self.steps)
+    }
+}
+
+/// Watches one clock against a reference that advances with it.
+#[derive(Debug, Clone)]
+pub struct ClockGuard {
+    clock: &'static str,
+    config: ClockGuardConfig,
+    // (clock reading, clock minus reference) of the previous observation
+    last: Option<(u64, i64)>,
+    // (reference time of the first step, reference time of the last step) of the current disturbance
+    disturbance: Option<(u64, u64)>,
+    steps: u64,
+}
+
+impl ClockGuard {
+    pub fn new(clock: &'static str, config: ClockGuardConfig) -> Self {
+        Self {
+            clock,
+            config,
+            last: None,
+            disturbance: None,
+            steps: 0,
+        }
+    }
+
+    /// Observes a reading of the clock and the reference read at the same instant; returns a
+    /// step or the end of a disturbance. After a step, the new offset is the one followed.
+    pub fn observe(&mut self, clock_ns: u64, reference_ns: u64) -> Option<ClockEvent> {
+        let offset_ns = clock_ns as i64 - reference_ns as i64;
+        let last = self.last.replace((clock_ns, offset_ns));
+        let step_ns = last.map_or(0, |(_, last_offset_ns)| offset_ns - last_offset_ns);
+        let kind = match last {
+            Some((last_clock_ns, _)) if clock_ns < last_clock_ns || step_ns < -(self.config.max_step_ns as i64) => ClockEventKind::SteppedBack,
+            Some(_) if step_ns > self.config.max_step_ns as i64 => ClockEventKind::JumpedForward,
+            _ => {
+                let (since_ns, last_step_ns) = self.disturbance?;
+                if reference_ns < last_step_ns + self.config.settle_ns {
+                    return None;
+                }
+                self.disturbance = None;
+                return Some(ClockEvent {
+                    clock: self.clock,
+                    kind: ClockEventKind::Settled,
+                    step_ns: 0,
+                    clock_ns,
+                    disturbed_ns: reference_ns.saturating_sub(since_ns),
+                    steps: self.steps,
+                });
+            }
+        };
+        self.steps += 1;
+        let since_ns = self.disturbance.map_or(reference_ns, |(since_ns, _)| since_ns);
+        self.disturbance = Some((since_ns, reference_ns));
+        Some(ClockEvent {
+            clock: self.clock,
+            kind,
+            step_ns,
+            clock_ns,
+            disturbed_ns: 0,
+            steps: self.steps,
+        })
+    }
+
+    /// Between a step and the end of its settle time.
+    pub fn is_disturbed(&self) -> bool {
+        self.disturbance.is_some()
+    }
+
+    pub fn clock(&self) -> &'static str {
+        self.clock
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::burst::BurstPattern;
+    use crate::matching::DEFAULT_TOLERANCE_MS;
+    use crate::pipeline::{MatchPipeline, Stage, DRIFT_WINDOW};
+    use crate::CameraTrigger;
+
+    const START_NS: u64 = 1_700_000_000_000_000_000;
+    const MS: u64 = 1_000_000;
+
+    #[test]
+    fn reports_steps_and_the_end_of_the_disturbance() {
+        let config = ClockGuardConfig::default();
+        let mut guard = ClockGuard::new("realtime", config);
+        // Slewing by 50us per 30ms is no step
+        let mut reference_ns = 5_000 * MS;
+        let mut offset_ns = START_NS - reference_ns;
+        for _ in 0..10 {
+            assert_eq!(guard.observe(reference_ns + offset_ns, reference_ns), None);
+            reference_ns += 30 * MS;
+            offset_ns += 50_000;
+        }
+        assert!(!guard.is_disturbed());
+
+        // An NTP step back by 1.5s
+        offset_ns -= 1_500 * MS;
+        let event = guard.observe(reference_ns + offset_ns, reference_ns).unwrap();
+        assert_eq!(event.kind, ClockEventKind::SteppedBack);
+        assert_eq!(event.step_ns, -1_500 * MS as i64 + 50_000);
+        assert_eq!(event.to_string(), "CLOCK EVENT: realtime clock stepped back by 1499.950ms (step 1)");
+        assert!(guard.is_disturbed());
+
+        // The new offset is followed; a second step extends the disturbance
+        reference_ns += 30 * MS;
+        assert_eq!(guard.observe(reference_ns + offset_ns, reference_ns), None);
+        reference_ns += 30 * MS;
+        offset_ns += 40 * MS;
+        let event = guard.observe(reference_ns + offset_ns, reference_ns).unwrap();
+        assert_eq!((event.kind, event.step_ns, event.steps), (ClockEventKind::JumpedForward, 40 * MS as i64, 2));
+        let last_step_ns = reference_ns;
+
+        reference_ns = last_step_ns + config.settle_ns - 1;
+        assert_eq!(guard.observe(reference_ns + offset_ns, reference_ns), None);
+        assert!(guard.is_disturbed());
+        reference_ns = last_step_ns + config.settle_ns;
+        let event = guard.observe(reference_ns + offset_ns, reference_ns).unwrap();
+        assert_eq!(event.kind, ClockEventKind::Settled);
+        assert_eq!(event.disturbed_ns, config.settle_ns + 60 * MS);
+        assert!(!event.is_step());
+        assert!(!guard.is_disturbed());
+
+        // A reading before the previous one is a step back, however small
+        let clock_ns = reference_ns + offset_ns;
+        assert_eq!(guard.observe(clock_ns - 1, reference_ns).unwrap().kind, ClockEventKind::SteppedBack);
+    }
+
+    #[test]
+    fn frozen_matcher_keeps_its_learned_tolerance() {
+        let mut pipeline: MatchPipeline<()> = MatchPipeline::with_stages(&[Stage::Drift, Stage::Associate], DEFAULT_TOLERANCE_MS, BurstPattern::new(1));
+        pipeline.set_adaptive_tolerance(true);
+        let capture = |pipeline: &mut MatchPipeline<()>, index: u64, latency_ns: u64| {
+            let hw_ts = START_NS + index * 1_000 * MS;
+            pipeline.on_trigger(CameraTrigger::new(index, hw_ts, hw_ts), ());
+            pipeline.on_frame(hw_ts + latency_ns).matched.is_some()
+        };
+        for index in 0..DRIFT_WINDOW as u64 {
+            assert!(capture(&mut pipeline, index, 110 * MS));
+        }
+        let learned_ms = pipeline.tolerance_ms();
+        let snapshot = pipeline.snapshot();
+
+        // Frames across a clock step still match within the learned tolerance, but are not learned from
+        pipeline.set_learning_frozen(true);
+        let mut index = DRIFT_WINDOW as u64;
+        for _ in 0..DRIFT_WINDOW {
+            capture(&mut pipeline, index, 111 * MS);
+            index += 1;
+        }
+        assert_eq!(pipeline.tolerance_ms(), learned_ms);
+        assert_eq!(pipeline.snapshot(), snapshot);
+
+        pipeline.set_learning_frozen(false);
+        assert!(capture(&mut pipeline, index, 111 * MS));
+        assert_ne!(pipeline.snapshot(), snapshot);
+    }
+}
@@ -0,0 +1,458 @@
+// Quality of the clock the trigger timestamps come from, and what happens
+// while it is downgraded.
+//
+// A trigger clock disciplined by GNSS or PTP keeps running when the lock is
+// lost, but drifts away from the other hosts' clocks: matching goes on with
+// timestamps nobody can vouch for. `publisher --clock-quality` watches the
+// clock's synchronization state, read from one of:
+//
+//   kernel        the kernel's NTP state (adjtimex): unsynchronized while
+//                 STA_UNSYNC is set, degraded while the maximum error exceeds
+//                 `--clock-max-error-us` (default 1000). Kept by chrony, ntpd
+//                 and phc2sys -w.
+//   file:<path>   a status file written by a GNSS or PTP monitor (a gpsd hook,
+//                 a pmc script): `synchronized`/`locked`, `degraded`/`holdover`
+//                 or `unsynchronized`. A missing file is unsynchronized.
+//
+// A clock that is not synchronized is downgraded at once; it recovers after
+// it was synchronized for `--clock-settle-ms`. While it is downgraded, the
+// publisher applies `--on-clock-downgrade`:
+//
+//   flag    consumers keep matching and flag the matched frames (default)
+//   widen   consumers also widen their match tolerance by `WIDEN_FACTOR`
+//   pause   consumers stop matching until the clock recovers
+//
+// The active policy travels with every trigger in the user header
+// (`clock_policy`, 0 while the clock is synchronized), so every consumer
+// reacts on the same trigger. Consumers freeze the matcher's learning under
+// every policy: a drifting clock must not teach it a latency.
+
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::cli::Args;
+use crate::clock_guard::ClockGuardConfig;
+
+/// Factor on the consumers' match tolerance under the `widen` policy.
+pub const WIDEN_FACTOR: f64 = 2.0;
+
+/// Readings of the clock quality are taken at most this often.
+pub const POLL_INTERVAL_NS: u64 = 100_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockQuality {
+    Synchronized,
+    /// Still steered, but with a larger error than allowed (e.g. GNSS holdover).
+    Degraded,
+    Unsynchronized,
+}
+
+impl fmt::Display for ClockQuality {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Synchronized => "synchronized",
+            Self::Degraded => "degraded",
+            Self::Unsynchronized => "unsynchronized",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for ClockQuality {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "synchronized" | "locked" => Ok(Self::Synchronized),
+            "degraded" | "holdover" => Ok(Self::Degraded),
+            "unsynchronized" => Ok(Self::Unsynchronized),
+            other => Err(format!("unknown clock quality '{}' (expected synchronized, degraded or unsynchronized)", other)),
+        }
+    }
+}
+
+/// What consumers do while the trigger clock is downgraded. The discriminant is the value carried
+/// in the trigger header, where 0 means synchronized.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DowngradePolicy {
+    #[default]
+    Flag = 1,
+    Widen = 2,
+    Pause = 3,
+}
+
+impl DowngradePolicy {
+    /// Decodes the header value: `None` while synchronized. Policies unknown to this build (newer
+    /// publishers) are followed as `Flag`.
+    pub fn from_header(value: u8) -> Option<Self> {
+        match value {
+            0 => None,
+            2 => Some(Self::Widen),
+            3 => Some(Self::Pause),
+            _ => Some(Self::Flag),
+        }
+    }
+
+    /// The header value of an active policy, if any.
+    pub fn to_header(policy: Option<Self>) -> u8 {
+        policy.map_or(0, |policy| policy as u8)
+    }
+
+    /// What the consumers do, as named in logs.
+    pub fn describe(self) -> &'static str {
+        match self {
+            Self::Flag => "matched frames are flagged",
+            Self::Widen => "the match tolerance is widened",
+            Self::Pause => "matching is paused",
+        }
+    }
+}
+
+impl fmt::Display for DowngradePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Flag => "flag",
+            Self::Widen => "widen",
+            Self::Pause => "pause",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for DowngradePolicy {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "flag" => Ok(Self::Flag),
+            "widen" => Ok(Self::Widen),
+            "pause" => Ok(Self::Pause),
+            other => Err(format!("unknown clock downgrade policy '{}' (expected flag, widen or pause)", other)),
+        }
+    }
+}
+
+/// Where the clock quality is read from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QualitySource {
+    /// The kernel's NTP state of CLOCK_REALTIME.
+    Kernel,
+    /// A status file kept by an external monitor.
+    File(PathBuf),
+}
+
+impl fmt::Display for QualitySource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Kernel => write!(f, "kernel"),
+            Self::File(path) => write!(f, "file:{}", path.display()),
+        }
+    }
+}
+
+impl FromStr for QualitySource {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text.split_once(':') {
+            None if text == "kernel" => Ok(Self::Kernel),
+            Some(("file", path)) if !path.is_empty() => Ok(Self::File(PathBuf::from(path))),
+            _ => Err(format!("unknown clock quality source '{}' (expected kernel or file:<path>)", text)),
+        }
+    }
+}
+
+/// One reading of the clock quality, with what it was derived from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QualityReading {
+    pub quality: ClockQuality,
+    /// E.g. `maximum error 12us`.
+    pub detail: String,
+}
+
+impl QualitySource {
+    /// Reads the quality now; a source that cannot be read is unsynchronized.
+    pub fn read(&self, max_error_us: u64) -> QualityReading {
+        match self {
+            Self::Kernel => read_kernel(max_error_us),
+            Self::File(path) => match fs::read_to_string(path) {
+                Ok(text) => match text.trim().parse::<ClockQuality>() {
+                    Ok(quality) => QualityReading {
+                        quality,
+                        detail: format!("{} says {}", path.display(), text.trim()),
+                    },
+                    Err(e) => QualityReading {
+                        quality: ClockQuality::Unsynchronized,
+                        detail: format!("{}: {}", path.display(), e),
+                    },
+                },
+                Err(e) => QualityReading {
+                    quality: ClockQuality::Unsynchronized,
+                    detail: format!("could not read {}: {}", path.display(), e),
+                },
+            },
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn read_kernel(max_error_us: u64) -> QualityReading {
+    // SAFETY: `timex` is plain integers, all zero is a valid value, and modes 0 only reads
+    let mut timex: libc::timex = unsafe { std::mem::zeroed() };
+    let state = unsafe { libc::adjtimex(&mut timex) };
+    if state < 0 {
+        return QualityReading {
+            quality: ClockQuality::Unsynchronized,
+            detail: format!("adjtimex failed: {}", std::io::Error::last_os_error()),
+        };
+    }
+    let max_error = timex.maxerror.max(0) as u64;
+    let quality = if state == libc::TIME_ERROR || timex.status & libc::STA_UNSYNC != 0 {
+        ClockQuality::Unsynchronized
+    } else if max_error > max_error_us {
+        ClockQuality::Degraded
+    } else {
+        ClockQuality::Synchronized
+    };
+    QualityReading {
+        quality,
+        detail: format!("kernel maximum error {}us, estimated {}us", max_error, timex.esterror.max(0)),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn read_kernel(_max_error_us: u64) -> QualityReading {
+    QualityReading {
+        quality: ClockQuality::Unsynchronized,
+        detail: "no kernel clock state on this platform".to_string(),
+    }
+}
+
+/// A change of the clock quality or of the active policy.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QualityChange {
+    pub quality: ClockQuality,
+    pub detail: String,
+    /// The policy consumers apply from now on, `None` once synchronized.
+    pub active: Option<DowngradePolicy>,
+    /// Since the downgrade began, on recovery; 0 otherwise.
+    pub downgraded_ns: u64,
+}
+
+impl QualityChange {
+    pub fn is_downgrade(&self) -> bool {
+        self.active.is_some()
+    }
+}
+
+impl fmt::Display for QualityChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.active {
+            Some(policy) => write!(f, "CLOCK QUALITY: trigger clock {} ({}), policy {}: {}", self.quality, self.detail, policy, policy.describe()),
+            None if self.downgraded_ns > 0 => write!(f, "CLOCK QUALITY: trigger clock synchronized again after {:.3}s ({})", self.downgraded_ns as f64 / 1e9, self.detail),
+            None => write!(f, "CLOCK QUALITY: trigger clock {} ({})", self.quality, self.detail),
+        }
+    }
+}
+
+/// Reads the trigger clock's quality and decides the policy published with the triggers.
+#[derive(Debug, Clone)]
+pub struct ClockQualityMonitor {
+    source: QualitySource,
+    policy: DowngradePolicy,
+    max_error_us: u64,
+    settle_ns: u64,
+    // Quality of the last change reported
+    reported: Option<ClockQuality>,
+    downgraded_since_ns: Option<u64>,
+    synchronized_since_ns: Option<u64>,
+    last_poll_ns: Option<u64>,
+}
+
+impl ClockQualityMonitor {
+    /// Synchronized readings for `settle_ns` end a downgrade.
+    pub fn new(source: QualitySource, policy: DowngradePolicy, max_error_us: u64, settle_ns: u64) -> Self {
+        Self {
+            source,
+            policy,
+            max_error_us,
+            settle_ns,
+            reported: None,
+            downgraded_since_ns: None,
+            synchronized_since_ns: None,
+            last_poll_ns: None,
+        }
+    }
+
+    /// `--clock-quality`, `--on-clock-downgrade`, `--clock-max-error-us` and `--clock-settle-ms`;
+    /// `None` without `--clock-quality`.
+    pub fn from_args(args: &Args) -> Result<Option<Self>, String> {
+        let Some(source) = args.value("clock-quality") else {
+            return Ok(None);
+        };
+        let policy = args.value("on-clock-downgrade").map_or(Ok(DowngradePolicy::default()), str::parse)?;
+        let max_error_us = args.value_as::<u64>("clock-max-error-us").unwrap_or(1000);
+        Ok(Some(Self::new(source.parse()?, policy, max_error_us, ClockGuardConfig::from_args(args).settle_ns)))
+    }
+
+    pub fn source(&self) -> &QualitySource {
+        &self.source
+    }
+
+    pub fn policy(&self) -> DowngradePolicy {
+        self.policy
+    }
+
+    /// The policy consumers apply now, `None` while synchronized.
+    pub fn active_policy(&self) -> Option<DowngradePolicy> {
+        self.downgraded_since_ns.map(|_| self.policy)
+    }
+
+    /// Reads the source, at most every `POLL_INTERVAL_NS` of `now_ns` (CLOCK_MONOTONIC).
+    pub fn poll(&mut self, now_ns: u64) -> Option<QualityChange> {
+        if self.last_poll_ns.is_some_and(|last_ns| now_ns < last_ns + POLL_INTERVAL_NS) {
+            return None;
+        }
+        self.last_poll_ns = Some(now_ns);
+        let reading = self.source.read(self.max_error_us);
+        self.observe(reading, now_ns)
+    }
+
+    /// Follows a reading taken at `now_ns`; returns the first reading, a downgrade, a change of
+    /// quality while downgraded, and the recovery.
+    pub fn observe(&mut self, reading: QualityReading, now_ns: u64) -> Option<QualityChange> {
+        let QualityReading { quality, detail } = reading;
+        if quality != ClockQuality::Synchronized {
+            self.synchronized_since_ns = None;
+            self.downgraded_since_ns.get_or_insert(now_ns);
+            if self.reported == Some(quality) {
+                return None;
+            }
+        } else if let Some(since_ns) = self.downgraded_since_ns {
+            let synchronized_since_ns = *self.synchronized_since_ns.get_or_insert(now_ns);
+            if now_ns < synchronized_since_ns + self.settle_ns {
+                return None;
+            }
+            self.downgraded_since_ns = None;
+            self.synchronized_since_ns = None;
+            self.reported = Some(quality);
+            return Some(QualityChange {
+                quality,
+                detail,
+                active: None,
+                downgraded_ns: now_ns - since_ns,
+            });
+        } else if self.reported.is_some() {
+            return None;
+        }
+        self.reported = Some(quality);
+        Some(QualityChange {
+            quality,
+            detail,
+            active: self.active_policy(),
+            downgraded_ns: 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::burst::BurstPattern;
+    use crate::pipeline::MatchPipeline;
+    use crate::signing::TriggerSignature;
+    use crate::wire::TriggerMessage;
+    use crate::CameraTrigger;
+
+    const MS: u64 = 1_000_000;
+
+    fn reading(quality: ClockQuality) -> QualityReading {
+        QualityReading {
+            quality,
+            detail: "test".to_string(),
+        }
+    }
+
+    #[test]
+    fn downgrades_last_until_the_clock_settles() {
+        let mut monitor = ClockQualityMonitor::new(QualitySource::Kernel, DowngradePolicy::Widen, 1000, 2_000 * MS);
+
+        // The first reading is reported, later synchronized ones are not
+        let first = monitor.observe(reading(ClockQuality::Synchronized), 0).unwrap();
+        assert_eq!((first.quality, first.active), (ClockQuality::Synchronized, None));
+        assert_eq!(monitor.observe(reading(ClockQuality::Synchronized), 100 * MS), None);
+
+        // Lock lost: the policy applies at once, a worse quality is reported again
+        let downgrade = monitor.observe(reading(ClockQuality::Degraded), 1_000 * MS).unwrap();
+        assert_eq!((downgrade.quality, downgrade.active), (ClockQuality::Degraded, Some(DowngradePolicy::Widen)));
+        assert_eq!(DowngradePolicy::to_header(monitor.active_policy()), 2);
+        assert_eq!(monitor.observe(reading(ClockQuality::Degraded), 1_100 * MS), None);
+        assert_eq!(monitor.observe(reading(ClockQuality::Unsynchronized), 1_200 * MS).unwrap().quality, ClockQuality::Unsynchronized);
+
+        // A short lock does not end the downgrade, a settled one does
+        assert_eq!(monitor.observe(reading(ClockQuality::Synchronized), 2_000 * MS), None);
+        assert_eq!(monitor.observe(reading(ClockQuality::Degraded), 3_000 * MS).unwrap().quality, ClockQuality::Degraded);
+        assert_eq!(monitor.observe(reading(ClockQuality::Synchronized), 4_000 * MS), None);
+        assert_eq!(monitor.observe(reading(ClockQuality::Synchronized), 5_900 * MS), None);
+        let recovery = monitor.observe(reading(ClockQuality::Synchronized), 6_000 * MS).unwrap();
+        assert_eq!((recovery.active, recovery.downgraded_ns), (None, 5_000 * MS));
+        assert_eq!(DowngradePolicy::to_header(monitor.active_policy()), 0);
+    }
+
+    #[test]
+    fn quality_sources_and_policies_parse() {
+        let dir = std::env::temp_dir().join(format!("camera_sync_clock_quality_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("gnss");
+        let source: QualitySource = format!("file:{}", path.display()).parse().unwrap();
+        assert_eq!(source.read(1000).quality, ClockQuality::Unsynchronized);
+        fs::write(&path, "holdover\n").unwrap();
+        assert_eq!(source.read(1000).quality, ClockQuality::Degraded);
+        fs::write(&path, "locked").unwrap();
+        assert_eq!(source.read(1000).quality, ClockQuality::Synchronized);
+        fs::write(&path, "fix 3d").unwrap();
+        assert_eq!(source.read(1000).quality, ClockQuality::Unsynchronized);
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!("kernel".parse::<QualitySource>(), Ok(QualitySource::Kernel));
+        assert!("file:".parse::<QualitySource>().is_err());
+        assert!("ntp".parse::<QualitySource>().is_err());
+        for policy in [DowngradePolicy::Flag, DowngradePolicy::Widen, DowngradePolicy::Pause] {
+            assert_eq!(policy.to_string().parse::<DowngradePolicy>(), Ok(policy));
+            assert_eq!(DowngradePolicy::from_header(policy as u8), Some(policy));
+        }
+        assert!("stop".parse::<DowngradePolicy>().is_err());
+        assert_eq!(DowngradePolicy::from_header(0), None);
+        // Policies of newer publishers are followed as flag
+        assert_eq!(DowngradePolicy::from_header(9), Some(DowngradePolicy::Flag));
+    }
+
+    #[test]
+    fn policy_reaches_the_matcher() {
+        let message = TriggerMessage {
+            trigger: CameraTrigger::new(7, 1_000 * MS, 1_000 * MS + 40_000),
+            signature: TriggerSignature {
+                clock_policy: DowngradePolicy::Pause as u8,
+                ..TriggerSignature::default()
+            },
+            link_tx: None,
+        };
+        assert_eq!(TriggerMessage::decode(&message.encode()).unwrap().signature.clock_policy, DowngradePolicy::Pause as u8);
+        let synchronized = TriggerMessage {
+            signature: TriggerSignature::default(),
+            ..message
+        };
+        assert_eq!(synchronized.encode().len() + 2, message.encode().len());
+
+        // 15ms from the frame: outside the 10ms tolerance, inside the widened one
+        let mut matcher = MatchPipeline::new(10.0, BurstPattern::new(1));
+        matcher.on_trigger(message.trigger, ());
+        assert!(matcher.on_frame(1_015 * MS).matched.is_none());
+        matcher.set_tolerance_factor(WIDEN_FACTOR);
+        assert_eq!(matcher.tolerance_ms(), 20.0);
+        assert_eq!(matcher.on_frame(1_015 * MS + 1_000).matched.map(|(trigger, _, _)| trigger.frame_id), Some(7));
+    }
+}
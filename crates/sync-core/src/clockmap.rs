@@ -0,0 +1,41 @@
+// Clock mapping of a recording host to the trigger publisher's clock.
+//
+// Every trigger carries the publisher's CLOCK_REALTIME at publish time. The
+// local receive time minus that is the transport delay plus the offset of the
+// local clock. Over a window of triggers, the least delayed one bounds the
+// offset best (a minimum filter, as in NTP); the median minus the minimum of
+// the window is kept as its uncertainty. On the publisher's own host the
+// offset is 0 plus the IPC delay; across a bridge it is the clock offset
+// between the hosts plus the network delay.
+
+use crate::clock;
+use crate::session::ClockMapping;
+
+/// Triggers per mapping, about 3 s at 30 Hz.
+pub const MAPPING_WINDOW: usize = 100;
+
+#[derive(Debug, Clone, Default)]
+pub struct ClockMapper {
+    /// Received minus published, ns.
+    window: Vec<i64>,
+}
+
+impl ClockMapper {
+    /// Adds a received trigger; returns a new mapping once the window is full.
+    pub fn on_trigger(&mut self, publish_timestamp_ns: u64, received_ns: u64) -> Option<ClockMapping> {
+        self.window.push(received_ns as i64 - publish_timestamp_ns as i64);
+        if self.window.len() < MAPPING_WINDOW {
+            return None;
+        }
+        self.window.sort_unstable();
+        let offset_ns = self.window[0];
+        let median_ns = self.window[self.window.len() / 2];
+        self.window.clear();
+        Some(ClockMapping {
+            realtime_ns: received_ns,
+            monotonic_ns: clock::monotonic_now_ns(),
+            offset_ns,
+            uncertainty_ns: (median_ns - offset_ns) as u64,
+        })
+    }
+}
@@ -0,0 +1,176 @@
+// Soft deadline monitoring of the capture loop and matcher iterations.
+//
+// Sporadic stalls (page faults, preemption by other tasks, SMIs) rarely show
+// in averages. Each monitored section gets a budget; a run over budget is a
+// missed deadline, reported with its duration and counted, so stalls become
+// visible and comparable across runs. Sections are timed with the CPU cycle
+// counter (TSC on x86_64, calibrated against CLOCK_MONOTONIC once), elsewhere
+// with the monotonic clock.
+
+use std::sync::OnceLock;
+
+use crate::clock;
+use crate::units::{DisplayMs, Unit};
+
+/// Raw cycle counter reading.
+#[cfg(target_arch = "x86_64")]
+pub fn cycles() -> u64 {
+    // SAFETY: rdtsc is available on every x86_64 CPU and has no side effects
+    unsafe { core::arch::x86_64::_rdtsc() }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+pub fn cycles() -> u64 {
+    clock::monotonic_now_ns()
+}
+
+/// Cycle counter ticks per nanosecond, measured over a short busy wait on first use.
+fn ticks_per_ns() -> f64 {
+    static TICKS_PER_NS: OnceLock<f64> = OnceLock::new();
+    *TICKS_PER_NS.get_or_init(|| {
+        if cfg!(not(target_arch = "x86_64")) {
+            return 1.0;
+        }
+        let (start_ns, start_cycles) = (clock::monotonic_now_ns(), cycles());
+        while clock::monotonic_now_ns() - start_ns < 5_000_000 {}
+        let (end_ns, end_cycles) = (clock::monotonic_now_ns(), cycles());
+        (end_cycles - start_cycles) as f64 / (end_ns - start_ns) as f64
+    })
+}
+
+/// A section that ran over its budget.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MissedDeadline {
+    pub section: &'static str,
+    /// Thread the section ran on.
+    pub thread: String,
+    pub duration_ns: u64,
+    pub budget_ns: u64,
+    /// Misses of this section so far, including this one.
+    pub missed: u64,
+}
+
+impl MissedDeadline {
+    /// The `DEADLINE MISSED:` line, with durations in `unit`.
+    pub fn summary(&self, unit: Unit) -> String {
+        format!(
+            "DEADLINE MISSED: {} on thread {} took {:.3} (budget {:.3}, {} misses)",
+            self.section,
+            self.thread,
+            DisplayMs(self.duration_ns as f64 / 1e6, unit),
+            DisplayMs(self.budget_ns as f64 / 1e6, unit),
+            self.missed
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DeadlineMonitor {
+    section: &'static str,
+    budget_ns: u64,
+    started: Option<u64>,
+    runs: u64,
+    missed: u64,
+    worst_ns: u64,
+}
+
+impl DeadlineMonitor {
+    pub fn new(section: &'static str, budget_ms: f64) -> Self {
+        // Calibrate up front rather than inside the first timed section
+        ticks_per_ns();
+        Self {
+            section,
+            budget_ns: (budget_ms * 1e6) as u64,
+            started: None,
+            runs: 0,
+            missed: 0,
+            worst_ns: 0,
+        }
+    }
+
+    pub fn start(&mut self) {
+        self.started = Some(cycles());
+    }
+
+    /// Ends the section started last; returns the miss if it ran over budget.
+    pub fn finish(&mut self) -> Option<MissedDeadline> {
+        let started = self.started.take()?;
+        let duration_ns = (cycles().saturating_sub(started) as f64 / ticks_per_ns()) as u64;
+        self.runs += 1;
+        self.worst_ns = self.worst_ns.max(duration_ns);
+        if duration_ns <= self.budget_ns {
+            return None;
+        }
+        self.missed += 1;
+        Some(MissedDeadline {
+            section: self.section,
+            thread: std::thread::current().name().unwrap_or("unnamed").to_string(),
+            duration_ns,
+            budget_ns: self.budget_ns,
+            missed: self.missed,
+        })
+    }
+
+    pub fn section(&self) -> &'static str {
+        self.section
+    }
+
+    pub fn budget_ns(&self) -> u64 {
+        self.budget_ns
+    }
+
+    /// Changes the budget for the following runs, e.g. on a config reload.
+    pub fn set_budget_ms(&mut self, budget_ms: f64) {
+        self.budget_ns = (budget_ms * 1e6) as u64;
+    }
+
+    /// Finished runs and how many of them missed the budget.
+    pub fn counts(&self) -> (u64, u64) {
+        (self.runs, self.missed)
+    }
+
+    pub fn worst_ns(&self) -> u64 {
+        self.worst_ns
+    }
+
+    /// Misses so far, with durations in `unit`.
+    pub fn summary(&self, unit: Unit) -> String {
+        format!(
+            "{}: {} of {} runs over {:.3} budget, worst {:.3}",
+            self.section,
+            self.missed,
+            self.runs,
+            DisplayMs(self.budget_ns as f64 / 1e6, unit),
+            DisplayMs(self.worst_ns as f64 / 1e6, unit)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn sections_over_budget_are_reported() {
+        let mut monitor = DeadlineMonitor::new("match", 1.0);
+        assert_eq!(monitor.finish(), None);
+
+        monitor.start();
+        assert_eq!(monitor.finish(), None);
+        monitor.start();
+        std::thread::sleep(Duration::from_millis(5));
+        let missed = monitor.finish().unwrap();
+        assert_eq!((missed.section, missed.budget_ns, missed.missed), ("match", 1_000_000, 1));
+        assert!(missed.duration_ns >= 4_000_000, "{:?}", missed);
+        assert_eq!(monitor.worst_ns(), missed.duration_ns);
+
+        monitor.set_budget_ms(1_000.0);
+        monitor.start();
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(monitor.finish(), None);
+        assert_eq!(monitor.counts(), (3, 1));
+        assert!(monitor.summary(Unit::Us).starts_with("match: 1 of 3 runs over 1000000.0us budget"), "{}", monitor.summary(Unit::Us));
+        assert!(missed.summary(Unit::Ms).starts_with("DEADLINE MISSED: match on thread"), "{}", missed.summary(Unit::Ms));
+    }
+}
@@ -0,0 +1,319 @@
+// Degradation ladder of the capture app under overload.
+//
+// A capture loop that cannot keep up used to just fall behind: queues filled,
+// deadlines were missed and every frame came out later. Instead, the load is
+// measured on every frame (the pipeline depth over its limit, see `depth`, or
+// a missed deadline, see `deadline`) and the app steps down a fixed ladder of
+// the work it can do without, cheapest loss first:
+//
+//   0 full            everything runs
+//   1 drop_preview    no preview frames for the UI
+//   2 drop_recording  no frame images or MCAP frames, except priority frames;
+//                     records.csv keeps every match
+//   3 skip            the skip ratio multiplied by `SKIP_FACTOR`
+//   4 shed            frames of normal triggers reach no frame consumer
+//                     (publisher, loopback, RTSP, recording), only priority
+//                     triggers' do (see `priority`)
+//
+// Each level includes the ones above it. The ladder steps down one level
+// after `escalate_after` overloaded frames, counted while they come less than
+// `escalate_after` frames apart, and steps back up one level after
+// `recover_after` frames in a row without overload. Shedding needs priority
+// triggers; without them the ladder stops at `skip`.
+
+use std::fmt;
+
+use crate::cli::Args;
+
+/// Multiplier of the skip ratio at `skip` and below: half the frames processed.
+pub const SKIP_FACTOR: u32 = 2;
+
+/// Default overloaded frames before the next level, half a second at 30fps.
+pub const DEFAULT_ESCALATE_AFTER: u32 = 15;
+
+/// Default frames without overload before the previous level, five seconds at 30fps.
+pub const DEFAULT_RECOVER_AFTER: u32 = 150;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Level {
+    #[default]
+    Full,
+    DropPreview,
+    DropRecording,
+    Skip,
+    Shed,
+}
+
+impl Level {
+    pub const ALL: [Level; 5] = [Level::Full, Level::DropPreview, Level::DropRecording, Level::Skip, Level::Shed];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Level::Full => "full",
+            Level::DropPreview => "drop_preview",
+            Level::DropRecording => "drop_recording",
+            Level::Skip => "skip",
+            Level::Shed => "shed",
+        }
+    }
+
+    /// Position on the ladder, 0 for `full`.
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|level| level.name() == name)
+    }
+
+    fn next(self) -> Self {
+        Self::ALL.get(self.index() + 1).copied().unwrap_or(self)
+    }
+
+    fn previous(self) -> Self {
+        Self::ALL[self.index().saturating_sub(1)]
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LadderConfig {
+    /// Lowest level the ladder steps down to; `full` turns it off.
+    pub max: Level,
+    pub escalate_after: u32,
+    pub recover_after: u32,
+}
+
+impl Default for LadderConfig {
+    fn default() -> Self {
+        Self {
+            max: Level::Shed,
+            escalate_after: DEFAULT_ESCALATE_AFTER,
+            recover_after: DEFAULT_RECOVER_AFTER,
+        }
+    }
+}
+
+impl LadderConfig {
+    /// `--max-degradation <level>`, `--degrade-after <frames>`, `--recover-after <frames>`.
+    pub fn from_args(args: &Args) -> Result<Self, String> {
+        let defaults = Self::default();
+        let max = match args.value("max-degradation") {
+            Some(name) => Level::from_name(name).ok_or_else(|| {
+                let names: Vec<&str> = Level::ALL.iter().map(|level| level.name()).collect();
+                format!("unknown degradation level '{}' (expected {})", name, names.join(", "))
+            })?,
+            None => defaults.max,
+        };
+        Ok(Self {
+            max,
+            escalate_after: args.value_as("degrade-after").unwrap_or(defaults.escalate_after).max(1),
+            recover_after: args.value_as("recover-after").unwrap_or(defaults.recover_after).max(1),
+        })
+    }
+}
+
+/// The ladder stepped down or back up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LevelChange {
+    pub from: Level,
+    pub to: Level,
+    /// What overloaded the pipeline, `None` when stepping back up.
+    pub cause: Option<&'static str>,
+    /// Overloaded frames before stepping down, or calm frames before stepping up.
+    pub frames: u32,
+}
+
+impl LevelChange {
+    pub fn is_escalation(&self) -> bool {
+        self.to > self.from
+    }
+}
+
+impl fmt::Display for LevelChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.cause {
+            Some(cause) => write!(f, "DEGRADED to {} (level {}, was {}): {} overloaded frames, last by {}", self.to, self.to.index(), self.from, self.frames, cause),
+            None => write!(f, "Degradation eased to {} (level {}, was {}) after {} frames without overload", self.to, self.to.index(), self.from, self.frames),
+        }
+    }
+}
+
+/// Work left undone by the ladder so far.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Degraded {
+    pub previews: u64,
+    pub recordings: u64,
+    pub skipped: u64,
+    pub shed: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DegradationLadder {
+    config: LadderConfig,
+    level: Level,
+    overloaded: u32,
+    calm: u32,
+    changes: u64,
+    /// Counted by the caller as it leaves work undone.
+    pub degraded: Degraded,
+}
+
+impl DegradationLadder {
+    pub fn new(config: LadderConfig) -> Self {
+        Self { config, ..Self::default() }
+    }
+
+    pub fn config(&self) -> LadderConfig {
+        self.config
+    }
+
+    pub fn level(&self) -> Level {
+        self.level
+    }
+
+    /// Level changes so far.
+    pub fn changes(&self) -> u64 {
+        self.changes
+    }
+
+    /// Takes the load of one frame, the cause if it was overloaded; a level change is returned once.
+    pub fn observe(&mut self, overload: Option<&'static str>) -> Option<LevelChange> {
+        let Some(cause) = overload else {
+            self.calm += 1;
+            // Spikes further apart than the escalation window do not add up
+            if self.calm >= self.config.escalate_after {
+                self.overloaded = 0;
+            }
+            if self.calm < self.config.recover_after || self.level == Level::Full {
+                return None;
+            }
+            let frames = std::mem::take(&mut self.calm);
+            return Some(self.step(self.level.previous(), None, frames));
+        };
+        self.calm = 0;
+        self.overloaded += 1;
+        if self.overloaded < self.config.escalate_after || self.level >= self.config.max {
+            return None;
+        }
+        let frames = std::mem::take(&mut self.overloaded);
+        Some(self.step(self.level.next(), Some(cause), frames))
+    }
+
+    fn step(&mut self, to: Level, cause: Option<&'static str>, frames: u32) -> LevelChange {
+        let from = std::mem::replace(&mut self.level, to);
+        self.changes += 1;
+        LevelChange { from, to, cause, frames }
+    }
+
+    pub fn shows_preview(&self) -> bool {
+        self.level < Level::DropPreview
+    }
+
+    /// Whether a matched frame is recorded (frame images, MCAP frames).
+    pub fn records(&self, priority: bool) -> bool {
+        self.level < Level::DropRecording || priority
+    }
+
+    /// Multiplier of the skip ratio.
+    pub fn skip_factor(&self) -> u32 {
+        if self.level >= Level::Skip {
+            SKIP_FACTOR
+        } else {
+            1
+        }
+    }
+
+    /// Whether a matched frame goes to the frame consumers.
+    pub fn delivers(&self, priority: bool) -> bool {
+        self.level < Level::Shed || priority
+    }
+}
+
+impl fmt::Display for DegradationLadder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Degradation {} (level {}/{}, {} changes): {} previews, {} recordings, {} frames skipped, {} frames shed",
+            self.level,
+            self.level.index(),
+            self.config.max.index(),
+            self.changes,
+            self.degraded.previews,
+            self.degraded.recordings,
+            self.degraded.skipped,
+            self.degraded.shed
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEPTH: Option<&str> = Some("pipeline depth");
+
+    fn config(line: &str) -> Result<LadderConfig, String> {
+        LadderConfig::from_args(&Args::parse(line.split_whitespace().map(str::to_string), &[]))
+    }
+
+    // Observes `frames` frames of the same load; returns the changes
+    fn run(ladder: &mut DegradationLadder, overload: Option<&'static str>, frames: u32) -> Vec<LevelChange> {
+        (0..frames).filter_map(|_| ladder.observe(overload)).collect()
+    }
+
+    #[test]
+    fn sustained_overload_steps_down_the_ladder() {
+        let mut ladder = DegradationLadder::new(config("v4l2_capture --degrade-after 10 --recover-after 50").unwrap());
+        assert!(ladder.shows_preview() && ladder.records(false) && ladder.delivers(false));
+        assert_eq!(ladder.skip_factor(), 1);
+
+        // One level per 10 overloaded frames, down to the last
+        let changes = run(&mut ladder, DEPTH, 60);
+        let levels: Vec<Level> = changes.iter().map(|change| change.to).collect();
+        assert_eq!(levels, [Level::DropPreview, Level::DropRecording, Level::Skip, Level::Shed]);
+        assert!(changes.iter().all(|change| change.is_escalation() && change.cause == DEPTH && change.frames == 10));
+        assert!(!ladder.shows_preview());
+        assert!(!ladder.records(false) && ladder.records(true));
+        assert_eq!(ladder.skip_factor(), 2);
+        assert!(!ladder.delivers(false) && ladder.delivers(true));
+        assert_eq!(
+            changes[0].to_string(),
+            "DEGRADED to drop_preview (level 1, was full): 10 overloaded frames, last by pipeline depth"
+        );
+
+        // Back up one level per 50 calm frames
+        let changes = run(&mut ladder, None, 120);
+        assert_eq!(changes.iter().map(|change| change.to).collect::<Vec<_>>(), [Level::Skip, Level::DropRecording]);
+        assert!(changes.iter().all(|change| !change.is_escalation() && change.cause.is_none()));
+        assert_eq!(ladder.changes(), 6);
+        assert_eq!(
+            changes[1].to_string(),
+            "Degradation eased to drop_recording (level 2, was skip) after 50 frames without overload"
+        );
+    }
+
+    #[test]
+    fn sparse_spikes_and_configured_levels() {
+        // Spikes further apart than the escalation window never add up
+        let mut ladder = DegradationLadder::new(config("v4l2_capture --degrade-after 5").unwrap());
+        for _ in 0..20 {
+            assert!(ladder.observe(Some("missed deadline")).is_none());
+            assert!(run(&mut ladder, None, 5).is_empty());
+        }
+        assert_eq!(ladder.level(), Level::Full);
+
+        // Never below --max-degradation; `full` turns the ladder off
+        let mut ladder = DegradationLadder::new(config("v4l2_capture --degrade-after 5 --max-degradation skip").unwrap());
+        assert_eq!(run(&mut ladder, DEPTH, 100).last().map(|change| change.to), Some(Level::Skip));
+        let mut ladder = DegradationLadder::new(config("v4l2_capture --max-degradation full").unwrap());
+        assert!(run(&mut ladder, DEPTH, 1000).is_empty());
+        assert_eq!(config("v4l2_capture --max-degradation none").unwrap_err(), "unknown degradation level 'none' (expected full, drop_preview, drop_recording, skip, shed)");
+        assert_eq!(config("v4l2_capture").unwrap(), LadderConfig::default());
+    }
+}
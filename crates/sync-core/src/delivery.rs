@@ -0,0 +1,126 @@
+// Delivery of triggers to slow consumers.
+//
+// By default the trigger service overwrites the oldest trigger in a full
+// subscriber buffer (safe overflow), so a consumer that falls behind loses
+// triggers and only the consumer can tell. The publisher can be configured
+// (`--unable-to-deliver`) to handle a full buffer differently:
+//
+//   overflow  replace the oldest trigger in the subscriber's buffer (default)
+//   discard   do not deliver the new trigger to that subscriber
+//   block     wait until the subscriber made room; a stuck consumer stalls
+//             the publisher and with it every other consumer
+//
+// Discard and block need the service without safe overflow. It is created
+// that way by the publisher, so the publisher has to be started before the
+// camera processes. The publisher counts the subscribers a trigger did not
+// reach (with discard, or a buffer overwritten in the in-memory mock), and the
+// counts are published as `delivery` telemetry. A blocking send reaches every
+// subscriber in the end, so its waits are not counted.
+
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnableToDeliver {
+    #[default]
+    Overflow,
+    Discard,
+    Block,
+}
+
+impl UnableToDeliver {
+    /// Whether the service overwrites the oldest sample of a full subscriber buffer.
+    pub fn safe_overflow(&self) -> bool {
+        *self == UnableToDeliver::Overflow
+    }
+}
+
+impl fmt::Display for UnableToDeliver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnableToDeliver::Overflow => write!(f, "overflow"),
+            UnableToDeliver::Discard => write!(f, "discard"),
+            UnableToDeliver::Block => write!(f, "block"),
+        }
+    }
+}
+
+impl FromStr for UnableToDeliver {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "overflow" => Ok(UnableToDeliver::Overflow),
+            "discard" => Ok(UnableToDeliver::Discard),
+            "block" => Ok(UnableToDeliver::Block),
+            other => Err(format!("unknown unable-to-deliver strategy '{}' (expected overflow, discard or block)", other)),
+        }
+    }
+}
+
+/// Published triggers and the deliveries a subscriber's full buffer did not take.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeliveryCounts {
+    pub published: u64,
+    /// Triggers times subscribers they were not delivered to.
+    pub undelivered: u64,
+}
+
+impl DeliveryCounts {
+    pub fn record_published(&mut self) {
+        self.published += 1;
+    }
+
+    /// A trigger sent to `subscribers` subscribers reached only `recipients` of them.
+    pub fn record_sent(&mut self, subscribers: usize, recipients: usize) {
+        self.record_undelivered(subscribers.saturating_sub(recipients) as u64);
+    }
+
+    pub fn record_undelivered(&mut self, count: u64) {
+        self.undelivered += count;
+    }
+
+    pub fn summary(&self) -> String {
+        format!("{} deliveries of {} triggers failed", self.undelivered, self.published)
+    }
+}
+
+/// Telemetry body: `published` and `undelivered`.
+impl fmt::Display for DeliveryCounts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "published = {}", self.published)?;
+        write!(f, "undelivered = {}", self.undelivered)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sends_that_miss_subscribers_are_counted() {
+        let mut delivery = DeliveryCounts::default();
+        for (subscribers, recipients) in [(3, 3), (3, 1), (0, 0), (2, 2)] {
+            delivery.record_published();
+            delivery.record_sent(subscribers, recipients);
+        }
+        assert_eq!(delivery.published, 4);
+        assert_eq!(delivery.undelivered, 2);
+        assert_eq!(delivery.summary(), "2 deliveries of 4 triggers failed");
+        assert_eq!(delivery.to_string(), "published = 4\nundelivered = 2");
+
+        // A subscriber that connected after the count was taken received more than expected
+        delivery.record_sent(1, 2);
+        assert_eq!(delivery.undelivered, 2);
+    }
+
+    #[test]
+    fn only_overflow_keeps_safe_overflow() {
+        assert!(UnableToDeliver::default().safe_overflow());
+        for strategy in ["discard", "block"] {
+            let strategy: UnableToDeliver = strategy.parse().unwrap();
+            assert!(!strategy.safe_overflow());
+        }
+        assert!("drop".parse::<UnableToDeliver>().is_err());
+    }
+}
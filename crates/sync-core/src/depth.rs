@@ -0,0 +1,203 @@
+// End-to-end queue depth of the capture pipeline.
+//
+// Latency grows silently when frames pile up in a queue: the driver queue the
+// capture loop falls behind on, a preview frame the UI has not taken yet, a
+// recorder or frame publisher behind on its thread. Each stage reports how
+// many frames it holds right now and how many it can hold; `PipelineDepth`
+// adds them up to one pipeline depth, the frames in flight between the sensor
+// and the last consumer. The depth is exceeded when the total goes over
+// `--max-pipeline-depth` (default 4) or a stage is full. Both transitions are
+// reported as they happen, so hidden queuing shows with the first frame it
+// delays rather than in the latency statistics much later.
+//
+// The driver queue cannot be read directly. Its depth is estimated from the
+// dequeued frame's age: a frame that waited two frame intervals in the queue
+// had two more frames captured behind it.
+
+use std::fmt;
+
+use crate::cli::Args;
+
+/// Default limit of the frames in flight, e.g. one per stage while the consumers keep up.
+pub const DEFAULT_MAX_DEPTH: usize = 4;
+
+/// Frames one stage holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StageDepth {
+    pub name: &'static str,
+    pub depth: usize,
+    pub capacity: usize,
+}
+
+impl StageDepth {
+    pub fn is_full(&self) -> bool {
+        self.depth >= self.capacity
+    }
+}
+
+impl fmt::Display for StageDepth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}/{}{}", self.name, self.depth, self.capacity, if self.is_full() { " full" } else { "" })
+    }
+}
+
+/// Frames waiting in the driver queue behind one that was captured at `driver_ns` and dequeued at `dequeue_ns`.
+pub fn driver_backlog(dequeue_ns: u64, driver_ns: u64, frame_interval_ns: u64) -> usize {
+    (dequeue_ns.saturating_sub(driver_ns) / frame_interval_ns.max(1)) as usize
+}
+
+/// The pipeline depth went over its limit, or came back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DepthChange {
+    pub exceeded: bool,
+    pub total: usize,
+    pub limit: usize,
+    pub stages: Vec<StageDepth>,
+}
+
+impl fmt::Display for DepthChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.exceeded {
+            write!(f, "PIPELINE DEPTH EXCEEDED: {} frames in flight (limit {}): ", self.total, self.limit)?;
+        } else {
+            write!(f, "Pipeline depth back to {} frames (limit {}): ", self.total, self.limit)?;
+        }
+        write_stages(f, &self.stages)
+    }
+}
+
+fn write_stages(f: &mut fmt::Formatter<'_>, stages: &[StageDepth]) -> fmt::Result {
+    for (index, stage) in stages.iter().enumerate() {
+        write!(f, "{}{}", if index > 0 { ", " } else { "" }, stage)?;
+    }
+    Ok(())
+}
+
+/// Frames in flight across all stages, checked against the limit after every frame.
+#[derive(Debug, Clone)]
+pub struct PipelineDepth {
+    limit: usize,
+    stages: Vec<StageDepth>,
+    exceeded: bool,
+    max_total: usize,
+    exceeded_count: u64,
+}
+
+impl Default for PipelineDepth {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_DEPTH)
+    }
+}
+
+impl PipelineDepth {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit: limit.max(1),
+            stages: Vec::new(),
+            exceeded: false,
+            max_total: 0,
+            exceeded_count: 0,
+        }
+    }
+
+    /// `--max-pipeline-depth`.
+    pub fn from_args(args: &Args) -> Self {
+        Self::new(args.value_as("max-pipeline-depth").unwrap_or(DEFAULT_MAX_DEPTH))
+    }
+
+    /// Takes the current depth of every stage; a change against the limit is returned once.
+    pub fn observe(&mut self, stages: &[StageDepth]) -> Option<DepthChange> {
+        self.stages = stages.to_vec();
+        let total = self.total();
+        self.max_total = self.max_total.max(total);
+        let exceeded = total > self.limit || stages.iter().any(StageDepth::is_full);
+        if exceeded == self.exceeded {
+            return None;
+        }
+        self.exceeded = exceeded;
+        self.exceeded_count += exceeded as u64;
+        Some(DepthChange {
+            exceeded,
+            total,
+            limit: self.limit,
+            stages: self.stages.clone(),
+        })
+    }
+
+    /// Frames in flight at the last observation.
+    pub fn total(&self) -> usize {
+        self.stages.iter().map(|stage| stage.depth).sum()
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    pub fn stages(&self) -> &[StageDepth] {
+        &self.stages
+    }
+
+    /// Largest total seen so far.
+    pub fn max_total(&self) -> usize {
+        self.max_total
+    }
+
+    pub fn is_exceeded(&self) -> bool {
+        self.exceeded
+    }
+
+    /// Times the depth went over its limit.
+    pub fn exceeded_count(&self) -> u64 {
+        self.exceeded_count
+    }
+}
+
+impl fmt::Display for PipelineDepth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Pipeline depth {}/{} (max {}, exceeded {}x): ", self.total(), self.limit, self.max_total, self.exceeded_count)?;
+        write_stages(f, &self.stages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: u64 = 1_000_000;
+
+    fn stages(driver: usize, preview: usize, recorder: usize) -> [StageDepth; 3] {
+        [
+            StageDepth { name: "driver", depth: driver, capacity: 3 },
+            StageDepth { name: "preview", depth: preview, capacity: 2 },
+            StageDepth { name: "recorder", depth: recorder, capacity: 5 },
+        ]
+    }
+
+    #[test]
+    fn reports_hidden_queuing_once_per_excursion() {
+        // A frame dequeued 25ms after capture at 100fps had two more captured behind it
+        assert_eq!(driver_backlog(1_025 * MS, 1_000 * MS, 10 * MS), 2);
+        assert_eq!(driver_backlog(1_000 * MS, 1_001 * MS, 10 * MS), 0);
+
+        let mut depth = PipelineDepth::new(4);
+        assert_eq!(depth.observe(&stages(0, 1, 1)), None);
+        assert_eq!(depth.total(), 2);
+
+        // Over the limit in total
+        let change = depth.observe(&stages(2, 1, 2)).unwrap();
+        assert!(change.exceeded && depth.is_exceeded());
+        assert_eq!(change.total, 5);
+        assert_eq!(change.to_string(), "PIPELINE DEPTH EXCEEDED: 5 frames in flight (limit 4): driver 2/3, preview 1/2, recorder 2/5");
+        assert_eq!(depth.observe(&stages(2, 1, 3)), None);
+
+        let change = depth.observe(&stages(0, 0, 1)).unwrap();
+        assert!(!change.exceeded);
+        assert_eq!(change.to_string(), "Pipeline depth back to 1 frames (limit 4): driver 0/3, preview 0/2, recorder 1/5");
+
+        // A full stage exceeds the depth below the total limit
+        let change = depth.observe(&stages(3, 0, 0)).unwrap();
+        assert!(change.exceeded);
+        assert!(change.to_string().ends_with("driver 3/3 full, preview 0/2, recorder 0/5"));
+        assert_eq!((depth.max_total(), depth.exceeded_count()), (6, 2));
+    }
+}
@@ -0,0 +1,264 @@
+// Diagnosis of growing numbers of unmatched frames.
+//
+// When too many recent frames found no trigger, the recent history is checked
+// for the usual root causes: camera and trigger running at different rates,
+// a constant delay that makes the nearest trigger the wrong one, or so much
+// timing jitter that matches become ambiguous. The result is a readable report
+// with suggested parameter changes.
+
+use std::collections::VecDeque;
+use std::fmt;
+
+use crate::rate::RateEstimate;
+
+/// Number of recent frames considered.
+const WINDOW: usize = 100;
+/// Diagnose once this share of the recent frames is unmatched.
+const UNMATCHED_THRESHOLD: f64 = 0.2;
+/// Camera and trigger rates closer than this are considered equal.
+const RATE_TOLERANCE: f64 = 0.05;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cause {
+    /// No trigger rate could be estimated.
+    NoTriggers,
+    CameraFaster { camera_hz: f64, trigger_hz: f64 },
+    TriggersFaster { camera_hz: f64, trigger_hz: f64 },
+    /// Frames arrive so long after their trigger that a later trigger is nearer.
+    ConstantOffset { offset_ms: f64, period_ms: f64 },
+    /// Trigger-to-frame delay varies too much to tell neighbouring triggers apart.
+    ExcessiveJitter { jitter_ms: f64, period_ms: f64 },
+}
+
+impl Cause {
+    pub fn suggestion(&self) -> String {
+        match self {
+            Cause::NoTriggers => "check that the publisher is running and uses the same service and signing key".to_string(),
+            Cause::CameraFaster { trigger_hz, .. } => format!(
+                "set the camera to the trigger rate (--camera-fps {:.0}) or use external trigger mode",
+                trigger_hz
+            ),
+            Cause::TriggersFaster { camera_hz, .. } => format!(
+                "lower the trigger rate (publisher interval {:.0}ms) or raise the camera frame rate",
+                1000.0 / camera_hz
+            ),
+            Cause::ConstantOffset { offset_ms, .. } => format!(
+                "reduce the capture delay of {:.1}ms (fewer V4L2 buffers, --target-latency-ms) or check that both hosts use the same clock",
+                offset_ms
+            ),
+            Cause::ExcessiveJitter { .. } => {
+                "use driver timestamps (--backend v4l2), reduce system load or lower the output fps".to_string()
+            }
+        }
+    }
+}
+
+impl fmt::Display for Cause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Cause::NoTriggers => write!(f, "no triggers received"),
+            Cause::CameraFaster { camera_hz, trigger_hz } => {
+                write!(f, "camera faster than triggers ({:.2}fps vs {:.2}Hz)", camera_hz, trigger_hz)
+            }
+            Cause::TriggersFaster { camera_hz, trigger_hz } => {
+                write!(f, "triggers faster than camera ({:.2}Hz vs {:.2}fps)", trigger_hz, camera_hz)
+            }
+            Cause::ConstantOffset { offset_ms, period_ms } => write!(
+                f,
+                "large constant offset: frames arrive {:.1}ms after their trigger (trigger period {:.1}ms)",
+                offset_ms, period_ms
+            ),
+            Cause::ExcessiveJitter { jitter_ms, period_ms } => write!(
+                f,
+                "excessive jitter: trigger-to-frame delay varies by {:.1}ms (trigger period {:.1}ms)",
+                jitter_ms, period_ms
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnosis {
+    pub unmatched_pct: f64,
+    pub causes: Vec<Cause>,
+}
+
+impl fmt::Display for Diagnosis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "MATCH DIAGNOSIS ({:.0}% of recent frames unmatched):", self.unmatched_pct)?;
+        if self.causes.is_empty() {
+            write!(f, "\n  no known cause found; matches may be rejected by the tolerance window")?;
+        }
+        for cause in &self.causes {
+            write!(f, "\n  {}\n    suggestion: {}", cause, cause.suggestion())?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MatchDiagnostics {
+    /// Camera frames represented by each recorded frame (frame skipping).
+    frame_step: u32,
+    frames_ns: VecDeque<u64>,
+    /// Trigger-to-frame delay of each matched frame, `None` when unmatched.
+    outcomes: VecDeque<Option<f64>>,
+    since_report: usize,
+}
+
+impl Default for MatchDiagnostics {
+    fn default() -> Self {
+        Self::new(1)
+    }
+}
+
+impl MatchDiagnostics {
+    /// `frame_step` is the number of camera frames per recorded frame, when only every n-th frame is seen.
+    pub fn new(frame_step: u32) -> Self {
+        Self {
+            frame_step: frame_step.max(1),
+            frames_ns: VecDeque::new(),
+            outcomes: VecDeque::new(),
+            since_report: WINDOW,
+        }
+    }
+
+    /// Records the arrival of a camera frame.
+    pub fn on_frame(&mut self, frame_ns: u64) {
+        if self.frames_ns.len() == WINDOW {
+            self.frames_ns.pop_front();
+        }
+        self.frames_ns.push_back(frame_ns);
+    }
+
+    /// Records the outcome of matching a frame: its delay after the trigger, or `None` when unmatched.
+    pub fn on_match(&mut self, offset_ms: Option<f64>) {
+        if self.outcomes.len() == WINDOW {
+            self.outcomes.pop_front();
+        }
+        self.outcomes.push_back(offset_ms);
+        self.since_report += 1;
+    }
+
+    pub fn unmatched_ratio(&self) -> f64 {
+        if self.outcomes.is_empty() {
+            return 0.0;
+        }
+        self.outcomes.iter().filter(|outcome| outcome.is_none()).count() as f64 / self.outcomes.len() as f64
+    }
+
+    /// Diagnoses when unmatched frames grow past the threshold, at most once per window.
+    pub fn check(&mut self, trigger_rate: Option<&RateEstimate>) -> Option<Diagnosis> {
+        if self.since_report < WINDOW || self.outcomes.len() < WINDOW / 4 || self.unmatched_ratio() < UNMATCHED_THRESHOLD {
+            return None;
+        }
+        self.since_report = 0;
+        Some(self.diagnose(trigger_rate))
+    }
+
+    fn camera_hz(&self) -> Option<f64> {
+        let (first, last) = (*self.frames_ns.front()?, *self.frames_ns.back()?);
+        if self.frames_ns.len() < 2 || last <= first {
+            return None;
+        }
+        let frames = ((self.frames_ns.len() - 1) as u64 * self.frame_step as u64) as f64;
+        Some(frames * 1e9 / (last - first) as f64)
+    }
+
+    pub fn diagnose(&self, trigger_rate: Option<&RateEstimate>) -> Diagnosis {
+        let unmatched_pct = self.unmatched_ratio() * 100.0;
+        let Some(trigger_rate) = trigger_rate else {
+            return Diagnosis {
+                unmatched_pct,
+                causes: vec![Cause::NoTriggers],
+            };
+        };
+        let trigger_hz = trigger_rate.rate_hz;
+        let period_ms = trigger_rate.period_ns / 1e6;
+        let mut causes = Vec::new();
+
+        if let Some(camera_hz) = self.camera_hz() {
+            if camera_hz > trigger_hz * (1.0 + RATE_TOLERANCE) {
+                causes.push(Cause::CameraFaster { camera_hz, trigger_hz });
+            } else if trigger_hz > camera_hz * (1.0 + RATE_TOLERANCE) {
+                causes.push(Cause::TriggersFaster { camera_hz, trigger_hz });
+            }
+        }
+
+        let offsets: Vec<f64> = self.outcomes.iter().flatten().copied().collect();
+        if offsets.len() >= 2 {
+            let mean = offsets.iter().sum::<f64>() / offsets.len() as f64;
+            let jitter_ms = (offsets.iter().map(|o| (o - mean).powi(2)).sum::<f64>() / offsets.len() as f64).sqrt();
+            if jitter_ms > period_ms / 4.0 {
+                causes.push(Cause::ExcessiveJitter { jitter_ms, period_ms });
+            } else if mean.abs() > period_ms / 2.0 {
+                causes.push(Cause::ConstantOffset {
+                    offset_ms: mean,
+                    period_ms,
+                });
+            }
+        }
+
+        Diagnosis { unmatched_pct, causes }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trigger_rate(rate_hz: f64) -> RateEstimate {
+        RateEstimate {
+            rate_hz,
+            period_ns: 1e9 / rate_hz,
+            phase_ns: 0.0,
+            jitter_ns: 0.0,
+            triggers: 100,
+        }
+    }
+
+    // A window of frames at `camera_hz`, every other one matched `offset_ms(frame)` after its trigger
+    fn diagnostics(camera_hz: f64, offset_ms: impl Fn(usize) -> f64) -> MatchDiagnostics {
+        let mut diagnostics = MatchDiagnostics::default();
+        for frame in 0..WINDOW {
+            diagnostics.on_frame(1_000_000_000 + (frame as f64 * 1e9 / camera_hz) as u64);
+            diagnostics.on_match(frame.is_multiple_of(2).then(|| offset_ms(frame)));
+        }
+        diagnostics
+    }
+
+    #[test]
+    fn diagnoses_rate_mismatch_offset_and_jitter() {
+        let mut camera_faster = diagnostics(60.0, |_| 2.0);
+        assert_eq!(camera_faster.unmatched_ratio(), 0.5);
+        let diagnosis = camera_faster.check(Some(&trigger_rate(30.0))).unwrap();
+        assert!(matches!(diagnosis.causes[..], [Cause::CameraFaster { .. }]), "{}", diagnosis);
+        assert!(diagnosis.to_string().contains("--camera-fps 30"));
+        // Once per window
+        assert!(camera_faster.check(Some(&trigger_rate(30.0))).is_none());
+
+        let triggers_faster = diagnostics(30.0, |_| 2.0).diagnose(Some(&trigger_rate(60.0)));
+        assert!(matches!(triggers_faster.causes[..], [Cause::TriggersFaster { .. }]));
+
+        let late = diagnostics(30.0, |_| 25.0).diagnose(Some(&trigger_rate(30.0)));
+        assert!(matches!(late.causes[..], [Cause::ConstantOffset { offset_ms, .. }] if offset_ms == 25.0));
+
+        let jittery = diagnostics(30.0, |frame| if frame.is_multiple_of(4) { 0.0 } else { 20.0 }).diagnose(Some(&trigger_rate(30.0)));
+        assert!(matches!(jittery.causes[..], [Cause::ExcessiveJitter { .. }]));
+
+        let no_triggers = diagnostics(30.0, |_| 2.0).diagnose(None);
+        assert_eq!(no_triggers.causes, [Cause::NoTriggers]);
+    }
+
+    #[test]
+    fn matching_frames_are_not_diagnosed() {
+        let mut diagnostics = MatchDiagnostics::default();
+        for frame in 0..WINDOW as u64 {
+            diagnostics.on_frame(frame * 33_333_333);
+            diagnostics.on_match(Some(2.0));
+        }
+        assert_eq!(diagnostics.unmatched_ratio(), 0.0);
+        assert!(diagnostics.check(Some(&trigger_rate(30.0))).is_none());
+        assert!(diagnostics.diagnose(Some(&trigger_rate(30.0))).causes.is_empty());
+    }
+}
@@ -0,0 +1,334 @@
+// End-to-end accuracy against a display clock in the scene.
+//
+// A spare camera of the rig points at a clock that shows the time, e.g. an LED
+// bar driven by a PPS-disciplined microcontroller. The clock does not depend on
+// the trigger chain, so comparing the time it shows in a matched frame with
+// the hardware timestamp of the frame's trigger measures the whole path, from
+// trigger timestamp to match, against an outside reference.
+//
+// The clock shows CLOCK_REALTIME in `unit` steps, modulo 2^`bits`, as a strip
+// of `bits + 2` equal cells side by side in `region` of the frame
+// (`--display-clock x,y,width,height`):
+//
+//   1 cell    lit reference
+//   bits      time as a Gray code, most significant bit first, lit = 1
+//   1 cell    dark reference
+//
+// The threshold is midway between the references, and a strip without
+// `MIN_CONTRAST` between them is unreadable (clock out of view, over-exposed).
+// With the Gray code an exposure across an update reads the old or the new
+// time, never a mix of both; exposures should still be shorter than `unit`.
+// The displayed time is the middle of the shown step, taken in the 2^`bits`
+// period nearest to the trigger time, so the clock only has to be right to
+// half a period.
+//
+// error = trigger hw_ts - (displayed time + clock lag), where the lag
+// (`--display-clock-lag-ms`) is how far the clock runs behind, e.g. its LED
+// driver's latency. A positive error means frames are stamped later than
+// their scene.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::cli::Args;
+use crate::stats::LatencyStats;
+use crate::units::Unit;
+
+pub const MEASUREMENTS_FILE: &str = "display_clock.csv";
+
+/// Time bits the strip shows (`--display-clock-bits`).
+pub const DEFAULT_BITS: u32 = 16;
+
+/// Time step of the strip's least significant cell (`--display-clock-unit-us`).
+pub const DEFAULT_UNIT_NS: u64 = 1_000_000;
+
+/// Luma between the lit and the dark reference below which the strip is unreadable.
+pub const MIN_CONTRAST: f64 = 40.0;
+
+/// Rectangle of a frame in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl FromStr for Region {
+    type Err = String;
+
+    /// `x,y,width,height`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values: Vec<u32> = s
+            .split(',')
+            .map(|value| value.trim().parse().map_err(|_| format!("invalid region {:?}: expected x,y,width,height in pixels", s)))
+            .collect::<Result<_, _>>()?;
+        match values[..] {
+            [x, y, width, height] if width > 0 && height > 0 => Ok(Self { x, y, width, height }),
+            _ => Err(format!("invalid region {:?}: expected x,y,width,height in pixels", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for Region {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{},{},{},{}", self.x, self.y, self.width, self.height)
+    }
+}
+
+/// Where the display clock is in the frame and what it shows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DisplayClockConfig {
+    pub region: Region,
+    pub bits: u32,
+    pub unit_ns: u64,
+    /// How far the clock runs behind CLOCK_REALTIME, ns.
+    pub lag_ns: i64,
+}
+
+impl DisplayClockConfig {
+    /// `--display-clock`, `--display-clock-bits`, `--display-clock-unit-us` and `--display-clock-lag-ms`;
+    /// `None` without `--display-clock`.
+    pub fn from_args(args: &Args) -> Result<Option<Self>, String> {
+        let Some(region) = args.value("display-clock") else {
+            return Ok(None);
+        };
+        Ok(Some(Self {
+            region: region.parse()?,
+            bits: args.value_as("display-clock-bits").unwrap_or(DEFAULT_BITS).clamp(4, 32),
+            unit_ns: args.value_as::<f64>("display-clock-unit-us").map_or(DEFAULT_UNIT_NS, |us| (us * 1e3).max(1.0) as u64),
+            lag_ns: args.value_as::<f64>("display-clock-lag-ms").map_or(0, |ms| (ms * 1e6) as i64),
+        }))
+    }
+
+    // Time range the strip shows before it wraps, ns
+    fn period_ns(&self) -> i128 {
+        (self.unit_ns as i128) << self.bits
+    }
+
+    // Left edge and width of cell `index`, in pixels
+    fn cell(&self, index: u32) -> (u32, u32) {
+        let width = (self.region.width / (self.bits + 2)).max(1);
+        (self.region.x + index * width, width)
+    }
+}
+
+fn luma(pixel: &[u8]) -> u32 {
+    (pixel[0] as u32 * 77 + pixel[1] as u32 * 150 + pixel[2] as u32 * 29) >> 8
+}
+
+/// Paints the strip showing `time_ns` into the packed RGB frame `rgb` of `width` pixels per row,
+/// e.g. to drive a display clock or to test the decoder.
+pub fn paint(config: &DisplayClockConfig, time_ns: u64, rgb: &mut [u8], width: u32) {
+    let count = (time_ns / config.unit_ns) & ((1u64 << config.bits) - 1);
+    let gray = count ^ (count >> 1);
+    for index in 0..config.bits + 2 {
+        let lit = match index {
+            0 => true,
+            index if index == config.bits + 1 => false,
+            index => (gray >> (config.bits - index)) & 1 == 1,
+        };
+        let (left, cell_width) = config.cell(index);
+        for y in config.region.y..config.region.y + config.region.height {
+            let start = (y as usize * width as usize + left as usize) * 3;
+            if let Some(row) = rgb.get_mut(start..start + cell_width as usize * 3) {
+                row.fill(if lit { 255 } else { 0 });
+            }
+        }
+    }
+}
+
+/// The count the strip shows in a packed RGB frame, `None` if the region is outside the frame or unreadable.
+pub fn read(config: &DisplayClockConfig, rgb: &[u8], width: u32, height: u32) -> Option<u64> {
+    let region = &config.region;
+    let (right, _) = config.cell(config.bits + 2);
+    if right > width || region.y + region.height > height || rgb.len() < width as usize * height as usize * 3 {
+        return None;
+    }
+    // Mean luma of the middle half of every cell, so blurred edges do not count
+    let cell_luma = |index: u32| {
+        let (left, cell_width) = config.cell(index);
+        let (x0, x1) = (left + cell_width / 4, left + (cell_width * 3).div_ceil(4));
+        let (y0, y1) = (region.y + region.height / 4, region.y + (region.height * 3).div_ceil(4));
+        let mut sum = 0u64;
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let pixel = (y as usize * width as usize + x as usize) * 3;
+                sum += luma(&rgb[pixel..pixel + 3]) as u64;
+            }
+        }
+        sum as f64 / ((x1 - x0) as u64 * (y1 - y0) as u64).max(1) as f64
+    };
+    let (lit, dark) = (cell_luma(0), cell_luma(config.bits + 1));
+    if lit - dark < MIN_CONTRAST {
+        return None;
+    }
+    let threshold = (lit + dark) / 2.0;
+    // Gray to binary: every bit is the previous binary bit xor the Gray bit
+    let mut count = 0u64;
+    for index in 1..=config.bits {
+        let bit = (cell_luma(index) >= threshold) as u64 ^ (count & 1);
+        count = (count << 1) | bit;
+    }
+    Some(count)
+}
+
+/// One frame's display clock reading against its trigger.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reading {
+    /// Hardware timestamp of the frame's trigger, ns.
+    pub hw_ts: u64,
+    /// Time the clock showed plus its lag, ns.
+    pub displayed_ns: u64,
+    /// `hw_ts` minus `displayed_ns`.
+    pub error_ns: i64,
+}
+
+/// Capture side: reads the display clock in every matched frame.
+pub struct DisplayClock {
+    config: DisplayClockConfig,
+    error: LatencyStats,
+    unreadable: u64,
+    writer: Option<BufWriter<File>>,
+}
+
+impl DisplayClock {
+    /// Readings are appended to `display_clock.csv` in `session_dir` if given.
+    pub fn new(config: DisplayClockConfig, session_dir: Option<&Path>) -> std::io::Result<Self> {
+        let writer = match session_dir {
+            Some(dir) => {
+                let mut writer = BufWriter::new(File::create(dir.join(MEASUREMENTS_FILE))?);
+                writeln!(writer, "hw_ts,displayed_ns,error_ns")?;
+                Some(writer)
+            }
+            None => None,
+        };
+        Ok(Self {
+            config,
+            error: LatencyStats::default(),
+            unreadable: 0,
+            writer,
+        })
+    }
+
+    pub fn config(&self) -> &DisplayClockConfig {
+        &self.config
+    }
+
+    /// Reads the clock in a frame matched to the trigger at `hw_ts`; unmatched frames are skipped.
+    pub fn on_frame(&mut self, rgb: &[u8], width: u32, height: u32, hw_ts: Option<u64>) -> Option<Reading> {
+        let hw_ts = hw_ts?;
+        let Some(count) = read(&self.config, rgb, width, height) else {
+            self.unreadable += 1;
+            return None;
+        };
+        // The middle of the shown step, in the period nearest to the trigger time
+        let period = self.config.period_ns();
+        let target = hw_ts as i128 - self.config.lag_ns as i128;
+        let phase = count as i128 * self.config.unit_ns as i128 + self.config.unit_ns as i128 / 2;
+        let shown = phase + (target - phase + period / 2).div_euclid(period) * period;
+        let reading = Reading {
+            hw_ts,
+            displayed_ns: (shown + self.config.lag_ns as i128).max(0) as u64,
+            error_ns: (target - shown) as i64,
+        };
+        self.error.add(reading.error_ns as f64 / 1e6);
+        if let Some(writer) = &mut self.writer {
+            let _ = writeln!(writer, "{},{},{}", reading.hw_ts, reading.displayed_ns, reading.error_ns);
+            let _ = writer.flush();
+        }
+        Some(reading)
+    }
+
+    /// Trigger time minus displayed time of the read frames, ms.
+    pub fn error(&self) -> &LatencyStats {
+        &self.error
+    }
+
+    pub fn unreadable(&self) -> u64 {
+        self.unreadable
+    }
+
+    /// Multi-line report with the error in `unit`.
+    pub fn report(&self, unit: Unit) -> String {
+        let mut report = format!("DISPLAY CLOCK ({} frames read, {} unreadable, resolution {:.3}ms):", self.error.count(), self.unreadable, self.config.unit_ns as f64 / 1e6);
+        if self.error.count() > 0 {
+            report.push_str(&format!("\n  trigger-display    {}", self.error.summary(unit)));
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: u64 = 1_000_000;
+    const WIDTH: u32 = 320;
+    const HEIGHT: u32 = 240;
+
+    fn config() -> DisplayClockConfig {
+        let args = Args::parse("v4l2_capture --display-clock 20,100,270,30 --display-clock-bits 16 --display-clock-lag-ms 0.5".split_whitespace().map(str::to_string), &[]);
+        DisplayClockConfig::from_args(&args).unwrap().unwrap()
+    }
+
+    fn frame(config: &DisplayClockConfig, time_ns: u64) -> Vec<u8> {
+        let mut rgb = vec![90u8; (WIDTH * HEIGHT * 3) as usize];
+        paint(config, time_ns, &mut rgb, WIDTH);
+        rgb
+    }
+
+    #[test]
+    fn strip_decodes_to_the_displayed_time() {
+        let config = config();
+        assert_eq!(config.region, Region { x: 20, y: 100, width: 270, height: 30 });
+        assert_eq!((config.unit_ns, config.lag_ns), (MS, 500_000));
+
+        // Every count reads back, including the wrap at 2^16 units
+        for count in [0u64, 1, 2, 3, 1234, 32767, 32768, 65535, 65536, 70000] {
+            let rgb = frame(&config, count * MS + 400_000);
+            assert_eq!(read(&config, &rgb, WIDTH, HEIGHT), Some(count % 65536));
+        }
+
+        // Exposed across an update: the one cell that changes is half lit
+        for count in [7u64, 255, 32767] {
+            let (old, new) = (frame(&config, count * MS), frame(&config, (count + 1) * MS));
+            let blended: Vec<u8> = old.iter().zip(&new).map(|(a, b)| ((*a as u16 + *b as u16) / 2) as u8).collect();
+            let read = read(&config, &blended, WIDTH, HEIGHT).unwrap();
+            assert!(read == count || read == count + 1, "{} read as {}", count, read);
+        }
+
+        // No clock in view, or the region outside the frame
+        assert_eq!(read(&config, &vec![90u8; (WIDTH * HEIGHT * 3) as usize], WIDTH, HEIGHT), None);
+        assert_eq!(read(&config, &frame(&config, 0), 200, 120), None);
+    }
+
+    #[test]
+    fn error_against_the_trigger_includes_the_lag() {
+        let config = config();
+        let mut clock = DisplayClock::new(config, None).unwrap();
+        // 1.7e18ns wraps the 16-bit strip many times over
+        let now = 1_700_000_000_000 * MS;
+
+        // The clock runs 0.5ms behind; the frame was stamped 2ms after what it shows
+        let rgb = frame(&config, now - 2 * MS - 500_000);
+        let reading = clock.on_frame(&rgb, WIDTH, HEIGHT, Some(now)).unwrap();
+        assert_eq!((reading.displayed_ns, reading.error_ns), (now - 2 * MS, 2_000_000));
+
+        // Stamped before the scene
+        let rgb = frame(&config, now + 3 * MS - 500_000);
+        assert_eq!(clock.on_frame(&rgb, WIDTH, HEIGHT, Some(now)).unwrap().error_ns, -3_000_000);
+
+        // Unmatched frames are skipped, unreadable ones counted
+        assert_eq!(clock.on_frame(&rgb, WIDTH, HEIGHT, None), None);
+        assert_eq!(clock.on_frame(&vec![0u8; (WIDTH * HEIGHT * 3) as usize], WIDTH, HEIGHT, Some(now)), None);
+        assert_eq!((clock.error().count(), clock.unreadable()), (2, 1));
+
+        assert!("10,20,30".parse::<Region>().is_err());
+        assert!("10,20,0,5".parse::<Region>().is_err());
+        let args = Args::parse("v4l2_capture".split_whitespace().map(str::to_string), &[]);
+        assert_eq!(DisplayClockConfig::from_args(&args), Ok(None));
+    }
+}
@@ -0,0 +1,313 @@
+// Root-cause counters for dropped and unmatched frames.
+//
+// Frame loss has two very different sources: the transport (USB bandwidth,
+// UVC payload errors, a full buffer queue) and the association of frames to
+// triggers. Driver-level indicators tell them apart: gaps in the V4L2 buffer
+// sequence count frames the driver never delivered, and buffers flagged with
+// V4L2_BUF_FLAG_ERROR (uvcvideo sets it on UVC payload header errors) were
+// delivered corrupted. Unmatched frames that were corrupted or came right
+// after a sequence gap are attributed to the transport; unmatched clean frames
+// are association failures, whose causes `diagnosis` explains.
+//
+// Triggers are numbered without gaps (a sensor's stream every `divider`-th
+// id), so `TriggerGaps` counts the triggers a consumer never received from
+// gaps in the received ids. A backward step is a restarted publisher. Matches
+// tell about the frames: a frame matching trigger N right after a match of
+// N-3 leaves two frames unaccounted for; unless they were delivered unmatched,
+// they were lost before matching.
+
+use std::fmt;
+
+/// Frames after a sequence gap whose match failures are blamed on the gap.
+const GAP_SETTLE_FRAMES: u32 = 2;
+
+#[derive(Debug, Clone, Default)]
+pub struct DropCounters {
+    /// Frames delivered by the driver.
+    pub frames: u64,
+    /// Sequence gaps, and the frames missing in them.
+    pub sequence_gaps: u64,
+    pub lost_frames: u64,
+    /// Frames flagged as erroneous by the driver.
+    pub error_frames: u64,
+    pub matched: u64,
+    /// Unmatched frames that were corrupted or followed a sequence gap.
+    pub unmatched_transport: u64,
+    /// Unmatched clean frames.
+    pub unmatched_association: u64,
+    last_sequence: Option<u32>,
+    frames_since_gap: Option<u32>,
+    suspect: bool,
+}
+
+impl DropCounters {
+    /// Records a delivered frame; returns the number of frames lost right before it, if any.
+    pub fn on_frame(&mut self, sequence: Option<u32>, driver_error: bool) -> Option<u32> {
+        self.frames += 1;
+        if driver_error {
+            self.error_frames += 1;
+        }
+        self.frames_since_gap = self.frames_since_gap.map(|n| n + 1).filter(|n| *n <= GAP_SETTLE_FRAMES);
+
+        let mut lost = None;
+        if let (Some(last), Some(sequence)) = (self.last_sequence, sequence) {
+            let step = sequence.wrapping_sub(last);
+            // A step back or a huge jump is a stream restart, not a gap
+            if step > 1 && step < u32::MAX / 2 {
+                self.sequence_gaps += 1;
+                self.lost_frames += (step - 1) as u64;
+                self.frames_since_gap = Some(0);
+                lost = Some(step - 1);
+            }
+        }
+        if sequence.is_some() {
+            self.last_sequence = sequence;
+        }
+        self.suspect = driver_error || self.frames_since_gap.is_some();
+        lost
+    }
+
+    /// Forgets the driver sequence: a reopened stream counts its own (see `stall`).
+    pub fn restart(&mut self) {
+        self.last_sequence = None;
+        self.frames_since_gap = None;
+    }
+
+    /// Records the match result of the last frame.
+    pub fn on_match(&mut self, matched: bool) {
+        if matched {
+            self.matched += 1;
+        } else if self.suspect {
+            self.unmatched_transport += 1;
+        } else {
+            self.unmatched_association += 1;
+        }
+    }
+
+    /// True once the driver reported any loss or corruption.
+    pub fn has_transport_loss(&self) -> bool {
+        self.lost_frames > 0 || self.error_frames > 0
+    }
+}
+
+impl fmt::Display for DropCounters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "DROPS frames={} | transport: lost={} in {} gaps, corrupted={} | unmatched: {} transport, {} association",
+            self.frames,
+            self.lost_frames,
+            self.sequence_gaps,
+            self.error_frames,
+            self.unmatched_transport,
+            self.unmatched_association
+        )
+    }
+}
+
+/// Triggers missing between two received ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TriggerGap {
+    /// Last id received before the gap.
+    pub after: u64,
+    /// First id received after the gap.
+    pub before: u64,
+    pub missing: u64,
+}
+
+impl fmt::Display for TriggerGap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} triggers missing between id={} and id={}", self.missing, self.after, self.before)
+    }
+}
+
+/// Continuity of the trigger ids a consumer received and matched.
+#[derive(Debug, Clone)]
+pub struct TriggerGaps {
+    // Id step between consecutive triggers of the stream
+    step: u64,
+    // Triggers per processed frame (the skip ratio)
+    frame_step: u64,
+    pub received: u64,
+    /// Gaps in the received ids, and the triggers missing in them.
+    pub gaps: u64,
+    pub missing_triggers: u64,
+    /// Backward steps of the ids (publisher restarted).
+    pub restarts: u64,
+    pub matched: u64,
+    /// Frames neither matched nor delivered unmatched between two matches.
+    pub lost_frames: u64,
+    last_received: Option<u64>,
+    last_matched: Option<u64>,
+    unmatched_since_match: u64,
+}
+
+impl Default for TriggerGaps {
+    fn default() -> Self {
+        Self::new(1, 1)
+    }
+}
+
+impl TriggerGaps {
+    /// `step` is the id step of the stream (a sensor's divider), `frame_step` the triggers per processed frame.
+    pub fn new(step: u64, frame_step: u64) -> Self {
+        Self {
+            step: step.max(1),
+            frame_step: frame_step.max(1),
+            received: 0,
+            gaps: 0,
+            missing_triggers: 0,
+            restarts: 0,
+            matched: 0,
+            lost_frames: 0,
+            last_received: None,
+            last_matched: None,
+            unmatched_since_match: 0,
+        }
+    }
+
+    /// Changes the triggers per processed frame, e.g. when the skip ratio follows the load.
+    pub fn set_frame_step(&mut self, frame_step: u64) {
+        self.frame_step = frame_step.max(1);
+    }
+
+    /// Records a received trigger id (canaries excluded); returns the gap before it, if any.
+    pub fn on_trigger(&mut self, trigger_id: u64) -> Option<TriggerGap> {
+        self.received += 1;
+        let last = self.last_received.replace(trigger_id);
+        let last = last?;
+        if trigger_id < last {
+            // Ids start over, as do the matches
+            self.restarts += 1;
+            self.last_matched = None;
+            self.unmatched_since_match = 0;
+            return None;
+        }
+        let missing = ((trigger_id - last) / self.step).saturating_sub(1);
+        // Also a repeated id, which the matcher reports as duplicate
+        if missing == 0 {
+            return None;
+        }
+        self.gaps += 1;
+        self.missing_triggers += missing;
+        Some(TriggerGap { after: last, before: trigger_id, missing })
+    }
+
+    /// Records the match result of a processed frame; returns the frames lost since the previous match, if any.
+    pub fn on_frame(&mut self, matched: Option<u64>) -> Option<u64> {
+        let Some(trigger_id) = matched else {
+            self.unmatched_since_match += 1;
+            return None;
+        };
+        self.matched += 1;
+        let unmatched = std::mem::take(&mut self.unmatched_since_match);
+        let last = self.last_matched.replace(trigger_id.max(self.last_matched.unwrap_or(0)))?;
+        let frames = trigger_id.saturating_sub(last) / (self.step * self.frame_step);
+        let lost = frames.saturating_sub(1 + unmatched);
+        if lost == 0 {
+            return None;
+        }
+        self.lost_frames += lost;
+        Some(lost)
+    }
+
+    /// Forgets the last match: a reopened stream lost the frames in between (see `stall`).
+    pub fn restart(&mut self) {
+        self.last_matched = None;
+        self.unmatched_since_match = 0;
+    }
+
+    /// True once any trigger was missing or a frame was lost between matches.
+    pub fn has_loss(&self) -> bool {
+        self.missing_triggers > 0 || self.lost_frames > 0
+    }
+}
+
+impl fmt::Display for TriggerGaps {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "TRIGGER GAPS received={} | missing={} in {} gaps, restarts={} | frames lost between {} matches={}",
+            self.received, self.missing_triggers, self.gaps, self.restarts, self.matched, self.lost_frames
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unmatched_frames_are_split_by_cause() {
+        let mut drops = DropCounters::default();
+        let frame = |drops: &mut DropCounters, sequence: u32, error: bool, matched: bool| {
+            let lost = drops.on_frame(Some(sequence), error);
+            drops.on_match(matched);
+            lost
+        };
+        assert_eq!(frame(&mut drops, 10, false, true), None);
+        assert_eq!(frame(&mut drops, 11, false, false), None);
+        // Three frames lost; the next two frames are blamed on the gap
+        assert_eq!(frame(&mut drops, 15, false, false), Some(3));
+        assert_eq!(frame(&mut drops, 16, false, false), None);
+        assert_eq!(frame(&mut drops, 17, false, false), None);
+        assert_eq!(frame(&mut drops, 18, true, false), None);
+        assert_eq!(frame(&mut drops, 19, false, true), None);
+
+        assert_eq!((drops.frames, drops.sequence_gaps, drops.lost_frames, drops.error_frames), (7, 1, 3, 1));
+        assert_eq!((drops.matched, drops.unmatched_transport, drops.unmatched_association), (2, 4, 1));
+        assert!(drops.has_transport_loss());
+    }
+
+    #[test]
+    fn restarted_streams_are_not_gaps() {
+        let mut drops = DropCounters::default();
+        drops.on_frame(Some(500), false);
+        assert_eq!(drops.on_frame(Some(0), false), None);
+        drops.restart();
+        assert_eq!(drops.on_frame(Some(40), false), None);
+        assert_eq!(drops.on_frame(None, false), None);
+        assert!(!drops.has_transport_loss());
+    }
+
+    #[test]
+    fn gaps_in_received_ids_are_missing_triggers() {
+        let mut gaps = TriggerGaps::default();
+        assert_eq!(gaps.on_trigger(1), None);
+        assert_eq!(gaps.on_trigger(2), None);
+        assert_eq!(gaps.on_trigger(5), Some(TriggerGap { after: 2, before: 5, missing: 2 }));
+        // Repeated, then a restarted publisher
+        assert_eq!(gaps.on_trigger(5), None);
+        assert_eq!(gaps.on_trigger(1), None);
+        assert_eq!(gaps.on_trigger(2), None);
+        assert_eq!((gaps.received, gaps.gaps, gaps.missing_triggers, gaps.restarts), (6, 1, 2, 1));
+
+        // A sensor fired on every 3rd trigger: ids 1, 4, 7, ...
+        let mut sensor = TriggerGaps::new(3, 1);
+        assert_eq!(sensor.on_trigger(1), None);
+        assert_eq!(sensor.on_trigger(4), None);
+        assert_eq!(sensor.on_trigger(13).map(|gap| gap.missing), Some(2));
+    }
+
+    #[test]
+    fn frames_between_matches_are_lost_unless_seen_unmatched() {
+        let mut gaps = TriggerGaps::default();
+        assert_eq!(gaps.on_frame(Some(10)), None);
+        assert_eq!(gaps.on_frame(Some(11)), None);
+        // Matched 14 after 11: frames of 12 and 13 lost
+        assert_eq!(gaps.on_frame(Some(14)), Some(2));
+        // One of the two frames in between was delivered but not matched
+        assert_eq!(gaps.on_frame(None), None);
+        assert_eq!(gaps.on_frame(Some(17)), Some(1));
+        assert_eq!((gaps.matched, gaps.lost_frames), (4, 3));
+        assert!(gaps.has_loss());
+
+        // Processing every 2nd frame, matches advance by 2
+        let mut skipping = TriggerGaps::new(1, 2);
+        assert_eq!(skipping.on_frame(Some(2)), None);
+        assert_eq!(skipping.on_frame(Some(4)), None);
+        assert_eq!(skipping.on_frame(Some(10)), Some(2));
+        skipping.restart();
+        assert_eq!(skipping.on_frame(Some(30)), None);
+    }
+}
@@ -0,0 +1,102 @@
+// Encoder (wheel/motor) tick stream, interpolated at trigger timestamps.
+//
+// A quadrature encoder counter publishes its accumulated tick count with a
+// timestamp in the trigger clock domain on the `Motion/Encoder` service.
+// `EncoderTrack::position` answers "where was the axis at this hw_ts", which
+// line-scan and vehicle-mounted captures need to place every frame along the
+// path. Counts are signed so reversing axes work; the counter must be
+// unwrapped (64 bit) before publishing.
+
+#[cfg(feature = "iceoryx2")]
+use iceoryx2::prelude::ZeroCopySend;
+use std::collections::VecDeque;
+
+/// Encoder samples kept for interpolation, several seconds at kHz rates.
+const TRACK_CAPACITY: usize = 8192;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "iceoryx2", derive(ZeroCopySend))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EncoderSample {
+    /// Trigger clock domain, ns.
+    pub timestamp_ns: u64,
+    /// Accumulated quadrature ticks since the counter started.
+    pub ticks: i64,
+}
+
+/// Recent encoder samples in timestamp order.
+#[derive(Debug, Clone)]
+pub struct EncoderTrack {
+    samples: VecDeque<EncoderSample>,
+    /// Ticks per unit of travel (e.g. per mm), 1.0 to report raw ticks.
+    ticks_per_unit: f64,
+}
+
+impl Default for EncoderTrack {
+    fn default() -> Self {
+        Self::new(1.0)
+    }
+}
+
+impl EncoderTrack {
+    pub fn new(ticks_per_unit: f64) -> Self {
+        Self {
+            samples: VecDeque::new(),
+            ticks_per_unit,
+        }
+    }
+
+    pub fn ticks_per_unit(&self) -> f64 {
+        self.ticks_per_unit
+    }
+
+    /// Adds a sample; samples older than the latest one are dropped.
+    pub fn push(&mut self, sample: EncoderSample) {
+        if self.samples.back().is_some_and(|last| sample.timestamp_ns < last.timestamp_ns) {
+            return;
+        }
+        if self.samples.len() == TRACK_CAPACITY {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    /// Interpolated tick count at `timestamp_ns`, `None` outside the covered time range.
+    pub fn ticks_at(&self, timestamp_ns: u64) -> Option<f64> {
+        let after = self.samples.partition_point(|s| s.timestamp_ns < timestamp_ns);
+        let next = *self.samples.get(after)?;
+        if next.timestamp_ns == timestamp_ns {
+            return Some(next.ticks as f64);
+        }
+        let prev = *self.samples.get(after.checked_sub(1)?)?;
+        let t = (timestamp_ns - prev.timestamp_ns) as f64 / (next.timestamp_ns - prev.timestamp_ns) as f64;
+        Some(prev.ticks as f64 + (next.ticks - prev.ticks) as f64 * t)
+    }
+
+    /// Axis position at `timestamp_ns` in units of travel.
+    pub fn position(&self, timestamp_ns: u64) -> Option<f64> {
+        self.ticks_at(timestamp_ns).map(|ticks| ticks / self.ticks_per_unit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn positions_are_interpolated_in_units_of_travel() {
+        let mut track = EncoderTrack::new(4.0);
+        track.push(EncoderSample { timestamp_ns: 1_000, ticks: 100 });
+        track.push(EncoderSample { timestamp_ns: 2_000, ticks: 200 });
+        // A reversing axis counts down
+        track.push(EncoderSample { timestamp_ns: 3_000, ticks: 120 });
+
+        assert_eq!(track.ticks_at(1_500), Some(150.0));
+        assert_eq!(track.ticks_at(2_500), Some(160.0));
+        assert_eq!(track.position(1_500), Some(37.5));
+        assert_eq!(track.position(3_000), Some(30.0));
+        assert_eq!(track.position(500), None);
+        assert_eq!(track.position(3_001), None);
+    }
+}
@@ -0,0 +1,151 @@
+// Export of sync events for correlation with kernel logs and system traces.
+//
+// Every event carries CLOCK_MONOTONIC (the clock of `dmesg` and of journald's
+// `__MONOTONIC_TIMESTAMP`) next to CLOCK_REALTIME (the trigger clock domain),
+// so a USB reset in the kernel log lines up with the unmatched frames and
+// dropped triggers it caused. `--event-log journald` sends structured entries
+// over journald's native protocol (fields `SYNC_EVENT`, `SYNC_MONOTONIC_NS`,
+// `SYNC_REALTIME_NS` and one `SYNC_<KEY>` per event field; query with
+// `journalctl -o verbose SYNC_EVENT=unmatched_frame`). Any other value is a
+// file that gets dmesg style lines, which sort together with `dmesg` output:
+//
+//   [ 8231.504112] subscriber: unmatched_frame frame_ns=1733... realtime_ns=1733...
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+#[cfg(target_os = "linux")]
+use std::os::unix::net::UnixDatagram;
+
+use crate::clock;
+
+#[cfg(target_os = "linux")]
+const JOURNALD_SOCKET: &str = "/run/systemd/journal/socket";
+
+#[derive(Default)]
+enum Sink {
+    /// Drops all events (no `--event-log`).
+    #[default]
+    Disabled,
+    #[cfg(target_os = "linux")]
+    Journald(UnixDatagram),
+    File(BufWriter<File>),
+}
+
+#[derive(Default)]
+pub struct EventLog {
+    sink: Sink,
+    /// Process name, `SYSLOG_IDENTIFIER` in the journal.
+    identifier: String,
+}
+
+impl EventLog {
+    /// Opens `journald` or appends to the file at `target`.
+    pub fn open(target: &str, identifier: &str) -> io::Result<Self> {
+        let sink = match target {
+            #[cfg(target_os = "linux")]
+            "journald" => {
+                let socket = UnixDatagram::unbound()?;
+                socket.connect(JOURNALD_SOCKET)?;
+                Sink::Journald(socket)
+            }
+            path => Sink::File(BufWriter::new(OpenOptions::new().create(true).append(true).open(path)?)),
+        };
+        Ok(Self {
+            sink,
+            identifier: identifier.to_string(),
+        })
+    }
+
+    /// Records an event; `warning` marks anomalies (journal priority 4 instead of 6).
+    pub fn event(&mut self, event: &str, warning: bool, fields: &[(&str, String)]) -> io::Result<()> {
+        if matches!(self.sink, Sink::Disabled) {
+            return Ok(());
+        }
+        let monotonic_ns = clock::monotonic_now_ns();
+        let realtime_ns = clock::realtime_now_ns();
+        let summary: String = fields.iter().map(|(key, value)| format!(" {}={}", key, quote(value))).collect();
+        match &mut self.sink {
+            Sink::Disabled => Ok(()),
+            #[cfg(target_os = "linux")]
+            Sink::Journald(socket) => {
+                let mut entry = Vec::new();
+                journal_field(&mut entry, "MESSAGE", &format!("{}{}", event, summary));
+                journal_field(&mut entry, "PRIORITY", if warning { "4" } else { "6" });
+                journal_field(&mut entry, "SYSLOG_IDENTIFIER", &self.identifier);
+                journal_field(&mut entry, "SYNC_EVENT", event);
+                journal_field(&mut entry, "SYNC_MONOTONIC_NS", &monotonic_ns.to_string());
+                journal_field(&mut entry, "SYNC_REALTIME_NS", &realtime_ns.to_string());
+                for (key, value) in fields {
+                    journal_field(&mut entry, &format!("SYNC_{}", key.to_ascii_uppercase()), value);
+                }
+                socket.send(&entry)?;
+                Ok(())
+            }
+            Sink::File(file) => {
+                writeln!(
+                    file,
+                    "[{:>5}.{:06}] {}: {}{} realtime_ns={}",
+                    monotonic_ns / 1_000_000_000,
+                    monotonic_ns % 1_000_000_000 / 1_000,
+                    self.identifier,
+                    event,
+                    summary,
+                    realtime_ns
+                )?;
+                file.flush()
+            }
+        }
+    }
+}
+
+/// Quotes values with whitespace so `key=value` pairs stay parseable.
+fn quote(value: &str) -> String {
+    if value.contains(char::is_whitespace) {
+        format!("{:?}", value)
+    } else {
+        value.to_string()
+    }
+}
+
+/// Appends one field in journald's native format (length prefixed when the value spans lines).
+#[cfg(target_os = "linux")]
+fn journal_field(entry: &mut Vec<u8>, key: &str, value: &str) {
+    entry.extend_from_slice(key.as_bytes());
+    if value.contains('\n') {
+        entry.push(b'\n');
+        entry.extend_from_slice(&(value.len() as u64).to_le_bytes());
+    } else {
+        entry.push(b'=');
+    }
+    entry.extend_from_slice(value.as_bytes());
+    entry.push(b'\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_logs_get_dmesg_style_lines() {
+        let path = std::env::temp_dir().join(format!("camera_sync_eventlog_{}.log", std::process::id()));
+        let mut log = EventLog::open(path.to_str().unwrap(), "subscriber").unwrap();
+        log.event("unmatched_frame", true, &[("frame_ns", "42".to_string()), ("cause", "no trigger".to_string())]).unwrap();
+        EventLog::default().event("ignored", false, &[]).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let (monotonic, rest) = text.trim_end().split_once("] ").unwrap();
+        assert!(monotonic.starts_with('[') && monotonic[1..].trim().split_once('.').is_some(), "{}", text);
+        assert!(rest.starts_with("subscriber: unmatched_frame frame_ns=42 cause=\"no trigger\" realtime_ns="), "{}", text);
+        assert_eq!(text.lines().count(), 1);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn multi_line_journal_fields_are_length_prefixed() {
+        let mut entry = Vec::new();
+        journal_field(&mut entry, "SYNC_EVENT", "stall");
+        journal_field(&mut entry, "MESSAGE", "a\nb");
+        assert_eq!(entry, b"SYNC_EVENT=stall\nMESSAGE\n\x03\0\0\0\0\0\0\0a\nb\n");
+    }
+}
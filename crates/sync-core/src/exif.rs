@@ -0,0 +1,227 @@
+// EXIF and XMP metadata of exported stills.
+//
+// Every exported image carries its synchronization data, so a single file
+// copied out of the dataset still says which trigger it belongs to:
+//
+//   EXIF  DateTimeOriginal + SubSecTimeOriginal (9 digits) + OffsetTimeOriginal
+//         = trigger hw_ts in UTC, ExposureTime, Model (camera name),
+//         BodySerialNumber, ImageUniqueID (trigger id as 32 hex digits)
+//   XMP   sync:TriggerId, sync:HardwareTimestampNs, sync:ExposureUs,
+//         sync:CameraSerial, sync:Session, xmpDM:startTimecode (SMPTE
+//         timecode, if the session recorded one)
+//
+// EXIF readers (exiftool, image viewers) show the time with sub-second
+// precision; the XMP fields keep the exact integers for scripts.
+
+use crate::export::utc_datetime;
+use crate::timecode::{Timecode, TimecodeRate};
+use crate::units::Micros;
+
+/// XMP namespace of the synchronization fields.
+pub const XMP_NAMESPACE: &str = "urn:camera-sync:xmp:1.0:";
+
+/// Adobe XMP dynamic media namespace (`xmpDM:startTimecode`).
+pub const XMP_DYNAMIC_MEDIA_NAMESPACE: &str = "http://ns.adobe.com/xmp/1.0/DynamicMedia/";
+
+const ASCII: u16 = 2;
+const LONG: u16 = 4;
+const RATIONAL: u16 = 5;
+const UNDEFINED: u16 = 7;
+
+#[derive(Debug, Clone, Default)]
+pub struct StillMetadata {
+    pub trigger_id: u64,
+    pub hw_timestamp_ns: u64,
+    pub exposure: Option<Micros>,
+    pub camera_name: Option<String>,
+    pub camera_serial: Option<String>,
+    /// Session directory name.
+    pub session: String,
+    /// SMPTE timecode of the trigger and the rate it counts in.
+    pub timecode: Option<(Timecode, TimecodeRate)>,
+}
+
+enum Value {
+    Ascii(String),
+    Long(u32),
+    Rational(u32, u32),
+    Undefined(Vec<u8>),
+}
+
+impl StillMetadata {
+    /// Little endian TIFF structure as stored in a PNG `eXIf` chunk
+    /// (JPEG APP1 adds the `Exif\0\0` prefix).
+    pub fn exif(&self) -> Vec<u8> {
+        // `YYYY-MM-DD HH:MM:SS.nnnnnnnnn` -> `YYYY:MM:DD HH:MM:SS` and `nnnnnnnnn`
+        let datetime = utc_datetime(self.hw_timestamp_ns);
+        let (seconds, subseconds) = datetime.split_once('.').unwrap_or((&datetime, ""));
+        let seconds = seconds.replacen('-', ":", 2);
+
+        let mut ifd0 = Vec::new();
+        if let Some(name) = &self.camera_name {
+            ifd0.push((0x0110, Value::Ascii(name.clone())));
+        }
+        ifd0.push((0x0132, Value::Ascii(seconds.clone())));
+        // Offset of the Exif IFD, patched below
+        ifd0.push((0x8769, Value::Long(0)));
+
+        let mut exif_ifd = Vec::new();
+        if let Some(exposure) = self.exposure {
+            exif_ifd.push((0x829a, Value::Rational(exposure.0 as u32, 1_000_000)));
+        }
+        // Exif 2.31 introduced the offset time tags
+        exif_ifd.push((0x9000, Value::Undefined(b"0231".to_vec())));
+        exif_ifd.push((0x9003, Value::Ascii(seconds)));
+        exif_ifd.push((0x9011, Value::Ascii("+00:00".to_string())));
+        exif_ifd.push((0x9291, Value::Ascii(subseconds.to_string())));
+        exif_ifd.push((0xa420, Value::Ascii(format!("{:032x}", self.trigger_id))));
+        if let Some(serial) = &self.camera_serial {
+            exif_ifd.push((0xa431, Value::Ascii(serial.clone())));
+        }
+
+        let mut tiff = vec![b'I', b'I', 42, 0, 8, 0, 0, 0];
+        let exif_offset = 8 + ifd_len(&ifd0);
+        if let Some((_, value)) = ifd0.iter_mut().find(|(tag, _)| *tag == 0x8769) {
+            *value = Value::Long(exif_offset as u32);
+        }
+        write_ifd(&mut tiff, &ifd0);
+        write_ifd(&mut tiff, &exif_ifd);
+        tiff
+    }
+
+    /// XMP packet as stored in a PNG `iTXt` chunk with keyword `XML:com.adobe.xmp`.
+    pub fn xmp(&self) -> String {
+        let mut fields = format!(
+            "   <sync:TriggerId>{}</sync:TriggerId>\n   <sync:HardwareTimestampNs>{}</sync:HardwareTimestampNs>\n   <sync:Session>{}</sync:Session>\n",
+            self.trigger_id,
+            self.hw_timestamp_ns,
+            escape(&self.session)
+        );
+        if let Some(exposure) = self.exposure {
+            fields.push_str(&format!("   <sync:ExposureUs>{}</sync:ExposureUs>\n", exposure.0));
+        }
+        if let Some(serial) = &self.camera_serial {
+            fields.push_str(&format!("   <sync:CameraSerial>{}</sync:CameraSerial>\n", escape(serial)));
+        }
+        // Adobe's dynamic media schema, which video and editing tools read timecodes from
+        if let Some((timecode, rate)) = &self.timecode {
+            fields.push_str(&format!(
+                "   <xmpDM:startTimecode rdf:parseType=\"Resource\">\n    <xmpDM:timeValue>{}</xmpDM:timeValue>\n    <xmpDM:timeFormat>{}</xmpDM:timeFormat>\n   </xmpDM:startTimecode>\n",
+                timecode,
+                rate.xmp_time_format()
+            ));
+        }
+        format!(
+            "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n  <rdf:Description rdf:about=\"\" xmlns:sync=\"{}\" xmlns:xmpDM=\"{}\">\n{}  </rdf:Description>\n </rdf:RDF>\n</x:xmpmeta>\n<?xpacket end=\"w\"?>",
+            XMP_NAMESPACE, XMP_DYNAMIC_MEDIA_NAMESPACE, fields
+        )
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+fn value_len(value: &Value) -> usize {
+    match value {
+        Value::Ascii(text) => text.len() + 1,
+        Value::Long(_) => 4,
+        Value::Rational(..) => 8,
+        Value::Undefined(bytes) => bytes.len(),
+    }
+}
+
+/// Entry count, entries, next IFD offset, then the values that do not fit into an entry.
+fn ifd_len(entries: &[(u16, Value)]) -> usize {
+    let external: usize = entries.iter().map(|(_, value)| value_len(value)).filter(|&len| len > 4).map(|len| len + len % 2).sum();
+    2 + 12 * entries.len() + 4 + external
+}
+
+/// Appends an IFD at the end of `tiff` (offsets are relative to the TIFF header).
+fn write_ifd(tiff: &mut Vec<u8>, entries: &[(u16, Value)]) {
+    let mut data_offset = tiff.len() + 2 + 12 * entries.len() + 4;
+    let mut data = Vec::new();
+    tiff.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    for (tag, value) in entries {
+        let (kind, count, bytes) = match value {
+            Value::Ascii(text) => (ASCII, text.len() as u32 + 1, [text.as_bytes(), &[0]].concat()),
+            Value::Long(value) => (LONG, 1, value.to_le_bytes().to_vec()),
+            Value::Rational(numerator, denominator) => (RATIONAL, 1, [numerator.to_le_bytes(), denominator.to_le_bytes()].concat()),
+            Value::Undefined(bytes) => (UNDEFINED, bytes.len() as u32, bytes.clone()),
+        };
+        tiff.extend_from_slice(&tag.to_le_bytes());
+        tiff.extend_from_slice(&kind.to_le_bytes());
+        tiff.extend_from_slice(&count.to_le_bytes());
+        if bytes.len() <= 4 {
+            let mut inline = [0u8; 4];
+            inline[..bytes.len()].copy_from_slice(&bytes);
+            tiff.extend_from_slice(&inline);
+        } else {
+            tiff.extend_from_slice(&(data_offset as u32).to_le_bytes());
+            data.extend_from_slice(&bytes);
+            // Values start on word boundaries
+            if bytes.len() % 2 == 1 {
+                data.push(0);
+            }
+            data_offset += bytes.len() + bytes.len() % 2;
+        }
+    }
+    // No next IFD
+    tiff.extend_from_slice(&0u32.to_le_bytes());
+    tiff.extend_from_slice(&data);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u16_at(tiff: &[u8], at: usize) -> u16 {
+        u16::from_le_bytes([tiff[at], tiff[at + 1]])
+    }
+
+    fn u32_at(tiff: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes(tiff[at..at + 4].try_into().unwrap())
+    }
+
+    // Value bytes of `tag` in the IFD at `ifd`, read back like an EXIF reader does
+    fn tag(tiff: &[u8], ifd: usize, tag: u16) -> Option<Vec<u8>> {
+        (0..u16_at(tiff, ifd) as usize).map(|i| ifd + 2 + 12 * i).find(|&entry| u16_at(tiff, entry) == tag).map(|entry| {
+            let size = match u16_at(tiff, entry + 2) {
+                RATIONAL => 8,
+                LONG => 4,
+                _ => 1,
+            };
+            let len = size * u32_at(tiff, entry + 4) as usize;
+            let at = if len <= 4 { entry + 8 } else { u32_at(tiff, entry + 8) as usize };
+            tiff[at..at + len].to_vec()
+        })
+    }
+
+    #[test]
+    fn exif_carries_the_trigger_time_and_identity() {
+        let still = StillMetadata {
+            trigger_id: 0x2a,
+            hw_timestamp_ns: 1_709_294_400_033_333_333,
+            exposure: Some(Micros(5_000)),
+            camera_name: Some("cam0".to_string()),
+            camera_serial: Some("ABC123".to_string()),
+            session: "run <1>".to_string(),
+            timecode: None,
+        };
+        let tiff = still.exif();
+        assert_eq!(&tiff[..8], b"II*\0\x08\0\0\0");
+        assert_eq!(tag(&tiff, 8, 0x0110).unwrap(), b"cam0\0");
+        let exif_ifd = u32_at(&tag(&tiff, 8, 0x8769).unwrap(), 0) as usize;
+        assert_eq!(tag(&tiff, exif_ifd, 0x9003).unwrap(), b"2024:03:01 12:00:00\0");
+        assert_eq!(tag(&tiff, exif_ifd, 0x9291).unwrap(), b"033333333\0");
+        assert_eq!(tag(&tiff, exif_ifd, 0xa420).unwrap(), format!("{:032x}\0", 42).as_bytes());
+        assert_eq!(tag(&tiff, exif_ifd, 0xa431).unwrap(), b"ABC123\0");
+        assert_eq!(tag(&tiff, exif_ifd, 0x829a).unwrap(), [5_000u32.to_le_bytes(), 1_000_000u32.to_le_bytes()].concat());
+
+        let xmp = still.xmp();
+        assert!(xmp.contains("<sync:TriggerId>42</sync:TriggerId>"), "{}", xmp);
+        assert!(xmp.contains("<sync:HardwareTimestampNs>1709294400033333333</sync:HardwareTimestampNs>"), "{}", xmp);
+        assert!(xmp.contains("<sync:Session>run &lt;1&gt;</sync:Session>"), "{}", xmp);
+        assert!(!xmp.contains("xmpDM:startTimecode"), "{}", xmp);
+    }
+}
@@ -0,0 +1,280 @@
+// Export of recorded sessions into common dataset layouts.
+//
+// Every session directory becomes one camera, in the order given. Frames are
+// stamped with the hardware timestamp of their matched trigger, the point of
+// the whole rig, so pipelines consume the synchronized times directly.
+//
+//   euroc: mav0/cam<N>/data/<hw_ts>.png and mav0/cam<N>/data.csv
+//          (`#timestamp [ns],filename`, ASL/EuRoC MAV format)
+//   kitti: image_<NN>/data/<index:010>.png and image_<NN>/timestamps.txt
+//          (`YYYY-MM-DD HH:MM:SS.nnnnnnnnn` UTC, KITTI raw format)
+//
+// Images come from the session's `frames/` (recorded with `--save-frames`);
+// sessions without saved frames export their timestamps only. Every PNG embeds
+// its trigger id, hw_ts, exposure, camera and SMPTE timecode (if recorded) as
+// EXIF/XMP (see `exif`). Sessions with intrinsics (`--camera-info`) also get a
+// EuRoC `sensor.yaml` (pinhole model, identity extrinsics). The rig has no
+// IMU, so no `imu0` is written.
+
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::exif::StillMetadata;
+use crate::intrinsics::{self, CameraIntrinsics};
+use crate::leap::Tai;
+use crate::manifest::SessionManifest;
+use crate::png;
+use crate::session::{self, MatchRecord, FRAMES_DIR};
+use crate::timecode::{Timecode, TimecodeRate};
+use crate::units::{Micros, Nanos};
+use crate::CameraTrigger;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DatasetFormat {
+    Euroc,
+    Kitti,
+}
+
+impl FromStr for DatasetFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "euroc" => Ok(Self::Euroc),
+            "kitti" => Ok(Self::Kitti),
+            _ => Err(format!("unknown dataset format '{}' (expected euroc or kitti)", s)),
+        }
+    }
+}
+
+impl fmt::Display for DatasetFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Euroc => "euroc",
+            Self::Kitti => "kitti",
+        })
+    }
+}
+
+/// What was exported for one session.
+#[derive(Debug, Clone)]
+pub struct CameraExport {
+    /// Session directory name.
+    pub session: String,
+    /// Camera directory relative to the output, e.g. `mav0/cam0`.
+    pub camera: String,
+    pub frames: usize,
+    pub images: usize,
+    /// Frames recorded with `--save-frames` whose image is missing.
+    pub missing_images: usize,
+    /// A `sensor.yaml` with intrinsics was written.
+    pub calibrated: bool,
+}
+
+/// Exports `session_dir` as camera `index` of the dataset in `out_dir`.
+pub fn export_session(format: DatasetFormat, session_dir: &Path, index: usize, out_dir: &Path) -> Result<CameraExport, Box<dyn std::error::Error>> {
+    let mut records = session::read_records(session_dir)?;
+    records.sort_by_key(|record| record.trigger.hw_timestamp_ns);
+    records.dedup_by_key(|record| record.trigger.frame_id);
+    let has_frames = session_dir.join(FRAMES_DIR).is_dir();
+
+    let camera = match format {
+        DatasetFormat::Euroc => format!("mav0/cam{}", index),
+        DatasetFormat::Kitti => format!("image_{:02}", index),
+    };
+    let data_dir = out_dir.join(&camera).join("data");
+    fs::create_dir_all(&data_dir)?;
+    let mut timestamps = BufWriter::new(File::create(out_dir.join(&camera).join(match format {
+        DatasetFormat::Euroc => "data.csv",
+        DatasetFormat::Kitti => "timestamps.txt",
+    }))?);
+    if format == DatasetFormat::Euroc {
+        writeln!(timestamps, "#timestamp [ns],filename")?;
+    }
+
+    let mut export = CameraExport {
+        session: session_dir
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| session_dir.display().to_string()),
+        camera,
+        frames: 0,
+        images: 0,
+        missing_images: 0,
+        calibrated: false,
+    };
+    let mut span_ns = None;
+    // Camera and exposure for the image metadata, if the session has a readable manifest
+    let manifest = SessionManifest::read(session_dir).ok();
+    let camera_entry = manifest.as_ref().and_then(|manifest| manifest.cameras().first());
+    let exposure = manifest
+        .as_ref()
+        .and_then(|manifest| manifest.config_value("exposure_us"))
+        .and_then(|value| value.parse().ok())
+        .map(Micros);
+    let timecode_rate = manifest
+        .as_ref()
+        .and_then(|manifest| manifest.config_value("timecode_rate"))
+        .and_then(|value| value.parse::<TimecodeRate>().ok());
+    for record in &records {
+        let CameraTrigger { frame_id: trigger_id, hw_timestamp_ns: hw_ts, .. } = record.trigger;
+        let file_name = match format {
+            DatasetFormat::Euroc => format!("{}.png", hw_ts),
+            DatasetFormat::Kitti => format!("{:010}.png", export.frames),
+        };
+        if has_frames {
+            let Some((width, height, rgb)) = session::read_frame(session_dir, trigger_id)? else {
+                // Keep the image list consistent with the files on disk
+                export.missing_images += 1;
+                continue;
+            };
+            let still = StillMetadata {
+                trigger_id,
+                hw_timestamp_ns: hw_ts,
+                exposure,
+                camera_name: camera_entry.map(|camera| camera.name.clone()),
+                camera_serial: camera_entry.and_then(|camera| camera.serial.clone()),
+                session: export.session.clone(),
+                timecode: timecode_rate.and_then(|rate| Some((record_timecode(record)?, rate))),
+            };
+            png::write_rgb(&data_dir.join(&file_name), width, height, &rgb, Some(&still.exif()), Some(&still.xmp()))?;
+            export.images += 1;
+        }
+        match format {
+            DatasetFormat::Euroc => writeln!(timestamps, "{},{}", hw_ts, file_name)?,
+            DatasetFormat::Kitti => writeln!(timestamps, "{}", utc_datetime(hw_ts))?,
+        }
+        export.frames += 1;
+        span_ns = Some(span_ns.map_or((hw_ts, hw_ts), |(first, _)| (first, hw_ts)));
+    }
+    timestamps.flush()?;
+
+    if format == DatasetFormat::Euroc {
+        if let Some(intrinsics) = intrinsics::read_session(session_dir)? {
+            // On TAI, so a leap second inside the session does not stretch the rate
+            let rate_hz = match span_ns.map(|(first, last)| Tai::from_utc(Nanos(last)).ns_since(Tai::from_utc(Nanos(first)))) {
+                Some(span_ns) if span_ns > 0 => (export.frames - 1) as f64 * 1e9 / span_ns as f64,
+                _ => 0.0,
+            };
+            fs::write(out_dir.join(&export.camera).join("sensor.yaml"), euroc_sensor(&intrinsics, &export.session, rate_hz))?;
+            export.calibrated = true;
+        }
+    }
+    Ok(export)
+}
+
+/// EuRoC camera `sensor.yaml`. The body frame is the camera itself: the rig knows no extrinsics.
+// Timecode the capture app stored in the record's metadata (`--timecode-rate`)
+fn record_timecode(record: &MatchRecord) -> Option<Timecode> {
+    let (_, value) = record.metadata.iter().find(|(key, _)| key == "timecode")?;
+    value.parse().ok()
+}
+
+fn euroc_sensor(intrinsics: &CameraIntrinsics, session: &str, rate_hz: f64) -> String {
+    let [fx, fy, cx, cy] = intrinsics.pinhole();
+    let distortion_model = match intrinsics.distortion_model.as_str() {
+        "equidistant" => "equidistant",
+        _ => "radial-tangential",
+    };
+    let coefficients: Vec<String> = intrinsics.d.iter().take(4).map(|value| value.to_string()).collect();
+    format!(
+        "# General sensor definitions.\nsensor_type: camera\ncomment: {}\n\n# Sensor extrinsics wrt. the body-frame.\nT_BS:\n  cols: 4\n  rows: 4\n  data: [1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0]\n\n# Camera specific definitions.\nrate_hz: {:.3}\nresolution: [{}, {}]\ncamera_model: pinhole\nintrinsics: [{}, {}, {}, {}]\ndistortion_model: {}\ndistortion_coefficients: [{}]\n",
+        session,
+        rate_hz,
+        intrinsics.width,
+        intrinsics.height,
+        fx,
+        fy,
+        cx,
+        cy,
+        distortion_model,
+        coefficients.join(", ")
+    )
+}
+
+/// `YYYY-MM-DD HH:MM:SS.nnnnnnnnn` (UTC) of a CLOCK_REALTIME timestamp.
+pub fn utc_datetime(realtime_ns: u64) -> String {
+    let seconds = realtime_ns / 1_000_000_000;
+    let (days, second_of_day) = ((seconds / 86_400) as i64, seconds % 86_400);
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:09}",
+        year,
+        month,
+        day,
+        second_of_day / 3600,
+        second_of_day / 60 % 60,
+        second_of_day % 60,
+        realtime_ns % 1_000_000_000
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::SessionRecorder;
+    use crate::signing::{SignatureStatus, TriggerSignature};
+
+    // 2024-03-01 12:00:00 UTC
+    const HW_TS: u64 = 1_709_294_400_000_000_000;
+
+    fn record(trigger_id: u64, hw_ts: u64) -> MatchRecord {
+        MatchRecord {
+            trigger: CameraTrigger::new(trigger_id, hw_ts, hw_ts),
+            v4l2_timestamp_ns: hw_ts + 20_000_000,
+            total_latency_ms: 20.0,
+            score_ms: 0.0,
+            signature_status: SignatureStatus::Unsigned,
+            signature: TriggerSignature::default(),
+            metadata: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn sessions_export_in_trigger_time_order() {
+        let dir = std::env::temp_dir().join(format!("camera_sync_export_{}", std::process::id()));
+        let session_dir = dir.join("cam-left");
+        {
+            let mut recorder = SessionRecorder::create(&session_dir).unwrap().with_frames().unwrap();
+            for (trigger_id, hw_ts) in [(2, HW_TS + 33_333_333), (1, HW_TS), (2, HW_TS + 33_333_333)] {
+                recorder.record(&record(trigger_id, hw_ts)).unwrap();
+            }
+            recorder.record_frame(1, 2, 1, &[255, 0, 0, 0, 0, 255]).unwrap();
+        }
+
+        let euroc = export_session(DatasetFormat::Euroc, &session_dir, 0, &dir.join("euroc")).unwrap();
+        let kitti = export_session(DatasetFormat::Kitti, &session_dir, 1, &dir.join("kitti")).unwrap();
+        let data_csv = fs::read_to_string(dir.join("euroc/mav0/cam0/data.csv")).unwrap();
+        let timestamps = fs::read_to_string(dir.join("kitti/image_01/timestamps.txt")).unwrap();
+        let image = fs::read(dir.join(format!("euroc/mav0/cam0/data/{}.png", HW_TS))).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        // The duplicate is dropped, the frame without an image is left out of the image list
+        assert_eq!((euroc.frames, euroc.images, euroc.missing_images, euroc.calibrated), (1, 1, 1, false));
+        assert_eq!(data_csv, format!("#timestamp [ns],filename\n{0},{0}.png\n", HW_TS));
+        assert_eq!(kitti.camera, "image_01");
+        assert_eq!(timestamps, "2024-03-01 12:00:00.000000000\n");
+        assert!(image.starts_with(b"\x89PNG\r\n\x1a\n"));
+    }
+
+    #[test]
+    fn kitti_timestamps_are_utc_datetimes() {
+        assert_eq!(utc_datetime(0), "1970-01-01 00:00:00.000000000");
+        assert_eq!(utc_datetime(951_782_400_123_456_789), "2000-02-29 00:00:00.123456789");
+        assert_eq!(utc_datetime(HW_TS + 33_333_333), "2024-03-01 12:00:00.033333333");
+    }
+}
@@ -0,0 +1,461 @@
+// Multi-rig federation: trigger distribution across shared-memory domains.
+//
+// A shared-memory domain ends at the host. Large installations run one master
+// publisher whose `trigger_bridge send` fans the triggers out to relays
+// (`trigger_bridge relay`), one per rig. A relay republishes the triggers on
+// its rig's trigger service and may forward them to relays further down, so
+// the rigs form a tree. Relays do not need clocks synchronized with their
+// upstream: every probe interval each relay probes its upstream NTP-style
+// over the trigger link itself:
+//
+//   t1 probe sent (relay clock)       t2 probe received (upstream clock)
+//   t4 reply received (relay clock)   t3 reply sent (upstream clock)
+//
+//   offset     = ((t2 - t1) + (t3 - t4)) / 2      (upstream minus relay)
+//   round trip = (t4 - t1) - (t3 - t2)
+//
+// All four are SO_TIMESTAMPING stamps (see `netstamp`). t3 is only known
+// once the reply has left, so it travels with the next reply (like the
+// link_tx of triggers). Of the last `OFFSET_WINDOW` samples, the one with the
+// shortest round trip bounds the offset best; half its round trip is the
+// uncertainty. The relay shifts `hw_timestamp_ns` and `publish_timestamp_ns`
+// of every trigger by the offset into its own clock, so every rig stamps its
+// frames in a common timebase while each host keeps its own clock. Forwarded
+// triggers carry the relay's clock; the offsets of a tree add up hop by hop.
+//
+// Probes carry the relay's health up the tree: triggers received and lost
+// (gaps in the frame ids), the measured offset and the size of its subtree
+// with the number of unhealthy rigs in it, so the master reports the state of
+// the whole federation from the relays directly below it.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::net::SocketAddr;
+
+use crate::wire::{ProbeMessage, ProbeReply};
+
+/// Probe samples the offset is taken from, 2 s at the default probe interval.
+pub const OFFSET_WINDOW: usize = 8;
+/// Longest rig name carried in probes, in bytes.
+pub const MAX_RIG_NAME_LEN: usize = 32;
+/// A relay without probes for this long counts as lost.
+pub const RELAY_TIMEOUT_NS: u64 = 5_000_000_000;
+/// Unanswered probes remembered for late replies.
+const PENDING_PROBES: usize = 8;
+
+/// One probe exchange, ns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProbeSample {
+    /// t1, relay clock.
+    pub sent_ns: u64,
+    /// t2, upstream clock.
+    pub upstream_rx_ns: u64,
+    /// t3, upstream clock.
+    pub upstream_tx_ns: u64,
+    /// t4, relay clock.
+    pub received_ns: u64,
+}
+
+impl ProbeSample {
+    /// Upstream clock minus relay clock.
+    pub fn offset_ns(&self) -> i64 {
+        let outbound = self.upstream_rx_ns as i128 - self.sent_ns as i128;
+        let inbound = self.upstream_tx_ns as i128 - self.received_ns as i128;
+        ((outbound + inbound) / 2) as i64
+    }
+
+    pub fn round_trip_ns(&self) -> u64 {
+        let total = self.received_ns.saturating_sub(self.sent_ns);
+        total.saturating_sub(self.upstream_tx_ns.saturating_sub(self.upstream_rx_ns))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OffsetEstimate {
+    /// Upstream clock minus local clock.
+    pub offset_ns: i64,
+    /// Round trip of the sample the offset was taken from.
+    pub round_trip_ns: u64,
+    /// Largest error of the offset.
+    pub uncertainty_ns: u64,
+    /// Local time of the latest sample.
+    pub updated_ns: u64,
+}
+
+impl OffsetEstimate {
+    /// Converts an upstream timestamp into the local clock.
+    pub fn to_local(&self, upstream_ns: u64) -> u64 {
+        upstream_ns.saturating_add_signed(-self.offset_ns)
+    }
+
+    /// `+12.3us ±4.1us (round trip 180.0us)`
+    pub fn summary(&self) -> String {
+        format!(
+            "{:+.1}us ±{:.1}us (round trip {:.1}us)",
+            self.offset_ns as f64 / 1e3,
+            self.uncertainty_ns as f64 / 1e3,
+            self.round_trip_ns as f64 / 1e3
+        )
+    }
+}
+
+/// Minimum filter over the recent probe samples.
+#[derive(Debug, Clone, Default)]
+pub struct OffsetEstimator {
+    window: VecDeque<ProbeSample>,
+    estimate: Option<OffsetEstimate>,
+}
+
+impl OffsetEstimator {
+    pub fn on_sample(&mut self, sample: ProbeSample) -> OffsetEstimate {
+        if self.window.len() == OFFSET_WINDOW {
+            self.window.pop_front();
+        }
+        self.window.push_back(sample);
+        let best = self.window.iter().min_by_key(|sample| sample.round_trip_ns()).unwrap_or(&sample);
+        let estimate = OffsetEstimate {
+            offset_ns: best.offset_ns(),
+            round_trip_ns: best.round_trip_ns(),
+            uncertainty_ns: best.round_trip_ns() / 2,
+            updated_ns: sample.received_ns,
+        };
+        self.estimate = Some(estimate);
+        estimate
+    }
+
+    pub fn estimate(&self) -> Option<OffsetEstimate> {
+        self.estimate
+    }
+}
+
+/// Relay side of the probe exchange with the upstream bridge.
+#[derive(Debug, Clone, Default)]
+pub struct ProbeClient {
+    next_sequence: u64,
+    /// `(sequence, sent_ns)` of probes without reply, oldest first.
+    pending: VecDeque<(u64, u64)>,
+    /// Probe answered by the last reply: `(sequence, sent_ns, upstream_rx_ns, received_ns)`.
+    answered: Option<(u64, u64, u64, u64)>,
+    estimator: OffsetEstimator,
+    upstream_hops: Option<u32>,
+}
+
+impl ProbeClient {
+    /// Sequence for the next probe.
+    pub fn next_sequence(&mut self) -> u64 {
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        sequence
+    }
+
+    /// Records the send time of probe `sequence`.
+    pub fn on_sent(&mut self, sequence: u64, sent_ns: u64) {
+        if self.pending.len() == PENDING_PROBES {
+            self.pending.pop_front();
+        }
+        self.pending.push_back((sequence, sent_ns));
+    }
+
+    /// Handles a reply received at `received_ns`; returns the new estimate if the reply
+    /// completed a sample (it carries the send time of the previous reply).
+    pub fn on_reply(&mut self, reply: &ProbeReply, received_ns: u64) -> Option<OffsetEstimate> {
+        self.upstream_hops = Some(reply.hops);
+        let mut estimate = None;
+        if let (Some((tx_sequence, upstream_tx_ns)), Some((sequence, sent_ns, upstream_rx_ns, answered_ns))) = (reply.link_tx, self.answered) {
+            if tx_sequence == sequence {
+                estimate = Some(self.estimator.on_sample(ProbeSample {
+                    sent_ns,
+                    upstream_rx_ns,
+                    upstream_tx_ns,
+                    received_ns: answered_ns,
+                }));
+            }
+        }
+        self.answered = self
+            .pending
+            .iter()
+            .position(|(sequence, _)| *sequence == reply.sequence)
+            .and_then(|index| self.pending.remove(index))
+            .map(|(sequence, sent_ns)| (sequence, sent_ns, reply.probe_rx_ns, received_ns));
+        estimate
+    }
+
+    /// Whether a probe is still waiting for its reply.
+    pub fn awaiting_reply(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    pub fn estimate(&self) -> Option<OffsetEstimate> {
+        self.estimator.estimate()
+    }
+
+    /// Hops of this relay from the master, once the upstream answered.
+    pub fn hops(&self) -> Option<u32> {
+        self.upstream_hops.map(|hops| hops + 1)
+    }
+}
+
+/// Triggers received and lost, from gaps in the frame ids.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LossCounter {
+    last_frame_id: Option<u64>,
+    pub received: u64,
+    pub lost: u64,
+}
+
+impl LossCounter {
+    pub fn on_trigger(&mut self, frame_id: u64) {
+        self.received += 1;
+        // Ids going backwards mean the master restarted
+        if let Some(last) = self.last_frame_id.filter(|last| frame_id > *last) {
+            self.lost += frame_id - last - 1;
+        }
+        self.last_frame_id = Some(frame_id);
+    }
+}
+
+/// What a relay reports about itself and its subtree with every probe.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RigHealth {
+    pub rig: String,
+    /// Links between the master and this relay.
+    pub hops: u32,
+    pub received: u64,
+    pub lost: u64,
+    /// Offset to the upstream clock, once measured.
+    pub offset_ns: Option<i64>,
+    pub uncertainty_ns: Option<u64>,
+    /// Relays in the subtree, this one included.
+    pub rigs: u32,
+    /// Unhealthy relays in the subtree, this one included.
+    pub unhealthy: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelayState {
+    /// Configured, but no probe received yet.
+    Waiting,
+    Ok,
+    /// The relay or a relay below it is unhealthy.
+    Degraded,
+    /// No probe for `RELAY_TIMEOUT_NS`.
+    Lost,
+}
+
+impl fmt::Display for RelayState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RelayState::Waiting => write!(f, "waiting"),
+            RelayState::Ok => write!(f, "ok"),
+            RelayState::Degraded => write!(f, "degraded"),
+            RelayState::Lost => write!(f, "lost"),
+        }
+    }
+}
+
+/// A relay directly below this bridge.
+#[derive(Debug, Clone)]
+pub struct RelayStatus {
+    pub address: SocketAddr,
+    /// Health reported with the last probe.
+    pub health: Option<RigHealth>,
+    /// Local receive time of the last probe.
+    pub last_probe_ns: Option<u64>,
+    /// `(sequence, tx_timestamp_ns)` of the last reply sent.
+    last_reply_tx: Option<(u64, u64)>,
+}
+
+impl RelayStatus {
+    pub fn state(&self, now_ns: u64) -> RelayState {
+        match (&self.health, self.last_probe_ns) {
+            (Some(health), Some(last_probe_ns)) if now_ns.saturating_sub(last_probe_ns) < RELAY_TIMEOUT_NS => {
+                if health.unhealthy == 0 {
+                    RelayState::Ok
+                } else {
+                    RelayState::Degraded
+                }
+            }
+            (Some(_), _) => RelayState::Lost,
+            (None, _) => RelayState::Waiting,
+        }
+    }
+}
+
+/// Answers the probes of the relays below a bridge and keeps their health.
+#[derive(Debug, Clone)]
+pub struct FederationTable {
+    hops: u32,
+    relays: Vec<RelayStatus>,
+}
+
+impl FederationTable {
+    /// A bridge `hops` links below the master, forwarding to `relays`.
+    pub fn new(hops: u32, relays: impl IntoIterator<Item = SocketAddr>) -> Self {
+        let mut table = Self { hops, relays: Vec::new() };
+        for address in relays {
+            table.relay_mut(address);
+        }
+        table
+    }
+
+    pub fn set_hops(&mut self, hops: u32) {
+        self.hops = hops;
+    }
+
+    fn relay_mut(&mut self, address: SocketAddr) -> &mut RelayStatus {
+        let index = match self.relays.iter().position(|relay| relay.address == address) {
+            Some(index) => index,
+            None => {
+                self.relays.push(RelayStatus {
+                    address,
+                    health: None,
+                    last_probe_ns: None,
+                    last_reply_tx: None,
+                });
+                self.relays.len() - 1
+            }
+        };
+        &mut self.relays[index]
+    }
+
+    /// Records a probe from `from` received at `received_ns` and builds the reply.
+    pub fn on_probe(&mut self, from: SocketAddr, probe: &ProbeMessage, received_ns: u64) -> ProbeReply {
+        let hops = self.hops;
+        let relay = self.relay_mut(from);
+        relay.health = Some(probe.health.clone());
+        relay.last_probe_ns = Some(received_ns);
+        ProbeReply {
+            sequence: probe.sequence,
+            probe_rx_ns: received_ns,
+            hops,
+            link_tx: relay.last_reply_tx,
+        }
+    }
+
+    /// Records when the reply to probe `sequence` left for `to`.
+    pub fn on_reply_sent(&mut self, to: SocketAddr, sequence: u64, sent_ns: u64) {
+        self.relay_mut(to).last_reply_tx = Some((sequence, sent_ns));
+    }
+
+    pub fn relays(&self) -> &[RelayStatus] {
+        &self.relays
+    }
+
+    /// `(rigs, unhealthy)` below this bridge. Relays not heard from count as unhealthy, with
+    /// their whole last known subtree.
+    pub fn subtree(&self, now_ns: u64) -> (u32, u32) {
+        self.relays.iter().fold((0, 0), |(rigs, unhealthy), relay| match (&relay.health, relay.state(now_ns)) {
+            (Some(health), RelayState::Lost) => (rigs + health.rigs, unhealthy + health.rigs),
+            (Some(health), _) => (rigs + health.rigs, unhealthy + health.unhealthy),
+            (None, _) => (rigs + 1, unhealthy + 1),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wire::{ProbeMessage, WireMessage};
+
+    const START_NS: u64 = 1_700_000_000_000_000_000;
+
+    // Relay clock behind the master by 3.25ms
+    const OFFSET_NS: i64 = 3_250_000;
+
+    // Deterministic jitter in [0, amplitude]
+    struct Jitter(u64);
+
+    impl Jitter {
+        fn next(&mut self, amplitude_ns: u64) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0 % (amplitude_ns + 1)
+        }
+    }
+
+    fn address(text: &str) -> SocketAddr {
+        text.parse().unwrap()
+    }
+
+    fn health(rig: &str, rigs: u32, unhealthy: u32) -> RigHealth {
+        RigHealth {
+            rig: rig.to_string(),
+            hops: 1,
+            received: 1_000,
+            lost: 0,
+            offset_ns: Some(-OFFSET_NS),
+            uncertainty_ns: Some(40_000),
+            rigs,
+            unhealthy,
+        }
+    }
+
+    #[test]
+    fn relays_measure_the_upstream_offset() {
+        let relay = address("192.168.1.20:5006");
+        let mut master = FederationTable::new(0, [relay]);
+        let mut client = ProbeClient::default();
+        let mut queueing = Jitter(5);
+        let mut estimate = None;
+        for index in 0..20u64 {
+            // Relay clock; one-way delays of 80us plus up to 400us of queueing, either way
+            let sent_ns = START_NS + index * 250_000_000;
+            let sequence = client.next_sequence();
+            client.on_sent(sequence, sent_ns);
+            let probe = ProbeMessage {
+                sequence,
+                health: health("rig-b", 1, 0),
+            };
+            let WireMessage::Probe(probe) = WireMessage::decode(&probe.encode()).unwrap() else {
+                panic!("not a probe");
+            };
+            assert_eq!(probe.health, health("rig-b", 1, 0));
+
+            let upstream_rx_ns = sent_ns.saturating_add_signed(OFFSET_NS) + 80_000 + queueing.next(400_000);
+            let reply = master.on_probe(relay, &probe, upstream_rx_ns);
+            let upstream_tx_ns = upstream_rx_ns + 15_000;
+            master.on_reply_sent(relay, reply.sequence, upstream_tx_ns);
+            let WireMessage::ProbeReply(reply) = WireMessage::decode(&reply.encode()).unwrap() else {
+                panic!("not a probe reply");
+            };
+            let received_ns = upstream_tx_ns.saturating_add_signed(-OFFSET_NS) + 80_000 + queueing.next(400_000);
+            if let Some(update) = client.on_reply(&reply, received_ns) {
+                assert!((update.offset_ns - OFFSET_NS).unsigned_abs() <= update.uncertainty_ns, "{}", update.summary());
+                estimate = Some(update);
+            }
+        }
+        // The first reply only completes with the second
+        let estimate = estimate.unwrap();
+        assert!(estimate.uncertainty_ns < 200_000, "{}", estimate.summary());
+        assert_eq!(client.hops(), Some(1));
+        let local_ns = estimate.to_local(START_NS + OFFSET_NS as u64);
+        assert!(local_ns.abs_diff(START_NS) <= estimate.uncertainty_ns);
+        assert_eq!(master.relays()[0].state(START_NS + 5_000_000_000), RelayState::Ok);
+    }
+
+    #[test]
+    fn health_rolls_up_the_tree() {
+        let (a, b, c) = (address("10.0.0.2:5006"), address("10.0.0.3:5006"), address("10.0.0.4:5006"));
+        let mut master = FederationTable::new(0, [a, b, c]);
+        master.on_probe(a, &ProbeMessage { sequence: 0, health: health("rig-a", 3, 0) }, START_NS);
+        master.on_probe(b, &ProbeMessage { sequence: 0, health: health("rig-b", 2, 1) }, START_NS);
+        let states = |master: &FederationTable, now_ns: u64| master.relays().iter().map(|relay| relay.state(now_ns)).collect::<Vec<_>>();
+        assert_eq!(states(&master, START_NS), [RelayState::Ok, RelayState::Degraded, RelayState::Waiting]);
+        // Relays never heard from count as one unhealthy rig
+        assert_eq!(master.subtree(START_NS), (6, 2));
+
+        // Rig A goes silent: its whole subtree is unhealthy
+        let later = START_NS + RELAY_TIMEOUT_NS;
+        master.on_probe(b, &ProbeMessage { sequence: 1, health: health("rig-b", 2, 0) }, later);
+        assert_eq!(states(&master, later), [RelayState::Lost, RelayState::Ok, RelayState::Waiting]);
+        assert_eq!(master.subtree(later), (6, 4));
+
+        let mut loss = LossCounter::default();
+        for frame_id in [1, 2, 5, 6, 0, 1] {
+            loss.on_trigger(frame_id);
+        }
+        // 3 and 4 are lost; the restart at 0 is not a gap
+        assert_eq!((loss.received, loss.lost), (6, 2));
+    }
+}
@@ -0,0 +1,246 @@
+// Receive-time feedback from trigger consumers.
+//
+// Every consumer of `Camera/Sync` reports when it received a trigger, for the
+// canary triggers (see `canary`) and for every `FEEDBACK_EVERY`th trigger id,
+// as a `ReceiveFeedback` on the `Camera/Feedback` service (see
+// `sync_iceoryx2::feedback`). The feedback echoes the trigger's publish time,
+// so the publisher's `DeliveryMonitor` gets the delivery latency (receive
+// minus publish time, both on the host's realtime clock) per consumer without
+// keeping its own record of the sampled triggers.
+//
+// The monitor keeps a latency distribution per consumer. The p95 of the
+// first `BASELINE_SAMPLES` samples of a consumer is its baseline; the consumer
+// is degraded while the p95 of its last `RECENT_SAMPLES` samples exceeds
+// `DEGRADED_FACTOR` times the baseline plus `DEGRADED_MARGIN_MS`, and recovers
+// once it is back within `DEGRADED_FACTOR` times the baseline.
+
+#[cfg(feature = "iceoryx2")]
+use iceoryx2::prelude::ZeroCopySend;
+use std::collections::VecDeque;
+
+use crate::canary;
+use crate::stats::LatencyStats;
+use crate::units::{DisplayMs, Unit};
+use crate::CameraTrigger;
+
+/// Longest consumer name the feedback carries, longer names are truncated.
+pub const MAX_CONSUMER_LEN: usize = 32;
+
+/// Consumers report every trigger whose id is a multiple of this.
+pub const FEEDBACK_EVERY: u64 = 10;
+
+/// Samples of a consumer that make up its baseline.
+pub const BASELINE_SAMPLES: usize = 50;
+
+/// Samples of a consumer its current p95 is taken over.
+pub const RECENT_SAMPLES: usize = 50;
+
+pub const DEGRADED_FACTOR: f64 = 2.0;
+pub const DEGRADED_MARGIN_MS: f64 = 0.5;
+
+/// Whether consumers report the receive time of this trigger.
+pub fn is_sampled(trigger_id: u64) -> bool {
+    canary::is_canary(trigger_id) || trigger_id.is_multiple_of(FEEDBACK_EVERY)
+}
+
+/// When one consumer received one trigger.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "iceoryx2", derive(ZeroCopySend))]
+pub struct ReceiveFeedback {
+    pub trigger_id: u64,
+    /// Publish time of the trigger, echoed from the trigger, CLOCK_REALTIME ns.
+    pub published_ns: u64,
+    /// When the consumer received the trigger, CLOCK_REALTIME ns.
+    pub received_ns: u64,
+    /// Consumer name, UTF-8, zero padded.
+    pub consumer: [u8; MAX_CONSUMER_LEN],
+}
+
+impl ReceiveFeedback {
+    pub fn new(consumer: &str, trigger: &CameraTrigger, received_ns: u64) -> Self {
+        let mut name = [0u8; MAX_CONSUMER_LEN];
+        let mut len = consumer.len().min(MAX_CONSUMER_LEN);
+        while !consumer.is_char_boundary(len) {
+            len -= 1;
+        }
+        name[..len].copy_from_slice(&consumer.as_bytes()[..len]);
+        Self {
+            trigger_id: trigger.frame_id,
+            published_ns: trigger.publish_timestamp_ns,
+            received_ns,
+            consumer: name,
+        }
+    }
+
+    pub fn consumer_str(&self) -> String {
+        String::from_utf8_lossy(&self.consumer).trim_end_matches('\0').to_string()
+    }
+
+    /// Receive minus publish time.
+    pub fn latency_ns(&self) -> u64 {
+        self.received_ns.saturating_sub(self.published_ns)
+    }
+}
+
+/// Delivery latency of one consumer.
+#[derive(Debug, Clone)]
+pub struct ConsumerDelivery {
+    pub consumer: String,
+    pub latency: LatencyStats,
+    /// p95 of the first `BASELINE_SAMPLES` samples, ms.
+    pub baseline_p95_ms: Option<f64>,
+    recent: VecDeque<f64>,
+    pub degraded: bool,
+}
+
+impl ConsumerDelivery {
+    fn new(consumer: String) -> Self {
+        Self {
+            consumer,
+            latency: LatencyStats::default(),
+            baseline_p95_ms: None,
+            recent: VecDeque::new(),
+            degraded: false,
+        }
+    }
+
+    /// p95 of the last `RECENT_SAMPLES` samples, ms.
+    pub fn recent_p95_ms(&self) -> f64 {
+        let mut sorted: Vec<f64> = self.recent.iter().copied().collect();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let rank = (0.95 * sorted.len().saturating_sub(1) as f64).round() as usize;
+        sorted.get(rank).copied().unwrap_or_default()
+    }
+
+    // Returns the new state when the consumer became degraded or recovered
+    fn add(&mut self, latency_ms: f64) -> Option<bool> {
+        self.latency.add(latency_ms);
+        if self.recent.len() == RECENT_SAMPLES {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(latency_ms);
+        let Some(baseline) = self.baseline_p95_ms else {
+            if self.latency.count() as usize >= BASELINE_SAMPLES {
+                self.baseline_p95_ms = Some(self.latency.percentile(95.0));
+            }
+            return None;
+        };
+        let recent = self.recent_p95_ms();
+        let degraded = if self.degraded {
+            recent > baseline * DEGRADED_FACTOR
+        } else {
+            recent > baseline * DEGRADED_FACTOR + DEGRADED_MARGIN_MS
+        };
+        (degraded != self.degraded).then(|| {
+            self.degraded = degraded;
+            degraded
+        })
+    }
+
+    /// One-line summary with durations in `unit`.
+    pub fn summary(&self, unit: Unit) -> String {
+        let mut summary = format!("{}: {}", self.consumer, self.latency.summary(unit));
+        if let Some(baseline) = self.baseline_p95_ms {
+            summary += &format!(", recent p95={:.2} (baseline {:.2}){}", DisplayMs(self.recent_p95_ms(), unit), DisplayMs(baseline, unit), if self.degraded { " DEGRADED" } else { "" });
+        }
+        summary
+    }
+}
+
+/// Publisher side: delivery latency per consumer from the feedback of sampled triggers.
+#[derive(Debug, Clone, Default)]
+pub struct DeliveryMonitor {
+    consumers: Vec<ConsumerDelivery>,
+}
+
+impl DeliveryMonitor {
+    /// Records the latency of a sampled trigger; returns the consumer if it just became degraded or recovered.
+    pub fn on_feedback(&mut self, feedback: &ReceiveFeedback) -> Option<&ConsumerDelivery> {
+        let name = feedback.consumer_str();
+        let index = match self.consumers.iter().position(|consumer| consumer.consumer == name) {
+            Some(index) => index,
+            None => {
+                self.consumers.push(ConsumerDelivery::new(name));
+                self.consumers.len() - 1
+            }
+        };
+        let consumer = &mut self.consumers[index];
+        consumer.add(feedback.latency_ns() as f64 / 1e6).map(|_| &*consumer)
+    }
+
+    /// In the order their first feedback arrived.
+    pub fn consumers(&self) -> &[ConsumerDelivery] {
+        &self.consumers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canary::CANARY_BIT;
+    use crate::units::Unit;
+
+    const START_NS: u64 = 1_700_000_000_000_000_000;
+
+    // Sampled trigger `index`, received `latency_ns` after it was published
+    fn received(consumer: &str, index: u64, latency_ns: u64) -> ReceiveFeedback {
+        let published_ns = START_NS + index * 10_000_000;
+        ReceiveFeedback::new(consumer, &CameraTrigger::new(index * FEEDBACK_EVERY, published_ns - 1_000, published_ns), published_ns + latency_ns)
+    }
+
+    #[test]
+    fn flags_degraded_consumers_against_their_baseline() {
+        let mut monitor = DeliveryMonitor::default();
+        let mut index = 0;
+        // 50us to 90us to the subscriber, a steady 2ms to the slow camera
+        for _ in 0..BASELINE_SAMPLES {
+            assert!(monitor.on_feedback(&received("subscriber", index, 50_000 + index % 5 * 10_000)).is_none());
+            assert!(monitor.on_feedback(&received("camera 0", index, 2_000_000)).is_none());
+            index += 1;
+        }
+        let subscriber = &monitor.consumers()[0];
+        assert_eq!(subscriber.consumer, "subscriber");
+        assert!((subscriber.baseline_p95_ms.unwrap() - 0.09).abs() < 1e-9);
+        assert_eq!(monitor.consumers()[1].baseline_p95_ms, Some(2.0));
+
+        // The subscriber falls behind; it is degraded once enough of its recent samples are late
+        let mut changes = Vec::new();
+        for _ in 0..RECENT_SAMPLES {
+            if let Some(consumer) = monitor.on_feedback(&received("subscriber", index, 3_000_000)) {
+                changes.push((consumer.consumer.clone(), consumer.degraded));
+            }
+            assert!(monitor.on_feedback(&received("camera 0", index, 2_000_000)).is_none());
+            index += 1;
+        }
+        assert_eq!(changes, [("subscriber".to_string(), true)]);
+        assert!(monitor.consumers()[0].summary(Unit::Ms).ends_with("(baseline 0.09ms) DEGRADED"), "{}", monitor.consumers()[0].summary(Unit::Ms));
+        assert!(!monitor.consumers()[1].degraded);
+
+        // And recovers once its recent samples are back near the baseline
+        changes.clear();
+        for _ in 0..RECENT_SAMPLES {
+            if let Some(consumer) = monitor.on_feedback(&received("subscriber", index, 60_000)) {
+                changes.push((consumer.consumer.clone(), consumer.degraded));
+            }
+            index += 1;
+        }
+        assert_eq!(changes, [("subscriber".to_string(), false)]);
+    }
+
+    #[test]
+    fn samples_canaries_and_every_nth_trigger() {
+        assert!(is_sampled(0));
+        assert!(is_sampled(3 * FEEDBACK_EVERY));
+        assert!(!is_sampled(3 * FEEDBACK_EVERY + 1));
+        assert!(is_sampled(CANARY_BIT | 7));
+
+        let trigger = CameraTrigger::new(FEEDBACK_EVERY, START_NS - 1_000, START_NS);
+        let feedback = ReceiveFeedback::new("subscriber", &trigger, START_NS + 80_000);
+        assert_eq!(feedback.consumer_str(), "subscriber");
+        assert_eq!(feedback.latency_ns(), 80_000);
+        // The two-byte characters start on odd bytes, the last one would be cut in half
+        let name = format!("a{}", "é".repeat(MAX_CONSUMER_LEN));
+        assert_eq!(ReceiveFeedback::new(&name, &trigger, START_NS).consumer_str(), format!("a{}", "é".repeat(MAX_CONSUMER_LEN / 2 - 1)));
+    }
+}
@@ -0,0 +1,149 @@
+// Regression fixtures generated from recorded sessions.
+//
+// `make_fixture` turns the `records.csv` of a field session into a compact
+// text fixture: the triggers and frame timestamps, relative to the start of
+// the session, and for every frame the trigger it was matched to. The fixtures
+// live in `crates/sync-core/tests/fixtures/`; `cargo test` replays each one
+// through the matcher and asserts the recorded pairs, so a change that would
+// associate a field-captured frame differently fails the build.
+//
+// One entry per line, `#` starts a comment:
+//
+//   tolerance_ms 500
+//   burst_size 1
+//   t <trigger_id> <hw_ts_ns> <publish_ts_ns>
+//   f <frame_ns> <expected trigger_id, or - for no match>
+
+use std::fmt;
+
+use crate::burst::BurstPattern;
+use crate::matching;
+use crate::pipeline::MatchPipeline;
+use crate::session::MatchRecord;
+use crate::CameraTrigger;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fixture {
+    pub tolerance_ms: f64,
+    pub burst: BurstPattern,
+    /// `(trigger_id, hw_ts_ns, publish_ts_ns)` in publish order.
+    pub triggers: Vec<(u64, u64, u64)>,
+    /// `(frame_ns, expected trigger_id)` in frame order.
+    pub frames: Vec<(u64, Option<u64>)>,
+}
+
+impl Fixture {
+    /// Fixture of a recorded session: every recorded frame is expected to match its recorded trigger.
+    pub fn from_records(records: &[MatchRecord], tolerance_ms: f64, burst: BurstPattern) -> Self {
+        let mut triggers: Vec<_> = records
+            .iter()
+            .map(|record| (record.trigger.frame_id, record.trigger.hw_timestamp_ns, record.trigger.publish_timestamp_ns))
+            .collect();
+        triggers.sort_by_key(|&(id, _, pub_ts)| (pub_ts, id));
+        triggers.dedup_by_key(|&mut (id, _, _)| id);
+        let mut frames: Vec<_> = records.iter().map(|record| (record.v4l2_timestamp_ns, Some(record.trigger.frame_id))).collect();
+        frames.sort();
+
+        // Timestamps relative to the earliest one keep the fixture small
+        let base = triggers
+            .iter()
+            .map(|&(_, hw_ts, _)| hw_ts)
+            .chain(frames.iter().map(|&(frame_ns, _)| frame_ns))
+            .min()
+            .unwrap_or(0);
+        Self {
+            tolerance_ms,
+            burst,
+            triggers: triggers
+                .into_iter()
+                .map(|(id, hw_ts, pub_ts)| (id, hw_ts - base, pub_ts.saturating_sub(base)))
+                .collect(),
+            frames: frames.into_iter().map(|(frame_ns, id)| (frame_ns - base, id)).collect(),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut fixture = Self {
+            tolerance_ms: matching::DEFAULT_TOLERANCE_MS,
+            burst: BurstPattern::default(),
+            triggers: Vec::new(),
+            frames: Vec::new(),
+        };
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let invalid = || format!("line {}: invalid entry '{}'", number + 1, line);
+            let int = |field: &str| field.parse::<u64>().map_err(|_| invalid());
+            match fields.as_slice() {
+                ["tolerance_ms", value] => fixture.tolerance_ms = value.parse().map_err(|_| invalid())?,
+                ["burst_size", value] => fixture.burst = BurstPattern::new(value.parse().map_err(|_| invalid())?),
+                ["t", id, hw_ts, pub_ts] => fixture.triggers.push((int(id)?, int(hw_ts)?, int(pub_ts)?)),
+                ["f", frame_ns, "-"] => fixture.frames.push((int(frame_ns)?, None)),
+                ["f", frame_ns, id] => fixture.frames.push((int(frame_ns)?, Some(int(id)?))),
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(fixture)
+    }
+
+    /// Runs the frames through the matcher; returns the matched trigger id per frame.
+    pub fn replay(&self) -> Vec<Option<u64>> {
+        self.replay_shifted(0).into_iter().map(|matched| matched.map(|trigger| trigger.frame_id)).collect()
+    }
+
+    /// Runs the frames through the matcher with their timestamps moved back by `offset_ns` (a fixed
+    /// pipeline delay); triggers still arrive by the original frame times. Returns the matched trigger per frame.
+    pub fn replay_shifted(&self, offset_ns: i64) -> Vec<Option<CameraTrigger>> {
+        let mut pipeline: MatchPipeline<()> = MatchPipeline::new(self.tolerance_ms, self.burst);
+        let mut triggers = self.triggers.iter().peekable();
+        let mut matches = Vec::with_capacity(self.frames.len());
+        for &(frame_ns, _) in &self.frames {
+            // Triggers published before the frame was dequeued have arrived
+            while let Some(&(id, hw_ts, pub_ts)) = triggers.next_if(|&&(_, _, pub_ts)| pub_ts <= frame_ns) {
+                pipeline.on_trigger(CameraTrigger::new(id, hw_ts, pub_ts), ());
+            }
+            let shifted_ns = frame_ns.saturating_add_signed(offset_ns.saturating_neg());
+            matches.push(pipeline.on_frame(shifted_ns).matched.map(|(trigger, _, _)| trigger));
+        }
+        matches
+    }
+
+    /// Checks the replay against the expected pairs; the error lists every differing frame.
+    pub fn check(&self) -> Result<(), String> {
+        let mismatches: Vec<String> = self
+            .frames
+            .iter()
+            .zip(self.replay())
+            .filter(|((_, expected), actual)| expected != actual)
+            .map(|(&(frame_ns, expected), actual)| {
+                let id = |id: Option<u64>| id.map_or("-".to_string(), |id| id.to_string());
+                format!("frame at {}ns: expected trigger {}, matched {}", frame_ns, id(expected), id(actual))
+            })
+            .collect();
+        if mismatches.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("{} of {} frames differ\n{}", mismatches.len(), self.frames.len(), mismatches.join("\n")))
+        }
+    }
+}
+
+impl fmt::Display for Fixture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "tolerance_ms {}", self.tolerance_ms)?;
+        writeln!(f, "burst_size {}", self.burst.size)?;
+        for (id, hw_ts, pub_ts) in &self.triggers {
+            writeln!(f, "t {} {} {}", id, hw_ts, pub_ts)?;
+        }
+        for (frame_ns, id) in &self.frames {
+            match id {
+                Some(id) => writeln!(f, "f {} {}", frame_ns, id)?,
+                None => writeln!(f, "f {} -", frame_ns)?,
+            }
+        }
+        Ok(())
+    }
+}
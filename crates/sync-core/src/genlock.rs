@@ -0,0 +1,466 @@
+// Phase of the trigger stream against an external video reference (genlock).
+//
+// Broadcast and virtual production stages lock every device to a house
+// reference (black burst, tri-level sync), so cameras expose in step with LED
+// walls, switchers and monitors. A sync separator on a GPIO line or a capture
+// card reports the timestamp of every reference edge (vsync) on the
+// `Video/Reference` service, and the publisher measures the phase of each
+// trigger against the reference grid fitted through the recent edges:
+//
+//   phase = trigger - nearest reference edge, in [-period/2, period/2)
+//
+// Triggers at a multiple of the reference rate (or an integer fraction of it)
+// have a stable phase, taken modulo the shorter of the two periods. Triggers
+// paced by the publisher's own interval drift against the reference; the
+// report shows how fast (`drift_us_per_s`).
+//
+// Steering (`--genlock-steer`) schedules the triggers on the reference period
+// instead, the multiple or fraction of it closest to the trigger interval, and
+// moves them towards the target phase (`--genlock-phase-ms`) with a slow
+// phase-locked loop: a proportional step plus an integral term that absorbs
+// the remaining rate difference between the reference and the local clock.
+// Each step is limited to `--genlock-max-step-us`, so cameras never see a jump
+// in the trigger interval; only the first steered trigger moves straight onto
+// the target phase. The stream counts as locked once the phase error stayed
+// within `--genlock-tolerance-us` for `LOCK_COUNT` triggers in a row.
+
+use std::collections::VecDeque;
+
+use crate::cli::Args;
+use crate::stats::LatencyStats;
+
+/// Reference edges used for the period fit.
+const WINDOW: usize = 64;
+/// Fewer edges do not give a usable reference grid.
+const MIN_EDGES: usize = 4;
+/// A reference without edges for this many periods is considered lost.
+const STALE_PERIODS: f64 = 16.0;
+/// Triggers within tolerance in a row before the stream counts as locked.
+pub const LOCK_COUNT: u32 = 10;
+
+// Loop gains per trigger; small enough to settle without overshoot at one correction per trigger
+const PROPORTIONAL_GAIN: f64 = 0.25;
+const INTEGRAL_GAIN: f64 = 0.02;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GenlockConfig {
+    /// Move the trigger schedule towards the target phase.
+    pub steer: bool,
+    /// Wanted trigger phase after the reference edge.
+    pub target_phase_ns: i64,
+    /// Largest correction of one trigger interval.
+    pub max_step_ns: u64,
+    /// Phase error still counted as locked.
+    pub tolerance_ns: u64,
+}
+
+impl Default for GenlockConfig {
+    fn default() -> Self {
+        Self {
+            steer: false,
+            target_phase_ns: 0,
+            max_step_ns: 100_000,
+            tolerance_ns: 50_000,
+        }
+    }
+}
+
+impl GenlockConfig {
+    /// `--genlock` with `--genlock-steer`, `--genlock-phase-ms`, `--genlock-max-step-us` and
+    /// `--genlock-tolerance-us`; `None` without `--genlock`.
+    pub fn from_args(args: &Args) -> Option<Self> {
+        if !args.flag("genlock") {
+            return None;
+        }
+        let defaults = Self::default();
+        Some(Self {
+            steer: args.flag("genlock-steer"),
+            target_phase_ns: args.value_as::<f64>("genlock-phase-ms").map_or(defaults.target_phase_ns, |ms| (ms * 1e6) as i64),
+            max_step_ns: args.value_as::<f64>("genlock-max-step-us").map_or(defaults.max_step_ns, |us| (us * 1e3) as u64),
+            tolerance_ns: args.value_as::<f64>("genlock-tolerance-us").map_or(defaults.tolerance_ns, |us| (us * 1e3) as u64),
+        })
+    }
+}
+
+/// Phase of one trigger against the reference.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PhaseSample {
+    pub phase_ns: f64,
+    /// Phase minus the target phase.
+    pub error_ns: f64,
+    /// Shift of the next trigger decided by the steering loop.
+    pub correction_ns: i64,
+    /// `Some(true)` when the stream just locked, `Some(false)` when it lost lock.
+    pub lock_changed: Option<bool>,
+}
+
+/// Phase statistics since the previous report.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GenlockReport {
+    pub reference_hz: Option<f64>,
+    pub triggers: u64,
+    pub phase_us: f64,
+    /// Standard deviation of the phase.
+    pub jitter_us: f64,
+    pub error_us: f64,
+    /// Change of the mean phase since the previous report.
+    pub drift_us_per_s: f64,
+    pub locked: bool,
+}
+
+impl GenlockReport {
+    /// One-line summary, e.g. `reference 29.970Hz, phase 12.3us ±4.1us, drift 0.0us/s, locked`.
+    pub fn summary(&self) -> String {
+        let Some(reference_hz) = self.reference_hz else {
+            return "no reference".to_string();
+        };
+        format!(
+            "reference {:.3}Hz, phase {:.1}us ±{:.1}us (error {:.1}us), drift {:.1}us/s, {}",
+            reference_hz,
+            self.phase_us,
+            self.jitter_us,
+            self.error_us,
+            self.drift_us_per_s,
+            if self.locked { "locked" } else { "not locked" }
+        )
+    }
+}
+
+/// Measures (and optionally steers) the phase of the triggers against the reference edges.
+#[derive(Debug, Clone)]
+pub struct Genlock {
+    config: GenlockConfig,
+    interval_ns: u64,
+    // Recent reference edges, oldest first
+    edges: VecDeque<u64>,
+    // Reference grid fitted through the edges: (an edge on the grid, period ns)
+    reference: Option<(u64, f64)>,
+    // Steering: the last scheduled trigger, the pending correction and the integral of the phase error
+    scheduled_ns: Option<u64>,
+    aligned: bool,
+    correction_ns: i64,
+    integral_ns: f64,
+    within_tolerance: u32,
+    locked: bool,
+    // Phases and errors (ms) since the last report, with the first and last trigger time
+    phases: LatencyStats,
+    errors: LatencyStats,
+    span_ns: Option<(u64, u64)>,
+    // Middle of the previous report's span and its mean phase (us)
+    last_report: Option<(u64, f64)>,
+}
+
+impl Genlock {
+    /// Triggers nominally `interval_ns` apart.
+    pub fn new(config: GenlockConfig, interval_ns: u64) -> Self {
+        Self {
+            config,
+            interval_ns: interval_ns.max(1),
+            edges: VecDeque::with_capacity(WINDOW),
+            reference: None,
+            scheduled_ns: None,
+            aligned: false,
+            correction_ns: 0,
+            integral_ns: 0.0,
+            within_tolerance: 0,
+            locked: false,
+            phases: LatencyStats::default(),
+            errors: LatencyStats::default(),
+            span_ns: None,
+            last_report: None,
+        }
+    }
+
+    pub fn config(&self) -> &GenlockConfig {
+        &self.config
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// Adds the timestamp of a reference edge.
+    pub fn on_reference(&mut self, edge_ns: u64) {
+        // A restarted reference source starts a new grid
+        if self.edges.back().is_some_and(|&last| edge_ns <= last) {
+            self.edges.clear();
+        }
+        if self.edges.len() == WINDOW {
+            self.edges.pop_front();
+        }
+        self.edges.push_back(edge_ns);
+        self.reference = self.fit();
+    }
+
+    // Least-squares grid through the edges, numbered by the median interval so missed edges do not matter
+    fn fit(&self) -> Option<(u64, f64)> {
+        if self.edges.len() < MIN_EDGES {
+            return None;
+        }
+        let first = self.edges[0];
+        let mut intervals: Vec<u64> = self.edges.iter().zip(self.edges.iter().skip(1)).map(|(a, b)| b - a).collect();
+        intervals.sort_unstable();
+        let median = intervals[intervals.len() / 2].max(1) as f64;
+        let points: Vec<(f64, f64)> = self
+            .edges
+            .iter()
+            .map(|&edge| {
+                let offset = (edge - first) as f64;
+                ((offset / median).round(), offset)
+            })
+            .collect();
+        let n = points.len() as f64;
+        let mean_index = points.iter().map(|(index, _)| index).sum::<f64>() / n;
+        let mean_offset = points.iter().map(|(_, offset)| offset).sum::<f64>() / n;
+        let covariance: f64 = points.iter().map(|(index, offset)| (index - mean_index) * (offset - mean_offset)).sum();
+        let variance: f64 = points.iter().map(|(index, _)| (index - mean_index).powi(2)).sum();
+        if variance == 0.0 {
+            return None;
+        }
+        let period = covariance / variance;
+        let intercept = mean_offset - period * mean_index;
+        Some((first.saturating_add_signed(intercept.round() as i64), period))
+    }
+
+    /// Reference period, once enough edges arrived.
+    pub fn reference_period_ns(&self) -> Option<f64> {
+        self.reference.map(|(_, period)| period)
+    }
+
+    /// Trigger period locked to the reference: the multiple or integer fraction of the
+    /// reference period closest to the trigger interval, the interval itself without reference.
+    pub fn trigger_period_ns(&self) -> f64 {
+        let interval = self.interval_ns as f64;
+        match self.reference_period_ns() {
+            Some(period) if interval >= period => period * (interval / period).round().max(1.0),
+            Some(period) => period / (period / interval).round().max(1.0),
+            None => interval,
+        }
+    }
+
+    // `value` wrapped into [-period/2, period/2)
+    fn wrap(value: f64, period: f64) -> f64 {
+        (value + period / 2.0).rem_euclid(period) - period / 2.0
+    }
+
+    /// Measures the phase of a trigger fired at `trigger_ns`; `None` without a (recent) reference.
+    pub fn on_trigger(&mut self, trigger_ns: u64) -> Option<PhaseSample> {
+        let (origin, period) = self.reference?;
+        let newest = *self.edges.back()?;
+        if trigger_ns as f64 > newest as f64 + period * STALE_PERIODS {
+            let was_locked = self.locked;
+            self.reference = None;
+            self.edges.clear();
+            self.within_tolerance = 0;
+            self.integral_ns = 0.0;
+            self.correction_ns = 0;
+            self.locked = false;
+            return was_locked.then_some(PhaseSample {
+                phase_ns: 0.0,
+                error_ns: 0.0,
+                correction_ns: 0,
+                lock_changed: Some(false),
+            });
+        }
+
+        let wrap = self.trigger_period_ns().min(period);
+        let phase_ns = Self::wrap((trigger_ns as i128 - origin as i128) as f64, wrap);
+        let error_ns = Self::wrap(phase_ns - self.config.target_phase_ns as f64, wrap);
+        self.phases.add(phase_ns / 1e6);
+        self.errors.add(error_ns / 1e6);
+        self.span_ns = Some(self.span_ns.map_or((trigger_ns, trigger_ns), |(first, _)| (first, trigger_ns)));
+
+        let within = error_ns.abs() <= self.config.tolerance_ns as f64;
+        self.within_tolerance = if within { self.within_tolerance + 1 } else { 0 };
+        let lock_changed = if !self.locked && self.within_tolerance >= LOCK_COUNT {
+            self.locked = true;
+            Some(true)
+        } else if self.locked && !within {
+            self.locked = false;
+            Some(false)
+        } else {
+            None
+        };
+
+        // Proportional-integral step against the error; the integral stops while the step is limited
+        if self.config.steer && self.aligned {
+            let max_step = self.config.max_step_ns as f64;
+            let output = -(PROPORTIONAL_GAIN * error_ns + INTEGRAL_GAIN * (self.integral_ns + error_ns));
+            if output.abs() < max_step {
+                self.integral_ns += error_ns;
+            }
+            self.correction_ns = output.clamp(-max_step, max_step).round() as i64;
+        }
+        Some(PhaseSample {
+            phase_ns,
+            error_ns,
+            correction_ns: self.correction_ns,
+            lock_changed,
+        })
+    }
+
+    /// Realtime of the next steered trigger after `now_ns`. Follows the reference period with
+    /// the latest correction applied; the first trigger after the reference appears is put on
+    /// the target phase directly.
+    pub fn next_trigger_ns(&mut self, now_ns: u64) -> u64 {
+        let period = self.trigger_period_ns().round() as u64;
+        let next = match (self.reference, self.scheduled_ns) {
+            (Some((origin, reference_period)), _) if !self.aligned => {
+                self.aligned = true;
+                self.integral_ns = 0.0;
+                // First point of the target phase grid after now
+                let target = origin as i128 + self.config.target_phase_ns as i128;
+                let since = (now_ns as i128 - target).rem_euclid(reference_period.round() as i128);
+                (now_ns as i128 - since) as u64 + reference_period.round() as u64
+            }
+            (_, Some(last)) => (last + period).saturating_add_signed(std::mem::take(&mut self.correction_ns)),
+            (_, None) => now_ns + period,
+        };
+        // After a stall, skip the missed slots instead of firing them back to back
+        let next = if next <= now_ns { next + ((now_ns - next) / period + 1) * period } else { next };
+        if self.reference.is_none() {
+            self.aligned = false;
+        }
+        self.scheduled_ns = Some(next);
+        next
+    }
+
+    /// Phase statistics since the previous report.
+    pub fn report(&mut self) -> GenlockReport {
+        let phases = std::mem::take(&mut self.phases);
+        let errors = std::mem::take(&mut self.errors);
+        let phase_us = phases.mean() * 1e3;
+        // The mean phase belongs to the middle of the span it was taken over
+        let middle_ns = self.span_ns.take().map(|(first, last)| first + (last - first) / 2);
+        let drift_us_per_s = match (self.last_report, middle_ns, self.reference_period_ns()) {
+            (Some((then_ns, then_phase_us)), Some(now_ns), Some(period)) if now_ns > then_ns => {
+                let wrap_us = self.trigger_period_ns().min(period) / 1e3;
+                Self::wrap(phase_us - then_phase_us, wrap_us) / ((now_ns - then_ns) as f64 / 1e9)
+            }
+            _ => 0.0,
+        };
+        self.last_report = middle_ns.map(|now_ns| (now_ns, phase_us));
+        GenlockReport {
+            reference_hz: self.reference_period_ns().map(|period| 1e9 / period),
+            triggers: phases.count(),
+            phase_us,
+            jitter_us: phases.std_dev() * 1e3,
+            error_us: errors.mean() * 1e3,
+            drift_us_per_s,
+            locked: self.locked,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START_NS: u64 = 1_700_000_000_000_000_000;
+
+    // 29.97Hz
+    const REFERENCE_PERIOD_NS: f64 = 1e9 * 1001.0 / 30_000.0;
+
+    // Deterministic jitter in [-amplitude, amplitude]
+    struct Jitter(u64);
+
+    impl Jitter {
+        fn next(&mut self, amplitude_ns: u64) -> i64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % (2 * amplitude_ns + 1)) as i64 - amplitude_ns as i64
+        }
+    }
+
+    // Reference edges of a clock running `ppm` fast, with timestamp jitter
+    struct Reference {
+        next: u64,
+        ppm: f64,
+        jitter: Jitter,
+    }
+
+    impl Reference {
+        fn edge_ns(&self, index: u64) -> u64 {
+            START_NS + (index as f64 * REFERENCE_PERIOD_NS / (1.0 + self.ppm * 1e-6)) as u64
+        }
+
+        // Feeds every edge up to `now_ns`
+        fn feed(&mut self, genlock: &mut Genlock, now_ns: u64) {
+            while self.edge_ns(self.next) <= now_ns {
+                let edge = self.edge_ns(self.next).saturating_add_signed(self.jitter.next(20_000));
+                genlock.on_reference(edge);
+                self.next += 1;
+            }
+        }
+    }
+
+    #[test]
+    fn measures_phase_and_drift() {
+        let mut reference = Reference { next: 0, ppm: 0.0, jitter: Jitter(7) };
+        // One microsecond longer than the reference period: 1us per trigger, ~30us/s
+        let interval = REFERENCE_PERIOD_NS + 1_000.0;
+        let mut genlock = Genlock::new(GenlockConfig::default(), interval as u64);
+        let trigger_ns = |index: u64| START_NS + 3_000_000 + (index as f64 * interval) as u64;
+        for index in 0..200 {
+            reference.feed(&mut genlock, trigger_ns(index));
+            genlock.on_trigger(trigger_ns(index));
+        }
+        genlock.report();
+        for index in 200..300 {
+            reference.feed(&mut genlock, trigger_ns(index));
+            let sample = genlock.on_trigger(trigger_ns(index)).unwrap();
+            assert!((sample.phase_ns - (3e6 + index as f64 * 1_000.0)).abs() < 30_000.0, "phase {}", sample.phase_ns);
+            assert_eq!(sample.correction_ns, 0);
+        }
+        let report = genlock.report();
+        assert!((report.reference_hz.unwrap() - 29.97).abs() < 0.001, "{}", report.summary());
+        assert!((report.drift_us_per_s - 1e3 / (interval / 1e6)).abs() < 2.0, "{}", report.summary());
+        assert!(!report.locked);
+    }
+
+    #[test]
+    fn steers_onto_the_target_phase() {
+        // Reference 50ppm fast against the local clock, triggers at twice the reference period
+        let mut reference = Reference { next: 0, ppm: 50.0, jitter: Jitter(11) };
+        let config = GenlockConfig {
+            steer: true,
+            target_phase_ns: 2_000_000,
+            ..Default::default()
+        };
+        let mut genlock = Genlock::new(config, 66_000_000);
+        let mut lateness = Jitter(3);
+        let mut now_ns = START_NS;
+        let mut scheduled = Vec::new();
+        let mut locked_at = None;
+        for index in 0..600 {
+            reference.feed(&mut genlock, now_ns);
+            let next = genlock.next_trigger_ns(now_ns);
+            scheduled.push(next);
+            // Triggers fire up to 10us late
+            let fired = next + lateness.next(5_000).unsigned_abs() * 2;
+            reference.feed(&mut genlock, fired);
+            if let Some(sample) = genlock.on_trigger(fired) {
+                if sample.lock_changed == Some(true) {
+                    locked_at = Some(index);
+                }
+                assert_ne!(sample.lock_changed, Some(false), "lost lock at trigger {}", index);
+                if index > 300 {
+                    assert!(sample.error_ns.abs() < config.tolerance_ns as f64, "error {}ns at trigger {}", sample.error_ns, index);
+                }
+            }
+            now_ns = fired + 1_000_000;
+        }
+        assert!(genlock.is_locked());
+        assert!(locked_at.unwrap() < 300, "locked at trigger {:?}", locked_at);
+
+        // After the first aligned trigger, intervals stay within the step limit of the locked period
+        let period = genlock.trigger_period_ns();
+        assert!((period - 2.0 * REFERENCE_PERIOD_NS / 1.00005).abs() < 500.0, "period {}", period);
+        let aligned = scheduled.windows(2).position(|pair| pair[1] - pair[0] != 66_000_000).unwrap() + 1;
+        for pair in scheduled[aligned..].windows(2) {
+            let step = (pair[1] - pair[0]) as f64 - period;
+            assert!(step.abs() <= config.max_step_ns as f64 + 1_000.0, "interval changed by {}ns", step);
+        }
+    }
+}
@@ -0,0 +1,264 @@
+// Glass-to-glass latency measurement.
+//
+// Every `interval` triggers the GUI flashes a white marker. The measurement
+// loop closes either through the camera itself (pointed at the screen: the
+// flash shows up as a jump in frame brightness) or through a photodiode taped
+// to the marker that reports light edges on the `Latency/Photodiode` service.
+//
+// Measured per flash (all in the trigger clock domain):
+//   trigger->display   trigger timestamp → marker painted
+//   display->light     marker painted → photodiode edge
+//   display->capture   marker painted → exposure of the first frame showing it
+//   glass-to-glass     marker painted → that frame painted on screen again
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::stats::LatencyStats;
+use crate::units::Unit;
+
+pub const MEASUREMENTS_FILE: &str = "glass_to_glass.csv";
+
+/// How long the marker stays white.
+const FLASH_DURATION_NS: u64 = 100_000_000;
+/// Give up on a flash that was not seen within this time.
+const DETECTION_TIMEOUT_NS: u64 = 2_000_000_000;
+/// Brightness jump over the running baseline that counts as a detected flash.
+const DETECTION_THRESHOLD: f64 = 40.0;
+
+#[derive(Debug, Clone, Default)]
+struct Flash {
+    id: u64,
+    trigger_id: u64,
+    trigger_ns: u64,
+    painted_ns: Option<u64>,
+    light_ns: Option<u64>,
+    captured_hw_ns: Option<u64>,
+    redisplayed_ns: Option<u64>,
+    /// Set once the detected frame is waiting to be painted
+    awaiting_redisplay: bool,
+}
+
+pub struct GlassToGlass {
+    interval: u64,
+    next_id: u64,
+    flash: Option<Flash>,
+    baseline_luma: Option<f64>,
+    trigger_to_display: LatencyStats,
+    display_to_light: LatencyStats,
+    display_to_capture: LatencyStats,
+    glass_to_glass: LatencyStats,
+    missed: u64,
+    writer: Option<BufWriter<File>>,
+}
+
+fn ms(from_ns: u64, to_ns: u64) -> f64 {
+    (to_ns as i64 - from_ns as i64) as f64 / 1_000_000.0
+}
+
+/// Mean luminance (0-255) of a packed RGB frame, sampling every 16th pixel.
+pub fn mean_luma(rgb: &[u8]) -> f64 {
+    let mut sum = 0.0;
+    let mut count = 0;
+    for pixel in rgb.chunks_exact(3).step_by(16) {
+        sum += 0.299 * pixel[0] as f64 + 0.587 * pixel[1] as f64 + 0.114 * pixel[2] as f64;
+        count += 1;
+    }
+    if count == 0 {
+        0.0
+    } else {
+        sum / count as f64
+    }
+}
+
+impl GlassToGlass {
+    /// Flashes on every `interval`-th trigger. Measurements are appended to
+    /// `glass_to_glass.csv` in `session_dir` if given.
+    pub fn new(interval: u64, session_dir: Option<&Path>) -> std::io::Result<Self> {
+        let writer = match session_dir {
+            Some(dir) => {
+                let mut writer = BufWriter::new(File::create(dir.join(MEASUREMENTS_FILE))?);
+                writeln!(writer, "flash_id,trigger_id,trigger_ns,painted_ns,light_ns,captured_hw_ns,redisplayed_ns")?;
+                Some(writer)
+            }
+            None => None,
+        };
+        Ok(Self {
+            interval: interval.max(1),
+            next_id: 1,
+            flash: None,
+            baseline_luma: None,
+            trigger_to_display: LatencyStats::default(),
+            display_to_light: LatencyStats::default(),
+            display_to_capture: LatencyStats::default(),
+            glass_to_glass: LatencyStats::default(),
+            missed: 0,
+            writer,
+        })
+    }
+
+    /// Starts a flash on every `interval`-th trigger while no other flash is in flight.
+    pub fn on_trigger(&mut self, trigger_id: u64, trigger_ns: u64) {
+        if self.flash.is_none() && trigger_id.is_multiple_of(self.interval) {
+            self.flash = Some(Flash {
+                id: self.next_id,
+                trigger_id,
+                trigger_ns,
+                ..Default::default()
+            });
+            self.next_id += 1;
+        }
+    }
+
+    /// Whether the GUI should draw the marker white right now.
+    pub fn marker_lit(&self, now_ns: u64) -> bool {
+        match &self.flash {
+            Some(flash) => match flash.painted_ns {
+                Some(painted_ns) => now_ns < painted_ns + FLASH_DURATION_NS,
+                None => true,
+            },
+            None => false,
+        }
+    }
+
+    /// Called after every GUI paint.
+    pub fn on_painted(&mut self, now_ns: u64) {
+        let Some(flash) = &mut self.flash else {
+            return;
+        };
+        if flash.painted_ns.is_none() {
+            flash.painted_ns = Some(now_ns);
+        } else if flash.awaiting_redisplay {
+            flash.redisplayed_ns = Some(now_ns);
+            self.finish();
+            return;
+        }
+        if flash.painted_ns.is_some_and(|painted_ns| now_ns > painted_ns + DETECTION_TIMEOUT_NS) {
+            self.missed += 1;
+            self.finish();
+        }
+    }
+
+    /// Camera loop: feeds the brightness of each captured frame and the hardware
+    /// timestamp of its matched trigger, if any.
+    pub fn on_frame(&mut self, luma: f64, hw_ts: Option<u64>) {
+        let baseline = *self.baseline_luma.get_or_insert(luma);
+        match &mut self.flash {
+            Some(flash) if flash.painted_ns.is_some() && flash.captured_hw_ns.is_none() => {
+                if let Some(hw_ts) = hw_ts.filter(|_| luma - baseline > DETECTION_THRESHOLD) {
+                    flash.captured_hw_ns = Some(hw_ts);
+                    flash.awaiting_redisplay = true;
+                }
+            }
+            // Track the baseline only while the marker is dark
+            None => self.baseline_luma = Some(baseline * 0.9 + luma * 0.1),
+            _ => {}
+        }
+    }
+
+    /// Photodiode loop: rising light edge reported by external hardware.
+    pub fn on_light_edge(&mut self, edge_ns: u64) {
+        if let Some(flash) = &mut self.flash {
+            if flash.painted_ns.is_some() && flash.light_ns.is_none() {
+                flash.light_ns = Some(edge_ns);
+            }
+        }
+    }
+
+    fn finish(&mut self) {
+        let Some(flash) = self.flash.take() else {
+            return;
+        };
+        if let Some(painted_ns) = flash.painted_ns {
+            self.trigger_to_display.add(ms(flash.trigger_ns, painted_ns));
+            if let Some(light_ns) = flash.light_ns {
+                self.display_to_light.add(ms(painted_ns, light_ns));
+            }
+            if let Some(captured_hw_ns) = flash.captured_hw_ns {
+                self.display_to_capture.add(ms(painted_ns, captured_hw_ns));
+            }
+            if let Some(redisplayed_ns) = flash.redisplayed_ns {
+                self.glass_to_glass.add(ms(painted_ns, redisplayed_ns));
+            }
+        }
+        if let Some(writer) = &mut self.writer {
+            let field = |value: Option<u64>| value.map(|v| v.to_string()).unwrap_or_default();
+            let _ = writeln!(
+                writer,
+                "{},{},{},{},{},{},{}",
+                flash.id,
+                flash.trigger_id,
+                flash.trigger_ns,
+                field(flash.painted_ns),
+                field(flash.light_ns),
+                field(flash.captured_hw_ns),
+                field(flash.redisplayed_ns)
+            );
+            let _ = writer.flush();
+        }
+    }
+
+    /// Multi-line report with durations in `unit`.
+    pub fn report(&self, unit: Unit) -> String {
+        let mut report = format!("GLASS-TO-GLASS ({} flashes, {} missed):", self.next_id - 1, self.missed);
+        for (name, stats) in [
+            ("trigger->display", &self.trigger_to_display),
+            ("display->light", &self.display_to_light),
+            ("display->capture", &self.display_to_capture),
+            ("glass-to-glass", &self.glass_to_glass),
+        ] {
+            if stats.count() > 0 {
+                report.push_str(&format!("\n  {:<18} {}", name, stats.summary(unit)));
+            }
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: u64 = 1_000_000;
+
+    #[test]
+    fn camera_loop_measures_a_flash_from_paint_to_redisplay() {
+        let mut g2g = GlassToGlass::new(10, None).unwrap();
+        g2g.on_frame(20.0, Some(990 * MS));
+        g2g.on_trigger(9, 995 * MS);
+        assert!(!g2g.marker_lit(995 * MS));
+
+        g2g.on_trigger(10, 1_000 * MS);
+        assert!(g2g.marker_lit(1_000 * MS));
+        g2g.on_painted(1_016 * MS);
+        assert!(g2g.marker_lit(1_100 * MS));
+        assert!(!g2g.marker_lit(1_116 * MS));
+
+        // A dark frame is no detection, the bright one is
+        g2g.on_frame(22.0, Some(1_033 * MS));
+        g2g.on_frame(200.0, Some(1_050 * MS));
+        g2g.on_light_edge(1_030 * MS);
+        g2g.on_painted(1_090 * MS);
+
+        assert_eq!(g2g.trigger_to_display.mean(), 16.0);
+        assert_eq!(g2g.display_to_capture.mean(), 34.0);
+        assert_eq!(g2g.glass_to_glass.mean(), 74.0);
+        // The photodiode edge came after the frame was detected, still for the same flash
+        assert_eq!(g2g.display_to_light.count(), 1);
+        assert!(g2g.report(Unit::Ms).starts_with("GLASS-TO-GLASS (1 flashes, 0 missed):"));
+    }
+
+    #[test]
+    fn undetected_flashes_time_out_as_missed() {
+        let mut g2g = GlassToGlass::new(1, None).unwrap();
+        g2g.on_trigger(1, 0);
+        g2g.on_painted(10 * MS);
+        g2g.on_painted(10 * MS + DETECTION_TIMEOUT_NS + 1);
+        assert_eq!(g2g.missed, 1);
+        assert!(!g2g.marker_lit(10 * MS + DETECTION_TIMEOUT_NS + 1));
+        assert_eq!(g2g.glass_to_glass.count(), 0);
+        assert_eq!(mean_luma(&[255; 3 * 32]), 255.0);
+        assert_eq!(mean_luma(&[]), 0.0);
+    }
+}
@@ -0,0 +1,234 @@
+// GPIO trigger edges with hardware timestamps (Linux GPIO character device).
+//
+// When the trigger line is wired to a GPIO input, the publisher can stamp
+// triggers with the edge time seen by the kernel instead of the time its own
+// loop happened to run. On SoCs with a hardware timestamp engine (HTE, e.g.
+// NVIDIA Jetson) the edge is latched in hardware, independent of interrupt
+// latency. Where the line or kernel has no HTE support, the request falls
+// back to the interrupt-time timestamps of the GPIO driver and the source is
+// reported as `gpio` instead of `hte`.
+//
+// The line is requested through the v2 uAPI (`<linux/gpio.h>`); the structs
+// in `uapi` mirror its layout.
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::os::fd::{AsRawFd, FromRawFd};
+
+use crate::clock;
+use crate::timestamping::{TimestampSource, TriggerTimestamper};
+use crate::units::Nanos;
+use uapi::*;
+
+/// Layout of `<linux/gpio.h>`; most fields are only read by the kernel.
+#[allow(dead_code)]
+mod uapi {
+    pub const GPIO_V2_LINES_MAX: usize = 64;
+    pub const GPIO_V2_LINE_NUM_ATTRS_MAX: usize = 10;
+    pub const GPIO_MAX_NAME_SIZE: usize = 32;
+
+    pub const GPIO_V2_LINE_FLAG_INPUT: u64 = 1 << 2;
+    pub const GPIO_V2_LINE_FLAG_EDGE_RISING: u64 = 1 << 4;
+    pub const GPIO_V2_LINE_FLAG_EDGE_FALLING: u64 = 1 << 5;
+    pub const GPIO_V2_LINE_FLAG_EVENT_CLOCK_REALTIME: u64 = 1 << 11;
+    pub const GPIO_V2_LINE_FLAG_EVENT_CLOCK_HTE: u64 = 1 << 12;
+
+    /// `_IOWR(0xB4, 0x07, struct gpio_v2_line_request)`
+    pub const GPIO_V2_GET_LINE_IOCTL: u64 = 0xC250_B407;
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub struct LineAttribute {
+        pub id: u32,
+        pub padding: u32,
+        pub value: u64,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub struct LineConfigAttribute {
+        pub attr: LineAttribute,
+        pub mask: u64,
+    }
+
+    #[repr(C)]
+    pub struct LineConfig {
+        pub flags: u64,
+        pub num_attrs: u32,
+        pub padding: [u32; 5],
+        pub attrs: [LineConfigAttribute; GPIO_V2_LINE_NUM_ATTRS_MAX],
+    }
+
+    #[repr(C)]
+    pub struct LineRequest {
+        pub offsets: [u32; GPIO_V2_LINES_MAX],
+        pub consumer: [u8; GPIO_MAX_NAME_SIZE],
+        pub config: LineConfig,
+        pub num_lines: u32,
+        pub event_buffer_size: u32,
+        pub padding: [u32; 5],
+        pub fd: i32,
+    }
+
+    /// `struct gpio_v2_line_event`
+    #[repr(C)]
+    #[derive(Default)]
+    pub struct LineEvent {
+        pub timestamp_ns: u64,
+        pub id: u32,
+        pub offset: u32,
+        pub seqno: u32,
+        pub line_seqno: u32,
+        pub padding: [u32; 6],
+    }
+}
+
+/// Which trigger edges are timestamped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+    Rising,
+    Falling,
+    Both,
+}
+
+impl Edge {
+    fn flags(self) -> u64 {
+        match self {
+            Edge::Rising => GPIO_V2_LINE_FLAG_EDGE_RISING,
+            Edge::Falling => GPIO_V2_LINE_FLAG_EDGE_FALLING,
+            Edge::Both => GPIO_V2_LINE_FLAG_EDGE_RISING | GPIO_V2_LINE_FLAG_EDGE_FALLING,
+        }
+    }
+}
+
+impl std::str::FromStr for Edge {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "rising" => Ok(Edge::Rising),
+            "falling" => Ok(Edge::Falling),
+            "both" => Ok(Edge::Both),
+            other => Err(format!("unknown edge '{}' (expected rising, falling or both)", other)),
+        }
+    }
+}
+
+/// Edge events of one GPIO line, timestamped by HTE if available.
+pub struct GpioEdges {
+    chip: String,
+    line: u32,
+    events: File,
+    source: TimestampSource,
+    /// HTE timestamps are in the CLOCK_MONOTONIC domain (on Tegra the HTE
+    /// counter is the system counter), interrupt timestamps in CLOCK_REALTIME.
+    monotonic: bool,
+}
+
+impl GpioEdges {
+    /// Requests `line` of `chip` (e.g. `/dev/gpiochip0`) as an edge-detecting input,
+    /// with HTE timestamps or, where unsupported, interrupt-time CLOCK_REALTIME timestamps.
+    pub fn open(chip: &str, line: u32, edge: Edge) -> io::Result<Self> {
+        let chip_file = File::open(chip)?;
+        let (events, source) = match request_line(&chip_file, line, edge.flags() | GPIO_V2_LINE_FLAG_EVENT_CLOCK_HTE) {
+            Ok(events) => (events, TimestampSource::Hte),
+            // EOPNOTSUPP from lines without an HTE provider, EINVAL from kernels before 5.19
+            Err(e) if matches!(e.raw_os_error(), Some(libc::EOPNOTSUPP) | Some(libc::EINVAL) | Some(libc::ENODEV)) => (
+                request_line(&chip_file, line, edge.flags() | GPIO_V2_LINE_FLAG_EVENT_CLOCK_REALTIME)?,
+                TimestampSource::Gpio,
+            ),
+            Err(e) => return Err(e),
+        };
+        Ok(Self {
+            chip: chip.to_string(),
+            line,
+            events,
+            source,
+            monotonic: source == TimestampSource::Hte,
+        })
+    }
+}
+
+fn request_line(chip: &File, line: u32, flags: u64) -> io::Result<File> {
+    let mut consumer = [0u8; GPIO_MAX_NAME_SIZE];
+    consumer[..b"camera-trigger".len()].copy_from_slice(b"camera-trigger");
+    let mut offsets = [0u32; GPIO_V2_LINES_MAX];
+    offsets[0] = line;
+    let mut request = LineRequest {
+        offsets,
+        consumer,
+        config: LineConfig {
+            flags: GPIO_V2_LINE_FLAG_INPUT | flags,
+            num_attrs: 0,
+            padding: [0; 5],
+            attrs: [LineConfigAttribute {
+                attr: LineAttribute { id: 0, padding: 0, value: 0 },
+                mask: 0,
+            }; GPIO_V2_LINE_NUM_ATTRS_MAX],
+        },
+        num_lines: 1,
+        // Room for a few periods of edges if the publisher stalls
+        event_buffer_size: 64,
+        padding: [0; 5],
+        fd: -1,
+    };
+    // SAFETY: `request` matches `struct gpio_v2_line_request`; the kernel fills in `fd`
+    if unsafe { libc::ioctl(chip.as_raw_fd(), GPIO_V2_GET_LINE_IOCTL as _, &mut request) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: the kernel returned a new file descriptor that nothing else owns
+    Ok(unsafe { File::from_raw_fd(request.fd) })
+}
+
+impl TriggerTimestamper for GpioEdges {
+    fn source(&self) -> TimestampSource {
+        self.source
+    }
+
+    fn describe(&self) -> String {
+        let timestamps = match self.source {
+            TimestampSource::Hte => "hardware timestamp engine",
+            _ => "interrupt time, no HTE support",
+        };
+        format!("GPIO edges on {} line {} ({})", self.chip, self.line, timestamps)
+    }
+
+    fn is_external(&self) -> bool {
+        true
+    }
+
+    fn next(&mut self) -> io::Result<Nanos> {
+        let mut event = LineEvent::default();
+        // SAFETY: `LineEvent` is plain old data, any bytes form a valid value
+        let bytes = unsafe { std::slice::from_raw_parts_mut(&mut event as *mut LineEvent as *mut u8, std::mem::size_of::<LineEvent>()) };
+        self.events.read_exact(bytes)?;
+        if !self.monotonic {
+            return Ok(Nanos(event.timestamp_ns));
+        }
+        // Offset sampled per edge, so clock adjustments while running are followed
+        Ok(Nanos(clock::monotonic_to_realtime_ns(event.timestamp_ns, clock::realtime_minus_monotonic_ns())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem::size_of;
+
+    #[test]
+    fn uapi_structs_match_the_kernel_layout() {
+        // The request size is part of the ioctl number
+        assert_eq!(size_of::<LineRequest>() as u64, (GPIO_V2_GET_LINE_IOCTL >> 16) & 0x3fff);
+        assert_eq!(size_of::<LineConfig>(), 272);
+        assert_eq!(size_of::<LineEvent>(), 48);
+    }
+
+    #[test]
+    fn edges_select_the_event_flags() {
+        assert_eq!("rising".parse::<Edge>().unwrap().flags(), GPIO_V2_LINE_FLAG_EDGE_RISING);
+        assert_eq!("falling".parse::<Edge>().unwrap().flags(), GPIO_V2_LINE_FLAG_EDGE_FALLING);
+        assert_eq!("both".parse::<Edge>().unwrap().flags(), GPIO_V2_LINE_FLAG_EDGE_RISING | GPIO_V2_LINE_FLAG_EDGE_FALLING);
+        assert!("level".parse::<Edge>().is_err());
+        assert!(GpioEdges::open("/nonexistent/gpiochip", 12, Edge::Rising).is_err());
+    }
+}
@@ -0,0 +1,204 @@
+// Wait-free handoff between the capture thread and the UI thread.
+//
+// The capture thread timestamps and matches frames; it must never wait for
+// the UI. A mutex shared with the UI would let a repaint, a slow texture
+// upload or a descheduled UI thread holding the lock delay a frame, and
+// without priority inheritance a low priority UI thread can block a real-time
+// capture thread indefinitely. The two threads therefore share no locks, only
+// these structures:
+//
+//   triple_buffer  latest value from one writer to one reader (preview frame,
+//                  status reports). The writer fills a private slot and swaps
+//                  it with the shared middle slot in one atomic operation; the
+//                  reader swaps the middle slot with its own. Neither side
+//                  ever waits and the reader always gets the newest complete
+//                  value; values overwritten before being read are skipped.
+//   ring           bounded FIFO from one sender to one receiver (display
+//                  events back to the capture thread). Sending to a full ring
+//                  fails instead of waiting.
+//
+// Both are single producer, single consumer: the endpoints are not `Clone`
+// and their methods take `&mut self`. The memory orderings are checked with
+// loom (`RUSTFLAGS="--cfg loom" cargo test -p sync-core --test loom_handoff
+// --release`), which builds this module on loom's atomics and cells.
+
+use std::mem::MaybeUninit;
+
+#[cfg(loom)]
+use loom::cell::UnsafeCell;
+#[cfg(loom)]
+use loom::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+#[cfg(loom)]
+use loom::sync::Arc;
+#[cfg(not(loom))]
+use std::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+#[cfg(not(loom))]
+use std::sync::Arc;
+
+/// `std::cell::UnsafeCell` with the closure access of loom's, so both build the same code.
+#[cfg(not(loom))]
+#[derive(Debug)]
+struct UnsafeCell<T>(std::cell::UnsafeCell<T>);
+
+#[cfg(not(loom))]
+impl<T> UnsafeCell<T> {
+    fn new(value: T) -> Self {
+        Self(std::cell::UnsafeCell::new(value))
+    }
+
+    fn with<R>(&self, f: impl FnOnce(*const T) -> R) -> R {
+        f(self.0.get())
+    }
+
+    fn with_mut<R>(&self, f: impl FnOnce(*mut T) -> R) -> R {
+        f(self.0.get())
+    }
+}
+
+/// Set in `TripleBuffer::middle` while the middle slot holds a value the reader has not taken.
+const DIRTY: u8 = 0b100;
+const INDEX: u8 = 0b011;
+
+struct TripleBuffer<T> {
+    slots: [UnsafeCell<T>; 3],
+    /// Index of the middle slot, with `DIRTY`.
+    middle: AtomicU8,
+}
+
+// Each slot is owned by exactly one of writer, middle and reader at a time;
+// ownership moves only through the atomic swap of `middle`.
+unsafe impl<T: Send> Send for TripleBuffer<T> {}
+unsafe impl<T: Send> Sync for TripleBuffer<T> {}
+
+/// Publishing end of a triple buffer.
+pub struct TripleWriter<T> {
+    shared: Arc<TripleBuffer<T>>,
+    back: u8,
+}
+
+/// Reading end of a triple buffer.
+pub struct TripleReader<T> {
+    shared: Arc<TripleBuffer<T>>,
+    front: u8,
+}
+
+/// Triple buffer whose reader sees `initial` until the first publish.
+pub fn triple_buffer<T: Clone>(initial: T) -> (TripleWriter<T>, TripleReader<T>) {
+    let shared = Arc::new(TripleBuffer {
+        slots: [UnsafeCell::new(initial.clone()), UnsafeCell::new(initial.clone()), UnsafeCell::new(initial)],
+        middle: AtomicU8::new(1),
+    });
+    (TripleWriter { shared: shared.clone(), back: 0 }, TripleReader { shared, front: 2 })
+}
+
+impl<T> TripleWriter<T> {
+    /// Makes `value` the latest value; never waits for the reader.
+    pub fn publish(&mut self, value: T) {
+        // The value replaced in the back slot is dropped here, on the writer's thread
+        self.shared.slots[self.back as usize].with_mut(|slot| unsafe { *slot = value });
+        // Release: the slot contents are visible to the reader that swaps it in
+        let previous = self.shared.middle.swap(self.back | DIRTY, Ordering::AcqRel);
+        self.back = previous & INDEX;
+    }
+
+    /// Whether the last published value has not been taken by the reader yet.
+    pub fn is_unread(&self) -> bool {
+        self.shared.middle.load(Ordering::Relaxed) & DIRTY != 0
+    }
+}
+
+impl<T> TripleReader<T> {
+    /// Takes the latest published value, if there is one the reader has not seen yet.
+    pub fn update(&mut self) -> bool {
+        if self.shared.middle.load(Ordering::Relaxed) & DIRTY == 0 {
+            return false;
+        }
+        // Acquire: pairs with the writer's swap, so the slot is completely written
+        let previous = self.shared.middle.swap(self.front, Ordering::AcqRel);
+        self.front = previous & INDEX;
+        true
+    }
+
+    /// Reads the value taken by the last `update`.
+    pub fn read<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        self.shared.slots[self.front as usize].with(|slot| f(unsafe { &*slot }))
+    }
+
+    /// Like `read`, e.g. to take an `Option` out of the slot.
+    pub fn read_mut<R>(&mut self, f: impl FnOnce(&mut T) -> R) -> R {
+        self.shared.slots[self.front as usize].with_mut(|slot| f(unsafe { &mut *slot }))
+    }
+}
+
+struct Ring<T> {
+    slots: Box<[UnsafeCell<MaybeUninit<T>>]>,
+    /// Count of values received; advanced by the receiver only.
+    head: AtomicUsize,
+    /// Count of values sent; advanced by the sender only.
+    tail: AtomicUsize,
+}
+
+// Slots between head and tail belong to the receiver, the others to the sender.
+unsafe impl<T: Send> Send for Ring<T> {}
+unsafe impl<T: Send> Sync for Ring<T> {}
+
+impl<T> Drop for Ring<T> {
+    fn drop(&mut self) {
+        let (head, tail) = (self.head.load(Ordering::Acquire), self.tail.load(Ordering::Acquire));
+        for index in (0..tail.wrapping_sub(head)).map(|offset| head.wrapping_add(offset)) {
+            self.slots[index % self.slots.len()].with_mut(|slot| unsafe { (*slot).assume_init_drop() });
+        }
+    }
+}
+
+/// Sending end of a ring.
+pub struct RingSender<T> {
+    shared: Arc<Ring<T>>,
+    tail: usize,
+}
+
+/// Receiving end of a ring.
+pub struct RingReceiver<T> {
+    shared: Arc<Ring<T>>,
+    head: usize,
+}
+
+/// Bounded FIFO holding up to `capacity` values.
+pub fn ring<T>(capacity: usize) -> (RingSender<T>, RingReceiver<T>) {
+    let shared = Arc::new(Ring {
+        slots: (0..capacity.max(1)).map(|_| UnsafeCell::new(MaybeUninit::uninit())).collect(),
+        head: AtomicUsize::new(0),
+        tail: AtomicUsize::new(0),
+    });
+    (RingSender { shared: shared.clone(), tail: 0 }, RingReceiver { shared, head: 0 })
+}
+
+impl<T> RingSender<T> {
+    /// Appends `value`, or hands it back if the ring is full; never waits for the receiver.
+    pub fn try_send(&mut self, value: T) -> Result<(), T> {
+        let capacity = self.shared.slots.len();
+        // Acquire: the receiver has finished reading the slots it released
+        if self.tail.wrapping_sub(self.shared.head.load(Ordering::Acquire)) == capacity {
+            return Err(value);
+        }
+        self.shared.slots[self.tail % capacity].with_mut(|slot| unsafe { (*slot).write(value) });
+        self.tail = self.tail.wrapping_add(1);
+        self.shared.tail.store(self.tail, Ordering::Release);
+        Ok(())
+    }
+}
+
+impl<T> RingReceiver<T> {
+    /// Takes the oldest value, `None` if the ring is empty.
+    pub fn try_recv(&mut self) -> Option<T> {
+        // Acquire: pairs with the sender's release, the slot is written
+        if self.head == self.shared.tail.load(Ordering::Acquire) {
+            return None;
+        }
+        let capacity = self.shared.slots.len();
+        let value = self.shared.slots[self.head % capacity].with(|slot| unsafe { (*slot).assume_init_read() });
+        self.head = self.head.wrapping_add(1);
+        self.shared.head.store(self.head, Ordering::Release);
+        Some(value)
+    }
+}
@@ -0,0 +1,101 @@
+// In-memory ring of recently published triggers.
+//
+// The trigger service keeps only the last few triggers as iceoryx2 history.
+// The publisher additionally keeps a much longer ring, which late-joining
+// consumers query with "all triggers since T" over the `Camera/SyncHistory`
+// request-response service (see `sync_iceoryx2::history`), e.g. an analysis
+// tool attached minutes into a run.
+
+#[cfg(feature = "iceoryx2")]
+use iceoryx2::prelude::ZeroCopySend;
+use std::collections::VecDeque;
+
+use crate::clock::{ClockDomain, DomainConverter};
+use crate::signing::TriggerSignature;
+use crate::units::Nanos;
+use crate::CameraTrigger;
+
+/// Triggers kept by default, about 5 minutes at 30 Hz.
+pub const DEFAULT_CAPACITY: usize = 10_000;
+
+/// A trigger with its user header, as kept in the ring and sent in query responses.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "iceoryx2", derive(ZeroCopySend))]
+pub struct HistoryEntry {
+    pub trigger: CameraTrigger,
+    pub header: TriggerSignature,
+}
+
+/// Published triggers in publish order, the oldest dropped when full.
+#[derive(Debug, Clone)]
+pub struct TriggerHistory {
+    entries: VecDeque<HistoryEntry>,
+    capacity: usize,
+}
+
+impl Default for TriggerHistory {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl TriggerHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(1),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn push(&mut self, trigger: CameraTrigger, header: TriggerSignature) {
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(HistoryEntry { trigger, header });
+    }
+
+    /// Triggers with a hardware timestamp at or after `since` (CLOCK_REALTIME), oldest first.
+    pub fn since(&self, since: Nanos) -> impl Iterator<Item = &HistoryEntry> {
+        // Compared in the clock domain the triggers were published in, which one publisher keeps
+        let since = match self.entries.front().and_then(|entry| ClockDomain::from_u8(entry.header.clock_domain)) {
+            Some(domain) => Nanos(DomainConverter::sample().from_realtime_ns(since.0, domain)),
+            None => since,
+        };
+        // Hardware timestamps grow with the trigger id
+        let start = self.entries.partition_point(|entry| entry.trigger.hw_timestamp() < since);
+        self.entries.range(start..)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queries_return_the_triggers_still_in_the_ring() {
+        let mut history = TriggerHistory::new(3);
+        assert!(history.since(Nanos(0)).next().is_none());
+        for id in 1..=5 {
+            history.push(CameraTrigger::new(id, id * 1_000, id * 1_000 + 10), TriggerSignature::default());
+        }
+        assert_eq!(history.len(), 3);
+        let ids = |since: u64| history.since(Nanos(since)).map(|entry| entry.trigger.frame_id).collect::<Vec<_>>();
+        assert_eq!(ids(0), [3, 4, 5]);
+        assert_eq!(ids(4_000), [4, 5]);
+        assert_eq!(ids(4_001), [5]);
+        assert!(ids(6_000).is_empty());
+    }
+}
@@ -0,0 +1,217 @@
+// Camera intrinsics in the ROS `camera_calibration` YAML format.
+//
+// `--camera-info <file.yaml>` loads the file written by the ROS camera
+// calibrator (`camera_info_manager` layout, also produced by OpenCV based
+// tools). The camera processes store it in the session as `camera_info.yaml`,
+// hash it into the manifest and publish it as a `camera_info` telemetry
+// message, so every consumer gets the intrinsics that belong to the frame
+// timestamps. The exporters put them into `sensor_msgs/CameraInfo` and the
+// EuRoC `sensor.yaml`.
+//
+//   image_width: 640
+//   image_height: 480
+//   camera_name: cam0
+//   camera_matrix: {rows: 3, cols: 3, data: [fx, 0, cx, 0, fy, cy, 0, 0, 1]}
+//   distortion_model: plumb_bob
+//   distortion_coefficients: {rows: 1, cols: 5, data: [k1, k2, t1, t2, k3]}
+//   rectification_matrix: {rows: 3, cols: 3, data: [...]}
+//   projection_matrix: {rows: 3, cols: 4, data: [...]}
+//
+// Matrices may also be written as block mappings (`rows:`, `cols:`, `data:` on
+// indented lines), and `data` may span several lines.
+
+use std::fmt::{self, Display};
+use std::fs;
+use std::path::Path;
+
+pub const CAMERA_INFO_FILE: &str = "camera_info.yaml";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CameraIntrinsics {
+    pub camera_name: String,
+    pub width: u32,
+    pub height: u32,
+    /// `plumb_bob`, `rational_polynomial` or `equidistant`.
+    pub distortion_model: String,
+    pub d: Vec<f64>,
+    /// Row-major 3x3 camera matrix.
+    pub k: [f64; 9],
+    /// Row-major 3x3 rectification matrix.
+    pub r: [f64; 9],
+    /// Row-major 3x4 projection matrix.
+    pub p: [f64; 12],
+}
+
+impl CameraIntrinsics {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let text = fs::read_to_string(path)?;
+        Self::parse(&text).map_err(|e| format!("{}: {}", path.display(), e).into())
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut scalars: Vec<(String, String)> = Vec::new();
+        let mut matrices: Vec<(String, Vec<f64>)> = Vec::new();
+        // Top-level key of the block mapping being read, and an unfinished `data: [` list
+        let mut block: Option<String> = None;
+        let mut pending: Option<(String, String)> = None;
+
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or("");
+            if line.trim().is_empty() || line.trim() == "---" || line.trim().starts_with("%YAML") {
+                continue;
+            }
+            if let Some((key, mut list)) = pending.take() {
+                list.push_str(line);
+                if line.contains(']') {
+                    matrices.push((key, parse_list(&list)?));
+                } else {
+                    pending = Some((key, list));
+                }
+                continue;
+            }
+
+            let indented = line.starts_with(char::is_whitespace);
+            let (key, value) = line.trim().split_once(':').ok_or_else(|| format!("expected `key: value`, got '{}'", line.trim()))?;
+            let value = value.trim();
+            if !indented {
+                block = None;
+                if value.is_empty() {
+                    block = Some(key.to_string());
+                } else if value.starts_with('{') {
+                    // Flow mapping: only its `data` list matters
+                    let list = value.split_once("data:").map(|(_, list)| list).ok_or_else(|| format!("{}: missing data", key))?;
+                    matrices.push((key.to_string(), parse_list(list)?));
+                } else {
+                    scalars.push((key.to_string(), value.trim_matches(|c| c == '"' || c == '\'').to_string()));
+                }
+            } else if let (Some(block), "data") = (&block, key) {
+                if value.contains(']') {
+                    matrices.push((block.clone(), parse_list(value)?));
+                } else {
+                    pending = Some((block.clone(), value.to_string()));
+                }
+            }
+        }
+        if let Some((key, _)) = pending {
+            return Err(format!("{}: unterminated data list", key));
+        }
+
+        let scalar = |key: &str| scalars.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str());
+        let matrix = |key: &str| matrices.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_slice());
+        let fixed = |key: &str, default: &[f64]| -> Result<Vec<f64>, String> {
+            let values = matrix(key).unwrap_or(default);
+            if values.len() != default.len() {
+                return Err(format!("{}: expected {} values, got {}", key, default.len(), values.len()));
+            }
+            Ok(values.to_vec())
+        };
+
+        let k = fixed("camera_matrix", &[0.0; 9])?;
+        if k.iter().all(|&v| v == 0.0) {
+            return Err("missing camera_matrix".to_string());
+        }
+        let identity = [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0];
+        // Without a projection matrix, the unrectified camera matrix with zero translation
+        let unrectified = [k[0], k[1], k[2], 0.0, k[3], k[4], k[5], 0.0, k[6], k[7], k[8], 0.0];
+        let r = fixed("rectification_matrix", &identity)?;
+        let p = match matrix("projection_matrix") {
+            Some(_) => fixed("projection_matrix", &[0.0; 12])?,
+            None => unrectified.to_vec(),
+        };
+        Ok(Self {
+            camera_name: scalar("camera_name").unwrap_or("").to_string(),
+            width: scalar("image_width").ok_or("missing image_width")?.parse().map_err(|_| "invalid image_width")?,
+            height: scalar("image_height").ok_or("missing image_height")?.parse().map_err(|_| "invalid image_height")?,
+            distortion_model: scalar("distortion_model").unwrap_or("plumb_bob").to_string(),
+            d: matrix("distortion_coefficients").unwrap_or_default().to_vec(),
+            k: k.try_into().expect("length checked"),
+            r: r.try_into().expect("length checked"),
+            p: p.try_into().expect("length checked"),
+        })
+    }
+
+    /// Focal lengths and principal point: `[fx, fy, cx, cy]`.
+    pub fn pinhole(&self) -> [f64; 4] {
+        [self.k[0], self.k[4], self.k[2], self.k[5]]
+    }
+
+    pub fn write(&self, dir: &Path) -> std::io::Result<()> {
+        fs::write(dir.join(CAMERA_INFO_FILE), self.to_string())
+    }
+}
+
+/// The intrinsics a session was recorded with; `None` if it has no `camera_info.yaml`.
+pub fn read_session(dir: &Path) -> Result<Option<CameraIntrinsics>, Box<dyn std::error::Error>> {
+    let path = dir.join(CAMERA_INFO_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+    CameraIntrinsics::load(&path).map(Some)
+}
+
+fn parse_list(text: &str) -> Result<Vec<f64>, String> {
+    let start = text.find('[').ok_or("expected a [...] list")?;
+    let end = text.rfind(']').ok_or("expected a [...] list")?;
+    text[start + 1..end]
+        .split(',')
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(|value| value.parse().map_err(|_| format!("invalid number '{}'", value)))
+        .collect()
+}
+
+fn list(values: &[f64]) -> String {
+    values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+impl Display for CameraIntrinsics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "image_width: {}", self.width)?;
+        writeln!(f, "image_height: {}", self.height)?;
+        writeln!(f, "camera_name: {}", self.camera_name)?;
+        writeln!(f, "camera_matrix:\n  rows: 3\n  cols: 3\n  data: [{}]", list(&self.k))?;
+        writeln!(f, "distortion_model: {}", self.distortion_model)?;
+        writeln!(f, "distortion_coefficients:\n  rows: 1\n  cols: {}\n  data: [{}]", self.d.len(), list(&self.d))?;
+        writeln!(f, "rectification_matrix:\n  rows: 3\n  cols: 3\n  data: [{}]", list(&self.r))?;
+        writeln!(f, "projection_matrix:\n  rows: 3\n  cols: 4\n  data: [{}]", list(&self.p))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CALIBRATION: &str = "\
+image_width: 640
+image_height: 480
+camera_name: \"cam0\"  # from the ROS calibrator
+camera_matrix: {rows: 3, cols: 3, data: [500.5, 0, 320, 0, 501, 240, 0, 0, 1]}
+distortion_model: plumb_bob
+distortion_coefficients:
+  rows: 1
+  cols: 5
+  data: [-0.1, 0.01,
+         0, 0, 0]
+";
+
+    #[test]
+    fn calibrator_files_parse_and_write_back() {
+        let intrinsics = CameraIntrinsics::parse(CALIBRATION).unwrap();
+        assert_eq!((intrinsics.camera_name.as_str(), intrinsics.width, intrinsics.height), ("cam0", 640, 480));
+        assert_eq!(intrinsics.pinhole(), [500.5, 501.0, 320.0, 240.0]);
+        assert_eq!(intrinsics.d, [-0.1, 0.01, 0.0, 0.0, 0.0]);
+        // Defaults without rectification and projection
+        assert_eq!(intrinsics.r, [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(intrinsics.p, [500.5, 0.0, 320.0, 0.0, 0.0, 501.0, 240.0, 0.0, 0.0, 0.0, 1.0, 0.0]);
+        assert_eq!(CameraIntrinsics::parse(&intrinsics.to_string()), Ok(intrinsics));
+    }
+
+    #[test]
+    fn incomplete_files_are_rejected() {
+        assert_eq!(CameraIntrinsics::parse("image_width: 640\nimage_height: 480\n"), Err("missing camera_matrix".to_string()));
+        let short = CALIBRATION.replace("0, 0, 1]}", "0, 0]}");
+        assert_eq!(CameraIntrinsics::parse(&short), Err("camera_matrix: expected 9 values, got 8".to_string()));
+        let unterminated = CALIBRATION.replace("0, 0, 0]", "0, 0, 0");
+        assert_eq!(CameraIntrinsics::parse(&unterminated), Err("distortion_coefficients: unterminated data list".to_string()));
+    }
+}
@@ -0,0 +1,321 @@
+// Trigger interrupt timestamps taken by an eBPF program (Linux).
+//
+// Where the trigger reaches the host as an interrupt the publisher cannot
+// request as a GPIO line (a deserializer's frame sync, a line owned by a
+// camera driver), the `system` source stamps it only once the publisher got
+// scheduled. The eBPF program in `bpf/trigger_irq.bpf.c` instead stamps every
+// entry of the trigger's IRQ in kernel context: attached to the
+// `irq:irq_handler_entry` tracepoint, it submits the CLOCK_MONOTONIC entry
+// time to a BPF ring buffer. `--timestamp-source irq:<irq>[:<bpf dir>]`
+// writes the IRQ number into the program's config map and reads the ring
+// buffer; both maps are pinned in the bpf dir (`DEFAULT_BPF_DIR`).
+//
+// The program is built with clang and loaded and attached with bpftool (see
+// the README); reading it needs no libbpf. The maps are opened with
+// `BPF_OBJ_GET`, and the ring buffer is consumed like libbpf's
+// `ring_buffer__consume` does: a writable consumer page, then the producer
+// page and the data pages, mapped twice in a row so a record never wraps.
+// Every entry of the IRQ gets a sequence number, also those the program could
+// not submit to a full ring, so gaps count as missed pulses.
+
+use std::fs::File;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+use crate::clock;
+use crate::timestamping::{TimestampSource, TriggerTimestamper};
+use crate::units::Nanos;
+use uapi::*;
+
+/// Where `bpftool prog loadall ... pinmaps` put the maps of the program by default.
+pub const DEFAULT_BPF_DIR: &str = "/sys/fs/bpf/camera_sync";
+
+/// Pinned name of the ring buffer the program submits to.
+pub const EVENTS_MAP: &str = "irq_events";
+
+/// Pinned name of the program's config array.
+pub const CONFIG_MAP: &str = "irq_config";
+
+/// Layout of `<linux/bpf.h>` and of the program's maps; most fields are only read by the kernel.
+#[allow(dead_code)]
+mod uapi {
+    pub const BPF_MAP_UPDATE_ELEM: libc::c_long = 2;
+    pub const BPF_OBJ_GET: libc::c_long = 7;
+    pub const BPF_OBJ_GET_INFO_BY_FD: libc::c_long = 15;
+    pub const BPF_MAP_TYPE_RINGBUF: u32 = 27;
+    pub const BPF_RINGBUF_BUSY_BIT: u32 = 1 << 31;
+    pub const BPF_RINGBUF_DISCARD_BIT: u32 = 1 << 30;
+    pub const BPF_RINGBUF_HDR_SZ: u64 = 8;
+
+    /// `struct trigger_irq_config` of the program, key 0 of its config array.
+    #[repr(C)]
+    pub struct IrqConfig {
+        pub irq: u32,
+        pub armed: u32,
+        pub seqno: u32,
+    }
+
+    // `union bpf_attr` for BPF_OBJ_GET
+    #[repr(C)]
+    pub struct ObjGetAttr {
+        pub pathname: u64,
+        pub bpf_fd: u32,
+        pub file_flags: u32,
+    }
+
+    // `union bpf_attr` for BPF_MAP_*_ELEM
+    #[repr(C)]
+    pub struct MapElemAttr {
+        pub map_fd: u32,
+        pub padding: u32,
+        pub key: u64,
+        pub value: u64,
+        pub flags: u64,
+    }
+
+    // `union bpf_attr` for BPF_OBJ_GET_INFO_BY_FD
+    #[repr(C)]
+    pub struct InfoAttr {
+        pub bpf_fd: u32,
+        pub info_len: u32,
+        pub info: u64,
+    }
+
+    // Leading fields of `struct bpf_map_info`
+    #[repr(C)]
+    #[derive(Default)]
+    pub struct MapInfo {
+        pub map_type: u32,
+        pub id: u32,
+        pub key_size: u32,
+        pub value_size: u32,
+        pub max_entries: u32,
+        pub map_flags: u32,
+    }
+}
+
+/// `struct trigger_irq_event` of the program.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IrqEvent {
+    /// Handler entry, CLOCK_MONOTONIC ns.
+    pub timestamp_ns: u64,
+    pub irq: u32,
+    /// Counts every entry of the IRQ since the config was written.
+    pub seqno: u32,
+}
+
+fn bpf<T>(command: libc::c_long, attr: &mut T) -> io::Result<libc::c_long> {
+    // SAFETY: `attr` is the `union bpf_attr` variant of `command`, its size is passed along
+    let result = unsafe { libc::syscall(libc::SYS_bpf, command, attr as *mut T, std::mem::size_of::<T>() as libc::c_uint) };
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(result)
+}
+
+// Opens a pinned map
+fn open_map(path: &Path) -> io::Result<File> {
+    let pathname = std::ffi::CString::new(path.as_os_str().as_bytes()).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut attr = ObjGetAttr {
+        pathname: pathname.as_ptr() as u64,
+        bpf_fd: 0,
+        file_flags: 0,
+    };
+    let fd = bpf(BPF_OBJ_GET, &mut attr).map_err(|e| io::Error::new(e.kind(), format!("{}: {} (is the trigger_irq program loaded?)", path.display(), e)))?;
+    // SAFETY: the kernel returned a new file descriptor that nothing else owns
+    Ok(unsafe { File::from_raw_fd(fd as i32) })
+}
+
+fn map_info(map: &File) -> io::Result<MapInfo> {
+    let mut info = MapInfo::default();
+    let mut attr = InfoAttr {
+        bpf_fd: map.as_raw_fd() as u32,
+        info_len: std::mem::size_of::<MapInfo>() as u32,
+        info: &mut info as *mut MapInfo as u64,
+    };
+    bpf(BPF_OBJ_GET_INFO_BY_FD, &mut attr)?;
+    Ok(info)
+}
+
+// A mapping of a map's pages, unmapped on drop
+struct Mapping {
+    address: *mut libc::c_void,
+    len: usize,
+}
+
+impl Mapping {
+    fn new(map: &File, len: usize, protection: libc::c_int, offset: usize) -> io::Result<Self> {
+        // SAFETY: a fresh shared mapping of the map's pages, checked below
+        let address = unsafe { libc::mmap(std::ptr::null_mut(), len, protection, libc::MAP_SHARED, map.as_raw_fd(), offset as libc::off_t) };
+        if address == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self { address, len })
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        // SAFETY: mapped in `new` and not used afterwards
+        unsafe { libc::munmap(self.address, self.len) };
+    }
+}
+
+/// Consumer side of a BPF ring buffer map.
+struct RingBuffer {
+    map: File,
+    consumer: Mapping,
+    // Producer page, then the data pages twice
+    producer: Mapping,
+    page_size: usize,
+    mask: u64,
+}
+
+impl RingBuffer {
+    fn open(map: File) -> io::Result<Self> {
+        let info = map_info(&map)?;
+        if info.map_type != BPF_MAP_TYPE_RINGBUF {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("BPF map type {} is not a ring buffer", info.map_type)));
+        }
+        // SAFETY: sysconf has no preconditions
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        let size = info.max_entries as usize;
+        Ok(Self {
+            consumer: Mapping::new(&map, page_size, libc::PROT_READ | libc::PROT_WRITE, 0)?,
+            producer: Mapping::new(&map, page_size + 2 * size, libc::PROT_READ, page_size)?,
+            map,
+            page_size,
+            mask: size as u64 - 1,
+        })
+    }
+
+    fn consumer_pos(&self) -> &AtomicU64 {
+        // SAFETY: the consumer position is the first u64 of the page-aligned consumer page
+        unsafe { AtomicU64::from_ptr(self.consumer.address as *mut u64) }
+    }
+
+    fn producer_pos(&self) -> &AtomicU64 {
+        // SAFETY: the producer position is the first u64 of the page-aligned producer page
+        unsafe { AtomicU64::from_ptr(self.producer.address as *mut u64) }
+    }
+
+    /// The next submitted record, skipping discarded ones; `None` if none is ready.
+    fn next(&mut self) -> Option<IrqEvent> {
+        loop {
+            let consumer = self.consumer_pos().load(Ordering::Acquire);
+            if consumer >= self.producer_pos().load(Ordering::Acquire) {
+                return None;
+            }
+            // SAFETY: records start 8-byte aligned within the data pages, which are mapped twice so
+            // a record starting before their end is contiguous
+            let (header, record) = unsafe {
+                let header = (self.producer.address as *mut u8).add(self.page_size + (consumer & self.mask) as usize);
+                (AtomicU32::from_ptr(header as *mut u32), header.add(BPF_RINGBUF_HDR_SZ as usize))
+            };
+            let len = header.load(Ordering::Acquire);
+            // Reserved, not yet submitted
+            if len & BPF_RINGBUF_BUSY_BIT != 0 {
+                return None;
+            }
+            let payload = len & !(BPF_RINGBUF_BUSY_BIT | BPF_RINGBUF_DISCARD_BIT);
+            let event = (len & BPF_RINGBUF_DISCARD_BIT == 0 && payload as usize >= std::mem::size_of::<IrqEvent>())
+                // SAFETY: the record holds at least an `IrqEvent`, plain old data
+                .then(|| unsafe { std::ptr::read_unaligned(record as *const IrqEvent) });
+            self.consumer_pos().store(consumer + (payload as u64 + BPF_RINGBUF_HDR_SZ).next_multiple_of(8), Ordering::Release);
+            if event.is_some() {
+                return event;
+            }
+        }
+    }
+
+    // Blocks until the program submitted a record
+    fn wait(&self) -> io::Result<()> {
+        let mut poll = libc::pollfd {
+            fd: self.map.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // SAFETY: one valid pollfd
+        if unsafe { libc::poll(&mut poll, 1, -1) } < 0 {
+            let error = io::Error::last_os_error();
+            if error.kind() != io::ErrorKind::Interrupted {
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Entries of the trigger's IRQ, stamped by the eBPF program.
+pub struct IrqTimestamps {
+    irq: u32,
+    dir: PathBuf,
+    events: RingBuffer,
+    // Sequence number of the next entry, from the first one read
+    next_seqno: Option<u32>,
+    missed: u64,
+}
+
+impl IrqTimestamps {
+    /// Arms the program pinned in `dir` for `irq` and opens its ring buffer.
+    pub fn open(irq: u32, dir: &Path) -> io::Result<Self> {
+        let mut events = RingBuffer::open(open_map(&dir.join(EVENTS_MAP))?)?;
+        let config_map = open_map(&dir.join(CONFIG_MAP))?;
+        let key = 0u32;
+        let config = IrqConfig { irq, armed: 1, seqno: 0 };
+        let mut attr = MapElemAttr {
+            map_fd: config_map.as_raw_fd() as u32,
+            padding: 0,
+            key: &key as *const u32 as u64,
+            value: &config as *const IrqConfig as u64,
+            flags: 0,
+        };
+        bpf(BPF_MAP_UPDATE_ELEM, &mut attr)?;
+        // Entries of a previous configuration
+        while events.next().is_some() {}
+        Ok(Self {
+            irq,
+            dir: dir.to_path_buf(),
+            events,
+            next_seqno: None,
+            missed: 0,
+        })
+    }
+}
+
+impl TriggerTimestamper for IrqTimestamps {
+    fn source(&self) -> TimestampSource {
+        TimestampSource::Irq
+    }
+
+    fn describe(&self) -> String {
+        format!("IRQ {} handler entry stamped by eBPF ({})", self.irq, self.dir.display())
+    }
+
+    fn is_external(&self) -> bool {
+        true
+    }
+
+    fn missed(&self) -> u64 {
+        self.missed
+    }
+
+    fn next(&mut self) -> io::Result<Nanos> {
+        loop {
+            if let Some(event) = self.events.next() {
+                // Entries the program could not submit to a full ring
+                if let Some(expected) = self.next_seqno {
+                    self.missed += event.seqno.wrapping_sub(expected) as u64;
+                }
+                self.next_seqno = Some(event.seqno.wrapping_add(1));
+                // Offset sampled per entry, so clock adjustments while running are followed
+                return Ok(Nanos(clock::monotonic_to_realtime_ns(event.timestamp_ns, clock::realtime_minus_monotonic_ns())));
+            }
+            self.events.wait()?;
+        }
+    }
+}
@@ -0,0 +1,196 @@
+// Decomposition of the trigger-to-match latency into pipeline stages.
+//
+// Stages, as far as they can be measured:
+//   exposure            trigger → end of exposure (configured exposure time)
+//   readout+transfer    end of exposure → V4L2 buffer timestamp (sensor readout, USB delivery)
+//   driver→dequeue      V4L2 buffer timestamp → userspace DQBUF return (queueing in the driver)
+//   dequeue→match       DQBUF return → trigger association decided
+//   dequeue→display     DQBUF return → frame painted in the preview (GUI only)
+//   publish→wakeup      trigger published → consumer woken and received it
+//
+// Without a configured exposure time the first two stages are reported together,
+// and without driver timestamps (e.g. nokhwa) everything before the dequeue is
+// one stage. The wakeup stage is measured per received trigger, not per frame:
+// how long a trigger waits before the consumer sees it (the IPC notification
+// for the subscriber, the frame wait for the capture app).
+
+use crate::stats::{LatencyStats, StatsSummary};
+use crate::units::Unit;
+
+/// Timestamps of one matched frame, all in the trigger clock domain (ns).
+#[derive(Debug, Clone, Copy)]
+pub struct FrameTiming {
+    pub trigger_ns: u64,
+    pub driver_ns: Option<u64>,
+    pub dequeue_ns: u64,
+    pub match_ns: u64,
+}
+
+pub struct LatencyBreakdown {
+    exposure_ns: Option<u64>,
+    exposure: LatencyStats,
+    readout_transfer: LatencyStats,
+    driver_to_dequeue: LatencyStats,
+    trigger_to_dequeue: LatencyStats,
+    dequeue_to_match: LatencyStats,
+    total: LatencyStats,
+    dequeue_to_display: LatencyStats,
+    trigger_to_display: LatencyStats,
+    publish_to_wakeup: LatencyStats,
+}
+
+fn delta_ms(from_ns: u64, to_ns: u64) -> f64 {
+    (to_ns as i64 - from_ns as i64) as f64 / 1_000_000.0
+}
+
+impl Default for LatencyBreakdown {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl LatencyBreakdown {
+    /// `exposure_ns` is the sensor exposure time, if known.
+    pub fn new(exposure_ns: Option<u64>) -> Self {
+        Self {
+            exposure_ns,
+            exposure: LatencyStats::default(),
+            readout_transfer: LatencyStats::default(),
+            driver_to_dequeue: LatencyStats::default(),
+            trigger_to_dequeue: LatencyStats::default(),
+            dequeue_to_match: LatencyStats::default(),
+            total: LatencyStats::default(),
+            dequeue_to_display: LatencyStats::default(),
+            trigger_to_display: LatencyStats::default(),
+            publish_to_wakeup: LatencyStats::default(),
+        }
+    }
+
+    pub fn exposure_ns(&self) -> Option<u64> {
+        self.exposure_ns
+    }
+
+    pub fn record(&mut self, timing: &FrameTiming) {
+        match timing.driver_ns {
+            Some(driver_ns) => {
+                let mut readout_start_ns = timing.trigger_ns;
+                if let Some(exposure_ns) = self.exposure_ns {
+                    readout_start_ns += exposure_ns;
+                    self.exposure.add(exposure_ns as f64 / 1_000_000.0);
+                }
+                self.readout_transfer.add(delta_ms(readout_start_ns, driver_ns));
+                self.driver_to_dequeue.add(delta_ms(driver_ns, timing.dequeue_ns));
+            }
+            None => self.trigger_to_dequeue.add(delta_ms(timing.trigger_ns, timing.dequeue_ns)),
+        }
+        self.dequeue_to_match.add(delta_ms(timing.dequeue_ns, timing.match_ns));
+        self.total.add(delta_ms(timing.trigger_ns, timing.match_ns));
+    }
+
+    /// Records when a dequeued frame was presented in the preview. Frames without
+    /// a matched trigger only contribute to the dequeue→display stage.
+    pub fn record_display(&mut self, trigger_ns: Option<u64>, dequeue_ns: u64, displayed_ns: u64) {
+        self.dequeue_to_display.add(delta_ms(dequeue_ns, displayed_ns));
+        if let Some(trigger_ns) = trigger_ns {
+            self.trigger_to_display.add(delta_ms(trigger_ns, displayed_ns));
+        }
+    }
+
+    /// Records when a consumer received a trigger published at `publish_ns`.
+    pub fn record_wakeup(&mut self, publish_ns: u64, received_ns: u64) {
+        self.publish_to_wakeup.add(delta_ms(publish_ns, received_ns));
+    }
+
+    pub fn count(&self) -> u64 {
+        self.total.count()
+    }
+
+    /// Non-empty stages in pipeline order, with their statistics.
+    pub fn stages(&self) -> Vec<(&'static str, &LatencyStats)> {
+        let readout_name = if self.exposure_ns.is_some() { "readout+transfer" } else { "exposure+readout+transfer" };
+        [
+            ("exposure", &self.exposure),
+            (readout_name, &self.readout_transfer),
+            ("driver->dequeue", &self.driver_to_dequeue),
+            ("trigger->dequeue", &self.trigger_to_dequeue),
+            ("dequeue->match", &self.dequeue_to_match),
+            ("total", &self.total),
+            ("dequeue->display", &self.dequeue_to_display),
+            ("trigger->display", &self.trigger_to_display),
+            ("publish->wakeup", &self.publish_to_wakeup),
+        ]
+        .into_iter()
+        .filter(|(_, stats)| stats.count() > 0)
+        .collect()
+    }
+
+    /// Summary per measured stage, keyed by stage name.
+    pub fn snapshot(&self) -> Vec<(&'static str, StatsSummary)> {
+        self.stages().into_iter().map(|(name, stats)| (name, stats.snapshot())).collect()
+    }
+
+    /// Multi-line report with per-stage statistics in `unit` and the share of the mean total (trigger→match) latency.
+    pub fn report(&self, unit: Unit) -> String {
+        let total_mean = self.total.mean();
+        let mut report = format!("LATENCY BREAKDOWN ({} frames):", self.count());
+        for (name, stats) in self.stages() {
+            // Display stages extend past the match and wakeups are per trigger, so they have no share of the total
+            if name.ends_with("->display") || name == "publish->wakeup" {
+                report.push_str(&format!("\n  {:<26}         {}", name, stats.summary(unit)));
+                continue;
+            }
+            let share = if total_mean > 0.0 { stats.mean() / total_mean * 100.0 } else { 0.0 };
+            report.push_str(&format!("\n  {:<26} {:>5.1}%  {}", name, share, stats.summary(unit)));
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: u64 = 1_000_000;
+
+    fn means(breakdown: &LatencyBreakdown) -> Vec<(&'static str, f64)> {
+        breakdown.stages().into_iter().map(|(name, stats)| (name, stats.mean())).collect()
+    }
+
+    #[test]
+    fn stages_split_at_the_measured_timestamps() {
+        let timing = FrameTiming {
+            trigger_ns: 1_000 * MS,
+            driver_ns: Some(1_025 * MS),
+            dequeue_ns: 1_027 * MS,
+            match_ns: 1_028 * MS,
+        };
+        let mut breakdown = LatencyBreakdown::new(Some(10 * MS));
+        breakdown.record(&timing);
+        assert_eq!(
+            means(&breakdown),
+            [("exposure", 10.0), ("readout+transfer", 15.0), ("driver->dequeue", 2.0), ("dequeue->match", 1.0), ("total", 28.0)]
+        );
+
+        // Without an exposure time the sensor stages are one; without driver timestamps everything before the dequeue is
+        let mut breakdown = LatencyBreakdown::default();
+        breakdown.record(&timing);
+        breakdown.record(&FrameTiming { driver_ns: None, ..timing });
+        assert_eq!(breakdown.count(), 2);
+        assert_eq!(
+            means(&breakdown),
+            [("exposure+readout+transfer", 25.0), ("driver->dequeue", 2.0), ("trigger->dequeue", 27.0), ("dequeue->match", 1.0), ("total", 28.0)]
+        );
+        assert!(breakdown.report(Unit::Ms).starts_with("LATENCY BREAKDOWN (2 frames):"));
+    }
+
+    #[test]
+    fn display_stages_are_reported_without_a_share_of_the_total() {
+        let mut breakdown = LatencyBreakdown::default();
+        breakdown.record_display(Some(1_000 * MS), 1_030 * MS, 1_046 * MS);
+        // An unmatched frame only counts from its dequeue
+        breakdown.record_display(None, 1_060 * MS, 1_070 * MS);
+        assert_eq!(means(&breakdown), [("dequeue->display", 13.0), ("trigger->display", 46.0)]);
+        assert_eq!(breakdown.count(), 0);
+        assert!(breakdown.report(Unit::Ms).lines().skip(1).all(|line| !line.contains('%')));
+    }
+}
@@ -0,0 +1,244 @@
+// Kalman filter of the trigger->frame latency and its drift.
+//
+// The frame-minus-trigger offset of a camera is not constant: the sensor and
+// host clocks run at slightly different rates and the pipeline warms up, so
+// the latency walks slowly. The filter tracks the offset and its rate of
+// change (ms per s of trigger time) from the matches, and predicts the
+// latency of the next frame. The `predict` matcher stage subtracts it from
+// the frame time, so triggers are scored by their distance from where the
+// frame's trigger is expected rather than from the frame itself (see
+// `pipeline`). At high frame rates, where neighbouring triggers are only a few
+// ms apart, that keeps a drifting latency from walking the matches onto the
+// wrong trigger.
+//
+// The state is [offset, drift] with a constant-drift model whose drift takes
+// a random walk (`DRIFT_NOISE`). The measurement noise is learned as an
+// exponential average of the squared innovations, starting at
+// `INITIAL_JITTER_MS`. Offsets beyond `OUTLIER_SIGMAS` of the innovation's
+// spread are not fed; after `RESET_AFTER_OUTLIERS` of them in a row the
+// latency has jumped, and the filter starts over at the new offset.
+
+/// Spread of the per-frame latency assumed before any match.
+pub const INITIAL_JITTER_MS: f64 = 1.0;
+
+/// Random walk of the drift, (ms/s)^2 per s.
+pub const DRIFT_NOISE: f64 = 1e-4;
+
+/// Matches before the filter predicts.
+pub const CONVERGED_AFTER: u64 = 8;
+
+/// Offsets further than this many standard deviations from the prediction are outliers.
+pub const OUTLIER_SIGMAS: f64 = 5.0;
+
+/// Outliers in a row that restart the filter.
+pub const RESET_AFTER_OUTLIERS: u32 = 8;
+
+// Innovations averaged into the measurement noise
+const NOISE_WINDOW: f64 = 32.0;
+
+// Smallest measurement noise, (ms)^2: sources with ns jitter would otherwise gate everything out
+const MIN_NOISE_VAR: f64 = 1e-4;
+
+// Drift uncertainty of a fresh filter, (ms/s)^2
+const INITIAL_DRIFT_VAR: f64 = 1.0;
+
+/// Current estimate of a `LatencyFilter`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LatencyEstimate {
+    pub latency_ms: f64,
+    /// Change of the latency, ms per s of trigger time.
+    pub drift_ms_per_s: f64,
+    /// Standard deviation of the latency estimate.
+    pub uncertainty_ms: f64,
+    /// Learned spread of the per-frame latency around the estimate.
+    pub jitter_ms: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct LatencyFilter {
+    offset_ms: f64,
+    drift_ms_per_s: f64,
+    covariance: [[f64; 2]; 2],
+    noise_var: f64,
+    last_ns: Option<u64>,
+    updates: u64,
+    outlier_run: u32,
+    /// Offsets not fed as outliers, and restarts after a jump.
+    pub outliers: u64,
+    pub resets: u64,
+}
+
+impl Default for LatencyFilter {
+    fn default() -> Self {
+        Self {
+            offset_ms: 0.0,
+            drift_ms_per_s: 0.0,
+            covariance: [[0.0; 2]; 2],
+            noise_var: INITIAL_JITTER_MS * INITIAL_JITTER_MS,
+            last_ns: None,
+            updates: 0,
+            outlier_run: 0,
+            outliers: 0,
+            resets: 0,
+        }
+    }
+}
+
+impl LatencyFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_converged(&self) -> bool {
+        self.updates >= CONVERGED_AFTER
+    }
+
+    /// Latency expected for a trigger at `hw_ts`; `None` until converged.
+    pub fn predict(&self, hw_ts: u64) -> Option<f64> {
+        let last_ns = self.last_ns.filter(|_| self.is_converged())?;
+        Some(self.offset_ms + self.drift_ms_per_s * seconds_between(last_ns, hw_ts))
+    }
+
+    pub fn estimate(&self) -> Option<LatencyEstimate> {
+        self.is_converged().then(|| LatencyEstimate {
+            latency_ms: self.offset_ms,
+            drift_ms_per_s: self.drift_ms_per_s,
+            uncertainty_ms: self.covariance[0][0].sqrt(),
+            jitter_ms: self.noise_var.sqrt(),
+        })
+    }
+
+    /// Feeds the latency of a match with the trigger at `hw_ts`; returns its
+    /// innovation (measured minus predicted, ms), `None` if it was an outlier.
+    pub fn update(&mut self, hw_ts: u64, latency_ms: f64) -> Option<f64> {
+        let Some(last_ns) = self.last_ns else {
+            self.start(hw_ts, latency_ms);
+            return Some(0.0);
+        };
+        let dt = seconds_between(last_ns, hw_ts);
+        let [[p00, p01], [p10, p11]] = self.covariance;
+        // Predict: offset moves with the drift, the drift takes a random walk
+        let offset = self.offset_ms + self.drift_ms_per_s * dt;
+        let p00 = p00 + dt * (p10 + p01) + dt * dt * p11 + DRIFT_NOISE * dt * dt * dt / 3.0;
+        let p01 = p01 + dt * p11 + DRIFT_NOISE * dt * dt / 2.0;
+        let p10 = p10 + dt * p11 + DRIFT_NOISE * dt * dt / 2.0;
+        let p11 = p11 + DRIFT_NOISE * dt;
+
+        let innovation = latency_ms - offset;
+        let spread = p00 + self.noise_var;
+        if self.updates > 1 && innovation * innovation > OUTLIER_SIGMAS * OUTLIER_SIGMAS * spread {
+            self.outliers += 1;
+            self.outlier_run += 1;
+            if self.outlier_run >= RESET_AFTER_OUTLIERS {
+                self.resets += 1;
+                self.start(hw_ts, latency_ms);
+            }
+            return None;
+        }
+        self.outlier_run = 0;
+
+        // Correct with the measured offset
+        let (k0, k1) = (p00 / spread, p10 / spread);
+        self.offset_ms = offset + k0 * innovation;
+        self.drift_ms_per_s += k1 * innovation;
+        self.covariance = [[(1.0 - k0) * p00, (1.0 - k0) * p01], [p10 - k1 * p00, p11 - k1 * p01]];
+        // The innovation spreads by the prediction's uncertainty on top of the jitter
+        let jitter_sample = (innovation * innovation - p00).max(0.0);
+        self.noise_var = (self.noise_var + (jitter_sample - self.noise_var) / NOISE_WINDOW).max(MIN_NOISE_VAR);
+        self.last_ns = Some(hw_ts);
+        self.updates += 1;
+        Some(innovation)
+    }
+
+    /// Forgets the learned latency, e.g. when the matcher relearns.
+    pub fn reset(&mut self) {
+        *self = Self {
+            outliers: self.outliers,
+            resets: self.resets,
+            ..Self::default()
+        };
+    }
+
+    fn start(&mut self, hw_ts: u64, latency_ms: f64) {
+        let noise_var = self.noise_var;
+        self.reset();
+        self.noise_var = noise_var;
+        self.offset_ms = latency_ms;
+        self.covariance = [[noise_var, 0.0], [0.0, INITIAL_DRIFT_VAR]];
+        self.last_ns = Some(hw_ts);
+        self.updates = 1;
+    }
+}
+
+// Signed time from `from_ns` to `to_ns`, s
+fn seconds_between(from_ns: u64, to_ns: u64) -> f64 {
+    (to_ns as i128 - from_ns as i128) as f64 / 1e9
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::burst::BurstPattern;
+    use crate::pipeline::{self, MatchPipeline, Stage};
+    use crate::CameraTrigger;
+
+    const PERIOD_NS: u64 = 8_333_333;
+    const START_NS: u64 = 1_700_000_000_000_000_000;
+
+    // Latency of frame `n`: 5ms drifting by 2ms/s, with +-0.3ms of jitter
+    fn latency_ns(n: u64) -> u64 {
+        let drift_ns = n * PERIOD_NS * 2 / 1000;
+        let jitter_ns = (n * 7919 % 601) * 1000;
+        5_000_000 + drift_ns + jitter_ns - 300_000
+    }
+
+    // Frames whose matched trigger id is not their own
+    fn mismatches(stages: &[Stage]) -> (usize, MatchPipeline<()>) {
+        let mut pipeline = MatchPipeline::with_stages(stages, 50.0, BurstPattern::default());
+        let mut queued = 0;
+        let mut wrong = 0;
+        for n in 0..600 {
+            let frame_ns = START_NS + n * PERIOD_NS + latency_ns(n);
+            while START_NS + queued * PERIOD_NS <= frame_ns {
+                pipeline.on_trigger(CameraTrigger::new(queued, START_NS + queued * PERIOD_NS, START_NS + queued * PERIOD_NS), ());
+                queued += 1;
+            }
+            let matched = pipeline.on_frame(frame_ns).matched.map(|(trigger, _, _)| trigger.frame_id);
+            wrong += usize::from(matched != Some(n));
+        }
+        (wrong, pipeline)
+    }
+
+    #[test]
+    fn predicted_latency_follows_the_drift() {
+        // From 5ms to 15ms over 5s: past the 8.3ms interval the nearest trigger is the next one
+        let (wrong, _) = mismatches(&[Stage::Associate]);
+        assert!(wrong > 200, "{} frames on the wrong trigger", wrong);
+        let (wrong, pipeline) = mismatches(&[Stage::Predict, Stage::Associate]);
+        assert_eq!(wrong, 0);
+        let estimate = pipeline.latency_estimate().unwrap();
+        assert!((estimate.drift_ms_per_s - 2.0).abs() < 0.2, "{:?}", estimate);
+        assert!((estimate.latency_ms - latency_ns(599) as f64 / 1e6).abs() < 1.0, "{:?}", estimate);
+
+        assert!(pipeline::parse_stages("drift,predict,associate").is_err());
+        assert_eq!(pipeline::parse_stages("dedupe,predict,associate").unwrap()[1], Stage::Predict);
+    }
+
+    #[test]
+    fn a_latency_jump_restarts_the_filter() {
+        let mut filter = LatencyFilter::new();
+        assert_eq!(filter.predict(START_NS), None);
+        for n in 0..100 {
+            filter.update(START_NS + n * PERIOD_NS, 12.0);
+        }
+        assert!((filter.predict(START_NS + 100 * PERIOD_NS).unwrap() - 12.0).abs() < 0.01);
+        for n in 100..100 + RESET_AFTER_OUTLIERS as u64 {
+            assert_eq!(filter.update(START_NS + n * PERIOD_NS, 32.0), None);
+        }
+        assert_eq!((filter.outliers, filter.resets), (RESET_AFTER_OUTLIERS as u64, 1));
+        for n in 110..130 {
+            assert!(filter.update(START_NS + n * PERIOD_NS, 32.0).is_some());
+        }
+        assert!((filter.estimate().unwrap().latency_ms - 32.0).abs() < 0.01);
+    }
+}
@@ -0,0 +1,237 @@
+// Memory layout of the shared-memory payloads.
+//
+// Processes built with different Rust versions, and C or C++ consumers, read
+// the same samples, so the payloads must have a layout no compiler is free to
+// change. `CameraTrigger` is `#[repr(C)]` and travels as is on the trigger
+// service and in history responses, like the user header (`TriggerSignature`)
+// and the batched samples (`trigger_batch`).
+//
+// `CameraTrigger` is versioned: every trigger carries the
+// `TRIGGER_SCHEMA_VERSION` of its writer, and the version is part of the
+// iceoryx2 type names of the trigger and batch payloads (`camera_trigger_v5`,
+// `camera_trigger_batch_v5`). iceoryx2 refuses to open a service whose type
+// name, size or alignment differs, so a binary built against another layout
+// fails at service-open time instead of misreading the fields; subscribers
+// also reject payloads of another version from writers that bypass the type
+// check. Bump the version with every change of the payload layout, and with
+// every change of the user header's meaning: version 3 added the clock domain
+// of the timestamps to the header (see `clock::ClockDomain`), version 4 the
+// offset of the publisher's PTP hardware clock (see `timestamping`) at the end
+// of the header, version 5 the publisher's clock downgrade policy (see
+// `clock_quality`) in what was padding.
+//
+// The layout is pinned three ways: the constant assertions below fail the
+// build if a field moves, `c_header` generates the C header checked in as
+// `crates/sync-core/include/camera_sync.h` (with the same assertions as
+// `_Static_assert`), and `tests/layout.rs` checks that the header is current
+// and round-trips samples through a C program compiled against it.
+// Regenerate the header with `cargo run --bin c_header`.
+
+#[cfg(feature = "iceoryx2")]
+use iceoryx2::prelude::ZeroCopySend;
+use std::fmt::Write;
+use std::mem::{offset_of, size_of};
+
+use crate::canary::CANARY_BIT;
+use crate::clock::ClockDomain;
+use crate::clock_quality::DowngradePolicy;
+use crate::signing::TriggerSignature;
+use crate::trigger_batch::{TriggerBatch, TriggerDelta, MAX_BATCH};
+use crate::units::Nanos;
+
+/// Layout version of `CameraTrigger`; version 1 was the unversioned 24-byte payload, version 2 had no clock domain,
+/// version 3 no PHC offset, version 4 no clock policy.
+pub const TRIGGER_SCHEMA_VERSION: u32 = 5;
+
+/// iceoryx2 type names of `CameraTrigger` and `TriggerBatch`, for C++ consumers; the same as in their derive attributes.
+pub const TRIGGER_TYPE_NAME: &str = "camera_trigger_v5";
+pub const BATCH_TYPE_NAME: &str = "camera_trigger_batch_v5";
+
+/// `sequence_flags` bit of canary triggers (whose id has `CANARY_BIT`); the other bits are reserved and zero.
+pub const SEQUENCE_CANARY: u32 = 1 << 0;
+
+/// A camera trigger, in process and over shared memory.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "iceoryx2", derive(ZeroCopySend), type_name("camera_trigger_v5"))]
+pub struct CameraTrigger {
+    /// Trigger id, counting up from the publisher's start (see `canary` for the reserved bits).
+    pub frame_id: u64,
+    /// ns in the header's clock domain, CLOCK_REALTIME unless the publisher chose another.
+    pub hw_timestamp_ns: u64,
+    pub publish_timestamp_ns: u64,
+    /// `SEQUENCE_*` bits.
+    pub sequence_flags: u32,
+    /// `TRIGGER_SCHEMA_VERSION` of the writer.
+    pub schema_version: u32,
+}
+
+impl Default for CameraTrigger {
+    fn default() -> Self {
+        Self::new(0, 0, 0)
+    }
+}
+
+impl CameraTrigger {
+    /// A trigger of this layout version, flagged as canary if `frame_id` has `CANARY_BIT`.
+    pub fn new(frame_id: u64, hw_timestamp_ns: u64, publish_timestamp_ns: u64) -> Self {
+        Self {
+            frame_id,
+            hw_timestamp_ns,
+            publish_timestamp_ns,
+            sequence_flags: if frame_id & CANARY_BIT != 0 { SEQUENCE_CANARY } else { 0 },
+            schema_version: TRIGGER_SCHEMA_VERSION,
+        }
+    }
+
+    /// Fails for a payload written with another layout version.
+    pub fn check_version(&self) -> Result<(), String> {
+        if self.schema_version == TRIGGER_SCHEMA_VERSION {
+            return Ok(());
+        }
+        Err(format!(
+            "trigger payload schema version {} (expected {}), rebuild publisher and subscribers from the same release",
+            self.schema_version, TRIGGER_SCHEMA_VERSION
+        ))
+    }
+
+    pub fn hw_timestamp(&self) -> Nanos {
+        Nanos(self.hw_timestamp_ns)
+    }
+
+    pub fn publish_timestamp(&self) -> Nanos {
+        Nanos(self.publish_timestamp_ns)
+    }
+}
+
+// The wire layout; changing any of these breaks every deployed consumer
+const _: () = {
+    assert!(TRIGGER_SCHEMA_VERSION == 5);
+    assert!(size_of::<CameraTrigger>() == 32);
+    assert!(offset_of!(CameraTrigger, frame_id) == 0);
+    assert!(offset_of!(CameraTrigger, hw_timestamp_ns) == 8);
+    assert!(offset_of!(CameraTrigger, publish_timestamp_ns) == 16);
+    assert!(offset_of!(CameraTrigger, sequence_flags) == 24);
+    assert!(offset_of!(CameraTrigger, schema_version) == 28);
+
+    assert!(size_of::<TriggerSignature>() == 88);
+    assert!(offset_of!(TriggerSignature, key_id) == 0);
+    assert!(offset_of!(TriggerSignature, signed) == 8);
+    assert!(offset_of!(TriggerSignature, timestamp_source) == 9);
+    assert!(offset_of!(TriggerSignature, signature) == 10);
+    assert!(offset_of!(TriggerSignature, clock_domain) == 74);
+    assert!(offset_of!(TriggerSignature, clock_policy) == 75);
+    assert!(offset_of!(TriggerSignature, trace_origin) == 76);
+    assert!(offset_of!(TriggerSignature, phc_offset_ns) == 80);
+
+    assert!(size_of::<TriggerDelta>() == 12);
+    assert!(offset_of!(TriggerDelta, trigger_id) == 0);
+    assert!(offset_of!(TriggerDelta, hw_timestamp_ns) == 4);
+    assert!(offset_of!(TriggerDelta, publish_timestamp_ns) == 8);
+
+    assert!(MAX_BATCH == 64);
+    assert!(size_of::<TriggerBatch>() == 792);
+    assert!(offset_of!(TriggerBatch, first) == 0);
+    assert!(offset_of!(TriggerBatch, count) == 32);
+    assert!(offset_of!(TriggerBatch, deltas) == 36);
+};
+
+/// A payload struct as described to C: name, size and `(C type, field, offset)`.
+struct CStruct {
+    name: &'static str,
+    comment: &'static str,
+    size: usize,
+    fields: &'static [(&'static str, &'static str, usize)],
+}
+
+const C_STRUCTS: &[CStruct] = &[
+    CStruct {
+        name: "camera_trigger",
+        comment: "Payload of the trigger service (`Camera/Sync`); timestamps in ns of the header's clock_domain, schema_version is CAMERA_SYNC_SCHEMA_VERSION.",
+        size: size_of::<CameraTrigger>(),
+        fields: &[
+            // `trigger_id` in C, which consumers already compile against
+            ("uint64_t", "trigger_id", offset_of!(CameraTrigger, frame_id)),
+            ("uint64_t", "hw_timestamp_ns", offset_of!(CameraTrigger, hw_timestamp_ns)),
+            ("uint64_t", "publish_timestamp_ns", offset_of!(CameraTrigger, publish_timestamp_ns)),
+            ("uint32_t", "sequence_flags", offset_of!(CameraTrigger, sequence_flags)),
+            ("uint32_t", "schema_version", offset_of!(CameraTrigger, schema_version)),
+        ],
+    },
+    CStruct {
+        name: "trigger_signature",
+        comment: "User header of the trigger service: ed25519 signature, timestamp source, clock domain, clock policy, trace origin and PHC offset.",
+        size: size_of::<TriggerSignature>(),
+        fields: &[
+            ("uint64_t", "key_id", offset_of!(TriggerSignature, key_id)),
+            ("bool", "is_signed", offset_of!(TriggerSignature, signed)),
+            ("uint8_t", "timestamp_source", offset_of!(TriggerSignature, timestamp_source)),
+            ("uint8_t", "signature[64]", offset_of!(TriggerSignature, signature)),
+            ("uint8_t", "clock_domain", offset_of!(TriggerSignature, clock_domain)),
+            ("uint8_t", "clock_policy", offset_of!(TriggerSignature, clock_policy)),
+            ("uint32_t", "trace_origin", offset_of!(TriggerSignature, trace_origin)),
+            ("int64_t", "phc_offset_ns", offset_of!(TriggerSignature, phc_offset_ns)),
+        ],
+    },
+    CStruct {
+        name: "camera_trigger_delta",
+        comment: "A batched trigger as the difference to the one before it.",
+        size: size_of::<TriggerDelta>(),
+        fields: &[
+            ("uint32_t", "trigger_id", offset_of!(TriggerDelta, trigger_id)),
+            ("uint32_t", "hw_timestamp_ns", offset_of!(TriggerDelta, hw_timestamp_ns)),
+            ("uint32_t", "publish_timestamp_ns", offset_of!(TriggerDelta, publish_timestamp_ns)),
+        ],
+    },
+    CStruct {
+        name: "camera_trigger_batch",
+        comment: "Payload of the batched trigger service: `count` triggers, the first in full; deltas valid up to `count - 1`.",
+        size: size_of::<TriggerBatch>(),
+        fields: &[
+            ("camera_trigger", "first", offset_of!(TriggerBatch, first)),
+            ("uint32_t", "count", offset_of!(TriggerBatch, count)),
+            ("camera_trigger_delta", "deltas[63]", offset_of!(TriggerBatch, deltas)),
+        ],
+    },
+];
+
+/// C header declaring the payloads, with their layout as `_Static_assert`s.
+pub fn c_header() -> String {
+    let mut header = String::new();
+    header.push_str("/* Shared-memory payloads of the camera trigger sync services.\n");
+    header.push_str(" * Generated by `cargo run --bin c_header` from sync_core::layout, do not edit. */\n\n");
+    header.push_str("#ifndef CAMERA_SYNC_H\n#define CAMERA_SYNC_H\n\n");
+    header.push_str("#include <stdbool.h>\n#include <stddef.h>\n#include <stdint.h>\n");
+    header.push_str("\n/* Trigger ids with this bit set are canaries (see sync_core::canary), never match them. */\n");
+    let _ = writeln!(header, "#define CAMERA_SYNC_CANARY_BIT UINT64_C({:#x})", CANARY_BIT);
+    header.push_str("\n/* camera_trigger.sequence_flags of canary triggers; the other bits are reserved and zero. */\n");
+    let _ = writeln!(header, "#define CAMERA_SYNC_SEQUENCE_CANARY UINT32_C({:#x})", SEQUENCE_CANARY);
+    header.push_str("\n/* Layout version in camera_trigger.schema_version, and the iceoryx2 type names of the payloads. */\n");
+    let _ = writeln!(header, "#define CAMERA_SYNC_SCHEMA_VERSION UINT32_C({})", TRIGGER_SCHEMA_VERSION);
+    let _ = writeln!(header, "#define CAMERA_SYNC_TRIGGER_TYPE_NAME \"{}\"", TRIGGER_TYPE_NAME);
+    let _ = writeln!(header, "#define CAMERA_SYNC_BATCH_TYPE_NAME \"{}\"", BATCH_TYPE_NAME);
+    header.push_str("\n/* trigger_signature.clock_domain: the clock both timestamps count in (see sync_core::clock). */\n");
+    for domain in [ClockDomain::Realtime, ClockDomain::Monotonic, ClockDomain::Tai, ClockDomain::Ptp] {
+        let _ = writeln!(header, "#define CAMERA_SYNC_CLOCK_{} UINT8_C({})", domain.to_string().to_uppercase(), domain as u8);
+    }
+    header.push_str("\n/* trigger_signature.clock_policy: what consumers do while the publisher's clock is downgraded (see sync_core::clock_quality). */\n");
+    header.push_str("#define CAMERA_SYNC_CLOCK_POLICY_NONE UINT8_C(0)\n");
+    for policy in [DowngradePolicy::Flag, DowngradePolicy::Widen, DowngradePolicy::Pause] {
+        let _ = writeln!(header, "#define CAMERA_SYNC_CLOCK_POLICY_{} UINT8_C({})", policy.to_string().to_uppercase(), policy as u8);
+    }
+    for c_struct in C_STRUCTS {
+        let _ = writeln!(header, "\n/* {} */", c_struct.comment);
+        let _ = writeln!(header, "typedef struct {} {{", c_struct.name);
+        for (c_type, field, _) in c_struct.fields {
+            let _ = writeln!(header, "    {} {};", c_type, field);
+        }
+        let _ = writeln!(header, "}} {};\n", c_struct.name);
+        let _ = writeln!(header, "_Static_assert(sizeof({0}) == {1}, \"{0} size\");", c_struct.name, c_struct.size);
+        for (_, field, offset) in c_struct.fields {
+            let field = field.split('[').next().unwrap_or(field);
+            let _ = writeln!(header, "_Static_assert(offsetof({0}, {1}) == {2}, \"{0}.{1} offset\");", c_struct.name, field, offset);
+        }
+    }
+    header.push_str("\n#endif /* CAMERA_SYNC_H */\n");
+    header
+}
@@ -0,0 +1,296 @@
+// Leap seconds: TAI as the absolute timescale, UTC for display.
+//
+// CLOCK_REALTIME counts UTC, and UTC repeats a second whenever a leap second
+// is inserted (the kernel steps the clock back at midnight), so the difference
+// of two realtime timestamps across a leap second is a second short. TAI has
+// no leap seconds: it is what PTP hardware clocks count, and the timescale on
+// which intervals of multi-hour recordings stay consistent. A `Tai` timestamp
+// is TAI nanoseconds since 1970-01-01 00:00:00 TAI (the PTP epoch); it
+// converts from and to UTC with the TAI-UTC offset valid at that instant.
+//
+// The offsets come from a `LeapSeconds` table. The bundled one ends with the
+// leap second of 2016-12-31 (TAI-UTC 37s) and is valid until its expiry date;
+// `--leap-seconds <file>` loads a newer `leap-seconds.list` as published by
+// the IERS and IETF (NTP seconds since 1900 and TAI-UTC per line, `#@` the
+// expiry). The process-wide table (`table()`) is the bundled one unless the
+// binary installed another at startup (`install_from_args`). During an
+// inserted leap second, UTC repeats 23:59:59 like the kernel does; for display,
+// `utc_datetime` shows it as 23:59:60.
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::sync::OnceLock;
+
+use crate::cli::Args;
+use crate::export;
+use crate::units::Nanos;
+
+/// NTP epoch (1900-01-01) to Unix epoch, seconds.
+const NTP_UNIX_OFFSET_S: u64 = 2_208_988_800;
+
+const NS_PER_SECOND: u64 = 1_000_000_000;
+
+/// `(Unix seconds from which it applies, TAI-UTC seconds)`, from the IERS `leap-seconds.list`.
+const BUNDLED: [(u64, u32); 28] = [
+    (63_072_000, 10),    // 1972-01-01
+    (78_796_800, 11),    // 1972-07-01
+    (94_694_400, 12),    // 1973-01-01
+    (126_230_400, 13),   // 1974-01-01
+    (157_766_400, 14),   // 1975-01-01
+    (189_302_400, 15),   // 1976-01-01
+    (220_924_800, 16),   // 1977-01-01
+    (252_460_800, 17),   // 1978-01-01
+    (283_996_800, 18),   // 1979-01-01
+    (315_532_800, 19),   // 1980-01-01
+    (362_793_600, 20),   // 1981-07-01
+    (394_329_600, 21),   // 1982-07-01
+    (425_865_600, 22),   // 1983-07-01
+    (489_024_000, 23),   // 1985-07-01
+    (567_993_600, 24),   // 1988-01-01
+    (631_152_000, 25),   // 1990-01-01
+    (662_688_000, 26),   // 1991-01-01
+    (709_948_800, 27),   // 1992-07-01
+    (741_484_800, 28),   // 1993-07-01
+    (773_020_800, 29),   // 1994-07-01
+    (820_454_400, 30),   // 1996-01-01
+    (867_715_200, 31),   // 1997-07-01
+    (915_148_800, 32),   // 1999-01-01
+    (1_136_073_600, 33), // 2006-01-01
+    (1_230_768_000, 34), // 2009-01-01
+    (1_341_100_800, 35), // 2012-07-01
+    (1_435_708_800, 36), // 2015-07-01
+    (1_483_228_800, 37), // 2017-01-01
+];
+
+/// Expiry of the bundled table (2026-12-28), Unix seconds.
+const BUNDLED_EXPIRES_UNIX_S: u64 = 1_798_416_000;
+
+/// TAI nanoseconds since 1970-01-01 00:00:00 TAI, the timescale of PTP hardware clocks.
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Tai(pub u64);
+
+impl Tai {
+    /// `utc` on the process-wide table.
+    pub fn from_utc(utc: Nanos) -> Self {
+        table().to_tai(utc)
+    }
+
+    /// UTC of this instant on the process-wide table.
+    pub fn to_utc(self) -> Nanos {
+        table().to_utc(self)
+    }
+
+    /// Signed interval from `earlier`, leap seconds included.
+    pub fn ns_since(self, earlier: Tai) -> i64 {
+        self.0 as i64 - earlier.0 as i64
+    }
+}
+
+impl fmt::Display for Tai {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}ns TAI", self.0)
+    }
+}
+
+/// TAI-UTC offsets over time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeapSeconds {
+    // (Unix seconds from which it applies, TAI-UTC seconds), ascending
+    changes: Vec<(u64, u32)>,
+    expires_unix_s: Option<u64>,
+}
+
+impl LeapSeconds {
+    pub fn bundled() -> Self {
+        Self {
+            changes: BUNDLED.to_vec(),
+            expires_unix_s: Some(BUNDLED_EXPIRES_UNIX_S),
+        }
+    }
+
+    /// Parses the `leap-seconds.list` format: `<NTP seconds> <TAI-UTC>` per line, `#@ <NTP seconds>` the expiry, other `#` lines comments.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut changes = Vec::new();
+        let mut expires_unix_s = None;
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if let Some(expiry) = line.strip_prefix("#@") {
+                let ntp_s: u64 = expiry.trim().parse().map_err(|_| format!("line {}: invalid expiry '{}'", index + 1, expiry.trim()))?;
+                expires_unix_s = Some(ntp_s.saturating_sub(NTP_UNIX_OFFSET_S));
+                continue;
+            }
+            let entry = line.split('#').next().unwrap_or_default();
+            let mut fields = entry.split_whitespace();
+            let (Some(ntp_s), Some(offset_s)) = (fields.next(), fields.next()) else {
+                continue;
+            };
+            let ntp_s: u64 = ntp_s.parse().map_err(|_| format!("line {}: invalid NTP time '{}'", index + 1, ntp_s))?;
+            let offset_s: u32 = offset_s.parse().map_err(|_| format!("line {}: invalid TAI-UTC '{}'", index + 1, offset_s))?;
+            let unix_s = ntp_s.checked_sub(NTP_UNIX_OFFSET_S).ok_or_else(|| format!("line {}: before the Unix epoch", index + 1))?;
+            if changes.last().is_some_and(|&(last_s, _)| unix_s <= last_s) {
+                return Err(format!("line {}: entries out of order", index + 1));
+            }
+            changes.push((unix_s, offset_s));
+        }
+        if changes.is_empty() {
+            return Err("no leap second entries".to_string());
+        }
+        Ok(Self { changes, expires_unix_s })
+    }
+
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let text = fs::read_to_string(path).map_err(|e| format!("cannot read leap second table {}: {}", path, e))?;
+        Ok(Self::parse(&text).map_err(|e| format!("{}: {}", path, e))?)
+    }
+
+    /// `--leap-seconds <file>`, the bundled table without it.
+    pub fn from_args(args: &Args) -> Result<Self, Box<dyn Error>> {
+        args.value("leap-seconds").map_or_else(|| Ok(Self::bundled()), Self::load)
+    }
+
+    /// TAI-UTC at the UTC instant `utc`; the first offset before the table starts.
+    pub fn tai_minus_utc_ns(&self, utc: Nanos) -> u64 {
+        let seconds = utc.0 / NS_PER_SECOND;
+        let after = self.changes.partition_point(|&(unix_s, _)| unix_s <= seconds);
+        self.changes[after.saturating_sub(1)].1 as u64 * NS_PER_SECOND
+    }
+
+    pub fn to_tai(&self, utc: Nanos) -> Tai {
+        Tai(utc.0 + self.tai_minus_utc_ns(utc))
+    }
+
+    /// UTC of `tai`; during an inserted leap second the last second of the day repeats.
+    pub fn to_utc(&self, tai: Tai) -> Nanos {
+        Nanos(tai.0.saturating_sub(self.tai_minus_utc_at_tai_ns(tai)))
+    }
+
+    /// Whether `tai` falls into an inserted leap second (23:59:60 UTC).
+    pub fn is_leap_second(&self, tai: Tai) -> bool {
+        self.changes.windows(2).any(|pair| {
+            let ((_, before_s), (unix_s, offset_s)) = (pair[0], pair[1]);
+            let start_ns = (unix_s + offset_s as u64 - 1) * NS_PER_SECOND;
+            offset_s > before_s && (start_ns..start_ns + NS_PER_SECOND).contains(&tai.0)
+        })
+    }
+
+    /// `YYYY-MM-DD HH:MM:SS.nnnnnnnnn` (UTC) of `tai`, with second 60 during a leap second.
+    pub fn utc_datetime(&self, tai: Tai) -> String {
+        let mut datetime = export::utc_datetime(self.to_utc(tai).0);
+        if self.is_leap_second(tai) {
+            datetime.replace_range(17..19, "60");
+        }
+        datetime
+    }
+
+    /// Unix seconds after which the table may miss announced leap seconds.
+    pub fn expires_unix_s(&self) -> Option<u64> {
+        self.expires_unix_s
+    }
+
+    pub fn is_expired(&self, now: Nanos) -> bool {
+        self.expires_unix_s.is_some_and(|expires_s| now.0 / NS_PER_SECOND >= expires_s)
+    }
+
+    /// Latest TAI-UTC in the table, seconds.
+    pub fn current_offset_s(&self) -> u32 {
+        self.changes.last().map_or(0, |&(_, offset_s)| offset_s)
+    }
+
+    // A change to offset n at Unix second T starts at TAI T + n - 1: the inserted second
+    // already counts with the new offset, so UTC repeats T - 1
+    fn tai_minus_utc_at_tai_ns(&self, tai: Tai) -> u64 {
+        let seconds = tai.0 / NS_PER_SECOND;
+        let after = self.changes.partition_point(|&(unix_s, offset_s)| unix_s + offset_s as u64 - 1 <= seconds);
+        self.changes[after.saturating_sub(1)].1 as u64 * NS_PER_SECOND
+    }
+}
+
+impl Default for LeapSeconds {
+    fn default() -> Self {
+        Self::bundled()
+    }
+}
+
+static TABLE: OnceLock<LeapSeconds> = OnceLock::new();
+
+/// The process-wide table: the installed one, else the bundled one.
+pub fn table() -> &'static LeapSeconds {
+    TABLE.get_or_init(LeapSeconds::bundled)
+}
+
+/// Installs the table of `--leap-seconds` process-wide; call before the first conversion.
+pub fn install_from_args(args: &Args) -> Result<&'static LeapSeconds, Box<dyn Error>> {
+    if args.value("leap-seconds").is_none() {
+        return Ok(table());
+    }
+    TABLE.set(LeapSeconds::from_args(args)?).map_err(|_| "leap second table already in use")?;
+    Ok(table())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::{Epoch, Nanos, TimestampFormat, Unit};
+
+    const NS_PER_SECOND: u64 = 1_000_000_000;
+
+    // 2017-01-01 00:00:00 UTC, right after the leap second 2016-12-31 23:59:60
+    const NEW_YEAR_2017_S: u64 = 1_483_228_800;
+
+    #[test]
+    fn intervals_across_a_leap_second_count_it() {
+        let table = LeapSeconds::bundled();
+        let before = Nanos((NEW_YEAR_2017_S - 1) * NS_PER_SECOND + NS_PER_SECOND / 2);
+        let after = Nanos(NEW_YEAR_2017_S * NS_PER_SECOND + NS_PER_SECOND / 2);
+        assert_eq!(table.tai_minus_utc_ns(before), 36 * NS_PER_SECOND);
+        assert_eq!(table.tai_minus_utc_ns(after), 37 * NS_PER_SECOND);
+        // One second apart on UTC, two on TAI
+        assert_eq!(table.to_tai(after).ns_since(table.to_tai(before)), 2 * NS_PER_SECOND as i64);
+
+        // The inserted second repeats 23:59:59 on UTC and shows as 23:59:60
+        let leap = Tai(table.to_tai(before).0 + NS_PER_SECOND);
+        assert!(table.is_leap_second(leap));
+        assert!(!table.is_leap_second(table.to_tai(before)) && !table.is_leap_second(table.to_tai(after)));
+        assert_eq!(table.to_utc(leap), before);
+        assert_eq!(table.utc_datetime(leap), "2016-12-31 23:59:60.500000000");
+        assert_eq!(table.utc_datetime(table.to_tai(before)), "2016-12-31 23:59:59.500000000");
+        for utc in [before, after, Nanos(1_700_000_000 * NS_PER_SECOND)] {
+            assert_eq!(table.to_utc(table.to_tai(utc)), utc);
+        }
+
+        // TAI and GPS presentation use the leap seconds of each timestamp (2010: TAI-UTC 34s)
+        let tai_seconds = TimestampFormat { unit: Unit::Ms, epoch: Epoch::Tai };
+        let in_2010 = Nanos(1_275_350_400 * NS_PER_SECOND);
+        assert_eq!(tai_seconds.present(in_2010), (1_275_350_400 + 34) * 1_000);
+        assert_eq!(tai_seconds.parse(tai_seconds.present(in_2010)), in_2010);
+        let gps = TimestampFormat { unit: Unit::Ns, epoch: Epoch::Gps };
+        assert_eq!(gps.parse(gps.present(after)), after);
+    }
+
+    #[test]
+    fn loads_leap_seconds_list_with_expiry() {
+        let text = "\
+#\tUpdated through IERS Bulletin C
+#$\t 3960057600
+#@\t3991593600
+#
+2272060800\t10\t# 1 Jan 1972
+3644697600\t36\t# 1 Jul 2015
+3692217600\t37\t# 1 Jan 2017
+#h\t16edd0f0 3666784f 37db6bdd e74ced87 59af48f1
+";
+        let table = LeapSeconds::parse(text).unwrap();
+        assert_eq!(table.current_offset_s(), 37);
+        assert_eq!(table.expires_unix_s(), Some(3_991_593_600 - 2_208_988_800));
+        assert_eq!(table.tai_minus_utc_ns(Nanos(NEW_YEAR_2017_S * NS_PER_SECOND)), 37 * NS_PER_SECOND);
+        assert_eq!(table.tai_minus_utc_ns(Nanos(1_000_000_000 * NS_PER_SECOND)), 10 * NS_PER_SECOND);
+        assert!(!table.is_expired(Nanos(1_700_000_000 * NS_PER_SECOND)));
+        assert!(table.is_expired(Nanos((3_991_593_600 - 2_208_988_800) * NS_PER_SECOND)));
+
+        assert!(LeapSeconds::parse("# comments only\n").is_err());
+        assert!(LeapSeconds::parse("3692217600 37\n3644697600 36\n").unwrap_err().contains("out of order"));
+        assert!(LeapSeconds::parse("3692217600 x\n").unwrap_err().starts_with("line 1"));
+    }
+}
@@ -0,0 +1,94 @@
+// Transport independent core of the camera trigger synchronization.
+//
+// The trigger type and its wire and shared-memory forms, the matcher that
+// pairs camera frames with triggers, and the clock, statistics and session
+// code shared by every binary of the rig. Each module describes its own part.
+// The iceoryx2 services live in `sync-iceoryx2`, the camera backends in
+// `sync-capture`.
+
+pub mod analyze;
+pub mod assoc_diff;
+pub mod batch;
+pub mod burst;
+pub mod canary;
+pub mod channel;
+pub mod cli;
+pub mod clock;
+pub mod clock_guard;
+pub mod clock_quality;
+pub mod clockmap;
+pub mod deadline;
+pub mod degrade;
+pub mod delivery;
+pub mod depth;
+pub mod diagnosis;
+pub mod display_clock;
+pub mod drops;
+pub mod encoder;
+pub mod eventlog;
+pub mod exif;
+pub mod export;
+pub mod federation;
+pub mod feedback;
+pub mod fixture;
+pub mod genlock;
+pub mod glass_to_glass;
+#[cfg(target_os = "linux")]
+pub mod gpio;
+pub mod handoff;
+pub mod history;
+pub mod intrinsics;
+#[cfg(target_os = "linux")]
+pub mod irq;
+pub mod latency;
+pub mod latency_filter;
+pub mod layout;
+pub mod leap;
+pub mod loans;
+pub mod lighting;
+pub mod manifest;
+pub mod matcher_state;
+pub mod matching;
+pub mod mcap;
+pub mod mcap_record;
+pub mod merge;
+pub mod namespace;
+pub mod netstamp;
+pub mod offset_sweep;
+pub mod png;
+pub mod pipeline;
+pub mod pose;
+pub mod priority;
+pub mod rate;
+pub mod readiness;
+pub mod reload;
+pub mod retention;
+pub mod retime;
+pub mod ros2_bridge;
+pub mod rosbag;
+pub mod runs;
+#[cfg(feature = "serde")]
+pub mod schema;
+pub mod sensor;
+pub mod session;
+pub mod shared_stats;
+pub mod signing;
+pub mod skew;
+pub mod stall;
+pub mod stats;
+pub mod status;
+pub mod supervise;
+#[cfg(target_os = "linux")]
+pub mod timer;
+pub mod timecode;
+pub mod timestamping;
+pub mod trace;
+pub mod transport;
+pub mod trigger_batch;
+pub mod ui_budget;
+pub mod units;
+pub mod wire;
+
+// Timestamps count in the clock domain of the trigger header, CLOCK_REALTIME by default (see `clock::ClockDomain`)
+// The layout is fixed and versioned, shared memory carries it as is (see `layout`)
+pub use layout::CameraTrigger;
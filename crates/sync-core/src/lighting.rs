@@ -0,0 +1,145 @@
+// Illumination/strobe commands sent in lockstep with the triggers.
+//
+// The publisher emits one `StrobeCommand` per trigger on the `Lighting/Strobe`
+// service: lighting controllers apply it to the exposure of that trigger, and
+// camera processes record which lighting state applied to each matched frame.
+// A `StrobePattern` cycles through a list of states per trigger, e.g.
+// `0:1.0,1:1.0,off` alternates two wavelength channels with a dark frame.
+
+#[cfg(feature = "iceoryx2")]
+use iceoryx2::prelude::ZeroCopySend;
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+
+/// Strobe commands kept for matching, enough for several seconds of triggers.
+const LOG_CAPACITY: usize = 256;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "iceoryx2", derive(ZeroCopySend))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StrobeCommand {
+    pub trigger_id: u64,
+    /// Relative intensity, 0.0 to 1.0.
+    pub intensity: f32,
+    /// Wavelength/LED channel of the lighting controller.
+    pub channel: u8,
+    pub on: bool,
+}
+
+/// Displays as `off` or `<channel>:<intensity>`, the syntax of `StrobePattern`.
+impl fmt::Display for StrobeCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.on {
+            write!(f, "{}:{:.2}", self.channel, self.intensity)
+        } else {
+            write!(f, "off")
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StrobePattern {
+    /// `(channel, intensity)` per step, `None` for off.
+    steps: Vec<Option<(u8, f32)>>,
+}
+
+impl FromStr for StrobePattern {
+    type Err = String;
+
+    /// Accepts comma separated steps: `off`, `on` (channel 0, full intensity) or `<channel>:<intensity>`.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let steps = text
+            .split(',')
+            .map(|step| match step.trim() {
+                "off" => Ok(None),
+                "on" => Ok(Some((0, 1.0))),
+                step => {
+                    let (channel, intensity) = step
+                        .split_once(':')
+                        .ok_or_else(|| format!("invalid strobe step '{}' (expected off, on or <channel>:<intensity>)", step))?;
+                    let channel = channel.parse::<u8>().map_err(|_| format!("invalid strobe channel '{}'", channel))?;
+                    let intensity = intensity
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|i| (0.0..=1.0).contains(i))
+                        .ok_or_else(|| format!("invalid strobe intensity '{}' (expected 0.0 to 1.0)", intensity))?;
+                    Ok(Some((channel, intensity)))
+                }
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(Self { steps })
+    }
+}
+
+impl StrobePattern {
+    /// Command for a trigger; trigger ids start at 1 with the first step.
+    pub fn command(&self, trigger_id: u64) -> StrobeCommand {
+        let step = self.steps[(trigger_id.saturating_sub(1) % self.steps.len() as u64) as usize];
+        match step {
+            Some((channel, intensity)) => StrobeCommand {
+                trigger_id,
+                intensity,
+                channel,
+                on: true,
+            },
+            None => StrobeCommand {
+                trigger_id,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// Recent strobe commands, looked up by trigger id when a frame is matched.
+#[derive(Debug, Clone, Default)]
+pub struct StrobeLog {
+    commands: VecDeque<StrobeCommand>,
+}
+
+impl StrobeLog {
+    pub fn push(&mut self, command: StrobeCommand) {
+        if self.commands.len() == LOG_CAPACITY {
+            self.commands.pop_front();
+        }
+        self.commands.push_back(command);
+    }
+
+    pub fn get(&self, trigger_id: u64) -> Option<StrobeCommand> {
+        self.commands.iter().rev().find(|command| command.trigger_id == trigger_id).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patterns_cycle_through_their_steps() {
+        let pattern: StrobePattern = "0:1.0, 1:0.5,off".parse().unwrap();
+        let commands: Vec<String> = (1..=4).map(|id| pattern.command(id).to_string()).collect();
+        assert_eq!(commands, ["0:1.00", "1:0.50", "off", "0:1.00"]);
+        assert_eq!(pattern.command(3), StrobeCommand { trigger_id: 3, ..Default::default() });
+        assert_eq!("on".parse::<StrobePattern>().unwrap().command(7).to_string(), "0:1.00");
+    }
+
+    #[test]
+    fn invalid_steps_are_rejected() {
+        assert!("dim".parse::<StrobePattern>().unwrap_err().contains("invalid strobe step 'dim'"));
+        assert!("300:1.0".parse::<StrobePattern>().unwrap_err().contains("invalid strobe channel '300'"));
+        assert!("0:1.5".parse::<StrobePattern>().unwrap_err().contains("invalid strobe intensity '1.5'"));
+    }
+
+    #[test]
+    fn the_log_keeps_the_most_recent_commands() {
+        let pattern: StrobePattern = "on,off".parse().unwrap();
+        let mut log = StrobeLog::default();
+        for trigger_id in 1..=LOG_CAPACITY as u64 + 10 {
+            log.push(pattern.command(trigger_id));
+        }
+        assert_eq!(log.get(5), None);
+        assert_eq!(log.get(11).map(|command| command.on), Some(true));
+        assert_eq!(log.get(LOG_CAPACITY as u64 + 10).map(|command| command.on), Some(false));
+    }
+}
@@ -0,0 +1,88 @@
+// Loan pool of the trigger publisher.
+//
+// A trigger is written into a shared-memory sample loaned from the
+// publisher's pool and given back when it is sent. The pool holds
+// `max_loaned_samples` samples (`--max-loaned-samples`). A loan fails when the
+// pool is exhausted: too many samples loaned and not sent yet, or, without
+// safe overflow (see `delivery`), no free memory because slow subscribers
+// still hold earlier triggers. The trigger is then not published. Pulses of a
+// burst are published back to back, so the pool should hold a whole burst
+// plus one spare; a smaller pool is flagged at startup, and every exhaustion
+// is counted together with the time each loan took.
+
+use crate::stats::LatencyStats;
+
+/// iceoryx2 sample loans of the trigger publisher unless configured.
+pub const DEFAULT_MAX_LOANS: usize = 5;
+
+/// Pool size that holds every pulse of a burst of `burst_size`, plus one spare.
+pub fn recommended_max_loans(burst_size: u32) -> usize {
+    burst_size.max(1) as usize + 1
+}
+
+/// Startup warning if `max_loans` is too small for bursts of `burst_size`.
+pub fn sizing_warning(max_loans: usize, burst_size: u32) -> Option<String> {
+    let recommended = recommended_max_loans(burst_size);
+    (max_loans < recommended).then(|| {
+        format!(
+            "loan pool of {} samples is smaller than the {} recommended for bursts of {} (--max-loaned-samples {})",
+            max_loans, recommended, burst_size, recommended
+        )
+    })
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LoanStats {
+    pub loans: u64,
+    /// Loans that failed because the pool was exhausted; those triggers were not published.
+    pub exhausted: u64,
+    /// Time per successful loan, ms.
+    pub loan_time: LatencyStats,
+}
+
+impl LoanStats {
+    pub fn record_loan(&mut self, duration_ms: f64) {
+        self.loans += 1;
+        self.loan_time.add(duration_ms);
+    }
+
+    pub fn record_exhausted(&mut self) {
+        self.exhausted += 1;
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "{} loans, {} exhausted, loan time p50 {:.1}us, max {:.1}us",
+            self.loans,
+            self.exhausted,
+            self.loan_time.percentile(50.0) * 1000.0,
+            self.loan_time.max() * 1000.0
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pools_smaller_than_a_burst_are_flagged() {
+        assert_eq!(recommended_max_loans(0), 2);
+        assert_eq!(sizing_warning(DEFAULT_MAX_LOANS, 4), None);
+        assert_eq!(
+            sizing_warning(DEFAULT_MAX_LOANS, 8).as_deref(),
+            Some("loan pool of 5 samples is smaller than the 9 recommended for bursts of 8 (--max-loaned-samples 9)")
+        );
+    }
+
+    #[test]
+    fn exhausted_loans_are_counted_apart_from_the_loan_time() {
+        let mut stats = LoanStats::default();
+        stats.record_loan(0.002);
+        stats.record_loan(0.004);
+        stats.record_exhausted();
+        assert_eq!((stats.loans, stats.exhausted, stats.loan_time.count()), (2, 1, 2));
+        assert!(stats.summary().starts_with("2 loans, 1 exhausted, loan time"), "{}", stats.summary());
+        assert!(stats.summary().ends_with("max 4.0us"), "{}", stats.summary());
+    }
+}
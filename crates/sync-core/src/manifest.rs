@@ -0,0 +1,232 @@
+// Session manifest: everything needed to reproduce or audit a recording.
+//
+// The manifest lists the effective configuration of the recording process,
+// the software version (crate version + git revision), the cameras in use and
+// the SHA-256 of every calibration file. The configuration hash identifies
+// runs made with identical settings. The manifest is written as TOML next to
+// the session records and published once on the telemetry channel.
+
+use sha2::{Digest, Sha256};
+use std::fmt::{self, Display};
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const MANIFEST_FILE: &str = "manifest.toml";
+
+pub const CRATE_VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const GIT_REVISION: &str = env!("GIT_REVISION");
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CameraEntry {
+    /// Identity as configured (`serial:ABC123`, `bus:1-2`, `index:0`).
+    pub selector: String,
+    /// Device node the selector resolved to for this session.
+    pub device: String,
+    pub index: u32,
+    pub name: String,
+    pub serial: Option<String>,
+    pub bus_path: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SessionManifest {
+    binary: String,
+    created_ns: u64,
+    crate_version: String,
+    git_revision: String,
+    #[cfg_attr(feature = "serde", serde(with = "crate::schema::pairs"))]
+    config: Vec<(String, String)>,
+    cameras: Vec<CameraEntry>,
+    /// `path → sha256`
+    #[cfg_attr(feature = "serde", serde(with = "crate::schema::pairs"))]
+    calibration: Vec<(String, String)>,
+}
+
+impl SessionManifest {
+    pub fn new(binary: &str) -> Self {
+        let created_ns = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        Self {
+            binary: binary.to_string(),
+            created_ns,
+            crate_version: CRATE_VERSION.to_string(),
+            git_revision: GIT_REVISION.to_string(),
+            config: Vec::new(),
+            cameras: Vec::new(),
+            calibration: Vec::new(),
+        }
+    }
+
+    /// Adds one effective configuration value. Later values for the same key replace earlier ones.
+    pub fn config(&mut self, key: &str, value: impl Display) -> &mut Self {
+        let value = value.to_string();
+        match self.config.iter_mut().find(|(k, _)| k == key) {
+            Some(entry) => entry.1 = value,
+            None => self.config.push((key.to_string(), value)),
+        }
+        self
+    }
+
+    pub fn camera(&mut self, camera: CameraEntry) -> &mut Self {
+        self.cameras.push(camera);
+        self
+    }
+
+    /// Hashes a calibration file into the manifest.
+    pub fn calibration_file(&mut self, path: &Path) -> std::io::Result<&mut Self> {
+        let digest = Sha256::digest(fs::read(path)?);
+        self.calibration.push((path.display().to_string(), format!("{:x}", digest)));
+        Ok(self)
+    }
+
+    pub fn binary(&self) -> &str {
+        &self.binary
+    }
+
+    /// A configuration value; `None` if missing or empty.
+    pub fn config_value(&self, key: &str) -> Option<&str> {
+        self.config.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str()).filter(|v| !v.is_empty())
+    }
+
+    pub fn cameras(&self) -> &[CameraEntry] {
+        &self.cameras
+    }
+
+    pub fn created_ns(&self) -> u64 {
+        self.created_ns
+    }
+
+    /// SHA-256 over the sorted `key=value` configuration lines.
+    pub fn config_hash(&self) -> String {
+        let mut entries: Vec<_> = self.config.iter().map(|(k, v)| format!("{}={}\n", k, v)).collect();
+        entries.sort();
+        format!("{:x}", Sha256::digest(entries.concat()))
+    }
+
+    pub fn write(&self, dir: &Path) -> std::io::Result<()> {
+        fs::write(dir.join(MANIFEST_FILE), self.to_string())
+    }
+
+    /// Reads the manifest of a session directory.
+    pub fn read(dir: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let table: toml::Table = fs::read_to_string(dir.join(MANIFEST_FILE))?.parse()?;
+        let string = |table: &toml::Table, key: &str| table.get(key).and_then(|v| v.as_str()).unwrap_or_default().to_string();
+        let optional = |table: &toml::Table, key: &str| Some(string(table, key)).filter(|v| !v.is_empty());
+        let tables = |key: &str| table.get(key).and_then(|v| v.as_array()).into_iter().flatten().filter_map(|v| v.as_table());
+        Ok(Self {
+            binary: string(&table, "binary"),
+            created_ns: table.get("created_ns").and_then(|v| v.as_integer()).unwrap_or(0) as u64,
+            crate_version: string(&table, "crate_version"),
+            git_revision: string(&table, "git_revision"),
+            config: table
+                .get("config")
+                .and_then(|v| v.as_table())
+                .map(|config| config.keys().map(|key| (key.clone(), string(config, key))).collect())
+                .unwrap_or_default(),
+            cameras: tables("cameras")
+                .map(|camera| CameraEntry {
+                    selector: string(camera, "selector"),
+                    device: string(camera, "device"),
+                    index: camera.get("index").and_then(|v| v.as_integer()).unwrap_or(0) as u32,
+                    name: string(camera, "name"),
+                    serial: optional(camera, "serial"),
+                    bus_path: optional(camera, "bus_path"),
+                })
+                .collect(),
+            calibration: tables("calibration").map(|entry| (string(entry, "path"), string(entry, "sha256"))).collect(),
+        })
+    }
+}
+
+pub(crate) fn quoted(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+impl Display for SessionManifest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "binary = {}", quoted(&self.binary))?;
+        writeln!(f, "created_ns = {}", self.created_ns)?;
+        writeln!(f, "crate_version = {}", quoted(&self.crate_version))?;
+        writeln!(f, "git_revision = {}", quoted(&self.git_revision))?;
+        writeln!(f, "config_hash = {}", quoted(&self.config_hash()))?;
+
+        writeln!(f, "\n[config]")?;
+        for (key, value) in &self.config {
+            writeln!(f, "{} = {}", key, quoted(value))?;
+        }
+
+        for camera in &self.cameras {
+            writeln!(f, "\n[[cameras]]")?;
+            writeln!(f, "selector = {}", quoted(&camera.selector))?;
+            writeln!(f, "device = {}", quoted(&camera.device))?;
+            writeln!(f, "index = {}", camera.index)?;
+            writeln!(f, "name = {}", quoted(&camera.name))?;
+            writeln!(f, "serial = {}", quoted(camera.serial.as_deref().unwrap_or("")))?;
+            writeln!(f, "bus_path = {}", quoted(camera.bus_path.as_deref().unwrap_or("")))?;
+        }
+
+        for (path, sha256) in &self.calibration {
+            writeln!(f, "\n[[calibration]]")?;
+            writeln!(f, "path = {}", quoted(path))?;
+            writeln!(f, "sha256 = {}", quoted(sha256))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_hash_ignores_order_and_follows_values() {
+        let mut first = SessionManifest::new("subscriber");
+        first.config("tolerance_ms", 5).config("camera", "serial:ABC123");
+        let mut second = SessionManifest::new("v4l2_capture");
+        second.config("camera", "serial:ABC123").config("tolerance_ms", 5);
+        assert_eq!(first.config_hash(), second.config_hash());
+
+        // A later value replaces the earlier one
+        second.config("tolerance_ms", 8);
+        assert_ne!(first.config_hash(), second.config_hash());
+        assert_eq!(second.config_value("tolerance_ms"), Some("8"));
+        assert_eq!(second.config_value("missing"), None);
+    }
+
+    #[test]
+    fn written_manifest_reads_back() {
+        let dir = std::env::temp_dir().join(format!("camera_sync_manifest_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let calibration = dir.join("intrinsics.yaml");
+        fs::write(&calibration, "fx: 600\n").unwrap();
+
+        let mut manifest = SessionManifest::new("v4l2_capture");
+        manifest.config("camera", "serial:ABC123").config("note", "quoted \"value\"").calibration_file(&calibration).unwrap();
+        manifest.camera(CameraEntry {
+            selector: "serial:ABC123".to_string(),
+            device: "/dev/video2".to_string(),
+            index: 2,
+            name: "USB Camera".to_string(),
+            serial: Some("ABC123".to_string()),
+            bus_path: None,
+        });
+        manifest.write(&dir).unwrap();
+        let text = fs::read_to_string(dir.join(MANIFEST_FILE)).unwrap();
+        let read = SessionManifest::read(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(text.contains(&format!("crate_version = \"{}\"", CRATE_VERSION)));
+        assert!(text.contains(&format!("config_hash = \"{}\"", manifest.config_hash())));
+        assert_eq!(read.binary(), "v4l2_capture");
+        assert_eq!(read.created_ns(), manifest.created_ns());
+        assert_eq!(read.config_value("note"), Some("quoted \"value\""));
+        assert_eq!(read.config_hash(), manifest.config_hash());
+        assert_eq!((read.cameras()[0].index, read.cameras()[0].serial.as_deref(), read.cameras()[0].bus_path.as_deref()), (2, Some("ABC123"), None));
+        assert_eq!(read.calibration[0].1, format!("{:x}", Sha256::digest(b"fx: 600\n")));
+    }
+}
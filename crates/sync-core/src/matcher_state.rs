@@ -0,0 +1,223 @@
+// Learned matcher state persisted across restarts (warm restart).
+//
+// A capture process learns the latency of its camera from the first matches
+// (see `pipeline`): until `DRIFT_WINDOW` frames matched, it has no drift model
+// and matches with the wide configured tolerance. With `--matcher-state
+// <file>`, the processes save their learned state every `SAVE_INTERVAL_NS` and
+// when they stop, and restore it at startup, so a restarted process matches
+// with its learned tolerance from the first frame. Several processes can share
+// one file; each keeps its own stream (camera) in it:
+//
+//   stream <name>
+//   saved_ns <realtime ns of the last save>
+//   drift_baseline_ns <ns>                     (once learned)
+//   offsets_ns <frame - trigger ns> ...        (oldest first)
+//
+// Saves read the file, replace their own stream and rename a temporary file
+// over it. Snapshots older than `MAX_SNAPSHOT_AGE_NS` are not restored; a
+// restored model that no longer fits is discarded after `RELEARN_AFTER`
+// unmatched frames in a row.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::pipeline::{MatchPipeline, MatcherSnapshot};
+
+/// Snapshots older than this are not restored, a day.
+pub const MAX_SNAPSHOT_AGE_NS: u64 = 86_400_000_000_000;
+/// Running processes save their state this often.
+pub const SAVE_INTERVAL_NS: u64 = 10_000_000_000;
+
+/// Restores the state of `stream` saved in `path` into `pipeline`; returns the restored snapshot,
+/// `None` if the file does not exist, lacks the stream or the stream's state is too old.
+pub fn restore<T>(path: &Path, stream: &str, pipeline: &mut MatchPipeline<T>, now_ns: u64) -> Result<Option<MatcherSnapshot>, Box<dyn std::error::Error>> {
+    let Some(state) = MatcherState::load(path)? else {
+        return Ok(None);
+    };
+    let snapshot = state.restorable(stream, now_ns).cloned();
+    if let Some(snapshot) = &snapshot {
+        pipeline.restore(snapshot);
+    }
+    Ok(snapshot)
+}
+
+/// Learned state of one stream.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SavedStream {
+    pub name: String,
+    /// When the state was saved, realtime ns.
+    pub saved_ns: u64,
+    pub snapshot: MatcherSnapshot,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MatcherState {
+    /// In file order.
+    pub streams: Vec<SavedStream>,
+}
+
+impl MatcherState {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut state = Self::default();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = || format!("line {}: invalid entry '{}'", number + 1, line);
+            let (key, value) = line.split_once(' ').ok_or_else(invalid)?;
+            let value = value.trim();
+            let int = |field: &str| field.parse::<i64>().map_err(|_| invalid());
+            match (key, state.streams.last_mut()) {
+                ("stream", _) => state.streams.push(SavedStream {
+                    name: value.to_string(),
+                    ..SavedStream::default()
+                }),
+                ("saved_ns", Some(stream)) => stream.saved_ns = value.parse().map_err(|_| invalid())?,
+                ("drift_baseline_ns", Some(stream)) => stream.snapshot.drift_baseline_ns = Some(int(value)?),
+                ("offsets_ns", Some(stream)) => stream.snapshot.offsets_ns = value.split_whitespace().map(int).collect::<Result<_, _>>()?,
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(state)
+    }
+
+    /// Reads `path`; `None` if it does not exist yet.
+    pub fn load(path: &Path) -> Result<Option<Self>, Box<dyn std::error::Error>> {
+        match fs::read_to_string(path) {
+            Ok(text) => Ok(Some(Self::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))?)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// The snapshot of `stream`, unless it is older than `MAX_SNAPSHOT_AGE_NS` at `now_ns`.
+    pub fn restorable(&self, stream: &str, now_ns: u64) -> Option<&MatcherSnapshot> {
+        self.streams
+            .iter()
+            .find(|saved| saved.name == stream && now_ns.saturating_sub(saved.saved_ns) <= MAX_SNAPSHOT_AGE_NS)
+            .map(|saved| &saved.snapshot)
+    }
+
+    /// Replaces the snapshot of `stream` in `path`, keeping the other streams of the file.
+    pub fn save(path: &Path, stream: &str, snapshot: MatcherSnapshot, now_ns: u64) -> Result<(), Box<dyn std::error::Error>> {
+        // A damaged file is replaced rather than blocking every later save
+        let mut state = Self::load(path).ok().flatten().unwrap_or_default();
+        let saved = SavedStream {
+            name: stream.to_string(),
+            saved_ns: now_ns,
+            snapshot,
+        };
+        match state.streams.iter_mut().find(|existing| existing.name == stream) {
+            Some(existing) => *existing = saved,
+            None => state.streams.push(saved),
+        }
+        let temporary = path.with_extension("tmp");
+        fs::write(&temporary, state.to_string())?;
+        fs::rename(&temporary, path)?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for MatcherState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for stream in &self.streams {
+            writeln!(f, "stream {}", stream.name)?;
+            writeln!(f, "saved_ns {}", stream.saved_ns)?;
+            if let Some(baseline) = stream.snapshot.drift_baseline_ns {
+                writeln!(f, "drift_baseline_ns {}", baseline)?;
+            }
+            if !stream.snapshot.offsets_ns.is_empty() {
+                let offsets: Vec<String> = stream.snapshot.offsets_ns.iter().map(i64::to_string).collect();
+                writeln!(f, "offsets_ns {}", offsets.join(" "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::burst::BurstPattern;
+    use crate::matching::DEFAULT_TOLERANCE_MS;
+    use crate::pipeline::{MatchPipeline, Stage, DRIFT_WINDOW, RELEARN_AFTER};
+    use crate::CameraTrigger;
+
+    const START_NS: u64 = 1_700_000_000_000_000_000;
+
+    // One trigger per second
+    const INTERVAL_NS: u64 = 1_000_000_000;
+
+    fn adaptive_pipeline() -> MatchPipeline<()> {
+        let mut pipeline = MatchPipeline::with_stages(&[Stage::Drift, Stage::Associate], DEFAULT_TOLERANCE_MS, BurstPattern::new(1));
+        pipeline.set_adaptive_tolerance(true);
+        pipeline
+    }
+
+    // Trigger `index`, then its frame `latency_ns` later with up to 400us of jitter; returns (matched, relearned)
+    fn capture(pipeline: &mut MatchPipeline<()>, index: u64, latency_ns: u64) -> (bool, bool) {
+        let hw_ts = START_NS + index * INTERVAL_NS;
+        pipeline.on_trigger(CameraTrigger::new(index, hw_ts, hw_ts), ());
+        let frame = pipeline.on_frame(hw_ts + latency_ns + (index * 37 % 5) * 100_000);
+        (frame.matched.is_some(), frame.relearned)
+    }
+
+    #[test]
+    fn learns_saves_and_restores_the_tolerance() {
+        let mut pipeline = adaptive_pipeline();
+        for index in 0..DRIFT_WINDOW as u64 {
+            assert_eq!(pipeline.tolerance_ms(), DEFAULT_TOLERANCE_MS);
+            assert_eq!(capture(&mut pipeline, index, 110_000_000), (true, false));
+        }
+        let learned_ms = pipeline.tolerance_ms();
+        assert!((112.0..114.0).contains(&learned_ms), "tolerance {}ms", learned_ms);
+
+        let snapshot = pipeline.snapshot();
+        assert_eq!(snapshot.offsets_ns.len(), DRIFT_WINDOW);
+        assert!((snapshot.latency_ms().unwrap() - 110.2).abs() < 0.3);
+        let saved_ns = START_NS + 40 * INTERVAL_NS;
+        let state = MatcherState {
+            streams: vec![SavedStream {
+                name: "serial:ABC123".to_string(),
+                saved_ns,
+                snapshot,
+            }],
+        };
+        let parsed = MatcherState::parse(&state.to_string()).unwrap();
+        assert_eq!(parsed, state);
+        assert!(parsed.restorable("serial:ABC123", saved_ns + MAX_SNAPSHOT_AGE_NS + 1).is_none());
+        assert!(parsed.restorable("0", saved_ns).is_none());
+
+        // A restarted process matches with the learned tolerance from the first frame
+        let mut restarted = adaptive_pipeline();
+        restarted.restore(parsed.restorable("serial:ABC123", saved_ns + 60 * INTERVAL_NS).unwrap());
+        assert_eq!(restarted.tolerance_ms(), learned_ms);
+        assert_eq!(capture(&mut restarted, 100, 110_000_000), (true, false));
+    }
+
+    #[test]
+    fn relearns_after_a_latency_change() {
+        let mut pipeline = adaptive_pipeline();
+        for index in 0..DRIFT_WINDOW as u64 {
+            capture(&mut pipeline, index, 110_000_000);
+        }
+        // The camera now delivers 300ms after the trigger, outside the learned tolerance
+        let mut index = DRIFT_WINDOW as u64;
+        for run in 1..=RELEARN_AFTER {
+            assert_eq!(capture(&mut pipeline, index, 300_000_000), (false, run == RELEARN_AFTER));
+            index += 1;
+        }
+        assert_eq!(pipeline.tolerance_ms(), DEFAULT_TOLERANCE_MS);
+        assert_eq!(pipeline.snapshot().offsets_ns.len(), 0);
+
+        // Relearned at the new latency
+        for _ in 0..DRIFT_WINDOW {
+            assert_eq!(capture(&mut pipeline, index, 300_000_000), (true, false));
+            index += 1;
+        }
+        assert!((302.0..304.0).contains(&pipeline.tolerance_ms()), "tolerance {}ms", pipeline.tolerance_ms());
+    }
+}
@@ -0,0 +1,193 @@
+// Association of a frame with the pending triggers.
+//
+// The nearest trigger wins. Future triggers (hw_ts after the frame) are scored
+// at `future_penalty` times their distance (twice by default) since they more
+// likely belong to a subsequent frame, and triggers further away than the
+// tolerance never match. Shared by
+// the camera processes and the session fixture replay, so recorded sessions
+// check exactly the association the live processes make.
+//
+// `TriggerMatcher` is the association for capture loops outside this
+// workspace: the plain `associate` stage of `pipeline` behind a push/match
+// interface, without payloads, stage chains or learned state.
+
+use crate::pipeline::{MatchConfig, MatchPipeline, Queued};
+use crate::CameraTrigger;
+
+/// Triggers further than this from the frame never match.
+pub const DEFAULT_TOLERANCE_MS: f64 = 500.0;
+
+/// Factor on the distance of future triggers.
+pub const DEFAULT_FUTURE_PENALTY: f64 = 2.0;
+
+/// Index and score (ms) of the best matching trigger among `hw_timestamps`.
+pub fn best_match(hw_timestamps: impl IntoIterator<Item = u64>, frame_ns: u64, tolerance_ms: f64, future_penalty: f64) -> Option<(usize, f64)> {
+    let mut best: Option<(usize, f64)> = None;
+    for (index, hw_ts) in hw_timestamps.into_iter().enumerate() {
+        let time_diff_ms = hw_ts.abs_diff(frame_ns) as f64 / 1_000_000.0;
+
+        // Prefer past triggers (hw_ts < frame time), penalize future ones
+        let score = if hw_ts < frame_ns { time_diff_ms } else { time_diff_ms * future_penalty };
+
+        if time_diff_ms < tolerance_ms && best.is_none_or(|(_, best_score)| score < best_score) {
+            best = Some((index, score));
+        }
+    }
+    best
+}
+
+/// Pending triggers of one camera, matched against its frames.
+#[derive(Debug, Clone, Default)]
+pub struct TriggerMatcher {
+    pipeline: MatchPipeline<()>,
+}
+
+impl TriggerMatcher {
+    /// Matches within `config.tolerance_ms`, keeping up to `config.max_pending` triggers.
+    pub fn new(config: &MatchConfig) -> Self {
+        let mut pipeline = MatchPipeline::default();
+        pipeline.configure(config);
+        Self { pipeline }
+    }
+
+    /// Queues a received trigger for the frames to come.
+    pub fn push_trigger(&mut self, trigger: CameraTrigger) -> Queued {
+        self.pipeline.on_trigger(trigger, ())
+    }
+
+    /// Trigger of the frame captured at `frame_ns` and its score in ms; it and the older pending triggers leave the queue.
+    pub fn match_frame(&mut self, frame_ns: u64) -> Option<(CameraTrigger, f64)> {
+        self.pipeline.on_frame(frame_ns).matched.map(|(trigger, (), score_ms)| (trigger, score_ms))
+    }
+
+    pub fn pending(&self) -> usize {
+        self.pipeline.pending()
+    }
+
+    pub fn tolerance_ms(&self) -> f64 {
+        self.pipeline.tolerance_ms()
+    }
+
+    pub fn future_penalty(&self) -> f64 {
+        self.pipeline.future_penalty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::burst::BurstPattern;
+    use crate::cli::Args;
+    use crate::pipeline::{MatchConfig, MatchPipeline, Queued, MAX_PENDING};
+
+    const MS: u64 = 1_000_000;
+
+    fn matcher(tolerance_ms: f64, future_penalty: f64) -> TriggerMatcher {
+        TriggerMatcher::new(&MatchConfig { tolerance_ms, future_penalty, ..MatchConfig::default() })
+    }
+
+    fn trigger(frame_id: u64, hw_ms: u64) -> CameraTrigger {
+        CameraTrigger::new(frame_id, hw_ms * MS, hw_ms * MS + MS / 20)
+    }
+
+    #[test]
+    fn frames_within_the_tolerance_take_the_nearest_trigger() {
+        let mut matcher = matcher(20.0, DEFAULT_FUTURE_PENALTY);
+        assert_eq!((matcher.tolerance_ms(), matcher.future_penalty()), (20.0, DEFAULT_FUTURE_PENALTY));
+        for (frame_id, hw_ms) in [(1, 1_000), (2, 1_033), (3, 1_066)] {
+            assert_eq!(matcher.push_trigger(trigger(frame_id, hw_ms)), Queued::Added);
+        }
+        assert_eq!(matcher.match_frame(1_045 * MS), Some((trigger(2, 1_033), 12.0)));
+        // The older trigger 1 left the queue with the match
+        assert_eq!(matcher.pending(), 1);
+    }
+
+    #[test]
+    fn frames_outside_the_tolerance_match_nothing() {
+        let mut matcher = matcher(20.0, DEFAULT_FUTURE_PENALTY);
+        matcher.push_trigger(trigger(1, 1_000));
+        assert_eq!(matcher.match_frame(1_020 * MS), None);
+        assert_eq!(matcher.match_frame(980 * MS), None);
+        assert_eq!(matcher.pending(), 1);
+        assert_eq!(matcher.match_frame(1_019 * MS), Some((trigger(1, 1_000), 19.0)));
+        assert_eq!(matcher.pending(), 0);
+    }
+
+    #[test]
+    fn future_penalty_decides_between_a_past_and_a_future_trigger() {
+        // 10ms after trigger 1, 6ms before trigger 2
+        let matched = |future_penalty: f64| {
+            let mut matcher = matcher(50.0, future_penalty);
+            matcher.push_trigger(trigger(1, 1_000));
+            matcher.push_trigger(trigger(2, 1_016));
+            matcher.match_frame(1_010 * MS).map(|(trigger, score_ms)| (trigger.frame_id, score_ms))
+        };
+        assert_eq!(matched(DEFAULT_FUTURE_PENALTY), Some((1, 10.0)));
+        assert_eq!(matched(1.0), Some((2, 6.0)));
+        assert_eq!(matched(1.5), Some((2, 9.0)));
+        assert_eq!(TriggerMatcher::default().future_penalty(), DEFAULT_FUTURE_PENALTY);
+    }
+
+    #[test]
+    fn the_pipeline_scores_future_triggers_with_the_penalty() {
+        // 10ms after one trigger, 6ms before the next
+        let frame_ns = 1_010 * MS;
+        let triggers = [CameraTrigger::new(1, 1_000 * MS, 1_000 * MS), CameraTrigger::new(2, 1_016 * MS, 1_016 * MS)];
+        let match_with = |future_penalty: f64| {
+            let mut matcher = MatchPipeline::new(50.0, BurstPattern::default());
+            matcher.set_future_penalty(future_penalty);
+            for trigger in triggers {
+                matcher.on_trigger(trigger, ());
+            }
+            matcher.on_frame(frame_ns).matched.map(|(trigger, (), score_ms)| (trigger.frame_id, score_ms))
+        };
+        assert_eq!(match_with(DEFAULT_FUTURE_PENALTY), Some((1, 10.0)));
+        assert_eq!(match_with(1.0), Some((2, 6.0)));
+        assert_eq!(MatchPipeline::<()>::default().future_penalty(), DEFAULT_FUTURE_PENALTY);
+
+        // Nothing matches outside the tolerance
+        assert_eq!(best_match(triggers.map(|trigger| trigger.hw_timestamp_ns), frame_ns, 5.0, 1.0), None);
+    }
+
+    #[test]
+    fn match_config_tunes_tolerance_penalty_and_queue() {
+        let args = |line: &str| Args::parse(line.split_whitespace().map(str::to_string), &[]);
+        let config = MatchConfig::from_args(&args("v4l2_capture --match-tolerance-ms 25 --future-penalty 1 --max-pending-triggers 2")).unwrap();
+        assert_eq!(config, MatchConfig { tolerance_ms: 25.0, future_penalty: 1.0, max_pending: 2 });
+        assert_eq!(MatchConfig::from_args(&args("subscriber")), Ok(MatchConfig::default()));
+        // The older name still works, the new one wins
+        assert_eq!(MatchConfig::from_args(&args("subscriber --tolerance-ms 40")).unwrap().tolerance_ms, 40.0);
+        assert_eq!(MatchConfig::from_args(&args("subscriber --tolerance-ms 40 --match-tolerance-ms 30")).unwrap().tolerance_ms, 30.0);
+        assert_eq!(MatchConfig::default().max_pending, MAX_PENDING);
+
+        let mut matcher = MatchPipeline::new(DEFAULT_TOLERANCE_MS, BurstPattern::default());
+        matcher.configure(&config);
+        assert_eq!((matcher.tolerance_ms(), matcher.future_penalty()), (25.0, 1.0));
+        let triggers = [(1, 1_000 * MS), (2, 1_016 * MS), (3, 1_033 * MS)].map(|(id, hw_ts)| CameraTrigger::new(id, hw_ts, hw_ts));
+        assert_eq!(matcher.on_trigger(triggers[0], ()), Queued::Added);
+        assert_eq!(matcher.on_trigger(triggers[1], ()), Queued::Added);
+        assert_eq!(matcher.on_trigger(triggers[2], ()), Queued::Evicted(triggers[0]));
+        assert_eq!(matcher.pending(), 2);
+
+        // Without the penalty the nearer trigger after the frame wins, within 25ms only
+        assert_eq!(matcher.on_frame(1_027 * MS).matched.map(|(trigger, (), _)| trigger.frame_id), Some(3));
+        matcher.on_trigger(CameraTrigger::new(4, 1_040 * MS, 1_040 * MS), ());
+        assert!(matcher.on_frame(1_070 * MS).matched.is_none());
+    }
+
+    #[test]
+    fn match_config_rejects_invalid_and_out_of_range_values() {
+        let error = |line: &str| MatchConfig::from_args(&Args::parse(line.split_whitespace().map(str::to_string), &[])).unwrap_err();
+        assert_eq!(error("subscriber --match-tolerance-ms abc"), "invalid --match-tolerance-ms 'abc'");
+        assert_eq!(error("subscriber --match-tolerance-ms 0"), "--match-tolerance-ms 0 out of range (expected above 0 and at most 10000)");
+        assert_eq!(error("subscriber --match-tolerance-ms -5"), "--match-tolerance-ms -5 out of range (expected above 0 and at most 10000)");
+        assert_eq!(error("subscriber --match-tolerance-ms 10001"), "--match-tolerance-ms 10001 out of range (expected above 0 and at most 10000)");
+        assert!(error("subscriber --match-tolerance-ms NaN").contains("out of range"));
+        assert_eq!(error("subscriber --tolerance-ms 0"), "--tolerance-ms 0 out of range (expected above 0 and at most 10000)");
+        assert_eq!(error("subscriber --future-penalty -1"), "--future-penalty -1 out of range (expected 1 to 100)");
+        assert_eq!(error("subscriber --future-penalty 0.5"), "--future-penalty 0.5 out of range (expected 1 to 100)");
+        assert_eq!(error("subscriber --max-pending-triggers 0"), "--max-pending-triggers 0 out of range (expected 1 to 100000)");
+        assert_eq!(error("subscriber --max-pending-triggers -3"), "invalid --max-pending-triggers '-3'");
+        assert_eq!(error("subscriber --max-pending-triggers 100001"), "--max-pending-triggers 100001 out of range (expected 1 to 100000)");
+    }
+}
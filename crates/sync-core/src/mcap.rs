@@ -0,0 +1,109 @@
+// Minimal MCAP writer.
+//
+// Writes the records a reader needs to play a file back: header, schemas,
+// channels and messages in log time order, then the data end and footer. There
+// are no chunks and no summary section; readers (Foxglove, `mcap`, the rosbag2
+// MCAP plugin) fall back to a linear scan of the data section for such files.
+// `mcap recover` adds chunk indexes if random access is needed.
+//
+// Format: https://mcap.dev/spec
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+const MAGIC: [u8; 8] = [0x89, b'M', b'C', b'A', b'P', b'0', b'\r', b'\n'];
+
+const OP_HEADER: u8 = 0x01;
+const OP_FOOTER: u8 = 0x02;
+const OP_SCHEMA: u8 = 0x03;
+const OP_CHANNEL: u8 = 0x04;
+const OP_MESSAGE: u8 = 0x05;
+const OP_DATA_END: u8 = 0x0f;
+
+pub struct McapWriter {
+    out: BufWriter<File>,
+    schemas: u16,
+    channels: u16,
+    sequence: u32,
+}
+
+impl McapWriter {
+    /// Creates `path` and writes the magic and header with `profile` (e.g. `ros2`).
+    pub fn create(path: &Path, profile: &str) -> io::Result<Self> {
+        let mut writer = Self {
+            out: BufWriter::new(File::create(path)?),
+            schemas: 0,
+            channels: 0,
+            sequence: 0,
+        };
+        writer.out.write_all(&MAGIC)?;
+        let mut header = Vec::new();
+        put_str(&mut header, profile);
+        put_str(&mut header, concat!("sync-core ", env!("CARGO_PKG_VERSION")));
+        writer.record(OP_HEADER, &header)?;
+        Ok(writer)
+    }
+
+    /// Adds a schema, e.g. a `ros2msg` message definition; returns its id.
+    pub fn add_schema(&mut self, name: &str, encoding: &str, data: &[u8]) -> io::Result<u16> {
+        self.schemas += 1;
+        let mut schema = Vec::new();
+        schema.extend_from_slice(&self.schemas.to_le_bytes());
+        put_str(&mut schema, name);
+        put_str(&mut schema, encoding);
+        put_bytes(&mut schema, data);
+        self.record(OP_SCHEMA, &schema)?;
+        Ok(self.schemas)
+    }
+
+    /// Adds a channel without metadata; returns its id.
+    pub fn add_channel(&mut self, schema_id: u16, topic: &str, message_encoding: &str) -> io::Result<u16> {
+        self.channels += 1;
+        let mut channel = Vec::new();
+        channel.extend_from_slice(&self.channels.to_le_bytes());
+        channel.extend_from_slice(&schema_id.to_le_bytes());
+        put_str(&mut channel, topic);
+        put_str(&mut channel, message_encoding);
+        // Empty metadata map
+        channel.extend_from_slice(&0u32.to_le_bytes());
+        self.record(OP_CHANNEL, &channel)?;
+        Ok(self.channels)
+    }
+
+    pub fn write_message(&mut self, channel_id: u16, log_time_ns: u64, publish_time_ns: u64, data: &[u8]) -> io::Result<()> {
+        self.sequence += 1;
+        let mut message = Vec::with_capacity(22 + data.len());
+        message.extend_from_slice(&channel_id.to_le_bytes());
+        message.extend_from_slice(&self.sequence.to_le_bytes());
+        message.extend_from_slice(&log_time_ns.to_le_bytes());
+        message.extend_from_slice(&publish_time_ns.to_le_bytes());
+        message.extend_from_slice(data);
+        self.record(OP_MESSAGE, &message)
+    }
+
+    /// Writes the data end and footer records and the closing magic.
+    pub fn finish(mut self) -> io::Result<()> {
+        // Data section CRC 0: not computed
+        self.record(OP_DATA_END, &0u32.to_le_bytes())?;
+        // No summary section and no summary offsets, summary CRC 0
+        self.record(OP_FOOTER, &[0; 20])?;
+        self.out.write_all(&MAGIC)?;
+        self.out.flush()
+    }
+
+    fn record(&mut self, opcode: u8, content: &[u8]) -> io::Result<()> {
+        self.out.write_all(&[opcode])?;
+        self.out.write_all(&(content.len() as u64).to_le_bytes())?;
+        self.out.write_all(content)
+    }
+}
+
+fn put_str(out: &mut Vec<u8>, value: &str) {
+    put_bytes(out, value.as_bytes());
+}
+
+fn put_bytes(out: &mut Vec<u8>, value: &[u8]) {
+    out.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    out.extend_from_slice(value);
+}
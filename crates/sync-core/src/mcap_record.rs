@@ -0,0 +1,289 @@
+// Live MCAP recording of the synchronization, for offline analysis in Foxglove.
+//
+// `v4l2_capture --record <file.mcap>` writes, while capturing, one MCAP file
+// with these channels (`<ns>` is `/camera`):
+//
+//   <ns>/trigger    sensor_msgs/msg/TimeReference (cdr)  every received trigger,
+//                   stamp = hw_ts, time_ref = publish time, source = trigger id
+//   <ns>/image_raw  sensor_msgs/msg/Image (cdr, rgb8)    every matched frame,
+//                   stamp = hw_ts
+//   <ns>/sync       camera_sync.MatchedFrame (json)      every matched frame:
+//                   trigger, publish and frame times, latency, score, confidence
+//   <ns>/latency    camera_sync.LatencyStages (json)     every
+//                   `LATENCY_INTERVAL_NS`: count, mean and percentiles per
+//                   latency stage (see `latency`)
+//
+// Images and triggers reuse the ROS 2 message definitions of the bag export
+// (see `rosbag`), so Foxglove's image panel and `ros2 bag play` understand
+// them; the metrics are JSON with a JSON schema, which Foxglove plots field by
+// field. JSON numbers lose precision beyond 2^53, so times are written as
+// `{sec, nsec}` objects like Foxglove's own time type. Messages are logged at
+// their trigger's hw_ts, as in the bag export, and published at the frame's
+// V4L2 timestamp. The file is written by `McapWriter`, without chunks; it is
+// complete once the recording is finished or dropped.
+
+use std::io;
+use std::path::Path;
+
+use crate::mcap::McapWriter;
+use crate::rosbag;
+use crate::stats::StatsSummary;
+use crate::status::{json_array, json_object, Value};
+use crate::CameraTrigger;
+
+/// Topic namespace of the recorded channels.
+pub const NAMESPACE: &str = "/camera";
+
+/// Latency stages are written at most this often (trigger time).
+pub const LATENCY_INTERVAL_NS: u64 = 1_000_000_000;
+
+const TIME_SCHEMA: &str = r#"{"type":"object","properties":{"sec":{"type":"integer"},"nsec":{"type":"integer"}}}"#;
+
+const MATCHED_FRAME_TYPE: &str = "camera_sync.MatchedFrame";
+const LATENCY_STAGES_TYPE: &str = "camera_sync.LatencyStages";
+
+/// A matched frame as recorded on the `sync` channel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RecordedMatch {
+    pub trigger: CameraTrigger,
+    /// V4L2 timestamp of the frame.
+    pub frame_ns: u64,
+    pub latency_ms: f64,
+    pub score_ms: f64,
+    /// Match confidence (see `retention`).
+    pub confidence: f64,
+}
+
+/// Messages written so far.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RecordingCounts {
+    pub triggers: u64,
+    pub frames: u64,
+    pub images: u64,
+    pub latency: u64,
+}
+
+pub struct McapRecording {
+    // Taken by `finish`, or on drop
+    writer: Option<McapWriter>,
+    trigger_channel: u16,
+    image_channel: u16,
+    sync_channel: u16,
+    latency_channel: u16,
+    next_latency_ns: u64,
+    counts: RecordingCounts,
+}
+
+impl McapRecording {
+    /// Creates `path` with the schemas and channels of the recording.
+    pub fn create(path: &Path) -> io::Result<Self> {
+        // Mixed encodings: no profile
+        let mut writer = McapWriter::create(path, "")?;
+        let time_reference_schema = rosbag::add_time_reference_schema(&mut writer)?;
+        let image_schema = rosbag::add_image_schema(&mut writer)?;
+        let sync_schema = writer.add_schema(MATCHED_FRAME_TYPE, "jsonschema", matched_frame_schema().as_bytes())?;
+        let latency_schema = writer.add_schema(LATENCY_STAGES_TYPE, "jsonschema", latency_stages_schema().as_bytes())?;
+        Ok(Self {
+            trigger_channel: writer.add_channel(time_reference_schema, &format!("{}/trigger", NAMESPACE), "cdr")?,
+            image_channel: writer.add_channel(image_schema, &format!("{}/image_raw", NAMESPACE), "cdr")?,
+            sync_channel: writer.add_channel(sync_schema, &format!("{}/sync", NAMESPACE), "json")?,
+            latency_channel: writer.add_channel(latency_schema, &format!("{}/latency", NAMESPACE), "json")?,
+            writer: Some(writer),
+            next_latency_ns: 0,
+            counts: RecordingCounts::default(),
+        })
+    }
+
+    pub fn counts(&self) -> RecordingCounts {
+        self.counts
+    }
+
+    /// Records a received trigger.
+    pub fn record_trigger(&mut self, CameraTrigger { frame_id: trigger_id, hw_timestamp_ns: hw_ts, publish_timestamp_ns: pub_ts, .. }: CameraTrigger) -> io::Result<()> {
+        let data = rosbag::time_reference(hw_ts, "camera", pub_ts, &trigger_id.to_string());
+        self.write(self.trigger_channel, hw_ts, pub_ts, &data)?;
+        self.counts.triggers += 1;
+        Ok(())
+    }
+
+    /// Records a matched frame, with its rgb8 image if given as (width, height, rgb).
+    pub fn record_match(&mut self, matched: &RecordedMatch, image: Option<(u32, u32, &[u8])>) -> io::Result<()> {
+        let CameraTrigger { frame_id: trigger_id, hw_timestamp_ns: hw_ts, publish_timestamp_ns: pub_ts, .. } = matched.trigger;
+        if let Some((width, height, rgb)) = image {
+            self.write(self.image_channel, hw_ts, matched.frame_ns, &rosbag::image(hw_ts, "camera", width, height, rgb))?;
+            self.counts.images += 1;
+        }
+        let sync = json_object(&[
+            ("trigger_id", trigger_id.into()),
+            ("trigger_time", Value::Json(time(hw_ts))),
+            ("publish_time", Value::Json(time(pub_ts))),
+            ("frame_time", Value::Json(time(matched.frame_ns))),
+            ("latency_ms", matched.latency_ms.into()),
+            ("score_ms", matched.score_ms.into()),
+            ("confidence", matched.confidence.into()),
+        ]);
+        self.write(self.sync_channel, hw_ts, matched.frame_ns, sync.as_bytes())?;
+        self.counts.frames += 1;
+        Ok(())
+    }
+
+    /// Records the latency stages (`LatencyBreakdown::snapshot`) if due at the trigger time `hw_ts`; returns whether it did.
+    pub fn record_latency(&mut self, hw_ts: u64, stages: &[(&str, StatsSummary)]) -> io::Result<bool> {
+        if hw_ts < self.next_latency_ns || stages.is_empty() {
+            return Ok(false);
+        }
+        let stages: Vec<Value> = stages
+            .iter()
+            .map(|(stage, summary)| {
+                Value::Json(json_object(&[
+                    ("stage", (*stage).into()),
+                    ("count", summary.count.into()),
+                    ("mean_ms", summary.mean_ms.into()),
+                    ("std_ms", summary.std_ms.into()),
+                    ("p50_ms", summary.p50_ms.into()),
+                    ("p95_ms", summary.p95_ms.into()),
+                    ("min_ms", summary.min_ms.into()),
+                    ("max_ms", summary.max_ms.into()),
+                ]))
+            })
+            .collect();
+        let latency = json_object(&[("timestamp", Value::Json(time(hw_ts))), ("stages", Value::Json(json_array(&stages)))]);
+        self.write(self.latency_channel, hw_ts, hw_ts, latency.as_bytes())?;
+        self.counts.latency += 1;
+        self.next_latency_ns = hw_ts + LATENCY_INTERVAL_NS;
+        Ok(true)
+    }
+
+    /// Completes the file; dropping the recording does too, but without reporting errors.
+    pub fn finish(mut self) -> io::Result<RecordingCounts> {
+        if let Some(writer) = self.writer.take() {
+            writer.finish()?;
+        }
+        Ok(self.counts)
+    }
+
+    fn write(&mut self, channel: u16, log_time_ns: u64, publish_time_ns: u64, data: &[u8]) -> io::Result<()> {
+        self.writer.as_mut().expect("taken only when finished").write_message(channel, log_time_ns, publish_time_ns, data)
+    }
+}
+
+impl Drop for McapRecording {
+    fn drop(&mut self) {
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.finish();
+        }
+    }
+}
+
+// Foxglove's time type
+fn time(ns: u64) -> String {
+    json_object(&[("sec", (ns / 1_000_000_000).into()), ("nsec", (ns % 1_000_000_000).into())])
+}
+
+fn matched_frame_schema() -> String {
+    format!(
+        r#"{{"title":"{}","type":"object","properties":{{"trigger_id":{{"type":"integer"}},"trigger_time":{},"publish_time":{},"frame_time":{},"latency_ms":{{"type":"number"}},"score_ms":{{"type":"number"}},"confidence":{{"type":"number"}}}}}}"#,
+        MATCHED_FRAME_TYPE, TIME_SCHEMA, TIME_SCHEMA, TIME_SCHEMA
+    )
+}
+
+fn latency_stages_schema() -> String {
+    let number = r#"{"type":"number"}"#;
+    let stage = format!(
+        r#"{{"type":"object","properties":{{"stage":{{"type":"string"}},"count":{{"type":"integer"}},"mean_ms":{n},"std_ms":{n},"p50_ms":{n},"p95_ms":{n},"min_ms":{n},"max_ms":{n}}}}}"#,
+        n = number
+    );
+    format!(r#"{{"title":"{}","type":"object","properties":{{"timestamp":{},"stages":{{"type":"array","items":{}}}}}}}"#, LATENCY_STAGES_TYPE, TIME_SCHEMA, stage)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use crate::latency::{FrameTiming, LatencyBreakdown};
+
+    const MAGIC: &[u8] = b"\x89MCAP0\r\n";
+    const START_NS: u64 = 1_700_000_000_000_000_000;
+
+    // (opcode, content) of every record between the magics
+    fn records(bytes: &[u8]) -> Vec<(u8, &[u8])> {
+        assert!(bytes.starts_with(MAGIC) && bytes.ends_with(MAGIC));
+        let mut records = Vec::new();
+        let mut rest = &bytes[MAGIC.len()..bytes.len() - MAGIC.len()];
+        while !rest.is_empty() {
+            let len = u64::from_le_bytes(rest[1..9].try_into().unwrap()) as usize;
+            records.push((rest[0], &rest[9..9 + len]));
+            rest = &rest[9 + len..];
+        }
+        records
+    }
+
+    // Topic of a channel record
+    fn topic(channel: &[u8]) -> String {
+        let len = u32::from_le_bytes(channel[4..8].try_into().unwrap()) as usize;
+        String::from_utf8(channel[8..8 + len].to_vec()).unwrap()
+    }
+
+    #[test]
+    fn recording_has_all_channels() {
+        let path = std::env::temp_dir().join(format!("camera_sync_mcap_record_{}.mcap", std::process::id()));
+        let mut recording = McapRecording::create(&path).unwrap();
+        let mut latency = LatencyBreakdown::default();
+        for n in 0..60u64 {
+            let hw_ts = START_NS + n * 33_333_333;
+            let trigger = CameraTrigger::new(n, hw_ts, hw_ts + 50_000);
+            recording.record_trigger(trigger).unwrap();
+            // Every other trigger matched, images of the first ten
+            if n % 2 == 1 {
+                continue;
+            }
+            let frame_ns = hw_ts + 12_000_000;
+            latency.record(&FrameTiming {
+                trigger_ns: hw_ts,
+                driver_ns: None,
+                dequeue_ns: frame_ns,
+                match_ns: frame_ns,
+            });
+            let matched = RecordedMatch {
+                trigger,
+                frame_ns,
+                latency_ms: 12.0,
+                score_ms: 0.5,
+                confidence: 0.95,
+            };
+            let rgb = [128u8; 4 * 2 * 3];
+            recording.record_match(&matched, (n < 10).then_some((4, 2, &rgb[..]))).unwrap();
+            recording.record_latency(hw_ts, &latency.snapshot()).unwrap();
+        }
+        // Two seconds of triggers: latency stages at the start and one interval later
+        let counts = recording.finish().unwrap();
+        assert_eq!(counts, RecordingCounts { triggers: 60, frames: 30, images: 5, latency: 2 });
+
+        let bytes = fs::read(&path).unwrap();
+        let records = records(&bytes);
+        let topics: Vec<String> = records.iter().filter(|(opcode, _)| *opcode == 0x04).map(|(_, channel)| topic(channel)).collect();
+        assert_eq!(topics, ["/camera/trigger", "/camera/image_raw", "/camera/sync", "/camera/latency"]);
+        let messages: Vec<&[u8]> = records.iter().filter(|(opcode, _)| *opcode == 0x05).map(|(_, message)| *message).collect();
+        assert_eq!(messages.len(), 60 + 30 + 5 + 2);
+        // Channel 3 is the sync channel, JSON after channel id, sequence and the two times
+        let sync = messages.iter().find(|message| message[0..2] == 3u16.to_le_bytes()).unwrap();
+        let sync = std::str::from_utf8(&sync[22..]).unwrap();
+        assert!(sync.starts_with(r#"{"trigger_id":0,"trigger_time":{"sec":1700000000,"nsec":0},"#), "{}", sync);
+        assert!(sync.contains(r#""frame_time":{"sec":1700000000,"nsec":12000000}"#), "{}", sync);
+        assert_eq!(records.last().unwrap().0, 0x02);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn dropped_recording_is_complete() {
+        let path = std::env::temp_dir().join(format!("camera_sync_mcap_record_drop_{}.mcap", std::process::id()));
+        let mut recording = McapRecording::create(&path).unwrap();
+        recording.record_trigger(CameraTrigger::new(1, START_NS, START_NS)).unwrap();
+        drop(recording);
+        let bytes = fs::read(&path).unwrap();
+        let records = records(&bytes);
+        assert_eq!(records.iter().filter(|(opcode, _)| *opcode == 0x05).count(), 1);
+        assert_eq!(records.last().unwrap().0, 0x02);
+        fs::remove_file(&path).unwrap();
+    }
+}
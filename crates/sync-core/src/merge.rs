@@ -0,0 +1,247 @@
+// Alignment of sessions recorded on several hosts into one timeline.
+//
+// Every host records its frames with local CLOCK_REALTIME timestamps and its
+// clock mappings (`clock.csv`, see `clockmap`). The merge subtracts each
+// host's offset, interpolated between its mappings, so all frame timestamps
+// are in the trigger publisher's clock, and joins the hosts' records on the
+// shared trigger ids.
+//
+// Residual alignment error is estimated from the data itself: per host, the
+// residual of a frame is its aligned latency (aligned frame time minus
+// trigger time) minus the host's median latency. For triggers seen by several
+// hosts, the spread of their residuals is what the alignment leaves unexplained
+// (mapping error plus capture jitter). Sessions without clock mappings are
+// merged with offset 0 and reported as such.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::session::{self, ClockMapping, MatchRecord};
+use crate::stats::LatencyStats;
+use crate::CameraTrigger;
+
+pub const MERGED_FILE: &str = "merged.csv";
+
+const MERGED_HEADER: &str = "trigger_id,hw_timestamp_ns,host,aligned_timestamp_ns,offset_ns,offset_uncertainty_ns,residual_ns";
+
+/// The records and clock mappings of one host's session.
+#[derive(Debug, Clone)]
+pub struct HostSession {
+    /// Session directory name, e.g. `cam-left`.
+    pub name: String,
+    pub records: Vec<MatchRecord>,
+    pub mappings: Vec<ClockMapping>,
+}
+
+impl HostSession {
+    pub fn load(dir: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let name = dir
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| dir.display().to_string());
+        Ok(Self {
+            name,
+            records: session::read_records(dir)?,
+            mappings: session::read_clock_mappings(dir)?,
+        })
+    }
+
+    /// Offset and uncertainty of the local clock at local realtime `realtime_ns`,
+    /// linearly interpolated between the surrounding mappings.
+    pub fn offset_at(&self, realtime_ns: u64) -> (i64, u64) {
+        let after = self.mappings.partition_point(|mapping| mapping.realtime_ns < realtime_ns);
+        match (after.checked_sub(1).map(|i| &self.mappings[i]), self.mappings.get(after)) {
+            (None, None) => (0, 0),
+            (Some(only), None) | (None, Some(only)) => (only.offset_ns, only.uncertainty_ns),
+            (Some(before), Some(after)) => {
+                let span = (after.realtime_ns - before.realtime_ns).max(1) as f64;
+                let fraction = (realtime_ns - before.realtime_ns) as f64 / span;
+                let offset = before.offset_ns as f64 + (after.offset_ns - before.offset_ns) as f64 * fraction;
+                (offset.round() as i64, before.uncertainty_ns.max(after.uncertainty_ns))
+            }
+        }
+    }
+}
+
+/// One frame on the merged timeline.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MergedRecord {
+    pub trigger_id: u64,
+    pub hw_timestamp_ns: u64,
+    /// Index into the merged hosts.
+    pub host: usize,
+    /// Frame timestamp in the publisher's clock.
+    pub aligned_timestamp_ns: u64,
+    pub offset_ns: i64,
+    pub offset_uncertainty_ns: u64,
+    /// Aligned latency minus the host's median latency.
+    pub residual_ns: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct HostSummary {
+    pub name: String,
+    pub records: usize,
+    pub mappings: usize,
+    pub mean_offset_ns: f64,
+    pub max_uncertainty_ns: u64,
+    pub median_latency_ms: f64,
+    /// Absolute residuals, ms.
+    pub residual: LatencyStats,
+}
+
+#[derive(Debug, Clone)]
+pub struct MergedSessions {
+    pub hosts: Vec<HostSummary>,
+    /// Sorted by trigger time, then host.
+    pub records: Vec<MergedRecord>,
+    /// Spread of the residuals per trigger seen by more than one host, ms.
+    pub cross_host: LatencyStats,
+}
+
+pub fn merge(sessions: &[HostSession]) -> MergedSessions {
+    let mut records = Vec::new();
+    let mut hosts = Vec::new();
+    for (host, session) in sessions.iter().enumerate() {
+        let first = records.len();
+        let mut latencies = Vec::with_capacity(session.records.len());
+        for record in &session.records {
+            let CameraTrigger { frame_id: trigger_id, hw_timestamp_ns: hw_ts, .. } = record.trigger;
+            let (offset_ns, offset_uncertainty_ns) = session.offset_at(record.v4l2_timestamp_ns);
+            let aligned_timestamp_ns = (record.v4l2_timestamp_ns as i64 - offset_ns) as u64;
+            latencies.push(aligned_timestamp_ns as i64 - hw_ts as i64);
+            records.push(MergedRecord {
+                trigger_id,
+                hw_timestamp_ns: hw_ts,
+                host,
+                aligned_timestamp_ns,
+                offset_ns,
+                offset_uncertainty_ns,
+                residual_ns: 0,
+            });
+        }
+
+        let mut sorted = latencies.clone();
+        sorted.sort_unstable();
+        let median_ns = sorted.get(sorted.len() / 2).copied().unwrap_or(0);
+        let mut residual = LatencyStats::default();
+        for (record, latency_ns) in records[first..].iter_mut().zip(&latencies) {
+            record.residual_ns = latency_ns - median_ns;
+            residual.add(record.residual_ns.unsigned_abs() as f64 / 1e6);
+        }
+        let offsets: Vec<i64> = session.mappings.iter().map(|mapping| mapping.offset_ns).collect();
+        hosts.push(HostSummary {
+            name: session.name.clone(),
+            records: session.records.len(),
+            mappings: session.mappings.len(),
+            mean_offset_ns: if offsets.is_empty() { 0.0 } else { offsets.iter().sum::<i64>() as f64 / offsets.len() as f64 },
+            max_uncertainty_ns: session.mappings.iter().map(|mapping| mapping.uncertainty_ns).max().unwrap_or(0),
+            median_latency_ms: median_ns as f64 / 1e6,
+            residual,
+        });
+    }
+
+    let mut per_trigger: BTreeMap<u64, (i64, i64, usize)> = BTreeMap::new();
+    for record in &records {
+        let entry = per_trigger.entry(record.trigger_id).or_insert((i64::MAX, i64::MIN, 0));
+        entry.0 = entry.0.min(record.residual_ns);
+        entry.1 = entry.1.max(record.residual_ns);
+        entry.2 += 1;
+    }
+    let mut cross_host = LatencyStats::default();
+    for (min, max, _) in per_trigger.values().filter(|(_, _, count)| *count > 1) {
+        cross_host.add((max - min) as f64 / 1e6);
+    }
+
+    records.sort_by_key(|record| (record.hw_timestamp_ns, record.host));
+    MergedSessions { hosts, records, cross_host }
+}
+
+impl MergedSessions {
+    /// Writes `merged.csv` into `dir`, with host names instead of indices.
+    pub fn write(&self, dir: &Path) -> std::io::Result<()> {
+        fs::create_dir_all(dir)?;
+        let mut out = BufWriter::new(File::create(dir.join(MERGED_FILE))?);
+        writeln!(out, "{}", MERGED_HEADER)?;
+        for record in &self.records {
+            writeln!(
+                out,
+                "{},{},{},{},{},{},{}",
+                record.trigger_id,
+                record.hw_timestamp_ns,
+                self.hosts[record.host].name,
+                record.aligned_timestamp_ns,
+                record.offset_ns,
+                record.offset_uncertainty_ns,
+                record.residual_ns
+            )?;
+        }
+        out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clockmap::ClockMapper;
+    use crate::signing::{SignatureStatus, TriggerSignature};
+
+    const MS: u64 = 1_000_000;
+
+    // Frames of triggers 1..=4 every 33ms, seen `latency_ms` later on a clock `offset_ms` ahead
+    fn host(name: &str, offset_ms: i64, latency_ms: u64, mappings: Vec<ClockMapping>) -> HostSession {
+        let records = (1..=4)
+            .map(|id| {
+                let hw_ts = 1_000 * MS + id * 33 * MS;
+                MatchRecord {
+                    trigger: CameraTrigger::new(id, hw_ts, hw_ts),
+                    v4l2_timestamp_ns: (hw_ts as i64 + offset_ms * MS as i64) as u64 + latency_ms * MS,
+                    total_latency_ms: latency_ms as f64,
+                    score_ms: 0.0,
+                    signature_status: SignatureStatus::Unsigned,
+                    signature: TriggerSignature::default(),
+                    metadata: Vec::new(),
+                }
+            })
+            .collect();
+        HostSession { name: name.to_string(), records, mappings }
+    }
+
+    fn mapping(realtime_ns: u64, offset_ms: i64) -> ClockMapping {
+        ClockMapping { realtime_ns, monotonic_ns: 0, offset_ns: offset_ms * MS as i64, uncertainty_ns: 20_000 }
+    }
+
+    #[test]
+    fn hosts_are_aligned_on_the_publisher_clock() {
+        let left = host("cam-left", 0, 20, vec![mapping(0, 0)]);
+        let right = host("cam-right", 250, 20, vec![mapping(1_200 * MS, 248), mapping(1_500 * MS, 252)]);
+        assert_eq!(right.offset_at(1_350 * MS), (250 * MS as i64, 20_000));
+        assert_eq!(host("solo", 0, 0, Vec::new()).offset_at(5), (0, 0));
+
+        let merged = merge(&[left, right]);
+        assert_eq!(merged.records.len(), 8);
+        assert_eq!((merged.records[0].host, merged.records[1].host), (0, 1));
+        // Interpolated offsets leave at most the mapping error
+        for pair in merged.records.chunks(2) {
+            assert_eq!(pair[0].trigger_id, pair[1].trigger_id);
+            assert!(pair[0].aligned_timestamp_ns.abs_diff(pair[1].aligned_timestamp_ns) < MS, "{:?}", pair);
+        }
+        assert_eq!(merged.cross_host.count(), 4);
+        assert_eq!((merged.hosts[1].mappings, merged.hosts[1].mean_offset_ns), (2, 250e6));
+    }
+
+    #[test]
+    fn mappings_bound_the_offset_with_the_least_delayed_trigger() {
+        let mut mapper = ClockMapper::default();
+        let mut mapping = None;
+        for i in 0..crate::clockmap::MAPPING_WINDOW as u64 {
+            // 5ms clock offset plus 100..199us of delay
+            mapping = mapper.on_trigger(i * 33 * MS, i * 33 * MS + 5 * MS + 100_000 + i % 100 * 1_000);
+        }
+        let mapping = mapping.unwrap();
+        assert_eq!((mapping.offset_ns, mapping.uncertainty_ns), (5_100_000, 50_000));
+        assert!(mapper.on_trigger(0, 0).is_none());
+    }
+}
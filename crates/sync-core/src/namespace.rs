@@ -0,0 +1,116 @@
+// Deterministic iceoryx2 node names within a rig namespace.
+//
+// Every process names its iceoryx2 node `<rig>/<program>[/<instance>]`, e.g.
+// `lab2/publisher` or `lab2/subscriber/left`, so the nodes of a rig can be
+// told apart from other iceoryx2 applications on the host and a stale node
+// left by a crashed run can be traced back to the process that created it.
+// The rig comes from `--rig-id` (default `default`), the instance from what
+// distinguishes several processes of one program (camera, sensor, bridge
+// mode); `--node-name` replaces the part after the rig.
+//
+// A rig other than the default also gets its own resource prefix
+// (`iox2_<rig>_` instead of iceoryx2's `iox2_`), so two rigs on one host do
+// not see each other's services. `rig_nodes cleanup` only removes nodes that
+// belong to its rig (`NodeIdentity::owns`).
+
+use crate::cli::Args;
+
+/// Rig of the processes started without `--rig-id`; keeps iceoryx2's default resource prefix.
+pub const DEFAULT_RIG: &str = "default";
+/// Longest rig id, in bytes; it is part of every shared-memory file name.
+pub const MAX_RIG_ID_LEN: usize = 32;
+
+/// The rig a process belongs to and the name of its iceoryx2 node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeIdentity {
+    rig: String,
+    name: String,
+}
+
+impl NodeIdentity {
+    /// Names the node `<rig>/<program>[/<instance>]`.
+    pub fn new(rig: &str, program: &str, instance: Option<&str>) -> Result<Self, String> {
+        check_rig_id(rig)?;
+        let name = match instance {
+            Some(instance) if !instance.is_empty() => format!("{}/{}/{}", rig, program, instance),
+            _ => format!("{}/{}", rig, program),
+        };
+        Ok(Self { rig: rig.to_string(), name })
+    }
+
+    /// `--rig-id` and `--node-name`; `program` and `instance` name the node unless `--node-name` is given.
+    pub fn from_args(args: &Args, program: &str, instance: Option<&str>) -> Result<Self, String> {
+        let rig = args.value("rig-id").unwrap_or(DEFAULT_RIG);
+        match args.value("node-name") {
+            Some("") => Err("--node-name must not be empty".to_string()),
+            Some(name) => Self::new(rig, name, None),
+            None => Self::new(rig, program, instance),
+        }
+    }
+
+    pub fn rig(&self) -> &str {
+        &self.rig
+    }
+
+    /// The full node name, rig included.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Prefix of the rig's shared-memory resources; `None` keeps iceoryx2's default.
+    pub fn resource_prefix(&self) -> Option<String> {
+        (self.rig != DEFAULT_RIG).then(|| format!("iox2_{}_", self.rig))
+    }
+
+    /// A second node of the same process, named `<name>/<suffix>` (e.g. for a thread that needs its own node).
+    pub fn child(&self, suffix: &str) -> Self {
+        Self {
+            rig: self.rig.clone(),
+            name: format!("{}/{}", self.name, suffix),
+        }
+    }
+
+    /// Whether a node of this name belongs to the rig.
+    pub fn owns(&self, node_name: &str) -> bool {
+        node_name.strip_prefix(self.rig.as_str()).is_some_and(|rest| rest.starts_with('/'))
+    }
+}
+
+/// Rig ids end up in file names: 1 to `MAX_RIG_ID_LEN` ASCII letters, digits, `-` or `_`.
+pub fn check_rig_id(rig: &str) -> Result<(), String> {
+    if rig.is_empty() || rig.len() > MAX_RIG_ID_LEN {
+        return Err(format!("rig id must be 1 to {} characters, got {:?}", MAX_RIG_ID_LEN, rig));
+    }
+    match rig.chars().find(|c| !(c.is_ascii_alphanumeric() || *c == '-' || *c == '_')) {
+        Some(c) => Err(format!("rig id {:?} contains {:?} (allowed: letters, digits, '-', '_')", rig, c)),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Args {
+        Args::parse(line.split_whitespace().map(str::to_string), &[])
+    }
+
+    #[test]
+    fn node_names_are_scoped_to_the_rig() {
+        let camera = NodeIdentity::from_args(&args("v4l2_capture 0 --rig-id lab2"), "v4l2_capture", Some("serial:ABC123")).unwrap();
+        assert_eq!(camera.name(), "lab2/v4l2_capture/serial:ABC123");
+        assert_eq!(camera.child("frames").name(), "lab2/v4l2_capture/serial:ABC123/frames");
+        assert_eq!(camera.resource_prefix().as_deref(), Some("iox2_lab2_"));
+        assert!(camera.owns("lab2/publisher"));
+        assert!(!camera.owns("lab20/publisher"));
+        assert!(!camera.owns("lab2"));
+
+        let publisher = NodeIdentity::from_args(&args("publisher --node-name trigger_main"), "publisher", None).unwrap();
+        assert_eq!((publisher.rig(), publisher.name()), (DEFAULT_RIG, "default/trigger_main"));
+        assert_eq!(publisher.resource_prefix(), None);
+        assert!(!publisher.owns("iceoryx2_app"));
+
+        assert!(NodeIdentity::from_args(&args("publisher --rig-id lab/2"), "publisher", None).is_err());
+        assert!(NodeIdentity::new(&"r".repeat(33), "publisher", None).is_err());
+    }
+}
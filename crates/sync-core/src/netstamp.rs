@@ -0,0 +1,338 @@
+// Kernel and NIC timestamps of UDP datagrams (SO_TIMESTAMPING).
+//
+// Bridging triggers over Ethernet adds a network delay that the receiving
+// side wants to know. Reading the clock in user space around `send`/`recv`
+// adds scheduling noise of tens of microseconds to every estimate. With
+// SO_TIMESTAMPING the kernel reports when a datagram left or arrived: taken
+// by the NIC itself where it supports hardware timestamping (enabled per
+// interface with SIOCSHWTSTAMP, usually needs CAP_NET_ADMIN), otherwise by
+// the network stack. Sockets fall back step by step (hardware, software,
+// user space) and report which kind of timestamp they delivered.
+//
+// All timestamps are CLOCK_REALTIME ns. NIC timestamps come from the PTP
+// hardware clock, which linuxptp keeps on TAI; they are shifted to UTC with the leap
+// seconds valid then (see `leap`).
+
+use std::fmt;
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::Duration;
+
+use crate::clock;
+#[cfg(target_os = "linux")]
+use crate::leap::Tai;
+
+/// Who took a timestamp, from most to least precise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum StampMode {
+    Hardware,
+    Software,
+    User,
+}
+
+impl fmt::Display for StampMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StampMode::Hardware => write!(f, "hardware"),
+            StampMode::Software => write!(f, "software"),
+            StampMode::User => write!(f, "user"),
+        }
+    }
+}
+
+/// Layout and constants of `<linux/net_tstamp.h>` and `<linux/sockios.h>`.
+#[cfg(target_os = "linux")]
+#[allow(dead_code)]
+mod uapi {
+    pub const SOF_TIMESTAMPING_TX_HARDWARE: u32 = 1 << 0;
+    pub const SOF_TIMESTAMPING_TX_SOFTWARE: u32 = 1 << 1;
+    pub const SOF_TIMESTAMPING_RX_HARDWARE: u32 = 1 << 2;
+    pub const SOF_TIMESTAMPING_RX_SOFTWARE: u32 = 1 << 3;
+    pub const SOF_TIMESTAMPING_SOFTWARE: u32 = 1 << 4;
+    pub const SOF_TIMESTAMPING_RAW_HARDWARE: u32 = 1 << 6;
+    pub const SOF_TIMESTAMPING_OPT_TSONLY: u32 = 1 << 11;
+
+    pub const SIOCSHWTSTAMP: u64 = 0x89b0;
+    pub const HWTSTAMP_TX_ON: i32 = 1;
+    pub const HWTSTAMP_FILTER_ALL: i32 = 1;
+
+    #[repr(C)]
+    pub struct HwTstampConfig {
+        pub flags: i32,
+        pub tx_type: i32,
+        pub rx_filter: i32,
+    }
+
+    /// `struct ifreq` with the `ifr_data` member of its union.
+    #[repr(C)]
+    pub struct IfReq {
+        pub name: [u8; 16],
+        pub data: *mut HwTstampConfig,
+        pub padding: [u8; 16],
+    }
+}
+
+/// How long `send_to` waits for the TX timestamp before falling back to a user space read.
+#[cfg(target_os = "linux")]
+const TX_STAMP_TIMEOUT_MS: i32 = 10;
+
+/// A UDP socket that timestamps every datagram sent and received.
+pub struct StampedSocket {
+    socket: UdpSocket,
+    mode: StampMode,
+}
+
+impl StampedSocket {
+    /// Binds to `address` and enables the best timestamping available. `interface`
+    /// (e.g. `eth0`) is the NIC to switch to hardware timestamping; without it, or
+    /// if the NIC or permissions do not allow it, kernel software timestamps are used.
+    pub fn bind(address: &str, interface: Option<&str>) -> io::Result<Self> {
+        let socket = UdpSocket::bind(address)?;
+        let mode = enable_timestamping(&socket, interface);
+        Ok(Self { socket, mode })
+    }
+
+    /// The best timestamps this socket delivers; single datagrams may fall back further.
+    pub fn mode(&self) -> StampMode {
+        self.mode
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Sends one datagram and returns the time it left.
+    pub fn send_to(&self, bytes: &[u8], target: impl ToSocketAddrs) -> io::Result<(u64, StampMode)> {
+        self.socket.send_to(bytes, target)?;
+        let sent_ns = clock::realtime_now_ns();
+        #[cfg(target_os = "linux")]
+        if self.mode != StampMode::User {
+            if let Some(stamp) = read_tx_stamp(&self.socket)? {
+                return Ok(stamp);
+            }
+        }
+        Ok((sent_ns, StampMode::User))
+    }
+
+    /// Receives one datagram into `buffer` and returns its length and the time it arrived.
+    pub fn recv(&self, buffer: &mut [u8]) -> io::Result<(usize, u64, StampMode)> {
+        let (len, _, rx_ns, mode) = self.recv_from(buffer)?;
+        Ok((len, rx_ns, mode))
+    }
+
+    /// Like `recv`, also returning the sender's address.
+    pub fn recv_from(&self, buffer: &mut [u8]) -> io::Result<(usize, SocketAddr, u64, StampMode)> {
+        #[cfg(target_os = "linux")]
+        if self.mode != StampMode::User {
+            return recv_stamped(&self.socket, buffer);
+        }
+        let (len, from) = self.socket.recv_from(buffer)?;
+        Ok((len, from, clock::realtime_now_ns(), StampMode::User))
+    }
+
+    /// Lets `recv` fail with `WouldBlock` or `TimedOut` after `timeout`, `None` blocks.
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.socket.set_read_timeout(timeout)
+    }
+
+    /// Lets `recv` fail with `WouldBlock` right away when no datagram is queued.
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        self.socket.set_nonblocking(nonblocking)
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn enable_timestamping(socket: &UdpSocket, interface: Option<&str>) -> StampMode {
+    use uapi::*;
+
+    let software = SOF_TIMESTAMPING_TX_SOFTWARE | SOF_TIMESTAMPING_RX_SOFTWARE | SOF_TIMESTAMPING_SOFTWARE | SOF_TIMESTAMPING_OPT_TSONLY;
+    let hardware = SOF_TIMESTAMPING_TX_HARDWARE | SOF_TIMESTAMPING_RX_HARDWARE | SOF_TIMESTAMPING_RAW_HARDWARE;
+    if interface.is_some_and(|interface| enable_nic_timestamping(socket, interface).is_ok())
+        && set_timestamping(socket, software | hardware).is_ok()
+    {
+        return StampMode::Hardware;
+    }
+    match set_timestamping(socket, software) {
+        Ok(()) => StampMode::Software,
+        Err(_) => StampMode::User,
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn enable_timestamping(_socket: &UdpSocket, _interface: Option<&str>) -> StampMode {
+    StampMode::User
+}
+
+#[cfg(target_os = "linux")]
+fn set_timestamping(socket: &UdpSocket, flags: u32) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    // SAFETY: `flags` outlives the call and its size is passed along
+    let result = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_TIMESTAMPING,
+            &flags as *const u32 as *const libc::c_void,
+            std::mem::size_of::<u32>() as libc::socklen_t,
+        )
+    };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Switches the NIC to timestamp all received and sent packets.
+#[cfg(target_os = "linux")]
+fn enable_nic_timestamping(socket: &UdpSocket, interface: &str) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+    use uapi::*;
+
+    let mut config = HwTstampConfig {
+        flags: 0,
+        tx_type: HWTSTAMP_TX_ON,
+        rx_filter: HWTSTAMP_FILTER_ALL,
+    };
+    let mut request = IfReq {
+        name: [0; 16],
+        data: &mut config,
+        padding: [0; 16],
+    };
+    if interface.len() >= request.name.len() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "interface name too long"));
+    }
+    request.name[..interface.len()].copy_from_slice(interface.as_bytes());
+    // SAFETY: `request` matches `struct ifreq` and points at a live `hwtstamp_config`
+    if unsafe { libc::ioctl(socket.as_raw_fd(), SIOCSHWTSTAMP as _, &mut request) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// A kernel timestamp in UTC nanoseconds and where it was taken
+#[cfg(target_os = "linux")]
+type Stamp = (u64, StampMode);
+
+/// Receives from `socket` with `flags`, returning the length, the sender and the SCM_TIMESTAMPING stamp.
+#[cfg(target_os = "linux")]
+fn recvmsg_stamped(socket: &UdpSocket, buffer: &mut [u8], flags: libc::c_int) -> io::Result<(usize, Option<SocketAddr>, Option<Stamp>)> {
+    use std::os::fd::AsRawFd;
+
+    // Room for a few control messages, aligned for `cmsghdr`
+    let mut control = [0u64; 64];
+    // SAFETY: an all-zero sockaddr_storage is valid (AF_UNSPEC)
+    let mut name: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+    let mut iov = libc::iovec {
+        iov_base: buffer.as_mut_ptr() as *mut libc::c_void,
+        iov_len: buffer.len(),
+    };
+    // SAFETY: an all-zero msghdr is valid; the pointers set below outlive the call
+    let mut message: libc::msghdr = unsafe { std::mem::zeroed() };
+    message.msg_name = &mut name as *mut libc::sockaddr_storage as *mut libc::c_void;
+    message.msg_namelen = std::mem::size_of_val(&name) as libc::socklen_t;
+    message.msg_iov = &mut iov;
+    message.msg_iovlen = 1;
+    message.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+    message.msg_controllen = std::mem::size_of_val(&control) as _;
+    // SAFETY: `message` describes valid buffers for the kernel to fill
+    let len = unsafe { libc::recvmsg(socket.as_raw_fd(), &mut message, flags) };
+    if len < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let mut stamp = None;
+    // SAFETY: walking the control messages the kernel just wrote into `control`
+    unsafe {
+        let mut cmsg = libc::CMSG_FIRSTHDR(&message);
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SO_TIMESTAMPING {
+                // `struct scm_timestamping`: software, (deprecated), raw hardware
+                let stamps = std::ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const [libc::timespec; 3]);
+                let ns = |ts: &libc::timespec| ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64;
+                stamp = match (ns(&stamps[2]), ns(&stamps[0])) {
+                    (0, 0) => None,
+                    (0, software) => Some((software, StampMode::Software)),
+                    (hardware, _) => Some((Tai(hardware).to_utc().0, StampMode::Hardware)),
+                };
+            }
+            cmsg = libc::CMSG_NXTHDR(&message, cmsg);
+        }
+    }
+    Ok((len as usize, socket_addr(&name), stamp))
+}
+
+/// The IPv4 or IPv6 address in `name`.
+#[cfg(target_os = "linux")]
+fn socket_addr(name: &libc::sockaddr_storage) -> Option<SocketAddr> {
+    use std::net::{Ipv4Addr, Ipv6Addr, SocketAddrV4, SocketAddrV6};
+
+    match name.ss_family as libc::c_int {
+        libc::AF_INET => {
+            // SAFETY: the kernel wrote a sockaddr_in for AF_INET
+            let addr = unsafe { &*(name as *const libc::sockaddr_storage as *const libc::sockaddr_in) };
+            let ip = Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr));
+            Some(SocketAddr::V4(SocketAddrV4::new(ip, u16::from_be(addr.sin_port))))
+        }
+        libc::AF_INET6 => {
+            // SAFETY: the kernel wrote a sockaddr_in6 for AF_INET6
+            let addr = unsafe { &*(name as *const libc::sockaddr_storage as *const libc::sockaddr_in6) };
+            let ip = Ipv6Addr::from(addr.sin6_addr.s6_addr);
+            Some(SocketAddr::V6(SocketAddrV6::new(ip, u16::from_be(addr.sin6_port), addr.sin6_flowinfo, addr.sin6_scope_id)))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn recv_stamped(socket: &UdpSocket, buffer: &mut [u8]) -> io::Result<(usize, SocketAddr, u64, StampMode)> {
+    let (len, from, stamp) = recvmsg_stamped(socket, buffer, 0)?;
+    let from = from.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "datagram without a sender address"))?;
+    let (ns, mode) = stamp.unwrap_or_else(|| (clock::realtime_now_ns(), StampMode::User));
+    Ok((len, from, ns, mode))
+}
+
+/// Waits for the TX timestamp of the datagram just sent on the socket's error queue.
+#[cfg(target_os = "linux")]
+fn read_tx_stamp(socket: &UdpSocket) -> io::Result<Option<Stamp>> {
+    use std::os::fd::AsRawFd;
+
+    let mut poll = libc::pollfd {
+        fd: socket.as_raw_fd(),
+        // Error queue readiness is always reported as POLLERR
+        events: 0,
+        revents: 0,
+    };
+    // SAFETY: `poll` is a single valid pollfd
+    if unsafe { libc::poll(&mut poll, 1, TX_STAMP_TIMEOUT_MS) } <= 0 {
+        return Ok(None);
+    }
+    let mut buffer = [0u8; 64];
+    let (_, _, stamp) = recvmsg_stamped(socket, &mut buffer, libc::MSG_ERRQUEUE)?;
+    Ok(stamp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loopback_datagrams_carry_send_and_receive_times() {
+        let receiver = StampedSocket::bind("127.0.0.1:0", None).unwrap();
+        let sender = StampedSocket::bind("127.0.0.1:0", None).unwrap();
+        // No interface, so no NIC timestamps
+        assert!(receiver.mode() >= StampMode::Software);
+        receiver.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+
+        let before_ns = clock::realtime_now_ns();
+        let (tx_ns, tx_mode) = sender.send_to(b"trigger", receiver.local_addr().unwrap()).unwrap();
+        let mut buffer = [0u8; 16];
+        let (len, from, rx_ns, rx_mode) = receiver.recv_from(&mut buffer).unwrap();
+        let after_ns = clock::realtime_now_ns();
+
+        assert_eq!(&buffer[..len], b"trigger");
+        assert_eq!(from, sender.local_addr().unwrap());
+        assert!(tx_mode >= StampMode::Software && rx_mode >= StampMode::Software);
+        assert!(before_ns <= tx_ns && tx_ns <= rx_ns && rx_ns <= after_ns, "{} {} {} {}", before_ns, tx_ns, rx_ns, after_ns);
+    }
+}
@@ -0,0 +1,242 @@
+// Sweep of a static frame timestamp offset over a recorded session.
+//
+// A camera pipeline with an unknown fixed delay (sensor readout, ISP, a
+// driver stamping buffers late) moves every frame timestamp by the same
+// amount. The sweep replays a session through the matcher once per offset of
+// a range, with the frame timestamps moved back by the offset (see
+// `Fixture::replay_shifted`), and records per offset the share of frames that
+// matched and the residual: shifted frame time minus matched trigger time.
+// The optimum is the offset with the most matches, ties broken by the
+// smallest median absolute residual, i.e. the delay that lines the frames up
+// with their triggers. A tolerance below the trigger interval makes the match
+// rate fall off away from the delay; with the default 500ms nearly every
+// offset matches and the residual decides. `analyze sweep` prints every
+// offset and charts match rate and residual against the offset.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::analyze::{self, Chart, Mark, Series};
+use crate::fixture::Fixture;
+use crate::stats::StatsSummary;
+use crate::units::{DisplayMs, Unit};
+
+/// Offsets swept by default (`--range`): -50ms to 50ms in 1ms steps.
+pub const DEFAULT_RANGE: SweepRange = SweepRange {
+    from_ms: -50.0,
+    to_ms: 50.0,
+    step_ms: 1.0,
+};
+
+/// Most offsets of one sweep.
+pub const MAX_STEPS: usize = 10_000;
+
+/// Offsets from `from_ms` to `to_ms` (inclusive) every `step_ms`, written `<from>:<to>:<step>`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SweepRange {
+    pub from_ms: f64,
+    pub to_ms: f64,
+    pub step_ms: f64,
+}
+
+impl SweepRange {
+    pub fn offsets_ms(&self) -> impl Iterator<Item = f64> {
+        // A step that lands on `to_ms` up to rounding still counts
+        let steps = ((self.to_ms - self.from_ms) / self.step_ms + 1e-9).floor() as usize;
+        let range = *self;
+        (0..=steps).map(move |step| range.from_ms + step as f64 * range.step_ms)
+    }
+}
+
+impl FromStr for SweepRange {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let values: Vec<f64> = text.split(':').map(|value| value.trim().parse::<f64>()).collect::<Result<_, _>>().unwrap_or_default();
+        let [from_ms, to_ms, step_ms] = values[..] else {
+            return Err(format!("invalid offset range '{}' (expected <from_ms>:<to_ms>:<step_ms>)", text));
+        };
+        if !(step_ms > 0.0 && from_ms <= to_ms) {
+            return Err(format!("offset range '{}' needs from <= to and a positive step", text));
+        }
+        if (to_ms - from_ms) / step_ms >= MAX_STEPS as f64 {
+            return Err(format!("offset range '{}' has more than {} steps", text, MAX_STEPS));
+        }
+        Ok(Self { from_ms, to_ms, step_ms })
+    }
+}
+
+impl fmt::Display for SweepRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.from_ms, self.to_ms, self.step_ms)
+    }
+}
+
+/// The replay at one offset.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OffsetPoint {
+    pub offset_ms: f64,
+    pub frames: usize,
+    pub matched: usize,
+    /// Shifted frame time minus matched trigger time.
+    pub residual: StatsSummary,
+    /// Median of the absolute residuals.
+    pub abs_residual_p50_ms: f64,
+}
+
+impl OffsetPoint {
+    pub fn match_rate(&self) -> f64 {
+        if self.frames == 0 {
+            return 0.0;
+        }
+        self.matched as f64 / self.frames as f64
+    }
+
+    /// One-line summary with the residuals in `unit`.
+    pub fn summary(&self, unit: Unit) -> String {
+        format!(
+            "offset {:+.3}ms: {}/{} matched ({:.1}%), residual p50={:.3} std={:.3} |p50|={:.3}",
+            self.offset_ms,
+            self.matched,
+            self.frames,
+            self.match_rate() * 100.0,
+            DisplayMs(self.residual.p50_ms, unit),
+            DisplayMs(self.residual.std_ms, unit),
+            DisplayMs(self.abs_residual_p50_ms, unit)
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OffsetSweep {
+    pub name: String,
+    /// In offset order.
+    pub points: Vec<OffsetPoint>,
+}
+
+impl OffsetSweep {
+    /// Replays `fixture` once per offset of `range`.
+    pub fn run(name: &str, fixture: &Fixture, range: &SweepRange) -> Self {
+        // Fixtures start at 0; moved later by the largest offset, no frame is shifted before the first trigger
+        let margin_ns = (range.to_ms.max(0.0) * 1e6).ceil() as u64;
+        let fixture = &Fixture {
+            triggers: fixture.triggers.iter().map(|&(id, hw_ts, pub_ts)| (id, hw_ts + margin_ns, pub_ts + margin_ns)).collect(),
+            frames: fixture.frames.iter().map(|&(frame_ns, id)| (frame_ns + margin_ns, id)).collect(),
+            ..fixture.clone()
+        };
+        let points = range
+            .offsets_ms()
+            .map(|offset_ms| {
+                let offset_ns = (offset_ms * 1e6).round() as i64;
+                let residuals: Vec<f64> = fixture
+                    .frames
+                    .iter()
+                    .zip(fixture.replay_shifted(offset_ns))
+                    .filter_map(|(&(frame_ns, _), matched)| matched.map(|trigger| (frame_ns as i64 - offset_ns - trigger.hw_timestamp_ns as i64) as f64 / 1e6))
+                    .collect();
+                let absolute: Vec<f64> = residuals.iter().map(|residual| residual.abs()).collect();
+                OffsetPoint {
+                    offset_ms,
+                    frames: fixture.frames.len(),
+                    matched: residuals.len(),
+                    residual: analyze::summarize(&residuals),
+                    abs_residual_p50_ms: analyze::summarize(&absolute).p50_ms,
+                }
+            })
+            .collect();
+        Self {
+            name: name.to_string(),
+            points,
+        }
+    }
+
+    /// The offset with the most matched frames and, among those, the smallest median absolute residual.
+    pub fn optimum(&self) -> Option<&OffsetPoint> {
+        self.points
+            .iter()
+            .filter(|point| point.matched > 0)
+            .min_by(|a, b| b.matched.cmp(&a.matched).then(a.abs_residual_p50_ms.total_cmp(&b.abs_residual_p50_ms)))
+    }
+}
+
+/// Match rate and median absolute residual of every sweep against the offset.
+pub fn sweep_chart(sweeps: &[OffsetSweep]) -> Chart {
+    let mut series = Vec::new();
+    for sweep in sweeps {
+        series.push(Series {
+            name: format!("{} matched %", sweep.name),
+            marks: sweep.points.iter().map(|point| Mark::Point { x: point.offset_ms, y: point.match_rate() * 100.0 }).collect(),
+        });
+        series.push(Series {
+            name: format!("{} |residual| ms", sweep.name),
+            marks: sweep
+                .points
+                .iter()
+                .filter(|point| point.matched > 0)
+                .map(|point| Mark::Point { x: point.offset_ms, y: point.abs_residual_p50_ms })
+                .collect(),
+        });
+    }
+    Chart {
+        title: "Match rate and residual against a static frame offset".to_string(),
+        x_label: "frame offset (ms)".to_string(),
+        y_label: "matched (%), median |residual| (ms)".to_string(),
+        series,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::burst::BurstPattern;
+    use crate::units::Unit;
+
+    const INTERVAL_NS: u64 = 33_333_333;
+
+    // 30fps triggers and frames 12ms late, +-0.3ms of jitter
+    fn delayed_session(tolerance_ms: f64) -> Fixture {
+        let triggers: Vec<(u64, u64, u64)> = (1..=60).map(|id| (id, id * INTERVAL_NS, id * INTERVAL_NS + 100_000)).collect();
+        let frames = triggers.iter().map(|&(id, hw_ts, _)| (hw_ts + 12_000_000 + (id % 3) * 300_000 - 300_000, Some(id))).collect();
+        Fixture {
+            tolerance_ms,
+            burst: BurstPattern::default(),
+            triggers,
+            frames,
+        }
+    }
+
+    #[test]
+    fn sweep_finds_the_pipeline_delay() {
+        let range: SweepRange = "0:20:0.5".parse().unwrap();
+        assert_eq!(range.offsets_ms().count(), 41);
+        let sweep = OffsetSweep::run("cam0", &delayed_session(5.0), &range);
+        let best = sweep.optimum().unwrap();
+        assert_eq!(best.offset_ms, 12.0);
+        assert_eq!((best.matched, best.frames), (60, 60));
+        assert_eq!(best.abs_residual_p50_ms, 0.3);
+
+        // Without the offset the frames are out of the 5ms tolerance
+        assert_eq!(sweep.points[0].matched, 0);
+        assert_eq!(sweep.points[0].match_rate(), 0.0);
+        assert!(sweep.points.iter().filter(|point| point.matched == 60).all(|point| (7.5..=16.5).contains(&point.offset_ms)));
+        assert!(best.summary(Unit::Ms).starts_with("offset +12.000ms: 60/60 matched (100.0%)"), "{}", best.summary(Unit::Ms));
+
+        let chart = sweep_chart(&[sweep]);
+        assert_eq!(chart.series.len(), 2);
+        assert!(chart.svg().contains("cam0 matched %"));
+    }
+
+    #[test]
+    fn offset_ranges_are_checked() {
+        assert_eq!(DEFAULT_RANGE.offsets_ms().count(), 101);
+        assert_eq!("-1:1:0.5".parse::<SweepRange>().unwrap().offsets_ms().collect::<Vec<_>>(), [-1.0, -0.5, 0.0, 0.5, 1.0]);
+        assert_eq!("-1:1".parse::<SweepRange>().unwrap_err(), "invalid offset range '-1:1' (expected <from_ms>:<to_ms>:<step_ms>)");
+        assert_eq!("1:-1:0.5".parse::<SweepRange>().unwrap_err(), "offset range '1:-1:0.5' needs from <= to and a positive step");
+        assert_eq!("0:100:0.001".parse::<SweepRange>().unwrap_err(), "offset range '0:100:0.001' has more than 10000 steps");
+
+        // The default tolerance matches at every offset, the residual decides
+        let sweep = OffsetSweep::run("cam0", &delayed_session(500.0), &"0:20:1".parse().unwrap());
+        assert!(sweep.points.iter().all(|point| point.matched == 60));
+        assert_eq!(sweep.optimum().unwrap().offset_ms, 12.0);
+    }
+}
@@ -0,0 +1,627 @@
+// Matcher stages chained into a configurable pipeline.
+//
+// The association of a frame with the pending triggers runs through a chain
+// of stages, selected and ordered by name (`--match-stages`):
+//
+//   dedupe     drops repeated trigger ids and frames carrying the timestamp of
+//              the previous frame (sources that deliver a buffer twice)
+//   drift      shifts the frame time by how far the frame-minus-trigger offset
+//              has walked since the first matches, so a drifting source clock
+//              keeps the matcher's preference for past triggers
+//   predict    shifts the frame time back by the latency a Kalman filter
+//              predicts for it (see `latency_filter`), so triggers are scored
+//              by their distance from the expected trigger time
+//   associate  the matcher of `matching` with the burst order of `burst`
+//   audit      flags matches whose trigger id does not increase or whose score
+//              exceeds half the tolerance
+//
+// `associate` alone is the association the camera processes always made and
+// stays the default. A frame leaves the chain at the stage that drops it
+// (associate drops the frames it cannot match). Every stage counts the frames
+// it saw, passed and dropped, what it modified (dedupe: duplicate triggers
+// removed, drift and predict: corrected frame times, associate: older triggers
+// cleaned with a match, audit: flagged matches) and the time it took per
+// frame. Drift and predict both correct the frame time, only one of them can
+// be in the chain.
+//
+// The pipeline learns from its matches: the frame-minus-trigger offsets of the
+// last `DRIFT_WINDOW` matches give the latency estimate and the drift model,
+// and with `predict` every match feeds the latency filter.
+// With an adaptive tolerance, a full window narrows the match tolerance from
+// the configured one to the largest recent offset plus its spread (at least
+// `MIN_ADAPTIVE_MARGIN_MS`), so a stray trigger far from the learned latency
+// no longer matches. `RELEARN_AFTER` unmatched frames in a row (the latency
+// changed, or a restored model no longer fits) discard the learned state. The
+// state can be saved and restored with `snapshot` and `restore`, so a
+// restarted process starts with its learned tolerance (see `matcher_state`);
+// the latency filter is not saved, it converges within a few frames.
+// While a clock is disturbed (see `clock_guard`) or the trigger clock is
+// downgraded (see `clock_quality`), learning is frozen: matches neither feed
+// the estimates nor unmatched frames count towards a relearn. A tolerance
+// factor widens the tolerance, learned or configured, under the `widen`
+// downgrade policy.
+//
+// The configured tolerance, the penalty on triggers after the frame and the
+// length of the pending trigger queue are set per process (`MatchConfig`):
+// at 120fps the default 500ms tolerance spans 60 frames.
+//
+// With a priority pattern (see `priority`), a full queue drops its oldest
+// normal trigger before any priority trigger, and a frame that matches no
+// trigger within the current tolerance may still match a priority trigger
+// within the configured one.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Instant;
+
+use crate::burst::BurstPattern;
+use crate::cli::Args;
+use crate::latency_filter::{LatencyEstimate, LatencyFilter};
+use crate::matching;
+use crate::priority::PriorityPattern;
+use crate::stats::LatencyStats;
+use crate::CameraTrigger;
+
+/// Pending triggers kept by default (`--max-pending-triggers`).
+pub const MAX_PENDING: usize = 100;
+
+/// Matches whose frame-minus-trigger offset makes up the latency and drift estimates.
+pub const DRIFT_WINDOW: usize = 32;
+
+/// Smallest margin of a learned tolerance around the recent offsets.
+pub const MIN_ADAPTIVE_MARGIN_MS: f64 = 2.0;
+
+/// Unmatched frames in a row that discard the learned state.
+pub const RELEARN_AFTER: u32 = 8;
+
+/// Largest configured tolerance, ms; beyond it every trigger of the last seconds competes for a frame.
+pub const MAX_TOLERANCE_MS: f64 = 10_000.0;
+
+/// Largest configured future penalty.
+pub const MAX_FUTURE_PENALTY: f64 = 100.0;
+
+/// Largest configured pending trigger queue.
+pub const MAX_PENDING_LIMIT: usize = 100_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Dedupe,
+    Drift,
+    Predict,
+    Associate,
+    Audit,
+}
+
+impl Stage {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Stage::Dedupe => "dedupe",
+            Stage::Drift => "drift",
+            Stage::Predict => "predict",
+            Stage::Associate => "associate",
+            Stage::Audit => "audit",
+        }
+    }
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Stage {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "dedupe" => Ok(Stage::Dedupe),
+            "drift" => Ok(Stage::Drift),
+            "predict" => Ok(Stage::Predict),
+            "associate" => Ok(Stage::Associate),
+            "audit" => Ok(Stage::Audit),
+            other => Err(format!("unknown matcher stage '{}' (expected dedupe, drift, predict, associate or audit)", other)),
+        }
+    }
+}
+
+/// Parses a comma separated stage list, e.g. `dedupe,drift,associate,audit`.
+///
+/// `associate` is required; dedupe, drift and predict prepare its input, so they go before it, audit after it.
+/// Drift and predict exclude each other.
+pub fn parse_stages(list: &str) -> Result<Vec<Stage>, String> {
+    let stages = list.split(',').map(str::parse).collect::<Result<Vec<Stage>, String>>()?;
+    let Some(associate) = stages.iter().position(|&stage| stage == Stage::Associate) else {
+        return Err(format!("matcher stages '{}' lack associate", list));
+    };
+    for (index, stage) in stages.iter().enumerate() {
+        if stages[..index].contains(stage) {
+            return Err(format!("matcher stage {} listed twice", stage));
+        }
+        let before_associate = matches!(stage, Stage::Dedupe | Stage::Drift | Stage::Predict);
+        if (before_associate && index > associate) || (*stage == Stage::Audit && index < associate) {
+            return Err(format!("matcher stage {} must come {} associate", stage, if before_associate { "before" } else { "after" }));
+        }
+    }
+    if stages.contains(&Stage::Drift) && stages.contains(&Stage::Predict) {
+        return Err("matcher stages drift and predict both correct the frame time, use one of them".to_string());
+    }
+    Ok(stages)
+}
+
+/// Counters and timing of one stage.
+#[derive(Debug, Clone, Default)]
+pub struct StageStats {
+    pub frames: u64,
+    pub passed: u64,
+    pub dropped: u64,
+    /// Stage specific, see the module comment.
+    pub modified: u64,
+    /// Time per frame, ms.
+    pub time: LatencyStats,
+}
+
+impl StageStats {
+    pub fn summary(&self) -> String {
+        format!(
+            "{} in, {} out, {} dropped, {} modified, p50 {:.1}us, max {:.1}us",
+            self.frames,
+            self.passed,
+            self.dropped,
+            self.modified,
+            self.time.percentile(50.0) * 1000.0,
+            self.time.max() * 1000.0
+        )
+    }
+}
+
+/// Outcome of `MatchPipeline::on_trigger`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Queued {
+    Added,
+    /// Dropped by dedupe, the id is already pending.
+    Duplicate,
+    /// Added; the oldest pending trigger was dropped for it (the oldest normal one with a priority pattern).
+    Evicted(CameraTrigger),
+}
+
+/// Outcome of `MatchPipeline::on_frame`.
+#[derive(Debug, Clone)]
+pub struct FrameMatch<T> {
+    /// Frame time the association used, after drift correction or latency prediction.
+    pub frame_ns: u64,
+    /// Trigger, the payload it was queued with and the match score in ms.
+    pub matched: Option<(CameraTrigger, T, f64)>,
+    /// Pending triggers older than the match, which left the queue with it.
+    pub cleaned: Vec<CameraTrigger>,
+    /// Stage the frame left the chain at, `None` if it passed all of them.
+    pub dropped_by: Option<Stage>,
+    /// Findings of the audit stage.
+    pub findings: Vec<String>,
+    /// The learned state was discarded after this frame, see the module comment.
+    pub relearned: bool,
+}
+
+/// Learned state of a pipeline, kept across restarts.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MatcherSnapshot {
+    /// Frame-minus-trigger offsets of the recent matches, oldest first.
+    pub offsets_ns: Vec<i64>,
+    /// Offset the drift is measured against.
+    pub drift_baseline_ns: Option<i64>,
+}
+
+impl MatcherSnapshot {
+    /// Median of the offsets, ms.
+    pub fn latency_ms(&self) -> Option<f64> {
+        let mut offsets = self.offsets_ns.clone();
+        offsets.sort_unstable();
+        offsets.get(offsets.len() / 2).map(|&offset| offset as f64 / 1e6)
+    }
+}
+
+/// Matching parameters of a camera process.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MatchConfig {
+    /// Triggers further than this from a frame never match it.
+    pub tolerance_ms: f64,
+    /// Factor on the distance of triggers after the frame.
+    pub future_penalty: f64,
+    /// Triggers waiting for their frame; the oldest is dropped beyond.
+    pub max_pending: usize,
+}
+
+impl Default for MatchConfig {
+    fn default() -> Self {
+        Self {
+            tolerance_ms: matching::DEFAULT_TOLERANCE_MS,
+            future_penalty: matching::DEFAULT_FUTURE_PENALTY,
+            max_pending: MAX_PENDING,
+        }
+    }
+}
+
+impl MatchConfig {
+    /// `--match-tolerance-ms` (`--tolerance-ms` before it), `--future-penalty` and `--max-pending-triggers`; fails for
+    /// values that do not parse or are out of range.
+    pub fn from_args(args: &Args) -> Result<Self, String> {
+        let defaults = Self::default();
+        let tolerance_name = if args.value("match-tolerance-ms").is_some() { "match-tolerance-ms" } else { "tolerance-ms" };
+        Ok(Self {
+            tolerance_ms: checked_value(args, tolerance_name, defaults.tolerance_ms, |ms| *ms > 0.0 && *ms <= MAX_TOLERANCE_MS, &format!("above 0 and at most {}", MAX_TOLERANCE_MS))?,
+            future_penalty: checked_value(args, "future-penalty", defaults.future_penalty, |penalty| (1.0..=MAX_FUTURE_PENALTY).contains(penalty), &format!("1 to {}", MAX_FUTURE_PENALTY))?,
+            max_pending: checked_value(args, "max-pending-triggers", defaults.max_pending, |max| (1..=MAX_PENDING_LIMIT).contains(max), &format!("1 to {}", MAX_PENDING_LIMIT))?,
+        })
+    }
+}
+
+// `--<name>` if given and `valid`, `default` without it
+fn checked_value<T: FromStr + fmt::Display>(args: &Args, name: &str, default: T, valid: impl Fn(&T) -> bool, expected: &str) -> Result<T, String> {
+    let Some(value) = args.value(name) else {
+        return Ok(default);
+    };
+    match value.parse::<T>() {
+        Ok(parsed) if valid(&parsed) => Ok(parsed),
+        Ok(parsed) => Err(format!("--{} {} out of range (expected {})", name, parsed, expected)),
+        Err(_) => Err(format!("invalid --{} '{}'", name, value)),
+    }
+}
+
+/// Pending triggers of one frame stream and the stage chain its frames run through.
+///
+/// `T` is carried along with each trigger, e.g. its signature header.
+#[derive(Debug, Clone)]
+pub struct MatchPipeline<T> {
+    stages: Vec<(Stage, StageStats)>,
+    pending: VecDeque<(CameraTrigger, T)>,
+    tolerance_ms: f64,
+    future_penalty: f64,
+    burst: BurstPattern,
+    last_matched_id: Option<u64>,
+    last_frame_ns: Option<u64>,
+    offsets: VecDeque<i64>,
+    drift_baseline_ns: Option<i64>,
+    latency_filter: LatencyFilter,
+    last_audited_id: Option<u64>,
+    adaptive: bool,
+    unmatched_run: u32,
+    frozen: bool,
+    tolerance_factor: f64,
+    max_pending: usize,
+    priority: Option<PriorityPattern>,
+}
+
+impl<T> Default for MatchPipeline<T> {
+    fn default() -> Self {
+        Self::new(matching::DEFAULT_TOLERANCE_MS, BurstPattern::default())
+    }
+}
+
+impl<T> MatchPipeline<T> {
+    /// The plain association (`associate` only).
+    pub fn new(tolerance_ms: f64, burst: BurstPattern) -> Self {
+        Self::with_stages(&[Stage::Associate], tolerance_ms, burst)
+    }
+
+    /// `stages` as returned by `parse_stages`.
+    pub fn with_stages(stages: &[Stage], tolerance_ms: f64, burst: BurstPattern) -> Self {
+        Self {
+            stages: stages.iter().map(|&stage| (stage, StageStats::default())).collect(),
+            pending: VecDeque::new(),
+            tolerance_ms,
+            future_penalty: matching::DEFAULT_FUTURE_PENALTY,
+            burst,
+            last_matched_id: None,
+            last_frame_ns: None,
+            offsets: VecDeque::new(),
+            drift_baseline_ns: None,
+            latency_filter: LatencyFilter::default(),
+            last_audited_id: None,
+            adaptive: false,
+            unmatched_run: 0,
+            frozen: false,
+            tolerance_factor: 1.0,
+            max_pending: MAX_PENDING,
+            priority: None,
+        }
+    }
+
+    /// Applies the configured tolerance, future penalty and pending trigger limit.
+    pub fn configure(&mut self, config: &MatchConfig) {
+        self.tolerance_ms = config.tolerance_ms;
+        self.future_penalty = config.future_penalty;
+        self.max_pending = config.max_pending.max(1);
+    }
+
+    /// Narrows the tolerance to the learned latency once enough frames matched.
+    pub fn set_adaptive_tolerance(&mut self, adaptive: bool) {
+        self.adaptive = adaptive;
+    }
+
+    /// Factor on the distance of triggers after the frame (`matching::DEFAULT_FUTURE_PENALTY`); 1 scores both sides alike.
+    pub fn set_future_penalty(&mut self, future_penalty: f64) {
+        self.future_penalty = future_penalty;
+    }
+
+    pub fn future_penalty(&self) -> f64 {
+        self.future_penalty
+    }
+
+    /// Triggers shed last and matched within the configured tolerance, see the module comment.
+    pub fn set_priority(&mut self, priority: Option<PriorityPattern>) {
+        self.priority = priority;
+    }
+
+    pub fn priority(&self) -> Option<PriorityPattern> {
+        self.priority
+    }
+
+    /// Keeps the learned latency and drift model as they are, e.g. while a clock steps.
+    pub fn set_learning_frozen(&mut self, frozen: bool) {
+        self.frozen = frozen;
+    }
+
+    pub fn is_learning_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    /// Tolerance the next frame is matched with: the learned one, if adaptive and learned, else the configured one,
+    /// times the tolerance factor.
+    pub fn tolerance_ms(&self) -> f64 {
+        self.learned_tolerance_ms().unwrap_or(self.tolerance_ms) * self.tolerance_factor
+    }
+
+    /// Widens (or narrows) the tolerance by `factor`, e.g. while the trigger clock is downgraded; 1 by default.
+    pub fn set_tolerance_factor(&mut self, factor: f64) {
+        self.tolerance_factor = factor;
+    }
+
+    /// Changes the configured tolerance, e.g. on a config reload; a learned one stays capped by it.
+    pub fn set_tolerance_ms(&mut self, tolerance_ms: f64) {
+        self.tolerance_ms = tolerance_ms;
+    }
+
+    fn learned_tolerance_ms(&self) -> Option<f64> {
+        if !self.adaptive || self.offsets.len() < DRIFT_WINDOW {
+            return None;
+        }
+        let min = *self.offsets.iter().min()?;
+        let max = *self.offsets.iter().max()?;
+        let largest_ms = min.unsigned_abs().max(max.unsigned_abs()) as f64 / 1e6;
+        let spread_ms = (max - min) as f64 / 1e6;
+        Some((largest_ms + spread_ms.max(MIN_ADAPTIVE_MARGIN_MS)).min(self.tolerance_ms))
+    }
+
+    /// The learned latency and drift model.
+    pub fn snapshot(&self) -> MatcherSnapshot {
+        MatcherSnapshot {
+            offsets_ns: self.offsets.iter().copied().collect(),
+            drift_baseline_ns: self.drift_baseline_ns,
+        }
+    }
+
+    /// Continues from a model saved by `snapshot`, e.g. by the previous run.
+    pub fn restore(&mut self, snapshot: &MatcherSnapshot) {
+        let skip = snapshot.offsets_ns.len().saturating_sub(DRIFT_WINDOW);
+        self.offsets = snapshot.offsets_ns[skip..].iter().copied().collect();
+        self.drift_baseline_ns = snapshot.drift_baseline_ns;
+        self.unmatched_run = 0;
+    }
+
+    /// The latency filter's estimate; `None` without the `predict` stage or before it converged.
+    pub fn latency_estimate(&self) -> Option<LatencyEstimate> {
+        self.latency_filter.estimate()
+    }
+
+    /// Matches the latency filter took for outliers, and its restarts after a latency jump.
+    pub fn latency_outliers(&self) -> (u64, u64) {
+        (self.latency_filter.outliers, self.latency_filter.resets)
+    }
+
+    pub fn stages(&self) -> Vec<Stage> {
+        self.stages.iter().map(|(stage, _)| *stage).collect()
+    }
+
+    pub fn stats(&self) -> &[(Stage, StageStats)] {
+        &self.stages
+    }
+
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Queues a received trigger; the oldest (normal) one is dropped beyond the configured limit (`MAX_PENDING` by default).
+    pub fn on_trigger(&mut self, trigger: CameraTrigger, payload: T) -> Queued {
+        if let Some((_, stats)) = self.stages.iter_mut().find(|(stage, _)| *stage == Stage::Dedupe) {
+            if self.pending.iter().any(|(pending, _)| pending.frame_id == trigger.frame_id) {
+                stats.modified += 1;
+                return Queued::Duplicate;
+            }
+        }
+        self.pending.push_back((trigger, payload));
+        if self.pending.len() > self.max_pending {
+            // Priority triggers go only when nothing else is left
+            let index = self.priority.and_then(|pattern| self.pending.iter().position(|(trigger, _)| !pattern.is_priority(trigger.frame_id))).unwrap_or(0);
+            if let Some((evicted, _)) = self.pending.remove(index) {
+                return Queued::Evicted(evicted);
+            }
+        }
+        Queued::Added
+    }
+
+    /// Runs a frame taken at `frame_ns` (realtime) through the stages.
+    pub fn on_frame(&mut self, frame_ns: u64) -> FrameMatch<T> {
+        let mut frame = FrameMatch {
+            frame_ns,
+            matched: None,
+            cleaned: Vec::new(),
+            dropped_by: None,
+            findings: Vec::new(),
+            relearned: false,
+        };
+        for index in 0..self.stages.len() {
+            let stage = self.stages[index].0;
+            let started = Instant::now();
+            let (passed, modified) = match stage {
+                Stage::Dedupe => (self.last_frame_ns != Some(frame_ns), false),
+                Stage::Drift => (true, self.correct_drift(&mut frame)),
+                Stage::Predict => (true, self.predict_latency(&mut frame)),
+                Stage::Associate => (self.associate(&mut frame), !frame.cleaned.is_empty()),
+                Stage::Audit => (true, self.audit(&mut frame)),
+            };
+            let stats = &mut self.stages[index].1;
+            stats.time.add(started.elapsed().as_nanos() as f64 / 1e6);
+            stats.frames += 1;
+            if modified {
+                stats.modified += match stage {
+                    Stage::Associate => frame.cleaned.len() as u64,
+                    _ => 1,
+                };
+            }
+            if !passed {
+                stats.dropped += 1;
+                frame.dropped_by = Some(stage);
+                break;
+            }
+            stats.passed += 1;
+        }
+        self.last_frame_ns = Some(frame_ns);
+
+        // The latency and drift estimates follow the uncorrected frame times
+        if self.frozen {
+            return frame;
+        }
+        if let Some((CameraTrigger { hw_timestamp_ns: hw_ts, .. }, _, _)) = &frame.matched {
+            self.unmatched_run = 0;
+            self.offsets.push_back(frame_ns as i64 - *hw_ts as i64);
+            if self.offsets.len() > DRIFT_WINDOW {
+                self.offsets.pop_front();
+            }
+            if self.drift_baseline_ns.is_none() && self.offsets.len() == DRIFT_WINDOW {
+                self.drift_baseline_ns = self.median_offset();
+            }
+            if self.stages.iter().any(|(stage, _)| *stage == Stage::Predict) {
+                self.latency_filter.update(*hw_ts, (frame_ns as i64 - *hw_ts as i64) as f64 / 1e6);
+            }
+        } else if frame.dropped_by == Some(Stage::Associate) {
+            self.unmatched_run += 1;
+            // A latency filter that predicts past the frames' triggers learns nothing new either
+            if self.unmatched_run >= RELEARN_AFTER && (self.learned_tolerance_ms().is_some() || self.latency_filter.is_converged()) {
+                self.offsets.clear();
+                self.drift_baseline_ns = None;
+                self.latency_filter.reset();
+                self.unmatched_run = 0;
+                frame.relearned = true;
+            }
+        }
+        frame
+    }
+
+    fn median_offset(&self) -> Option<i64> {
+        let mut offsets: Vec<i64> = self.offsets.iter().copied().collect();
+        offsets.sort_unstable();
+        offsets.get(offsets.len() / 2).copied()
+    }
+
+    fn correct_drift(&self, frame: &mut FrameMatch<T>) -> bool {
+        let (Some(baseline), Some(median)) = (self.drift_baseline_ns, self.median_offset()) else {
+            return false;
+        };
+        let drift = median - baseline;
+        frame.frame_ns = (frame.frame_ns as i64 - drift).max(0) as u64;
+        drift != 0
+    }
+
+    // The frame's trigger is expected one predicted latency before it
+    fn predict_latency(&self, frame: &mut FrameMatch<T>) -> bool {
+        let Some(latency_ms) = self.latency_filter.predict(frame.frame_ns) else {
+            return false;
+        };
+        frame.frame_ns = (frame.frame_ns as i64 - (latency_ms * 1e6) as i64).max(0) as u64;
+        true
+    }
+
+    fn associate(&mut self, frame: &mut FrameMatch<T>) -> bool {
+        let frame_ns = frame.frame_ns;
+        let tolerance_ms = self.tolerance_ms();
+        let mut best = matching::best_match(self.pending.iter().map(|(trigger, _)| trigger.hw_timestamp_ns), frame_ns, tolerance_ms, self.future_penalty);
+        // Within a burst, the next pulse wins over a nearer one
+        if let Some(next_id) = self.last_matched_id.and_then(|id| self.burst.next_in_burst(id)) {
+            let tolerance_ns = (tolerance_ms * 1e6) as u64;
+            if let Some(next) = self.pending.iter().position(|(trigger, _)| trigger.frame_id == next_id && trigger.hw_timestamp_ns.abs_diff(frame_ns) < tolerance_ns) {
+                best = Some((next, self.pending[next].0.hw_timestamp_ns.abs_diff(frame_ns) as f64 / 1e6));
+            }
+        }
+        // A frame late beyond a narrowed tolerance still takes a priority trigger within the configured one
+        if let (None, Some(pattern)) = (best, self.priority) {
+            let candidates: Vec<usize> = (0..self.pending.len()).filter(|&index| pattern.is_priority(self.pending[index].0.frame_id)).collect();
+            let configured_ms = self.tolerance_ms * self.tolerance_factor;
+            best = matching::best_match(candidates.iter().map(|&index| self.pending[index].0.hw_timestamp_ns), frame_ns, configured_ms, self.future_penalty)
+                .map(|(candidate, score_ms)| (candidates[candidate], score_ms));
+        }
+        let Some((index, score_ms)) = best else {
+            return false;
+        };
+        // The matched trigger and all older ones leave the queue
+        frame.cleaned = self.pending.drain(..index).map(|(trigger, _)| trigger).collect();
+        if let Some((trigger, payload)) = self.pending.pop_front() {
+            self.last_matched_id = Some(trigger.frame_id);
+            frame.matched = Some((trigger, payload, score_ms));
+        }
+        true
+    }
+
+    fn audit(&mut self, frame: &mut FrameMatch<T>) -> bool {
+        let Some((CameraTrigger { frame_id: trigger_id, .. }, _, score_ms)) = &frame.matched else {
+            return false;
+        };
+        if let Some(last) = self.last_audited_id.filter(|last| trigger_id <= last) {
+            frame.findings.push(format!("trigger id {} matched after {}", trigger_id, last));
+        }
+        let tolerance_ms = self.tolerance_ms();
+        if *score_ms > tolerance_ms / 2.0 {
+            frame.findings.push(format!("score {:.1}ms above half the {:.1}ms tolerance", score_ms, tolerance_ms));
+        }
+        self.last_audited_id = Some(*trigger_id);
+        !frame.findings.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: u64 = 1_000_000;
+
+    #[test]
+    fn stage_lists_are_checked_for_order_and_conflicts() {
+        assert_eq!(parse_stages("dedupe, drift,associate,audit"), Ok(vec![Stage::Dedupe, Stage::Drift, Stage::Associate, Stage::Audit]));
+        assert!(parse_stages("dedupe").unwrap_err().contains("lack associate"));
+        assert!(parse_stages("associate,dedupe").unwrap_err().contains("dedupe must come before associate"));
+        assert!(parse_stages("audit,associate").unwrap_err().contains("audit must come after associate"));
+        assert!(parse_stages("associate,associate").unwrap_err().contains("listed twice"));
+        assert!(parse_stages("drift,predict,associate").unwrap_err().contains("use one of them"));
+        assert!(parse_stages("associate,sort").unwrap_err().contains("unknown matcher stage 'sort'"));
+    }
+
+    #[test]
+    fn frames_leave_the_chain_at_the_dropping_stage() {
+        let stages = parse_stages("dedupe,associate,audit").unwrap();
+        let mut pipeline = MatchPipeline::with_stages(&stages, 50.0, BurstPattern::default());
+        assert_eq!(pipeline.on_trigger(CameraTrigger::new(1, 100 * MS, 100 * MS), 'a'), Queued::Added);
+        assert_eq!(pipeline.on_trigger(CameraTrigger::new(1, 100 * MS, 100 * MS), 'a'), Queued::Duplicate);
+        pipeline.on_trigger(CameraTrigger::new(2, 133 * MS, 133 * MS), 'b');
+
+        let frame = pipeline.on_frame(140 * MS);
+        let (trigger, payload, score_ms) = frame.matched.unwrap();
+        assert_eq!((trigger.frame_id, payload, score_ms), (2, 'b', 7.0));
+        assert_eq!(frame.cleaned.iter().map(|trigger| trigger.frame_id).collect::<Vec<_>>(), [1]);
+        // The same buffer delivered twice
+        assert_eq!(pipeline.on_frame(140 * MS).dropped_by, Some(Stage::Dedupe));
+        assert_eq!(pipeline.on_frame(400 * MS).dropped_by, Some(Stage::Associate));
+
+        let counts: Vec<(Stage, u64, u64, u64, u64)> = pipeline.stats().iter().map(|(stage, stats)| (*stage, stats.frames, stats.passed, stats.dropped, stats.modified)).collect();
+        assert_eq!(counts, [(Stage::Dedupe, 3, 2, 1, 1), (Stage::Associate, 2, 1, 1, 1), (Stage::Audit, 1, 1, 0, 0)]);
+    }
+}
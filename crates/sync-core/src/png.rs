@@ -0,0 +1,137 @@
+// Minimal PNG encoder for dataset exports.
+//
+// Research pipelines expect PNG images. This writes 8-bit RGB PNGs with
+// uncompressed (stored) deflate blocks: larger files than a real compressor
+// produces, but no dependency and a few lines of code. Recompress with
+// `optipng` or similar if disk space matters.
+//
+// Metadata goes into an `eXIf` chunk (EXIF) and an `iTXt` chunk with keyword
+// `XML:com.adobe.xmp` (XMP), both before the image data as readers expect.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+/// Largest stored deflate block.
+const MAX_STORED_BLOCK: usize = 65_535;
+
+/// Encodes packed RGB pixels as a PNG file, with optional EXIF (TIFF structure) and XMP packet.
+pub fn encode_rgb(width: u32, height: u32, rgb: &[u8], exif: Option<&[u8]>, xmp: Option<&str>) -> io::Result<Vec<u8>> {
+    let stride = width as usize * 3;
+    if rgb.len() != stride * height as usize {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "pixel buffer does not match the image size"));
+    }
+
+    // Every scanline starts with its filter type (0 = none)
+    let mut raw = Vec::with_capacity((stride + 1) * height as usize);
+    for row in rgb.chunks_exact(stride.max(1)) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    // zlib stream of stored blocks
+    let mut zlib = Vec::with_capacity(raw.len() + raw.len() / MAX_STORED_BLOCK * 5 + 16);
+    zlib.extend_from_slice(&[0x78, 0x01]);
+    let mut blocks = raw.chunks(MAX_STORED_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        zlib.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let len = block.len() as u16;
+        zlib.push(last as u8);
+        zlib.extend_from_slice(&len.to_le_bytes());
+        zlib.extend_from_slice(&(!len).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // 8 bit, truecolor, deflate, adaptive filtering, no interlace
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    let mut png = Vec::with_capacity(zlib.len() + 64);
+    png.extend_from_slice(&SIGNATURE);
+    write_chunk(&mut png, b"IHDR", &header);
+    if let Some(exif) = exif {
+        write_chunk(&mut png, b"eXIf", exif);
+    }
+    if let Some(xmp) = xmp {
+        // Keyword, uncompressed, no language tag, no translated keyword
+        let mut text = b"XML:com.adobe.xmp\0\0\0\0\0".to_vec();
+        text.extend_from_slice(xmp.as_bytes());
+        write_chunk(&mut png, b"iTXt", &text);
+    }
+    write_chunk(&mut png, b"IDAT", &zlib);
+    write_chunk(&mut png, b"IEND", &[]);
+    Ok(png)
+}
+
+pub fn write_rgb(path: &Path, width: u32, height: u32, rgb: &[u8], exif: Option<&[u8]>, xmp: Option<&str>) -> io::Result<()> {
+    fs::write(path, encode_rgb(width, height, rgb, exif, xmp)?)
+}
+
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in bytes.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65_521;
+        b %= 65_521;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Chunk types in file order
+    fn chunks(png: &[u8]) -> Vec<String> {
+        let mut kinds = Vec::new();
+        let mut at = SIGNATURE.len();
+        while at < png.len() {
+            let len = u32::from_be_bytes(png[at..at + 4].try_into().unwrap()) as usize;
+            kinds.push(String::from_utf8_lossy(&png[at + 4..at + 8]).into_owned());
+            at += 12 + len;
+        }
+        kinds
+    }
+
+    #[test]
+    fn images_are_stored_with_metadata_before_the_pixels() {
+        let png = encode_rgb(2, 1, &[255, 0, 0, 0, 0, 255], Some(b"MM\0*"), Some("<x:xmpmeta/>")).unwrap();
+        assert_eq!(chunks(&png), ["IHDR", "eXIf", "iTXt", "IDAT", "IEND"]);
+        assert!(png.ends_with(&[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]));
+        // One stored block with both scanline bytes, then the Adler-32 of them
+        let idat = png.windows(4).position(|window| window == b"IDAT").unwrap() + 4;
+        assert_eq!(&png[idat..idat + 7], &[0x78, 0x01, 1, 7, 0, !7, 0xff]);
+        assert_eq!(&png[idat + 14..idat + 18], &adler32(&[0, 255, 0, 0, 0, 0, 255]).to_be_bytes());
+        assert!(encode_rgb(2, 2, &[0; 6], None, None).is_err());
+    }
+}
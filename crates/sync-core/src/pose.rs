@@ -0,0 +1,107 @@
+// Pan-tilt unit poses, interpolated at trigger timestamps.
+//
+// A PTZ/servo controller publishes timestamped pose samples (trigger clock
+// domain) on the `Motion/Pose` service. Camera processes keep the recent
+// samples in a `PoseTrack` and tag every matched frame with the pose
+// interpolated at its trigger `hw_ts`, i.e. at exposure time rather than at
+// the (later) frame arrival.
+
+#[cfg(feature = "iceoryx2")]
+use iceoryx2::prelude::ZeroCopySend;
+use std::collections::VecDeque;
+use std::fmt;
+
+/// Pose samples kept for interpolation, several seconds at servo rates.
+const TRACK_CAPACITY: usize = 1024;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "iceoryx2", derive(ZeroCopySend))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PoseSample {
+    /// Trigger clock domain, ns.
+    pub timestamp_ns: u64,
+    pub pan_deg: f64,
+    pub tilt_deg: f64,
+    /// Zoom factor, 1.0 for fixed lenses.
+    pub zoom: f64,
+}
+
+impl fmt::Display for PoseSample {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "pan={:.3}deg, tilt={:.3}deg, zoom={:.2}", self.pan_deg, self.tilt_deg, self.zoom)
+    }
+}
+
+impl PoseSample {
+    /// Metadata pairs recorded with a matched frame.
+    pub fn metadata(&self) -> Vec<(String, String)> {
+        vec![
+            ("pan_deg".to_string(), format!("{:.3}", self.pan_deg)),
+            ("tilt_deg".to_string(), format!("{:.3}", self.tilt_deg)),
+            ("zoom".to_string(), format!("{:.2}", self.zoom)),
+        ]
+    }
+}
+
+/// Recent pose samples in timestamp order.
+#[derive(Debug, Clone, Default)]
+pub struct PoseTrack {
+    samples: VecDeque<PoseSample>,
+}
+
+impl PoseTrack {
+    /// Adds a sample; samples older than the latest one are dropped.
+    pub fn push(&mut self, sample: PoseSample) {
+        if self.samples.back().is_some_and(|last| sample.timestamp_ns < last.timestamp_ns) {
+            return;
+        }
+        if self.samples.len() == TRACK_CAPACITY {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    /// Pose at `timestamp_ns`, linearly interpolated between the surrounding
+    /// samples. `None` outside the covered time range.
+    pub fn at(&self, timestamp_ns: u64) -> Option<PoseSample> {
+        let after = self.samples.partition_point(|s| s.timestamp_ns < timestamp_ns);
+        let next = *self.samples.get(after)?;
+        if next.timestamp_ns == timestamp_ns {
+            return Some(next);
+        }
+        let prev = *self.samples.get(after.checked_sub(1)?)?;
+        let t = (timestamp_ns - prev.timestamp_ns) as f64 / (next.timestamp_ns - prev.timestamp_ns) as f64;
+        let lerp = |a: f64, b: f64| a + (b - a) * t;
+        Some(PoseSample {
+            timestamp_ns,
+            pan_deg: lerp(prev.pan_deg, next.pan_deg),
+            tilt_deg: lerp(prev.tilt_deg, next.tilt_deg),
+            zoom: lerp(prev.zoom, next.zoom),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(timestamp_ns: u64, pan_deg: f64, tilt_deg: f64) -> PoseSample {
+        PoseSample { timestamp_ns, pan_deg, tilt_deg, zoom: 1.0 }
+    }
+
+    #[test]
+    fn poses_are_interpolated_at_the_trigger_time() {
+        let mut track = PoseTrack::default();
+        track.push(sample(1_000, 10.0, -5.0));
+        track.push(sample(2_000, 20.0, 5.0));
+        // Out of order samples are dropped
+        track.push(sample(1_500, 90.0, 90.0));
+
+        assert_eq!(track.at(1_250), Some(sample(1_250, 12.5, -2.5)));
+        assert_eq!(track.at(2_000), Some(sample(2_000, 20.0, 5.0)));
+        assert_eq!(track.at(999), None);
+        assert_eq!(track.at(2_001), None);
+        assert_eq!(track.at(1_500).unwrap().to_string(), "pan=15.000deg, tilt=0.000deg, zoom=1.00");
+    }
+}
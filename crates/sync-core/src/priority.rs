@@ -0,0 +1,131 @@
+// Priority tagging of triggers.
+//
+// Some triggers matter more than the rest: the keyframe of a burst, or the
+// frames a reconstruction uses as keyframes. With `--priority-every N`, the
+// first pulse of every N-th burst is high priority (every N-th trigger without
+// bursts, every burst's first pulse with N = 1). Like the burst position, the
+// priority follows from the trigger id, so the publisher and the consumers
+// started with the same `--priority-every` and `--burst-size` agree on it
+// without a field on the wire.
+//
+// Under overload the consumers shed normal triggers first:
+//
+//   - a full pending trigger queue drops its oldest normal trigger, a
+//     priority trigger only when no normal one is left (see `pipeline`)
+//   - a frame no trigger matches within the learned tolerance is matched
+//     against the priority triggers within the configured tolerance, so a
+//     priority frame late from a busy pipeline is not left unmatched
+//   - `v4l2_capture` waits for room for priority frames in its frame
+//     consumers (frame publisher, MCAP recording) instead of dropping them
+//   - down the degradation ladder, priority frames keep their recording and
+//     reach the frame consumers when normal frames no longer do (see `degrade`)
+//
+// Canaries are never priority triggers.
+
+use crate::burst::BurstPattern;
+use crate::canary;
+use crate::cli::Args;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PriorityPattern {
+    /// Every this many bursts (or triggers) one is high priority.
+    pub every: u64,
+    pub burst: BurstPattern,
+}
+
+impl PriorityPattern {
+    pub fn new(every: u64, burst: BurstPattern) -> Self {
+        Self { every: every.max(1), burst }
+    }
+
+    /// `--priority-every <n>`; `None` without it or for 0.
+    pub fn from_args(args: &Args, burst: BurstPattern) -> Option<Self> {
+        args.value_as::<u64>("priority-every").filter(|every| *every > 0).map(|every| Self::new(every, burst))
+    }
+
+    pub fn is_priority(&self, trigger_id: u64) -> bool {
+        if canary::is_canary(trigger_id) {
+            return false;
+        }
+        let position = self.burst.position(trigger_id);
+        position.index == 0 && position.burst_id.is_multiple_of(self.every)
+    }
+}
+
+/// Whether `trigger_id` is high priority under `pattern`; no trigger is without one.
+pub fn is_priority(pattern: Option<PriorityPattern>, trigger_id: u64) -> bool {
+    pattern.is_some_and(|pattern| pattern.is_priority(trigger_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canary::CANARY_BIT;
+    use crate::pipeline::{MatchConfig, MatchPipeline, Queued, DRIFT_WINDOW};
+    use crate::CameraTrigger;
+
+    const MS: u64 = 1_000_000;
+
+    #[test]
+    fn keyframes_are_shed_last() {
+        let args = |line: &str| Args::parse(line.split_whitespace().map(str::to_string), &[]);
+        let burst = BurstPattern::new(3);
+        let pattern = PriorityPattern::from_args(&args("subscriber --priority-every 2"), burst).unwrap();
+        // First pulse of every second burst: bursts 2 and 4 start at ids 4 and 10
+        let tagged: Vec<u64> = (1..=12).filter(|&id| pattern.is_priority(id)).collect();
+        assert_eq!(tagged, [4, 10]);
+        assert!(!pattern.is_priority(CANARY_BIT | 4));
+        assert!(PriorityPattern::from_args(&args("subscriber --priority-every 0"), burst).is_none());
+        assert!(!is_priority(None, 4));
+
+        let mut matcher = MatchPipeline::new(50.0, burst);
+        matcher.configure(&MatchConfig { max_pending: 3, ..MatchConfig::default() });
+        matcher.set_priority(Some(pattern));
+        let trigger = |id: u64| CameraTrigger::new(id, 1_000 * MS + id * 10 * MS, 1_000 * MS + id * 10 * MS);
+        for id in 3..=5 {
+            assert_eq!(matcher.on_trigger(trigger(id), ()), Queued::Added);
+        }
+        // The oldest normal trigger goes, the keyframe 4 before it stays
+        assert_eq!(matcher.on_trigger(trigger(6), ()), Queued::Evicted(trigger(3)));
+        assert_eq!(matcher.on_trigger(trigger(7), ()), Queued::Evicted(trigger(5)));
+        assert_eq!(matcher.on_frame(trigger(4).hw_timestamp_ns + MS).matched.map(|(trigger, (), _)| trigger.frame_id), Some(4));
+
+        // With nothing but keyframes pending, the oldest of them goes
+        let mut matcher = MatchPipeline::new(50.0, BurstPattern::default());
+        matcher.configure(&MatchConfig { max_pending: 1, ..MatchConfig::default() });
+        matcher.set_priority(Some(PriorityPattern::new(1, BurstPattern::default())));
+        matcher.on_trigger(trigger(1), ());
+        assert_eq!(matcher.on_trigger(trigger(2), ()), Queued::Evicted(trigger(1)));
+    }
+
+    #[test]
+    fn late_keyframe_matches_within_configured_tolerance() {
+        let learned = |priority: Option<PriorityPattern>| {
+            let mut matcher = MatchPipeline::new(100.0, BurstPattern::default());
+            matcher.set_adaptive_tolerance(true);
+            matcher.set_priority(priority);
+            // 10ms latency, 33ms apart: the learned tolerance narrows to 12ms
+            for id in 1..=DRIFT_WINDOW as u64 {
+                let hw_ts = id * 33 * MS;
+                matcher.on_trigger(CameraTrigger::new(id, hw_ts, hw_ts), ());
+                assert!(matcher.on_frame(hw_ts + 10 * MS).matched.is_some());
+            }
+            assert_eq!(matcher.tolerance_ms(), 12.0);
+            matcher
+        };
+        // Trigger 40 is a keyframe (every 10th), its frame 25ms late
+        let pattern = PriorityPattern::new(10, BurstPattern::default());
+        let (late, hw_ts) = (40, 40 * 33 * MS);
+        let mut plain = learned(None);
+        plain.on_trigger(CameraTrigger::new(late, hw_ts, hw_ts), ());
+        assert!(plain.on_frame(hw_ts + 25 * MS).matched.is_none());
+        let mut tagged = learned(Some(pattern));
+        tagged.on_trigger(CameraTrigger::new(late, hw_ts, hw_ts), ());
+        assert_eq!(tagged.on_frame(hw_ts + 25 * MS).matched.map(|(trigger, (), score_ms)| (trigger.frame_id, score_ms)), Some((late, 25.0)));
+
+        // A normal trigger stays unmatched as before
+        let mut tagged = learned(Some(pattern));
+        tagged.on_trigger(CameraTrigger::new(late + 1, hw_ts, hw_ts), ());
+        assert!(tagged.on_frame(hw_ts + 25 * MS).matched.is_none());
+    }
+}
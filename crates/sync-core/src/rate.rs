@@ -0,0 +1,157 @@
+// Trigger rate and phase estimation on the consumer side.
+//
+// Pull-based consumers only see the triggers they receive, so the trigger
+// rate is estimated from the received history: a least-squares line through
+// (frame_id, hw_timestamp_ns) of the recent triggers. The slope is the trigger
+// period (robust against dropped triggers, since frame ids keep counting), the
+// intercept gives the phase of the trigger grid within one period, measured
+// from the clock epoch.
+
+use std::collections::VecDeque;
+
+use crate::units::{DisplayMs, Unit};
+
+/// Number of recent triggers used for the estimate.
+const WINDOW: usize = 120;
+/// Fewer triggers do not give a usable estimate.
+const MIN_TRIGGERS: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateEstimate {
+    pub rate_hz: f64,
+    pub period_ns: f64,
+    /// Offset of the trigger grid within one period, `0..period_ns`.
+    pub phase_ns: f64,
+    /// RMS deviation of the trigger timestamps from the fitted grid.
+    pub jitter_ns: f64,
+    pub triggers: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TriggerRateEstimator {
+    recent: VecDeque<(u64, u64)>,
+}
+
+impl TriggerRateEstimator {
+    pub fn on_trigger(&mut self, frame_id: u64, hw_timestamp_ns: u64) {
+        // A restarted publisher starts counting again; the old history no longer fits
+        if self.recent.back().is_some_and(|(last_id, _)| frame_id <= *last_id) {
+            self.recent.clear();
+        }
+        if self.recent.len() == WINDOW {
+            self.recent.pop_front();
+        }
+        self.recent.push_back((frame_id, hw_timestamp_ns));
+    }
+
+    pub fn estimate(&self) -> Option<RateEstimate> {
+        if self.recent.len() < MIN_TRIGGERS {
+            return None;
+        }
+        // Fit relative to the first trigger to keep the sums small and exact enough
+        let (id0, ts0) = *self.recent.front()?;
+        let points: Vec<(f64, f64)> = self
+            .recent
+            .iter()
+            .map(|(id, ts)| ((id - id0) as f64, (*ts as i64 - ts0 as i64) as f64))
+            .collect();
+        let n = points.len() as f64;
+        let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+        let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+        let sxx: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+        let sxy: f64 = points.iter().map(|(x, y)| (x - mean_x) * (y - mean_y)).sum();
+        if sxx == 0.0 {
+            return None;
+        }
+        let period_ns = sxy / sxx;
+        if period_ns <= 0.0 {
+            return None;
+        }
+        let intercept = mean_y - period_ns * mean_x;
+        let jitter_ns = (points
+            .iter()
+            .map(|(x, y)| (y - (intercept + period_ns * x)).powi(2))
+            .sum::<f64>()
+            / n)
+            .sqrt();
+        // Grid position of the first trigger, reduced to one period
+        let phase_ns = (ts0 as f64 + intercept).rem_euclid(period_ns);
+        Some(RateEstimate {
+            rate_hz: 1e9 / period_ns,
+            period_ns,
+            phase_ns,
+            jitter_ns,
+            triggers: self.recent.len(),
+        })
+    }
+
+    /// Warning text if the observed rate differs from `expected_fps` by more than `tolerance_pct` percent.
+    pub fn check(&self, expected_fps: f64, tolerance_pct: f64) -> Option<String> {
+        let estimate = self.estimate()?;
+        let deviation_pct = (estimate.rate_hz - expected_fps) / expected_fps * 100.0;
+        if deviation_pct.abs() <= tolerance_pct {
+            return None;
+        }
+        Some(format!(
+            "trigger rate {:.2}Hz differs from the expected {:.2}fps by {:+.1}% (tolerance {:.1}%)",
+            estimate.rate_hz, expected_fps, deviation_pct, tolerance_pct
+        ))
+    }
+}
+
+impl RateEstimate {
+    /// One-line summary, e.g. `29.97Hz (period 33.37ms, phase 12.05ms, jitter 0.04ms, n=120)` with
+    /// durations in `unit`.
+    pub fn summary(&self, unit: Unit) -> String {
+        format!(
+            "{:.2}Hz (period {:.2}, phase {:.2}, jitter {:.2}, n={})",
+            self.rate_hz,
+            DisplayMs(self.period_ns / 1e6, unit),
+            DisplayMs(self.phase_ns / 1e6, unit),
+            DisplayMs(self.jitter_ns / 1e6, unit),
+            self.triggers
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PERIOD_NS: u64 = 33_333_333;
+    const PHASE_NS: u64 = 12_000_000;
+
+    fn grid(frame_id: u64) -> u64 {
+        1_000_000 * PERIOD_NS + PHASE_NS + frame_id * PERIOD_NS
+    }
+
+    #[test]
+    fn rate_and_phase_survive_dropped_triggers() {
+        let mut estimator = TriggerRateEstimator::default();
+        for frame_id in (1..=40).filter(|id| id % 5 != 0) {
+            estimator.on_trigger(frame_id, grid(frame_id));
+            if frame_id < MIN_TRIGGERS as u64 {
+                assert_eq!(estimator.estimate(), None);
+            }
+        }
+        let estimate = estimator.estimate().unwrap();
+        assert!((estimate.period_ns - PERIOD_NS as f64).abs() < 1.0, "{:?}", estimate);
+        assert!((estimate.rate_hz - 30.0).abs() < 1e-3);
+        assert!((estimate.phase_ns - PHASE_NS as f64).abs() < 100.0, "{:?}", estimate);
+        assert!(estimate.jitter_ns < 1.0);
+        assert_eq!(estimate.triggers, 32);
+
+        assert_eq!(estimator.check(30.0, 1.0), None);
+        assert!(estimator.check(25.0, 1.0).unwrap().starts_with("trigger rate 30.00Hz differs from the expected 25.00fps by +20.0%"));
+    }
+
+    #[test]
+    fn a_restarted_publisher_starts_a_new_estimate() {
+        let mut estimator = TriggerRateEstimator::default();
+        for frame_id in 100..120 {
+            estimator.on_trigger(frame_id, grid(frame_id));
+        }
+        estimator.on_trigger(1, grid(200));
+        assert_eq!(estimator.estimate(), None);
+    }
+}
@@ -0,0 +1,263 @@
+// Readiness checks before unattended runs.
+//
+// `v4l2_capture --check` and `selftest` collect PASS/WARN/FAIL checks in a
+// `ReadinessReport` instead of starting work: a failed check would stop the
+// run (or make it useless), a warning is worth a look but does not. The
+// checks here need nothing but the host: clock sanity, input files that must
+// be readable and output paths that must be writable. Output paths are
+// probed without creating what the run would create (a directory that does
+// not exist yet passes if its nearest existing ancestor is writable), so a
+// check leaves no traces.
+
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::clock;
+use crate::status::{self, Status, Value};
+
+/// Realtime must not drift against monotonic by more than this over the sampling period.
+pub const MAX_CLOCK_SKEW_MS: f64 = 1.0;
+/// Any realtime clock before 2020-01-01 was never set.
+pub const MIN_REALTIME_NS: u64 = 1_577_836_800_000_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl Outcome {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Pass => "pass",
+            Self::Warn => "warn",
+            Self::Fail => "fail",
+        }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name().to_uppercase())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Check {
+    pub name: String,
+    pub outcome: Outcome,
+    pub detail: String,
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {:<28} {}", self.outcome, self.name, self.detail)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ReadinessReport {
+    checks: Vec<Check>,
+}
+
+impl ReadinessReport {
+    pub fn add(&mut self, name: &str, outcome: Outcome, detail: impl Into<String>) {
+        self.checks.push(Check {
+            name: name.to_string(),
+            outcome,
+            detail: detail.into(),
+        });
+    }
+
+    pub fn check(&mut self, name: &str, passed: bool, detail: impl Into<String>) {
+        self.add(name, if passed { Outcome::Pass } else { Outcome::Fail }, detail);
+    }
+
+    pub fn warn(&mut self, name: &str, detail: impl Into<String>) {
+        self.add(name, Outcome::Warn, detail);
+    }
+
+    /// Passes with the detail of `Ok`, fails with the error.
+    pub fn result<E: fmt::Display>(&mut self, name: &str, result: Result<String, E>) {
+        match result {
+            Ok(detail) => self.add(name, Outcome::Pass, detail),
+            Err(e) => self.add(name, Outcome::Fail, e.to_string()),
+        }
+    }
+
+    pub fn checks(&self) -> &[Check] {
+        &self.checks
+    }
+
+    pub fn count(&self, outcome: Outcome) -> usize {
+        self.checks.iter().filter(|check| check.outcome == outcome).count()
+    }
+
+    /// No check failed; warnings do not count.
+    pub fn is_ready(&self) -> bool {
+        self.count(Outcome::Fail) == 0
+    }
+
+    /// One `check` line per check and the `readiness` summary.
+    pub fn print(&self, status: &Status) {
+        for check in &self.checks {
+            status.line(
+                "check",
+                format_args!("{}", check),
+                &[("check", check.name.as_str().into()), ("result", check.outcome.name().into()), ("detail", check.detail.as_str().into())],
+            );
+        }
+        let failed: Vec<Value> = self.checks.iter().filter(|check| check.outcome == Outcome::Fail).map(|check| check.name.as_str().into()).collect();
+        status.line(
+            "readiness",
+            format_args!(
+                "{}: {} passed, {} warnings, {} failed",
+                if self.is_ready() { "READY" } else { "NOT READY" },
+                self.count(Outcome::Pass),
+                self.count(Outcome::Warn),
+                failed.len()
+            ),
+            &[
+                ("ready", self.is_ready().into()),
+                ("passed", self.count(Outcome::Pass).into()),
+                ("warnings", self.count(Outcome::Warn).into()),
+                ("failed", failed.len().into()),
+                ("failed_checks", Value::Json(status::json_array(&failed))),
+            ],
+        );
+    }
+}
+
+/// Realtime clock set, monotonic clock never stepping back and no realtime steps or excessive
+/// slewing over 200ms.
+pub fn check_clocks(report: &mut ReadinessReport) {
+    let realtime = clock::realtime_now_ns();
+    report.check("realtime clock set", realtime >= MIN_REALTIME_NS, format!("realtime={}ns", realtime));
+
+    let mut previous = clock::monotonic_now_ns();
+    let mut backwards = 0;
+    for _ in 0..100_000 {
+        let now = clock::monotonic_now_ns();
+        if now < previous {
+            backwards += 1;
+        }
+        previous = now;
+    }
+    report.check("monotonic clock", backwards == 0, format!("{} backward steps in 100000 reads", backwards));
+
+    let offset_before = clock::realtime_minus_monotonic_ns();
+    std::thread::sleep(Duration::from_millis(200));
+    let offset_after = clock::realtime_minus_monotonic_ns();
+    let skew_ms = (offset_after - offset_before) as f64 / 1e6;
+    report.check("realtime vs monotonic skew", skew_ms.abs() <= MAX_CLOCK_SKEW_MS, format!("{:+.3}ms over 200ms", skew_ms));
+}
+
+/// A file the run reads.
+pub fn check_input_file(report: &mut ReadinessReport, name: &str, path: &Path) {
+    let result = match fs::File::open(path).and_then(|file| file.metadata()) {
+        Ok(metadata) if metadata.is_file() => Ok(format!("{} ({} bytes)", path.display(), metadata.len())),
+        Ok(_) => Err(format!("{} is not a file", path.display())),
+        Err(e) => Err(format!("{}: {}", path.display(), e)),
+    };
+    report.result(name, result);
+}
+
+/// A directory the run creates or writes into.
+pub fn check_output_dir(report: &mut ReadinessReport, name: &str, path: &Path) {
+    let result = if path.is_dir() {
+        writable_dir(path).map(|()| format!("{} (exists, writable)", path.display()))
+    } else if path.exists() {
+        Err(format!("{} is not a directory", path.display()))
+    } else {
+        existing_ancestor(path).map(|ancestor| format!("{} (created in {})", path.display(), ancestor.display()))
+    };
+    report.result(name, result);
+}
+
+/// A file the run creates or appends to.
+pub fn check_output_file(report: &mut ReadinessReport, name: &str, path: &Path) {
+    let result = if path.is_file() {
+        OpenOptions::new().append(true).open(path).map(|_| format!("{} (exists, appended to)", path.display())).map_err(|e| format!("{}: {}", path.display(), e))
+    } else if path.exists() {
+        Err(format!("{} is not a file", path.display()))
+    } else {
+        existing_ancestor(path).map(|_| format!("{} (created)", path.display()))
+    };
+    report.result(name, result);
+}
+
+// The nearest existing ancestor of a path that does not exist yet, if it is a writable directory
+fn existing_ancestor(path: &Path) -> Result<&Path, String> {
+    // A relative path's last ancestor is the empty path, i.e. the working directory
+    let ancestor = path
+        .ancestors()
+        .skip(1)
+        .map(|ancestor| if ancestor.as_os_str().is_empty() { Path::new(".") } else { ancestor })
+        .find(|ancestor| ancestor.exists())
+        .unwrap_or(Path::new("/"));
+    if !ancestor.is_dir() {
+        return Err(format!("{} is not a directory", ancestor.display()));
+    }
+    writable_dir(ancestor).map(|()| ancestor)
+}
+
+// Creates and removes a probe file: permission bits do not tell about read-only mounts or ACLs
+fn writable_dir(dir: &Path) -> Result<(), String> {
+    let probe = dir.join(format!(".readiness_probe_{}", std::process::id()));
+    OpenOptions::new().write(true).create_new(true).open(&probe).map_err(|e| format!("{} is not writable: {}", dir.display(), e))?;
+    fs::remove_file(&probe).map_err(|e| format!("could not remove {}: {}", probe.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn output_paths_are_probed_without_traces() {
+        let dir = std::env::temp_dir().join(format!("camera_sync_readiness_{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("temporary directory");
+        let input = dir.join("cam0.yaml");
+        fs::write(&input, "image_width: 640\n").expect("input file");
+
+        let mut report = ReadinessReport::default();
+        check_input_file(&mut report, "calibration", &input);
+        check_input_file(&mut report, "missing calibration", &dir.join("cam1.yaml"));
+        check_output_dir(&mut report, "session dir", &dir.join("session_001/left"));
+        check_output_dir(&mut report, "file as dir", &input);
+        check_output_file(&mut report, "event log", &dir.join("events.jsonl"));
+        check_output_file(&mut report, "event log in file", &input.join("events.jsonl"));
+        report.warn("trigger service", "Camera/Sync does not exist yet, start the publisher");
+
+        let outcomes: Vec<(&str, Outcome)> = report.checks().iter().map(|check| (check.name.as_str(), check.outcome)).collect();
+        assert_eq!(
+            outcomes,
+            [
+                ("calibration", Outcome::Pass),
+                ("missing calibration", Outcome::Fail),
+                ("session dir", Outcome::Pass),
+                ("file as dir", Outcome::Fail),
+                ("event log", Outcome::Pass),
+                ("event log in file", Outcome::Fail),
+                ("trigger service", Outcome::Warn),
+            ]
+        );
+        assert_eq!((report.count(Outcome::Pass), report.count(Outcome::Warn), report.count(Outcome::Fail)), (3, 1, 3));
+        assert!(!report.is_ready());
+        assert!(report.checks()[0].to_string().starts_with("[PASS] calibration "));
+
+        // Nothing was created, and the write probes were removed
+        let mut left: Vec<String> = fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned()).collect();
+        left.sort();
+        assert_eq!(left, ["cam0.yaml"]);
+        fs::remove_dir_all(&dir).ok();
+
+        let mut report = ReadinessReport::default();
+        report.warn("stale nodes", "2 dead nodes of rig lab2");
+        report.check("camera", true, "serial:ABC123 is /dev/video2");
+        assert!(report.is_ready());
+    }
+}
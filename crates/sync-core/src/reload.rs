@@ -0,0 +1,208 @@
+// Live reload of the `--config` file.
+//
+// Long-running processes re-read their `--config` file when it changes (its
+// modification time, checked every `CHECK_INTERVAL`) or when they receive
+// SIGHUP. The file is merged with the command line as at startup, so options
+// given on the command line keep winning. Each program lists the options it
+// can change while running (tolerances, thresholds, logging and recording
+// targets); a changed option outside that list is reported as requiring a
+// restart and keeps its old value until then. A file that does not parse is
+// reported and the running configuration stays as it is.
+//
+// SIGHUP only reloads in processes started with `--config`; the handler is
+// installed by `ConfigWatcher::from_args`, so a process without one keeps the
+// default action.
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::cli::Args;
+
+/// How often the config file's modification time is checked.
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+static HANGUP: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+extern "C" fn on_hangup(_: libc::c_int) {
+    HANGUP.store(true, Ordering::Relaxed);
+}
+
+#[cfg(unix)]
+fn install_hangup_handler() {
+    // Only stores to an atomic, which is async-signal-safe
+    unsafe { libc::signal(libc::SIGHUP, on_hangup as extern "C" fn(libc::c_int) as libc::sighandler_t) };
+}
+
+#[cfg(not(unix))]
+fn install_hangup_handler() {}
+
+/// A new configuration and what changed against the running one.
+#[derive(Debug, Clone)]
+pub struct ConfigReload {
+    /// Command line and config file merged, as `Args::from_env` gives them at startup.
+    pub args: Args,
+    /// Changed options the program applies now.
+    pub applied: Vec<String>,
+    /// Changed options that only take effect after a restart.
+    pub restart_required: Vec<String>,
+}
+
+impl ConfigReload {
+    pub fn is_applied(&self, name: &str) -> bool {
+        self.applied.iter().any(|applied| applied == name)
+    }
+}
+
+impl fmt::Display for ConfigReload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list = |names: &[String]| if names.is_empty() { "none".to_string() } else { names.join(", ") };
+        write!(f, "applied {}", list(&self.applied))?;
+        if !self.restart_required.is_empty() {
+            write!(f, "; restart required for {}", list(&self.restart_required))?;
+        }
+        Ok(())
+    }
+}
+
+/// Watches the `--config` file of a process.
+pub struct ConfigWatcher {
+    path: PathBuf,
+    command_line: Vec<String>,
+    switches: &'static [&'static str],
+    live: &'static [&'static str],
+    current: Args,
+    modified: Option<SystemTime>,
+    next_check: Instant,
+}
+
+impl ConfigWatcher {
+    /// Watches the file of `--config`, `None` without one. `switches` are the program's switches as given
+    /// to `Args::from_env`, `live` the options it can change while running. Installs the SIGHUP handler.
+    pub fn from_args(args: &Args, switches: &'static [&'static str], live: &'static [&'static str]) -> Option<Self> {
+        let path = args.value("config")?;
+        install_hangup_handler();
+        Some(Self::new(Path::new(path), std::env::args().collect(), args.clone(), switches, live))
+    }
+
+    /// Watches `path` for a process started with `command_line` and running with `current`.
+    pub fn new(path: &Path, command_line: Vec<String>, current: Args, switches: &'static [&'static str], live: &'static [&'static str]) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            command_line,
+            switches,
+            live,
+            current,
+            modified: modified(path),
+            next_check: Instant::now() + CHECK_INTERVAL,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The new configuration if the file changed or SIGHUP arrived since the last call, an error if it
+    /// does not load. Cheap enough to call every loop iteration.
+    pub fn poll(&mut self) -> Option<Result<ConfigReload, String>> {
+        let hangup = HANGUP.swap(false, Ordering::Relaxed);
+        let now = Instant::now();
+        if !hangup && now < self.next_check {
+            return None;
+        }
+        self.next_check = now + CHECK_INTERVAL;
+        let modified = modified(&self.path);
+        if !hangup && modified == self.modified {
+            return None;
+        }
+        // Not retried until the next change, also when it fails to load
+        self.modified = modified;
+        Some(self.reload())
+    }
+
+    /// Re-reads the file now.
+    pub fn reload(&mut self) -> Result<ConfigReload, String> {
+        let command_line = Args::parse(self.command_line.iter().cloned(), self.switches);
+        let mut args = command_line.clone();
+        args.load_config(&self.path).map_err(|e| format!("could not load config {}: {}", self.path.display(), e))?;
+        // A file entry overridden on the command line changes nothing
+        let (applied, restart_required) = changed_options(&self.current, &args)
+            .into_iter()
+            .filter(|name| !command_line.flag(name) || self.current.value(name) != args.value(name))
+            .partition(|name| self.live.contains(&name.as_str()));
+        self.current = args.clone();
+        Ok(ConfigReload { args, applied, restart_required })
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// Options whose values or presence differ, in order of first occurrence.
+pub fn changed_options(old: &Args, new: &Args) -> Vec<String> {
+    let mut names = old.names();
+    names.extend(new.names().into_iter().filter(|name| !old.flag(name)));
+    names
+        .into_iter()
+        .filter(|name| *name != "config" && (old.flag(name) != new.flag(name) || old.values(name) != new.values(name)))
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SWITCHES: &[&str] = &["adaptive-tolerance"];
+    const LIVE: &[&str] = &["tolerance-ms", "adaptive-tolerance", "event-log"];
+
+    fn command_line(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn reload_applies_live_options_and_reports_the_rest() {
+        let dir = std::env::temp_dir().join(format!("camera_sync_reload_{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("temporary directory");
+        let path = dir.join("subscriber.toml");
+        fs::write(&path, "tolerance-ms = 10\nrate-tolerance-pct = 10\nrig-id = \"lab2\"\n").expect("config file");
+        let line = command_line(&format!("subscriber 30 --config {} --rig-id lab2", path.display()));
+        let mut current = Args::parse(line.clone(), SWITCHES);
+        current.load_config(&path).unwrap();
+        let mut watcher = ConfigWatcher::new(&path, line, current, SWITCHES, LIVE);
+
+        let unchanged = watcher.reload().unwrap();
+        assert!(unchanged.applied.is_empty() && unchanged.restart_required.is_empty());
+
+        // The command line's rig id wins over the file, so changing it there changes nothing
+        fs::write(&path, "tolerance-ms = 8\nadaptive-tolerance = true\nrate-tolerance-pct = 5\nrig-id = \"lab3\"\n").expect("config file");
+        let reload = watcher.reload().unwrap();
+        assert_eq!(reload.applied, ["tolerance-ms", "adaptive-tolerance"]);
+        assert_eq!(reload.restart_required, ["rate-tolerance-pct"]);
+        assert_eq!(reload.args.value_as::<f64>("tolerance-ms"), Some(8.0));
+        assert!(reload.args.flag("adaptive-tolerance"));
+        assert_eq!(reload.args.value("rig-id"), Some("lab2"));
+        assert_eq!(reload.to_string(), "applied tolerance-ms, adaptive-tolerance; restart required for rate-tolerance-pct");
+
+        // A broken file is reported; the next good one is compared against the last applied
+        fs::write(&path, "tolerance-ms = [").expect("config file");
+        assert!(watcher.reload().is_err());
+        fs::write(&path, "tolerance-ms = 8\nrate-tolerance-pct = 5\n").expect("config file");
+        let reload = watcher.reload().unwrap();
+        assert_eq!(reload.applied, ["adaptive-tolerance"]);
+        assert!(reload.restart_required.is_empty());
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn changed_options_ignore_the_config_path() {
+        let old = Args::parse(command_line("subscriber --config a.toml --tolerance-ms 10 --event-log a.jsonl"), SWITCHES);
+        let new = Args::parse(command_line("subscriber --config b.toml --tolerance-ms 10 --adaptive-tolerance"), SWITCHES);
+        assert_eq!(changed_options(&old, &new), ["event-log", "adaptive-tolerance"]);
+        assert!(changed_options(&old, &old).is_empty());
+    }
+}
@@ -0,0 +1,198 @@
+// Retention of recorded frames by sync quality.
+//
+// A long dataset collection should not be polluted with samples whose
+// timestamps are questionable. Each match gets a confidence between 0 and 1
+// from what the matcher knows about it: how far the frame is from its
+// trigger's expected time relative to the tolerance, whether a clock stepped
+// around it (see `clock_guard`), whether the publisher's trigger clock was
+// downgraded (see `clock_quality`) and what the `audit` stage found.
+//
+// With `--min-confidence <c>` the session recorder keeps only frames at or
+// above `c`; the others are discarded, or with `--quarantine` recorded into
+// the session's `quarantine/` directory, itself a session that the usual tools
+// read, so they can be reviewed and recovered.
+
+use std::fmt;
+
+use crate::cli::Args;
+
+/// Subdirectory of a session that holds its quarantined frames.
+pub const QUARANTINE_DIR: &str = "quarantine";
+
+/// What the matcher knows about one match.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MatchQuality {
+    pub score_ms: f64,
+    pub tolerance_ms: f64,
+    /// A clock stepped around the match.
+    pub clock_disturbed: bool,
+    /// The publisher's trigger clock was downgraded.
+    pub clock_downgraded: bool,
+    /// Findings of the `audit` matcher stage.
+    pub findings: usize,
+}
+
+impl MatchQuality {
+    /// 1 for a frame exactly at its expected time, falling linearly to 0 at
+    /// the edge of the tolerance. Halved on a downgraded trigger clock and for
+    /// every audit finding; none while a clock is disturbed.
+    pub fn confidence(&self) -> f64 {
+        if self.clock_disturbed || self.tolerance_ms <= 0.0 {
+            return 0.0;
+        }
+        let mut confidence = (1.0 - self.score_ms / self.tolerance_ms).clamp(0.0, 1.0);
+        if self.clock_downgraded {
+            confidence /= 2.0;
+        }
+        confidence / 2f64.powi(self.findings.min(32) as i32)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetentionAction {
+    Keep,
+    Quarantine,
+    Discard,
+}
+
+impl fmt::Display for RetentionAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RetentionAction::Keep => "kept",
+            RetentionAction::Quarantine => "quarantined",
+            RetentionAction::Discard => "discarded",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetentionPolicy {
+    pub min_confidence: f64,
+    /// Record frames below `min_confidence` into `quarantine/` instead of discarding them.
+    pub quarantine: bool,
+}
+
+impl RetentionPolicy {
+    /// `--min-confidence <0..1>` and `--quarantine`; `None` without `--min-confidence`.
+    pub fn from_args(args: &Args) -> Result<Option<Self>, String> {
+        let Some(value) = args.value("min-confidence") else {
+            return Ok(None);
+        };
+        let min_confidence: f64 = value.parse().map_err(|_| format!("invalid --min-confidence '{}'", value))?;
+        if !(0.0..=1.0).contains(&min_confidence) {
+            return Err(format!("--min-confidence {} outside 0..1", min_confidence));
+        }
+        Ok(Some(Self {
+            min_confidence,
+            quarantine: args.flag("quarantine"),
+        }))
+    }
+
+    pub fn decide(&self, confidence: f64) -> RetentionAction {
+        if confidence >= self.min_confidence {
+            RetentionAction::Keep
+        } else if self.quarantine {
+            RetentionAction::Quarantine
+        } else {
+            RetentionAction::Discard
+        }
+    }
+}
+
+impl fmt::Display for RetentionPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "min_confidence={}, {} below", self.min_confidence, if self.quarantine { "quarantine" } else { "discard" })
+    }
+}
+
+/// Frames per retention decision of a session.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RetentionCounts {
+    pub kept: u64,
+    pub quarantined: u64,
+    pub discarded: u64,
+}
+
+impl RetentionCounts {
+    pub fn count(&mut self, action: RetentionAction) {
+        match action {
+            RetentionAction::Keep => self.kept += 1,
+            RetentionAction::Quarantine => self.quarantined += 1,
+            RetentionAction::Discard => self.discarded += 1,
+        }
+    }
+}
+
+impl fmt::Display for RetentionCounts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "RETENTION kept={} | quarantined={} | discarded={}", self.kept, self.quarantined, self.discarded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::Path;
+    use crate::session::{self, MatchRecord, SessionRecorder};
+    use crate::signing::{SignatureStatus, TriggerSignature};
+    use crate::CameraTrigger;
+
+    fn record(trigger_id: u64, score_ms: f64) -> MatchRecord {
+        MatchRecord {
+            trigger: CameraTrigger::new(trigger_id, trigger_id * 33_000_000, trigger_id * 33_000_000 + 50_000),
+            v4l2_timestamp_ns: trigger_id * 33_000_000 + 12_000_000,
+            total_latency_ms: 12.0,
+            score_ms,
+            signature_status: SignatureStatus::Unsigned,
+            signature: TriggerSignature::default(),
+            metadata: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn confidence_falls_with_score_and_doubts() {
+        let exact = MatchQuality {
+            score_ms: 0.0,
+            tolerance_ms: 10.0,
+            ..Default::default()
+        };
+        assert_eq!(exact.confidence(), 1.0);
+        let halfway = MatchQuality { score_ms: 5.0, ..exact };
+        assert_eq!(halfway.confidence(), 0.5);
+        assert_eq!(MatchQuality { findings: 2, ..halfway }.confidence(), 0.125);
+        assert_eq!(MatchQuality { clock_downgraded: true, ..exact }.confidence(), 0.5);
+        assert_eq!(MatchQuality { clock_disturbed: true, ..exact }.confidence(), 0.0);
+        assert_eq!(MatchQuality { score_ms: 12.0, ..exact }.confidence(), 0.0);
+    }
+
+    #[test]
+    fn low_confidence_frames_are_quarantined_or_discarded() {
+        let dir = std::env::temp_dir().join(format!("camera_sync_retention_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let policy = RetentionPolicy {
+            min_confidence: 0.5,
+            quarantine: true,
+        };
+        let mut recorder = SessionRecorder::create(&dir).unwrap().with_retention(policy).unwrap();
+        for (trigger_id, score_ms) in [(1, 1.0), (2, 8.0), (3, 4.0), (4, 9.0)] {
+            let confidence = MatchQuality { score_ms, tolerance_ms: 10.0, ..Default::default() }.confidence();
+            let action = recorder.retention_action(confidence);
+            if let Some(target) = recorder.retain(action) {
+                target.record(&record(trigger_id, score_ms)).unwrap();
+            }
+        }
+        let ids = |dir: &Path| session::read_records(dir).unwrap().iter().map(|record| record.trigger.frame_id).collect::<Vec<_>>();
+        assert_eq!(ids(&dir), [1, 3]);
+        assert_eq!(ids(&dir.join(QUARANTINE_DIR)), [2, 4]);
+
+        let discard = RetentionPolicy { quarantine: false, ..policy };
+        assert_eq!(discard.decide(0.2), RetentionAction::Discard);
+        assert_eq!(discard.decide(0.5), RetentionAction::Keep);
+        let mut recorder = SessionRecorder::create(&dir).unwrap().with_retention(discard).unwrap();
+        assert!(recorder.retain(RetentionAction::Discard).is_none());
+        let retained = recorder.retained();
+        assert_eq!((retained.kept, retained.discarded), (0, 1));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
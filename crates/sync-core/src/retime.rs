@@ -0,0 +1,93 @@
+// Re-timestamping of frames from pipelines without trigger synchronization.
+//
+// A frame stream is an iceoryx2 service of raw frame bytes with a
+// `FrameHeader` (see `sync_iceoryx2::frames`). The `retime_proxy` binary
+// subscribes to such a stream, associates every frame with the received
+// triggers using the same matcher pipeline as the camera processes (see
+// `pipeline`) and republishes it with the trigger's hw_ts as its timestamp.
+// The source keeps its own timestamp in `source_timestamp_ns`, so downstream
+// consumers can see the correction, and the header carries the trigger's trace
+// origin, so a frame can be followed back to its trigger (see `trace`).
+
+#[cfg(feature = "iceoryx2")]
+use iceoryx2::prelude::ZeroCopySend;
+
+use crate::trace::TraceId;
+use crate::CameraTrigger;
+
+/// The frame was associated with a trigger: `timestamp_ns` is its hw_ts.
+pub const FLAG_MATCHED: u32 = 1;
+
+/// User header of a frame stream sample; the payload is the frame bytes.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "iceoryx2", derive(ZeroCopySend))]
+pub struct FrameHeader {
+    /// Frame timestamp, CLOCK_REALTIME ns (the trigger hw_ts after re-timestamping).
+    pub timestamp_ns: u64,
+    /// Timestamp the source assigned, before re-timestamping.
+    pub source_timestamp_ns: u64,
+    pub sequence: u64,
+    /// Matched trigger id, valid with `FLAG_MATCHED`.
+    pub trigger_id: u64,
+    pub width: u32,
+    pub height: u32,
+    /// Pixel format as V4L2 FourCC, e.g. `RGB3`, `YUYV`, `MJPG`.
+    pub fourcc: [u8; 4],
+    pub flags: u32,
+    /// `TraceId::origin` of the matched trigger, valid with `FLAG_MATCHED`.
+    pub trace_origin: u32,
+}
+
+impl FrameHeader {
+    pub fn is_matched(&self) -> bool {
+        self.flags & FLAG_MATCHED != 0
+    }
+
+    pub fn fourcc_str(&self) -> String {
+        String::from_utf8_lossy(&self.fourcc).trim_end_matches(['\0', ' ']).to_string()
+    }
+
+    /// Trace id of the matched trigger.
+    pub fn trace_id(&self) -> Option<TraceId> {
+        self.is_matched().then(|| TraceId::new(self.trace_origin, self.trigger_id))
+    }
+
+    /// Header of the republished frame: re-timestamped if `matched` (with the trigger's trace origin), else passed through.
+    pub fn retimed(&self, matched: Option<(CameraTrigger, u32)>) -> Self {
+        let mut retimed = Self {
+            source_timestamp_ns: self.timestamp_ns,
+            ..*self
+        };
+        if let Some((trigger, trace_origin)) = matched {
+            retimed.timestamp_ns = trigger.hw_timestamp_ns;
+            retimed.trigger_id = trigger.frame_id;
+            retimed.trace_origin = trace_origin;
+            retimed.flags |= FLAG_MATCHED;
+        } else {
+            retimed.flags &= !FLAG_MATCHED;
+        }
+        retimed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matched_frames_take_the_trigger_time() {
+        let source = FrameHeader { timestamp_ns: 1_020, sequence: 7, width: 640, height: 480, fourcc: *b"YUYV", ..Default::default() };
+        let matched = source.retimed(Some((CameraTrigger::new(3, 1_000, 1_001), 0xab)));
+        assert_eq!((matched.timestamp_ns, matched.source_timestamp_ns, matched.sequence), (1_000, 1_020, 7));
+        assert_eq!(matched.trace_id(), Some(TraceId::new(0xab, 3)));
+        assert_eq!(matched.fourcc_str(), "YUYV");
+
+        // Republishing an unmatched frame clears an earlier match
+        let unmatched = matched.retimed(None);
+        assert_eq!((unmatched.timestamp_ns, unmatched.source_timestamp_ns), (1_000, 1_000));
+        assert!(!unmatched.is_matched());
+        assert_eq!(unmatched.trace_id(), None);
+        assert_eq!(FrameHeader { fourcc: *b"RGB\0", ..source }.fourcc_str(), "RGB");
+    }
+}
@@ -0,0 +1,109 @@
+// ROS 2 image messages of published frames, for the `ros2_bridge` binary.
+//
+// `ros2_bridge` reads a frame stream (`v4l2_capture --publish-frames`, or the
+// output of `retime_proxy`) and republishes every matched frame as a CDR
+// serialized `sensor_msgs/msg/Image` over Zenoh, where `zenoh-bridge-ros2dds`
+// hands it to the ROS 2 subscribers of the topic. `header.stamp` is the frame
+// header's `timestamp_ns`, which the publishers set to the matched trigger's
+// hw_ts: ROS 2 nodes see the exposure time, not the time the frame crossed a
+// host or the bridge. Unmatched frames carry no trigger time and are skipped.
+//
+// Uncompressed formats map to their `sensor_msgs/image_encodings` name:
+//
+//   RGB3 -> rgb8   BGR3 -> bgr8   YUYV -> yuv422_yuy2   UYVY -> yuv422
+//   GREY -> mono8
+//
+// Compressed frames (MJPG) have no `Image` encoding and are rejected.
+
+use crate::retime::FrameHeader;
+use crate::rosbag;
+
+/// Zenoh key of the images; `zenoh-bridge-ros2dds` maps it to `/camera/image_raw`.
+pub const DEFAULT_TOPIC: &str = "camera/image_raw";
+
+pub const DEFAULT_FRAME_ID: &str = "camera";
+
+/// `sensor_msgs/image_encodings` name and bytes per pixel of a V4L2 FourCC.
+pub fn encoding(fourcc: &str) -> Option<(&'static str, u32)> {
+    match fourcc {
+        "RGB3" => Some(("rgb8", 3)),
+        "BGR3" => Some(("bgr8", 3)),
+        "YUYV" => Some(("yuv422_yuy2", 2)),
+        "UYVY" => Some(("yuv422", 2)),
+        "GREY" => Some(("mono8", 1)),
+        _ => None,
+    }
+}
+
+/// CDR `sensor_msgs/msg/Image` of a frame, stamped with `header.timestamp_ns`.
+pub fn image_message(header: &FrameHeader, frame_id: &str, data: &[u8]) -> Result<Vec<u8>, String> {
+    let fourcc = header.fourcc_str();
+    let (name, bytes_per_pixel) = encoding(&fourcc).ok_or_else(|| format!("no ROS 2 image encoding for {}", fourcc))?;
+    let step = header.width * bytes_per_pixel;
+    let expected = step as usize * header.height as usize;
+    if data.len() != expected {
+        return Err(format!("{} bytes for a {}x{} {} frame, expected {}", data.len(), header.width, header.height, fourcc, expected));
+    }
+    Ok(rosbag::raw_image(header.timestamp_ns, frame_id, header.width, header.height, name, step, data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::retime::{FrameHeader, FLAG_MATCHED};
+
+    const HW_TS: u64 = 1_700_000_000_123_456_789;
+
+    fn header(fourcc: &[u8; 4], width: u32, height: u32) -> FrameHeader {
+        FrameHeader {
+            timestamp_ns: HW_TS,
+            source_timestamp_ns: HW_TS + 15_000_000,
+            sequence: 7,
+            trigger_id: 42,
+            width,
+            height,
+            fourcc: *fourcc,
+            flags: FLAG_MATCHED,
+            trace_origin: 0,
+        }
+    }
+
+    fn u32_at(message: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(message[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn image_is_stamped_with_trigger_time() {
+        let rgb = vec![200u8; 4 * 2 * 3];
+        let message = image_message(&header(b"RGB3", 4, 2), DEFAULT_FRAME_ID, &rgb).unwrap();
+        // Encapsulation header, then stamp sec and nanosec
+        assert_eq!(message[..4], [0x00, 0x01, 0x00, 0x00]);
+        assert_eq!(u32_at(&message, 4), 1_700_000_000);
+        assert_eq!(u32_at(&message, 8), 123_456_789);
+        // frame_id "camera\0" (length 7), padded to 4 bytes, then height, width and encoding
+        assert_eq!(u32_at(&message, 12), 7);
+        assert_eq!(&message[16..22], b"camera");
+        assert_eq!((u32_at(&message, 24), u32_at(&message, 28)), (2, 4));
+        assert_eq!(u32_at(&message, 32), 5);
+        assert_eq!(&message[36..40], b"rgb8");
+        // is_bigendian, step, data
+        assert_eq!(message[41], 0);
+        assert_eq!(u32_at(&message, 44), 12);
+        assert_eq!(u32_at(&message, 48), 24);
+        assert_eq!(&message[52..], &rgb[..]);
+    }
+
+    #[test]
+    fn encodings_follow_the_frame_format() {
+        assert_eq!(encoding("YUYV"), Some(("yuv422_yuy2", 2)));
+        assert_eq!(encoding("GREY"), Some(("mono8", 1)));
+        let yuyv = vec![0u8; 4 * 2 * 2];
+        let message = image_message(&header(b"YUYV", 4, 2), "cam0", &yuyv).unwrap();
+        assert!(message.windows(12).any(|window| window == b"yuv422_yuy2\0"));
+
+        let error = image_message(&header(b"MJPG", 4, 2), "cam0", &[0xff, 0xd8]).unwrap_err();
+        assert_eq!(error, "no ROS 2 image encoding for MJPG");
+        let error = image_message(&header(b"RGB3", 4, 2), "cam0", &yuyv).unwrap_err();
+        assert_eq!(error, "16 bytes for a 4x2 RGB3 frame, expected 24");
+    }
+}
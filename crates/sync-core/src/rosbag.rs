@@ -0,0 +1,349 @@
+// ROS 2 bag (rosbag2) export of recorded sessions.
+//
+// A rosbag2 bag is a directory with `metadata.yaml` and its storage files. The
+// export writes one MCAP storage file (`storage_identifier: mcap`) with CDR
+// serialized standard messages, so `ros2 bag play` works without custom
+// message packages. Every session becomes the namespace `/cam<N>`:
+//
+//   /cam<N>/trigger      sensor_msgs/msg/TimeReference  stamp = hw_ts,
+//                        time_ref = publish time, source = trigger id
+//   /cam<N>/image_raw    sensor_msgs/msg/Image (rgb8)   stamp = hw_ts
+//   /cam<N>/camera_info  sensor_msgs/msg/CameraInfo     stamp = hw_ts
+//
+// Images and camera infos need frames saved in the session (`--save-frames`);
+// camera infos carry the session's intrinsics (`--camera-info`), if recorded.
+// Messages are logged at their trigger's hw_ts, so a replay reproduces the
+// synchronized timeline rather than the capture hosts' receive times.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::intrinsics::{self, CameraIntrinsics};
+use crate::mcap::McapWriter;
+use crate::session::{self, MatchRecord, FRAMES_DIR};
+use crate::CameraTrigger;
+
+pub const METADATA_FILE: &str = "metadata.yaml";
+
+const SEPARATOR: &str = "================================================================================";
+const HEADER_DEFINITION: &str = "MSG: std_msgs/Header\nbuiltin_interfaces/Time stamp\nstring frame_id";
+const TIME_DEFINITION: &str = "MSG: builtin_interfaces/Time\nint32 sec\nuint32 nanosec";
+const ROI_DEFINITION: &str = "MSG: sensor_msgs/RegionOfInterest\nuint32 x_offset\nuint32 y_offset\nuint32 height\nuint32 width\nbool do_rectify";
+
+pub(crate) const IMAGE_TYPE: &str = "sensor_msgs/msg/Image";
+const CAMERA_INFO_TYPE: &str = "sensor_msgs/msg/CameraInfo";
+pub(crate) const TIME_REFERENCE_TYPE: &str = "sensor_msgs/msg/TimeReference";
+
+/// Little endian CDR serialization (XCDR1, as used by the ROS 2 middlewares).
+struct Cdr {
+    buf: Vec<u8>,
+}
+
+impl Cdr {
+    fn new() -> Self {
+        // Encapsulation header: CDR_LE, no options
+        Self { buf: vec![0x00, 0x01, 0x00, 0x00] }
+    }
+
+    /// Alignment is relative to the end of the encapsulation header.
+    fn align(&mut self, size: usize) {
+        while !(self.buf.len() - 4).is_multiple_of(size) {
+            self.buf.push(0);
+        }
+    }
+
+    fn u8(&mut self, value: u8) {
+        self.buf.push(value);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.align(4);
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn i32(&mut self, value: i32) {
+        self.align(4);
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn f64(&mut self, value: f64) {
+        self.align(8);
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn string(&mut self, value: &str) {
+        self.u32(value.len() as u32 + 1);
+        self.buf.extend_from_slice(value.as_bytes());
+        self.buf.push(0);
+    }
+
+    fn bytes(&mut self, value: &[u8]) {
+        self.u32(value.len() as u32);
+        self.buf.extend_from_slice(value);
+    }
+
+    fn time(&mut self, realtime_ns: u64) {
+        self.i32((realtime_ns / 1_000_000_000) as i32);
+        self.u32((realtime_ns % 1_000_000_000) as u32);
+    }
+
+    fn header(&mut self, stamp_ns: u64, frame_id: &str) {
+        self.time(stamp_ns);
+        self.string(frame_id);
+    }
+}
+
+fn definition(fields: &str, dependencies: &[&str]) -> String {
+    let mut text = fields.to_string();
+    for dependency in dependencies {
+        text.push_str(&format!("\n{}\n{}", SEPARATOR, dependency));
+    }
+    text
+}
+
+pub(crate) fn time_reference(stamp_ns: u64, frame_id: &str, time_ref_ns: u64, source: &str) -> Vec<u8> {
+    let mut cdr = Cdr::new();
+    cdr.header(stamp_ns, frame_id);
+    cdr.time(time_ref_ns);
+    cdr.string(source);
+    cdr.buf
+}
+
+pub(crate) fn image(stamp_ns: u64, frame_id: &str, width: u32, height: u32, rgb: &[u8]) -> Vec<u8> {
+    raw_image(stamp_ns, frame_id, width, height, "rgb8", width * 3, rgb)
+}
+
+/// `sensor_msgs/msg/Image` of any encoding, `step` bytes per row.
+pub(crate) fn raw_image(stamp_ns: u64, frame_id: &str, width: u32, height: u32, encoding: &str, step: u32, data: &[u8]) -> Vec<u8> {
+    let mut cdr = Cdr::new();
+    cdr.header(stamp_ns, frame_id);
+    cdr.u32(height);
+    cdr.u32(width);
+    cdr.string(encoding);
+    cdr.u8(0);
+    cdr.u32(step);
+    cdr.bytes(data);
+    cdr.buf
+}
+
+/// Adds the `ros2msg` schema of `TIME_REFERENCE_TYPE`; returns its id.
+pub(crate) fn add_time_reference_schema(writer: &mut McapWriter) -> io::Result<u16> {
+    writer.add_schema(
+        TIME_REFERENCE_TYPE,
+        "ros2msg",
+        definition("std_msgs/Header header\nbuiltin_interfaces/Time time_ref\nstring source", &[HEADER_DEFINITION, TIME_DEFINITION]).as_bytes(),
+    )
+}
+
+/// Adds the `ros2msg` schema of `IMAGE_TYPE`; returns its id.
+pub(crate) fn add_image_schema(writer: &mut McapWriter) -> io::Result<u16> {
+    writer.add_schema(
+        IMAGE_TYPE,
+        "ros2msg",
+        definition(
+            "std_msgs/Header header\nuint32 height\nuint32 width\nstring encoding\nuint8 is_bigendian\nuint32 step\nuint8[] data",
+            &[HEADER_DEFINITION, TIME_DEFINITION],
+        )
+        .as_bytes(),
+    )
+}
+
+/// Camera info of a frame; without intrinsics K stays all-zero, which marks an uncalibrated camera in ROS.
+fn camera_info(stamp_ns: u64, frame_id: &str, width: u32, height: u32, intrinsics: Option<&CameraIntrinsics>) -> Vec<u8> {
+    let mut cdr = Cdr::new();
+    cdr.header(stamp_ns, frame_id);
+    cdr.u32(height);
+    cdr.u32(width);
+    match intrinsics {
+        Some(intrinsics) => {
+            cdr.string(&intrinsics.distortion_model);
+            cdr.u32(intrinsics.d.len() as u32);
+            for &value in intrinsics.d.iter().chain(&intrinsics.k).chain(&intrinsics.r).chain(&intrinsics.p) {
+                cdr.f64(value);
+            }
+        }
+        None => {
+            cdr.string("");
+            // d
+            cdr.u32(0);
+            // k, r, p
+            for _ in 0..9 + 9 + 12 {
+                cdr.f64(0.0);
+            }
+        }
+    }
+    // binning_x, binning_y
+    cdr.u32(0);
+    cdr.u32(0);
+    // roi: x_offset, y_offset, height, width, do_rectify
+    for _ in 0..4 {
+        cdr.u32(0);
+    }
+    cdr.u8(0);
+    cdr.buf
+}
+
+/// What was exported for one session.
+#[derive(Debug, Clone)]
+pub struct BagCamera {
+    /// Session directory name.
+    pub session: String,
+    /// Topic namespace, e.g. `/cam0`.
+    pub namespace: String,
+    pub triggers: u64,
+    pub images: u64,
+    /// The session has a `camera_info.yaml`.
+    pub calibrated: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct BagSummary {
+    pub cameras: Vec<BagCamera>,
+    pub messages: u64,
+    /// First and last hw_ts in the bag.
+    pub start_ns: u64,
+    pub end_ns: u64,
+}
+
+struct Topic {
+    name: String,
+    kind: &'static str,
+    channel: u16,
+    messages: u64,
+}
+
+/// Writes the sessions (one camera each) as a rosbag2 bag directory `bag_dir`.
+pub fn export_bag(session_dirs: &[&Path], bag_dir: &Path) -> Result<BagSummary, Box<dyn std::error::Error>> {
+    let bag_name = bag_dir.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_else(|| "bag".to_string());
+    let storage_file = format!("{}_0.mcap", bag_name);
+    fs::create_dir_all(bag_dir)?;
+    let mut writer = McapWriter::create(&bag_dir.join(&storage_file), "ros2")?;
+    let time_reference_schema = add_time_reference_schema(&mut writer)?;
+    let image_schema = add_image_schema(&mut writer)?;
+    let camera_info_schema = writer.add_schema(
+        CAMERA_INFO_TYPE,
+        "ros2msg",
+        definition(
+            "std_msgs/Header header\nuint32 height\nuint32 width\nstring distortion_model\nfloat64[] d\nfloat64[9] k\nfloat64[9] r\nfloat64[12] p\nuint32 binning_x\nuint32 binning_y\nRegionOfInterest roi",
+            &[HEADER_DEFINITION, TIME_DEFINITION, ROI_DEFINITION],
+        )
+        .as_bytes(),
+    )?;
+
+    // All records of all sessions in hw_ts order, so the bag plays back in trigger order
+    let mut cameras = Vec::new();
+    let mut calibrations = Vec::new();
+    let mut topics = Vec::new();
+    let mut timeline: Vec<(usize, MatchRecord)> = Vec::new();
+    for (index, dir) in session_dirs.iter().enumerate() {
+        let namespace = format!("/cam{}", index);
+        let has_frames = dir.join(FRAMES_DIR).is_dir();
+        for (suffix, kind, schema) in [
+            ("trigger", TIME_REFERENCE_TYPE, time_reference_schema),
+            ("image_raw", IMAGE_TYPE, image_schema),
+            ("camera_info", CAMERA_INFO_TYPE, camera_info_schema),
+        ] {
+            if kind != TIME_REFERENCE_TYPE && !has_frames {
+                continue;
+            }
+            let name = format!("{}/{}", namespace, suffix);
+            let channel = writer.add_channel(schema, &name, "cdr")?;
+            topics.push(Topic { name, kind, channel, messages: 0 });
+        }
+        let calibration = intrinsics::read_session(dir)?;
+        cameras.push(BagCamera {
+            session: dir.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_else(|| dir.display().to_string()),
+            namespace,
+            triggers: 0,
+            images: 0,
+            calibrated: calibration.is_some(),
+        });
+        calibrations.push(calibration);
+        let mut records = session::read_records(dir)?;
+        records.dedup_by_key(|record| record.trigger.frame_id);
+        timeline.extend(records.into_iter().map(|record| (index, record)));
+    }
+    timeline.sort_by_key(|(index, record)| (record.trigger.hw_timestamp_ns, *index));
+
+    for (index, record) in &timeline {
+        let CameraTrigger { frame_id: trigger_id, hw_timestamp_ns: hw_ts, publish_timestamp_ns: pub_ts, .. } = record.trigger;
+        let frame_id = format!("cam{}", index);
+        let namespace = cameras[*index].namespace.clone();
+        let mut write = |suffix: &str, data: Vec<u8>| -> io::Result<()> {
+            let name = format!("{}/{}", namespace, suffix);
+            let topic = topics.iter_mut().find(|topic| topic.name == name).expect("topic added above");
+            topic.messages += 1;
+            writer.write_message(topic.channel, hw_ts, hw_ts, &data)
+        };
+        write("trigger", time_reference(hw_ts, &frame_id, pub_ts, &trigger_id.to_string()))?;
+        cameras[*index].triggers += 1;
+        if let Some((width, height, rgb)) = session::read_frame(session_dirs[*index], trigger_id)? {
+            write("image_raw", image(hw_ts, &frame_id, width, height, &rgb))?;
+            write("camera_info", camera_info(hw_ts, &frame_id, width, height, calibrations[*index].as_ref()))?;
+            cameras[*index].images += 1;
+        }
+    }
+    writer.finish()?;
+
+    let summary = BagSummary {
+        cameras,
+        messages: topics.iter().map(|topic| topic.messages).sum(),
+        start_ns: timeline.first().map(|(_, record)| record.trigger.hw_timestamp_ns).unwrap_or(0),
+        end_ns: timeline.last().map(|(_, record)| record.trigger.hw_timestamp_ns).unwrap_or(0),
+    };
+    fs::write(bag_dir.join(METADATA_FILE), metadata_yaml(&summary, &topics, &storage_file))?;
+    Ok(summary)
+}
+
+fn metadata_yaml(summary: &BagSummary, topics: &[Topic], storage_file: &str) -> String {
+    let duration_ns = summary.end_ns - summary.start_ns;
+    let mut yaml = format!(
+        "rosbag2_bagfile_information:\n  version: 5\n  storage_identifier: mcap\n  duration:\n    nanoseconds: {}\n  starting_time:\n    nanoseconds_since_epoch: {}\n  message_count: {}\n  topics_with_message_count:\n",
+        duration_ns, summary.start_ns, summary.messages
+    );
+    for topic in topics {
+        yaml.push_str(&format!(
+            "    - topic_metadata:\n        name: {}\n        type: {}\n        serialization_format: cdr\n        offered_qos_profiles: \"\"\n      message_count: {}\n",
+            topic.name, topic.kind, topic.messages
+        ));
+    }
+    yaml.push_str(&format!(
+        "  compression_format: \"\"\n  compression_mode: \"\"\n  relative_file_paths:\n    - {}\n  files:\n    - path: {}\n      starting_time:\n        nanoseconds_since_epoch: {}\n      duration:\n        nanoseconds: {}\n      message_count: {}\n",
+        storage_file, storage_file, summary.start_ns, duration_ns, summary.messages
+    ));
+    yaml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cdr_aligns_after_the_encapsulation_header() {
+        let mut cdr = Cdr::new();
+        cdr.u8(1);
+        cdr.f64(0.5);
+        cdr.u8(2);
+        cdr.u32(3);
+        let mut expected = vec![0x00, 0x01, 0x00, 0x00, 1, 0, 0, 0, 0, 0, 0, 0];
+        expected.extend_from_slice(&0.5f64.to_le_bytes());
+        expected.extend_from_slice(&[2, 0, 0, 0, 3, 0, 0, 0]);
+        assert_eq!(cdr.buf, expected);
+    }
+
+    #[test]
+    fn time_reference_pads_the_frame_id() {
+        let message = time_reference(1_500_000_000, "cam0", 2_000_000_001, "7");
+        let mut expected = vec![0x00, 0x01, 0x00, 0x00];
+        expected.extend_from_slice(&1i32.to_le_bytes());
+        expected.extend_from_slice(&500_000_000u32.to_le_bytes());
+        expected.extend_from_slice(&5u32.to_le_bytes());
+        expected.extend_from_slice(b"cam0\0\0\0\0");
+        expected.extend_from_slice(&2i32.to_le_bytes());
+        expected.extend_from_slice(&1u32.to_le_bytes());
+        expected.extend_from_slice(&2u32.to_le_bytes());
+        expected.extend_from_slice(b"7\0");
+        assert_eq!(message, expected);
+    }
+}
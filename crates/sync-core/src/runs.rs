@@ -0,0 +1,258 @@
+// Persistent per-run summary statistics and run-to-run comparison.
+//
+// With `--runs-dir <dir>` every run keeps its summary in
+// `<dir>/<config_hash>/<created_ns>.toml`, so runs with identical settings
+// are grouped and a cable, driver or parameter change shows up as a new
+// directory. `compare` diffs two summaries and hints whether a difference is
+// larger than the run-to-run noise (two-sample z-score over the recorded
+// means, standard deviations and counts).
+
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::latency::LatencyBreakdown;
+use crate::manifest::{quoted, SessionManifest};
+use crate::stats::StatsSummary;
+
+/// Differences with a z-score above this are reported as significant.
+const SIGNIFICANT_Z: f64 = 2.0;
+/// Fewer samples per run make the significance hint unreliable.
+const MIN_SAMPLES: u64 = 30;
+
+#[derive(Debug, Clone)]
+pub struct RunSummary {
+    pub binary: String,
+    pub config_hash: String,
+    pub created_ns: u64,
+    /// Frames that went through matching.
+    pub frames: u64,
+    pub matched: u64,
+    /// Latency statistics per stage (see `LatencyBreakdown`).
+    pub stages: Vec<(String, StatsSummary)>,
+}
+
+impl RunSummary {
+    pub fn new(manifest: &SessionManifest) -> Self {
+        Self {
+            binary: manifest.binary().to_string(),
+            config_hash: manifest.config_hash(),
+            created_ns: manifest.created_ns(),
+            frames: 0,
+            matched: 0,
+            stages: Vec::new(),
+        }
+    }
+
+    pub fn update(&mut self, frames: u64, matched: u64, latency: &LatencyBreakdown) {
+        self.frames = frames;
+        self.matched = matched;
+        self.stages = latency
+            .snapshot()
+            .into_iter()
+            .map(|(name, summary)| (name.to_string(), summary))
+            .collect();
+    }
+
+    pub fn match_rate(&self) -> f64 {
+        if self.frames == 0 {
+            0.0
+        } else {
+            self.matched as f64 / self.frames as f64
+        }
+    }
+
+    /// Writes (or overwrites) `<runs_dir>/<config_hash>/<created_ns>.toml`.
+    pub fn write(&self, runs_dir: &Path) -> std::io::Result<PathBuf> {
+        let dir = runs_dir.join(&self.config_hash);
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("{}.toml", self.created_ns));
+        fs::write(&path, self.to_toml())?;
+        Ok(path)
+    }
+
+    fn to_toml(&self) -> String {
+        let mut text = String::new();
+        let _ = writeln!(text, "binary = {}", quoted(&self.binary));
+        let _ = writeln!(text, "config_hash = {}", quoted(&self.config_hash));
+        let _ = writeln!(text, "created_ns = {}", self.created_ns);
+        let _ = writeln!(text, "frames = {}", self.frames);
+        let _ = writeln!(text, "matched = {}", self.matched);
+        for (name, stats) in &self.stages {
+            let _ = writeln!(text, "\n[stages.{}]", quoted(name));
+            let _ = writeln!(text, "count = {}", stats.count);
+            for (key, value) in [
+                ("mean_ms", stats.mean_ms),
+                ("std_ms", stats.std_ms),
+                ("p50_ms", stats.p50_ms),
+                ("p95_ms", stats.p95_ms),
+                ("min_ms", stats.min_ms),
+                ("max_ms", stats.max_ms),
+            ] {
+                let _ = writeln!(text, "{} = {:?}", key, value);
+            }
+        }
+        text
+    }
+
+    /// Reads a summary file, or the latest summary of a config hash directory.
+    pub fn read(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let file = if path.is_dir() { latest_summary(path)? } else { path.to_path_buf() };
+        let table: toml::Table = fs::read_to_string(&file)?.parse()?;
+        let string = |key: &str| table.get(key).and_then(|v| v.as_str()).unwrap_or_default().to_string();
+        let integer = |key: &str| table.get(key).and_then(|v| v.as_integer()).unwrap_or(0) as u64;
+        let mut stages = Vec::new();
+        if let Some(table) = table.get("stages").and_then(|v| v.as_table()) {
+            for (name, stats) in table {
+                let float = |key: &str| stats.get(key).and_then(|v| v.as_float()).unwrap_or(0.0);
+                stages.push((
+                    name.clone(),
+                    StatsSummary {
+                        count: stats.get("count").and_then(|v| v.as_integer()).unwrap_or(0) as u64,
+                        mean_ms: float("mean_ms"),
+                        std_ms: float("std_ms"),
+                        p50_ms: float("p50_ms"),
+                        p95_ms: float("p95_ms"),
+                        min_ms: float("min_ms"),
+                        max_ms: float("max_ms"),
+                    },
+                ));
+            }
+        }
+        Ok(Self {
+            binary: string("binary"),
+            config_hash: string("config_hash"),
+            created_ns: integer("created_ns"),
+            frames: integer("frames"),
+            matched: integer("matched"),
+            stages,
+        })
+    }
+
+    fn stage(&self, name: &str) -> Option<&StatsSummary> {
+        self.stages.iter().find(|(stage, _)| stage == name).map(|(_, stats)| stats)
+    }
+}
+
+fn latest_summary(dir: &Path) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+        .max()
+        .ok_or_else(|| format!("{}: no run summaries", dir.display()).into())
+}
+
+fn significance(z: f64, enough_samples: bool) -> String {
+    if !enough_samples {
+        "too few samples".to_string()
+    } else if z.abs() >= SIGNIFICANT_Z {
+        format!("significant, z={:.1}", z)
+    } else {
+        format!("within noise, z={:.1}", z)
+    }
+}
+
+/// Human readable diff of two runs, `a` being the baseline.
+pub fn compare(a: &RunSummary, b: &RunSummary) -> String {
+    let mut report = format!(
+        "COMPARE {} ({}, {}) -> {} ({}, {})",
+        a.created_ns,
+        a.binary,
+        &a.config_hash[..a.config_hash.len().min(12)],
+        b.created_ns,
+        b.binary,
+        &b.config_hash[..b.config_hash.len().min(12)]
+    );
+    if a.config_hash == b.config_hash {
+        report.push_str("\n  same configuration (repeat run)");
+    }
+
+    // Two-proportion z-test on the match rates
+    let (rate_a, rate_b) = (a.match_rate(), b.match_rate());
+    let pooled = (a.matched + b.matched) as f64 / (a.frames + b.frames).max(1) as f64;
+    let rate_se = (pooled * (1.0 - pooled) * (1.0 / a.frames.max(1) as f64 + 1.0 / b.frames.max(1) as f64)).sqrt();
+    let rate_z = if rate_se > 0.0 { (rate_b - rate_a) / rate_se } else { 0.0 };
+    let _ = write!(
+        report,
+        "\n  {:<26} {:>7.1}% -> {:>7.1}%  {:+.1}pp  ({})",
+        "match rate",
+        rate_a * 100.0,
+        rate_b * 100.0,
+        (rate_b - rate_a) * 100.0,
+        significance(rate_z, a.frames >= MIN_SAMPLES && b.frames >= MIN_SAMPLES)
+    );
+
+    for (name, stats_a) in &a.stages {
+        let Some(stats_b) = b.stage(name) else {
+            continue;
+        };
+        // Welch z-score of the mean difference
+        let se = (stats_a.std_ms.powi(2) / stats_a.count.max(1) as f64 + stats_b.std_ms.powi(2) / stats_b.count.max(1) as f64).sqrt();
+        let z = if se > 0.0 { (stats_b.mean_ms - stats_a.mean_ms) / se } else { 0.0 };
+        let _ = write!(
+            report,
+            "\n  {:<26} mean {:>7.2} -> {:>7.2}ms  {:+.2}ms  p50 {:.2} -> {:.2}  p95 {:.2} -> {:.2}  ({})",
+            name,
+            stats_a.mean_ms,
+            stats_b.mean_ms,
+            stats_b.mean_ms - stats_a.mean_ms,
+            stats_a.p50_ms,
+            stats_b.p50_ms,
+            stats_a.p95_ms,
+            stats_b.p95_ms,
+            significance(z, stats_a.count >= MIN_SAMPLES && stats_b.count >= MIN_SAMPLES)
+        );
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::latency::FrameTiming;
+
+    const MS: u64 = 1_000_000;
+
+    // A run of 100 frames matched `total_ms` (plus 0..1ms of noise) after their trigger
+    fn run(created_ns: u64, total_ms: u64, matched: u64) -> RunSummary {
+        let mut latency = LatencyBreakdown::default();
+        for frame in 0..100 {
+            let trigger_ns = frame * 33 * MS;
+            let match_ns = trigger_ns + total_ms * MS + frame % 10 * MS / 10;
+            latency.record(&FrameTiming { trigger_ns, driver_ns: None, dequeue_ns: match_ns, match_ns });
+        }
+        let mut manifest = SessionManifest::new("subscriber");
+        manifest.config("tolerance_ms", 5);
+        let mut summary = RunSummary::new(&manifest);
+        summary.created_ns = created_ns;
+        summary.update(100, matched, &latency);
+        summary
+    }
+
+    #[test]
+    fn summaries_are_grouped_by_config_and_read_back() {
+        let runs_dir = std::env::temp_dir().join(format!("camera_sync_runs_{}", std::process::id()));
+        let first = run(1_000, 20, 95);
+        let path = first.write(&runs_dir).unwrap();
+        assert_eq!(path, runs_dir.join(&first.config_hash).join("1000.toml"));
+        run(2_000, 30, 90).write(&runs_dir).unwrap();
+
+        let read = RunSummary::read(&path).unwrap();
+        let latest = RunSummary::read(&runs_dir.join(&first.config_hash)).unwrap();
+        fs::remove_dir_all(&runs_dir).unwrap();
+
+        assert_eq!((read.binary.as_str(), read.created_ns, read.frames, read.matched), ("subscriber", 1_000, 100, 95));
+        assert_eq!(read.stage("total").unwrap().count, 100);
+        assert_eq!(read.stage("total").unwrap().mean_ms, first.stage("total").unwrap().mean_ms);
+        assert_eq!(latest.created_ns, 2_000);
+    }
+
+    #[test]
+    fn compare_tells_significant_changes_from_noise() {
+        let report = compare(&run(1_000, 20, 95), &run(2_000, 30, 95));
+        assert!(report.contains("same configuration (repeat run)"), "{}", report);
+        assert!(report.contains("+0.0pp  (within noise, z=0.0)"), "{}", report);
+        let total = report.lines().find(|line| line.trim_start().starts_with("total")).unwrap();
+        assert!(total.contains("+10.00ms") && total.contains("significant"), "{}", total);
+    }
+}
@@ -0,0 +1,155 @@
+// Canonical serde schema of the public types (`serde` feature).
+//
+// JSON/CBOR logs, REST APIs and config files all use these field names. They
+// are part of the schema: a renamed, removed or retyped field bumps
+// `SCHEMA_VERSION`, added optional fields do not. Top-level documents carry
+// the version through `Versioned`, e.g.
+//
+//   {"schema_version":1,"trigger":{"frame_id":7,"hw_timestamp_ns":...},...}
+//
+// Version 1 field names:
+//   CameraTrigger     frame_id, hw_timestamp_ns, publish_timestamp_ns
+//   TriggerSignature  key_id, signature (hex, empty when unsigned), timestamp_source (optional),
+//                     clock_domain (optional), clock_policy (optional), trace_origin (optional),
+//                     phc_offset_ns (optional)
+//   SignatureStatus   "unsigned" | "valid" | "invalid"
+//   MatchRecord       trigger, v4l2_timestamp_ns, total_latency_ms, score_ms, signature_status, signature, metadata (optional map)
+//   StatsSummary      count, mean_ms, std_ms (optional), p50_ms, p95_ms, min_ms, max_ms
+//   SessionManifest   binary, created_ns, crate_version, git_revision, config, cameras, calibration
+//   CameraEntry       selector, device, index, name, serial, bus_path
+//   StrobeCommand     trigger_id, intensity, channel, on
+//   PoseSample        timestamp_ns, pan_deg, tilt_deg, zoom
+//   EncoderSample     timestamp_ns, ticks
+//
+// `sync-capture` follows the same version for its config types:
+//   CameraSelector    "index:N" | "serial:X" | "bus:X"
+//   BufferTuning      buffer_count, memory ("mmap" | "userptr"), frame_interval_ms, max_queue_delay_ms, reason
+
+use serde::{Deserialize, Serialize};
+
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// A top-level document tagged with the schema version it was written with.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Versioned<T> {
+    pub schema_version: u32,
+    #[serde(flatten)]
+    pub value: T,
+}
+
+impl<T> Versioned<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            value,
+        }
+    }
+
+    /// Unwraps the value, rejecting documents written with a newer schema.
+    pub fn into_current(self) -> Result<T, String> {
+        if self.schema_version > SCHEMA_VERSION {
+            return Err(format!(
+                "schema version {} is newer than the supported version {}",
+                self.schema_version, SCHEMA_VERSION
+            ));
+        }
+        Ok(self.value)
+    }
+}
+
+/// Serializes a `CameraTrigger` without its layout fields, which follow from the id and this build.
+///
+/// Use as `#[serde(with = "sync_core::schema::camera_trigger")]`.
+pub mod camera_trigger {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::CameraTrigger;
+
+    #[derive(Serialize, Deserialize)]
+    struct Fields {
+        frame_id: u64,
+        hw_timestamp_ns: u64,
+        publish_timestamp_ns: u64,
+    }
+
+    pub fn serialize<S: Serializer>(trigger: &CameraTrigger, serializer: S) -> Result<S::Ok, S::Error> {
+        Fields {
+            frame_id: trigger.frame_id,
+            hw_timestamp_ns: trigger.hw_timestamp_ns,
+            publish_timestamp_ns: trigger.publish_timestamp_ns,
+        }
+        .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<CameraTrigger, D::Error> {
+        let fields = Fields::deserialize(deserializer)?;
+        Ok(CameraTrigger::new(fields.frame_id, fields.hw_timestamp_ns, fields.publish_timestamp_ns))
+    }
+}
+
+/// Serializes ordered `(key, value)` pairs as a map, keeping their order.
+pub(crate) mod pairs {
+    use serde::de::{MapAccess, Visitor};
+    use serde::ser::SerializeMap;
+    use serde::{Deserializer, Serializer};
+    use std::fmt;
+
+    pub fn serialize<S: Serializer>(pairs: &[(String, String)], serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(pairs.len()))?;
+        for (key, value) in pairs {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<(String, String)>, D::Error> {
+        struct PairsVisitor;
+
+        impl<'de> Visitor<'de> for PairsVisitor {
+            type Value = Vec<(String, String)>;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "a map of strings")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut pairs = Vec::new();
+                while let Some(entry) = map.next_entry()? {
+                    pairs.push(entry);
+                }
+                Ok(pairs)
+            }
+        }
+
+        deserializer.deserialize_map(PairsVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::de::value::{Error, MapDeserializer};
+
+    use super::*;
+    use crate::CameraTrigger;
+
+    #[test]
+    fn documents_of_a_newer_schema_are_rejected() {
+        assert_eq!(Versioned::new("trigger").into_current(), Ok("trigger"));
+        let older = Versioned { schema_version: 0, value: "trigger" };
+        assert_eq!(older.into_current(), Ok("trigger"));
+        let newer = Versioned { schema_version: SCHEMA_VERSION + 1, value: "trigger" };
+        assert_eq!(newer.into_current(), Err(format!("schema version {} is newer than the supported version {}", SCHEMA_VERSION + 1, SCHEMA_VERSION)));
+    }
+
+    #[test]
+    fn triggers_are_read_from_their_named_fields() {
+        let fields = [("frame_id", 7u64), ("hw_timestamp_ns", 1_000), ("publish_timestamp_ns", 1_050)];
+        let trigger = camera_trigger::deserialize(MapDeserializer::<_, Error>::new(fields.into_iter())).unwrap();
+        assert_eq!((trigger.frame_id, trigger.hw_timestamp_ns, trigger.publish_timestamp_ns), (7, 1_000, 1_050));
+        // The layout fields are not part of the schema
+        assert_eq!(trigger, CameraTrigger::new(7, 1_000, 1_050));
+
+        let missing = [("frame_id", 7u64), ("hw_timestamp_ns", 1_000)];
+        assert!(camera_trigger::deserialize(MapDeserializer::<_, Error>::new(missing.into_iter())).is_err());
+    }
+}
@@ -0,0 +1,222 @@
+// Trigger fan-out profiles for non-camera sensors.
+//
+// Radar front ends and ToF depth sensors have their own trigger-in pins but
+// usually run at a fraction of the camera rate and fire a fixed time after
+// the camera exposure. A `SensorProfile` describes such a sensor: the
+// publisher derives its trigger stream from the master triggers (every
+// `divider`-th trigger, `offset` later) on a per-sensor service, and a
+// subscriber started with the same profile matches the sensor's frames
+// against that stream with the sensor's expected latency. Trigger ids are
+// kept, so frames of all sensors can be associated by id.
+//
+// Profiles are given as comma separated `key=value` pairs:
+//
+//   name=radar0,kind=radar,divider=2,offset_ms=1.5,latency_ms=12
+//
+// Multi-camera rigs (several GMSL cameras on one deserializer) fan out the
+// same way: `camera_fan_out` gives camera N the stream `camN` with every
+// trigger and its own offset, so each camera process subscribes to its own
+// service and the cameras can be synchronized independently.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::CameraTrigger;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SensorKind {
+    Camera,
+    Radar,
+    Tof,
+}
+
+impl fmt::Display for SensorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SensorKind::Camera => write!(f, "camera"),
+            SensorKind::Radar => write!(f, "radar"),
+            SensorKind::Tof => write!(f, "tof"),
+        }
+    }
+}
+
+impl FromStr for SensorKind {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "camera" => Ok(SensorKind::Camera),
+            "radar" => Ok(SensorKind::Radar),
+            "tof" => Ok(SensorKind::Tof),
+            other => Err(format!("unknown sensor kind '{}' (expected camera, radar or tof)", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SensorProfile {
+    /// Also names the sensor's trigger service.
+    pub name: String,
+    pub kind: SensorKind,
+    /// Every `divider`-th master trigger fires this sensor.
+    pub divider: u32,
+    /// Delay of the sensor's trigger-in edge after the master trigger.
+    pub offset_ns: i64,
+    /// Expected trigger to frame delivery latency of the sensor.
+    pub latency_ms: f64,
+}
+
+impl fmt::Display for SensorProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "name={},kind={},divider={},offset_ms={},latency_ms={}",
+            self.name,
+            self.kind,
+            self.divider,
+            self.offset_ns as f64 / 1e6,
+            self.latency_ms
+        )
+    }
+}
+
+impl FromStr for SensorProfile {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut name = None;
+        let mut kind = SensorKind::Camera;
+        let mut divider = 1;
+        let mut offset_ns = 0;
+        let mut latency_ms = 0.0;
+        for pair in text.split(',') {
+            let (key, value) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("invalid sensor profile entry '{}' (expected key=value)", pair))?;
+            let invalid = || format!("invalid sensor profile value {}='{}'", key, value);
+            match key.trim() {
+                "name" => name = Some(value.trim().to_string()),
+                "kind" => kind = value.trim().parse()?,
+                "divider" => divider = value.trim().parse::<u32>().ok().filter(|d| *d > 0).ok_or_else(invalid)?,
+                "offset_ms" => offset_ns = (value.trim().parse::<f64>().map_err(|_| invalid())? * 1e6) as i64,
+                "latency_ms" => latency_ms = value.trim().parse::<f64>().map_err(|_| invalid())?,
+                other => return Err(format!("unknown sensor profile key '{}'", other)),
+            }
+        }
+        let name = name
+            .filter(|name| !name.is_empty() && !name.contains('/'))
+            .ok_or("sensor profile needs a name without '/'")?;
+        Ok(Self {
+            name,
+            kind,
+            divider,
+            offset_ns,
+            latency_ms,
+        })
+    }
+}
+
+impl SensorProfile {
+    /// Camera `index` of a multi-camera fan-out: every trigger, `offset_ns` later, on `camera_name(index)`.
+    pub fn camera(index: u32, offset_ns: i64) -> Self {
+        Self {
+            name: camera_name(index),
+            kind: SensorKind::Camera,
+            divider: 1,
+            offset_ns,
+            latency_ms: 0.0,
+        }
+    }
+
+    /// The sensor's trigger for a master trigger, `None` if this trigger does not fire the sensor.
+    pub fn fan_out(&self, trigger: CameraTrigger) -> Option<CameraTrigger> {
+        if !trigger.frame_id.saturating_sub(1).is_multiple_of(self.divider as u64) {
+            return None;
+        }
+        Some(CameraTrigger {
+            hw_timestamp_ns: trigger.hw_timestamp_ns.saturating_add_signed(self.offset_ns),
+            ..trigger
+        })
+    }
+
+    /// Trigger rate of the sensor for a master trigger rate.
+    pub fn rate(&self, master_fps: u32) -> u32 {
+        (master_fps / self.divider).max(1)
+    }
+}
+
+/// Name of camera `index`'s fanned-out trigger stream, e.g. `cam0`.
+pub fn camera_name(index: u32) -> String {
+    format!("cam{}", index)
+}
+
+/// Streams of `cameras` cameras, with comma separated per-camera offsets in ms (missing ones are 0).
+pub fn camera_fan_out(cameras: u32, offsets_ms: Option<&str>) -> Result<Vec<SensorProfile>, String> {
+    let offsets_ns = match offsets_ms {
+        Some(list) => list
+            .split(',')
+            .map(|ms| ms.trim().parse::<f64>().map(|ms| (ms * 1e6) as i64).map_err(|_| format!("invalid camera offset '{}'", ms)))
+            .collect::<Result<Vec<_>, _>>()?,
+        None => Vec::new(),
+    };
+    if offsets_ns.len() > cameras as usize {
+        return Err(format!("{} camera offsets for {} cameras", offsets_ns.len(), cameras));
+    }
+    Ok((0..cameras).map(|index| SensorProfile::camera(index, offsets_ns.get(index as usize).copied().unwrap_or(0))).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trigger(frame_id: u64) -> CameraTrigger {
+        CameraTrigger::new(frame_id, frame_id * 10_000_000, frame_id * 10_000_000 + 50_000)
+    }
+
+    #[test]
+    fn profiles_parse_and_display_their_syntax() {
+        let text = "name=radar0,kind=radar,divider=2,offset_ms=1.5,latency_ms=12";
+        let profile: SensorProfile = text.parse().unwrap();
+        assert_eq!((profile.kind, profile.divider, profile.offset_ns), (SensorKind::Radar, 2, 1_500_000));
+        assert_eq!(profile.to_string(), text);
+        assert!("kind=tof".parse::<SensorProfile>().unwrap_err().contains("needs a name"));
+        assert!("name=a/b".parse::<SensorProfile>().unwrap_err().contains("needs a name"));
+        assert!("name=r,divider=0".parse::<SensorProfile>().unwrap_err().contains("divider='0'"));
+        assert!("name=r,kind=lidar".parse::<SensorProfile>().unwrap_err().contains("unknown sensor kind"));
+    }
+
+    #[test]
+    fn divided_sensors_fire_on_every_nth_trigger_with_their_offset() {
+        let profile: SensorProfile = "name=tof0,kind=tof,divider=3,offset_ms=-0.5".parse().unwrap();
+        let fired: Vec<CameraTrigger> = (1..=7).filter_map(|id| profile.fan_out(trigger(id))).collect();
+        assert_eq!(fired.iter().map(|t| t.frame_id).collect::<Vec<_>>(), [1, 4, 7]);
+        assert_eq!(fired[1].hw_timestamp_ns, 40_000_000 - 500_000);
+        assert_eq!(fired[1].publish_timestamp_ns, trigger(4).publish_timestamp_ns);
+        assert_eq!(profile.rate(30), 10);
+        assert_eq!(profile.rate(2), 1);
+    }
+
+    #[test]
+    fn cameras_get_their_own_offset_streams() {
+        let cameras = camera_fan_out(3, Some("0, 0.5")).unwrap();
+        let names: Vec<&str> = cameras.iter().map(|camera| camera.name.as_str()).collect();
+        assert_eq!(names, ["cam0", "cam1", "cam2"]);
+        assert!(cameras.iter().all(|camera| camera.kind == SensorKind::Camera && camera.divider == 1));
+
+        // Every trigger, ids kept, shifted by the camera's offset (missing offsets are 0)
+        let trigger = CameraTrigger::new(7, 1_000_000_000, 1_000_200_000);
+        assert_eq!(cameras[0].fan_out(trigger), Some(trigger));
+        assert_eq!(cameras[1].fan_out(trigger), Some(CameraTrigger::new(7, 1_000_500_000, 1_000_200_000)));
+        assert_eq!(cameras[2].fan_out(trigger), Some(trigger));
+        assert_eq!(cameras[1].to_string(), "name=cam1,kind=camera,divider=1,offset_ms=0.5,latency_ms=0");
+        assert_eq!(cameras[1].to_string().parse::<SensorProfile>().unwrap(), cameras[1]);
+    }
+
+    #[test]
+    fn camera_offsets_must_fit_the_cameras() {
+        assert!(camera_fan_out(0, None).unwrap().is_empty());
+        assert_eq!(camera_fan_out(2, Some("0,1,2")).unwrap_err(), "3 camera offsets for 2 cameras");
+        assert_eq!(camera_fan_out(2, Some("0,x")).unwrap_err(), "invalid camera offset 'x'");
+        assert_eq!(SensorProfile::camera(4, -250_000).fan_out(CameraTrigger::new(1, 1_000_000, 0)), Some(CameraTrigger::new(1, 750_000, 0)));
+    }
+}
@@ -0,0 +1,311 @@
+// Recording of synchronization results into a session directory.
+//
+// A session is a plain directory (`--session-dir <dir>`) holding one CSV line
+// per synchronized frame. Records keep the full trigger together with its
+// signature, so a recorded dataset can be re-verified offline against the
+// publisher's public key (see the `verify_session` binary).
+//
+// `clock.csv` holds the clock mapping of the recording host (see `clockmap`),
+// which `merge_sessions` uses to align sessions recorded on several hosts.
+//
+// With frame saving enabled, `frames/<trigger_id>.ppm` keeps the image of every
+// synchronized frame as binary PPM (cheap to write at capture rate), which
+// `export_dataset` converts into dataset layouts.
+//
+// With a retention policy, frames below its confidence go to the
+// `quarantine/` sub-session or are not recorded (see `retention`).
+
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::retention::{RetentionAction, RetentionCounts, RetentionPolicy, QUARANTINE_DIR};
+use crate::signing::{SignatureStatus, TriggerSignature};
+use crate::CameraTrigger;
+
+pub const RECORDS_FILE: &str = "records.csv";
+pub const CLOCK_FILE: &str = "clock.csv";
+pub const FRAMES_DIR: &str = "frames";
+
+const RECORDS_HEADER: &str =
+    "trigger_id,hw_timestamp_ns,publish_timestamp_ns,v4l2_timestamp_ns,total_latency_ms,score_ms,signature_status,key_id,signature,metadata";
+
+/// One synchronized frame as stored in `records.csv`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MatchRecord {
+    #[cfg_attr(feature = "serde", serde(with = "crate::schema::camera_trigger"))]
+    pub trigger: CameraTrigger,
+    pub v4l2_timestamp_ns: u64,
+    pub total_latency_ms: f64,
+    pub score_ms: f64,
+    pub signature_status: SignatureStatus,
+    pub signature: TriggerSignature,
+    /// Per-frame metadata from other services (e.g. `strobe` = lighting state),
+    /// stored as `key=value;key=value`. Keys and values must not contain `,`, `;` or `=`.
+    #[cfg_attr(feature = "serde", serde(default, with = "crate::schema::pairs"))]
+    pub metadata: Vec<(String, String)>,
+}
+
+impl MatchRecord {
+    fn to_csv(&self) -> String {
+        format!(
+            "{},{},{},{},{:.3},{:.3},{},{:016x},{},{}",
+            self.trigger.frame_id,
+            self.trigger.hw_timestamp_ns,
+            self.trigger.publish_timestamp_ns,
+            self.v4l2_timestamp_ns,
+            self.total_latency_ms,
+            self.score_ms,
+            self.signature_status,
+            self.signature.key_id,
+            self.signature.signature_hex(),
+            self.metadata.iter().map(|(key, value)| format!("{}={}", key, value)).collect::<Vec<_>>().join(";")
+        )
+    }
+
+    fn from_csv(line: &str) -> Option<Self> {
+        let fields: Vec<&str> = line.split(',').collect();
+        // Sessions recorded before the metadata column have 9 fields
+        if fields.len() != 9 && fields.len() != 10 {
+            return None;
+        }
+        let signature_status = match fields[6] {
+            "unsigned" => SignatureStatus::Unsigned,
+            "valid" => SignatureStatus::Valid,
+            _ => SignatureStatus::Invalid,
+        };
+        let signature = TriggerSignature::from_hex(u64::from_str_radix(fields[7], 16).ok()?, fields[8])?;
+        Some(Self {
+            trigger: CameraTrigger::new(fields[0].parse().ok()?, fields[1].parse().ok()?, fields[2].parse().ok()?),
+            v4l2_timestamp_ns: fields[3].parse().ok()?,
+            total_latency_ms: fields[4].parse().ok()?,
+            score_ms: fields[5].parse().ok()?,
+            signature_status,
+            signature,
+            metadata: fields
+                .get(9)
+                .map(|metadata| {
+                    metadata
+                        .split(';')
+                        .filter_map(|entry| entry.split_once('='))
+                        .map(|(key, value)| (key.to_string(), value.to_string()))
+                        .collect()
+                })
+                .unwrap_or_default(),
+        })
+    }
+}
+
+const CLOCK_HEADER: &str = "realtime_ns,monotonic_ns,offset_ns,uncertainty_ns";
+
+/// Offset of the recording host's CLOCK_REALTIME from the trigger publisher's clock at one point in time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockMapping {
+    /// Local CLOCK_REALTIME when the mapping was taken.
+    pub realtime_ns: u64,
+    /// Local CLOCK_MONOTONIC at the same instant, to map driver timestamps.
+    pub monotonic_ns: u64,
+    /// Local realtime minus publisher realtime.
+    pub offset_ns: i64,
+    pub uncertainty_ns: u64,
+}
+
+impl ClockMapping {
+    fn to_csv(self) -> String {
+        format!("{},{},{},{}", self.realtime_ns, self.monotonic_ns, self.offset_ns, self.uncertainty_ns)
+    }
+
+    fn from_csv(line: &str) -> Option<Self> {
+        let fields: Vec<&str> = line.split(',').collect();
+        if fields.len() != 4 {
+            return None;
+        }
+        Some(Self {
+            realtime_ns: fields[0].parse().ok()?,
+            monotonic_ns: fields[1].parse().ok()?,
+            offset_ns: fields[2].parse().ok()?,
+            uncertainty_ns: fields[3].parse().ok()?,
+        })
+    }
+}
+
+pub struct SessionRecorder {
+    dir: PathBuf,
+    records: BufWriter<File>,
+    clock: BufWriter<File>,
+    save_frames: bool,
+    retention: Option<RetentionPolicy>,
+    quarantine: Option<Box<SessionRecorder>>,
+    retained: RetentionCounts,
+}
+
+impl SessionRecorder {
+    /// Creates the session directory (if needed) and starts a fresh `records.csv`.
+    pub fn create(dir: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        fs::create_dir_all(dir)?;
+        let mut records = BufWriter::new(File::create(dir.join(RECORDS_FILE))?);
+        writeln!(records, "{}", RECORDS_HEADER)?;
+        let mut clock = BufWriter::new(File::create(dir.join(CLOCK_FILE))?);
+        writeln!(clock, "{}", CLOCK_HEADER)?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            records,
+            clock,
+            save_frames: false,
+            retention: None,
+            quarantine: None,
+            retained: RetentionCounts::default(),
+        })
+    }
+
+    /// Also stores the image of every recorded frame under `frames/`.
+    pub fn with_frames(mut self) -> std::io::Result<Self> {
+        fs::create_dir_all(self.dir.join(FRAMES_DIR))?;
+        self.save_frames = true;
+        Ok(self)
+    }
+
+    /// Records only frames the policy keeps; with quarantine, the others into
+    /// `quarantine/`, which saves frames if this session does (call after `with_frames`).
+    pub fn with_retention(mut self, policy: RetentionPolicy) -> Result<Self, Box<dyn std::error::Error>> {
+        if policy.quarantine {
+            let quarantine = Self::create(&self.dir.join(QUARANTINE_DIR))?;
+            self.quarantine = Some(Box::new(if self.save_frames { quarantine.with_frames()? } else { quarantine }));
+        }
+        self.retention = Some(policy);
+        Ok(self)
+    }
+
+    pub fn retention(&self) -> Option<RetentionPolicy> {
+        self.retention
+    }
+
+    pub fn retained(&self) -> RetentionCounts {
+        self.retained
+    }
+
+    /// What the retention policy does with a frame of `confidence`; `Keep` without a policy.
+    pub fn retention_action(&self, confidence: f64) -> RetentionAction {
+        self.retention.map_or(RetentionAction::Keep, |policy| policy.decide(confidence))
+    }
+
+    /// Counts a frame under `action` and returns the recorder it goes to:
+    /// this one, its quarantine, or none if it is discarded.
+    pub fn retain(&mut self, action: RetentionAction) -> Option<&mut SessionRecorder> {
+        self.retained.count(action);
+        match action {
+            RetentionAction::Keep => Some(self),
+            RetentionAction::Quarantine => self.quarantine.as_deref_mut(),
+            RetentionAction::Discard => None,
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn saves_frames(&self) -> bool {
+        self.save_frames
+    }
+
+    /// Appends a record. Flushed immediately so a crashed run keeps its data.
+    pub fn record(&mut self, record: &MatchRecord) -> std::io::Result<()> {
+        writeln!(self.records, "{}", record.to_csv())?;
+        self.records.flush()
+    }
+
+    /// Also into the quarantine, which is merged and analyzed on its own.
+    pub fn record_clock(&mut self, mapping: &ClockMapping) -> std::io::Result<()> {
+        if let Some(quarantine) = &mut self.quarantine {
+            quarantine.record_clock(mapping)?;
+        }
+        writeln!(self.clock, "{}", mapping.to_csv())?;
+        self.clock.flush()
+    }
+
+    /// Stores a packed RGB frame for `trigger_id`; does nothing unless frame saving is enabled.
+    pub fn record_frame(&self, trigger_id: u64, width: u32, height: u32, rgb: &[u8]) -> std::io::Result<()> {
+        if !self.save_frames {
+            return Ok(());
+        }
+        write_frame(&self.dir, trigger_id, width, height, rgb)
+    }
+}
+
+/// Stores a packed RGB frame for `trigger_id` in the session at `dir`, e.g. from a writer thread.
+pub fn write_frame(dir: &Path, trigger_id: u64, width: u32, height: u32, rgb: &[u8]) -> std::io::Result<()> {
+    let mut out = BufWriter::new(File::create(frame_path(dir, trigger_id))?);
+    write!(out, "P6\n{} {}\n255\n", width, height)?;
+    out.write_all(rgb)?;
+    out.flush()
+}
+
+pub fn frame_path(dir: &Path, trigger_id: u64) -> PathBuf {
+    dir.join(FRAMES_DIR).join(format!("{}.ppm", trigger_id))
+}
+
+/// A stored frame as (width, height, packed RGB).
+pub type StoredFrame = (u32, u32, Vec<u8>);
+
+/// Reads the stored frame of `trigger_id`; `None` if the session has no image for it.
+pub fn read_frame(dir: &Path, trigger_id: u64) -> Result<Option<StoredFrame>, Box<dyn std::error::Error>> {
+    let bytes = match fs::read(frame_path(dir, trigger_id)) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    // Header: magic, width, height and maxval separated by single whitespace
+    let mut fields = Vec::with_capacity(4);
+    let mut start = 0;
+    for (i, byte) in bytes.iter().enumerate() {
+        if byte.is_ascii_whitespace() {
+            fields.push(std::str::from_utf8(&bytes[start..i])?);
+            start = i + 1;
+            if fields.len() == 4 {
+                break;
+            }
+        }
+    }
+    let [magic, width, height, maxval] = fields[..] else {
+        return Err(format!("{}: truncated PPM header", frame_path(dir, trigger_id).display()).into());
+    };
+    if magic != "P6" || maxval != "255" {
+        return Err(format!("{}: not an 8-bit binary PPM", frame_path(dir, trigger_id).display()).into());
+    }
+    let (width, height): (u32, u32) = (width.parse()?, height.parse()?);
+    let rgb = bytes[start..].to_vec();
+    if rgb.len() != width as usize * height as usize * 3 {
+        return Err(format!("{}: pixel data does not match {}x{}", frame_path(dir, trigger_id).display(), width, height).into());
+    }
+    Ok(Some((width, height, rgb)))
+}
+
+/// Reads all records of a session directory, skipping malformed lines.
+pub fn read_records(dir: &Path) -> Result<Vec<MatchRecord>, Box<dyn std::error::Error>> {
+    let file = File::open(dir.join(RECORDS_FILE))?;
+    let mut records = Vec::new();
+    for line in BufReader::new(file).lines().skip(1) {
+        if let Some(record) = MatchRecord::from_csv(&line?) {
+            records.push(record);
+        }
+    }
+    Ok(records)
+}
+
+/// Reads the clock mappings of a session in recording order; empty for
+/// sessions recorded before `clock.csv` existed.
+pub fn read_clock_mappings(dir: &Path) -> Result<Vec<ClockMapping>, Box<dyn std::error::Error>> {
+    let file = match File::open(dir.join(CLOCK_FILE)) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut mappings = Vec::new();
+    for line in BufReader::new(file).lines().skip(1) {
+        if let Some(mapping) = ClockMapping::from_csv(&line?) {
+            mappings.push(mapping);
+        }
+    }
+    Ok(mappings)
+}
@@ -0,0 +1,423 @@
+// Memory-mapped stats snapshot of a running process.
+//
+// With `--shared-stats` the subscriber and the capture app keep their current
+// counters and latency percentiles in a small file under
+// `/dev/shm/camera_sync/<rig>/` (`DEFAULT_DIR`, `--shared-stats-dir`), one
+// per process, named after the node and the pid. Local tools read it without
+// subscribing to an iceoryx2 service or asking an HTTP endpoint: `sync_stats`
+// lists or watches the snapshots of a rig, and the file is a plain
+// `#[repr(C)]` `SnapshotFile` that other languages can map too.
+//
+// The writer updates the snapshot at most every `UPDATE_INTERVAL_NS`, under a
+// sequence lock: the sequence is odd while the stats are written, and a
+// reader that saw it odd or changed across its copy reads again. The file is
+// created under a temporary name and renamed when initialized, so readers
+// never see a half-written header, and removed when the writer is dropped.
+// Snapshots of processes that died without removing theirs are reported as
+// stale and removed by the next writer of the rig. Snapshots need mmap, other
+// platforms than Unix fail to create and read them.
+
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::mem::{offset_of, size_of};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{fence, AtomicU64, Ordering};
+
+use crate::batch::BatchResults;
+use crate::cli::Args;
+use crate::latency::LatencyBreakdown;
+use crate::namespace::NodeIdentity;
+
+/// Parent directory of the rigs' snapshot directories.
+pub const DEFAULT_DIR: &str = "/dev/shm/camera_sync";
+
+/// Snapshots are rewritten at most this often.
+pub const UPDATE_INTERVAL_NS: u64 = 1_000_000_000;
+
+/// `magic` of a snapshot file, "CSST".
+pub const SNAPSHOT_MAGIC: u32 = u32::from_le_bytes(*b"CSST");
+
+/// Layout version of `SnapshotFile`; bump with every change of it.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Node names longer than this are truncated in the snapshot.
+pub const NODE_NAME_LEN: usize = 96;
+
+/// File name extension of snapshots.
+pub const SNAPSHOT_EXTENSION: &str = "stats";
+
+// Reads that keep meeting a writer give up after this many attempts
+const READ_ATTEMPTS: usize = 1000;
+
+/// Counters and latency of a process at `updated_ns`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ProcessStats {
+    /// CLOCK_REALTIME ns of the process start and of this snapshot.
+    pub started_ns: u64,
+    pub updated_ns: u64,
+    /// Triggers received.
+    pub triggers: u64,
+    /// Frames that went through matching.
+    pub frames: u64,
+    pub matched: u64,
+    /// Dropped and missing triggers plus frames lost in the driver.
+    pub drops: u64,
+    pub deadline_misses: u64,
+    /// Triggers waiting for a frame.
+    pub pending_triggers: u64,
+    /// Trigger-to-match latency over the recent frames (see `LatencyBreakdown`).
+    pub latency_p50_ms: f64,
+    pub latency_p95_ms: f64,
+    pub latency_max_ms: f64,
+}
+
+impl ProcessStats {
+    /// The run's counters so far with the total latency of `latency`.
+    pub fn new(started_ns: u64, updated_ns: u64, results: &BatchResults, latency: &LatencyBreakdown) -> Self {
+        let total = latency.stages().into_iter().find(|(name, _)| *name == "total").map(|(_, stats)| stats.snapshot()).unwrap_or_default();
+        Self {
+            started_ns,
+            updated_ns,
+            triggers: results.triggers,
+            frames: results.frames,
+            matched: results.matched,
+            drops: results.drops,
+            deadline_misses: results.deadline_misses,
+            pending_triggers: 0,
+            latency_p50_ms: total.p50_ms,
+            latency_p95_ms: total.p95_ms,
+            latency_max_ms: total.max_ms,
+        }
+    }
+
+    pub fn match_rate(&self) -> f64 {
+        if self.frames == 0 {
+            0.0
+        } else {
+            self.matched as f64 / self.frames as f64
+        }
+    }
+}
+
+/// The mapped file: header, then the stats under the sequence lock.
+#[repr(C)]
+pub struct SnapshotFile {
+    pub magic: u32,
+    pub version: u32,
+    pub pid: u32,
+    pub reserved: u32,
+    /// Odd while the writer updates `stats`.
+    pub sequence: AtomicU64,
+    /// Node name, NUL padded.
+    pub node_name: [u8; NODE_NAME_LEN],
+    pub stats: ProcessStats,
+}
+
+const _: () = assert!(offset_of!(SnapshotFile, sequence) == 16);
+const _: () = assert!(offset_of!(SnapshotFile, node_name) == 24);
+const _: () = assert!(offset_of!(SnapshotFile, stats) == 24 + NODE_NAME_LEN);
+const _: () = assert!(size_of::<ProcessStats>() == 88);
+
+/// `--shared-stats-dir`, `DEFAULT_DIR` by default; `None` without `--shared-stats`.
+pub fn dir_from_args(args: &Args) -> Option<PathBuf> {
+    args.flag("shared-stats").then(|| PathBuf::from(args.value("shared-stats-dir").unwrap_or(DEFAULT_DIR)))
+}
+
+/// Snapshot directory of a rig under `dir`.
+pub fn rig_dir(dir: &Path, identity: &NodeIdentity) -> PathBuf {
+    dir.join(identity.rig())
+}
+
+// `<node>.<pid>.stats` with the rig dropped from the node name and '/' as '.'
+fn file_name(identity: &NodeIdentity, pid: u32) -> String {
+    let node = identity.name().strip_prefix(identity.rig()).unwrap_or(identity.name()).trim_start_matches('/');
+    format!("{}.{}.{}", node.replace('/', "."), pid, SNAPSHOT_EXTENSION)
+}
+
+// Shared mapping of a snapshot file
+struct Mapping {
+    address: *mut libc::c_void,
+    len: usize,
+}
+
+impl Mapping {
+    #[cfg(unix)]
+    fn new(file: &File, writable: bool) -> io::Result<Self> {
+        use std::os::fd::AsRawFd;
+
+        let len = size_of::<SnapshotFile>();
+        if file.metadata()?.len() < len as u64 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "snapshot file too short"));
+        }
+        let protection = if writable { libc::PROT_READ | libc::PROT_WRITE } else { libc::PROT_READ };
+        // SAFETY: a fresh shared mapping of the file, checked below
+        let address = unsafe { libc::mmap(std::ptr::null_mut(), len, protection, libc::MAP_SHARED, file.as_raw_fd(), 0) };
+        if address == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self { address, len })
+    }
+
+    #[cfg(not(unix))]
+    fn new(_file: &File, _writable: bool) -> io::Result<Self> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "stats snapshots need mmap"))
+    }
+
+    fn snapshot(&self) -> *mut SnapshotFile {
+        self.address.cast()
+    }
+}
+
+// The mapping is owned; the file's readers and writer synchronize through its sequence.
+unsafe impl Send for Mapping {}
+
+#[cfg(unix)]
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        // SAFETY: mapped in `new` and not used afterwards
+        unsafe { libc::munmap(self.address, self.len) };
+    }
+}
+
+/// Writer of the snapshot of this process.
+pub struct StatsPublisher {
+    mapping: Mapping,
+    path: PathBuf,
+    next_update_ns: u64,
+}
+
+impl StatsPublisher {
+    /// `--shared-stats [--shared-stats-dir <dir>]`; `None` without `--shared-stats`.
+    pub fn from_args(args: &Args, identity: &NodeIdentity) -> Result<Option<Self>, String> {
+        dir_from_args(args).map(|dir| Self::create(&dir, identity).map_err(|e| format!("cannot publish stats under {}: {}", dir.display(), e))).transpose()
+    }
+
+    /// Creates the snapshot of this process in the rig's directory under `dir`, removing stale snapshots of the rig.
+    pub fn create(dir: &Path, identity: &NodeIdentity) -> io::Result<Self> {
+        let rig_dir = rig_dir(dir, identity);
+        fs::create_dir_all(&rig_dir)?;
+        remove_stale(&rig_dir)?;
+        let pid = std::process::id();
+        let path = rig_dir.join(file_name(identity, pid));
+        let creating = path.with_extension("tmp");
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(&creating)?;
+        file.set_len(size_of::<SnapshotFile>() as u64)?;
+        let mapping = Mapping::new(&file, true)?;
+        let mut node_name = [0u8; NODE_NAME_LEN];
+        let name = identity.name().as_bytes();
+        let len = name.len().min(NODE_NAME_LEN);
+        node_name[..len].copy_from_slice(&name[..len]);
+        // SAFETY: the mapping spans a `SnapshotFile`, and no reader sees the file before the rename
+        unsafe {
+            mapping.snapshot().write(SnapshotFile {
+                magic: SNAPSHOT_MAGIC,
+                version: SNAPSHOT_VERSION,
+                pid,
+                reserved: 0,
+                sequence: AtomicU64::new(0),
+                node_name,
+                stats: ProcessStats::default(),
+            });
+        }
+        fs::rename(&creating, &path)?;
+        Ok(Self { mapping, path, next_update_ns: 0 })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether the snapshot is due for an update at `now_ns` (monotonic).
+    pub fn is_due(&self, now_ns: u64) -> bool {
+        now_ns >= self.next_update_ns
+    }
+
+    /// Writes `stats()` if an update is due at `now_ns` (monotonic).
+    pub fn update(&mut self, now_ns: u64, stats: impl FnOnce() -> ProcessStats) {
+        if self.is_due(now_ns) {
+            self.write(&stats());
+            self.next_update_ns = now_ns + UPDATE_INTERVAL_NS;
+        }
+    }
+
+    /// Writes `stats` now.
+    pub fn write(&mut self, stats: &ProcessStats) {
+        let snapshot = self.mapping.snapshot();
+        // SAFETY: the mapping spans a `SnapshotFile` initialized in `create`; this is its only writer
+        unsafe {
+            let sequence = &(*snapshot).sequence;
+            let odd = sequence.load(Ordering::Relaxed) + 1;
+            sequence.store(odd, Ordering::Relaxed);
+            fence(Ordering::Release);
+            std::ptr::addr_of_mut!((*snapshot).stats).write_volatile(*stats);
+            sequence.store(odd + 1, Ordering::Release);
+        }
+    }
+}
+
+impl Drop for StatsPublisher {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// A snapshot as read by `read`.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub path: PathBuf,
+    pub node_name: String,
+    pub pid: u32,
+    /// Whether the writing process is still running.
+    pub alive: bool,
+    pub stats: ProcessStats,
+}
+
+/// Reads a consistent copy of the snapshot at `path`.
+pub fn read(path: &Path) -> io::Result<Snapshot> {
+    let file = File::open(path)?;
+    let mapping = Mapping::new(&file, false)?;
+    let snapshot = mapping.snapshot();
+    // SAFETY: the mapping spans a `SnapshotFile`; the header is not written after the file got its name
+    let (magic, version, pid, node_name) = unsafe { ((*snapshot).magic, (*snapshot).version, (*snapshot).pid, (*snapshot).node_name) };
+    if magic != SNAPSHOT_MAGIC || version != SNAPSHOT_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}: not a version {} stats snapshot (version {})", path.display(), SNAPSHOT_VERSION, version),
+        ));
+    }
+    for _ in 0..READ_ATTEMPTS {
+        // SAFETY: as above; a copy torn by the writer is detected by the sequence and discarded
+        let (before, stats, after) = unsafe {
+            let sequence = &(*snapshot).sequence;
+            let before = sequence.load(Ordering::Acquire);
+            let stats = std::ptr::addr_of!((*snapshot).stats).read_volatile();
+            fence(Ordering::Acquire);
+            (before, stats, sequence.load(Ordering::Relaxed))
+        };
+        if before.is_multiple_of(2) && before == after {
+            let len = node_name.iter().position(|&b| b == 0).unwrap_or(NODE_NAME_LEN);
+            return Ok(Snapshot {
+                path: path.to_path_buf(),
+                node_name: String::from_utf8_lossy(&node_name[..len]).into_owned(),
+                pid,
+                alive: is_alive(pid),
+                stats,
+            });
+        }
+        std::hint::spin_loop();
+    }
+    Err(io::Error::new(io::ErrorKind::WouldBlock, format!("{}: snapshot kept changing while read", path.display())))
+}
+
+/// Snapshots in a rig directory, by file name; unreadable files are skipped.
+pub fn list(rig_dir: &Path) -> io::Result<Vec<Snapshot>> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(rig_dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == SNAPSHOT_EXTENSION))
+        .collect();
+    paths.sort();
+    Ok(paths.iter().filter_map(|path| read(path).ok()).collect())
+}
+
+/// Removes the snapshots of dead processes from a rig directory; returns their paths.
+pub fn remove_stale(rig_dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut removed = Vec::new();
+    for snapshot in list(rig_dir)?.into_iter().filter(|snapshot| !snapshot.alive) {
+        if fs::remove_file(&snapshot.path).is_ok() {
+            removed.push(snapshot.path);
+        }
+    }
+    Ok(removed)
+}
+
+// Whether a process of this pid exists
+#[cfg(unix)]
+fn is_alive(pid: u32) -> bool {
+    // SAFETY: signal 0 only checks that the process exists
+    let result = unsafe { libc::kill(pid as libc::pid_t, 0) };
+    result == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(unix))]
+fn is_alive(_pid: u32) -> bool {
+    true
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::batch::BatchResults;
+    use crate::latency::{FrameTiming, LatencyBreakdown};
+
+    #[test]
+    fn snapshot_reads_back_what_was_published() {
+        let dir = std::env::temp_dir().join(format!("camera_sync_shared_stats_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let identity = NodeIdentity::new("bench", "subscriber", Some("cam0")).unwrap();
+        let mut publisher = StatsPublisher::create(&dir, &identity).unwrap();
+        assert_eq!(publisher.path().file_name().unwrap().to_str().unwrap(), format!("subscriber.cam0.{}.stats", std::process::id()));
+
+        let mut latency = LatencyBreakdown::default();
+        for (n, latency_ms) in [10u64, 12, 14].into_iter().enumerate() {
+            let trigger_ns = n as u64 * 33_000_000;
+            latency.record(&FrameTiming {
+                trigger_ns,
+                driver_ns: None,
+                dequeue_ns: trigger_ns + latency_ms * 1_000_000,
+                match_ns: trigger_ns + latency_ms * 1_000_000,
+            });
+        }
+        let results = BatchResults {
+            triggers: 4,
+            frames: 3,
+            matched: 3,
+            drops: 1,
+            ..Default::default()
+        };
+        let stats = ProcessStats {
+            pending_triggers: 1,
+            ..ProcessStats::new(100, 200, &results, &latency)
+        };
+        publisher.update(5, || stats);
+        // Throttled: the next update is due a second later
+        publisher.update(6, ProcessStats::default);
+
+        let snapshots = list(&dir.join("bench")).unwrap();
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!((snapshots[0].node_name.as_str(), snapshots[0].pid, snapshots[0].alive), ("bench/subscriber/cam0", std::process::id(), true));
+        assert_eq!(snapshots[0].stats, stats);
+        assert_eq!((stats.latency_p50_ms, stats.latency_max_ms, stats.match_rate()), (12.0, 14.0, 1.0));
+
+        publisher.update(5 + UPDATE_INTERVAL_NS, || ProcessStats { matched: 2, ..stats });
+        assert_eq!(read(publisher.path()).unwrap().stats.matched, 2);
+        drop(publisher);
+        assert!(list(&dir.join("bench")).unwrap().is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn snapshots_of_dead_processes_are_removed() {
+        let dir = std::env::temp_dir().join(format!("camera_sync_shared_stats_stale_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let identity = NodeIdentity::new("bench", "subscriber", None).unwrap();
+        let publisher = StatsPublisher::create(&dir, &identity).unwrap();
+        // A snapshot left by an exited child process
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let dead_pid = child.id();
+        child.wait().unwrap();
+        let mut bytes = fs::read(publisher.path()).unwrap();
+        bytes[8..12].copy_from_slice(&dead_pid.to_ne_bytes());
+        let stale = dir.join("bench").join(format!("subscriber.{}.stats", dead_pid));
+        fs::write(&stale, bytes).unwrap();
+
+        let snapshots = list(&dir.join("bench")).unwrap();
+        assert_eq!(snapshots.len(), 2);
+        assert!(snapshots.iter().any(|snapshot| snapshot.pid == dead_pid && !snapshot.alive));
+        let other = StatsPublisher::create(&dir, &identity.child("second")).unwrap();
+        assert!(!stale.exists());
+        assert_eq!(list(&dir.join("bench")).unwrap().len(), 2);
+        drop((publisher, other));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
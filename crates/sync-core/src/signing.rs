@@ -0,0 +1,293 @@
+// Optional ed25519 signing of trigger messages.
+//
+// The publisher signs the trigger payload fields and carries the signature in
+// the iceoryx2 user header, so the payload layout stays unchanged. Subscribers
+// that are given the publisher's public key verify every trigger and can prove
+// later that recorded timestamps were not altered after publication.
+
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+#[cfg(feature = "iceoryx2")]
+use iceoryx2::prelude::ZeroCopySend;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::CameraTrigger;
+
+/// User header attached to every trigger sample.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "iceoryx2", derive(ZeroCopySend))]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "SignatureFields", try_from = "SignatureFields")
+)]
+pub struct TriggerSignature {
+    /// First 8 bytes of the signer's public key, 0 when unsigned.
+    pub key_id: u64,
+    pub signed: bool,
+    /// `timestamping::TimestampSource` of `hw_timestamp_ns`. Not covered by the signature.
+    pub timestamp_source: u8,
+    pub signature: [u8; 64],
+    /// `clock::ClockDomain` of both timestamps. Not covered by the signature.
+    pub clock_domain: u8,
+    /// `clock_quality::DowngradePolicy` in force at the publisher, 0 while its clock is synchronized. Not covered by the signature.
+    pub clock_policy: u8,
+    /// Publisher instance that created the trigger, 0 if unknown (`trace::TraceId`). Not covered by the signature.
+    pub trace_origin: u32,
+    /// PTP hardware clock minus `CLOCK_REALTIME` at the publisher when the trigger was stamped, 0 if it reads no PHC
+    /// (`timestamping::TriggerTimestamper::clock_offset_ns`). Not covered by the signature.
+    pub phc_offset_ns: i64,
+}
+
+impl Default for TriggerSignature {
+    fn default() -> Self {
+        Self {
+            key_id: 0,
+            signed: false,
+            timestamp_source: 0,
+            signature: [0; 64],
+            clock_domain: 0,
+            clock_policy: 0,
+            trace_origin: 0,
+            phc_offset_ns: 0,
+        }
+    }
+}
+
+impl TriggerSignature {
+    pub fn signature_hex(&self) -> String {
+        if !self.signed {
+            return String::new();
+        }
+        to_hex(&self.signature)
+    }
+
+    /// A `CLOCK_REALTIME` time in the publisher's PTP time (network time, usually TAI), if it sent its PHC offset.
+    pub fn network_time_ns(&self, realtime_ns: u64) -> Option<u64> {
+        (self.phc_offset_ns != 0).then(|| realtime_ns.saturating_add_signed(self.phc_offset_ns))
+    }
+
+    /// Rebuilds a header from its recorded form (`key_id` and hex signature, empty when unsigned).
+    pub fn from_hex(key_id: u64, signature_hex: &str) -> Option<Self> {
+        if key_id == 0 {
+            return Some(Self::default());
+        }
+        Some(Self {
+            key_id,
+            signed: true,
+            signature: from_hex(signature_hex)?.try_into().ok()?,
+            ..Self::default()
+        })
+    }
+}
+
+/// Serialized form of `TriggerSignature`: the signature as hex, empty when unsigned.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SignatureFields {
+    key_id: u64,
+    signature: String,
+    #[serde(default)]
+    timestamp_source: u8,
+    #[serde(default)]
+    clock_domain: u8,
+    #[serde(default)]
+    clock_policy: u8,
+    #[serde(default)]
+    trace_origin: u32,
+    #[serde(default)]
+    phc_offset_ns: i64,
+}
+
+#[cfg(feature = "serde")]
+impl From<TriggerSignature> for SignatureFields {
+    fn from(header: TriggerSignature) -> Self {
+        Self {
+            key_id: header.key_id,
+            signature: header.signature_hex(),
+            timestamp_source: header.timestamp_source,
+            clock_domain: header.clock_domain,
+            clock_policy: header.clock_policy,
+            trace_origin: header.trace_origin,
+            phc_offset_ns: header.phc_offset_ns,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<SignatureFields> for TriggerSignature {
+    type Error = String;
+
+    fn try_from(fields: SignatureFields) -> Result<Self, Self::Error> {
+        let header = TriggerSignature::from_hex(fields.key_id, &fields.signature).ok_or_else(|| "signature is not 64 bytes of hex".to_string())?;
+        Ok(TriggerSignature {
+            timestamp_source: fields.timestamp_source,
+            clock_domain: fields.clock_domain,
+            clock_policy: fields.clock_policy,
+            trace_origin: fields.trace_origin,
+            phc_offset_ns: fields.phc_offset_ns,
+            ..header
+        })
+    }
+}
+
+/// Outcome of verifying a received trigger.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "lowercase"))]
+pub enum SignatureStatus {
+    Unsigned,
+    Valid,
+    Invalid,
+}
+
+impl fmt::Display for SignatureStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignatureStatus::Unsigned => write!(f, "unsigned"),
+            SignatureStatus::Valid => write!(f, "valid"),
+            SignatureStatus::Invalid => write!(f, "INVALID"),
+        }
+    }
+}
+
+/// Bytes covered by the signature: all trigger fields, little endian.
+fn signed_message(trigger: &CameraTrigger) -> [u8; 24] {
+    let mut message = [0u8; 24];
+    message[0..8].copy_from_slice(&trigger.frame_id.to_le_bytes());
+    message[8..16].copy_from_slice(&trigger.hw_timestamp_ns.to_le_bytes());
+    message[16..24].copy_from_slice(&trigger.publish_timestamp_ns.to_le_bytes());
+    message
+}
+
+fn key_id(key: &VerifyingKey) -> u64 {
+    let bytes = key.to_bytes();
+    u64::from_le_bytes(bytes[0..8].try_into().unwrap())
+}
+
+pub struct TriggerSigner {
+    key: SigningKey,
+}
+
+impl TriggerSigner {
+    /// Loads a signing key from a file holding the 32-byte secret seed as hex,
+    /// e.g. created with `head -c 32 /dev/urandom | xxd -p -c 64 > trigger.key`.
+    pub fn from_key_file(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let seed = read_hex_key(path)?;
+        Ok(Self {
+            key: SigningKey::from_bytes(&seed),
+        })
+    }
+
+    /// Hex encoded public key to hand to subscribers (`--verify-key`).
+    pub fn public_key_hex(&self) -> String {
+        to_hex(&self.key.verifying_key().to_bytes())
+    }
+
+    pub fn sign(&self, trigger: &CameraTrigger) -> TriggerSignature {
+        let signature = self.key.sign(&signed_message(trigger));
+        TriggerSignature {
+            key_id: key_id(&self.key.verifying_key()),
+            signed: true,
+            signature: signature.to_bytes(),
+            ..TriggerSignature::default()
+        }
+    }
+}
+
+pub struct TriggerVerifier {
+    key: VerifyingKey,
+}
+
+impl TriggerVerifier {
+    /// Loads the publisher's public key from a file holding it as hex.
+    pub fn from_key_file(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let bytes = read_hex_key(path)?;
+        Ok(Self {
+            key: VerifyingKey::from_bytes(&bytes)?,
+        })
+    }
+
+    pub fn verify(&self, trigger: &CameraTrigger, header: &TriggerSignature) -> SignatureStatus {
+        if !header.signed {
+            return SignatureStatus::Unsigned;
+        }
+        if header.key_id != key_id(&self.key) {
+            return SignatureStatus::Invalid;
+        }
+        let signature = Signature::from_bytes(&header.signature);
+        match self.key.verify(&signed_message(trigger), &signature) {
+            Ok(()) => SignatureStatus::Valid,
+            Err(_) => SignatureStatus::Invalid,
+        }
+    }
+}
+
+fn read_hex_key(path: &Path) -> Result<[u8; 32], Box<dyn std::error::Error>> {
+    let text = fs::read_to_string(path)?;
+    let bytes = from_hex(text.trim()).ok_or_else(|| format!("{}: key is not valid hex", path.display()))?;
+    bytes
+        .try_into()
+        .map_err(|b: Vec<u8>| format!("{}: expected 32 key bytes, got {}", path.display(), b.len()).into())
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signer(seed: u8) -> TriggerSigner {
+        TriggerSigner {
+            key: SigningKey::from_bytes(&[seed; 32]),
+        }
+    }
+
+    fn verifier(signer: &TriggerSigner) -> TriggerVerifier {
+        TriggerVerifier {
+            key: signer.key.verifying_key(),
+        }
+    }
+
+    #[test]
+    fn signed_triggers_verify_and_tampering_is_detected() {
+        let publisher = signer(7);
+        let trigger = CameraTrigger::new(42, 1_000_000_000, 1_000_050_000);
+        let header = publisher.sign(&trigger);
+        assert!(header.signed);
+        assert_eq!(verifier(&publisher).verify(&trigger, &header), SignatureStatus::Valid);
+
+        // Any changed field breaks the signature
+        let altered = CameraTrigger::new(42, 1_000_000_001, 1_000_050_000);
+        assert_eq!(verifier(&publisher).verify(&altered, &header), SignatureStatus::Invalid);
+        // So does another publisher's key
+        assert_eq!(verifier(&signer(8)).verify(&trigger, &header), SignatureStatus::Invalid);
+        assert_eq!(verifier(&publisher).verify(&trigger, &TriggerSignature::default()), SignatureStatus::Unsigned);
+    }
+
+    #[test]
+    fn recorded_signatures_round_trip_through_hex() {
+        let publisher = signer(7);
+        let trigger = CameraTrigger::new(42, 1_000_000_000, 1_000_050_000);
+        let header = publisher.sign(&trigger);
+        let restored = TriggerSignature::from_hex(header.key_id, &header.signature_hex()).unwrap();
+        assert_eq!(verifier(&publisher).verify(&trigger, &restored), SignatureStatus::Valid);
+
+        assert!(!TriggerSignature::from_hex(0, "").unwrap().signed);
+        assert!(TriggerSignature::from_hex(header.key_id, "abc").is_none());
+        assert!(TriggerSignature::from_hex(header.key_id, "abcd").is_none());
+    }
+}
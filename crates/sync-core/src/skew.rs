@@ -0,0 +1,306 @@
+// Latency skew between the cameras of a group.
+//
+// A failing cable or USB hub slows one camera down while its latency still
+// looks plausible on its own. Cameras started with `--camera-group <name>`
+// report the latency of their matched frames (frame minus trigger time) for
+// the sampled trigger ids of `feedback` as a `GroupLatency` on the
+// `Camera/GroupLatency` service (see `sync_iceoryx2::skew`), under their
+// consumer name. The publisher's `SkewMonitor` compares every camera with its
+// group instead of with an absolute limit.
+//
+// Each camera's latency is the median of its last `WINDOW` samples; the
+// latency it had when its first window was full is its baseline, so cameras
+// with other exposures or pipelines compare alike. A camera's skew is how far
+// its latency moved from its baseline beyond the median move of the cameras
+// of its group that reported within `PEER_TIMEOUT_NS`: a slower publisher or
+// host moves all of them and cancels out. The camera has diverged once its
+// skew exceeded the skew threshold (`--skew-threshold-ms`,
+// `DEFAULT_SKEW_THRESHOLD_MS`) for `CONFIRM_SAMPLES` samples in a row, so the
+// cameras' medians following a common move one sample apart do not count, and
+// recovers once it stayed below `RECOVER_FRACTION` of it as long.
+// With two cameras in a group, both diverge from each other; three or more
+// point at the one that moved.
+
+#[cfg(feature = "iceoryx2")]
+use iceoryx2::prelude::ZeroCopySend;
+use std::collections::VecDeque;
+use std::fmt;
+
+use crate::feedback::MAX_CONSUMER_LEN;
+
+/// Longest group name a sample carries, longer names are truncated.
+pub const MAX_GROUP_LEN: usize = 32;
+
+/// Samples a camera's latency is the median of.
+pub const WINDOW: usize = 20;
+
+/// Cameras that did not report for this long are left out of their group's median.
+pub const PEER_TIMEOUT_NS: u64 = 5_000_000_000;
+
+/// Skew beyond which a camera diverged from its group (`--skew-threshold-ms`).
+pub const DEFAULT_SKEW_THRESHOLD_MS: f64 = 2.0;
+
+/// Share of the threshold a diverged camera's skew must fall below to recover.
+pub const RECOVER_FRACTION: f64 = 0.8;
+
+/// Samples in a row a camera's skew must be beyond (or back within) the threshold to diverge (or recover).
+pub const CONFIRM_SAMPLES: u32 = 3;
+
+/// Latency of one matched frame of a grouped camera.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "iceoryx2", derive(ZeroCopySend))]
+pub struct GroupLatency {
+    pub trigger_id: u64,
+    /// Frame minus trigger hw_ts, both CLOCK_REALTIME, ns.
+    pub latency_ns: i64,
+    /// Consumer name of the camera, UTF-8, zero padded.
+    pub camera: [u8; MAX_CONSUMER_LEN],
+    /// UTF-8, zero padded.
+    pub group: [u8; MAX_GROUP_LEN],
+}
+
+impl GroupLatency {
+    pub fn new(group: &str, camera: &str, trigger_id: u64, latency_ns: i64) -> Self {
+        Self {
+            trigger_id,
+            latency_ns,
+            camera: padded(camera),
+            group: padded(group),
+        }
+    }
+
+    pub fn camera_str(&self) -> String {
+        String::from_utf8_lossy(&self.camera).trim_end_matches('\0').to_string()
+    }
+
+    pub fn group_str(&self) -> String {
+        String::from_utf8_lossy(&self.group).trim_end_matches('\0').to_string()
+    }
+}
+
+// Truncated on a character boundary
+fn padded<const N: usize>(name: &str) -> [u8; N] {
+    let mut bytes = [0u8; N];
+    let mut len = name.len().min(N);
+    while !name.is_char_boundary(len) {
+        len -= 1;
+    }
+    bytes[..len].copy_from_slice(&name.as_bytes()[..len]);
+    bytes
+}
+
+pub(crate) fn median(values: impl IntoIterator<Item = f64>) -> Option<f64> {
+    let mut sorted: Vec<f64> = values.into_iter().collect();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let middle = sorted.len() / 2;
+    match sorted.len() {
+        0 => None,
+        len if len.is_multiple_of(2) => Some((sorted[middle - 1] + sorted[middle]) / 2.0),
+        _ => Some(sorted[middle]),
+    }
+}
+
+/// Latency of one camera of a group and its skew against the group.
+#[derive(Debug, Clone)]
+pub struct CameraSkew {
+    pub group: String,
+    pub camera: String,
+    recent: VecDeque<f64>,
+    last_ns: u64,
+    /// Latency when the first window was full, ms.
+    pub baseline_ms: Option<f64>,
+    /// Move of the latency from the baseline minus the group's median move, ms.
+    pub skew_ms: Option<f64>,
+    /// Cameras the last skew was taken against, this one included.
+    pub peers: usize,
+    pub diverged: bool,
+    // Samples in a row that disagree with `diverged`
+    streak: u32,
+}
+
+impl CameraSkew {
+    fn new(group: String, camera: String) -> Self {
+        Self {
+            group,
+            camera,
+            recent: VecDeque::new(),
+            last_ns: 0,
+            baseline_ms: None,
+            skew_ms: None,
+            peers: 0,
+            diverged: false,
+            streak: 0,
+        }
+    }
+
+    /// Median of the last `WINDOW` samples, ms.
+    pub fn latency_ms(&self) -> Option<f64> {
+        median(self.recent.iter().copied())
+    }
+
+    // How far the latency moved from the baseline, once the window is full
+    fn moved_ms(&self) -> Option<f64> {
+        if self.recent.len() < WINDOW {
+            return None;
+        }
+        Some(self.latency_ms()? - self.baseline_ms?)
+    }
+}
+
+impl fmt::Display for CameraSkew {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} in group {}: latency {:.2}ms", self.camera, self.group, self.latency_ms().unwrap_or_default())?;
+        if let Some(baseline) = self.baseline_ms {
+            write!(f, " (baseline {:.2}ms)", baseline)?;
+        }
+        if let Some(skew) = self.skew_ms {
+            write!(f, ", skew {:+.2}ms against {} cameras{}", skew, self.peers, if self.diverged { " DIVERGED" } else { "" })?;
+        }
+        Ok(())
+    }
+}
+
+/// Publisher side: the latency skew of every grouped camera from their reports.
+#[derive(Debug, Clone)]
+pub struct SkewMonitor {
+    threshold_ms: f64,
+    cameras: Vec<CameraSkew>,
+}
+
+impl SkewMonitor {
+    pub fn new(threshold_ms: f64) -> Self {
+        Self { threshold_ms, cameras: Vec::new() }
+    }
+
+    pub fn threshold_ms(&self) -> f64 {
+        self.threshold_ms
+    }
+
+    /// Records a sample received at `now_ns` (CLOCK_MONOTONIC); returns the camera if it just diverged from its
+    /// group or recovered.
+    pub fn on_sample(&mut self, sample: &GroupLatency, now_ns: u64) -> Option<&CameraSkew> {
+        let (group, name) = (sample.group_str(), sample.camera_str());
+        let index = match self.cameras.iter().position(|camera| camera.group == group && camera.camera == name) {
+            Some(index) => index,
+            None => {
+                self.cameras.push(CameraSkew::new(group, name));
+                self.cameras.len() - 1
+            }
+        };
+        let camera = &mut self.cameras[index];
+        if camera.recent.len() == WINDOW {
+            camera.recent.pop_front();
+        }
+        camera.recent.push_back(sample.latency_ns as f64 / 1e6);
+        camera.last_ns = now_ns;
+        if camera.recent.len() < WINDOW {
+            return None;
+        }
+        if camera.baseline_ms.is_none() {
+            camera.baseline_ms = camera.latency_ms();
+        }
+
+        // Moves of the cameras of the group that reported recently, this one included
+        let camera = &self.cameras[index];
+        let moves: Vec<f64> = self
+            .cameras
+            .iter()
+            .filter(|peer| peer.group == camera.group && now_ns.saturating_sub(peer.last_ns) <= PEER_TIMEOUT_NS)
+            .filter_map(CameraSkew::moved_ms)
+            .collect();
+        let peers = moves.len();
+        let skew_ms = camera.moved_ms().zip(median(moves)).map(|(moved, group)| moved - group);
+        let camera = &mut self.cameras[index];
+        camera.peers = peers;
+        if peers < 2 {
+            camera.skew_ms = None;
+            camera.streak = 0;
+            return None;
+        }
+        camera.skew_ms = skew_ms;
+        let skew = skew_ms?.abs();
+        let diverged = if camera.diverged { skew >= self.threshold_ms * RECOVER_FRACTION } else { skew > self.threshold_ms };
+        camera.streak = if diverged == camera.diverged { 0 } else { camera.streak + 1 };
+        (camera.streak == CONFIRM_SAMPLES).then(|| {
+            camera.diverged = diverged;
+            camera.streak = 0;
+            &*camera
+        })
+    }
+
+    /// In the order their first sample arrived.
+    pub fn cameras(&self) -> &[CameraSkew] {
+        &self.cameras
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: i64 = 1_000_000;
+
+    // One sample of every camera per trigger, at `latencies` ms; the changes reported
+    fn feed(monitor: &mut SkewMonitor, trigger_id: &mut u64, latencies: &[(&str, i64)], rounds: usize) -> Vec<(String, bool)> {
+        let mut changes = Vec::new();
+        for _ in 0..rounds {
+            *trigger_id += 10;
+            for (camera, latency_ms) in latencies {
+                let sample = GroupLatency::new("left", camera, *trigger_id, latency_ms * MS);
+                if let Some(skew) = monitor.on_sample(&sample, *trigger_id * 33 * MS as u64 / 10) {
+                    changes.push((skew.camera.clone(), skew.diverged));
+                }
+            }
+        }
+        changes
+    }
+
+    #[test]
+    fn a_camera_walking_away_from_its_group_diverges() {
+        let mut monitor = SkewMonitor::new(2.0);
+        let mut trigger_id = 0;
+        // c runs a longer exposure than a and b
+        assert!(feed(&mut monitor, &mut trigger_id, &[("a", 10), ("b", 12), ("c", 30)], WINDOW * 2).is_empty());
+
+        // The whole group slows down: no skew
+        assert!(feed(&mut monitor, &mut trigger_id, &[("a", 15), ("b", 17), ("c", 35)], WINDOW).is_empty());
+        assert!(monitor.cameras().iter().all(|camera| camera.skew_ms == Some(0.0) && camera.peers == 3));
+
+        // b's cable starts failing
+        assert_eq!(feed(&mut monitor, &mut trigger_id, &[("a", 15), ("b", 21), ("c", 35)], WINDOW), [("b".to_string(), true)]);
+        let b = &monitor.cameras()[1];
+        assert_eq!((b.latency_ms(), b.baseline_ms, b.skew_ms), (Some(21.0), Some(12.0), Some(4.0)));
+        assert!(b.to_string().ends_with("skew +4.00ms against 3 cameras DIVERGED"), "{}", b);
+
+        // Within the recovery fraction of the threshold again
+        assert_eq!(feed(&mut monitor, &mut trigger_id, &[("a", 15), ("b", 18), ("c", 35)], WINDOW), [("b".to_string(), false)]);
+    }
+
+    #[test]
+    fn silent_cameras_leave_the_group() {
+        let mut monitor = SkewMonitor::new(2.0);
+        let mut trigger_id = 0;
+        feed(&mut monitor, &mut trigger_id, &[("a", 10), ("b", 10), ("c", 10)], WINDOW);
+
+        // c stalls; once it is silent for the timeout, a and b are compared without it
+        let rounds = (PEER_TIMEOUT_NS / (33 * MS as u64)) as usize + 1;
+        feed(&mut monitor, &mut trigger_id, &[("a", 10), ("b", 10)], rounds);
+        assert_eq!(monitor.cameras()[0].peers, 2);
+
+        // Alone in its group, a camera has no skew
+        let mut alone = SkewMonitor::new(2.0);
+        let mut trigger_id = 0;
+        assert!(feed(&mut alone, &mut trigger_id, &[("a", 10)], WINDOW * 2).is_empty());
+        assert_eq!(alone.cameras()[0].skew_ms, None);
+    }
+
+    #[test]
+    fn samples_carry_truncated_names() {
+        let sample = GroupLatency::new("left", "camera serial:ABC123", 40, -3 * MS);
+        assert_eq!((sample.group_str().as_str(), sample.camera_str().as_str(), sample.latency_ns), ("left", "camera serial:ABC123", -3 * MS));
+        // 32 bytes, cut before a character that does not fit
+        let long = GroupLatency::new(&"g".repeat(40), &format!("{}é", "c".repeat(31)), 40, 0);
+        assert_eq!((long.group_str().len(), long.camera_str()), (32, "c".repeat(31)));
+    }
+}
@@ -0,0 +1,330 @@
+// Camera stall detection and the restart-and-resync procedure.
+//
+// A USB camera occasionally stops delivering frames without reporting an
+// error (bandwidth trouble, a firmware hang, a hub reset) while the triggers
+// keep coming. The `StallMonitor` watches frame arrivals: no frame for
+// `--stall-intervals` frame intervals (default 10) while triggers arrived is a
+// stall. Stalls are always reported; with `--stall-restart` the capture app
+// also recovers the stream:
+//
+//   1. report the stall (`camera_stall`)
+//   2. close the stream; from the second consecutive attempt on, run the
+//      `--stall-power-cycle` hook (e.g. uhubctl) to toggle the port power
+//   3. resolve the camera selector again (re-enumeration may have moved the
+//      device node) and reopen the stream with the same settings
+//   4. forget the driver sequence, so the restart is not counted as a gap
+//   5. discard `WARMUP_FRAMES` frames: stale buffers and exposure settling
+//   6. resume matching with the first frame after the warm-up and report
+//      `camera_resumed`; the matcher keeps its learned model through the
+//      outage, the triggers of the outage are cleaned up as too old
+//
+// A reopen that fails, or a stream that stalls again before it resumed, is
+// the next attempt one stall window later. After `--stall-max-restarts`
+// consecutive attempts the capture stops.
+
+use std::fmt;
+use std::time::Duration;
+
+use crate::cli::Args;
+
+/// Frames discarded after a restart before matching resumes.
+pub const WARMUP_FRAMES: u32 = 3;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StallConfig {
+    /// Frame intervals without a frame that make a stall.
+    pub intervals: u32,
+    /// Close and reopen the stream on a stall.
+    pub restart: bool,
+    /// Consecutive restart attempts before giving up.
+    pub max_restarts: u32,
+    /// Shell command toggling the camera's USB power, with `{hub}`, `{port}` and `{bus}` placeholders.
+    pub power_cycle: Option<String>,
+}
+
+impl Default for StallConfig {
+    fn default() -> Self {
+        Self {
+            intervals: 10,
+            restart: false,
+            max_restarts: 5,
+            power_cycle: None,
+        }
+    }
+}
+
+impl StallConfig {
+    /// `--stall-intervals`, `--stall-restart`, `--stall-max-restarts` and `--stall-power-cycle`.
+    pub fn from_args(args: &Args) -> Self {
+        let defaults = Self::default();
+        Self {
+            intervals: args.value_as("stall-intervals").unwrap_or(defaults.intervals).max(1),
+            restart: args.flag("stall-restart"),
+            max_restarts: args.value_as("stall-max-restarts").unwrap_or(defaults.max_restarts),
+            power_cycle: args.value("stall-power-cycle").map(str::to_string),
+        }
+    }
+
+    /// The power-cycle hook for the camera at USB path `bus_path` (e.g. `1-2.3`: hub `1-2`, port `3`).
+    pub fn power_cycle_command(&self, bus_path: &str) -> Option<String> {
+        let (hub, port) = bus_path.rsplit_once('.').or_else(|| bus_path.split_once('-'))?;
+        Some(self.power_cycle.as_ref()?.replace("{hub}", hub).replace("{port}", port).replace("{bus}", bus_path))
+    }
+}
+
+/// No frame for the stall window while triggers arrived.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stall {
+    /// Since the last frame, or since the last restart attempt.
+    pub silent_ns: u64,
+    /// Triggers received meanwhile.
+    pub missed_triggers: u64,
+    /// Consecutive stall of this outage, 1 for the first.
+    pub attempt: u32,
+}
+
+impl fmt::Display for Stall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "CAMERA STALL: no frame for {:.1}ms while {} triggers arrived", self.silent_ns as f64 / 1e6, self.missed_triggers)?;
+        if self.attempt > 1 {
+            write!(f, " (stall {} in a row)", self.attempt)?;
+        }
+        Ok(())
+    }
+}
+
+/// The stream delivers frames again after an outage.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resumed {
+    /// From the last frame before the outage to the first one after the warm-up.
+    pub downtime_ns: u64,
+    pub restarts: u32,
+    pub missed_triggers: u64,
+}
+
+impl fmt::Display for Resumed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "CAMERA RESUMED after {:.3}s without frames ({} restarts, {} triggers missed)",
+            self.downtime_ns as f64 / 1e9,
+            self.restarts,
+            self.missed_triggers
+        )
+    }
+}
+
+/// What to do with a delivered frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FrameAction {
+    Process,
+    /// Warm-up frame after a restart, not matched.
+    WarmUp,
+    /// First frame after an outage; matching resumes with it.
+    Resumed(Resumed),
+}
+
+#[derive(Debug, Clone)]
+struct Outage {
+    since_ns: u64,
+    stalls: u32,
+    restarts: u32,
+    missed_triggers: u64,
+}
+
+/// Watches frame arrivals of one camera for stalls.
+#[derive(Debug, Clone)]
+pub struct StallMonitor {
+    config: StallConfig,
+    frame_interval_ns: u64,
+    last_frame_ns: Option<u64>,
+    triggers_since_frame: u64,
+    outage: Option<Outage>,
+    warmup_left: u32,
+    stalls: u64,
+}
+
+impl StallMonitor {
+    pub fn new(config: StallConfig, camera_fps: u32) -> Self {
+        Self {
+            config,
+            frame_interval_ns: 1_000_000_000 / camera_fps.max(1) as u64,
+            last_frame_ns: None,
+            triggers_since_frame: 0,
+            outage: None,
+            warmup_left: 0,
+            stalls: 0,
+        }
+    }
+
+    pub fn config(&self) -> &StallConfig {
+        &self.config
+    }
+
+    /// Time without frames that makes a stall.
+    pub fn window(&self) -> Duration {
+        Duration::from_nanos(self.config.intervals as u64 * self.frame_interval_ns)
+    }
+
+    /// How long a backend may wait for a frame before the monitor gets to check: two frame intervals, at least 10ms.
+    pub fn frame_timeout(&self) -> Duration {
+        Duration::from_nanos((2 * self.frame_interval_ns).max(10_000_000))
+    }
+
+    /// Capture (re)started: the first window starts with the next check.
+    pub fn start(&mut self) {
+        self.last_frame_ns = None;
+        self.triggers_since_frame = 0;
+    }
+
+    pub fn on_trigger(&mut self) {
+        self.triggers_since_frame += 1;
+    }
+
+    /// A stall if no frame arrived for the stall window up to `now_ns` while triggers did;
+    /// the next window starts at `now_ns`. The first call starts the first window.
+    pub fn check(&mut self, now_ns: u64) -> Option<Stall> {
+        let since_ns = *self.last_frame_ns.get_or_insert(now_ns);
+        if now_ns.saturating_sub(since_ns) < self.window().as_nanos() as u64 || self.triggers_since_frame == 0 {
+            return None;
+        }
+        let outage = self.outage.get_or_insert(Outage {
+            since_ns,
+            stalls: 0,
+            restarts: 0,
+            missed_triggers: 0,
+        });
+        outage.stalls += 1;
+        outage.missed_triggers += self.triggers_since_frame;
+        self.stalls += 1;
+        let stall = Stall {
+            silent_ns: now_ns - since_ns,
+            missed_triggers: self.triggers_since_frame,
+            attempt: outage.stalls,
+        };
+        self.last_frame_ns = Some(now_ns);
+        self.triggers_since_frame = 0;
+        Some(stall)
+    }
+
+    /// Whether the outage has used up its restart attempts.
+    pub fn exhausted(&self) -> bool {
+        self.outage.as_ref().is_some_and(|outage| outage.restarts >= self.config.max_restarts)
+    }
+
+    /// Whether this restart attempt of the outage should power-cycle the camera first.
+    pub fn should_power_cycle(&self) -> bool {
+        self.config.power_cycle.is_some() && self.outage.as_ref().is_some_and(|outage| outage.restarts >= 1)
+    }
+
+    /// The stream was closed and reopened at `now_ns` (or the attempt failed); the warm-up starts.
+    pub fn restarted(&mut self, now_ns: u64) {
+        if let Some(outage) = &mut self.outage {
+            outage.restarts += 1;
+        }
+        self.warmup_left = WARMUP_FRAMES;
+        self.last_frame_ns = Some(now_ns);
+    }
+
+    /// Records a delivered frame.
+    pub fn on_frame(&mut self, now_ns: u64) -> FrameAction {
+        self.last_frame_ns = Some(now_ns);
+        self.triggers_since_frame = 0;
+        if self.warmup_left > 0 {
+            self.warmup_left -= 1;
+            return FrameAction::WarmUp;
+        }
+        match self.outage.take() {
+            Some(outage) => FrameAction::Resumed(Resumed {
+                downtime_ns: now_ns.saturating_sub(outage.since_ns),
+                restarts: outage.restarts,
+                missed_triggers: outage.missed_triggers,
+            }),
+            None => FrameAction::Process,
+        }
+    }
+
+    /// Stalls so far.
+    pub fn stalls(&self) -> u64 {
+        self.stalls
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: u64 = 1_000_000;
+
+    #[test]
+    fn restarts_a_stalled_stream_and_resumes_after_the_warm_up() {
+        let config = StallConfig {
+            restart: true,
+            max_restarts: 2,
+            power_cycle: Some("uhubctl -l {hub} -p {port} -a cycle".to_string()),
+            ..StallConfig::default()
+        };
+        // 10 intervals at 50fps
+        let mut monitor = StallMonitor::new(config, 50);
+        assert_eq!(monitor.window().as_millis(), 200);
+        let mut now_ns = 1_000 * MS;
+        assert_eq!(monitor.check(now_ns), None);
+        for _ in 0..5 {
+            now_ns += 20 * MS;
+            monitor.on_trigger();
+            assert_eq!(monitor.on_frame(now_ns), FrameAction::Process);
+        }
+        let last_frame_ns = now_ns;
+
+        // Without triggers, a silent camera is no stall (the publisher may be stopped)
+        now_ns += 500 * MS;
+        assert_eq!(monitor.check(now_ns), None);
+        monitor.on_trigger();
+        let stall = monitor.check(now_ns).unwrap();
+        assert_eq!((stall.silent_ns, stall.missed_triggers, stall.attempt), (500 * MS, 1, 1));
+        assert_eq!(stall.to_string(), "CAMERA STALL: no frame for 500.0ms while 1 triggers arrived");
+
+        // The first restart only reopens, the second power-cycles first
+        assert!(!monitor.exhausted() && !monitor.should_power_cycle());
+        monitor.restarted(now_ns);
+        // Reopened but still silent: the next window ends in the second stall
+        for _ in 0..9 {
+            now_ns += 20 * MS;
+            monitor.on_trigger();
+            assert_eq!(monitor.check(now_ns), None);
+        }
+        now_ns += 20 * MS;
+        monitor.on_trigger();
+        let stall = monitor.check(now_ns).unwrap();
+        assert_eq!((stall.missed_triggers, stall.attempt), (10, 2));
+        assert!(stall.to_string().ends_with("(stall 2 in a row)"));
+        assert!(monitor.should_power_cycle());
+        monitor.restarted(now_ns);
+        assert!(monitor.exhausted());
+
+        // Warm-up frames are discarded, the next one resumes matching
+        for _ in 0..WARMUP_FRAMES {
+            now_ns += 20 * MS;
+            assert_eq!(monitor.on_frame(now_ns), FrameAction::WarmUp);
+        }
+        now_ns += 20 * MS;
+        let FrameAction::Resumed(resumed) = monitor.on_frame(now_ns) else {
+            panic!("no resume after the warm-up");
+        };
+        assert_eq!((resumed.downtime_ns, resumed.restarts, resumed.missed_triggers), (now_ns - last_frame_ns, 2, 11));
+        assert_eq!(monitor.on_frame(now_ns + 20 * MS), FrameAction::Process);
+        assert!(!monitor.exhausted());
+        assert_eq!(monitor.stalls(), 2);
+    }
+
+    #[test]
+    fn power_cycle_hook_addresses_the_hub_port() {
+        let config = StallConfig {
+            power_cycle: Some("uhubctl -l {hub} -p {port} -a cycle # {bus}".to_string()),
+            ..StallConfig::default()
+        };
+        assert_eq!(config.power_cycle_command("1-2.3").as_deref(), Some("uhubctl -l 1-2 -p 3 -a cycle # 1-2.3"));
+        assert_eq!(config.power_cycle_command("3-1").as_deref(), Some("uhubctl -l 3 -p 1 -a cycle # 3-1"));
+        assert_eq!(StallConfig::default().power_cycle_command("1-2.3"), None);
+    }
+}
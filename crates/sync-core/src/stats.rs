@@ -0,0 +1,117 @@
+// Running statistics for latency-like measurements.
+
+use std::collections::VecDeque;
+
+use crate::units::{DisplayMs, Unit};
+
+/// Number of recent samples kept for percentile estimates.
+const WINDOW: usize = 1000;
+
+/// Point-in-time view of a `LatencyStats`, for logs and APIs.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StatsSummary {
+    pub count: u64,
+    pub mean_ms: f64,
+    /// Standard deviation over all samples (added after schema version 1, optional).
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub std_ms: f64,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub min_ms: f64,
+    pub max_ms: f64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LatencyStats {
+    recent: VecDeque<f64>,
+    count: u64,
+    sum: f64,
+    sum_sq: f64,
+    min: f64,
+    max: f64,
+}
+
+impl LatencyStats {
+    pub fn add(&mut self, value_ms: f64) {
+        if self.count == 0 {
+            self.min = value_ms;
+            self.max = value_ms;
+        } else {
+            self.min = self.min.min(value_ms);
+            self.max = self.max.max(value_ms);
+        }
+        self.count += 1;
+        self.sum += value_ms;
+        self.sum_sq += value_ms * value_ms;
+        if self.recent.len() == WINDOW {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(value_ms);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn mean(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.sum / self.count as f64
+        }
+    }
+
+    /// Population standard deviation over all samples.
+    pub fn std_dev(&self) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        let mean = self.mean();
+        (self.sum_sq / self.count as f64 - mean * mean).max(0.0).sqrt()
+    }
+
+    pub fn min(&self) -> f64 {
+        self.min
+    }
+
+    pub fn max(&self) -> f64 {
+        self.max
+    }
+
+    /// Percentile (0-100) over the most recent samples.
+    pub fn percentile(&self, p: f64) -> f64 {
+        if self.recent.is_empty() {
+            return 0.0;
+        }
+        let mut sorted: Vec<f64> = self.recent.iter().copied().collect();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let rank = ((p / 100.0) * (sorted.len() - 1) as f64).round() as usize;
+        sorted[rank.min(sorted.len() - 1)]
+    }
+
+    pub fn snapshot(&self) -> StatsSummary {
+        StatsSummary {
+            count: self.count,
+            mean_ms: self.mean(),
+            std_ms: self.std_dev(),
+            p50_ms: self.percentile(50.0),
+            p95_ms: self.percentile(95.0),
+            min_ms: self.min,
+            max_ms: self.max,
+        }
+    }
+
+    /// One-line summary with durations in `unit`, e.g. `n=120 mean=12.30ms p50=12.10ms p95=14.00ms max=15.20ms`.
+    pub fn summary(&self, unit: Unit) -> String {
+        format!(
+            "n={} mean={:.2} p50={:.2} p95={:.2} min={:.2} max={:.2}",
+            self.count,
+            DisplayMs(self.mean(), unit),
+            DisplayMs(self.percentile(50.0), unit),
+            DisplayMs(self.percentile(95.0), unit),
+            DisplayMs(self.min, unit),
+            DisplayMs(self.max, unit)
+        )
+    }
+}
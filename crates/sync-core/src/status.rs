@@ -0,0 +1,392 @@
+// Machine-readable status output.
+//
+// With `--status-format json` the binaries print every status and statistics
+// line as one JSON object per line (JSON Lines) instead of human text, so a
+// supervising process can parse stdout instead of scraping it. Every object
+// starts with the keys `type` (what the line reports, e.g. `synced`,
+// `unmatched_frame`, `latency`), `program`, `realtime_ns` and `monotonic_ns`,
+// followed by the fields of that type:
+//
+//   {"type":"synced","program":"subscriber","realtime_ns":...,"monotonic_ns":...,"trigger_id":42,...}
+//
+// Keys are stable: fields may be added, but are never renamed or removed.
+// Human-only output (usage, banners) is not printed in JSON mode.
+
+use std::fmt::{self, Write as _};
+use std::str::FromStr;
+
+use crate::cli::Args;
+use crate::clock;
+use crate::deadline::DeadlineMonitor;
+use crate::export;
+use crate::latency::LatencyBreakdown;
+use crate::leap::LeapSeconds;
+use crate::pipeline::{MatchPipeline, Stage, StageStats};
+use crate::units::{self, DisplayMs, Nanos, Unit};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StatusFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl FromStr for StatusFormat {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            other => Err(format!("unknown status format '{}' (expected text or json)", other)),
+        }
+    }
+}
+
+/// A field value of a JSON status line.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Uint(u64),
+    /// Non-finite values are written as `null`.
+    Float(f64),
+    Text(String),
+    /// Already encoded JSON, for nested objects and arrays.
+    Json(String),
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(value: i64) -> Self {
+        Self::Int(value)
+    }
+}
+
+impl From<u64> for Value {
+    fn from(value: u64) -> Self {
+        Self::Uint(value)
+    }
+}
+
+impl From<u32> for Value {
+    fn from(value: u32) -> Self {
+        Self::Uint(value as u64)
+    }
+}
+
+impl From<usize> for Value {
+    fn from(value: usize) -> Self {
+        Self::Uint(value as u64)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Self::Float(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Self::Text(value.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Self::Text(value)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Self::Null, Into::into)
+    }
+}
+
+impl fmt::Display for Value {
+    /// Formats the value as JSON.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => write!(f, "null"),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Int(value) => write!(f, "{}", value),
+            Value::Uint(value) => write!(f, "{}", value),
+            Value::Float(value) if value.is_finite() => write!(f, "{}", value),
+            Value::Float(_) => write!(f, "null"),
+            Value::Text(text) => write_json_string(f, text),
+            Value::Json(json) => write!(f, "{}", json),
+        }
+    }
+}
+
+fn write_json_string(out: &mut impl fmt::Write, text: &str) -> fmt::Result {
+    out.write_char('"')?;
+    for c in text.chars() {
+        match c {
+            '"' => out.write_str("\\\"")?,
+            '\\' => out.write_str("\\\\")?,
+            '\n' => out.write_str("\\n")?,
+            '\r' => out.write_str("\\r")?,
+            '\t' => out.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32)?,
+            c => out.write_char(c)?,
+        }
+    }
+    out.write_char('"')
+}
+
+/// Encodes `fields` as one JSON object.
+pub fn json_object(fields: &[(&str, Value)]) -> String {
+    let mut object = String::from("{");
+    write_fields(&mut object, fields);
+    object.push('}');
+    object
+}
+
+/// Encodes `values` as one JSON array.
+pub fn json_array(values: &[Value]) -> String {
+    let items: Vec<String> = values.iter().map(ToString::to_string).collect();
+    format!("[{}]", items.join(","))
+}
+
+fn write_fields(out: &mut String, fields: &[(&str, Value)]) {
+    for (i, (key, value)) in fields.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        let _ = write_json_string(out, key);
+        let _ = write!(out, ":{}", value);
+    }
+}
+
+/// Prints status lines in the selected format, with durations in the display unit.
+#[derive(Debug, Clone)]
+pub struct Status {
+    format: StatusFormat,
+    program: String,
+    display_unit: Unit,
+}
+
+impl Default for Status {
+    fn default() -> Self {
+        Self::new(StatusFormat::default(), "")
+    }
+}
+
+impl Status {
+    pub fn new(format: StatusFormat, program: &str) -> Self {
+        Self {
+            format,
+            program: program.to_string(),
+            display_unit: units::DEFAULT_DISPLAY_UNIT,
+        }
+    }
+
+    /// Durations in human-readable output in `unit` (see `units::DisplayMs`).
+    pub fn with_display_unit(self, display_unit: Unit) -> Self {
+        Self { display_unit, ..self }
+    }
+
+    /// Reads `--status-format` and `--display-unit`; an unknown value is an error for the binary to report.
+    pub fn from_args(args: &Args, program: &str) -> Result<Self, String> {
+        let format = args.value("status-format").map(str::parse::<StatusFormat>).transpose()?.unwrap_or_default();
+        let display_unit = args.value("display-unit").map(str::parse::<Unit>).transpose().map_err(|e| format!("--display-unit: {}", e))?;
+        Ok(Self::new(format, program).with_display_unit(display_unit.unwrap_or(units::DEFAULT_DISPLAY_UNIT)))
+    }
+
+    /// Unit durations are displayed in, `--display-unit` (ms by default).
+    pub fn display_unit(&self) -> Unit {
+        self.display_unit
+    }
+
+    pub fn is_json(&self) -> bool {
+        self.format == StatusFormat::Json
+    }
+
+    /// Prints `text`, or in JSON mode one object of type `kind` with `fields`.
+    pub fn line(&self, kind: &str, text: fmt::Arguments<'_>, fields: &[(&str, Value)]) {
+        match self.format {
+            StatusFormat::Text => println!("{}", text),
+            StatusFormat::Json => println!("{}", self.json(kind, fields)),
+        }
+    }
+
+    /// Prints human-only output (usage, banners); nothing in JSON mode.
+    pub fn text(&self, text: fmt::Arguments<'_>) {
+        if self.format == StatusFormat::Text {
+            println!("{}", text);
+        }
+    }
+
+    /// A `leap_seconds_expired` warning if the leap second table expired, nothing otherwise.
+    pub fn leap_seconds(&self, table: &LeapSeconds) {
+        let Some(expires_unix_s) = table.expires_unix_s().filter(|_| table.is_expired(Nanos::now())) else {
+            return;
+        };
+        let expired = export::utc_datetime(expires_unix_s * 1_000_000_000);
+        self.line(
+            "leap_seconds_expired",
+            format_args!("WARNING: leap second table expired on {}, leap seconds announced since are missing (update it with --leap-seconds <leap-seconds.list>)", &expired[..10]),
+            &[("expires_unix_s", expires_unix_s.into()), ("tai_minus_utc_s", table.current_offset_s().into())],
+        );
+    }
+
+    /// The latency report, in JSON mode one `latency` line per stage.
+    pub fn latency(&self, latency: &LatencyBreakdown) {
+        if !self.is_json() {
+            println!("{}", latency.report(self.display_unit));
+            return;
+        }
+        for (stage, stats) in latency.snapshot() {
+            println!(
+                "{}",
+                self.json(
+                    "latency",
+                    &[
+                        ("stage", stage.into()),
+                        ("count", stats.count.into()),
+                        ("mean_ms", stats.mean_ms.into()),
+                        ("std_ms", stats.std_ms.into()),
+                        ("p50_ms", stats.p50_ms.into()),
+                        ("p95_ms", stats.p95_ms.into()),
+                        ("min_ms", stats.min_ms.into()),
+                        ("max_ms", stats.max_ms.into()),
+                    ]
+                )
+            );
+        }
+    }
+
+    /// The `DEADLINES:` summary, in JSON mode one `deadline` line per monitor.
+    pub fn deadlines(&self, monitors: &[&DeadlineMonitor]) {
+        if !self.is_json() {
+            let summaries: Vec<String> = monitors.iter().map(|monitor| monitor.summary(self.display_unit)).collect();
+            println!("DEADLINES: {}", summaries.join(" | "));
+            return;
+        }
+        for monitor in monitors {
+            let (runs, missed) = monitor.counts();
+            println!(
+                "{}",
+                self.json(
+                    "deadline",
+                    &[
+                        ("section", monitor.section().into()),
+                        ("runs", runs.into()),
+                        ("missed", missed.into()),
+                        ("budget_ns", monitor.budget_ns().into()),
+                        ("worst_ns", monitor.worst_ns().into()),
+                    ]
+                )
+            );
+        }
+    }
+
+    /// The `STAGES:` summary of a matcher pipeline, in JSON mode one `match_stage` line per stage.
+    pub fn stages(&self, stages: &[(Stage, StageStats)]) {
+        if !self.is_json() {
+            let summaries: Vec<String> = stages.iter().map(|(stage, stats)| format!("{} {}", stage, stats.summary())).collect();
+            println!("STAGES: {}", summaries.join(" | "));
+            return;
+        }
+        for (stage, stats) in stages {
+            println!(
+                "{}",
+                self.json(
+                    "match_stage",
+                    &[
+                        ("stage", stage.name().into()),
+                        ("frames", stats.frames.into()),
+                        ("passed", stats.passed.into()),
+                        ("dropped", stats.dropped.into()),
+                        ("modified", stats.modified.into()),
+                        ("p50_us", (stats.time.percentile(50.0) * 1000.0).into()),
+                        ("p95_us", (stats.time.percentile(95.0) * 1000.0).into()),
+                        ("max_us", (stats.time.max() * 1000.0).into()),
+                    ]
+                )
+            );
+        }
+    }
+
+    /// The `LATENCY FILTER:` estimate of a pipeline with the `predict` stage, once it converged.
+    pub fn latency_filter<T>(&self, pipeline: &MatchPipeline<T>) {
+        let Some(estimate) = pipeline.latency_estimate() else {
+            return;
+        };
+        let (outliers, resets) = pipeline.latency_outliers();
+        let drift_us_per_s = estimate.drift_ms_per_s * 1e3;
+        self.line(
+            "latency_filter",
+            format_args!(
+                "LATENCY FILTER: latency={:.3} drift={:+.2}us/s uncertainty={:.3} jitter={:.3} outliers={} resets={}",
+                DisplayMs(estimate.latency_ms, self.display_unit),
+                drift_us_per_s,
+                DisplayMs(estimate.uncertainty_ms, self.display_unit),
+                DisplayMs(estimate.jitter_ms, self.display_unit),
+                outliers,
+                resets
+            ),
+            &[
+                ("latency_ms", estimate.latency_ms.into()),
+                ("drift_us_per_s", drift_us_per_s.into()),
+                ("uncertainty_ms", estimate.uncertainty_ms.into()),
+                ("jitter_ms", estimate.jitter_ms.into()),
+                ("outliers", outliers.into()),
+                ("resets", resets.into()),
+            ],
+        );
+    }
+
+    fn json(&self, kind: &str, fields: &[(&str, Value)]) -> String {
+        let mut line = String::from("{\"type\":");
+        let _ = write_json_string(&mut line, kind);
+        let _ = write!(line, ",\"program\":");
+        let _ = write_json_string(&mut line, &self.program);
+        let _ = write!(line, ",\"realtime_ns\":{},\"monotonic_ns\":{}", clock::realtime_now_ns(), clock::monotonic_now_ns());
+        if !fields.is_empty() {
+            line.push(',');
+            write_fields(&mut line, fields);
+        }
+        line.push('}');
+        line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::Unit;
+
+    fn from_args(line: &str) -> Result<Status, String> {
+        Status::from_args(&Args::parse(line.split_whitespace().map(str::to_string), &[]), "subscriber")
+    }
+
+    #[test]
+    fn status_format_is_parsed_and_unknown_values_are_errors() {
+        assert!(!from_args("subscriber").unwrap().is_json());
+        assert!(!from_args("subscriber --status-format text").unwrap().is_json());
+        assert!(from_args("subscriber --status-format json").unwrap().is_json());
+
+        assert_eq!(from_args("subscriber --status-format yaml").unwrap_err(), "unknown status format 'yaml' (expected text or json)");
+        assert!(from_args("subscriber --display-unit s").unwrap_err().starts_with("--display-unit: "));
+    }
+
+    #[test]
+    fn display_unit_is_kept_with_the_status_options() {
+        assert_eq!(from_args("subscriber").unwrap().display_unit(), Unit::Ms);
+        assert_eq!(from_args("subscriber --display-unit ns").unwrap().display_unit(), Unit::Ns);
+        // Each status keeps its own unit
+        let us = from_args("subscriber --display-unit us").unwrap();
+        assert_eq!(from_args("subscriber --status-format json").unwrap().display_unit(), Unit::Ms);
+        assert_eq!(us.display_unit(), Unit::Us);
+    }
+}
@@ -0,0 +1,284 @@
+// Supervision of the processes of a rig, for `sync_manager`.
+//
+// A rig runs a handful of binaries (publisher, capture processes, bridges)
+// that have to be started together, kept running and stopped together. The
+// rig config lists them in start order:
+//
+//   rig_id = "lab2"                # passed as --rig-id to every process
+//   restart_delay_ms = 500         # first restart delay, doubled per crash
+//   max_restart_delay_ms = 30000
+//   shutdown_timeout_ms = 5000     # SIGTERM to SIGKILL
+//
+//   [[process]]
+//   name = "publisher"
+//   program = "publisher"          # next to sync_manager, in PATH, or a path
+//   args = ["33", "--burst-size", "3"]
+//   restart = "always"             # always, on-failure or never
+//
+// A process that exits is restarted after a delay that doubles with every
+// exit in a row, up to `max_restart_delay_ms`; one that ran for `STABLE_AFTER`
+// starts over at `restart_delay_ms`. On SIGINT or SIGTERM the manager stops
+// restarting and terminates the processes in reverse start order, each with
+// SIGTERM and, after `shutdown_timeout_ms`, SIGKILL.
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// A process that ran this long restarts after the initial delay again.
+pub const STABLE_AFTER: Duration = Duration::from_secs(10);
+
+const DEFAULT_RESTART_DELAY_MS: u64 = 500;
+const DEFAULT_MAX_RESTART_DELAY_MS: u64 = 30_000;
+const DEFAULT_SHUTDOWN_TIMEOUT_MS: u64 = 5_000;
+
+static SHUTDOWN: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestartPolicy {
+    Always,
+    /// Only after a non-zero exit status or a signal.
+    OnFailure,
+    Never,
+}
+
+impl RestartPolicy {
+    pub fn name(self) -> &'static str {
+        match self {
+            RestartPolicy::Always => "always",
+            RestartPolicy::OnFailure => "on-failure",
+            RestartPolicy::Never => "never",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [RestartPolicy::Always, RestartPolicy::OnFailure, RestartPolicy::Never].into_iter().find(|policy| policy.name() == name)
+    }
+
+    /// Whether a process that exited, successfully or not, is started again.
+    pub fn restarts(self, success: bool) -> bool {
+        match self {
+            RestartPolicy::Always => true,
+            RestartPolicy::OnFailure => !success,
+            RestartPolicy::Never => false,
+        }
+    }
+}
+
+impl fmt::Display for RestartPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// One supervised process of the rig.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessSpec {
+    /// Prefix of its output lines, unique within the rig.
+    pub name: String,
+    pub program: String,
+    pub args: Vec<String>,
+    pub restart: RestartPolicy,
+}
+
+impl ProcessSpec {
+    /// Its arguments, with `--rig-id` added unless given.
+    pub fn command_args(&self, rig_id: Option<&str>) -> Vec<String> {
+        let mut args = self.args.clone();
+        if let Some(rig_id) = rig_id.filter(|_| !self.args.iter().any(|arg| arg == "--rig-id")) {
+            args.extend(["--rig-id".to_string(), rig_id.to_string()]);
+        }
+        args
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RigConfig {
+    pub rig_id: Option<String>,
+    pub restart_delay: Duration,
+    pub max_restart_delay: Duration,
+    pub shutdown_timeout: Duration,
+    /// In start order.
+    pub processes: Vec<ProcessSpec>,
+}
+
+impl RigConfig {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self::parse(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let table: toml::Table = text.parse().map_err(|e| format!("invalid rig config: {}", e))?;
+        let millis = |key: &str, default: u64| -> Result<Duration, String> {
+            match table.get(key) {
+                Some(value) => value.as_integer().filter(|ms| *ms >= 0).map(|ms| Duration::from_millis(ms as u64)).ok_or_else(|| format!("{} must be a number of milliseconds", key)),
+                None => Ok(Duration::from_millis(default)),
+            }
+        };
+        let mut processes: Vec<ProcessSpec> = Vec::new();
+        for (index, entry) in table.get("process").and_then(|value| value.as_array()).map(Vec::as_slice).unwrap_or_default().iter().enumerate() {
+            let entry = entry.as_table().ok_or_else(|| format!("process {} is not a table", index + 1))?;
+            let string = |key: &str| entry.get(key).and_then(|value| value.as_str()).map(str::to_string);
+            let program = string("program").ok_or_else(|| format!("process {} has no program", index + 1))?;
+            // Named after the program unless several run
+            let name = string("name").unwrap_or_else(|| program.clone());
+            if processes.iter().any(|process| process.name == name) {
+                return Err(format!("process name '{}' used twice", name));
+            }
+            let args = match entry.get("args") {
+                Some(args) => args
+                    .as_array()
+                    .and_then(|args| args.iter().map(|arg| arg.as_str().map(str::to_string)).collect::<Option<Vec<_>>>())
+                    .ok_or_else(|| format!("args of process '{}' must be a list of strings", name))?,
+                None => Vec::new(),
+            };
+            let restart = match string("restart") {
+                Some(policy) => RestartPolicy::from_name(&policy).ok_or_else(|| format!("unknown restart policy '{}' of process '{}' (expected always, on-failure or never)", policy, name))?,
+                None => RestartPolicy::Always,
+            };
+            processes.push(ProcessSpec { name, program, args, restart });
+        }
+        if processes.is_empty() {
+            return Err("no [[process]] in the rig config".to_string());
+        }
+        Ok(Self {
+            rig_id: table.get("rig_id").and_then(|value| value.as_str()).map(str::to_string),
+            restart_delay: millis("restart_delay_ms", DEFAULT_RESTART_DELAY_MS)?,
+            max_restart_delay: millis("max_restart_delay_ms", DEFAULT_MAX_RESTART_DELAY_MS)?,
+            shutdown_timeout: millis("shutdown_timeout_ms", DEFAULT_SHUTDOWN_TIMEOUT_MS)?,
+            processes,
+        })
+    }
+}
+
+/// Restart delays of one process: doubled per exit in a row, reset by a stable run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backoff {
+    initial: Duration,
+    max: Duration,
+    next: Duration,
+    /// Exits in a row without a stable run.
+    pub crashes: u32,
+}
+
+impl Backoff {
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        Self { initial, max: max.max(initial), next: initial, crashes: 0 }
+    }
+
+    /// The delay before restarting a process that exited after running for `ran_for`.
+    pub fn on_exit(&mut self, ran_for: Duration) -> Duration {
+        if ran_for >= STABLE_AFTER {
+            self.next = self.initial;
+            self.crashes = 0;
+        }
+        self.crashes += 1;
+        let delay = self.next;
+        self.next = (self.next * 2).min(self.max);
+        delay
+    }
+}
+
+#[cfg(unix)]
+extern "C" fn on_shutdown(_: libc::c_int) {
+    SHUTDOWN.store(true, Ordering::Relaxed);
+}
+
+/// Makes SIGINT and SIGTERM request a shutdown instead of ending the process.
+#[cfg(unix)]
+pub fn install_shutdown_handler() {
+    // Only stores to an atomic, which is async-signal-safe
+    unsafe {
+        libc::signal(libc::SIGINT, on_shutdown as extern "C" fn(libc::c_int) as libc::sighandler_t);
+        libc::signal(libc::SIGTERM, on_shutdown as extern "C" fn(libc::c_int) as libc::sighandler_t);
+    }
+}
+
+#[cfg(not(unix))]
+pub fn install_shutdown_handler() {}
+
+pub fn shutdown_requested() -> bool {
+    SHUTDOWN.load(Ordering::Relaxed)
+}
+
+/// Asks the process `pid` to exit (SIGTERM); `Child::kill` is the SIGKILL after the timeout.
+#[cfg(unix)]
+pub fn terminate(pid: u32) -> std::io::Result<()> {
+    // SAFETY: sends a signal, no memory is shared with the target
+    if unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn terminate(_pid: u32) -> std::io::Result<()> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "SIGTERM needs a unix host"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RIG: &str = r#"
+rig_id = "lab2"
+restart_delay_ms = 200
+
+[[process]]
+program = "publisher"
+args = ["33", "--burst-size", "3"]
+
+[[process]]
+name = "cam0"
+program = "v4l2_capture"
+args = ["/dev/video0", "--rig-id", "other"]
+restart = "on-failure"
+"#;
+
+    #[test]
+    fn rig_config_lists_processes_in_start_order() {
+        let config = RigConfig::parse(RIG).unwrap();
+        assert_eq!(config.rig_id.as_deref(), Some("lab2"));
+        assert_eq!(config.restart_delay, Duration::from_millis(200));
+        assert_eq!(config.max_restart_delay, Duration::from_secs(30));
+        assert_eq!(config.shutdown_timeout, Duration::from_secs(5));
+        let names: Vec<&str> = config.processes.iter().map(|process| process.name.as_str()).collect();
+        assert_eq!(names, ["publisher", "cam0"]);
+        assert_eq!(config.processes[0].restart, RestartPolicy::Always);
+        assert_eq!(config.processes[1].restart, RestartPolicy::OnFailure);
+
+        // The rig id is added unless a process has its own
+        assert_eq!(config.processes[0].command_args(Some("lab2")), ["33", "--burst-size", "3", "--rig-id", "lab2"]);
+        assert_eq!(config.processes[1].command_args(Some("lab2")), ["/dev/video0", "--rig-id", "other"]);
+        assert_eq!(config.processes[0].command_args(None), ["33", "--burst-size", "3"]);
+
+        let twice = "[[process]]\nprogram = \"publisher\"\n\n[[process]]\nprogram = \"publisher\"\n";
+        assert_eq!(RigConfig::parse(twice).unwrap_err(), "process name 'publisher' used twice");
+        assert_eq!(RigConfig::parse("rig_id = \"lab2\"\n").unwrap_err(), "no [[process]] in the rig config");
+        assert_eq!(RigConfig::parse("[[process]]\nname = \"cam0\"\n").unwrap_err(), "process 1 has no program");
+        assert_eq!(
+            RigConfig::parse("[[process]]\nprogram = \"publisher\"\nrestart = \"sometimes\"\n").unwrap_err(),
+            "unknown restart policy 'sometimes' of process 'publisher' (expected always, on-failure or never)"
+        );
+    }
+
+    #[test]
+    fn crashes_back_off_until_a_stable_run() {
+        let mut backoff = Backoff::new(Duration::from_millis(500), Duration::from_secs(3));
+        let quick = Duration::from_millis(100);
+        let delays: Vec<u128> = (0..5).map(|_| backoff.on_exit(quick).as_millis()).collect();
+        assert_eq!(delays, [500, 1000, 2000, 3000, 3000]);
+        assert_eq!(backoff.crashes, 5);
+
+        // A run of STABLE_AFTER starts over
+        assert_eq!(backoff.on_exit(STABLE_AFTER), Duration::from_millis(500));
+        assert_eq!(backoff.crashes, 1);
+
+        assert!(RestartPolicy::Always.restarts(true));
+        assert!(!RestartPolicy::OnFailure.restarts(true) && RestartPolicy::OnFailure.restarts(false));
+        assert!(!RestartPolicy::Never.restarts(false));
+        assert_eq!(RestartPolicy::from_name("on-failure"), Some(RestartPolicy::OnFailure));
+    }
+}
@@ -0,0 +1,302 @@
+// SMPTE 12M time-of-day timecodes derived from trigger timestamps.
+//
+// Video tooling (control room monitors, editing and broadcast gear) shows and
+// aligns streams by timecode, not by nanosecond timestamps. A matched frame
+// gets the timecode of its trigger's hardware timestamp: the time of day on
+// the chosen timescale (`--timecode-epoch unix|tai|gps`, UTC by default),
+// counted in frames of the timecode rate (`--timecode-rate`):
+//
+//   24, 25, 30, 50, 60, ...  non-drop-frame at whole rates
+//   23.976                   counted as 24 (the timecode runs 0.1% slow)
+//   29.97, 59.94             drop-frame: frame numbers 0-1 (0-3 at 59.94) are
+//                            skipped every minute except every tenth, so the
+//                            timecode stays on the wall clock
+//
+// Drop-frame timecodes print with `;` before the frame number. Frame metadata
+// in session records uses `.` instead, since `;` separates metadata entries.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::cli::Args;
+use crate::units::{Epoch, Nanos};
+
+const NS_PER_SECOND: u128 = 1_000_000_000;
+const SECONDS_PER_DAY: u64 = 86_400;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Timecode {
+    pub hours: u32,
+    pub minutes: u32,
+    pub seconds: u32,
+    pub frames: u32,
+    pub drop_frame: bool,
+}
+
+impl Timecode {
+    /// Non-drop-frame UTC time of day of `timestamp` at `fps` frames per second (at least 1).
+    pub fn at(timestamp: Nanos, fps: u32) -> Self {
+        TimecodeClock::new(TimecodeRate::whole(fps), Epoch::Unix).timecode(timestamp)
+    }
+
+    /// As stored in frame metadata: drop-frame timecodes use `.` before the frame number.
+    pub fn metadata_value(&self) -> String {
+        self.to_string().replace(';', ".")
+    }
+}
+
+/// `HH:MM:SS:FF`, drop-frame `HH:MM:SS;FF`
+impl fmt::Display for Timecode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let separator = if self.drop_frame { ';' } else { ':' };
+        write!(f, "{:02}:{:02}:{:02}{}{:02}", self.hours, self.minutes, self.seconds, separator, self.frames)
+    }
+}
+
+/// Parses `HH:MM:SS:FF`, `HH:MM:SS;FF` and the metadata form `HH:MM:SS.FF` (drop-frame).
+impl FromStr for Timecode {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid timecode '{}' (expected HH:MM:SS:FF)", text);
+        let split = text.rfind([':', ';', '.']).ok_or_else(invalid)?;
+        let drop_frame = !text[split..].starts_with(':');
+        let fields: Vec<u32> = text[..split].split(':').map(str::parse).collect::<Result<_, _>>().map_err(|_| invalid())?;
+        let [hours, minutes, seconds] = fields[..] else {
+            return Err(invalid());
+        };
+        Ok(Self {
+            hours,
+            minutes,
+            seconds,
+            frames: text[split + 1..].parse().map_err(|_| invalid())?,
+            drop_frame,
+        })
+    }
+}
+
+/// Frame rate a timecode counts in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimecodeRate {
+    /// Actual rate as a fraction, e.g. 30000/1001.
+    pub numerator: u32,
+    pub denominator: u32,
+    /// Frame numbers per timecode second, e.g. 30 at 29.97.
+    pub nominal_fps: u32,
+    pub drop_frame: bool,
+}
+
+impl TimecodeRate {
+    /// Non-drop-frame at a whole rate (at least 1).
+    pub fn whole(fps: u32) -> Self {
+        let fps = fps.max(1);
+        Self {
+            numerator: fps,
+            denominator: 1,
+            nominal_fps: fps,
+            drop_frame: false,
+        }
+    }
+
+    /// NTSC rate of `nominal_fps` (24000/1001 at 24), drop-frame at 30 and 60.
+    fn ntsc(nominal_fps: u32) -> Self {
+        Self {
+            numerator: nominal_fps * 1000,
+            denominator: 1001,
+            nominal_fps,
+            drop_frame: nominal_fps.is_multiple_of(30),
+        }
+    }
+
+    /// Frame numbers skipped at the start of each minute not divisible by ten.
+    fn dropped_per_minute(&self) -> u64 {
+        if self.drop_frame {
+            self.nominal_fps as u64 / 15
+        } else {
+            0
+        }
+    }
+
+    /// XMP `xmpDM:timeFormat`, e.g. `25Timecode`, `23976Timecode` or `2997DropTimecode`.
+    pub fn xmp_time_format(&self) -> String {
+        if self.denominator == 1 {
+            return format!("{}Timecode", self.nominal_fps);
+        }
+        let label = self.to_string().replace('.', "");
+        format!("{}{}Timecode", label, if self.drop_frame { "Drop" } else { "" })
+    }
+}
+
+impl Default for TimecodeRate {
+    fn default() -> Self {
+        Self::whole(30)
+    }
+}
+
+impl fmt::Display for TimecodeRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.denominator, self.nominal_fps) {
+            (1, fps) => write!(f, "{}", fps),
+            (_, 24) => write!(f, "23.976"),
+            (_, fps) => write!(f, "{:.2}", fps as f64 * 1000.0 / 1001.0),
+        }
+    }
+}
+
+/// `24`, `25`, `30`, ... or the NTSC rates `23.976`, `29.97` and `59.94`.
+impl FromStr for TimecodeRate {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "23.976" | "23.98" => Ok(Self::ntsc(24)),
+            "29.97" => Ok(Self::ntsc(30)),
+            "59.94" => Ok(Self::ntsc(60)),
+            _ => match text.parse::<u32>() {
+                Ok(fps) if fps > 0 => Ok(Self::whole(fps)),
+                _ => Err(format!("invalid timecode rate '{}' (expected e.g. 25, 30, 23.976, 29.97 or 59.94)", text)),
+            },
+        }
+    }
+}
+
+/// Derives timecodes from trigger timestamps at a rate, on a timescale.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TimecodeClock {
+    pub rate: TimecodeRate,
+    pub epoch: Epoch,
+}
+
+impl TimecodeClock {
+    pub fn new(rate: TimecodeRate, epoch: Epoch) -> Self {
+        Self { rate, epoch }
+    }
+
+    /// `--timecode-rate` and `--timecode-epoch`; `None` without `--timecode-rate`.
+    pub fn from_args(args: &Args) -> Result<Option<Self>, String> {
+        let Some(rate) = args.value("timecode-rate") else {
+            return Ok(None);
+        };
+        Ok(Some(Self {
+            rate: rate.parse()?,
+            epoch: args.value("timecode-epoch").map_or(Ok(Epoch::default()), str::parse)?,
+        }))
+    }
+
+    /// Timecode of the frame exposed at `timestamp`.
+    pub fn timecode(&self, timestamp: Nanos) -> Timecode {
+        let rate = &self.rate;
+        let ns_of_day = (timestamp.0 as i128 + self.epoch.offset_ns(timestamp)).rem_euclid(SECONDS_PER_DAY as i128 * NS_PER_SECOND as i128) as u128;
+        let fps = rate.nominal_fps as u64;
+        let dropped = rate.dropped_per_minute();
+        let frames_per_10_minutes = fps * 600 - dropped * 9;
+        let frames_per_day = frames_per_10_minutes * 144;
+        let mut count = (ns_of_day * rate.numerator as u128 / (rate.denominator as u128 * NS_PER_SECOND)) as u64 % frames_per_day;
+
+        // Drop-frame: add back the frame numbers skipped so far, then count like non-drop-frame
+        if dropped > 0 {
+            let tens = count / frames_per_10_minutes;
+            let rest = count % frames_per_10_minutes;
+            count += dropped * 9 * tens;
+            if rest > dropped {
+                count += dropped * ((rest - dropped) / (fps * 60 - dropped));
+            }
+        }
+        Timecode {
+            hours: (count / (fps * 3_600) % 24) as u32,
+            minutes: (count / (fps * 60) % 60) as u32,
+            seconds: (count / fps % 60) as u32,
+            frames: (count % fps) as u32,
+            drop_frame: rate.drop_frame,
+        }
+    }
+
+    /// Short label for logs, e.g. `29.97fps drop-frame, unix time of day`.
+    pub fn describe(&self) -> String {
+        let drop_frame = if self.rate.drop_frame { " drop-frame" } else { "" };
+        format!("{}fps{}, {} time of day", self.rate, drop_frame, self.epoch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NS_PER_SECOND: u64 = 1_000_000_000;
+
+    // Midnight UTC, 2023-11-15
+    const MIDNIGHT_NS: u64 = 1_700_006_400 * NS_PER_SECOND;
+
+    // Start of frame `count` at `numerator`/`denominator` fps after midnight
+    fn frame_ns(count: u64, numerator: u64, denominator: u64) -> Nanos {
+        Nanos(MIDNIGHT_NS + (count * denominator * NS_PER_SECOND).div_ceil(numerator))
+    }
+
+    fn timecode(rate: &str, timestamp: Nanos) -> String {
+        TimecodeClock::new(rate.parse().unwrap(), Epoch::Unix).timecode(timestamp).to_string()
+    }
+
+    #[test]
+    fn triggers_get_the_utc_time_of_day_in_frames() {
+        // 2020-01-01 13:45:07.52 UTC
+        let timestamp = Nanos(1_577_886_307_520_000_000);
+        let timecode = Timecode::at(timestamp, 25);
+        assert_eq!((timecode.hours, timecode.minutes, timecode.seconds, timecode.frames), (13, 45, 7, 13));
+        assert_eq!(timecode.to_string(), "13:45:07:13");
+        assert_eq!(timecode.to_string().parse::<Timecode>(), Ok(timecode));
+        // The last frame of the day wraps to midnight
+        assert_eq!(Timecode::at(Nanos(86_399_999_999_999), 30).to_string(), "23:59:59:29");
+        assert_eq!(Timecode::at(Nanos(86_400_000_000_000), 30).to_string(), "00:00:00:00");
+        // A rate of 0 counts whole seconds
+        assert_eq!(Timecode::at(timestamp, 0).frames, 0);
+    }
+
+    #[test]
+    fn whole_rates_follow_the_wall_clock() {
+        assert_eq!(timecode("25", frame_ns(0, 25, 1)), "00:00:00:00");
+        assert_eq!(timecode("25", frame_ns(24, 25, 1)), "00:00:00:24");
+        assert_eq!(timecode("25", Nanos(MIDNIGHT_NS + 13 * 3_600 * NS_PER_SECOND + 500_000_000)), "13:00:00:12");
+        assert_eq!(timecode("25", Nanos(MIDNIGHT_NS - 1)), "23:59:59:24");
+        assert_eq!(Timecode::at(frame_ns(31, 30, 1), 30).to_string(), "00:00:01:01");
+    }
+
+    #[test]
+    fn drop_frame_skips_frame_numbers() {
+        assert_eq!(timecode("29.97", frame_ns(1_799, 30_000, 1_001)), "00:00:59;29");
+        assert_eq!(timecode("29.97", frame_ns(1_800, 30_000, 1_001)), "00:01:00;02");
+        // Every tenth minute keeps frames 0 and 1
+        assert_eq!(timecode("29.97", frame_ns(17_982, 30_000, 1_001)), "00:10:00;00");
+        assert_eq!(timecode("29.97", frame_ns(17_983, 30_000, 1_001)), "00:10:00;01");
+        assert_eq!(timecode("59.94", frame_ns(3_600, 60_000, 1_001)), "00:01:00;04");
+        // An hour of drop-frame timecode is an hour of wall-clock time
+        assert_eq!(timecode("29.97", Nanos(MIDNIGHT_NS + 3_600 * NS_PER_SECOND)), "01:00:00;00");
+    }
+
+    #[test]
+    fn epochs_shift_the_time_of_day() {
+        let rate = TimecodeRate::whole(25);
+        let gps = TimecodeClock::new(rate, Epoch::Gps).timecode(Nanos(MIDNIGHT_NS));
+        let tai = TimecodeClock::new(rate, Epoch::Tai).timecode(Nanos(MIDNIGHT_NS));
+        assert_eq!(gps.to_string(), "00:00:18:00");
+        assert_eq!(tai.to_string(), "00:00:37:00");
+    }
+
+    #[test]
+    fn timecodes_and_rates_have_log_and_metadata_forms() {
+        for text in ["01:02:03:04", "23:59:59;29"] {
+            let timecode: Timecode = text.parse().unwrap();
+            assert_eq!(timecode.to_string(), text);
+            assert_eq!(timecode.metadata_value().parse::<Timecode>().unwrap(), timecode);
+        }
+        assert_eq!("10:00:00;02".parse::<Timecode>().unwrap().metadata_value(), "10:00:00.02");
+        assert!("10:00:00".parse::<Timecode>().is_err());
+
+        for text in ["24", "25", "23.976", "29.97", "59.94"] {
+            assert_eq!(text.parse::<TimecodeRate>().unwrap().to_string(), text);
+        }
+        assert_eq!("29.97".parse::<TimecodeRate>().unwrap().xmp_time_format(), "2997DropTimecode");
+        assert_eq!("23.976".parse::<TimecodeRate>().unwrap().xmp_time_format(), "23976Timecode");
+        assert_eq!("25".parse::<TimecodeRate>().unwrap().xmp_time_format(), "25Timecode");
+        assert!("29.9".parse::<TimecodeRate>().is_err());
+    }
+}
@@ -0,0 +1,326 @@
+// Trigger pulse trains generated by a timer, for kHz trigger rates.
+//
+// The publisher loop cannot pace triggers much faster than its sleep
+// granularity allows, so strobed lighting and line-scan cameras (kHz rates)
+// get their pulses from a timer instead. The timer is programmed once with the
+// rate; the publisher only reads back when each pulse actually fired and
+// publishes it (batched at these rates, see `trigger_batch`). Two timers:
+//
+//   PHC periodic output  `PTP_PEROUT_REQUEST` makes the PTP hardware clock of
+//                        a NIC drive the trigger line. The line is looped back
+//                        to an external timestamp channel of the same clock
+//                        (`PTP_EXTTS_REQUEST`), which latches every edge in
+//                        hardware. Source `timer`.
+//   timerfd chain        an absolute periodic CLOCK_REALTIME timerfd; a pulse
+//                        fires when the publisher wakes for an expiration, and
+//                        its time is read right then. Source `system`.
+//
+// Pulses that fired but were never read back (the kernel's timestamp queue
+// overflowed) or expirations the publisher slept through are counted as
+// missed. The PHC pins must be assigned to the periodic output and external
+// timestamp functions first, e.g. `testptp -d /dev/ptp0 -L 0,2 -L 1,1`.
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::os::fd::{AsRawFd, FromRawFd};
+
+use crate::clock;
+use crate::leap::Tai;
+use crate::timestamping::{self, TimestampSource, TriggerTimestamper};
+use crate::units::Nanos;
+use uapi::*;
+
+/// Highest supported pulse rate.
+pub const MAX_RATE_HZ: f64 = 100_000.0;
+
+/// Layout of `<linux/ptp_clock.h>`; most fields are only read by the kernel.
+#[allow(dead_code)]
+mod uapi {
+    pub const PTP_ENABLE_FEATURE: u32 = 1 << 0;
+    pub const PTP_RISING_EDGE: u32 = 1 << 1;
+
+    /// `_IOW('=', 2, struct ptp_extts_request)`
+    pub const PTP_EXTTS_REQUEST: u64 = 0x4010_3D02;
+    /// `_IOW('=', 3, struct ptp_perout_request)`
+    pub const PTP_PEROUT_REQUEST: u64 = 0x4038_3D03;
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Default)]
+    pub struct ClockTime {
+        pub sec: i64,
+        pub nsec: u32,
+        pub reserved: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Default)]
+    pub struct ExttsRequest {
+        pub index: u32,
+        pub flags: u32,
+        pub rsv: [u32; 2],
+    }
+
+    #[repr(C)]
+    #[derive(Default)]
+    pub struct PeroutRequest {
+        pub start: ClockTime,
+        pub period: ClockTime,
+        pub index: u32,
+        pub flags: u32,
+        pub rsv: [u32; 4],
+    }
+
+    /// `struct ptp_extts_event`
+    #[repr(C)]
+    #[derive(Default)]
+    pub struct ExttsEvent {
+        pub t: ClockTime,
+        pub index: u32,
+        pub flags: u32,
+        pub rsv: [u32; 2],
+    }
+}
+
+/// Timer period for `rate_hz`, which must be above 0 and at most `MAX_RATE_HZ`.
+pub fn period_ns(rate_hz: f64) -> Result<u64, String> {
+    if !(rate_hz > 0.0 && rate_hz <= MAX_RATE_HZ) {
+        return Err(format!("invalid timer rate {}Hz (expected up to {}Hz)", rate_hz, MAX_RATE_HZ));
+    }
+    Ok((1e9 / rate_hz).round() as u64)
+}
+
+/// Pulses missing between two pulses read back `gap_ns` apart.
+fn missed_between(gap_ns: u64, period_ns: u64) -> u64 {
+    // Half a period of slack for jitter
+    ((gap_ns + period_ns / 2) / period_ns).saturating_sub(1)
+}
+
+enum Timer {
+    Phc {
+        path: String,
+        clock: File,
+        perout_channel: u32,
+        extts_channel: u32,
+    },
+    Timerfd(File),
+}
+
+/// A timer firing trigger pulses at a fixed rate, read back pulse by pulse.
+pub struct PulseTrain {
+    timer: Timer,
+    period_ns: u64,
+    last_ns: Option<u64>,
+    missed: u64,
+}
+
+impl PulseTrain {
+    /// Periodic output `perout_channel` of the PTP hardware clock at `path`, read back
+    /// through its external timestamp channel `extts_channel`. The first pulse fires on
+    /// the second full second of the clock.
+    pub fn phc(path: &str, perout_channel: u32, extts_channel: u32, period_ns: u64) -> io::Result<Self> {
+        let clock = File::options().read(true).write(true).open(path)?;
+        let mut now = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+        // SAFETY: `now` is a valid timespec and the clock id refers to the open PHC
+        if unsafe { libc::clock_gettime(timestamping::phc_clock_id(&clock), &mut now) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut extts = ExttsRequest {
+            index: extts_channel,
+            flags: PTP_ENABLE_FEATURE | PTP_RISING_EDGE,
+            ..Default::default()
+        };
+        // SAFETY: `extts` matches `struct ptp_extts_request`
+        if unsafe { libc::ioctl(clock.as_raw_fd(), PTP_EXTTS_REQUEST as _, &mut extts) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut perout = PeroutRequest {
+            start: ClockTime {
+                sec: now.tv_sec + 2,
+                ..Default::default()
+            },
+            period: ClockTime {
+                sec: (period_ns / 1_000_000_000) as i64,
+                nsec: (period_ns % 1_000_000_000) as u32,
+                reserved: 0,
+            },
+            index: perout_channel,
+            ..Default::default()
+        };
+        // SAFETY: `perout` matches `struct ptp_perout_request`
+        if unsafe { libc::ioctl(clock.as_raw_fd(), PTP_PEROUT_REQUEST as _, &mut perout) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self {
+            timer: Timer::Phc {
+                path: path.to_string(),
+                clock,
+                perout_channel,
+                extts_channel,
+            },
+            period_ns,
+            last_ns: None,
+            missed: 0,
+        })
+    }
+
+    /// An absolute periodic `CLOCK_REALTIME` timerfd, aligned to the UTC grid of the period.
+    pub fn timerfd(period_ns: u64) -> io::Result<Self> {
+        // SAFETY: plain syscall, the returned descriptor is owned by the `File` below
+        let fd = unsafe { libc::timerfd_create(libc::CLOCK_REALTIME, libc::TFD_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `fd` is a new descriptor that nothing else owns
+        let timer = unsafe { File::from_raw_fd(fd) };
+        let timespec = |ns: u64| libc::timespec {
+            tv_sec: (ns / 1_000_000_000) as libc::time_t,
+            tv_nsec: (ns % 1_000_000_000) as libc::c_long,
+        };
+        let spec = libc::itimerspec {
+            it_interval: timespec(period_ns),
+            it_value: timespec(clock::next_grid_ns(clock::realtime_now_ns(), period_ns, 0)),
+        };
+        // SAFETY: `spec` is a valid itimerspec, the old value is not requested
+        if unsafe { libc::timerfd_settime(timer.as_raw_fd(), libc::TFD_TIMER_ABSTIME, &spec, std::ptr::null_mut()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self {
+            timer: Timer::Timerfd(timer),
+            period_ns,
+            last_ns: None,
+            missed: 0,
+        })
+    }
+
+    // Next pulse from the PHC external timestamp channel, CLOCK_REALTIME ns
+    fn next_edge(clock: &mut File, extts_channel: u32) -> io::Result<u64> {
+        loop {
+            let mut event = ExttsEvent::default();
+            // SAFETY: `ExttsEvent` is plain old data, any bytes form a valid value
+            let bytes = unsafe { std::slice::from_raw_parts_mut(&mut event as *mut ExttsEvent as *mut u8, std::mem::size_of::<ExttsEvent>()) };
+            clock.read_exact(bytes)?;
+            // Other channels of the clock may be in use by someone else
+            if event.index == extts_channel {
+                let tai_ns = event.t.sec as u64 * 1_000_000_000 + event.t.nsec as u64;
+                return Ok(Tai(tai_ns).to_utc().0);
+            }
+        }
+    }
+}
+
+impl Drop for PulseTrain {
+    fn drop(&mut self) {
+        // Stop the pulses, a zero period disables the output
+        if let Timer::Phc {
+            clock,
+            perout_channel,
+            extts_channel,
+            ..
+        } = &self.timer
+        {
+            let mut perout = PeroutRequest {
+                index: *perout_channel,
+                ..Default::default()
+            };
+            let mut extts = ExttsRequest {
+                index: *extts_channel,
+                ..Default::default()
+            };
+            // SAFETY: the requests match their kernel structs; errors leave nothing to clean up
+            unsafe {
+                libc::ioctl(clock.as_raw_fd(), PTP_PEROUT_REQUEST as _, &mut perout);
+                libc::ioctl(clock.as_raw_fd(), PTP_EXTTS_REQUEST as _, &mut extts);
+            }
+        }
+    }
+}
+
+impl TriggerTimestamper for PulseTrain {
+    fn source(&self) -> TimestampSource {
+        match self.timer {
+            Timer::Phc { .. } => TimestampSource::Timer,
+            Timer::Timerfd(_) => TimestampSource::System,
+        }
+    }
+
+    fn describe(&self) -> String {
+        let rate_hz = 1e9 / self.period_ns as f64;
+        match &self.timer {
+            Timer::Phc {
+                path,
+                perout_channel,
+                extts_channel,
+                ..
+            } => format!(
+                "{:.0}Hz periodic output {} of PTP hardware clock {}, read back on external timestamp channel {}",
+                rate_hz, perout_channel, path, extts_channel
+            ),
+            Timer::Timerfd(_) => format!("{:.0}Hz timerfd chain (CLOCK_REALTIME)", rate_hz),
+        }
+    }
+
+    fn is_external(&self) -> bool {
+        true
+    }
+
+    fn missed(&self) -> u64 {
+        self.missed
+    }
+
+    fn next(&mut self) -> io::Result<Nanos> {
+        let fired_ns = match &mut self.timer {
+            Timer::Phc { clock, extts_channel, .. } => Self::next_edge(clock, *extts_channel)?,
+            Timer::Timerfd(timer) => {
+                let mut expirations = [0u8; 8];
+                timer.read_exact(&mut expirations)?;
+                // Expirations slept through never fired a pulse
+                self.missed += u64::from_ne_bytes(expirations).saturating_sub(1);
+                clock::realtime_now_ns()
+            }
+        };
+        if let (Timer::Phc { .. }, Some(last_ns)) = (&self.timer, self.last_ns) {
+            self.missed += missed_between(fired_ns.saturating_sub(last_ns), self.period_ns);
+        }
+        self.last_ns = Some(fired_ns);
+        Ok(Nanos(fired_ns))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem::size_of;
+
+    #[test]
+    fn rates_give_periods_and_missed_pulses() {
+        assert_eq!(period_ns(1_000.0), Ok(1_000_000));
+        assert_eq!(period_ns(3.0), Ok(333_333_333));
+        assert!(period_ns(0.0).is_err());
+        assert!(period_ns(MAX_RATE_HZ * 2.0).is_err());
+        assert!(period_ns(f64::NAN).is_err());
+        assert_eq!(missed_between(1_000_400, 1_000_000), 0);
+        assert_eq!(missed_between(3_000_400, 1_000_000), 2);
+        assert_eq!(missed_between(1_600_000, 1_000_000), 1);
+    }
+
+    #[test]
+    fn uapi_structs_match_the_ioctl_sizes() {
+        let size = |request: u64| ((request >> 16) & 0x3fff) as usize;
+        assert_eq!(size_of::<ExttsRequest>(), size(PTP_EXTTS_REQUEST));
+        assert_eq!(size_of::<PeroutRequest>(), size(PTP_PEROUT_REQUEST));
+        assert_eq!(size_of::<ExttsEvent>(), 32);
+    }
+
+    #[test]
+    fn timerfd_pulses_are_read_back_in_order() {
+        let period_ns = period_ns(1_000.0).unwrap();
+        let mut pulses = PulseTrain::timerfd(period_ns).unwrap();
+        assert_eq!(pulses.source(), TimestampSource::System);
+        assert_eq!(pulses.describe(), "1000Hz timerfd chain (CLOCK_REALTIME)");
+        let first = pulses.next().unwrap().0;
+        let second = pulses.next().unwrap().0;
+        // Read right after each expiration, a period apart unless the test thread was preempted
+        assert!(second > first);
+        assert!(second - first < 50 * period_ns, "{}", second - first);
+    }
+}
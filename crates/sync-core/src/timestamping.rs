@@ -0,0 +1,444 @@
+// Pluggable trigger timestamp sources, ranked by precision.
+//
+// How the trigger timestamp is taken bounds the achievable sync accuracy. The
+// publisher takes it from one `TriggerTimestamper`, selected with
+// `--timestamp-source`, and records the source in the user header of every
+// trigger (`TriggerSignature::timestamp_source`), so subscribers and recorded
+// sessions know how far a timestamp can be trusted:
+//
+//   rank  source  spec                       typical precision
+//   0     system  system                     ~50us, CLOCK_REALTIME read in user space
+//   1     irq     irq:<irq>[:<bpf dir>]      ~10us, IRQ handler entry stamped by eBPF
+//   2     gpio    (HTE fallback)             ~10us, GPIO edge at interrupt time
+//   3     phc     phc:/dev/ptp0              ~5us, PTP hardware clock read in user space
+//   4     mcu     mcu:<udp addr>             ~1us, edge timestamped by a trigger MCU
+//   5     timer   timer:<hz>:<phc>[:<o>:<i>] ~50ns, PHC periodic output read back in hardware
+//   6     hte     hte:<chip>:<line>[:<edge>] ~10ns, GPIO edge latched by the timestamp engine
+//
+// System and PHC stamp triggers generated by the publisher itself. External
+// sources (MCU, GPIO/HTE, trigger interrupts, timers) deliver the timestamps of edges fired
+// elsewhere: their `next` waits for the next edge, and the publisher follows
+// their pace. `timer:<hz>` without a PHC is a timerfd chain stamped by the
+// system clock (see `timer`).
+//
+// A PHC disciplined by a PTP grandmaster counts network time. The PHC source
+// reads the system clock right before and after the PHC and sends the PHC's
+// offset from the midpoint with every trigger (`phc_offset_ns` in the header),
+// so subscribers can move their `CLOCK_REALTIME` frame times onto network time
+// even where phc2sys does not keep the system clock in step. `--clock
+// ptp:<dev>` selects this source with the triggers published in PTP time.
+
+use std::fmt;
+use std::io;
+use std::net::UdpSocket;
+use std::str::FromStr;
+
+use crate::clock::{self, ClockDomain};
+#[cfg(target_os = "linux")]
+use crate::gpio;
+#[cfg(target_os = "linux")]
+use crate::irq;
+#[cfg(target_os = "linux")]
+use crate::leap::Tai;
+#[cfg(target_os = "linux")]
+use crate::timer;
+use crate::units::Nanos;
+use crate::wire::{TriggerMessage, MAX_MESSAGE_LEN};
+
+/// Where a trigger timestamp came from. The discriminant is the value carried in the trigger header.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimestampSource {
+    #[default]
+    System = 0,
+    Phc = 1,
+    Mcu = 2,
+    Hte = 3,
+    Gpio = 4,
+    Timer = 5,
+    Irq = 6,
+}
+
+impl TimestampSource {
+    /// Decodes the header value; unknown values (newer publishers) are `None`.
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Self::System),
+            1 => Some(Self::Phc),
+            2 => Some(Self::Mcu),
+            3 => Some(Self::Hte),
+            4 => Some(Self::Gpio),
+            5 => Some(Self::Timer),
+            6 => Some(Self::Irq),
+            _ => None,
+        }
+    }
+
+    /// Name of a header value, also for values unknown to this build.
+    pub fn label(value: u8) -> String {
+        Self::from_u8(value).map_or_else(|| format!("unknown-{}", value), |source| source.to_string())
+    }
+
+    /// Quality rank, higher is more precise.
+    pub fn rank(self) -> u8 {
+        match self {
+            Self::System => 0,
+            Self::Irq => 1,
+            Self::Gpio => 2,
+            Self::Phc => 3,
+            Self::Mcu => 4,
+            Self::Timer => 5,
+            Self::Hte => 6,
+        }
+    }
+
+    /// Typical timestamp error of the source.
+    pub fn precision_ns(self) -> u64 {
+        match self {
+            Self::System => 50_000,
+            Self::Irq => 10_000,
+            Self::Gpio => 10_000,
+            Self::Phc => 5_000,
+            Self::Mcu => 1_000,
+            Self::Timer => 50,
+            Self::Hte => 10,
+        }
+    }
+}
+
+impl fmt::Display for TimestampSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::System => "system",
+            Self::Phc => "phc",
+            Self::Mcu => "mcu",
+            Self::Hte => "hte",
+            Self::Gpio => "gpio",
+            Self::Timer => "timer",
+            Self::Irq => "irq",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for TimestampSource {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "system" => Ok(Self::System),
+            "phc" => Ok(Self::Phc),
+            "mcu" => Ok(Self::Mcu),
+            "hte" => Ok(Self::Hte),
+            "gpio" => Ok(Self::Gpio),
+            "timer" => Ok(Self::Timer),
+            "irq" => Ok(Self::Irq),
+            other => Err(format!("unknown timestamp source '{}' (expected system, phc, mcu, hte, gpio, timer or irq)", other)),
+        }
+    }
+}
+
+/// A trigger timestamp source plugin.
+pub trait TriggerTimestamper {
+    fn source(&self) -> TimestampSource;
+
+    /// Human readable description for logs and the session manifest.
+    fn describe(&self) -> String;
+
+    /// Triggers fire outside the publisher (GPIO edge, MCU); `next` waits for them.
+    fn is_external(&self) -> bool {
+        false
+    }
+
+    /// External pulses that fired but were never returned by `next`.
+    fn missed(&self) -> u64 {
+        0
+    }
+
+    /// PTP hardware clock minus `CLOCK_REALTIME` at the last `next`, for sources that read a PHC.
+    fn clock_offset_ns(&self) -> Option<i64> {
+        None
+    }
+
+    /// Timestamp of the trigger firing now, or of the next external edge (CLOCK_REALTIME).
+    fn next(&mut self) -> io::Result<Nanos>;
+}
+
+/// `CLOCK_REALTIME` at the time the publisher fires the trigger.
+pub struct SystemClock;
+
+impl TriggerTimestamper for SystemClock {
+    fn source(&self) -> TimestampSource {
+        TimestampSource::System
+    }
+
+    fn describe(&self) -> String {
+        "system clock (CLOCK_REALTIME)".to_string()
+    }
+
+    fn next(&mut self) -> io::Result<Nanos> {
+        Ok(Nanos::now())
+    }
+}
+
+/// A PTP hardware clock (`/dev/ptpN`) read as a dynamic POSIX clock.
+#[cfg(target_os = "linux")]
+pub struct PhcClock {
+    path: String,
+    // Keeps the clock id valid
+    _file: std::fs::File,
+    clock_id: libc::clockid_t,
+    offset_ns: Option<i64>,
+}
+
+#[cfg(target_os = "linux")]
+impl PhcClock {
+    pub fn open(path: &str) -> io::Result<Self> {
+        let file = std::fs::File::open(path)?;
+        let clock_id = phc_clock_id(&file);
+        Ok(Self {
+            path: path.to_string(),
+            _file: file,
+            clock_id,
+            offset_ns: None,
+        })
+    }
+}
+
+/// Dynamic POSIX clock id of an open PTP hardware clock (`FD_TO_CLOCKID` of the kernel's posix-timers ABI).
+#[cfg(target_os = "linux")]
+pub(crate) fn phc_clock_id(file: &std::fs::File) -> libc::clockid_t {
+    use std::os::fd::AsRawFd;
+
+    ((!file.as_raw_fd()) << 3) | 3
+}
+
+#[cfg(target_os = "linux")]
+impl TriggerTimestamper for PhcClock {
+    fn source(&self) -> TimestampSource {
+        TimestampSource::Phc
+    }
+
+    fn describe(&self) -> String {
+        format!("PTP hardware clock {}", self.path)
+    }
+
+    fn clock_offset_ns(&self) -> Option<i64> {
+        self.offset_ns
+    }
+
+    fn next(&mut self) -> io::Result<Nanos> {
+        let mut ts = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+        let before_ns = clock::realtime_now_ns();
+        // SAFETY: `ts` is a valid timespec and `clock_id` refers to the open PHC
+        if unsafe { libc::clock_gettime(self.clock_id, &mut ts) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let after_ns = clock::realtime_now_ns();
+        let tai_ns = ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64;
+        self.offset_ns = Some(phc_offset_ns(tai_ns, before_ns, after_ns));
+        Ok(Tai(tai_ns).to_utc())
+    }
+}
+
+/// Offset of a PHC reading taken between two `CLOCK_REALTIME` readings, against their midpoint. PTP time is
+/// TAI, so a system clock in step with the PHC is behind by the TAI-UTC offset (37s since 2017).
+pub fn phc_offset_ns(phc_ns: u64, realtime_before_ns: u64, realtime_after_ns: u64) -> i64 {
+    let midpoint_ns = realtime_before_ns / 2 + realtime_after_ns / 2 + (realtime_before_ns % 2 + realtime_after_ns % 2) / 2;
+    phc_ns as i64 - midpoint_ns as i64
+}
+
+/// Edge timestamps reported by a trigger MCU as wire-encoded triggers over UDP
+/// (see `wire`). The MCU clock is expected to be synchronized to UTC.
+pub struct McuReceiver {
+    socket: UdpSocket,
+    buffer: [u8; MAX_MESSAGE_LEN],
+}
+
+impl McuReceiver {
+    pub fn bind(address: &str) -> io::Result<Self> {
+        Ok(Self {
+            socket: UdpSocket::bind(address)?,
+            buffer: [0; MAX_MESSAGE_LEN],
+        })
+    }
+}
+
+impl TriggerTimestamper for McuReceiver {
+    fn source(&self) -> TimestampSource {
+        TimestampSource::Mcu
+    }
+
+    fn describe(&self) -> String {
+        match self.socket.local_addr() {
+            Ok(address) => format!("trigger MCU reporting to udp {}", address),
+            Err(_) => "trigger MCU".to_string(),
+        }
+    }
+
+    fn is_external(&self) -> bool {
+        true
+    }
+
+    fn next(&mut self) -> io::Result<Nanos> {
+        loop {
+            let len = self.socket.recv(&mut self.buffer)?;
+            // Malformed datagrams are skipped, the next edge follows soon
+            if let Ok(message) = TriggerMessage::decode(&self.buffer[..len]) {
+                return Ok(Nanos(message.trigger.hw_timestamp_ns));
+            }
+        }
+    }
+}
+
+/// Timestamp source spec and clock domain of a `--clock` spec: `ptp[:<device>]` reads a PTP hardware clock
+/// (`/dev/ptp0` by default) and publishes PTP time.
+pub fn clock_spec(spec: &str) -> Result<(String, ClockDomain), String> {
+    match spec.split_once(':').unwrap_or((spec, "")) {
+        ("ptp", "") => Ok(("phc:/dev/ptp0".to_string(), ClockDomain::Ptp)),
+        ("ptp", device) => Ok((format!("phc:{}", device), ClockDomain::Ptp)),
+        _ => Err(format!("unknown clock '{}' (expected ptp:<device>)", spec)),
+    }
+}
+
+/// Opens a source from its spec: `system`, `phc:<device>`, `mcu:<udp bind address>`,
+/// `hte:<gpiochip>:<line>[:rising|falling|both]`, `timer:<hz>[:<ptp device>[:<perout>:<extts>]]`
+/// or `irq:<irq>[:<bpf dir>]`. `gpio:` is the same as `hte:`; both fall back to interrupt-time
+/// timestamps where the line has no HTE support. A timer without a PTP device is a timerfd chain.
+/// `irq:` needs the eBPF program of `irq` loaded, its maps pinned in the bpf dir.
+pub fn open(spec: &str) -> Result<Box<dyn TriggerTimestamper>, Box<dyn std::error::Error>> {
+    let (kind, argument) = spec.split_once(':').unwrap_or((spec, ""));
+    match kind.parse::<TimestampSource>()? {
+        TimestampSource::System => Ok(Box::new(SystemClock)),
+        #[cfg(target_os = "linux")]
+        TimestampSource::Phc => Ok(Box::new(PhcClock::open(if argument.is_empty() { "/dev/ptp0" } else { argument })?)),
+        TimestampSource::Mcu if !argument.is_empty() => Ok(Box::new(McuReceiver::bind(argument)?)),
+        TimestampSource::Mcu => Err("mcu source needs a bind address, e.g. mcu:0.0.0.0:5005".into()),
+        #[cfg(target_os = "linux")]
+        TimestampSource::Hte | TimestampSource::Gpio => {
+            let mut parts = argument.split(':');
+            let (Some(chip), Some(line)) = (parts.next().filter(|chip| !chip.is_empty()), parts.next()) else {
+                return Err("hte source needs a GPIO chip and line, e.g. hte:/dev/gpiochip0:12".into());
+            };
+            let edge = parts.next().unwrap_or("rising").parse::<gpio::Edge>()?;
+            let line = line.parse::<u32>().map_err(|_| format!("invalid GPIO line '{}'", line))?;
+            Ok(Box::new(gpio::GpioEdges::open(chip, line, edge)?))
+        }
+        #[cfg(target_os = "linux")]
+        TimestampSource::Timer => {
+            let mut parts = argument.split(':');
+            let rate = parts.next().unwrap_or_default();
+            let rate_hz = rate.parse::<f64>().map_err(|_| format!("timer source needs a rate in Hz, e.g. timer:5000 (got '{}')", rate))?;
+            let period_ns = timer::period_ns(rate_hz)?;
+            let Some(device) = parts.next() else {
+                return Ok(Box::new(timer::PulseTrain::timerfd(period_ns)?));
+            };
+            let channel = |channel: Option<&str>| {
+                let channel = channel.unwrap_or("0");
+                channel.parse::<u32>().map_err(|_| format!("invalid PTP channel '{}'", channel))
+            };
+            let (perout, extts) = (channel(parts.next())?, channel(parts.next())?);
+            Ok(Box::new(timer::PulseTrain::phc(device, perout, extts, period_ns)?))
+        }
+        #[cfg(target_os = "linux")]
+        TimestampSource::Irq => {
+            let (number, dir) = argument.split_once(':').unwrap_or((argument, irq::DEFAULT_BPF_DIR));
+            let number = number.parse::<u32>().map_err(|_| format!("irq source needs the trigger's IRQ number, e.g. irq:142 (got '{}')", number))?;
+            Ok(Box::new(irq::IrqTimestamps::open(number, std::path::Path::new(dir))?))
+        }
+        #[cfg(not(target_os = "linux"))]
+        source => Err(format!("timestamp source '{}' is not supported on this platform", source).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ClockDomain;
+    use crate::signing::TriggerSignature;
+    use crate::wire::TriggerMessage;
+    use crate::CameraTrigger;
+
+    const NS_PER_SECOND: u64 = 1_000_000_000;
+
+    // 2023-11-14 22:13:20 UTC, TAI-UTC 37s
+    const REALTIME_NS: u64 = 1_700_000_000 * NS_PER_SECOND;
+
+    #[test]
+    fn sources_are_ranked_by_precision() {
+        let sources: Vec<TimestampSource> = (0..=6).map(|value| TimestampSource::from_u8(value).unwrap()).collect();
+        let mut ranked = sources.clone();
+        ranked.sort_by_key(|source| source.rank());
+        assert!(ranked.windows(2).all(|pair| pair[0].precision_ns() >= pair[1].precision_ns()));
+        for source in sources {
+            assert_eq!(source.to_string().parse::<TimestampSource>(), Ok(source));
+            assert_eq!(TimestampSource::from_u8(source as u8), Some(source));
+        }
+        assert_eq!(TimestampSource::label(9), "unknown-9");
+        assert!(open("mcu").is_err());
+        assert!(open("sundial").is_err());
+    }
+
+    #[test]
+    fn mcu_edges_arrive_as_wire_triggers() {
+        let mut mcu = McuReceiver::bind("127.0.0.1:0").unwrap();
+        assert!(mcu.is_external());
+        let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let address = mcu.socket.local_addr().unwrap();
+        let trigger = TriggerMessage { trigger: CameraTrigger::new(7, 1_700_000_000_123_456_789, 0), signature: TriggerSignature::default(), link_tx: None };
+        // Malformed datagrams are skipped
+        sender.send_to(b"noise", address).unwrap();
+        sender.send_to(&trigger.encode(), address).unwrap();
+        assert_eq!(mcu.next().unwrap(), Nanos(1_700_000_000_123_456_789));
+    }
+
+    #[test]
+    fn phc_offset_puts_frames_on_network_time() {
+        // A PHC in TAI read between two system clock readings 2001ns apart, 300ns after their midpoint
+        let phc_ns = REALTIME_NS + 37 * NS_PER_SECOND + 1_300;
+        let offset_ns = phc_offset_ns(phc_ns, REALTIME_NS, REALTIME_NS + 2_001);
+        assert_eq!(offset_ns, 37_000_000_300);
+        let header = TriggerSignature {
+            phc_offset_ns: offset_ns,
+            ..TriggerSignature::default()
+        };
+        assert_eq!(header.network_time_ns(REALTIME_NS + 5_000), Some(REALTIME_NS + 37 * NS_PER_SECOND + 5_300));
+        assert_eq!(TriggerSignature::default().network_time_ns(REALTIME_NS), None);
+
+        assert_eq!(clock_spec("ptp:/dev/ptp1"), Ok(("phc:/dev/ptp1".to_string(), ClockDomain::Ptp)));
+        assert_eq!(clock_spec("ptp"), Ok(("phc:/dev/ptp0".to_string(), ClockDomain::Ptp)));
+        assert_eq!(clock_spec("gps:/dev/pps0").unwrap_err(), "unknown clock 'gps:/dev/pps0' (expected ptp:<device>)");
+    }
+
+    #[test]
+    fn phc_offset_survives_the_wire() {
+        let message = TriggerMessage {
+            trigger: CameraTrigger::new(7, REALTIME_NS, REALTIME_NS + 40_000),
+            signature: TriggerSignature {
+                clock_domain: ClockDomain::Ptp as u8,
+                phc_offset_ns: -1_250,
+                ..TriggerSignature::default()
+            },
+            link_tx: None,
+        };
+        let decoded = TriggerMessage::decode(&message.encode()).unwrap();
+        assert_eq!(decoded.signature.phc_offset_ns, -1_250);
+
+        // Without a PHC the offset is left out
+        let without = TriggerMessage {
+            signature: TriggerSignature {
+                phc_offset_ns: 0,
+                ..message.signature
+            },
+            ..message
+        };
+        assert_eq!(without.encode().len() + 4, message.encode().len());
+        assert_eq!(TriggerMessage::decode(&without.encode()).unwrap().signature.phc_offset_ns, 0);
+
+        // An explicit 0 is not canonical
+        let mut bytes = without.encode();
+        bytes[0] += 1;
+        bytes.extend_from_slice(&[0x17, 0x00]);
+        assert_eq!(TriggerMessage::decode(&bytes).unwrap_err(), "non-canonical phc_offset_ns 0");
+    }
+}
@@ -0,0 +1,244 @@
+// Tracepoints on the hot path for whole-system timelines.
+//
+// With `--trace <target>` the processes write their hot path events into the
+// kernel's trace buffers, where they land next to scheduler and driver
+// activity:
+//
+//   ftrace   /sys/kernel/tracing/trace_marker, recorded by perfetto (the
+//            `ftrace/print` event with atrace parsing) and trace-cmd. Trigger
+//            ids show up as counter tracks, match decisions as slices.
+//   lttng    /proc/lttng-logger (lttng-modules), recorded as `lttng_logger`
+//            events of an LTTng kernel session.
+//
+// Events: trigger published, trigger received, frame dequeued, match decided.
+// Every event is a single write, as both interfaces require; write errors are
+// ignored so tracing can never stall the hot path.
+//
+// A `TraceId` follows one trigger, and the frames matched to it, through every
+// process from the publisher to the last consumer. It is the trigger id plus
+// a random origin the publisher picks at startup and sends in the user header
+// (`TriggerSignature::trace_origin`), so ids stay unique across publisher
+// restarts and federated rigs. Bridges pass the origin on, consumers print the
+// trace id as `trace=` in their trigger and match lines, tracepoints, session
+// records and published frame headers, so grepping one id reconstructs the
+// frame's journey across processes and hosts.
+
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+
+use crate::clock;
+use crate::signing::TriggerSignature;
+use crate::CameraTrigger;
+
+const FTRACE_MARKERS: [&str; 2] = ["/sys/kernel/tracing/trace_marker", "/sys/kernel/debug/tracing/trace_marker"];
+const LTTNG_LOGGER: &str = "/proc/lttng-logger";
+
+/// Identifies one trigger and its frames across processes: `<origin as 8 hex digits>-<trigger id>`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct TraceId {
+    /// Publisher instance that created the trigger, 0 if unknown (older publishers).
+    pub origin: u32,
+    pub trigger_id: u64,
+}
+
+impl TraceId {
+    pub fn new(origin: u32, trigger_id: u64) -> Self {
+        Self { origin, trigger_id }
+    }
+
+    /// Of a received trigger, from its user header.
+    pub fn of(trigger: &CameraTrigger, header: &TriggerSignature) -> Self {
+        Self::new(header.trace_origin, trigger.frame_id)
+    }
+
+    /// Origin for a new publisher instance: never 0, and different across restarts and hosts.
+    pub fn new_origin() -> u32 {
+        // splitmix64 over both clocks and the pid
+        let mut x = clock::realtime_now_ns() ^ clock::monotonic_now_ns().rotate_left(32) ^ ((std::process::id() as u64) << 48);
+        x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        ((x ^ (x >> 31)) as u32).max(1)
+    }
+
+    /// Parses the `Display` form.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let (origin, trigger_id) = text.split_once('-').ok_or_else(|| format!("trace id '{}' is not <origin>-<trigger id>", text))?;
+        if origin.len() != 8 {
+            return Err(format!("trace origin '{}' is not 8 hex digits", origin));
+        }
+        Ok(Self {
+            origin: u32::from_str_radix(origin, 16).map_err(|e| format!("trace origin '{}': {}", origin, e))?,
+            trigger_id: trigger_id.parse().map_err(|e| format!("trigger id '{}': {}", trigger_id, e))?,
+        })
+    }
+}
+
+impl fmt::Display for TraceId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:08x}-{}", self.origin, self.trigger_id)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    /// atrace/systrace text understood by perfetto.
+    Atrace,
+    /// Plain text lines.
+    Text,
+}
+
+#[derive(Default)]
+pub struct Tracer {
+    sink: Option<(File, Format)>,
+    pid: u32,
+}
+
+impl Tracer {
+    /// Opens `ftrace`, `lttng` or a file path (atrace text, for testing without root).
+    pub fn open(target: &str) -> io::Result<Self> {
+        let open = |path: &str| OpenOptions::new().append(true).open(path);
+        let sink = match target {
+            "ftrace" => (open(FTRACE_MARKERS[0]).or_else(|_| open(FTRACE_MARKERS[1]))?, Format::Atrace),
+            "lttng" => (open(LTTNG_LOGGER)?, Format::Text),
+            path => (OpenOptions::new().create(true).append(true).open(path)?, Format::Atrace),
+        };
+        Ok(Self {
+            sink: Some(sink),
+            pid: std::process::id(),
+        })
+    }
+
+    pub fn trigger_published(&mut self, trace: TraceId, hw_ts: u64) {
+        self.counter("trigger_published", trace, hw_ts);
+    }
+
+    pub fn trigger_received(&mut self, trace: TraceId, hw_ts: u64) {
+        self.counter("trigger_received", trace, hw_ts);
+    }
+
+    pub fn frame_dequeued(&mut self, frame_ns: u64) {
+        let Some((_, format)) = &self.sink else {
+            return;
+        };
+        let line = match format {
+            Format::Atrace => format!("I|{}|frame_dequeued frame_ns={}", self.pid, frame_ns),
+            Format::Text => format!("sync frame_dequeued frame_ns={}", frame_ns),
+        };
+        self.write(&line);
+    }
+
+    /// Opens the match slice; closed by `match_decided`.
+    pub fn match_started(&mut self) {
+        if let Some((_, Format::Atrace)) = &self.sink {
+            let line = format!("B|{}|match", self.pid);
+            self.write(&line);
+        }
+    }
+
+    pub fn match_decided(&mut self, trace: Option<TraceId>, score_ms: f64) {
+        let Some((_, format)) = &self.sink else {
+            return;
+        };
+        let line = match (format, trace) {
+            (Format::Atrace, Some(trace)) => format!("E|{}|trigger_id={} trace={} score_ms={:.3}", self.pid, trace.trigger_id, trace, score_ms),
+            (Format::Atrace, None) => format!("E|{}|unmatched", self.pid),
+            (Format::Text, Some(trace)) => format!("sync match_decided trigger_id={} trace={} score_ms={:.3}", trace.trigger_id, trace, score_ms),
+            (Format::Text, None) => "sync match_decided unmatched".to_string(),
+        };
+        self.write(&line);
+    }
+
+    // Counter tracks carry only a number, the trigger id; the text format adds the trace id
+    fn counter(&mut self, name: &str, trace: TraceId, hw_ts: u64) {
+        let Some((_, format)) = &self.sink else {
+            return;
+        };
+        let line = match format {
+            Format::Atrace => format!("C|{}|{}|{}", self.pid, name, trace.trigger_id),
+            Format::Text => format!("sync {} trigger_id={} trace={} hw_ts={}", name, trace.trigger_id, trace, hw_ts),
+        };
+        self.write(&line);
+    }
+
+    fn write(&mut self, line: &str) {
+        if let Some((file, _)) = &mut self.sink {
+            let _ = file.write_all(format!("{}\n", line).as_bytes());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::retime::FrameHeader;
+    use crate::wire::TriggerMessage;
+
+    #[test]
+    fn file_targets_get_atrace_events() {
+        let path = std::env::temp_dir().join(format!("camera_sync_trace_{}.txt", std::process::id()));
+        let mut tracer = Tracer::open(path.to_str().unwrap()).unwrap();
+        let trace = TraceId::new(0xab, 7);
+        tracer.trigger_published(trace, 1_000);
+        tracer.frame_dequeued(2_000);
+        tracer.match_started();
+        tracer.match_decided(Some(trace), 1.25);
+        tracer.match_started();
+        tracer.match_decided(None, 0.0);
+        // Without a target nothing is written, and nothing fails
+        Tracer::default().trigger_received(trace, 1_000);
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let pid = std::process::id();
+        let expected = [
+            format!("C|{}|trigger_published|7", pid),
+            format!("I|{}|frame_dequeued frame_ns=2000", pid),
+            format!("B|{}|match", pid),
+            format!("E|{}|trigger_id=7 trace=000000ab-7 score_ms=1.250", pid),
+            format!("B|{}|match", pid),
+            format!("E|{}|unmatched", pid),
+        ];
+        assert_eq!(text.lines().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn trace_id_survives_the_wire_and_retimed_frames() {
+        let origin = TraceId::new_origin();
+        assert_ne!(origin, 0);
+        let trigger = CameraTrigger::new(1042, 1_700_000_000_000_000_000, 1_700_000_000_000_050_000);
+        let header = TriggerSignature {
+            trace_origin: 0x3fa2_c91b,
+            ..TriggerSignature::default()
+        };
+        let message = TriggerMessage {
+            trigger,
+            signature: header,
+            link_tx: None,
+        };
+        let decoded = TriggerMessage::decode(&message.encode()).unwrap();
+        let trace = TraceId::of(&decoded.trigger, &decoded.signature);
+        assert_eq!(trace, TraceId::new(0x3fa2_c91b, 1042));
+        assert_eq!(trace.to_string(), "3fa2c91b-1042");
+        assert_eq!(TraceId::parse("3fa2c91b-1042"), Ok(trace));
+        assert!(TraceId::parse("3fa2c91b").is_err());
+
+        // Unknown origins cost no bytes on the wire
+        let unknown = TriggerMessage {
+            signature: TriggerSignature::default(),
+            ..message
+        };
+        assert_eq!(unknown.encode().len() + 6, message.encode().len());
+        assert_eq!(TriggerMessage::decode(&unknown.encode()).unwrap().signature.trace_origin, 0);
+
+        let source = FrameHeader {
+            timestamp_ns: 1_700_000_000_004_000_000,
+            ..FrameHeader::default()
+        };
+        assert_eq!(source.trace_id(), None);
+        let retimed = source.retimed(Some((trigger, trace.origin)));
+        assert_eq!((retimed.timestamp_ns, retimed.trace_id()), (trigger.hw_timestamp_ns, Some(trace)));
+        assert_eq!(retimed.retimed(None).trace_id(), None);
+    }
+}
@@ -0,0 +1,317 @@
+// The trigger transport as the binaries see it, and an in-memory mock of it.
+//
+// `TriggerSink` and `TriggerSource` are the few calls the processes make on
+// the trigger service: publish a trigger or a batch, receive the next
+// trigger, and wait for one to arrive instead of polling.
+// `sync_iceoryx2::trigger` implements them over shared memory;
+// `MockTransport` implements them over queues in memory, owned by a thread
+// that stands in for the service and served over channels, so tests of
+// publisher and subscriber logic run in plain `cargo test` on machines
+// without shared-memory permissions.
+//
+// The mock follows the QoS of the real service, which takes its settings from
+// the constants below: late subscribers get the last `HISTORY_SIZE` samples,
+// a subscriber buffers up to `SUBSCRIBER_BUFFER_SIZE` samples and then loses
+// the oldest (safe overflow, counted as undelivered), at most
+// `MAX_SUBSCRIBERS` subscribers connect, and batches are returned trigger by
+// trigger. Loan pool exhaustion can be switched on to test the handling of
+// dropped triggers.
+//
+// `round_trips` is the delivery check of `selftest`, written against the
+// traits so it runs on either transport.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::error::Error;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::{Duration, Instant};
+
+use crate::clock;
+use crate::delivery::DeliveryCounts;
+use crate::signing::TriggerSignature;
+use crate::stats::LatencyStats;
+use crate::trigger_batch::TriggerBatch;
+use crate::CameraTrigger;
+
+/// Samples kept for subscribers that connect later, for late V4L2 frames.
+pub const HISTORY_SIZE: usize = 10;
+/// Samples a subscriber buffers, for trigger bursts.
+pub const SUBSCRIBER_BUFFER_SIZE: usize = 20;
+/// Camera processes subscribing to one trigger service.
+pub const MAX_SUBSCRIBERS: usize = 3;
+
+/// Publishing side of a trigger service.
+pub trait TriggerSink {
+    /// Publishes a trigger; `false` if the loan pool was exhausted and the trigger was dropped.
+    fn publish(&self, trigger: CameraTrigger, signature: TriggerSignature) -> Result<bool, Box<dyn Error>>;
+
+    /// Publishes a batch of triggers in one sample, see `publish`.
+    fn publish_batch(&self, batch: &TriggerBatch, header: TriggerSignature) -> Result<bool, Box<dyn Error>>;
+
+    /// Triggers published so far and the ones subscribers could not take.
+    fn delivery(&self) -> DeliveryCounts;
+}
+
+/// Receiving side of a trigger service.
+pub trait TriggerSource {
+    /// The next trigger and its header, if any; batches are returned trigger by trigger.
+    fn receive(&self) -> Result<Option<(CameraTrigger, TriggerSignature)>, Box<dyn Error>>;
+
+    /// Blocks until a trigger arrives or `timeout` passed, once `receive` returned `None`; `false` on
+    /// timeout. A trigger that arrived since the last `receive` returns right away.
+    fn wait(&self, timeout: Duration) -> Result<bool, Box<dyn Error>>;
+}
+
+/// Triggers sent through a transport and read back, see `round_trips`.
+#[derive(Debug, Clone, Default)]
+pub struct RoundTrips {
+    pub sent: u64,
+    /// Not received within the timeout, or dropped by the publisher.
+    pub lost: u64,
+    /// Received with a different trigger or header.
+    pub corrupted: u64,
+    /// Publish to receive, ms.
+    pub latency: LatencyStats,
+}
+
+/// Sends `count` triggers one at a time, each with its own signature bytes, and waits up to `timeout` for each.
+pub fn round_trips(publisher: &impl TriggerSink, subscriber: &impl TriggerSource, count: u64, timeout: Duration) -> Result<RoundTrips, Box<dyn Error>> {
+    let mut results = RoundTrips::default();
+    for frame_id in 1..=count {
+        let mut signature = TriggerSignature::default();
+        signature.signature[0] = frame_id as u8;
+        let published_ns = clock::realtime_now_ns();
+        let trigger = CameraTrigger::new(frame_id, published_ns, published_ns);
+        results.sent += 1;
+        if !publisher.publish(trigger, signature)? {
+            results.lost += 1;
+            continue;
+        }
+
+        let deadline = Instant::now() + timeout;
+        let received = loop {
+            if let Some(received) = subscriber.receive()? {
+                break Some(received);
+            }
+            let now = Instant::now();
+            if now > deadline {
+                break None;
+            }
+            subscriber.wait(deadline - now)?;
+        };
+        let received_ns = clock::realtime_now_ns();
+        match received {
+            Some((received, header)) => {
+                if received != trigger || header.signature != signature.signature {
+                    results.corrupted += 1;
+                }
+                results.latency.add(received_ns.saturating_sub(published_ns) as f64 / 1e6);
+            }
+            None => results.lost += 1,
+        }
+    }
+    Ok(results)
+}
+
+// A sample: one trigger, or the triggers of a batch
+type Sample = Vec<(CameraTrigger, TriggerSignature)>;
+
+// Calls on the bus thread, which owns the state of the mock service; answers come back on the
+// enclosed channel
+enum Request {
+    Send(Sample, Sender<bool>),
+    Subscribe(Sender<Result<(usize, Receiver<()>), String>>),
+    Unsubscribe(usize),
+    Take(usize, Sender<Option<Sample>>),
+    SetLoansExhausted(bool),
+    Delivery(Sender<DeliveryCounts>),
+}
+
+// A subscriber's buffer; every sample sent to it also sends a wakeup for its `wait`
+struct Inbox {
+    id: usize,
+    samples: VecDeque<Sample>,
+    wake: Sender<()>,
+}
+
+#[derive(Default)]
+struct Bus {
+    history: VecDeque<Sample>,
+    subscribers: Vec<Inbox>,
+    next_id: usize,
+    delivery: DeliveryCounts,
+    loans_exhausted: bool,
+}
+
+impl Bus {
+    // Serves requests until every handle of the mock is dropped
+    fn run(mut self, requests: Receiver<Request>) {
+        for request in requests {
+            match request {
+                Request::Send(sample, sent) => {
+                    let _ = sent.send(self.send(sample));
+                }
+                Request::Subscribe(subscribed) => {
+                    let _ = subscribed.send(self.subscribe());
+                }
+                Request::Unsubscribe(id) => self.subscribers.retain(|inbox| inbox.id != id),
+                Request::Take(id, taken) => {
+                    let sample = self.subscribers.iter_mut().find(|inbox| inbox.id == id).and_then(|inbox| inbox.samples.pop_front());
+                    let _ = taken.send(sample);
+                }
+                Request::SetLoansExhausted(exhausted) => self.loans_exhausted = exhausted,
+                Request::Delivery(delivery) => {
+                    let _ = delivery.send(self.delivery.clone());
+                }
+            }
+        }
+    }
+
+    fn send(&mut self, sample: Sample) -> bool {
+        if self.loans_exhausted {
+            return false;
+        }
+        self.delivery.published += sample.len() as u64;
+        for inbox in &mut self.subscribers {
+            if inbox.samples.len() == SUBSCRIBER_BUFFER_SIZE {
+                let overwritten = inbox.samples.pop_front().unwrap_or_default();
+                self.delivery.record_undelivered(overwritten.len() as u64);
+            }
+            inbox.samples.push_back(sample.clone());
+            let _ = inbox.wake.send(());
+        }
+        if self.history.len() == HISTORY_SIZE {
+            self.history.pop_front();
+        }
+        self.history.push_back(sample);
+        true
+    }
+
+    fn subscribe(&mut self) -> Result<(usize, Receiver<()>), String> {
+        if self.subscribers.len() == MAX_SUBSCRIBERS {
+            return Err(format!("trigger service supports at most {} subscribers", MAX_SUBSCRIBERS));
+        }
+        self.next_id += 1;
+        let (wake, woken) = mpsc::channel();
+        self.subscribers.push(Inbox {
+            id: self.next_id,
+            samples: self.history.clone(),
+            wake,
+        });
+        Ok((self.next_id, woken))
+    }
+}
+
+// Sends a request to the bus thread and returns its answer
+fn call<T>(requests: &Sender<Request>, request: impl FnOnce(Sender<T>) -> Request) -> Result<T, Box<dyn Error>> {
+    let (answer, answered) = mpsc::channel();
+    requests.send(request(answer)).map_err(|_| "mock trigger bus stopped")?;
+    Ok(answered.recv().map_err(|_| "mock trigger bus stopped")?)
+}
+
+/// An in-memory trigger service; publishers and subscribers of one mock see each other, across threads.
+#[derive(Clone)]
+pub struct MockTransport {
+    requests: Sender<Request>,
+}
+
+impl MockTransport {
+    /// Starts the thread that stands in for the service; it ends with the last handle of the mock.
+    pub fn new() -> Self {
+        let (requests, served) = mpsc::channel();
+        std::thread::Builder::new()
+            .name("mock-trigger-bus".to_string())
+            .spawn(move || Bus::default().run(served))
+            .expect("could not start the mock trigger bus");
+        Self { requests }
+    }
+
+    pub fn publisher(&self) -> MockPublisher {
+        MockPublisher { requests: self.requests.clone() }
+    }
+
+    /// Connects a subscriber, which gets the history first. Fails beyond `MAX_SUBSCRIBERS`.
+    pub fn subscriber(&self) -> Result<MockSubscriber, Box<dyn Error>> {
+        let (id, woken) = call(&self.requests, Request::Subscribe)??;
+        Ok(MockSubscriber {
+            requests: self.requests.clone(),
+            id,
+            woken,
+            unpacked: RefCell::default(),
+        })
+    }
+
+    /// While set, publishing fails like an exhausted loan pool.
+    pub fn set_loans_exhausted(&self, exhausted: bool) {
+        let _ = self.requests.send(Request::SetLoansExhausted(exhausted));
+    }
+}
+
+impl Default for MockTransport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct MockPublisher {
+    requests: Sender<Request>,
+}
+
+impl TriggerSink for MockPublisher {
+    fn publish(&self, trigger: CameraTrigger, signature: TriggerSignature) -> Result<bool, Box<dyn Error>> {
+        call(&self.requests, |sent| Request::Send(vec![(trigger, signature)], sent))
+    }
+
+    fn publish_batch(&self, batch: &TriggerBatch, header: TriggerSignature) -> Result<bool, Box<dyn Error>> {
+        let sample = batch.triggers().map(|trigger| (trigger, header)).collect();
+        call(&self.requests, |sent| Request::Send(sample, sent))
+    }
+
+    fn delivery(&self) -> DeliveryCounts {
+        call(&self.requests, Request::Delivery).unwrap_or_default()
+    }
+}
+
+/// Disconnects when dropped.
+pub struct MockSubscriber {
+    requests: Sender<Request>,
+    id: usize,
+    // One wakeup per sample sent to this subscriber
+    woken: Receiver<()>,
+    // Triggers of a received batch not returned yet
+    unpacked: RefCell<VecDeque<(CameraTrigger, TriggerSignature)>>,
+}
+
+impl MockSubscriber {
+    // Moves the next buffered sample into `unpacked`; `false` if there was none
+    fn take(&self) -> Result<bool, Box<dyn Error>> {
+        let sample = call(&self.requests, |taken| Request::Take(self.id, taken))?;
+        let taken = sample.is_some();
+        self.unpacked.borrow_mut().extend(sample.unwrap_or_default());
+        Ok(taken)
+    }
+}
+
+impl TriggerSource for MockSubscriber {
+    fn receive(&self) -> Result<Option<(CameraTrigger, TriggerSignature)>, Box<dyn Error>> {
+        if self.unpacked.borrow().is_empty() {
+            self.take()?;
+        }
+        Ok(self.unpacked.borrow_mut().pop_front())
+    }
+
+    fn wait(&self, timeout: Duration) -> Result<bool, Box<dyn Error>> {
+        // Wakeups of samples sent before the check below are stale
+        while self.woken.try_recv().is_ok() {}
+        if !self.unpacked.borrow().is_empty() || self.take()? {
+            return Ok(true);
+        }
+        Ok(self.woken.recv_timeout(timeout).is_ok())
+    }
+}
+
+impl Drop for MockSubscriber {
+    fn drop(&mut self) {
+        let _ = self.requests.send(Request::Unsubscribe(self.id));
+    }
+}
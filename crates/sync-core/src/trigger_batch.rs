@@ -0,0 +1,261 @@
+// Batched trigger samples for kHz trigger rates.
+//
+// Above a few hundred Hz (strobes, line-scan rigs) the per-sample cost of
+// the trigger service (loan, send, waking every subscriber) dominates. The
+// publisher then packs up to `MAX_BATCH` consecutive triggers into one
+// `TriggerBatch` sample on the batched trigger service (see
+// `sync_iceoryx2::trigger`): the first trigger in full, the others as deltas
+// to their predecessor. Deltas are u32, so consecutive triggers may be up to
+// about 4.2s apart; a trigger that cannot be encoded that way (ids not
+// increasing, a clock going backwards) starts a new batch. Subscribers
+// unpack batches transparently.
+//
+// `BatchSelector` switches to batches when the observed trigger rate exceeds
+// `--batch-above-hz` and back when it falls below 80% of it. A batch is sent
+// when it is full, when its first trigger is `--batch-max-delay-ms` old, or
+// before the publisher goes back to single triggers, so batching adds at most
+// that delay. A batch carries one user header, which holds the timestamp
+// source; signed triggers are never batched.
+
+#[cfg(feature = "iceoryx2")]
+use iceoryx2::prelude::ZeroCopySend;
+
+use crate::CameraTrigger;
+
+/// Triggers per batch sample.
+pub const MAX_BATCH: usize = 64;
+
+/// Batches are left again below this fraction of the threshold rate.
+const HYSTERESIS: f64 = 0.8;
+
+/// Weight of the newest trigger interval in the rate estimate.
+const RATE_SMOOTHING: f64 = 0.1;
+
+/// A trigger relative to the one before it in the batch.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "iceoryx2", derive(ZeroCopySend))]
+pub struct TriggerDelta {
+    pub trigger_id: u32,
+    pub hw_timestamp_ns: u32,
+    pub publish_timestamp_ns: u32,
+}
+
+/// Up to `MAX_BATCH` consecutive triggers in one sample.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "iceoryx2", derive(ZeroCopySend), type_name("camera_trigger_batch_v5"))]
+pub struct TriggerBatch {
+    pub first: CameraTrigger,
+    /// Triggers in the batch, `first` included.
+    pub count: u32,
+    /// Valid up to `count - 1`.
+    pub deltas: [TriggerDelta; MAX_BATCH - 1],
+}
+
+impl Default for TriggerBatch {
+    fn default() -> Self {
+        Self {
+            first: CameraTrigger::default(),
+            count: 0,
+            deltas: [TriggerDelta::default(); MAX_BATCH - 1],
+        }
+    }
+}
+
+impl TriggerBatch {
+    pub fn new(first: CameraTrigger) -> Self {
+        Self {
+            first,
+            count: 1,
+            ..Default::default()
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.count as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Appends `trigger`, which follows `last`; `false` if the batch is full or it cannot be delta-encoded.
+    pub fn push(&mut self, last: CameraTrigger, trigger: CameraTrigger) -> bool {
+        let delta = |from: u64, to: u64| to.checked_sub(from).and_then(|delta| u32::try_from(delta).ok());
+        let (Some(trigger_id), Some(hw_timestamp_ns), Some(publish_timestamp_ns)) =
+            (
+                delta(last.frame_id, trigger.frame_id),
+                delta(last.hw_timestamp_ns, trigger.hw_timestamp_ns),
+                delta(last.publish_timestamp_ns, trigger.publish_timestamp_ns),
+            )
+        else {
+            return false;
+        };
+        if self.is_empty() || self.len() == MAX_BATCH {
+            return false;
+        }
+        self.deltas[self.len() - 1] = TriggerDelta {
+            trigger_id,
+            hw_timestamp_ns,
+            publish_timestamp_ns,
+        };
+        self.count += 1;
+        true
+    }
+
+    /// The triggers in publish order.
+    pub fn triggers(&self) -> impl Iterator<Item = CameraTrigger> + '_ {
+        let first = self.first;
+        let rest = self.deltas[..self.len().saturating_sub(1)].iter().scan(first, |last, delta| {
+            *last = CameraTrigger::new(
+                last.frame_id + delta.trigger_id as u64,
+                last.hw_timestamp_ns + delta.hw_timestamp_ns as u64,
+                last.publish_timestamp_ns + delta.publish_timestamp_ns as u64,
+            );
+            Some(*last)
+        });
+        (!self.is_empty()).then_some(first).into_iter().chain(rest)
+    }
+}
+
+/// Collects triggers into batches of up to `max_triggers` (at least 2).
+#[derive(Debug, Clone)]
+pub struct TriggerBatcher {
+    max_triggers: usize,
+    max_delay_ns: u64,
+    /// Batch being filled, its last trigger and when its first was added.
+    pending: Option<(TriggerBatch, CameraTrigger, u64)>,
+}
+
+impl TriggerBatcher {
+    pub fn new(max_triggers: usize, max_delay_ms: f64) -> Self {
+        Self {
+            max_triggers: max_triggers.clamp(2, MAX_BATCH),
+            max_delay_ns: (max_delay_ms.max(0.0) * 1e6) as u64,
+            pending: None,
+        }
+    }
+
+    /// Adds a trigger at `now_ns`; returns a batch that is ready to be sent.
+    pub fn push(&mut self, trigger: CameraTrigger, now_ns: u64) -> Option<TriggerBatch> {
+        if let Some((batch, last, _)) = &mut self.pending {
+            if batch.push(*last, trigger) {
+                *last = trigger;
+                return if batch.len() >= self.max_triggers { self.flush() } else { None };
+            }
+        }
+        // A new batch, the previous one (if any) goes out first
+        let ready = self.flush();
+        self.pending = Some((TriggerBatch::new(trigger), trigger, now_ns));
+        ready
+    }
+
+    /// The pending batch, if its first trigger waited `max_delay` by `now_ns`.
+    pub fn due(&mut self, now_ns: u64) -> Option<TriggerBatch> {
+        let (_, _, started_ns) = self.pending.as_ref()?;
+        if now_ns.saturating_sub(*started_ns) >= self.max_delay_ns {
+            self.flush()
+        } else {
+            None
+        }
+    }
+
+    pub fn flush(&mut self) -> Option<TriggerBatch> {
+        self.pending.take().map(|(batch, _, _)| batch)
+    }
+}
+
+/// Decides from the observed trigger rate whether triggers are batched.
+#[derive(Debug, Clone)]
+pub struct BatchSelector {
+    threshold_hz: f64,
+    interval_ns: Option<f64>,
+    last_ns: Option<u64>,
+    batched: bool,
+}
+
+impl BatchSelector {
+    pub fn new(threshold_hz: f64) -> Self {
+        Self {
+            threshold_hz,
+            interval_ns: None,
+            last_ns: None,
+            batched: false,
+        }
+    }
+
+    /// Adds a trigger at `hw_ts`; whether it goes into a batch.
+    pub fn observe(&mut self, hw_ts: u64) -> bool {
+        if let Some(last_ns) = self.last_ns.filter(|&last_ns| hw_ts > last_ns) {
+            let interval_ns = (hw_ts - last_ns) as f64;
+            self.interval_ns = Some(self.interval_ns.map_or(interval_ns, |smoothed| smoothed + RATE_SMOOTHING * (interval_ns - smoothed)));
+        }
+        self.last_ns = Some(hw_ts);
+        let rate_hz = self.rate_hz();
+        if rate_hz > self.threshold_hz {
+            self.batched = true;
+        } else if rate_hz < self.threshold_hz * HYSTERESIS {
+            self.batched = false;
+        }
+        self.batched
+    }
+
+    /// Smoothed trigger rate, 0 before the second trigger.
+    pub fn rate_hz(&self) -> f64 {
+        self.interval_ns.map_or(0.0, |interval_ns| 1e9 / interval_ns)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const US: u64 = 1_000;
+
+    fn trigger(id: u64, hw_ts: u64) -> CameraTrigger {
+        CameraTrigger::new(id, hw_ts, hw_ts + 20 * US)
+    }
+
+    #[test]
+    fn batches_unpack_to_the_original_triggers() {
+        let triggers: Vec<CameraTrigger> = (1..=5).map(|id| trigger(id, 1_000_000 + id * 500 * US)).collect();
+        let mut batcher = TriggerBatcher::new(4, 10.0);
+        let ready: Vec<TriggerBatch> = triggers.iter().filter_map(|&t| batcher.push(t, t.hw_timestamp_ns)).collect();
+        assert_eq!(ready.len(), 1);
+        assert_eq!(ready[0].triggers().collect::<Vec<_>>(), triggers[..4]);
+        assert_eq!(batcher.due(triggers[4].hw_timestamp_ns + 5_000 * US), None);
+        assert_eq!(batcher.due(triggers[4].hw_timestamp_ns + 10_000 * US).unwrap().triggers().collect::<Vec<_>>(), triggers[4..]);
+        assert_eq!(batcher.flush(), None);
+    }
+
+    #[test]
+    fn triggers_that_cannot_be_delta_encoded_start_a_new_batch() {
+        let mut batcher = TriggerBatcher::new(MAX_BATCH, 10.0);
+        assert_eq!(batcher.push(trigger(10, 1_000 * US), 0), None);
+        // A restarted publisher counts from 1 again
+        let ready = batcher.push(trigger(1, 2_000 * US), 0).unwrap();
+        assert_eq!(ready.len(), 1);
+        // More than 4.2s apart
+        let ready = batcher.push(trigger(2, 2_000 * US + 5_000_000_000), 0).unwrap();
+        assert_eq!(ready.triggers().map(|t| t.frame_id).collect::<Vec<_>>(), [1]);
+        assert_eq!(TriggerBatch::default().triggers().count(), 0);
+    }
+
+    #[test]
+    fn batching_follows_the_rate_with_hysteresis() {
+        let mut selector = BatchSelector::new(1_000.0);
+        let mut hw_ts = 0;
+        let mut run = |selector: &mut BatchSelector, hz: u64, triggers: usize| {
+            (0..triggers).map(|_| {
+                hw_ts += 1_000_000_000 / hz;
+                selector.observe(hw_ts)
+            }).last().unwrap()
+        };
+        assert!(!run(&mut selector, 500, 10));
+        assert!(run(&mut selector, 2_000, 50));
+        // Between 80% and 100% of the threshold, batches continue
+        assert!(run(&mut selector, 900, 100));
+        assert!(!run(&mut selector, 500, 100));
+    }
+}
@@ -0,0 +1,187 @@
+// Time the preview UI takes per frame, against the camera's frame budget.
+//
+// The preview runs on the UI thread, apart from capture, but on boards with
+// few cores its work competes with the capture thread for the CPU. Every
+// paint that shows a new frame reports how long the UI spent converting it
+// (downscaling, histogram, focus peaking), handing it to egui as a texture
+// (the upload) and in the whole egui update, conversion and upload included
+// (the paint). Over the last `WINDOW` paints, the paint time per second of
+// wall time is the share of a CPU the UI takes; times the camera's frame
+// interval, it is the UI time spent per captured frame. Above `warn_pct` of
+// the frame interval the UI may delay capture, and `check` warns until the
+// share drops `HYSTERESIS_PCT` below it again.
+
+use std::collections::VecDeque;
+
+use crate::stats::LatencyStats;
+use crate::units::{DisplayMs, Unit};
+
+/// Default share of the frame interval the UI may take before it is warned about.
+pub const DEFAULT_WARN_PCT: f64 = 50.0;
+
+// Paints the share is measured over
+const WINDOW: usize = 30;
+
+// Below `warn_pct` by this much, the warning clears
+const HYSTERESIS_PCT: f64 = 10.0;
+
+/// UI thread times of one paint that showed a new frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UiTiming {
+    pub convert_ns: u64,
+    pub upload_ns: u64,
+    /// The whole egui update, conversion and upload included.
+    pub paint_ns: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct UiBudget {
+    frame_interval_ns: u64,
+    warn_pct: f64,
+    pub convert: LatencyStats,
+    pub upload: LatencyStats,
+    pub paint: LatencyStats,
+    // (paint time, paint_ns) of the last paints
+    recent: VecDeque<(u64, u64)>,
+    over: bool,
+    /// Times the UI went over `warn_pct`.
+    pub warnings: u64,
+}
+
+impl UiBudget {
+    /// `camera_fps` sets the frame budget.
+    pub fn new(camera_fps: u32, warn_pct: f64) -> Self {
+        Self {
+            frame_interval_ns: 1_000_000_000 / camera_fps.max(1) as u64,
+            warn_pct,
+            convert: LatencyStats::default(),
+            upload: LatencyStats::default(),
+            paint: LatencyStats::default(),
+            recent: VecDeque::with_capacity(WINDOW),
+            over: false,
+            warnings: 0,
+        }
+    }
+
+    /// Records a paint that ended at `painted_ns` (any clock, only differences are used).
+    pub fn record(&mut self, timing: UiTiming, painted_ns: u64) {
+        self.convert.add(timing.convert_ns as f64 / 1e6);
+        self.upload.add(timing.upload_ns as f64 / 1e6);
+        self.paint.add(timing.paint_ns as f64 / 1e6);
+        if self.recent.len() == WINDOW {
+            self.recent.pop_front();
+        }
+        self.recent.push_back((timing.paint_ns, painted_ns));
+    }
+
+    pub fn frame_interval_ms(&self) -> f64 {
+        self.frame_interval_ns as f64 / 1e6
+    }
+
+    /// Share of the frame interval the UI took over the last paints, in percent; `None` before two paints.
+    pub fn share_pct(&self) -> Option<f64> {
+        let (&(_, first_ns), &(_, last_ns)) = (self.recent.front()?, self.recent.back()?);
+        if last_ns <= first_ns {
+            return None;
+        }
+        // The first paint ended at the start of the span
+        let busy_ns: u64 = self.recent.iter().skip(1).map(|(paint_ns, _)| paint_ns).sum();
+        Some(busy_ns as f64 / (last_ns - first_ns) as f64 * 100.0)
+    }
+
+    /// UI time per captured frame over the last paints.
+    pub fn ui_ms_per_frame(&self) -> Option<f64> {
+        self.share_pct().map(|pct| pct / 100.0 * self.frame_interval_ms())
+    }
+
+    /// A warning while the UI takes more than `warn_pct` of the frame interval, with durations in `unit`.
+    pub fn check(&mut self, unit: Unit) -> Option<String> {
+        let pct = self.share_pct()?;
+        let over = if self.over { pct >= self.warn_pct - HYSTERESIS_PCT } else { pct > self.warn_pct };
+        if over && !self.over {
+            self.warnings += 1;
+        }
+        self.over = over;
+        over.then(|| {
+            format!(
+                "the preview UI takes {:.1} of every {:.1} frame interval ({:.0}%, limit {:.0}%) and may delay capture; run with --headless, a smaller --preview-max-width or a lower --preview-fps",
+                DisplayMs(pct / 100.0 * self.frame_interval_ms(), unit),
+                DisplayMs(self.frame_interval_ms(), unit),
+                pct,
+                self.warn_pct
+            )
+        })
+    }
+
+    /// The `UI per frame:` report, with durations in `unit`.
+    pub fn report(&self, unit: Unit) -> String {
+        format!(
+            "UI per frame: convert p50={:.2} p95={:.2}, upload p50={:.2} p95={:.2}, paint p50={:.2} p95={:.2} | {:.0}% of the {:.1} frame interval",
+            DisplayMs(self.convert.percentile(50.0), unit),
+            DisplayMs(self.convert.percentile(95.0), unit),
+            DisplayMs(self.upload.percentile(50.0), unit),
+            DisplayMs(self.upload.percentile(95.0), unit),
+            DisplayMs(self.paint.percentile(50.0), unit),
+            DisplayMs(self.paint.percentile(95.0), unit),
+            self.share_pct().unwrap_or(0.0),
+            DisplayMs(self.frame_interval_ms(), unit)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::Unit;
+
+    const MS: u64 = 1_000_000;
+
+    fn paint(paint_ms: u64) -> UiTiming {
+        UiTiming { convert_ns: paint_ms * MS / 2, upload_ns: MS, paint_ns: paint_ms * MS }
+    }
+
+    #[test]
+    fn warning_holds_until_the_share_drops_below_the_hysteresis() {
+        // 30fps: a 33.3ms budget, paints every 33.3ms
+        let mut budget = UiBudget::new(30, 50.0);
+        let interval = 1_000_000_000 / 30;
+        assert_eq!(budget.check(Unit::Ms), None);
+        let mut at = 0;
+        let mut feed = |budget: &mut UiBudget, paint_ms: u64, paints: usize| {
+            for _ in 0..paints {
+                at += interval;
+                budget.record(paint(paint_ms), at);
+            }
+        };
+        feed(&mut budget, 10, 30);
+        assert!((budget.share_pct().unwrap() - 30.0).abs() < 0.1);
+        assert_eq!(budget.check(Unit::Ms), None);
+
+        feed(&mut budget, 20, 30);
+        assert!((budget.ui_ms_per_frame().unwrap() - 20.0).abs() < 0.1);
+        let warning = budget.check(Unit::Ms).unwrap();
+        assert!(warning.contains("(60%, limit 50%)") && warning.contains("--headless"), "{}", warning);
+        assert_eq!(budget.warnings, 1);
+
+        // 45% is under the limit but within the hysteresis
+        feed(&mut budget, 15, 30);
+        assert!(budget.check(Unit::Ms).is_some());
+        feed(&mut budget, 12, 30);
+        assert_eq!(budget.check(Unit::Ms), None);
+        assert_eq!(budget.warnings, 1);
+    }
+
+    #[test]
+    fn paint_times_are_kept_per_stage() {
+        let mut budget = UiBudget::new(0, 50.0);
+        assert_eq!(budget.frame_interval_ms(), 1000.0);
+        budget.record(paint(4), 0);
+        assert_eq!(budget.share_pct(), None);
+        budget.record(paint(8), 100 * MS);
+        assert_eq!(budget.paint.count(), 2);
+        assert_eq!(budget.upload.percentile(95.0), 1.0);
+        assert!((budget.share_pct().unwrap() - 8.0).abs() < 1e-9);
+        assert!(budget.report(Unit::Ms).ends_with("| 8% of the 1000.0ms frame interval"), "{}", budget.report(Unit::Ms));
+        assert!(budget.report(Unit::Us).ends_with("| 8% of the 1000000.0us frame interval"), "{}", budget.report(Unit::Us));
+    }
+}
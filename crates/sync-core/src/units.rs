@@ -0,0 +1,309 @@
+// Timestamp units and epochs at API boundaries.
+//
+// Internally every timestamp is CLOCK_REALTIME nanoseconds since the Unix
+// epoch, but the surrounding pipeline is mixed: EXIF and camera settings count
+// microseconds, PTP hardware clocks run on TAI, GNSS-referenced consumers want
+// GPS time. A bare `u64` does not say which, so the boundaries where timestamps
+// enter or leave the crate (timestamp sources, history queries, exposure times)
+// take `Nanos` or `Micros`. Widening `Micros` into `Nanos` is a `From`
+// conversion; narrowing is an explicit `to_micros_floor`, so a lossy conversion
+// never happens by accident.
+//
+// Consumers that want another representation request it with
+// `--timestamp-unit ns|us|ms` and `--timestamp-epoch unix|tai|gps`
+// (`TimestampFormat`). Coarser units truncate towards the epoch. TAI and GPS
+// use the leap second count at each timestamp (see `leap`).
+//
+// Durations in human-readable output (latency statistics, deadlines, match
+// scores) are formatted through `DisplayMs` in a display unit the caller
+// passes along, `--display-unit ms|us|ns` (ms by default, see
+// `Status::display_unit`), so sub-millisecond differences can be seen in logs
+// and the GUI. The formatting never depends on the
+// locale: `.` as the decimal separator and no digit grouping. JSON status
+// fields keep their units.
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::cli::Args;
+use crate::clock;
+use crate::leap::{self, Tai};
+
+/// GPS epoch (1980-01-06 00:00:00 UTC) as Unix seconds.
+const GPS_EPOCH_UNIX_S: u64 = 315_964_800;
+
+/// TAI minus GPS time, fixed since the GPS epoch.
+const TAI_GPS_OFFSET_NS: u64 = 19_000_000_000;
+
+/// TAI since 1970 minus GPS time since 1980.
+const TAI_MINUS_GPS_NS: i128 = TAI_GPS_OFFSET_NS as i128 + GPS_EPOCH_UNIX_S as i128 * 1_000_000_000;
+
+/// Nanoseconds since the Unix epoch (CLOCK_REALTIME), or a nanosecond duration.
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Nanos(pub u64);
+
+/// Microseconds since the Unix epoch, or a microsecond duration.
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Micros(pub u64);
+
+impl Nanos {
+    pub fn now() -> Self {
+        Self(clock::realtime_now_ns())
+    }
+
+    /// Truncates to whole microseconds.
+    pub fn to_micros_floor(self) -> Micros {
+        Micros(self.0 / 1_000)
+    }
+
+    pub fn saturating_sub(self, duration: Duration) -> Self {
+        Self(self.0.saturating_sub(duration.as_nanos() as u64))
+    }
+}
+
+/// Exact; saturates more than 584 years after the epoch.
+impl From<Micros> for Nanos {
+    fn from(micros: Micros) -> Self {
+        Self(micros.0.saturating_mul(1_000))
+    }
+}
+
+impl fmt::Display for Nanos {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}ns", self.0)
+    }
+}
+
+impl fmt::Display for Micros {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}us", self.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Unit {
+    #[default]
+    Ns,
+    Us,
+    Ms,
+}
+
+impl Unit {
+    fn ns_per_unit(self) -> u64 {
+        match self {
+            Unit::Ns => 1,
+            Unit::Us => 1_000,
+            Unit::Ms => 1_000_000,
+        }
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Unit::Ns => write!(f, "ns"),
+            Unit::Us => write!(f, "us"),
+            Unit::Ms => write!(f, "ms"),
+        }
+    }
+}
+
+impl FromStr for Unit {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "ns" => Ok(Unit::Ns),
+            "us" => Ok(Unit::Us),
+            "ms" => Ok(Unit::Ms),
+            other => Err(format!("unknown unit '{}' (expected ns, us or ms)", other)),
+        }
+    }
+}
+
+/// Unit durations are displayed in without `--display-unit`.
+pub const DEFAULT_DISPLAY_UNIT: Unit = Unit::Ms;
+
+/// A duration in milliseconds, displayed in the given unit with its suffix: `12.35ms`,
+/// `12345.7us` or `12345678ns`. A precision given in the format applies to milliseconds
+/// (2 decimals by default); microseconds get one decimal and nanoseconds none.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DisplayMs(pub f64, pub Unit);
+
+impl fmt::Display for DisplayMs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.1 {
+            Unit::Ms => write!(f, "{:.*}ms", f.precision().unwrap_or(2), self.0),
+            Unit::Us => write!(f, "{:.1}us", self.0 * 1e3),
+            Unit::Ns => write!(f, "{:.0}ns", self.0 * 1e6),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Epoch {
+    /// UTC since 1970-01-01, what `CLOCK_REALTIME` counts.
+    #[default]
+    Unix,
+    /// TAI since 1970-01-01, what PTP hardware clocks count.
+    Tai,
+    /// GPS time since 1980-01-06.
+    Gps,
+}
+
+impl Epoch {
+    /// Nanoseconds to add to the Unix timestamp `at` to get one in this epoch (negative: subtract).
+    pub(crate) fn offset_ns(self, at: Nanos) -> i128 {
+        match self {
+            Epoch::Unix => 0,
+            Epoch::Tai => leap::table().tai_minus_utc_ns(at) as i128,
+            Epoch::Gps => leap::table().tai_minus_utc_ns(at) as i128 - TAI_MINUS_GPS_NS,
+        }
+    }
+
+    /// The Unix timestamp of `ns` in this epoch, with the leap seconds valid then.
+    pub(crate) fn to_unix_ns(self, ns: i128) -> i128 {
+        let tai_ns = match self {
+            Epoch::Unix => return ns,
+            Epoch::Tai => ns,
+            Epoch::Gps => ns + TAI_MINUS_GPS_NS,
+        };
+        let Ok(tai_ns) = u64::try_from(tai_ns) else {
+            return tai_ns - leap::table().tai_minus_utc_ns(Nanos(0)) as i128;
+        };
+        leap::table().to_utc(Tai(tai_ns)).0 as i128
+    }
+}
+
+impl fmt::Display for Epoch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Epoch::Unix => write!(f, "unix"),
+            Epoch::Tai => write!(f, "tai"),
+            Epoch::Gps => write!(f, "gps"),
+        }
+    }
+}
+
+impl FromStr for Epoch {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "unix" => Ok(Epoch::Unix),
+            "tai" => Ok(Epoch::Tai),
+            "gps" => Ok(Epoch::Gps),
+            other => Err(format!("unknown timestamp epoch '{}' (expected unix, tai or gps)", other)),
+        }
+    }
+}
+
+/// How a consumer wants timestamps presented: unit and epoch.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TimestampFormat {
+    pub unit: Unit,
+    pub epoch: Epoch,
+}
+
+impl TimestampFormat {
+    /// `--timestamp-unit` and `--timestamp-epoch`, ns since the Unix epoch by default.
+    pub fn from_args(args: &Args) -> Result<Self, String> {
+        Ok(Self {
+            unit: args.value("timestamp-unit").map_or(Ok(Unit::default()), str::parse)?,
+            epoch: args.value("timestamp-epoch").map_or(Ok(Epoch::default()), str::parse)?,
+        })
+    }
+
+    /// `timestamp` in this unit and epoch; 0 before the epoch.
+    pub fn present(&self, timestamp: Nanos) -> u64 {
+        let shifted = (timestamp.0 as i128 + self.epoch.offset_ns(timestamp)).max(0);
+        (shifted / self.unit.ns_per_unit() as i128) as u64
+    }
+
+    /// A timestamp given in this unit and epoch (e.g. on the command line); 0 before the Unix epoch.
+    pub fn parse(&self, value: u64) -> Nanos {
+        let ns = self.epoch.to_unix_ns(value as i128 * self.unit.ns_per_unit() as i128);
+        Nanos(ns.clamp(0, u64::MAX as i128) as u64)
+    }
+
+    /// Short label for logs, e.g. `us since gps`.
+    pub fn describe(&self) -> String {
+        format!("{} since {}", self.unit, self.epoch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::LatencyStats;
+    use crate::status::Status;
+
+    fn display_unit(line: &str) -> Unit {
+        Status::from_args(&Args::parse(line.split_whitespace().map(str::to_string), &[]), "subscriber").unwrap().display_unit()
+    }
+
+    #[test]
+    fn micros_widen_exactly_and_narrow_by_truncation() {
+        assert_eq!(Nanos::from(Micros(1_234)), Nanos(1_234_000));
+        assert_eq!(Nanos::from(Micros(u64::MAX)), Nanos(u64::MAX));
+        assert_eq!(Nanos(1_234_999).to_micros_floor(), Micros(1_234));
+        assert_eq!(Nanos(5_000).saturating_sub(Duration::from_micros(10)), Nanos(0));
+        assert_eq!(format!("{} {}", Nanos(7), Micros(7)), "7ns 7us");
+    }
+
+    #[test]
+    fn timestamps_are_presented_in_the_requested_unit_and_epoch() {
+        // 2020-01-01 00:00:00.123456789 UTC, when TAI - UTC was 37 s and GPS - UTC 18 s.
+        let utc = Nanos(1_577_836_800_123_456_789);
+        let format = |line: &str| {
+            let args = Args::parse(format!("v4l2_capture {}", line).split_whitespace().map(str::to_string), &[]);
+            TimestampFormat::from_args(&args).unwrap()
+        };
+
+        let default = format("");
+        assert_eq!(default.present(utc), utc.0);
+        assert_eq!(default.describe(), "ns since unix");
+
+        let tai_us = format("--timestamp-unit us --timestamp-epoch tai");
+        assert_eq!(tai_us.present(utc), 1_577_836_837_123_456);
+        assert_eq!(tai_us.parse(1_577_836_837_123_456), Nanos(1_577_836_800_123_456_000));
+
+        let gps_ms = format("--timestamp-unit ms --timestamp-epoch gps");
+        assert_eq!(gps_ms.present(utc), (1_577_836_800 - 315_964_800 + 18) * 1_000 + 123);
+        assert_eq!(gps_ms.parse(gps_ms.present(utc)), Nanos(1_577_836_800_123_000_000));
+        assert_eq!(gps_ms.present(Nanos(0)), 0);
+        assert_eq!(gps_ms.describe(), "ms since gps");
+
+        let args = Args::parse(["v4l2_capture", "--timestamp-epoch", "j2000"].map(str::to_string), &[]);
+        assert!(TimestampFormat::from_args(&args).unwrap_err().contains("j2000"));
+        assert!("s".parse::<Unit>().is_err());
+    }
+
+    #[test]
+    fn durations_follow_the_display_unit() {
+        let mut stats = LatencyStats::default();
+        for value_ms in [12.3456789, 12.5, 13.0004] {
+            stats.add(value_ms);
+        }
+
+        assert_eq!(display_unit("subscriber"), Unit::Ms);
+        assert_eq!(format!("{}", DisplayMs(12.3456789, Unit::Ms)), "12.35ms");
+        assert_eq!(format!("{:.3}", DisplayMs(0.0421, Unit::Ms)), "0.042ms");
+        assert_eq!(stats.summary(Unit::Ms), "n=3 mean=12.62ms p50=12.50ms p95=13.00ms min=12.35ms max=13.00ms");
+
+        let us = display_unit("subscriber --display-unit us");
+        assert_eq!(us, Unit::Us);
+        assert_eq!(format!("{:.3}", DisplayMs(0.0421, us)), "42.1us");
+        assert_eq!(stats.summary(us), "n=3 mean=12615.4us p50=12500.0us p95=13000.4us min=12345.7us max=13000.4us");
+
+        assert_eq!(format!("{}", DisplayMs(1234.5678901, Unit::Ns)), "1234567890ns");
+        assert_eq!(format!("{}", DisplayMs(-0.0005, Unit::Ns)), "-500ns");
+
+        // Summaries of different units side by side in one process
+        assert!(stats.summary(Unit::Ns).starts_with("n=3 mean=12615360ns p50=12500000ns"));
+        assert!(stats.summary(Unit::Ms).starts_with("n=3 mean=12.62ms"));
+    }
+}
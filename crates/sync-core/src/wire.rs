@@ -0,0 +1,646 @@
+// Compact wire encoding of triggers for network bridges and MCUs.
+//
+// Outside shared memory (UDP/Zenoh bridges, trigger MCUs on a serial link) a
+// trigger is sent as one canonical CBOR map (RFC 8949, section 4.2.1) with
+// small integer keys:
+//
+//   0  wire version (uint)              3  hw_timestamp_ns (uint)
+//   1  message kind (uint, 0 = trigger)  4  publish_timestamp_ns (uint)
+//   2  frame_id (uint)                  5  key_id (uint, signed triggers only)
+//                                       6  signature (64 byte bstr, signed triggers only)
+//                                       7  timestamp_source (uint, omitted when 0 = system)
+//                                       8  link_tx_frame_id (uint, bridges only)
+//                                       9  link_tx_timestamp_ns (uint, bridges only)
+//                                       20 trace_origin (uint, omitted when 0 = unknown)
+//                                       21 clock_domain (uint, omitted when 0 = realtime)
+//                                       22 clock_policy (uint, omitted when 0 = synchronized)
+//                                       23 phc_offset_ns (int, omitted when 0 = no PHC)
+//
+// Keys 8 and 9 carry the send time of an earlier message on the same link
+// (the time is only known once it has left), so the receiving bridge can
+// estimate the one-way delay from its own receive time of that message.
+//
+// Federated bridges (see `federation`) also exchange clock probes over the
+// same link: kind 1 (probe, relay to upstream) and kind 2 (probe reply).
+//
+//   10 sequence (uint)                       15 lost (uint, probe)
+//   11 probe_rx_timestamp_ns (uint, reply)   16 offset_ns (int, probe, once measured)
+//   12 rig (tstr, probe)                     17 uncertainty_ns (uint, probe, once measured)
+//   13 hops (uint)                           18 rigs (uint, probe)
+//   14 received (uint, probe)                19 unhealthy (uint, probe)
+//
+// A reply carries the sequence and send time of the previous reply on the
+// link in keys 8 and 9, like triggers do.
+//
+// Canonical means: definite lengths, shortest integer encodings and keys in
+// ascending order, so every trigger has exactly one encoding and signatures
+// or hashes over the bytes are stable. The decoder accepts only that form.
+// It never panics, allocates or recurses, whatever the input. Unknown keys
+// above the known ones are skipped if their values are integers, byte or
+// text strings, so later versions can append fields.
+
+use crate::federation::{RigHealth, MAX_RIG_NAME_LEN};
+use crate::signing::TriggerSignature;
+use crate::CameraTrigger;
+
+pub const WIRE_VERSION: u64 = 1;
+/// Upper bound of an encoded trigger, for receive buffers.
+pub const MAX_MESSAGE_LEN: usize = 160;
+
+const KIND_TRIGGER: u64 = 0;
+const KIND_PROBE: u64 = 1;
+const KIND_PROBE_REPLY: u64 = 2;
+
+const KEY_VERSION: u64 = 0;
+const KEY_KIND: u64 = 1;
+const KEY_FRAME_ID: u64 = 2;
+const KEY_HW_TIMESTAMP: u64 = 3;
+const KEY_PUBLISH_TIMESTAMP: u64 = 4;
+const KEY_KEY_ID: u64 = 5;
+const KEY_SIGNATURE: u64 = 6;
+const KEY_TIMESTAMP_SOURCE: u64 = 7;
+const KEY_LINK_TX_FRAME_ID: u64 = 8;
+const KEY_LINK_TX_TIMESTAMP: u64 = 9;
+const KEY_SEQUENCE: u64 = 10;
+const KEY_PROBE_RX_TIMESTAMP: u64 = 11;
+const KEY_RIG: u64 = 12;
+const KEY_HOPS: u64 = 13;
+const KEY_RECEIVED: u64 = 14;
+const KEY_LOST: u64 = 15;
+const KEY_OFFSET: u64 = 16;
+const KEY_UNCERTAINTY: u64 = 17;
+const KEY_RIGS: u64 = 18;
+const KEY_UNHEALTHY: u64 = 19;
+const KEY_TRACE_ORIGIN: u64 = 20;
+const KEY_CLOCK_DOMAIN: u64 = 21;
+const KEY_CLOCK_POLICY: u64 = 22;
+const KEY_PHC_OFFSET: u64 = 23;
+const KEY_COUNT: usize = 24;
+
+const MAJOR_UINT: u8 = 0;
+const MAJOR_NEGATIVE: u8 = 1;
+const MAJOR_BYTES: u8 = 2;
+const MAJOR_TEXT: u8 = 3;
+const MAJOR_MAP: u8 = 5;
+
+/// A trigger as carried over the wire.
+#[derive(Debug, Clone, Copy)]
+pub struct TriggerMessage {
+    pub trigger: CameraTrigger,
+    pub signature: TriggerSignature,
+    /// `(frame_id, tx_timestamp_ns)` of the previous message sent on this link.
+    pub link_tx: Option<(u64, u64)>,
+}
+
+impl TriggerMessage {
+    pub fn encode(&self) -> Vec<u8> {
+        let signed = self.signature.signed;
+        let timestamp_source = self.signature.timestamp_source;
+        let trace_origin = self.signature.trace_origin;
+        let clock_domain = self.signature.clock_domain;
+        let clock_policy = self.signature.clock_policy;
+        let phc_offset_ns = self.signature.phc_offset_ns;
+        let entries = 5 + 2 * signed as u64 + (timestamp_source != 0) as u64 + 2 * self.link_tx.is_some() as u64 + (trace_origin != 0) as u64 + (clock_domain != 0) as u64 + (clock_policy != 0) as u64 + (phc_offset_ns != 0) as u64;
+        let mut out = Vec::with_capacity(MAX_MESSAGE_LEN);
+        write_head(&mut out, MAJOR_MAP, entries);
+        for (key, value) in [
+            (KEY_VERSION, WIRE_VERSION),
+            (KEY_KIND, KIND_TRIGGER),
+            (KEY_FRAME_ID, self.trigger.frame_id),
+            (KEY_HW_TIMESTAMP, self.trigger.hw_timestamp_ns),
+            (KEY_PUBLISH_TIMESTAMP, self.trigger.publish_timestamp_ns),
+        ] {
+            write_head(&mut out, MAJOR_UINT, key);
+            write_head(&mut out, MAJOR_UINT, value);
+        }
+        if signed {
+            write_head(&mut out, MAJOR_UINT, KEY_KEY_ID);
+            write_head(&mut out, MAJOR_UINT, self.signature.key_id);
+            write_head(&mut out, MAJOR_UINT, KEY_SIGNATURE);
+            write_head(&mut out, MAJOR_BYTES, self.signature.signature.len() as u64);
+            out.extend_from_slice(&self.signature.signature);
+        }
+        if timestamp_source != 0 {
+            write_head(&mut out, MAJOR_UINT, KEY_TIMESTAMP_SOURCE);
+            write_head(&mut out, MAJOR_UINT, timestamp_source as u64);
+        }
+        if let Some((tx_frame_id, tx_ts)) = self.link_tx {
+            for (key, value) in [(KEY_LINK_TX_FRAME_ID, tx_frame_id), (KEY_LINK_TX_TIMESTAMP, tx_ts)] {
+                write_head(&mut out, MAJOR_UINT, key);
+                write_head(&mut out, MAJOR_UINT, value);
+            }
+        }
+        if trace_origin != 0 {
+            write_uints(&mut out, &[(KEY_TRACE_ORIGIN, trace_origin as u64)]);
+        }
+        if clock_domain != 0 {
+            write_uints(&mut out, &[(KEY_CLOCK_DOMAIN, clock_domain as u64)]);
+        }
+        if clock_policy != 0 {
+            write_uints(&mut out, &[(KEY_CLOCK_POLICY, clock_policy as u64)]);
+        }
+        if phc_offset_ns != 0 {
+            write_head(&mut out, MAJOR_UINT, KEY_PHC_OFFSET);
+            write_int(&mut out, phc_offset_ns);
+        }
+        out
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, String> {
+        match WireMessage::decode(bytes)? {
+            WireMessage::Trigger(message) => Ok(message),
+            WireMessage::Probe(_) => Err(format!("unexpected message kind {}", KIND_PROBE)),
+            WireMessage::ProbeReply(_) => Err(format!("unexpected message kind {}", KIND_PROBE_REPLY)),
+        }
+    }
+
+    fn from_fields(fields: &Fields) -> Result<Self, String> {
+        let trigger = CameraTrigger::new(
+            fields.uint(KEY_FRAME_ID, "frame_id")?,
+            fields.uint(KEY_HW_TIMESTAMP, "hw_timestamp_ns")?,
+            fields.uint(KEY_PUBLISH_TIMESTAMP, "publish_timestamp_ns")?,
+        );
+        let timestamp_source = match fields.uints[KEY_TIMESTAMP_SOURCE as usize] {
+            None => 0,
+            Some(0) => return Err("non-canonical timestamp_source 0".to_string()),
+            Some(source) => u8::try_from(source).map_err(|_| format!("timestamp_source {} out of range", source))?,
+        };
+        let trace_origin = match fields.uints[KEY_TRACE_ORIGIN as usize] {
+            None => 0,
+            Some(0) => return Err("non-canonical trace_origin 0".to_string()),
+            Some(origin) => u32::try_from(origin).map_err(|_| format!("trace_origin {} out of range", origin))?,
+        };
+        let clock_domain = match fields.uints[KEY_CLOCK_DOMAIN as usize] {
+            None => 0,
+            Some(0) => return Err("non-canonical clock_domain 0".to_string()),
+            Some(domain) => u8::try_from(domain).map_err(|_| format!("clock_domain {} out of range", domain))?,
+        };
+        let clock_policy = match fields.uints[KEY_CLOCK_POLICY as usize] {
+            None => 0,
+            Some(0) => return Err("non-canonical clock_policy 0".to_string()),
+            Some(policy) => u8::try_from(policy).map_err(|_| format!("clock_policy {} out of range", policy))?,
+        };
+        let phc_offset_ns = match fields.phc_offset {
+            None => 0,
+            Some(0) => return Err("non-canonical phc_offset_ns 0".to_string()),
+            Some(offset) => offset,
+        };
+        let signature = match (fields.uints[KEY_KEY_ID as usize], fields.signature) {
+            (None, None) => TriggerSignature {
+                timestamp_source,
+                clock_domain,
+                clock_policy,
+                trace_origin,
+                phc_offset_ns,
+                ..TriggerSignature::default()
+            },
+            (Some(key_id), Some(signature)) if key_id != 0 => TriggerSignature {
+                key_id,
+                signed: true,
+                timestamp_source,
+                signature,
+                clock_domain,
+                clock_policy,
+                trace_origin,
+                phc_offset_ns,
+            },
+            _ => return Err("key_id and signature must be sent together".to_string()),
+        };
+        Ok(Self {
+            trigger,
+            signature,
+            link_tx: fields.link_tx()?,
+        })
+    }
+}
+
+/// Clock probe a federated relay sends to its upstream bridge, with its health.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProbeMessage {
+    pub sequence: u64,
+    pub health: RigHealth,
+}
+
+impl ProbeMessage {
+    pub fn encode(&self) -> Vec<u8> {
+        let health = &self.health;
+        let entries = 9 + health.offset_ns.is_some() as u64 + health.uncertainty_ns.is_some() as u64;
+        let mut out = Vec::with_capacity(MAX_MESSAGE_LEN);
+        write_head(&mut out, MAJOR_MAP, entries);
+        write_uints(&mut out, &[(KEY_VERSION, WIRE_VERSION), (KEY_KIND, KIND_PROBE), (KEY_SEQUENCE, self.sequence)]);
+        write_head(&mut out, MAJOR_UINT, KEY_RIG);
+        let rig = truncate_rig_name(&health.rig);
+        write_head(&mut out, MAJOR_TEXT, rig.len() as u64);
+        out.extend_from_slice(rig.as_bytes());
+        write_uints(
+            &mut out,
+            &[(KEY_HOPS, health.hops as u64), (KEY_RECEIVED, health.received), (KEY_LOST, health.lost)],
+        );
+        if let Some(offset_ns) = health.offset_ns {
+            write_head(&mut out, MAJOR_UINT, KEY_OFFSET);
+            write_int(&mut out, offset_ns);
+        }
+        if let Some(uncertainty_ns) = health.uncertainty_ns {
+            write_uints(&mut out, &[(KEY_UNCERTAINTY, uncertainty_ns)]);
+        }
+        write_uints(&mut out, &[(KEY_RIGS, health.rigs as u64), (KEY_UNHEALTHY, health.unhealthy as u64)]);
+        out
+    }
+
+    fn from_fields(fields: &Fields) -> Result<Self, String> {
+        let count = |key: u64, name: &str| -> Result<u32, String> {
+            let value = fields.uint(key, name)?;
+            u32::try_from(value).map_err(|_| format!("{} {} out of range", name, value))
+        };
+        Ok(Self {
+            sequence: fields.uint(KEY_SEQUENCE, "sequence")?,
+            health: RigHealth {
+                rig: fields.rig.ok_or("missing rig")?.to_string(),
+                hops: count(KEY_HOPS, "hops")?,
+                received: fields.uint(KEY_RECEIVED, "received")?,
+                lost: fields.uint(KEY_LOST, "lost")?,
+                offset_ns: fields.offset,
+                uncertainty_ns: fields.uints[KEY_UNCERTAINTY as usize],
+                rigs: count(KEY_RIGS, "rigs")?,
+                unhealthy: count(KEY_UNHEALTHY, "unhealthy")?,
+            },
+        })
+    }
+}
+
+/// Answer of an upstream bridge to a `ProbeMessage`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProbeReply {
+    /// Sequence of the probe answered.
+    pub sequence: u64,
+    /// When the probe arrived, upstream clock.
+    pub probe_rx_ns: u64,
+    /// Hops of the upstream bridge from the master (0 for the master).
+    pub hops: u32,
+    /// `(sequence, tx_timestamp_ns)` of the previous reply sent on this link.
+    pub link_tx: Option<(u64, u64)>,
+}
+
+impl ProbeReply {
+    pub fn encode(&self) -> Vec<u8> {
+        let entries = 5 + 2 * self.link_tx.is_some() as u64;
+        let mut out = Vec::with_capacity(MAX_MESSAGE_LEN);
+        write_head(&mut out, MAJOR_MAP, entries);
+        write_uints(&mut out, &[(KEY_VERSION, WIRE_VERSION), (KEY_KIND, KIND_PROBE_REPLY)]);
+        if let Some((tx_sequence, tx_ts)) = self.link_tx {
+            write_uints(&mut out, &[(KEY_LINK_TX_FRAME_ID, tx_sequence), (KEY_LINK_TX_TIMESTAMP, tx_ts)]);
+        }
+        write_uints(
+            &mut out,
+            &[(KEY_SEQUENCE, self.sequence), (KEY_PROBE_RX_TIMESTAMP, self.probe_rx_ns), (KEY_HOPS, self.hops as u64)],
+        );
+        out
+    }
+
+    fn from_fields(fields: &Fields) -> Result<Self, String> {
+        let hops = fields.uint(KEY_HOPS, "hops")?;
+        Ok(Self {
+            sequence: fields.uint(KEY_SEQUENCE, "sequence")?,
+            probe_rx_ns: fields.uint(KEY_PROBE_RX_TIMESTAMP, "probe_rx_timestamp_ns")?,
+            hops: u32::try_from(hops).map_err(|_| format!("hops {} out of range", hops))?,
+            link_tx: fields.link_tx()?,
+        })
+    }
+}
+
+/// Any message a bridge may receive.
+#[derive(Debug, Clone)]
+pub enum WireMessage {
+    Trigger(TriggerMessage),
+    Probe(ProbeMessage),
+    ProbeReply(ProbeReply),
+}
+
+impl WireMessage {
+    pub fn decode(bytes: &[u8]) -> Result<Self, String> {
+        let fields = Fields::read(bytes)?;
+        match fields.uint(KEY_KIND, "kind")? {
+            KIND_TRIGGER => TriggerMessage::from_fields(&fields).map(Self::Trigger),
+            KIND_PROBE => ProbeMessage::from_fields(&fields).map(Self::Probe),
+            KIND_PROBE_REPLY => ProbeReply::from_fields(&fields).map(Self::ProbeReply),
+            kind => Err(format!("unknown message kind {}", kind)),
+        }
+    }
+}
+
+/// The known fields of a decoded map.
+struct Fields<'a> {
+    uints: [Option<u64>; KEY_COUNT],
+    signature: Option<[u8; 64]>,
+    rig: Option<&'a str>,
+    offset: Option<i64>,
+    phc_offset: Option<i64>,
+}
+
+impl<'a> Fields<'a> {
+    /// Reads a map of a supported wire version.
+    fn read(bytes: &'a [u8]) -> Result<Self, String> {
+        let mut reader = Reader { bytes, pos: 0 };
+        let entries = reader.head(MAJOR_MAP)?;
+        let mut fields = Self {
+            uints: [None; KEY_COUNT],
+            signature: None,
+            rig: None,
+            offset: None,
+            phc_offset: None,
+        };
+        let mut last_key = None;
+        for _ in 0..entries {
+            let key = reader.head(MAJOR_UINT)?;
+            if last_key.is_some_and(|last| key <= last) {
+                return Err(format!("key {} out of order", key));
+            }
+            last_key = Some(key);
+            match key {
+                KEY_SIGNATURE => {
+                    let bytes: [u8; 64] = reader
+                        .byte_string()?
+                        .try_into()
+                        .map_err(|_| "signature is not 64 bytes".to_string())?;
+                    fields.signature = Some(bytes);
+                }
+                KEY_RIG => {
+                    let rig = reader.text_string()?;
+                    if rig.len() > MAX_RIG_NAME_LEN {
+                        return Err(format!("rig name longer than {} bytes", MAX_RIG_NAME_LEN));
+                    }
+                    fields.rig = Some(rig);
+                }
+                KEY_OFFSET => fields.offset = Some(reader.int()?),
+                KEY_PHC_OFFSET => fields.phc_offset = Some(reader.int()?),
+                KEY_VERSION..=KEY_KEY_ID | KEY_TIMESTAMP_SOURCE..=KEY_HOPS | KEY_RECEIVED..=KEY_LOST | KEY_UNCERTAINTY..=KEY_CLOCK_POLICY => {
+                    fields.uints[key as usize] = Some(reader.head(MAJOR_UINT)?)
+                }
+                _ => reader.skip_value()?,
+            }
+        }
+        if reader.pos != bytes.len() {
+            return Err(format!("{} trailing bytes", bytes.len() - reader.pos));
+        }
+        let version = fields.uint(KEY_VERSION, "version")?;
+        if version != WIRE_VERSION {
+            return Err(format!("unsupported wire version {}", version));
+        }
+        Ok(fields)
+    }
+
+    fn uint(&self, key: u64, name: &str) -> Result<u64, String> {
+        self.uints[key as usize].ok_or_else(|| format!("missing {}", name))
+    }
+
+    fn link_tx(&self) -> Result<Option<(u64, u64)>, String> {
+        match (self.uints[KEY_LINK_TX_FRAME_ID as usize], self.uints[KEY_LINK_TX_TIMESTAMP as usize]) {
+            (None, None) => Ok(None),
+            (Some(tx_id), Some(tx_ts)) => Ok(Some((tx_id, tx_ts))),
+            _ => Err("link_tx_frame_id and link_tx_timestamp_ns must be sent together".to_string()),
+        }
+    }
+}
+
+/// Longest prefix of `rig` that fits the wire, on a character boundary.
+fn truncate_rig_name(rig: &str) -> &str {
+    let mut end = rig.len().min(MAX_RIG_NAME_LEN);
+    while !rig.is_char_boundary(end) {
+        end -= 1;
+    }
+    &rig[..end]
+}
+
+fn write_uints(out: &mut Vec<u8>, entries: &[(u64, u64)]) {
+    for &(key, value) in entries {
+        write_head(out, MAJOR_UINT, key);
+        write_head(out, MAJOR_UINT, value);
+    }
+}
+
+/// Writes a CBOR integer, negative values as major type 1.
+fn write_int(out: &mut Vec<u8>, value: i64) {
+    if value >= 0 {
+        write_head(out, MAJOR_UINT, value as u64);
+    } else {
+        write_head(out, MAJOR_NEGATIVE, !(value as u64));
+    }
+}
+
+/// Writes a CBOR head with the shortest encoding of `value`.
+fn write_head(out: &mut Vec<u8>, major: u8, value: u64) {
+    let major = major << 5;
+    if value < 24 {
+        out.push(major | value as u8);
+    } else if value <= u8::MAX as u64 {
+        out.extend_from_slice(&[major | 24, value as u8]);
+    } else if value <= u16::MAX as u64 {
+        out.push(major | 25);
+        out.extend_from_slice(&(value as u16).to_be_bytes());
+    } else if value <= u32::MAX as u64 {
+        out.push(major | 26);
+        out.extend_from_slice(&(value as u32).to_be_bytes());
+    } else {
+        out.push(major | 27);
+        out.extend_from_slice(&value.to_be_bytes());
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let end = self.pos.checked_add(len).filter(|end| *end <= self.bytes.len()).ok_or("truncated message")?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    /// Reads a head of the expected major type, rejecting non-shortest encodings.
+    fn head(&mut self, major: u8) -> Result<u64, String> {
+        let (found, value) = self.any_head()?;
+        if found != major {
+            return Err(format!("expected major type {}, got {}", major, found));
+        }
+        Ok(value)
+    }
+
+    fn any_head(&mut self) -> Result<(u8, u64), String> {
+        let initial = self.take(1)?[0];
+        let (major, info) = (initial >> 5, initial & 0x1f);
+        let (value, min) = match info {
+            0..=23 => return Ok((major, info as u64)),
+            24 => (self.take(1)?[0] as u64, 24),
+            25 => (u16::from_be_bytes(self.take(2)?.try_into().unwrap()) as u64, 0x100),
+            26 => (u32::from_be_bytes(self.take(4)?.try_into().unwrap()) as u64, 0x1_0000),
+            27 => (u64::from_be_bytes(self.take(8)?.try_into().unwrap()), 0x1_0000_0000),
+            _ => return Err(format!("unsupported additional info {}", info)),
+        };
+        if value < min {
+            return Err("non-canonical integer encoding".to_string());
+        }
+        Ok((major, value))
+    }
+
+    fn byte_string(&mut self) -> Result<&'a [u8], String> {
+        let len = self.head(MAJOR_BYTES)?;
+        let len = usize::try_from(len).map_err(|_| "byte string too long".to_string())?;
+        self.take(len)
+    }
+
+    fn text_string(&mut self) -> Result<&'a str, String> {
+        let len = self.head(MAJOR_TEXT)?;
+        let len = usize::try_from(len).map_err(|_| "text string too long".to_string())?;
+        std::str::from_utf8(self.take(len)?).map_err(|_| "text string is not UTF-8".to_string())
+    }
+
+    /// Reads a signed integer of major type 0 or 1 that fits an i64.
+    fn int(&mut self) -> Result<i64, String> {
+        match self.any_head()? {
+            (MAJOR_UINT, value) => i64::try_from(value).map_err(|_| "integer out of range".to_string()),
+            (MAJOR_NEGATIVE, value) => i64::try_from(value).map(|value| -1 - value).map_err(|_| "integer out of range".to_string()),
+            (major, _) => Err(format!("expected an integer, got major type {}", major)),
+        }
+    }
+
+    fn skip_value(&mut self) -> Result<(), String> {
+        match self.any_head()? {
+            (MAJOR_UINT | MAJOR_NEGATIVE, _) => Ok(()),
+            (MAJOR_BYTES | MAJOR_TEXT, len) => {
+                let len = usize::try_from(len).map_err(|_| "byte string too long".to_string())?;
+                self.take(len).map(|_| ())
+            }
+            (major, _) => Err(format!("cannot skip unknown field of major type {}", major)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HW_TS: u64 = 1_700_000_000_000_000_000;
+
+    fn encoded() -> Vec<u8> {
+        TriggerMessage {
+            trigger: CameraTrigger::new(300, HW_TS, HW_TS + 50_000),
+            signature: TriggerSignature::default(),
+            link_tx: Some((299, HW_TS - 33_000_000)),
+        }
+        .encode()
+    }
+
+    // A trigger map with the given version and further uint entries after the required ones
+    fn trigger_map(version: u64, extra: &[(u64, u64)]) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_head(&mut out, MAJOR_MAP, 5 + extra.len() as u64);
+        write_uints(&mut out, &[(KEY_VERSION, version), (KEY_KIND, KIND_TRIGGER), (KEY_FRAME_ID, 1), (KEY_HW_TIMESTAMP, HW_TS), (KEY_PUBLISH_TIMESTAMP, HW_TS)]);
+        write_uints(&mut out, extra);
+        out
+    }
+
+    fn error(bytes: &[u8]) -> String {
+        TriggerMessage::decode(bytes).unwrap_err()
+    }
+
+    #[test]
+    fn decodes_what_it_encodes() {
+        let message = TriggerMessage::decode(&encoded()).unwrap();
+        assert_eq!((message.trigger.frame_id, message.trigger.hw_timestamp_ns), (300, HW_TS));
+        assert_eq!(message.link_tx, Some((299, HW_TS - 33_000_000)));
+        assert!(TriggerMessage::decode(&trigger_map(WIRE_VERSION, &[])).is_ok());
+    }
+
+    #[test]
+    fn rejects_truncated_input() {
+        let bytes = encoded();
+        for len in 0..bytes.len() {
+            assert!(TriggerMessage::decode(&bytes[..len]).is_err(), "prefix of {} bytes decoded", len);
+        }
+        assert_eq!(error(&[]), "truncated message");
+        assert_eq!(error(&bytes[..bytes.len() - 1]), "truncated message");
+    }
+
+    #[test]
+    fn rejects_wrong_major_types() {
+        // An array instead of the map
+        let mut bytes = encoded();
+        bytes[0] = (4 << 5) | (bytes[0] & 0x1f);
+        assert_eq!(error(&bytes), "expected major type 5, got 4");
+
+        // A text string where the frame_id belongs
+        let mut bytes = Vec::new();
+        write_head(&mut bytes, MAJOR_MAP, 3);
+        write_uints(&mut bytes, &[(KEY_VERSION, WIRE_VERSION), (KEY_KIND, KIND_TRIGGER)]);
+        write_head(&mut bytes, MAJOR_UINT, KEY_FRAME_ID);
+        write_head(&mut bytes, MAJOR_TEXT, 0);
+        assert_eq!(error(&bytes), "expected major type 0, got 3");
+
+        // A negative key
+        let mut bytes = trigger_map(WIRE_VERSION, &[]);
+        bytes[1] = MAJOR_NEGATIVE << 5;
+        assert_eq!(error(&bytes), "expected major type 0, got 1");
+    }
+
+    #[test]
+    fn rejects_non_canonical_and_overlong_integers() {
+        let canonical = trigger_map(WIRE_VERSION, &[]);
+        // frame_id 1 as one extra byte, then as two
+        let frame_id_at = canonical.iter().position(|&b| b == KEY_FRAME_ID as u8).unwrap() + 1;
+        for overlong in [&[24u8, 1][..], &[25, 0, 1], &[26, 0, 0, 0, 1], &[27, 0, 0, 0, 0, 0, 0, 0, 1]] {
+            let mut bytes = canonical.clone();
+            bytes.splice(frame_id_at..frame_id_at + 1, overlong.iter().copied());
+            assert_eq!(error(&bytes), "non-canonical integer encoding");
+        }
+
+        // Indefinite length and reserved additional info
+        let mut bytes = canonical.clone();
+        bytes[0] = (MAJOR_MAP << 5) | 31;
+        assert_eq!(error(&bytes), "unsupported additional info 31");
+        bytes[0] = (MAJOR_MAP << 5) | 28;
+        assert_eq!(error(&bytes), "unsupported additional info 28");
+
+        // Keys must ascend
+        let mut bytes = Vec::new();
+        write_head(&mut bytes, MAJOR_MAP, 2);
+        write_uints(&mut bytes, &[(KEY_KIND, KIND_TRIGGER), (KEY_VERSION, WIRE_VERSION)]);
+        assert_eq!(error(&bytes), "key 0 out of order");
+
+        // A negative offset below i64::MIN
+        let mut bytes = Vec::new();
+        write_head(&mut bytes, MAJOR_MAP, 6);
+        write_uints(&mut bytes, &[(KEY_VERSION, WIRE_VERSION), (KEY_KIND, KIND_TRIGGER), (KEY_FRAME_ID, 1), (KEY_HW_TIMESTAMP, HW_TS), (KEY_PUBLISH_TIMESTAMP, HW_TS)]);
+        write_head(&mut bytes, MAJOR_UINT, KEY_PHC_OFFSET);
+        write_head(&mut bytes, MAJOR_NEGATIVE, u64::MAX);
+        assert_eq!(error(&bytes), "integer out of range");
+    }
+
+    #[test]
+    fn rejects_unknown_wire_versions() {
+        assert_eq!(error(&trigger_map(0, &[])), "unsupported wire version 0");
+        assert_eq!(error(&trigger_map(WIRE_VERSION + 1, &[])), "unsupported wire version 2");
+    }
+
+    #[test]
+    fn rejects_trailing_bytes() {
+        let mut bytes = encoded();
+        bytes.push(0);
+        assert_eq!(error(&bytes), "1 trailing bytes");
+        bytes.extend_from_slice(&encoded());
+        assert_eq!(error(&bytes), format!("{} trailing bytes", 1 + encoded().len()));
+    }
+
+    #[test]
+    fn skips_unknown_keys_but_not_unknown_value_types() {
+        assert!(TriggerMessage::decode(&trigger_map(WIRE_VERSION, &[(KEY_COUNT as u64 + 10, 7)])).is_ok());
+
+        let mut bytes = trigger_map(WIRE_VERSION, &[]);
+        bytes[0] += 1;
+        write_head(&mut bytes, MAJOR_UINT, KEY_COUNT as u64 + 10);
+        write_head(&mut bytes, MAJOR_MAP, 0);
+        assert_eq!(error(&bytes), "cannot skip unknown field of major type 5");
+    }
+}
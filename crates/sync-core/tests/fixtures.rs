@@ -0,0 +1,29 @@
+// Replays every session fixture in `tests/fixtures/` (see `sync_core::fixture`).
+// New field-captured cases are added with the `make_fixture` tool.
+
+use std::fs;
+use std::path::Path;
+
+use sync_core::fixture::Fixture;
+
+#[test]
+fn recorded_sessions_match_as_recorded() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let mut replayed = 0;
+    let mut failures = Vec::new();
+    for entry in fs::read_dir(&dir).expect("fixture directory") {
+        let path = entry.expect("fixture entry").path();
+        if path.extension().is_none_or(|ext| ext != "fixture") {
+            continue;
+        }
+        let text = fs::read_to_string(&path).expect("readable fixture");
+        let fixture = Fixture::parse(&text).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        assert_eq!(Fixture::parse(&fixture.to_string()).unwrap(), fixture, "{} does not survive printing", path.display());
+        if let Err(differences) = fixture.check() {
+            failures.push(format!("{}: {}", path.display(), differences));
+        }
+        replayed += 1;
+    }
+    assert!(replayed > 0, "no fixtures in {}", dir.display());
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
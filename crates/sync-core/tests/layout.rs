@@ -0,0 +1,162 @@
+// Checks the shared-memory payload layout against C (see `sync_core::layout`):
+// the checked-in header must be what `c_header` generates, and samples written
+// by Rust must read back field by field in a C program compiled against it
+// (and the other way round). The round trip needs a C compiler (`$CC`, else
+// `cc`) and is skipped without one.
+
+use std::fs;
+use std::io::Write;
+use std::mem::{align_of, offset_of, size_of, MaybeUninit};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::ptr::{addr_of_mut, read_unaligned};
+
+use sync_core::canary::CANARY_BIT;
+use sync_core::clock::ClockDomain;
+use sync_core::clock_quality::DowngradePolicy;
+use sync_core::layout::{self, CameraTrigger, SEQUENCE_CANARY, TRIGGER_SCHEMA_VERSION};
+use sync_core::signing::TriggerSignature;
+
+// Reads a trigger and its header, checks the schema version, changes every field, writes them back
+const ROUND_TRIP_C: &str = r#"
+#include <stdio.h>
+#include "camera_sync.h"
+
+int main(void) {
+    camera_trigger trigger;
+    trigger_signature header;
+    if (fread(&trigger, sizeof trigger, 1, stdin) != 1 || fread(&header, sizeof header, 1, stdin) != 1) {
+        return 1;
+    }
+    if (trigger.schema_version != CAMERA_SYNC_SCHEMA_VERSION) {
+        return 2;
+    }
+    trigger.trigger_id += 1;
+    trigger.hw_timestamp_ns += 2;
+    trigger.publish_timestamp_ns += 3;
+    trigger.sequence_flags ^= CAMERA_SYNC_SEQUENCE_CANARY;
+    header.key_id ^= 0xff;
+    header.is_signed = !header.is_signed;
+    header.timestamp_source += 1;
+    for (int i = 0; i < 64; i++) {
+        header.signature[i] ^= 0x5a;
+    }
+    header.clock_domain = header.clock_domain == CAMERA_SYNC_CLOCK_MONOTONIC ? CAMERA_SYNC_CLOCK_PTP : CAMERA_SYNC_CLOCK_REALTIME;
+    header.clock_policy = header.clock_policy == CAMERA_SYNC_CLOCK_POLICY_FLAG ? CAMERA_SYNC_CLOCK_POLICY_PAUSE : CAMERA_SYNC_CLOCK_POLICY_NONE;
+    header.trace_origin += 4;
+    header.phc_offset_ns = -header.phc_offset_ns;
+    fwrite(&trigger, sizeof trigger, 1, stdout);
+    fwrite(&header, sizeof header, 1, stdout);
+    return 0;
+}
+"#;
+
+#[test]
+fn c_header_is_up_to_date() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("include/camera_sync.h");
+    let checked_in = fs::read_to_string(&path).expect("checked-in C header");
+    assert!(
+        checked_in == layout::c_header(),
+        "{} is outdated, regenerate it with `cargo run --bin c_header > {}`",
+        path.display(),
+        path.display()
+    );
+}
+
+#[test]
+fn camera_trigger_has_named_fields_at_fixed_offsets() {
+    assert_eq!((size_of::<CameraTrigger>(), align_of::<CameraTrigger>()), (32, 8));
+    let offsets = [
+        offset_of!(CameraTrigger, frame_id),
+        offset_of!(CameraTrigger, hw_timestamp_ns),
+        offset_of!(CameraTrigger, publish_timestamp_ns),
+        offset_of!(CameraTrigger, sequence_flags),
+        offset_of!(CameraTrigger, schema_version),
+    ];
+    assert_eq!(offsets, [0, 8, 16, 24, 28]);
+
+    let trigger = CameraTrigger::new(7, 1_000, 1_050);
+    assert_eq!((trigger.frame_id, trigger.hw_timestamp_ns, trigger.publish_timestamp_ns), (7, 1_000, 1_050));
+    assert_eq!((trigger.sequence_flags, trigger.schema_version), (0, TRIGGER_SCHEMA_VERSION));
+    assert_eq!(CameraTrigger::new(CANARY_BIT | 7, 1_000, 1_050).sequence_flags, SEQUENCE_CANARY);
+    assert_eq!(CameraTrigger::default(), CameraTrigger::new(0, 0, 0));
+}
+
+// Bytes of `value` with zeroed padding, so nothing uninitialized is read
+fn bytes_of<T>(write: impl FnOnce(*mut T)) -> Vec<u8> {
+    let mut value = MaybeUninit::<T>::zeroed();
+    write(value.as_mut_ptr());
+    unsafe { std::slice::from_raw_parts(value.as_ptr() as *const u8, size_of::<T>()) }.to_vec()
+}
+
+#[test]
+fn payloads_round_trip_through_c() {
+    let compiler = std::env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let dir = std::env::temp_dir().join(format!("camera_sync_layout_{}", std::process::id()));
+    fs::create_dir_all(&dir).expect("temporary directory");
+    let source = dir.join("round_trip.c");
+    let binary = dir.join("round_trip");
+    fs::write(&source, ROUND_TRIP_C).expect("C source");
+    let include = Path::new(env!("CARGO_MANIFEST_DIR")).join("include");
+    let compiled = Command::new(&compiler)
+        .args(["-std=c11", "-Wall", "-Werror", "-I"])
+        .arg(&include)
+        .arg("-o")
+        .arg(&binary)
+        .arg(&source)
+        .status();
+    match compiled {
+        Ok(status) => assert!(status.success(), "{} failed on the generated header", compiler),
+        Err(e) => {
+            eprintln!("skipping the C round trip, no C compiler ({}: {})", compiler, e);
+            return;
+        }
+    }
+
+    let trigger = CameraTrigger::new(41, 1_700_000_000_123_456_789, 1_700_000_000_123_556_789);
+    assert_eq!((trigger.sequence_flags, trigger.schema_version), (0, TRIGGER_SCHEMA_VERSION));
+    let mut signature = [0u8; 64];
+    signature.iter_mut().enumerate().for_each(|(i, byte)| *byte = i as u8);
+    let mut input = bytes_of::<CameraTrigger>(|p| unsafe { p.write(trigger) });
+    input.extend(bytes_of::<TriggerSignature>(|p| unsafe {
+        addr_of_mut!((*p).key_id).write(0x0123_4567_89ab_cdef);
+        addr_of_mut!((*p).signed).write(true);
+        addr_of_mut!((*p).timestamp_source).write(2);
+        addr_of_mut!((*p).signature).write(signature);
+        addr_of_mut!((*p).clock_domain).write(ClockDomain::Monotonic as u8);
+        addr_of_mut!((*p).clock_policy).write(DowngradePolicy::Flag as u8);
+        addr_of_mut!((*p).trace_origin).write(0x3fa2_c91b);
+        addr_of_mut!((*p).phc_offset_ns).write(37_000_004_210);
+    }));
+
+    let mut child = Command::new(&binary).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().expect("round trip program");
+    child.stdin.take().expect("stdin").write_all(&input).expect("write payloads");
+    let output = child.wait_with_output().expect("round trip output");
+    let _ = fs::remove_dir_all(&dir);
+    assert!(output.status.success(), "round trip program failed");
+    assert_eq!(output.stdout.len(), size_of::<CameraTrigger>() + size_of::<TriggerSignature>());
+
+    let (trigger_bytes, header_bytes) = output.stdout.split_at(size_of::<CameraTrigger>());
+    let returned = unsafe { read_unaligned(trigger_bytes.as_ptr() as *const CameraTrigger) };
+    assert_eq!(
+        returned,
+        CameraTrigger {
+            frame_id: 42,
+            hw_timestamp_ns: trigger.hw_timestamp_ns + 2,
+            publish_timestamp_ns: trigger.publish_timestamp_ns + 3,
+            sequence_flags: SEQUENCE_CANARY,
+            schema_version: TRIGGER_SCHEMA_VERSION,
+        }
+    );
+    assert!(returned.check_version().is_ok());
+    assert!(CameraTrigger { schema_version: 1, ..returned }.check_version().is_err());
+    let header = unsafe { read_unaligned(header_bytes.as_ptr() as *const TriggerSignature) };
+    assert_eq!(header.key_id, 0x0123_4567_89ab_cdef ^ 0xff);
+    assert!(!header.signed);
+    assert_eq!(header.timestamp_source, 3);
+    assert!(header.signature.iter().enumerate().all(|(i, byte)| *byte == i as u8 ^ 0x5a));
+    assert_eq!(ClockDomain::from_u8(header.clock_domain), Some(ClockDomain::Ptp));
+    assert_eq!(DowngradePolicy::from_header(header.clock_policy), Some(DowngradePolicy::Pause));
+    assert_eq!(header.trace_origin, 0x3fa2_c91f);
+    assert_eq!(header.phc_offset_ns, -37_000_004_210);
+}
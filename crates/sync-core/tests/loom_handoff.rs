@@ -0,0 +1,213 @@
+// Model checks of the capture/UI handoff (see `sync_core::handoff`). loom
+// runs every test body under all thread interleavings (up to the preemption
+// bound of the larger models) and fails on a deadlock, a data race on the
+// slots or a failed assertion. The structures are checked on their own, then
+// in the shape the GUI uses them: a capture thread publishing preview frames
+// and reports while the UI takes them, sends paint times back and stops the
+// capture. Only built with loom:
+//
+//   RUSTFLAGS="--cfg loom" cargo test -p sync-core --test loom_handoff --release
+#![cfg(loom)]
+
+use std::sync::atomic::AtomicUsize as StdAtomicUsize;
+
+use loom::sync::atomic::{AtomicBool, Ordering};
+use loom::sync::Arc;
+use loom::thread;
+use sync_core::handoff::{self, RingReceiver};
+
+/// Preemption bound of the models with more than a handful of operations per thread.
+const PREEMPTION_BOUND: usize = 3;
+
+fn bounded_model(f: impl Fn() + Sync + Send + 'static) {
+    let mut builder = loom::model::Builder::new();
+    builder.preemption_bound = Some(PREEMPTION_BOUND);
+    builder.check(f);
+}
+
+// Counts its drops; the counter is outside the model, read after the threads joined
+struct Counted(std::sync::Arc<StdAtomicUsize>);
+
+impl Drop for Counted {
+    fn drop(&mut self) {
+        self.0.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn triple_buffer_reader_sees_complete_newest_values() {
+    loom::model(|| {
+        let (mut writer, mut reader) = handoff::triple_buffer((0u32, 0u32));
+        let publisher = thread::spawn(move || {
+            writer.publish((1, 1));
+            writer.publish((2, 2));
+        });
+        let mut last = 0;
+        for _ in 0..2 {
+            reader.update();
+            let (a, b) = reader.read(|value| *value);
+            assert_eq!(a, b, "torn value");
+            assert!(a >= last, "went back from {} to {}", last, a);
+            last = a;
+        }
+        publisher.join().unwrap();
+        // The last value is never lost, however the two threads interleaved
+        if last != 2 {
+            assert!(reader.update());
+        }
+        assert_eq!(reader.read(|value| *value), (2, 2));
+        assert!(!reader.update());
+    });
+}
+
+#[test]
+fn ring_keeps_order_and_loses_nothing_sent() {
+    loom::model(|| {
+        let (mut sender, mut receiver) = handoff::ring::<u32>(2);
+        let producer = thread::spawn(move || (1..=3).filter(|&value| sender.try_send(value).is_ok()).collect::<Vec<_>>());
+        let mut received = Vec::new();
+        for _ in 0..2 {
+            received.extend(receiver.try_recv());
+        }
+        let sent = producer.join().unwrap();
+        received.extend(std::iter::from_fn(|| receiver.try_recv()));
+        assert_eq!(received, sent);
+    });
+}
+
+#[test]
+fn triple_buffer_take_yields_each_frame_once() {
+    loom::model(|| {
+        let (mut writer, mut reader) = handoff::triple_buffer(None);
+        let publisher = thread::spawn(move || {
+            writer.publish(Some(1u32));
+            writer.publish(Some(2));
+        });
+        let mut taken = Vec::new();
+        for _ in 0..2 {
+            if reader.update() {
+                taken.extend(reader.read_mut(Option::take));
+            }
+        }
+        publisher.join().unwrap();
+        if reader.update() {
+            taken.extend(reader.read_mut(Option::take));
+        }
+        // Frames may be skipped, but never repeated, reordered or the newest lost
+        assert!(taken.windows(2).all(|pair| pair[0] < pair[1]), "taken {:?}", taken);
+        assert_eq!(taken.last(), Some(&2));
+    });
+}
+
+#[test]
+fn ring_wraps_around_in_order() {
+    loom::model(|| {
+        let (mut sender, mut receiver) = handoff::ring::<u32>(1);
+        let producer = thread::spawn(move || {
+            for value in 1..=3 {
+                let mut value = value;
+                while let Err(rejected) = sender.try_send(value) {
+                    value = rejected;
+                    thread::yield_now();
+                }
+            }
+        });
+        let mut received = Vec::new();
+        while received.len() < 3 {
+            match receiver.try_recv() {
+                Some(value) => received.push(value),
+                None => thread::yield_now(),
+            }
+        }
+        producer.join().unwrap();
+        assert_eq!(received, [1, 2, 3]);
+        assert_eq!(receiver.try_recv(), None);
+    });
+}
+
+#[test]
+fn ring_drops_values_left_in_it() {
+    loom::model(|| {
+        let drops = std::sync::Arc::new(StdAtomicUsize::new(0));
+        let (mut sender, mut receiver) = handoff::ring(2);
+        let counter = drops.clone();
+        let producer = thread::spawn(move || {
+            for _ in 0..2 {
+                assert!(sender.try_send(Counted(counter.clone())).is_ok());
+            }
+        });
+        drop(receiver.try_recv());
+        producer.join().unwrap();
+        drop(receiver);
+        assert_eq!(drops.load(std::sync::atomic::Ordering::Relaxed), 2);
+    });
+}
+
+// What the capture thread got back from the UI
+struct CaptureEnd {
+    published: u32,
+    painted: RingReceiver<u32>,
+    received_painted: Vec<u32>,
+}
+
+#[test]
+fn capture_and_ui_lose_no_updates() {
+    bounded_model(|| {
+        let running = Arc::new(AtomicBool::new(true));
+        let (mut preview_writer, mut preview) = handoff::triple_buffer(None);
+        let (mut reports_writer, mut reports) = handoff::triple_buffer(0u32);
+        let (mut painted, painted_receiver) = handoff::ring(1);
+
+        let capture_running = running.clone();
+        let capture = thread::spawn(move || {
+            let mut end = CaptureEnd {
+                published: 0,
+                painted: painted_receiver,
+                received_painted: Vec::new(),
+            };
+            for frame in 1..=2 {
+                end.received_painted.extend(std::iter::from_fn(|| end.painted.try_recv()));
+                if !capture_running.load(Ordering::Relaxed) {
+                    break;
+                }
+                preview_writer.publish(Some(frame));
+                reports_writer.publish(frame);
+                end.published = frame;
+            }
+            end
+        });
+
+        // UI: one paint, then the stop button
+        let (mut shown, mut sent) = (Vec::new(), Vec::new());
+        let mut report_seen = 0;
+        if reports.update() {
+            report_seen = reports.read(|report| *report);
+        }
+        if preview.update() {
+            if let Some(frame) = preview.read_mut(Option::take) {
+                shown.push(frame);
+                // A full ring drops the paint, it never blocks the UI
+                if painted.try_send(frame).is_ok() {
+                    sent.push(frame);
+                }
+            }
+        }
+        running.store(false, Ordering::Relaxed);
+
+        let mut end = capture.join().unwrap();
+        // The frame and the report published last always reach the UI
+        if preview.update() {
+            shown.extend(preview.read_mut(Option::take));
+        }
+        if reports.update() {
+            report_seen = reports.read(|report| *report);
+        }
+        assert_eq!(report_seen, end.published);
+        assert_eq!(shown.last().copied().unwrap_or(0), end.published);
+        assert!(shown.windows(2).all(|pair| pair[0] < pair[1]), "shown {:?}", shown);
+
+        // Every paint that was sent arrives, in order
+        end.received_painted.extend(std::iter::from_fn(|| end.painted.try_recv()));
+        assert_eq!(end.received_painted, sent);
+    });
+}
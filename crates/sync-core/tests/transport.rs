@@ -0,0 +1,109 @@
+// Publisher and subscriber logic must run on the in-memory transport like on
+// shared memory: late subscribers get the history, batches arrive trigger by
+// trigger with their header, a subscriber that does not read loses the
+// oldest triggers (counted as undelivered), an exhausted loan pool drops
+// triggers, and a waiting subscriber wakes when a trigger arrives (see
+// `sync_core::transport`).
+
+use std::time::{Duration, Instant};
+
+use sync_core::burst::BurstPattern;
+use sync_core::clock;
+use sync_core::latency::LatencyBreakdown;
+use sync_core::pipeline::{MatchPipeline, Stage};
+use sync_core::signing::TriggerSignature;
+use sync_core::transport::{self, MockTransport, TriggerSink, TriggerSource, HISTORY_SIZE, MAX_SUBSCRIBERS, SUBSCRIBER_BUFFER_SIZE};
+use sync_core::trigger_batch::TriggerBatcher;
+use sync_core::units::Unit;
+use sync_core::CameraTrigger;
+
+const MS: u64 = 1_000_000;
+
+#[test]
+fn triggers_reach_the_matcher_through_the_mock() {
+    let transport = MockTransport::new();
+    let publisher = transport.publisher();
+    let header = TriggerSignature {
+        trace_origin: 7,
+        ..TriggerSignature::default()
+    };
+    let trigger = |id: u64| CameraTrigger::new(id, 1_000 * MS + id * 33 * MS, 1_000 * MS + id * 33 * MS + 50_000);
+
+    // Published before the camera process connects: only the history reaches it
+    for id in 1..=HISTORY_SIZE as u64 + 2 {
+        assert!(publisher.publish(trigger(id), header).unwrap());
+    }
+    let subscriber = transport.subscriber().unwrap();
+    let mut batcher = TriggerBatcher::new(8, 10.0);
+    for id in 13..=15 {
+        assert!(batcher.push(trigger(id), 0).is_none());
+    }
+    assert!(publisher.publish_batch(&batcher.flush().unwrap(), header).unwrap());
+
+    let mut matcher = MatchPipeline::with_stages(&[Stage::Associate], 10.0, BurstPattern::new(1));
+    let mut received = Vec::new();
+    while let Some((trigger, header)) = subscriber.receive().unwrap() {
+        received.push(trigger.frame_id);
+        matcher.on_trigger(trigger, header);
+    }
+    assert_eq!(received, (3..=15).collect::<Vec<_>>());
+    let frame = matcher.on_frame(trigger(15).hw_timestamp_ns + 4 * MS);
+    let (matched, header, _) = frame.matched.unwrap();
+    assert_eq!((matched.frame_id, header.trace_origin), (15, 7));
+
+    // A subscriber that stops reading loses the oldest triggers
+    for id in 16..16 + SUBSCRIBER_BUFFER_SIZE as u64 + 5 {
+        publisher.publish(trigger(id), header).unwrap();
+    }
+    assert_eq!(subscriber.receive().unwrap().unwrap().0.frame_id, 21);
+    assert_eq!(publisher.delivery().undelivered, 5);
+
+    // Subscribers are limited like on the real service; dropped ones disconnect
+    let others: Vec<_> = (1..MAX_SUBSCRIBERS).map(|_| transport.subscriber().unwrap()).collect();
+    assert!(transport.subscriber().is_err());
+    drop(others);
+    let subscriber = transport.subscriber().unwrap();
+    while subscriber.receive().unwrap().is_some() {}
+
+    let results = transport::round_trips(&publisher, &subscriber, 50, Duration::from_millis(10)).unwrap();
+    assert_eq!((results.sent, results.lost, results.corrupted, results.latency.count()), (50, 0, 0, 50));
+    transport.set_loans_exhausted(true);
+    let results = transport::round_trips(&publisher, &subscriber, 5, Duration::from_millis(10)).unwrap();
+    assert_eq!((results.lost, results.latency.count()), (5, 0));
+}
+
+#[test]
+fn waiting_subscribers_wake_on_publish() {
+    let transport = MockTransport::new();
+    let publisher = transport.publisher();
+    let subscriber = transport.subscriber().unwrap();
+
+    // Nothing published: the wait runs into its timeout
+    let start = Instant::now();
+    assert!(!subscriber.wait(Duration::from_millis(20)).unwrap());
+    assert!(start.elapsed() >= Duration::from_millis(20));
+
+    // Published from another thread while waiting: woken long before the timeout
+    let start = Instant::now();
+    let sender = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(10));
+        let now_ns = clock::realtime_now_ns();
+        publisher.publish(CameraTrigger::new(1, now_ns, now_ns), TriggerSignature::default()).unwrap();
+    });
+    assert!(subscriber.wait(Duration::from_secs(5)).unwrap());
+    assert!(start.elapsed() < Duration::from_secs(5));
+    sender.join().unwrap();
+    let (trigger, _) = subscriber.receive().unwrap().unwrap();
+    assert_eq!(trigger.frame_id, 1);
+    let pub_ts = trigger.publish_timestamp_ns;
+
+    // A trigger that arrived before the wait returns right away
+    transport.publisher().publish(CameraTrigger::new(2, pub_ts, pub_ts), TriggerSignature::default()).unwrap();
+    assert!(subscriber.wait(Duration::ZERO).unwrap());
+
+    // The wakeup latency has its own stage
+    let mut latency = LatencyBreakdown::new(None);
+    latency.record_wakeup(pub_ts, clock::realtime_now_ns());
+    assert_eq!(latency.stages().iter().map(|(name, stats)| (*name, stats.count())).collect::<Vec<_>>(), [("publish->wakeup", 1)]);
+    assert!(latency.report(Unit::Ms).contains("publish->wakeup"));
+}
@@ -0,0 +1,333 @@
+use eframe::egui;
+use eframe::egui::{ColorImage, TextureHandle};
+use std::cell::Cell;
+use std::process::ExitCode;
+use std::rc::Rc;
+use std::sync::atomic::Ordering;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use sync_core::cli::Args;
+use sync_core::clock;
+use sync_core::ui_budget::UiTiming;
+
+use crate::check;
+use crate::headless;
+use crate::preview;
+use crate::worker::{self, Painted, Reports, WorkerHandle, SWITCHES};
+
+/// Camera capture app: previews the frames the capture thread (`worker`) matched to triggers.
+pub struct CameraApp {
+    worker: WorkerHandle,
+    // Joined when it ends (a `--duration` run is over); its code is handed to `run` in `exit_code`
+    capture_thread: Option<JoinHandle<i32>>,
+    exit_code: Rc<Cell<ExitCode>>,
+    // Copy of the worker's latest reports
+    reports: Reports,
+    // Frame uploaded on the next paint: (matched hw_ts, dequeue time), and how long converting it took
+    pending_display: Option<(Option<u64>, u64)>,
+    convert_ns: u64,
+    // Minimum time between repaints while capturing, paced by the worker
+    repaint_interval: Duration,
+    // Widest preview texture in pixels (0 = full resolution) and the decimation factor in use
+    preview_max_width: usize,
+    preview_scale: usize,
+    // Exposure/focus overlays, toggled in the UI
+    show_histogram: bool,
+    histogram: [u32; preview::HISTOGRAM_BINS],
+    focus_peaking: bool,
+    preview_pacing: String,
+    display_interval_ms: f32,
+    width: u32,
+    height: u32,
+    current_frame: Option<ColorImage>,
+    texture: Option<TextureHandle>,
+}
+
+impl CameraApp {
+    /// Starts the capture thread; `exit_code` is set when it ends and closes the window.
+    pub fn new(cc: &eframe::CreationContext<'_>, exit_code: Rc<Cell<ExitCode>>) -> std::io::Result<Self> {
+        // The capture thread parses the capture options itself
+        let args = Args::from_env(SWITCHES);
+
+        // Preview pacing: vsync (default, no tearing) or immediate (lowest display latency)
+        let preview_pacing = args.value("preview-pacing").unwrap_or("vsync").to_string();
+
+        let repaint_interval = worker::RepaintThrottle::from_args(&args).interval;
+
+        // Large frames are downscaled for the preview only; recordings keep full resolution
+        let preview_max_width = args.value_as::<usize>("preview-max-width").unwrap_or(960);
+
+        let (worker, capture_thread) = worker::spawn(Some(Box::new(cc.egui_ctx.clone())))?;
+        Ok(Self {
+            worker,
+            capture_thread: Some(capture_thread),
+            exit_code,
+            reports: Reports::default(),
+            pending_display: None,
+            convert_ns: 0,
+            repaint_interval,
+            preview_max_width,
+            preview_scale: 1,
+            show_histogram: args.flag("histogram"),
+            histogram: [0; preview::HISTOGRAM_BINS],
+            focus_peaking: args.flag("focus-peaking"),
+            preview_pacing,
+            display_interval_ms: 0.0,
+            width: args.positional::<u32>(2).unwrap_or(640),
+            height: args.positional::<u32>(3).unwrap_or(480),
+            current_frame: None,
+            texture: None,
+        })
+    }
+
+    // Converts the latest processed frame for display (once per repaint, not per captured frame)
+    fn update_preview(&mut self) {
+        if !self.worker.preview.update() {
+            return;
+        }
+        let Some((frame, matched_hw_ts, v4l2_timestamp_ns)) = self.worker.preview.read_mut(Option::take) else {
+            return;
+        };
+        let buffer = &frame.rgb;
+        let actual_width = frame.width as usize;
+        let actual_height = frame.height as usize;
+
+        // Check if buffer size matches expected RGB format (3 bytes per pixel)
+        let expected_buffer_size = actual_width * actual_height * 3;
+        if buffer.len() != expected_buffer_size {
+            self.reports.sync_info = format!("Frame format mismatch: got {} bytes, expected {} bytes for {}x{} RGB",
+                                   buffer.len(), expected_buffer_size, actual_width, actual_height);
+            return;
+        }
+
+        let started = Instant::now();
+        self.preview_scale = preview::scale_factor(actual_width, self.preview_max_width);
+        let mut image = preview::to_color_image(buffer, actual_width, actual_height, self.preview_scale);
+        if self.show_histogram {
+            self.histogram = preview::luma_histogram(&image);
+        }
+        if self.focus_peaking {
+            preview::apply_focus_peaking(&mut image);
+        }
+        self.current_frame = Some(image);
+        self.pending_display = Some((matched_hw_ts, v4l2_timestamp_ns));
+        self.convert_ns = started.elapsed().as_nanos() as u64;
+        // Update stored dimensions to match actual camera resolution
+        self.width = actual_width as u32;
+        self.height = actual_height as u32;
+    }
+
+    fn draw_histogram(&self, ui: &mut egui::Ui) {
+        let (rect, _) = ui.allocate_exact_size(egui::vec2(256.0, 64.0), egui::Sense::hover());
+        let painter = ui.painter();
+        painter.rect_filled(rect, 0.0, egui::Color32::from_gray(24));
+        let peak = self.histogram.iter().copied().max().unwrap_or(0).max(1) as f32;
+        let bar_width = rect.width() / preview::HISTOGRAM_BINS as f32;
+        for (bin, &count) in self.histogram.iter().enumerate() {
+            let height = rect.height() * count as f32 / peak;
+            let left = rect.left() + bin as f32 * bar_width;
+            let bar = egui::Rect::from_min_max(egui::pos2(left, rect.bottom() - height), egui::pos2(left + bar_width, rect.bottom()));
+            painter.rect_filled(bar, 0.0, egui::Color32::LIGHT_GRAY);
+        }
+    }
+
+    fn draw_glass_to_glass_marker(&self, ui: &mut egui::Ui) {
+        if let Some(lit) = self.reports.marker_lit {
+            let (rect, _) = ui.allocate_exact_size(egui::vec2(160.0, 160.0), egui::Sense::hover());
+            let color = if lit { egui::Color32::WHITE } else { egui::Color32::BLACK };
+            ui.painter().rect_filled(rect, 0.0, color);
+        }
+    }
+}
+
+impl eframe::App for CameraApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let update_started = Instant::now();
+        // Smoothed interval between paints (the display refresh when vsync paced)
+        let dt_ms = ctx.input(|i| i.unstable_dt) * 1000.0;
+        self.display_interval_ms = if self.display_interval_ms == 0.0 { dt_ms } else { self.display_interval_ms * 0.95 + dt_ms * 0.05 };
+
+        if let Some(capture_thread) = self.capture_thread.take_if(|thread| thread.is_finished()) {
+            self.exit_code.set(worker::exit_code(capture_thread.join()));
+            ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+        }
+
+        if self.worker.reports.update() {
+            self.reports = self.worker.reports.read(Reports::clone);
+        }
+        self.update_preview();
+        let running = self.worker.running.load(Ordering::Relaxed);
+
+        let mut shown = None;
+        let mut upload_ns = 0;
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading("V4L2 Camera Capture with Iceoryx2 Sync");
+
+            ui.horizontal(|ui| {
+                if ui.button(if running { "Stop Capture" } else { "Start Capture" }).clicked() {
+                    self.worker.running.store(!running, Ordering::Relaxed);
+                }
+
+                ui.label(format!("Camera: {} (/dev/video{}) | {}x{} | {}fps output",
+                               self.reports.camera, self.reports.camera_index, self.width, self.height, self.reports.output_fps));
+            });
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.show_histogram, "Histogram");
+                ui.checkbox(&mut self.focus_peaking, "Focus peaking");
+            });
+
+            if !self.reports.stream_info.is_empty() {
+                ui.label(format!("Stream: {}", self.reports.stream_info));
+            }
+            if self.display_interval_ms > 0.0 {
+                ui.label(format!(
+                    "Preview: {} pacing, {:.1}Hz display, scale 1/{}, {}",
+                    self.preview_pacing,
+                    1000.0 / self.display_interval_ms,
+                    self.preview_scale,
+                    if self.repaint_interval.is_zero() || self.reports.marker_lit.is_some() {
+                        "repaint every frame".to_string()
+                    } else {
+                        format!("repaint at most {:.0}fps", 1.0 / self.repaint_interval.as_secs_f64())
+                    }
+                ));
+            }
+
+            if !self.reports.rate_info.is_empty() {
+                ui.label(format!("Triggers: {} (camera {}fps)", self.reports.rate_info, self.reports.camera_fps));
+            }
+            if let Some(warning) = &self.reports.rate_warning {
+                ui.colored_label(egui::Color32::YELLOW, warning);
+            }
+
+            ui.separator();
+
+            // Display sync info
+            ui.label(&self.reports.sync_info);
+            if !self.reports.latency_report.is_empty() {
+                ui.monospace(&self.reports.latency_report);
+            }
+            if !self.reports.diagnosis_report.is_empty() {
+                ui.monospace(&self.reports.diagnosis_report);
+            }
+            if !self.reports.drops_report.is_empty() {
+                ui.monospace(&self.reports.drops_report);
+            }
+            if !self.reports.retention_report.is_empty() {
+                ui.monospace(&self.reports.retention_report);
+            }
+            if !self.reports.deadline_report.is_empty() {
+                ui.monospace(&self.reports.deadline_report);
+            }
+            if !self.reports.depth_report.is_empty() {
+                ui.monospace(&self.reports.depth_report);
+            }
+            if !self.reports.ui_report.is_empty() {
+                ui.monospace(&self.reports.ui_report);
+            }
+            if let Some(warning) = &self.reports.ui_warning {
+                ui.colored_label(egui::Color32::YELLOW, warning);
+            }
+            if !self.reports.degradation_report.is_empty() {
+                ui.monospace(&self.reports.degradation_report);
+            }
+
+            // Glass-to-glass marker (point the camera or a photodiode at it)
+            self.draw_glass_to_glass_marker(ui);
+            if !self.reports.glass_to_glass_report.is_empty() {
+                ui.monospace(&self.reports.glass_to_glass_report);
+            }
+            if !self.reports.display_clock_report.is_empty() {
+                ui.monospace(&self.reports.display_clock_report);
+            }
+
+            if self.show_histogram {
+                self.draw_histogram(ui);
+            }
+
+            // Display frame
+            if let Some(frame) = &self.current_frame {
+                // Check if we need to recreate the texture due to size change
+                let needs_new_texture = if let Some(existing_texture) = &self.texture {
+                    let current_size = existing_texture.size_vec2();
+                    let frame_size = egui::Vec2::new(frame.size[0] as f32, frame.size[1] as f32);
+                    current_size != frame_size
+                } else {
+                    true
+                };
+
+                let upload_started = Instant::now();
+                if needs_new_texture {
+                    self.texture = Some(ui.ctx().load_texture("camera_frame", frame.clone(), Default::default()));
+                }
+
+                if let Some(texture) = &mut self.texture {
+                    // Upload only frames that were not shown yet and timestamp their presentation
+                    if let Some(displayed) = self.pending_display.take() {
+                        texture.set(frame.clone(), Default::default());
+                        shown = Some(displayed);
+                    }
+                    upload_ns = upload_started.elapsed().as_nanos() as u64;
+
+                    let size = texture.size_vec2();
+                    ui.image((texture.id(), size));
+                }
+            } else {
+                ui.label("No frame captured yet. Click 'Start Capture' to begin.");
+            }
+        });
+
+        // The worker records display latency and the glass-to-glass paint; a paint is
+        // not counted if it is a full ring behind
+        if shown.is_some() || self.reports.marker_lit.is_some() {
+            // Conversion, upload and the update so far count against the frame budget (see `sync_core::ui_budget`)
+            let ui = shown.map(|_| UiTiming {
+                convert_ns: self.convert_ns,
+                upload_ns,
+                paint_ns: update_started.elapsed().as_nanos() as u64,
+            });
+            let _ = self.worker.painted.try_send(Painted {
+                painted_ns: clock::realtime_now_ns(),
+                displayed: shown,
+                marker_lit: self.reports.marker_lit == Some(true),
+                ui,
+            });
+        }
+        if self.reports.marker_lit.is_some() {
+            // Keep repainting so the marker turns dark again on time
+            ctx.request_repaint();
+        }
+    }
+}
+
+/// Opens the preview window and runs the app until it is closed or a `--duration` run is over.
+pub fn run() -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let args = Args::from_env(SWITCHES);
+    // Readiness report instead of the GUI
+    if args.flag("check") {
+        check::run(&args)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    // Capture without a window
+    if args.flag("headless") {
+        return headless::run(&args);
+    }
+    let options = eframe::NativeOptions {
+        // Pace preview updates to the display refresh unless immediate presentation is requested
+        vsync: args.value("preview-pacing") != Some("immediate"),
+        viewport: egui::ViewportBuilder::default()
+            .with_inner_size([800.0, 600.0])
+            .with_title("V4L2 Camera Capture with Iceoryx2 Sync"),
+        ..Default::default()
+    };
+
+    let exit_code = Rc::new(Cell::new(ExitCode::SUCCESS));
+    let app_exit_code = exit_code.clone();
+    eframe::run_native(
+        "V4L2 Camera Capture with Iceoryx2 Sync",
+        options,
+        Box::new(|cc| Ok(Box::new(CameraApp::new(cc, app_exit_code)?))),
+    )?;
+
+    Ok(exit_code.get())
+}
\ No newline at end of file
@@ -0,0 +1,255 @@
+// Readiness check of the capture app (`v4l2_capture --check`).
+//
+// Goes through what the capture thread (`worker`) sets up at startup without
+// capturing or recording anything: the options and the config file, the
+// input files, the camera (resolved, opened once to see what it delivers and
+// closed again), the rig's iceoryx2 setup and the trigger service, the clocks
+// and the output paths. Prints one line per check and a summary (see
+// `sync_core::readiness`) and exits with 1 if any check failed, so an
+// unattended recording can be gated on it. Warnings (no publisher running
+// yet, dead nodes of the rig, a resolution other than requested) do not fail
+// the check.
+
+use std::fs::OpenOptions;
+use std::path::Path;
+use std::process;
+use sync_capture::device::CameraSelector;
+use sync_capture::{CaptureBackend, NokhwaBackend};
+use sync_core::batch;
+use sync_core::cli::Args;
+use sync_core::clock;
+use sync_core::eventlog::EventLog;
+use sync_core::intrinsics::CameraIntrinsics;
+use sync_core::matcher_state::MatcherState;
+use sync_core::pipeline;
+use sync_core::readiness::{self, ReadinessReport};
+use sync_core::session::RECORDS_FILE;
+use sync_core::signing::TriggerVerifier;
+use sync_core::status::Status;
+use sync_core::timecode::TimecodeClock;
+use sync_core::trace::Tracer;
+use sync_iceoryx2::node::{self, create_node};
+use sync_iceoryx2::trigger::{self, TriggerSubscriber};
+
+use crate::worker::Options;
+
+/// Runs the checks for the capture options in `args`, prints the report and exits with 1 unless ready.
+pub fn run(args: &Args) -> Result<(), Box<dyn std::error::Error>> {
+    let status = Status::from_args(args, "v4l2_capture")?;
+    status.text(format_args!("Checking readiness of {} (no capture is started)...", args.program()));
+    let camera = args.value("camera").map(str::to_string).or_else(|| args.positional::<String>(0)).unwrap_or_else(|| "0".to_string());
+
+    let mut report = ReadinessReport::default();
+    let options = check_options(&mut report, args, &camera);
+    readiness::check_clocks(&mut report);
+    if let Some((options, selector)) = &options {
+        let intrinsics = check_inputs(&mut report, args, options, selector);
+        check_camera(&mut report, args, options, selector, intrinsics.as_ref());
+        check_ipc(&mut report, options, selector);
+        check_outputs(&mut report, args, options);
+    }
+    report.print(&status);
+
+    if !report.is_ready() {
+        process::exit(1);
+    }
+    Ok(())
+}
+
+// Everything the worker parses before initializing; the other checks need the options and the camera
+fn check_options(report: &mut ReadinessReport, args: &Args, camera: &str) -> Option<(Options, CameraSelector)> {
+    // A config file that does not load ends the process before this (see `Args::from_env`)
+    match args.value("config") {
+        Some(path) => report.check("config", true, format!("{} loaded", path)),
+        None => report.check("config", true, "none, command line only"),
+    }
+    if let Some(stages) = args.value("match-stages") {
+        report.result("match stages", pipeline::parse_stages(stages).map(|stages| format!("{} stages", stages.len())));
+    }
+    match TimecodeClock::from_args(args) {
+        Ok(Some(clock)) => report.check("timecode", true, clock.describe()),
+        Ok(None) => {}
+        Err(e) => report.check("timecode", false, e),
+    }
+    if let Some(duration) = args.value("duration") {
+        report.result("batch duration", batch::parse_duration(duration).map(|duration| format!("{:?}", duration)));
+    }
+    let selector = match camera.parse::<CameraSelector>() {
+        Ok(selector) => selector,
+        Err(e) => {
+            report.check("camera selector", false, e);
+            return None;
+        }
+    };
+    match Options::from_args(args, camera) {
+        Ok(options) => {
+            report.check("node name", true, options.node.name());
+            Some((options, selector))
+        }
+        Err(e) => {
+            report.check("node name", false, e);
+            None
+        }
+    }
+}
+
+// Files read at startup; returns the intrinsics to compare with the camera's resolution
+fn check_inputs(report: &mut ReadinessReport, args: &Args, options: &Options, selector: &CameraSelector) -> Option<CameraIntrinsics> {
+    for path in &options.calibration_files {
+        readiness::check_input_file(report, "calibration", Path::new(path));
+    }
+    if let Some(path) = &options.verify_key {
+        report.result("verify key", TriggerVerifier::from_key_file(Path::new(path)).map(|_| path.clone()));
+    }
+    if let Some(path) = args.value("matcher-state") {
+        // A missing file is created by the first save
+        match MatcherState::load(Path::new(path)) {
+            Ok(Some(state)) if state.restorable(&selector.to_string(), clock::realtime_now_ns()).is_some() => report.check("matcher state", true, format!("{} (restored)", path)),
+            Ok(Some(_)) => report.check("matcher state", true, format!("{} (no recent state of camera {}, learning from the first matches)", path, selector)),
+            Ok(None) => readiness::check_output_file(report, "matcher state", Path::new(path)),
+            Err(e) => report.check("matcher state", false, e.to_string()),
+        }
+    }
+    let path = options.camera_info.as_ref()?;
+    match CameraIntrinsics::load(Path::new(path)) {
+        Ok(intrinsics) => {
+            report.check("camera info", true, format!("{} ({}x{}, {})", path, intrinsics.width, intrinsics.height, intrinsics.distortion_model));
+            Some(intrinsics)
+        }
+        Err(e) => {
+            report.check("camera info", false, format!("{}: {}", path, e));
+            None
+        }
+    }
+}
+
+// Resolves and opens the camera like the worker, then closes it again
+fn check_camera(report: &mut ReadinessReport, args: &Args, options: &Options, selector: &CameraSelector, intrinsics: Option<&CameraIntrinsics>) {
+    let width = args.positional::<u32>(2).unwrap_or(640);
+    let height = args.positional::<u32>(3).unwrap_or(480);
+    let camera_fps = args.value_as::<u32>("camera-fps").unwrap_or(30);
+    let opened: Result<Box<dyn CaptureBackend>, Box<dyn std::error::Error>> = match options.backend.as_str() {
+        // Synthetic frames: no device to probe
+        "pattern" => {
+            report.check("camera", true, "test pattern, no device");
+            return;
+        }
+        backend => match selector.resolve() {
+            Ok(index) => {
+                report.check("camera", true, format!("{} is /dev/video{}", selector, index));
+                open_camera(backend, index, width, height, camera_fps, options)
+            }
+            Err(e) => {
+                report.check("camera", false, e);
+                return;
+            }
+        },
+    };
+    let camera = match opened {
+        Ok(camera) => camera,
+        Err(e) => {
+            report.check("camera stream", false, e.to_string());
+            return;
+        }
+    };
+    report.check("camera stream", true, camera.describe());
+    let (actual_width, actual_height) = camera.resolution();
+    if (actual_width, actual_height) != (width, height) {
+        report.warn("camera resolution", format!("{}x{} requested, the camera delivers {}x{}", width, height, actual_width, actual_height));
+    }
+    if let Some(intrinsics) = intrinsics.filter(|intrinsics| (intrinsics.width, intrinsics.height) != (actual_width, actual_height)) {
+        report.warn("camera info resolution", format!("calibrated at {}x{}, the camera delivers {}x{}", intrinsics.width, intrinsics.height, actual_width, actual_height));
+    }
+}
+
+#[cfg_attr(not(target_os = "linux"), allow(unused_variables))]
+fn open_camera(backend: &str, index: u32, width: u32, height: u32, camera_fps: u32, options: &Options) -> Result<Box<dyn CaptureBackend>, Box<dyn std::error::Error>> {
+    match backend {
+        "nokhwa" => Ok(Box::new(NokhwaBackend::open(index, width, height)?)),
+        #[cfg(target_os = "linux")]
+        "v4l2" => {
+            let tuning = sync_capture::tuning::BufferTuning::plan(camera_fps, options.target_latency_ms, options.v4l2_buffers, options.v4l2_memory);
+            Ok(Box::new(sync_capture::V4l2Backend::open(index, width, height, camera_fps, tuning)?))
+        }
+        #[cfg(feature = "gstreamer")]
+        "gstreamer" => {
+            let description = options.gst_pipeline.as_deref().ok_or("--backend gstreamer needs --gst-pipeline <description>")?;
+            Ok(Box::new(sync_capture::GstreamerBackend::open(&description.replace("{device}", &format!("/dev/video{}", index)))?))
+        }
+        other => Err(format!("unknown capture backend '{}'", other).into()),
+    }
+}
+
+// Shared memory of the rig and the trigger service; the camera process only subscribes
+fn check_ipc(report: &mut ReadinessReport, options: &Options, selector: &CameraSelector) {
+    let node = match create_node(&options.node) {
+        Ok(node) => node,
+        Err(e) => {
+            report.check("iceoryx2 node", false, e.to_string());
+            return;
+        }
+    };
+    let prefix = options.node.resource_prefix().unwrap_or_else(|| "iox2_".to_string());
+    report.check("iceoryx2 node", true, format!("{} (resource prefix {})", options.node.name(), prefix));
+    match node::list_nodes(&options.node) {
+        Ok(nodes) => {
+            let dead = nodes.iter().filter(|node| !node.alive).count();
+            if dead > 0 {
+                report.warn("stale nodes", format!("{} dead nodes of rig {}, remove them with `rig_nodes cleanup --rig-id {}`", dead, options.node.rig(), options.node.rig()));
+            }
+        }
+        Err(e) => report.warn("stale nodes", format!("could not list the rig's nodes: {}", e)),
+    }
+    // A selector that does not resolve already failed the camera check
+    let service_name = options.trigger_service(selector.resolve().unwrap_or_default());
+    match node::list_services(&options.node) {
+        Ok(services) if services.contains(&service_name) => {
+            // Fails on a publisher with another payload version (see `sync_core::layout`)
+            report.result("trigger service", TriggerSubscriber::create_for(&node, &service_name).map(|_| format!("{} (publisher running)", service_name)));
+        }
+        Ok(_) if options.channel.is_some() => report.warn("trigger service", format!("{} does not exist yet, start the publisher with the same --channel", service_name)),
+        Ok(_) if service_name != trigger::TRIGGER_SERVICE => report.warn("trigger service", format!("{} does not exist yet, start the publisher with --cameras", service_name)),
+        Ok(_) => report.warn("trigger service", format!("{} does not exist yet, start the publisher", service_name)),
+        Err(e) => report.check("trigger service", false, e.to_string()),
+    }
+}
+
+// Paths and devices the run writes to; nothing is created
+fn check_outputs(report: &mut ReadinessReport, args: &Args, options: &Options) {
+    if let Some(dir) = &options.session_dir {
+        readiness::check_output_dir(report, "session dir", Path::new(dir));
+        if Path::new(dir).join(RECORDS_FILE).exists() {
+            report.warn("existing session", format!("{} already holds a session, its records are overwritten", dir));
+        }
+    }
+    if let Some(dir) = &options.runs_dir {
+        readiness::check_output_dir(report, "runs dir", Path::new(dir));
+    }
+    if let Some(path) = args.value("report") {
+        readiness::check_output_file(report, "batch report", Path::new(path));
+    }
+    match options.event_log.as_deref() {
+        Some(target @ "journald") => report.result("event log", EventLog::open(target, "v4l2_capture").map(|_| target.to_string())),
+        Some(path) => readiness::check_output_file(report, "event log", Path::new(path)),
+        None => {}
+    }
+    match options.trace.as_deref() {
+        Some(target @ ("ftrace" | "lttng")) => report.result("trace", Tracer::open(target).map(|_| target.to_string())),
+        Some(path) => readiness::check_output_file(report, "trace", Path::new(path)),
+        None => {}
+    }
+    if let Some(path) = &options.loopback {
+        if cfg!(target_os = "linux") {
+            report.result("loopback", OpenOptions::new().write(true).open(path).map(|_| path.clone()).map_err(|e| format!("{}: {}", path, e)));
+        } else {
+            report.check("loopback", false, "--loopback needs Linux (v4l2loopback)");
+        }
+    }
+    if let Some(port) = options.rtsp_port {
+        if cfg!(feature = "rtsp") {
+            report.result("rtsp port", std::net::TcpListener::bind(("0.0.0.0", port)).map(|_| format!("{} free", port)).map_err(|e| format!("port {}: {}", port, e)));
+        } else {
+            report.check("rtsp port", false, "--rtsp-port needs a build with the `rtsp` feature");
+        }
+    }
+}
@@ -0,0 +1,40 @@
+// Capture without a window, for embedded targets without a display.
+//
+// `--headless` (or the `capture_headless` binary, built without the preview
+// window) runs the same capture thread (`worker`) as the app, with capture
+// switched on from the start: the camera, trigger matching, recording and the
+// status lines on stdout. Nothing is painted, so no preview frames are handed
+// over and the display latency stays unmeasured. The capture thread ends with
+// exit code 1 when initialization or capture fails, and on SIGINT or SIGTERM
+// after flushing its outputs like a batch run (a `--duration` run still writes
+// its report and ends with its pass/fail code). The process exits with the
+// code of the joined thread.
+
+use std::process::ExitCode;
+use std::sync::atomic::Ordering;
+use sync_core::cli::Args;
+use sync_core::supervise;
+
+use crate::check;
+use crate::worker::{self, SWITCHES};
+
+/// Runs `v4l2_capture` without a window: `--check` or headless capture, until the capture thread ends.
+pub fn run_headless() -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let args = Args::from_env(SWITCHES);
+    if args.flag("check") {
+        check::run(&args)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    run(&args)
+}
+
+pub(crate) fn run(args: &Args) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    // The marker is flashed in the preview window
+    if args.value("glass-to-glass").is_some() {
+        return Err("--glass-to-glass needs the preview window, it does not work with --headless".into());
+    }
+    supervise::install_shutdown_handler();
+    let (worker, thread) = worker::spawn(None)?;
+    worker.running.store(true, Ordering::Relaxed);
+    Ok(worker::exit_code(thread.join()))
+}
@@ -0,0 +1,24 @@
+// Preview GUI of the camera capture app.
+//
+// The app opens a camera through `sync-capture`, receives triggers over
+// `sync-iceoryx2` and shows every matched frame together with the sync status,
+// latency breakdown and the glass-to-glass marker. Large frames are downscaled
+// for the preview only (`preview`). Capture and matching run on their own
+// thread (`worker`), which shares no locks with the UI thread; frame writing
+// and publication run on threads of their own (`sinks`). With `--check` the
+// app only checks its readiness and exits (`check`); with `--headless` it
+// captures without opening a window (`headless`). The window is the `window`
+// feature; without it only `run_headless` is built, and eframe is not needed.
+
+#[cfg(feature = "window")]
+mod app;
+mod check;
+mod headless;
+#[cfg(feature = "window")]
+mod preview;
+mod sinks;
+mod worker;
+
+#[cfg(feature = "window")]
+pub use app::{run, CameraApp};
+pub use headless::run_headless;
@@ -0,0 +1,127 @@
+// Preview downscaling before texture upload.
+//
+// Converting and uploading 4K frames at full size dominates the frame budget
+// of the GUI thread. The preview shrinks them by an integer factor with a box
+// filter (mean of each factor x factor block) while converting to `Color32`,
+// so every source pixel is read once. Matching, recordings and glass-to-glass
+// always see the full-resolution frame.
+//
+// The exposure and focus overlays work on the preview image as well: a
+// luminance histogram, and focus peaking that paints pixels on strong edges
+// (luma gradient above `FOCUS_PEAKING_THRESHOLD`) red.
+
+use eframe::egui::{self, Color32, ColorImage};
+
+/// Bins of the luminance histogram (4 luma levels each).
+pub const HISTOGRAM_BINS: usize = 64;
+/// Sum of the horizontal and vertical luma differences that counts as an in-focus edge.
+const FOCUS_PEAKING_THRESHOLD: u32 = 60;
+
+/// Integer decimation factor that brings `width` to at most `max_width` (0 = no limit).
+pub fn scale_factor(width: usize, max_width: usize) -> usize {
+    if max_width == 0 || width <= max_width {
+        1
+    } else {
+        width.div_ceil(max_width)
+    }
+}
+
+/// Converts a packed RGB frame to a preview image shrunk by `factor` in both directions.
+///
+/// Rows and columns that do not fill a whole block are dropped.
+pub fn to_color_image(rgb: &[u8], width: usize, height: usize, factor: usize) -> ColorImage {
+    let factor = factor.clamp(1, width.min(height).max(1));
+    let (preview_width, preview_height) = (width / factor, height / factor);
+    let pixels: Vec<Color32> = if factor == 1 {
+        rgb.chunks_exact(3).map(|pixel| Color32::from_rgb(pixel[0], pixel[1], pixel[2])).collect()
+    } else {
+        let area = (factor * factor) as u32;
+        let mut pixels = Vec::with_capacity(preview_width * preview_height);
+        let mut sums = vec![[0u32; 3]; preview_width];
+        for block_row in rgb.chunks_exact(width * 3 * factor).take(preview_height) {
+            sums.fill([0; 3]);
+            for row in block_row.chunks_exact(width * 3) {
+                for (sum, block) in sums.iter_mut().zip(row.chunks_exact(factor * 3)) {
+                    for pixel in block.chunks_exact(3) {
+                        sum[0] += pixel[0] as u32;
+                        sum[1] += pixel[1] as u32;
+                        sum[2] += pixel[2] as u32;
+                    }
+                }
+            }
+            pixels.extend(sums.iter().map(|sum| Color32::from_rgb((sum[0] / area) as u8, (sum[1] / area) as u8, (sum[2] / area) as u8)));
+        }
+        pixels
+    };
+    ColorImage {
+        size: [preview_width, preview_height],
+        pixels,
+        source_size: egui::Vec2::new(preview_width as f32, preview_height as f32),
+    }
+}
+
+fn luma(color: Color32) -> u32 {
+    (77 * color.r() as u32 + 150 * color.g() as u32 + 29 * color.b() as u32) >> 8
+}
+
+pub fn luma_histogram(image: &ColorImage) -> [u32; HISTOGRAM_BINS] {
+    let mut histogram = [0; HISTOGRAM_BINS];
+    for &pixel in &image.pixels {
+        histogram[luma(pixel) as usize * HISTOGRAM_BINS / 256] += 1;
+    }
+    histogram
+}
+
+/// Paints the pixels on sharp edges red.
+pub fn apply_focus_peaking(image: &mut ColorImage) {
+    let [width, height] = image.size;
+    let lumas: Vec<u32> = image.pixels.iter().map(|&pixel| luma(pixel)).collect();
+    for y in 0..height.saturating_sub(1) {
+        for x in 0..width.saturating_sub(1) {
+            let i = y * width + x;
+            let gradient = lumas[i].abs_diff(lumas[i + 1]) + lumas[i].abs_diff(lumas[i + width]);
+            if gradient > FOCUS_PEAKING_THRESHOLD {
+                image.pixels[i] = Color32::RED;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn large_frames_are_box_filtered_by_an_integer_factor() {
+        assert_eq!(scale_factor(3840, 960), 4);
+        assert_eq!(scale_factor(1000, 960), 2);
+        assert_eq!(scale_factor(640, 960), 1);
+        assert_eq!(scale_factor(3840, 0), 1);
+
+        // 5x4 pixels: the 2x2 blocks average, the fifth column is dropped
+        let rgb: Vec<u8> = (0..20u8).flat_map(|i| [i * 10, 0, 255]).collect();
+        let image = to_color_image(&rgb, 5, 4, 2);
+        assert_eq!(image.size, [2, 2]);
+        let reds: Vec<u8> = image.pixels.iter().map(|pixel| pixel.r()).collect();
+        assert_eq!(reds, [30, 50, 130, 150]);
+        assert!(image.pixels.iter().all(|pixel| pixel.b() == 255));
+        assert_eq!(to_color_image(&rgb, 5, 4, 1).pixels.len(), 20);
+    }
+
+    #[test]
+    fn overlays_see_the_luma_of_the_preview() {
+        let gray = |level: u8| Color32::from_rgb(level, level, level);
+        // Left half dark, right half bright: one sharp vertical edge
+        let mut image = ColorImage {
+            size: [4, 2],
+            pixels: [10, 10, 200, 200, 10, 10, 200, 200].into_iter().map(gray).collect(),
+            source_size: egui::Vec2::new(4.0, 2.0),
+        };
+        let histogram = luma_histogram(&image);
+        assert_eq!((histogram[2], histogram[50], histogram.iter().sum::<u32>()), (4, 4, 8));
+
+        apply_focus_peaking(&mut image);
+        let red: Vec<bool> = image.pixels.iter().map(|&pixel| pixel == Color32::RED).collect();
+        assert_eq!(red, [false, true, false, false, false, false, false, false]);
+    }
+}
@@ -0,0 +1,124 @@
+// Frame consumers that run off the capture thread.
+//
+// Writing a frame to the session and publishing it on iceoryx2 take
+// milliseconds for large frames, time the capture thread needs for the next
+// trigger and frame. Each of these consumers runs on its own thread and gets
+// the frames as shared `FrameBuffer`s (see `sync_capture::pool`), the same
+// buffers the preview shows, so the capture thread neither copies nor waits.
+// Queues are bounded: `offer` hands a frame back when its consumer is behind,
+// and the caller decides whether to handle it in place or to drop it; `push`
+// waits for room instead, for frames that must not be dropped (priority
+// triggers, see `sync_core::priority`).
+// Failures come back as messages for the capture thread's status output. The
+// frames queued or in progress count into the pipeline depth (see
+// `sync_core::depth`).
+// Dropping a sink finishes the queued frames before its thread exits.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+/// Frames queued for a consumer before `offer` hands them back.
+pub const QUEUE_CAPACITY: usize = 4;
+
+/// A consumer thread taking jobs of type `J` (frames and their metadata).
+pub struct FrameSink<J> {
+    name: String,
+    jobs: Option<SyncSender<J>>,
+    failures: Receiver<String>,
+    // Jobs queued or in progress
+    pending: Arc<AtomicUsize>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl<J: Send + 'static> FrameSink<J> {
+    /// Starts the thread `name`. `open` runs on it and returns the handler of the jobs, so
+    /// resources that must not leave their thread (iceoryx2 ports) are created there.
+    pub fn spawn<O, H>(name: &str, open: O) -> std::io::Result<Self>
+    where
+        O: FnOnce() -> Result<H, String> + Send + 'static,
+        H: FnMut(J) -> Result<(), String>,
+    {
+        let (jobs, queue) = mpsc::sync_channel::<J>(QUEUE_CAPACITY);
+        let (failed, failures) = mpsc::channel();
+        let pending = Arc::new(AtomicUsize::new(0));
+        let done = pending.clone();
+        let thread = std::thread::Builder::new().name(name.to_string()).spawn(move || {
+            let mut handle = match open() {
+                Ok(handle) => handle,
+                Err(e) => {
+                    let _ = failed.send(e);
+                    return;
+                }
+            };
+            for job in queue {
+                if let Err(e) = handle(job) {
+                    let _ = failed.send(e);
+                }
+                done.fetch_sub(1, Ordering::Relaxed);
+            }
+        })?;
+        Ok(Self {
+            name: name.to_string(),
+            jobs: Some(jobs),
+            failures,
+            pending,
+            thread: Some(thread),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Queues `job` without waiting; hands it back if the consumer is behind. Fails once the thread stopped.
+    pub fn offer(&self, job: J) -> Result<Option<J>, String> {
+        // Counted before the thread can finish it
+        self.pending.fetch_add(1, Ordering::Relaxed);
+        let sent = self.jobs.as_ref().map(|jobs| jobs.try_send(job));
+        if !matches!(sent, Some(Ok(()))) {
+            self.pending.fetch_sub(1, Ordering::Relaxed);
+        }
+        match sent {
+            Some(Ok(())) => Ok(None),
+            Some(Err(TrySendError::Full(job))) => Ok(Some(job)),
+            _ => Err(format!("{} thread stopped", self.name)),
+        }
+    }
+
+    /// Queues `job`, waiting for room if the consumer is behind. Fails once the thread stopped.
+    pub fn push(&self, job: J) -> Result<(), String> {
+        self.pending.fetch_add(1, Ordering::Relaxed);
+        if let Some(Ok(())) = self.jobs.as_ref().map(|jobs| jobs.send(job)) {
+            return Ok(());
+        }
+        self.pending.fetch_sub(1, Ordering::Relaxed);
+        Err(format!("{} thread stopped", self.name))
+    }
+
+    /// Jobs queued or in progress.
+    pub fn depth(&self) -> usize {
+        self.pending.load(Ordering::Relaxed)
+    }
+
+    /// Jobs the sink holds before `offer` hands them back: the queue and the one in progress.
+    pub fn capacity(&self) -> usize {
+        QUEUE_CAPACITY + 1
+    }
+
+    /// Failures since the last call.
+    pub fn failures(&self) -> Vec<String> {
+        self.failures.try_iter().collect()
+    }
+}
+
+impl<J> Drop for FrameSink<J> {
+    fn drop(&mut self) {
+        // Closing the queue ends the thread after the queued jobs
+        self.jobs = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
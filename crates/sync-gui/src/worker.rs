@@ -0,0 +1,2442 @@
+// Capture thread of the camera app.
+//
+// Everything timestamp-critical runs here: trigger reception, frame capture,
+// matching, recording and the status output. The UI thread (`app`) only
+// converts and paints. The two share no locks (see `sync_core::handoff`): the
+// worker publishes the latest frame and its reports through triple buffers
+// and takes the paint times back through a ring, so neither a slow repaint nor
+// a descheduled UI thread can delay a frame. The frames themselves are shared
+// buffers: the preview, the recorder and the frame publisher (`sinks`) hold
+// the captured buffer until they are done with it, no copies are made.
+
+#[cfg(feature = "window")]
+use eframe::egui;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use sync_capture::device::{self, CameraSelector};
+#[cfg(target_os = "linux")]
+use sync_capture::loopback::LoopbackOutput;
+#[cfg(feature = "rtsp")]
+use sync_capture::rtsp::RtspOutput;
+use sync_capture::test_pattern::{self, TestPatternBackend};
+use sync_capture::tuning::MemoryType;
+use sync_capture::{CaptureBackend, CapturedFrame, FrameBuffer, NokhwaBackend, PoolStats};
+use sync_core::batch::{self, BatchResults, BatchRun};
+use sync_core::burst::BurstPattern;
+use sync_core::canary;
+use sync_core::channel::TriggerChannel;
+use sync_core::cli::Args;
+use sync_core::clock::{self, ClockDomain, FrameTimestamp};
+use sync_core::clock_guard::{ClockEvent, ClockGuard, ClockGuardConfig};
+use sync_core::clock_quality::{self, DowngradePolicy};
+use sync_core::clockmap::ClockMapper;
+use sync_core::deadline::{DeadlineMonitor, MissedDeadline};
+use sync_core::degrade::{DegradationLadder, LadderConfig, Level, LevelChange};
+use sync_core::depth::{self, PipelineDepth, StageDepth};
+use sync_core::diagnosis::MatchDiagnostics;
+use sync_core::display_clock::{DisplayClock, DisplayClockConfig};
+use sync_core::drops::{DropCounters, TriggerGaps};
+use sync_core::encoder::EncoderTrack;
+use sync_core::eventlog::EventLog;
+use sync_core::feedback::{self, ReceiveFeedback};
+use sync_core::glass_to_glass::{self, GlassToGlass};
+use sync_core::handoff::{self, RingReceiver, RingSender, TripleReader, TripleWriter};
+use sync_core::intrinsics::CameraIntrinsics;
+use sync_core::latency::{FrameTiming, LatencyBreakdown};
+use sync_core::lighting::StrobeLog;
+use sync_core::manifest::{CameraEntry, SessionManifest};
+use sync_core::matcher_state::{self, MatcherState, SAVE_INTERVAL_NS};
+use sync_core::mcap_record::{McapRecording, RecordedMatch};
+use sync_core::namespace::NodeIdentity;
+use sync_core::pipeline::{self, MatchConfig, MatchPipeline, Queued, Stage};
+use sync_core::pose::PoseTrack;
+use sync_core::priority::{self, PriorityPattern};
+use sync_core::rate::TriggerRateEstimator;
+use sync_core::retention::{MatchQuality, RetentionAction, RetentionPolicy};
+use sync_core::retime::{FrameHeader, FLAG_MATCHED};
+use sync_core::runs::RunSummary;
+use sync_core::session::{self, MatchRecord, SessionRecorder};
+use sync_core::shared_stats::{self, ProcessStats, StatsPublisher};
+use sync_core::signing::{SignatureStatus, TriggerSignature, TriggerVerifier};
+use sync_core::skew::GroupLatency;
+use sync_core::stall::{FrameAction, Resumed, Stall, StallConfig, StallMonitor};
+use sync_core::stats::StatsSummary;
+use sync_core::status::{Status, StatusFormat};
+use sync_core::supervise;
+#[cfg(feature = "rtsp")]
+use sync_core::timecode::Timecode;
+use sync_core::timecode::TimecodeClock;
+use sync_core::timestamping::TimestampSource;
+use sync_core::trace::{TraceId, Tracer};
+use sync_core::ui_budget::{self, UiBudget, UiTiming};
+use sync_core::units::{DisplayMs, Nanos};
+use sync_core::CameraTrigger;
+use sync_iceoryx2::encoder::EncoderSubscriber;
+use sync_iceoryx2::feedback::FeedbackPublisher;
+use sync_iceoryx2::frames::{self, FramePublisher};
+use sync_iceoryx2::node::create_node;
+use sync_iceoryx2::photodiode::PhotodiodeSubscriber;
+use sync_iceoryx2::pose::PoseSubscriber;
+use sync_iceoryx2::skew::GroupLatencyPublisher;
+use sync_iceoryx2::strobe::StrobeSubscriber;
+use sync_iceoryx2::telemetry::TelemetryPublisher;
+use sync_iceoryx2::trigger::{self, TriggerSubscriber};
+
+use crate::sinks::FrameSink;
+
+/// Paint events the UI may queue while the worker is busy with a frame.
+const PAINTED_CAPACITY: usize = 64;
+
+/// Options that never take a value.
+pub(crate) const SWITCHES: &[&str] = &["histogram", "focus-peaking", "save-frames", "quarantine", "adaptive-tolerance", "stall-restart", "check", "shared-stats", "per-camera-trigger", "headless"];
+
+/// Wakes the UI thread when the worker has something new to show.
+pub trait Repaint: Send + 'static {
+    fn request_repaint(&self);
+    fn request_repaint_after(&self, delay: Duration);
+}
+
+#[cfg(feature = "window")]
+impl Repaint for egui::Context {
+    fn request_repaint(&self) {
+        egui::Context::request_repaint(self);
+    }
+
+    fn request_repaint_after(&self, delay: Duration) {
+        egui::Context::request_repaint_after(self, delay);
+    }
+}
+
+// Work for the MCAP recording thread (--record)
+enum RecordJob {
+    Trigger(CameraTrigger),
+    // The match, the frame's width and height, and its rgb8 pixels
+    Frame(RecordedMatch, u32, u32, FrameBuffer),
+    // Trigger time and latency stages (`LatencyBreakdown::snapshot`)
+    Latency(u64, Vec<(&'static str, StatsSummary)>),
+}
+
+/// Minimum time between repaints while capturing; frames in between are processed but not shown.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RepaintThrottle {
+    pub(crate) interval: Duration,
+    next: Instant,
+}
+
+impl RepaintThrottle {
+    /// Preview repaint rate `--preview-fps`, independent of the capture rate (0 repaints after every frame).
+    pub(crate) fn from_args(args: &Args) -> Self {
+        let preview_fps = args.value_as::<f64>("preview-fps").unwrap_or(30.0);
+        Self {
+            interval: if preview_fps > 0.0 { Duration::from_secs_f64(1.0 / preview_fps) } else { Duration::ZERO },
+            next: Instant::now(),
+        }
+    }
+
+    // Time left until the next repaint is due, zero to repaint now; `every_frame` ignores the rate
+    fn delay(&mut self, now: Instant, every_frame: bool) -> Duration {
+        if every_frame || now >= self.next {
+            self.next = now + self.interval;
+            Duration::ZERO
+        } else {
+            self.next - now
+        }
+    }
+}
+
+/// A matched frame against its trigger. The frame's time is the one it was matched on
+/// (`--frame-timestamp`); the V4L2 delay is always the dequeue time after the publish.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct FrameLatency {
+    trigger_type: &'static str,
+    total_latency_ms: f64,
+    v4l2_delay_ms: f64,
+}
+
+impl FrameLatency {
+    fn of(trigger: &CameraTrigger, frame_ns: u64, dequeue_ns: u64) -> Self {
+        Self {
+            trigger_type: if trigger.hw_timestamp_ns < frame_ns { "PAST" } else { "FUTURE" },
+            total_latency_ms: (frame_ns as i64 - trigger.hw_timestamp_ns as i64) as f64 / 1_000_000.0,
+            v4l2_delay_ms: (dequeue_ns as i64 - trigger.publish_timestamp_ns as i64) as f64 / 1_000_000.0,
+        }
+    }
+}
+
+/// Latest processed frame: (frame, matched hw_ts, dequeue time).
+pub type Preview = (CapturedFrame, Option<u64>, u64);
+
+/// What the UI shows besides the frame, published after every frame.
+#[derive(Debug, Clone, Default)]
+pub struct Reports {
+    pub sync_info: String,
+    pub stream_info: String,
+    pub camera: String,
+    pub camera_index: u32,
+    pub camera_fps: u32,
+    pub output_fps: u32,
+    // Observed trigger rate, and a warning while it is off the camera fps
+    pub rate_info: String,
+    pub rate_warning: Option<String>,
+    pub latency_report: String,
+    pub diagnosis_report: String,
+    pub drops_report: String,
+    // Frames the retention policy kept out of the session
+    pub retention_report: String,
+    pub deadline_report: String,
+    pub depth_report: String,
+    pub degradation_report: String,
+    pub glass_to_glass_report: String,
+    // Time the preview takes per frame, and a warning while it eats into the frame budget
+    pub ui_report: String,
+    pub ui_warning: Option<String>,
+    pub display_clock_report: String,
+    /// Glass-to-glass mode: whether the marker is white right now.
+    pub marker_lit: Option<bool>,
+}
+
+/// Sent by the UI after every paint.
+#[derive(Debug, Clone, Copy)]
+pub struct Painted {
+    pub painted_ns: u64,
+    /// A preview frame shown for the first time: (matched hw_ts, dequeue time).
+    pub displayed: Option<(Option<u64>, u64)>,
+    /// The glass-to-glass marker was painted white.
+    pub marker_lit: bool,
+    /// UI thread times of a paint that showed a new frame.
+    pub ui: Option<UiTiming>,
+}
+
+/// UI end of the capture thread.
+// Without a window only `running` is used
+#[cfg_attr(not(feature = "window"), allow(dead_code))]
+pub struct WorkerHandle {
+    /// Capture on/off, set by the UI button and by the worker when capture fails.
+    pub running: Arc<AtomicBool>,
+    pub preview: TripleReader<Option<Preview>>,
+    pub reports: TripleReader<Reports>,
+    pub painted: RingSender<Painted>,
+}
+
+// Startup options that are only needed while initializing
+pub(crate) struct Options {
+    pub node: NodeIdentity,
+    pub verify_key: Option<String>,
+    pub session_dir: Option<String>,
+    pub save_frames: bool,
+    pub retention: Option<RetentionPolicy>,
+    pub calibration_files: Vec<String>,
+    pub camera_info: Option<String>,
+    pub match_stages: Option<String>,
+    pub match_config: MatchConfig,
+    pub adaptive_tolerance: bool,
+    pub degradation: LadderConfig,
+    pub consumer_name: Option<String>,
+    pub per_camera_trigger: bool,
+    pub trigger_camera: Option<u32>,
+    pub camera_group: Option<String>,
+    pub channel: Option<TriggerChannel>,
+    pub backend: String,
+    pub gst_pipeline: Option<String>,
+    pub v4l2_buffers: Option<u32>,
+    pub v4l2_memory: Option<MemoryType>,
+    pub frame_timestamp: FrameTimestamp,
+    pub target_latency_ms: Option<f64>,
+    pub pattern_delay_ms: f64,
+    pub pattern_jitter_ms: f64,
+    pub loopback: Option<String>,
+    pub publish_frames: Option<String>,
+    pub rtsp_port: Option<u16>,
+    pub rtsp_mount: String,
+    pub glass_to_glass_interval: Option<u64>,
+    pub display_clock: Option<DisplayClockConfig>,
+    pub runs_dir: Option<String>,
+    pub record: Option<PathBuf>,
+    pub shared_stats_dir: Option<PathBuf>,
+    pub event_log: Option<String>,
+    pub trace: Option<String>,
+}
+
+impl Options {
+    /// Parses the same arguments as the UI; `camera` is the selector as given.
+    pub(crate) fn from_args(args: &Args, camera: &str) -> Result<Self, String> {
+        // iceoryx2 node `<rig>/v4l2_capture/<camera>` (`--rig-id`, `--node-name`, see `sync_core::namespace`)
+        let node = NodeIdentity::from_args(args, "v4l2_capture", Some(camera))?;
+        if args.value("channel").is_some() && (args.flag("per-camera-trigger") || args.value("trigger-camera").is_some()) {
+            return Err("--channel and --per-camera-trigger select different trigger services".to_string());
+        }
+        Ok(Self {
+            node,
+            verify_key: args.value("verify-key").map(str::to_string),
+            session_dir: args.value("session-dir").map(str::to_string),
+            // Keep the image of every synchronized frame in the session (for dataset export)
+            save_frames: args.flag("save-frames"),
+            // Only frames of this match confidence or above, the others discarded or quarantined (see `sync_core::retention`)
+            retention: RetentionPolicy::from_args(args)?,
+            calibration_files: args.values("calibration").into_iter().map(str::to_string).collect(),
+            // Intrinsics (ROS camera_calibration YAML), recorded and published with the session
+            camera_info: args.value("camera-info").map(str::to_string),
+            // Matcher chain, e.g. `dedupe,drift,associate,audit` (see `sync_core::pipeline`)
+            match_stages: args.value("match-stages").map(str::to_string),
+            // `--match-tolerance-ms`, `--future-penalty` and `--max-pending-triggers`
+            match_config: MatchConfig::from_args(args)?,
+            // Narrow the match tolerance to the learned latency
+            adaptive_tolerance: args.flag("adaptive-tolerance"),
+            // `--max-degradation`, `--degrade-after` and `--recover-after` (see `sync_core::degrade`)
+            degradation: LadderConfig::from_args(args)?,
+            // Name receive times are reported under, `camera <selector>` by default
+            consumer_name: args.value("consumer-name").map(str::to_string),
+            // Own trigger stream of a multi-camera publisher (`--cameras`), the camera index's or `--trigger-camera`'s
+            per_camera_trigger: args.flag("per-camera-trigger"),
+            trigger_camera: args.value_as("trigger-camera"),
+            // Cameras whose latencies the publisher compares (see `sync_core::skew`)
+            camera_group: args.value("camera-group").map(str::to_string),
+            // Independent trigger channel of the publisher this camera's group runs on (see `sync_core::channel`)
+            channel: args.value("channel").map(str::parse).transpose()?,
+            // Capture backend: nokhwa (portable), v4l2 (Linux, tunable buffer queue), gstreamer (any pipeline) or pattern (synthetic)
+            backend: args.value("backend").unwrap_or("nokhwa").to_string(),
+            // Pipeline of the gstreamer backend without its sink, `{device}` is the camera's /dev/videoN
+            gst_pipeline: args.value("gst-pipeline").map(str::to_string),
+            v4l2_buffers: args.value("v4l2-buffers").filter(|v| *v != "auto").and_then(|v| v.parse().ok()),
+            v4l2_memory: args.value("v4l2-memory").filter(|v| *v != "auto").and_then(|v| v.parse().ok()),
+            // Frames matched on the driver's buffer timestamp where the backend has one, or on the dequeue time
+            frame_timestamp: args.value("frame-timestamp").map_or(Ok(FrameTimestamp::default()), str::parse)?,
+            target_latency_ms: args.value_as("target-latency-ms"),
+            // Simulated pipeline delay of the test-pattern camera, after the trigger's hardware timestamp
+            pattern_delay_ms: args.value_as("pattern-delay-ms").unwrap_or(5.0),
+            pattern_jitter_ms: args.value_as("pattern-jitter-ms").unwrap_or(0.0),
+            // v4l2loopback device that gets every matched frame, stamped with its trigger time
+            loopback: args.value("loopback").map(str::to_string),
+            // iceoryx2 frame service that gets every matched frame with its trigger, `auto` for the camera's own (see `sync_iceoryx2::frames`)
+            publish_frames: args.value("publish-frames").map(str::to_string),
+            // RTSP stream of the matched frames with trigger timecodes, for remote monitoring
+            rtsp_port: args.value_as("rtsp-port"),
+            rtsp_mount: args.value("rtsp-mount").unwrap_or("/sync").to_string(),
+            // Flash a marker every N triggers and measure glass-to-glass latency
+            glass_to_glass_interval: args.value_as("glass-to-glass"),
+            // Read the time of a display clock in the scene and compare it with the matched trigger (see `sync_core::display_clock`)
+            display_clock: DisplayClockConfig::from_args(args)?,
+            runs_dir: args.value("runs-dir").map(str::to_string),
+            // MCAP file of the triggers, matched frames and latency, for Foxglove (see `sync_core::mcap_record`)
+            record: args.value("record").map(PathBuf::from),
+            // Counters and latency in a memory-mapped file for local tools (`--shared-stats`, see `sync_core::shared_stats`)
+            shared_stats_dir: shared_stats::dir_from_args(args),
+            // Sync events with monotonic timestamps for correlation with dmesg/journald
+            event_log: args.value("event-log").map(str::to_string),
+            // Tracepoints for perfetto/LTTng timelines (`ftrace`, `lttng` or a file)
+            trace: args.value("trace").map(str::to_string),
+        })
+    }
+
+    /// `Camera/Sync`, the service of `--channel`, or with `--per-camera-trigger` the one of camera `camera_index`.
+    pub(crate) fn trigger_service(&self, camera_index: u32) -> String {
+        if let Some(index) = self.trigger_camera.or(self.per_camera_trigger.then_some(camera_index)) {
+            return trigger::camera_service(index);
+        }
+        match &self.channel {
+            Some(channel) => trigger::channel_service(&channel.name),
+            None => trigger::TRIGGER_SERVICE.to_string(),
+        }
+    }
+
+    /// Frame stream of `--publish-frames`: the service given, or with `auto` the one of the camera whose triggers are matched.
+    pub(crate) fn frames_service(&self, camera_index: u32) -> Option<String> {
+        let value = self.publish_frames.as_deref()?;
+        Some(frames::publish_service(value, camera_index, self.trigger_camera))
+    }
+}
+
+/// Starts the capture thread; it repaints `window` when a new frame is ready.
+///
+/// Without a window the thread runs headless (see `headless`). It returns the
+/// exit code of the process once it ends: after a `--duration` run, and
+/// without a window on an error or on SIGINT or SIGTERM.
+pub fn spawn(window: Option<Box<dyn Repaint>>) -> std::io::Result<(WorkerHandle, JoinHandle<i32>)> {
+    let running = Arc::new(AtomicBool::new(false));
+    let (preview_writer, preview) = handoff::triple_buffer(None);
+    let (reports_writer, reports) = handoff::triple_buffer(Reports {
+        sync_info: "Initializing...".to_string(),
+        ..Default::default()
+    });
+    let (painted, painted_receiver) = handoff::ring(PAINTED_CAPACITY);
+    let worker_running = running.clone();
+    // The iceoryx2 ports and the camera are created on the capture thread and never leave it
+    let thread = std::thread::Builder::new().name("capture".to_string()).spawn(move || {
+        let (worker, options) = CaptureWorker::new(window, worker_running, preview_writer, reports_writer, painted_receiver);
+        worker.run(options)
+    })?;
+    let worker = WorkerHandle {
+        running,
+        preview,
+        reports,
+        painted,
+    };
+    Ok((worker, thread))
+}
+
+/// Exit code of the process from the result of joining the capture thread; a panic is a failure.
+pub fn exit_code(joined: std::thread::Result<i32>) -> ExitCode {
+    match joined {
+        Ok(code) => u8::try_from(code).map_or(ExitCode::FAILURE, ExitCode::from),
+        Err(_) => ExitCode::FAILURE,
+    }
+}
+
+// A frame image to store: (session or quarantine dir, trigger_id, width, height, rgb)
+type FrameJob = (PathBuf, u64, u32, u32, FrameBuffer);
+
+struct CaptureWorker {
+    camera: Option<Box<dyn CaptureBackend>>,
+    subscriber: Option<TriggerSubscriber>,
+    // Reports receive times of canary and sampled triggers under its name (see `sync_core::feedback`)
+    feedback: Option<(FeedbackPublisher, String)>,
+    // Reports the latency of sampled frames under the same name to its group: (publisher, group, name)
+    group_latency: Option<(GroupLatencyPublisher, String, String)>,
+    verifier: Option<TriggerVerifier>,
+    recorder: Option<SessionRecorder>,
+    // Frame images written and matched frames published off the capture thread
+    frame_writer: Option<FrameSink<FrameJob>>,
+    frame_publisher: Option<FrameSink<(FrameHeader, FrameBuffer)>>,
+    unpublished_frames: u64,
+    // MCAP recording (--record), written off the capture thread, and the messages dropped while it was behind
+    mcap: Option<FrameSink<RecordJob>>,
+    unrecorded_messages: u64,
+    // Frame buffers allocated by the capture backend's pool when last reported
+    pool_allocated: u64,
+    #[cfg(target_os = "linux")]
+    loopback: Option<LoopbackOutput>,
+    #[cfg(feature = "rtsp")]
+    rtsp: Option<RtspOutput>,
+    // Summary statistics of this run, kept per config hash in the runs directory
+    run_summary: Option<(RunSummary, PathBuf)>,
+    processed_frames: u64,
+    received_triggers: u64,
+    dropped_triggers: u64,
+    // Test-pattern camera: every matched frame is checked against the trigger it encodes
+    pattern_check: bool,
+    pattern_mismatches: u64,
+    // Fixed-duration run with a final report and pass/fail exit code (`--duration 60s`)
+    batch: Option<BatchRun>,
+    telemetry: Option<TelemetryPublisher>,
+    // Memory-mapped stats snapshot for local tools, and when this process started (CLOCK_REALTIME)
+    shared_stats: Option<StatsPublisher>,
+    started_ns: u64,
+    // Pending triggers and the matcher stages the frames run through (--match-stages)
+    pending_triggers: MatchPipeline<TriggerSignature>,
+    // Learned matcher state of this camera, saved for a warm restart (--matcher-state) and when it was last saved
+    matcher_state: Option<PathBuf>,
+    matcher_saved_ns: u64,
+    // Burst pattern of the publisher, the following pulses of a burst are matched in order
+    burst: BurstPattern,
+    // High priority triggers, as configured on the publisher (--priority-every)
+    priority: Option<PriorityPattern>,
+    // Lighting state per trigger, recorded with each matched frame
+    strobe: Option<StrobeSubscriber>,
+    strobe_log: StrobeLog,
+    // Pan-tilt poses, interpolated at the trigger timestamp of each matched frame
+    pose: Option<PoseSubscriber>,
+    pose_track: PoseTrack,
+    // Encoder position, interpolated at the trigger timestamp of each matched frame
+    encoder: Option<EncoderSubscriber>,
+    encoder_track: EncoderTrack,
+    // SMPTE timecode of each matched frame, recorded in its metadata (--timecode-rate)
+    timecode: Option<TimecodeClock>,
+    latency: LatencyBreakdown,
+    // CLOCK_REALTIME against CLOCK_MONOTONIC and the trigger timestamps against their publish times,
+    // with the frames matched while one of them was disturbed (see `sync_core::clock_guard`)
+    realtime_guard: ClockGuard,
+    trigger_guard: ClockGuard,
+    disturbed_matches: u64,
+    trigger_rate: TriggerRateEstimator,
+    // Offset of the local clock from the publisher's, recorded for multi-host merges
+    clock_mapper: ClockMapper,
+    // Clock domain of the received triggers, reported when it changes
+    published_domain: Option<u8>,
+    // What the publisher asks for while its trigger clock is downgraded, and the frames left unmatched under `pause`
+    clock_policy: Option<DowngradePolicy>,
+    paused_frames: u64,
+    rate_tolerance_pct: f64,
+    diagnostics: MatchDiagnostics,
+    // Driver-level drop indicators, correlated with unmatched frames
+    drops: DropCounters,
+    // Triggers missing from the received ids, and frames lost between matches
+    trigger_gaps: TriggerGaps,
+    // Frames missing while triggers arrive, recovered by reopening the stream (--stall-restart)
+    stall: StallMonitor,
+    // Anomalies exported for correlation with kernel logs (--event-log)
+    event_log: EventLog,
+    tracer: Tracer,
+    status: Status,
+    // Soft deadlines of frame processing and matching, with the misses so far
+    loop_deadline: DeadlineMonitor,
+    match_deadline: DeadlineMonitor,
+    // Frames in flight from the driver queue to the last consumer (--max-pipeline-depth)
+    depth: PipelineDepth,
+    // `--frame-timestamp dequeue`: frames matched on the dequeue time even where the driver stamps them
+    frame_timestamp: FrameTimestamp,
+    // Buffer timestamp of the last frame the driver stamped, to notice one going backwards
+    previous_driver_ns: Option<u64>,
+    // Work given up under overload, driven by the depth and deadlines, and whether a deadline was missed since the last frame
+    degradation: DegradationLadder,
+    deadline_missed: bool,
+    glass_to_glass: Option<GlassToGlass>,
+    // Conversion, upload and paint times of the preview against the frame interval (--ui-budget-pct)
+    ui_budget: UiBudget,
+    photodiode: Option<PhotodiodeSubscriber>,
+    display_clock: Option<DisplayClock>,
+    trigger_count: u32,
+    skip_ratio: u32,
+    output_fps: u32,
+    camera_fps: u32,
+    camera_selector: CameraSelector,
+    camera_index: u32,
+    width: u32,
+    height: u32,
+    // Shared with the UI thread, see the module comment
+    running: Arc<AtomicBool>,
+    reports: Reports,
+    reports_writer: TripleWriter<Reports>,
+    preview: TripleWriter<Option<Preview>>,
+    painted: RingReceiver<Painted>,
+    // None without a window (`--headless`): nothing is painted, errors and SIGINT/SIGTERM end the capture thread (see `headless`)
+    window: Option<Box<dyn Repaint>>,
+    repaint: RepaintThrottle,
+}
+
+impl CaptureWorker {
+    // Parses the same arguments as the UI; the options are used by `initialize`
+    fn new(
+        window: Option<Box<dyn Repaint>>,
+        running: Arc<AtomicBool>,
+        preview: TripleWriter<Option<Preview>>,
+        reports_writer: TripleWriter<Reports>,
+        painted: RingReceiver<Painted>,
+    ) -> (Self, Result<Options, String>) {
+        // Parse arguments: v4l2_capture [camera] [output_fps] [width] [height]
+        // The camera is an index or a stable identity (`serial:ABC123`, `bus:1-2.3`), also settable as `camera = ...` in --config
+        let args = Args::from_env(SWITCHES);
+        let camera = args.value("camera").map(str::to_string).or_else(|| args.positional::<String>(0));
+        let camera_selector = camera.as_deref().unwrap_or("0").parse::<CameraSelector>();
+        // An unknown --status-format fails the initialization; the error is still reported in text
+        let status = Status::from_args(&args, "v4l2_capture");
+        // So does an invalid --duration
+        let (batch, batch_checked) = match BatchRun::from_args(&args, "v4l2_capture") {
+            Ok(batch) => (batch, Ok(())),
+            Err(e) => (None, Err(e)),
+        };
+        let output_fps = args.positional::<u32>(1).unwrap_or(30);
+        let width = args.positional::<u32>(2).unwrap_or(640);
+        let height = args.positional::<u32>(3).unwrap_or(480);
+        let options = Options::from_args(&args, camera.as_deref().unwrap_or("0"));
+
+        let repaint = RepaintThrottle::from_args(&args);
+
+        // Known sensor exposure time splits exposure from readout in the latency breakdown
+        let exposure_ns = args.value_as::<u64>("exposure-us").map(|us| us * 1_000);
+
+        // Warn when the observed trigger rate is off the camera fps by more than this
+        let rate_tolerance_pct = args.value_as::<f64>("rate-tolerance-pct").unwrap_or(10.0);
+
+        // Encoder ticks per unit of travel (e.g. per mm), frames are tagged with the position in units
+        let encoder_ticks_per_unit = args.value_as::<f64>("encoder-ticks-per-unit").unwrap_or(1.0);
+
+        // Timecode rate and timescale, e.g. `--timecode-rate 29.97 --timecode-epoch tai`
+        let timecode = TimecodeClock::from_args(&args);
+
+        // Steps of the clocks beyond `--clock-max-step-ms` disturb them for `--clock-settle-ms`
+        let clock_guard_config = ClockGuardConfig::from_args(&args);
+
+        // Pulses per logical capture, and the triggers shed last under overload (see `sync_core::priority`)
+        let burst = BurstPattern::new(args.value_as::<u32>("burst-size").unwrap_or(1));
+        let priority = PriorityPattern::from_args(&args, burst);
+
+        // Calculate frame skip ratio
+        let input_fps = args.value_as::<u32>("camera-fps").unwrap_or(30);
+
+        // Soft deadlines: frame processing within one frame interval, matching within 1ms
+        let loop_deadline_ms = args.value_as::<f64>("loop-deadline-ms").unwrap_or(1000.0 / input_fps.max(1) as f64);
+        let match_deadline_ms = args.value_as::<f64>("match-deadline-ms").unwrap_or(1.0);
+        let skip_ratio = if output_fps >= input_fps {
+            1
+        } else {
+            (input_fps as f32 / output_fps as f32).round() as u32
+        };
+
+        let worker = Self {
+            camera: None,
+            subscriber: None,
+            feedback: None,
+            group_latency: None,
+            verifier: None,
+            recorder: None,
+            frame_writer: None,
+            frame_publisher: None,
+            unpublished_frames: 0,
+            mcap: None,
+            unrecorded_messages: 0,
+            pool_allocated: 0,
+            #[cfg(target_os = "linux")]
+            loopback: None,
+            #[cfg(feature = "rtsp")]
+            rtsp: None,
+            run_summary: None,
+            processed_frames: 0,
+            received_triggers: 0,
+            dropped_triggers: 0,
+            pattern_check: false,
+            pattern_mismatches: 0,
+            // Fixed-duration runs capture right away and end by themselves
+            batch,
+            telemetry: None,
+            shared_stats: None,
+            started_ns: clock::realtime_now_ns(),
+            pending_triggers: MatchPipeline::default(),
+            matcher_state: args.value("matcher-state").map(PathBuf::from),
+            matcher_saved_ns: 0,
+            burst,
+            priority,
+            strobe: None,
+            strobe_log: StrobeLog::default(),
+            pose: None,
+            pose_track: PoseTrack::default(),
+            encoder: None,
+            encoder_track: EncoderTrack::new(encoder_ticks_per_unit),
+            timecode: timecode.as_ref().ok().copied().flatten(),
+            latency: LatencyBreakdown::new(exposure_ns),
+            realtime_guard: ClockGuard::new("realtime", clock_guard_config),
+            trigger_guard: ClockGuard::new("trigger", clock_guard_config),
+            disturbed_matches: 0,
+            trigger_rate: TriggerRateEstimator::default(),
+            clock_mapper: ClockMapper::default(),
+            published_domain: None,
+            clock_policy: None,
+            paused_frames: 0,
+            rate_tolerance_pct,
+            diagnostics: MatchDiagnostics::default(),
+            drops: DropCounters::default(),
+            trigger_gaps: TriggerGaps::new(1, skip_ratio as u64),
+            stall: StallMonitor::new(StallConfig::from_args(&args), input_fps),
+            event_log: EventLog::default(),
+            tracer: Tracer::default(),
+            // Human text or line-delimited JSON with stable keys (`--status-format json`)
+            status: status.clone().unwrap_or_else(|_| Status::new(StatusFormat::Text, "v4l2_capture")),
+            loop_deadline: DeadlineMonitor::new("frame processing", loop_deadline_ms),
+            match_deadline: DeadlineMonitor::new("matching", match_deadline_ms),
+            depth: PipelineDepth::from_args(&args),
+            frame_timestamp: FrameTimestamp::default(),
+            previous_driver_ns: None,
+            degradation: DegradationLadder::default(),
+            deadline_missed: false,
+            glass_to_glass: None,
+            ui_budget: UiBudget::new(input_fps, args.value_as("ui-budget-pct").unwrap_or(ui_budget::DEFAULT_WARN_PCT)),
+            photodiode: None,
+            display_clock: None,
+            trigger_count: 0,
+            skip_ratio,
+            output_fps,
+            camera_fps: input_fps,
+            camera_selector: camera_selector.clone().unwrap_or_default(),
+            camera_index: 0,
+            width,
+            height,
+            running,
+            reports: Reports::default(),
+            reports_writer,
+            preview,
+            painted,
+            window,
+            repaint,
+        };
+        let options = status.and(batch_checked).and(camera_selector.map_err(|e| e.to_string())).and(timecode).and(options);
+        (worker, options)
+    }
+
+    // Initializes, then captures while the UI has capture switched on; returns the exit code once the thread ends
+    fn run(mut self, options: Result<Options, String>) -> i32 {
+        let code = self.capture(options);
+        // The window closes when it sees the thread has ended
+        self.repaint_now();
+        code
+    }
+
+    fn capture(&mut self, options: Result<Options, String>) -> i32 {
+        // Kept for reopening the camera after a stall
+        let options = options.and_then(|options| self.initialize(&options).map(|()| options).map_err(|e| e.to_string()));
+        if let Err(e) = &options {
+            self.reports.sync_info = format!("Initialization error: {}", e);
+            if self.window.is_none() {
+                eprintln!("Error: {}", e);
+                return 1;
+            }
+        }
+        self.reports.camera = self.camera_selector.to_string();
+        self.reports.camera_index = self.camera_index;
+        self.reports.camera_fps = self.camera_fps;
+        self.reports.output_fps = self.output_fps;
+        if self.batch.is_some() {
+            self.running.store(true, Ordering::Relaxed);
+        }
+        self.publish_reports();
+        self.repaint_now();
+
+        let mut capturing = false;
+        loop {
+            self.receive_painted();
+            if self.batch.as_ref().is_some_and(BatchRun::is_over) {
+                if let Some(code) = self.finish_batch() {
+                    return code;
+                }
+            }
+            if self.window.is_none() && supervise::shutdown_requested() {
+                return self.finish_headless();
+            }
+            // Keep the learned state for a warm restart: periodically and when capture stops
+            let now_ns = clock::realtime_now_ns();
+            if capturing && (!self.running.load(Ordering::Relaxed) || now_ns.saturating_sub(self.matcher_saved_ns) >= SAVE_INTERVAL_NS) {
+                self.save_matcher_state(now_ns);
+            }
+            let was_capturing = capturing;
+            capturing = self.running.load(Ordering::Relaxed) && self.camera.is_some();
+            // Time with capture switched off is no stall
+            if capturing && !was_capturing {
+                self.stall.start();
+            }
+            if capturing {
+                let result = self
+                    .capture_frame()
+                    .and_then(|()| match self.loop_deadline.finish() {
+                        Some(missed) => self.report_missed_deadline(missed),
+                        None => Ok(()),
+                    })
+                    .and_then(|()| match &options {
+                        Ok(options) => self.check_stall(options),
+                        Err(_) => Ok(()),
+                    });
+                if let Err(e) = result {
+                    if self.window.is_none() {
+                        eprintln!("Error: capture failed: {}", e);
+                        self.flush_outputs();
+                        return 1;
+                    }
+                    self.reports.sync_info = format!("Capture error: {}", e);
+                    self.running.store(false, Ordering::Relaxed);
+                    self.repaint_now();
+                }
+            } else {
+                // Stopped: keep taking paint events and serving the glass-to-glass marker
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            self.publish_reports();
+            self.publish_stats();
+        }
+    }
+
+    fn publish_reports(&mut self) {
+        if let Some(glass_to_glass) = &self.glass_to_glass {
+            self.reports.marker_lit = Some(glass_to_glass.marker_lit(clock::realtime_now_ns()));
+        }
+        self.reports_writer.publish(self.reports.clone());
+    }
+
+    // What frames are matched on: the buffer timestamp if the backend has one and `--frame-timestamp` allows it
+    fn frame_timestamp(configured: FrameTimestamp, camera: &dyn CaptureBackend) -> FrameTimestamp {
+        if camera.match_on_capture_time() {
+            configured
+        } else {
+            FrameTimestamp::Dequeue
+        }
+    }
+
+    // Rewrites the stats snapshot when due
+    fn publish_stats(&mut self) {
+        let Some(mut shared_stats) = self.shared_stats.take() else {
+            return;
+        };
+        shared_stats.update(clock::monotonic_now_ns(), || ProcessStats {
+            pending_triggers: self.pending_triggers.pending() as u64,
+            ..ProcessStats::new(self.started_ns, clock::realtime_now_ns(), &self.results(), &self.latency)
+        });
+        self.shared_stats = Some(shared_stats);
+    }
+
+    // Repaints at the preview rate, after every frame in glass-to-glass mode
+    fn request_repaint(&mut self) {
+        let Some(window) = &self.window else {
+            return;
+        };
+        match self.repaint.delay(Instant::now(), self.glass_to_glass.is_some()) {
+            Duration::ZERO => window.request_repaint(),
+            delay => window.request_repaint_after(delay),
+        }
+    }
+
+    fn repaint_now(&self) {
+        if let Some(window) = &self.window {
+            window.request_repaint();
+        }
+    }
+
+    // Paint times from the UI thread: display latency, the UI budget and the glass-to-glass loop
+    fn receive_painted(&mut self) {
+        while let Some(painted) = self.painted.try_recv() {
+            if let Some((hw_ts, dequeue_ns)) = painted.displayed {
+                self.latency.record_display(hw_ts, dequeue_ns, painted.painted_ns);
+            }
+            if let Some(timing) = painted.ui {
+                self.ui_budget.record(timing, painted.painted_ns);
+            }
+            if let Some(glass_to_glass) = &mut self.glass_to_glass {
+                // A paint that still showed the dark marker does not count as the flash
+                if painted.marker_lit || !glass_to_glass.marker_lit(painted.painted_ns) {
+                    glass_to_glass.on_painted(painted.painted_ns);
+                }
+                self.reports.glass_to_glass_report = glass_to_glass.report(self.status.display_unit());
+            }
+        }
+        if let (Some(glass_to_glass), Some(photodiode)) = (&mut self.glass_to_glass, &self.photodiode) {
+            while let Ok(Some(edge)) = photodiode.receive() {
+                glass_to_glass.on_light_edge(edge);
+            }
+        }
+    }
+
+    fn initialize(&mut self, options: &Options) -> Result<(), Box<dyn std::error::Error>> {
+        self.reports.sync_info = format!("Initializing camera {} and Iceoryx2 sync...", self.camera_selector);
+        let stages = match &options.match_stages {
+            Some(list) => pipeline::parse_stages(list)?,
+            None => vec![Stage::Associate],
+        };
+        self.pending_triggers = MatchPipeline::with_stages(&stages, options.match_config.tolerance_ms, self.burst);
+        self.pending_triggers.configure(&options.match_config);
+        self.pending_triggers.set_adaptive_tolerance(options.adaptive_tolerance);
+        self.pending_triggers.set_priority(self.priority);
+        self.restore_matcher_state()?;
+        // Shedding keeps the priority triggers' frames, without them there is nothing to keep
+        let mut degradation = options.degradation;
+        if self.priority.is_none() {
+            degradation.max = degradation.max.min(Level::Skip);
+        }
+        self.degradation = DegradationLadder::new(degradation);
+
+        // Resolve a stable camera identity to the current /dev/videoN node
+        self.camera_index = self.camera_selector.resolve()?;
+        self.status.line(
+            "camera",
+            format_args!("Camera {} resolved to /dev/video{}", self.camera_selector, self.camera_index),
+            &[("camera", self.camera_selector.to_string().into()), ("index", self.camera_index.into())],
+        );
+
+        if let Some(path) = &options.verify_key {
+            self.verifier = Some(TriggerVerifier::from_key_file(Path::new(path))?);
+        }
+        if let Some(dir) = &options.session_dir {
+            let recorder = SessionRecorder::create(Path::new(dir))?;
+            let recorder = if options.save_frames { recorder.with_frames()? } else { recorder };
+            self.recorder = Some(match options.retention {
+                Some(policy) => recorder.with_retention(policy)?,
+                None => recorder,
+            });
+        }
+        if self.recorder.as_ref().is_some_and(SessionRecorder::saves_frames) {
+            // Frames go to the session or its quarantine
+            self.frame_writer = Some(FrameSink::spawn("recorder", move || {
+                Ok(move |(dir, trigger_id, width, height, rgb): FrameJob| {
+                    session::write_frame(&dir, trigger_id, width, height, &rgb).map_err(|e| format!("frame of trigger id={}: {}", trigger_id, e))
+                })
+            })?);
+        }
+        if let Some(path) = &options.record {
+            let mut recording = McapRecording::create(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            self.status.line("mcap_record", format_args!("Recording triggers, matched frames and latency to {}", path.display()), &[("path", path.display().to_string().into())]);
+            self.mcap = Some(FrameSink::spawn("mcap", move || {
+                Ok(move |job: RecordJob| {
+                    match job {
+                        RecordJob::Trigger(trigger) => recording.record_trigger(trigger),
+                        RecordJob::Frame(matched, width, height, rgb) => recording.record_match(&matched, Some((width, height, &rgb))),
+                        RecordJob::Latency(hw_ts, stages) => recording.record_latency(hw_ts, &stages).map(|_| ()),
+                    }
+                    .map_err(|e| e.to_string())
+                })
+            })?);
+        }
+        if let Some(dir) = &options.shared_stats_dir {
+            let shared_stats = StatsPublisher::create(dir, &options.node)?;
+            let path = shared_stats.path().display().to_string();
+            self.status.line("shared_stats", format_args!("Stats snapshot published at {}", path), &[("path", path.as_str().into())]);
+            self.shared_stats = Some(shared_stats);
+        }
+        if let Some(target) = &options.event_log {
+            self.event_log = EventLog::open(target, "v4l2_capture")?;
+        }
+        if let Some(target) = &options.trace {
+            self.tracer = Tracer::open(target)?;
+        }
+
+        // Initialize camera; frame waits time out so stalls get noticed
+        let mut camera = self.open_camera(options)?;
+        camera.set_frame_timeout(self.stall.frame_timeout());
+        self.reports.stream_info = camera.describe();
+        self.status.line("stream", format_args!("Capture stream: {}", self.reports.stream_info), &[("stream", self.reports.stream_info.as_str().into())]);
+        self.frame_timestamp = options.frame_timestamp;
+        let source = Self::frame_timestamp(self.frame_timestamp, camera.as_ref());
+        self.status.line(
+            "frame_timestamp",
+            format_args!(
+                "Frames matched on {}",
+                match source {
+                    FrameTimestamp::Driver => "the driver's buffer timestamp (CLOCK_MONOTONIC converted to CLOCK_REALTIME), the dequeue time for frames without one",
+                    FrameTimestamp::Dequeue => "the time they were dequeued (CLOCK_REALTIME)",
+                }
+            ),
+            &[("source", source.to_string().into())],
+        );
+        #[cfg(target_os = "linux")]
+        if let Some(path) = &options.loopback {
+            let (width, height) = camera.resolution();
+            let loopback = LoopbackOutput::open(path, width, height)?;
+            self.status.line("loopback", format_args!("Loopback output: {}", loopback.describe()), &[("loopback", loopback.describe().into())]);
+            self.loopback = Some(loopback);
+        }
+        #[cfg(not(target_os = "linux"))]
+        if options.loopback.is_some() {
+            return Err("--loopback needs Linux (v4l2loopback)".into());
+        }
+        #[cfg(feature = "rtsp")]
+        if let Some(port) = options.rtsp_port {
+            let (width, height) = camera.resolution();
+            let mut rtsp = RtspOutput::start(port, &options.rtsp_mount, width, height, self.output_fps)?;
+            if let Some(clock) = &self.timecode {
+                rtsp.set_timecode_rate(clock.rate.numerator, clock.rate.denominator, clock.rate.drop_frame);
+            }
+            self.status.line("rtsp", format_args!("RTSP output: {}", rtsp.describe()), &[("rtsp", rtsp.describe().into())]);
+            self.rtsp = Some(rtsp);
+        }
+        #[cfg(not(feature = "rtsp"))]
+        if options.rtsp_port.is_some() {
+            return Err("--rtsp-port needs a build with the `rtsp` feature".into());
+        }
+        self.camera = Some(camera);
+
+        // Initialize Iceoryx2 subscriber
+        let node = create_node(&options.node)?;
+        let trigger_service = options.trigger_service(self.camera_index);
+        if let Some(channel) = &options.channel {
+            self.status.line(
+                "trigger_channel",
+                format_args!("Matching against trigger channel {} on {} ({})", channel.name, trigger_service, channel),
+                &[("channel", channel.name.as_str().into()), ("service", trigger_service.as_str().into()), ("rate", channel.rate().into())],
+            );
+        } else if trigger_service != trigger::TRIGGER_SERVICE {
+            self.status.line(
+                "trigger_camera",
+                format_args!("Matching against the per-camera trigger stream {}", trigger_service),
+                &[("service", trigger_service.as_str().into()), ("camera_index", self.camera_index.into())],
+            );
+        }
+        self.subscriber = Some(TriggerSubscriber::create_for(&node, &trigger_service)?);
+        let consumer_name = options.consumer_name.clone().unwrap_or_else(|| format!("camera {}", self.camera_selector));
+        if let Some(group) = &options.camera_group {
+            self.group_latency = Some((GroupLatencyPublisher::create(&node)?, group.clone(), consumer_name.clone()));
+        }
+        self.feedback = Some((FeedbackPublisher::create(&node)?, consumer_name));
+        self.strobe = Some(StrobeSubscriber::create(&node)?);
+        self.pose = Some(PoseSubscriber::create(&node)?);
+        self.encoder = Some(EncoderSubscriber::create(&node)?);
+        if let Some(service_name) = options.frames_service(self.camera_index) {
+            self.status.line("frame_publisher", format_args!("Publishing matched frames on {}", service_name), &[("service", service_name.as_str().into())]);
+            // The publisher has its own node: iceoryx2 ports stay on the thread that created them
+            let identity = options.node.child("frames");
+            self.frame_publisher = Some(FrameSink::spawn("frame publisher", move || {
+                let node = create_node(&identity).map_err(|e| e.to_string())?;
+                let publisher = FramePublisher::create(&node, &service_name).map_err(|e| e.to_string())?;
+                Ok(move |(header, rgb): (FrameHeader, FrameBuffer)| {
+                    let _node = &node;
+                    publisher.publish(header, &rgb).map_err(|e| e.to_string())
+                })
+            })?);
+        }
+
+        // Glass-to-glass mode: camera loop, plus photodiode edges if a sensor publishes them
+        if let Some(interval) = options.glass_to_glass_interval {
+            let session_dir = self.recorder.as_ref().map(|recorder| recorder.dir().to_path_buf());
+            self.glass_to_glass = Some(GlassToGlass::new(interval, session_dir.as_deref())?);
+            self.photodiode = Some(PhotodiodeSubscriber::create(&node)?);
+            self.status.line(
+                "glass_to_glass",
+                format_args!("Glass-to-glass mode: flashing marker every {} triggers", interval),
+                &[("interval", interval.into())],
+            );
+        }
+
+        // Display clock in the scene: end-to-end accuracy of the matched trigger times
+        if let Some(config) = options.display_clock {
+            let session_dir = self.recorder.as_ref().map(|recorder| recorder.dir().to_path_buf());
+            self.display_clock = Some(DisplayClock::new(config, session_dir.as_deref())?);
+            self.status.line(
+                "display_clock",
+                format_args!(
+                    "Display clock: reading {} bits of {:.3}ms at {} (lag {:.3}ms)",
+                    config.bits,
+                    config.unit_ns as f64 / 1e6,
+                    config.region,
+                    config.lag_ns as f64 / 1e6
+                ),
+                &[
+                    ("region", config.region.to_string().into()),
+                    ("bits", config.bits.into()),
+                    ("unit_us", (config.unit_ns as f64 / 1e3).into()),
+                    ("lag_ms", (config.lag_ns as f64 / 1e6).into()),
+                ],
+            );
+        }
+
+        // Session manifest: effective configuration, software version, camera identity and calibration hashes
+        let mut manifest = SessionManifest::new("v4l2_capture");
+        manifest
+            .config("camera", &self.camera_selector)
+            .config("camera_index", self.camera_index)
+            .config("output_fps", self.output_fps)
+            .config("skip_ratio", self.skip_ratio)
+            .config("width", self.width)
+            .config("height", self.height)
+            .config("camera_fps", self.camera_fps)
+            .config("rate_tolerance_pct", self.rate_tolerance_pct)
+            .config("burst_size", self.burst.size)
+            .config("priority_every", self.priority.map(|pattern| pattern.every.to_string()).unwrap_or_default())
+            .config("match_stages", self.pending_triggers.stages().iter().map(Stage::name).collect::<Vec<_>>().join(","))
+            .config("tolerance_ms", options.match_config.tolerance_ms)
+            .config("future_penalty", options.match_config.future_penalty)
+            .config("max_pending_triggers", options.match_config.max_pending)
+            .config("max_degradation", self.degradation.config().max.name())
+            .config("degrade_after", self.degradation.config().escalate_after)
+            .config("recover_after", self.degradation.config().recover_after)
+            .config("adaptive_tolerance", options.adaptive_tolerance)
+            .config("channel", options.channel.as_ref().map(ToString::to_string).unwrap_or_default())
+            .config("trigger_service", options.trigger_service(self.camera_index))
+            .config("camera_group", options.camera_group.as_deref().unwrap_or(""))
+            .config("display_clock", options.display_clock.map(|config| config.region.to_string()).unwrap_or_default())
+            .config("display_clock_bits", options.display_clock.map(|config| config.bits.to_string()).unwrap_or_default())
+            .config("display_clock_unit_us", options.display_clock.map(|config| (config.unit_ns as f64 / 1e3).to_string()).unwrap_or_default())
+            .config("display_clock_lag_ms", options.display_clock.map(|config| (config.lag_ns as f64 / 1e6).to_string()).unwrap_or_default())
+            .config("matcher_state", self.matcher_state.as_ref().map(|path| path.display().to_string()).unwrap_or_default())
+            .config("encoder_ticks_per_unit", self.encoder_track.ticks_per_unit())
+            .config("backend", &options.backend)
+            .config("gst_pipeline", options.gst_pipeline.as_deref().unwrap_or(""))
+            .config("loopback", options.loopback.as_deref().unwrap_or(""))
+            .config("publish_frames", options.frames_service(self.camera_index).unwrap_or_default())
+            .config("rtsp_port", options.rtsp_port.map(|port| port.to_string()).unwrap_or_default())
+            .config("rtsp_mount", if options.rtsp_port.is_some() { options.rtsp_mount.as_str() } else { "" })
+            .config("timecode_rate", self.timecode.map(|clock| clock.rate.to_string()).unwrap_or_default())
+            .config("timecode_epoch", self.timecode.map(|clock| clock.epoch.to_string()).unwrap_or_default())
+            .config("exposure_us", self.latency.exposure_ns().map(|ns| Nanos(ns).to_micros_floor().0.to_string()).unwrap_or_default())
+            .config("verify_key", options.verify_key.as_deref().unwrap_or(""))
+            .config("session_dir", options.session_dir.as_deref().unwrap_or(""))
+            .config("record", options.record.as_ref().map(|path| path.display().to_string()).unwrap_or_default())
+            .config("min_confidence", options.retention.map(|policy| policy.min_confidence.to_string()).unwrap_or_default())
+            .config("quarantine", options.retention.is_some_and(|policy| policy.quarantine));
+        if let Some(camera) = &self.camera {
+            let (actual_width, actual_height) = camera.resolution();
+            manifest
+                .config("actual_resolution", format!("{}x{}", actual_width, actual_height))
+                .config("capture_stream", camera.describe())
+                .config("frame_timestamp", Self::frame_timestamp(self.frame_timestamp, camera.as_ref()))
+                .camera(CameraEntry {
+                    selector: self.camera_selector.to_string(),
+                    device: format!("/dev/video{}", self.camera_index),
+                    index: self.camera_index,
+                    name: camera.device_name(),
+                    serial: device::usb_serial(self.camera_index),
+                    bus_path: device::usb_bus_path(self.camera_index),
+                });
+        }
+        for path in &options.calibration_files {
+            manifest.calibration_file(Path::new(path))?;
+        }
+        let intrinsics = match &options.camera_info {
+            Some(path) => {
+                manifest.calibration_file(Path::new(path))?;
+                Some(CameraIntrinsics::load(Path::new(path))?)
+            }
+            None => None,
+        };
+        if let Some(recorder) = &self.recorder {
+            manifest.write(recorder.dir())?;
+        }
+        let telemetry = TelemetryPublisher::create(&node)?;
+        telemetry.publish("manifest", &manifest.to_string())?;
+        if let Some(intrinsics) = &intrinsics {
+            if let Some(recorder) = &self.recorder {
+                intrinsics.write(recorder.dir())?;
+            }
+            telemetry.publish("camera_info", &intrinsics.to_string())?;
+            let [fx, fy, cx, cy] = intrinsics.pinhole();
+            self.status.line(
+                "camera_info",
+                format_args!(
+                    "Camera info {}x{} published: fx={:.1} fy={:.1} cx={:.1} cy={:.1} ({}, {} coefficients)",
+                    intrinsics.width,
+                    intrinsics.height,
+                    fx,
+                    fy,
+                    cx,
+                    cy,
+                    intrinsics.distortion_model,
+                    intrinsics.d.len()
+                ),
+                &[
+                    ("width", intrinsics.width.into()),
+                    ("height", intrinsics.height.into()),
+                    ("fx", fx.into()),
+                    ("fy", fy.into()),
+                    ("cx", cx.into()),
+                    ("cy", cy.into()),
+                    ("distortion_model", intrinsics.distortion_model.as_str().into()),
+                ],
+            );
+            // Intrinsics are only valid for the resolution they were calibrated at
+            if let Some(camera) = &self.camera {
+                let (actual_width, actual_height) = camera.resolution();
+                if (actual_width, actual_height) != (intrinsics.width, intrinsics.height) {
+                    self.status.line(
+                        "camera_info_mismatch",
+                        format_args!(
+                            "WARNING: camera info is for {}x{} but the camera delivers {}x{}",
+                            intrinsics.width, intrinsics.height, actual_width, actual_height
+                        ),
+                        &[
+                            ("calibrated_width", intrinsics.width.into()),
+                            ("calibrated_height", intrinsics.height.into()),
+                            ("width", actual_width.into()),
+                            ("height", actual_height.into()),
+                        ],
+                    );
+                }
+            }
+        }
+        self.status.line(
+            "start",
+            format_args!("Session manifest published (config_hash={})", manifest.config_hash()),
+            &[
+                ("config_hash", manifest.config_hash().into()),
+                ("camera", self.camera_selector.to_string().into()),
+                ("width", self.width.into()),
+                ("height", self.height.into()),
+                ("output_fps", self.output_fps.into()),
+            ],
+        );
+        self.event_log.event("start", false, &[("config_hash", manifest.config_hash()), ("camera", self.camera_selector.to_string())])?;
+        self.telemetry = Some(telemetry);
+        if let Some(runs_dir) = &options.runs_dir {
+            self.run_summary = Some((RunSummary::new(&manifest), PathBuf::from(runs_dir)));
+        }
+
+        // Drain historical triggers
+        self.reports.sync_info = "Draining historical triggers...".to_string();
+        let mut history_count = 0;
+        if let Some(subscriber) = &self.subscriber {
+            while subscriber.receive()?.is_some() {
+                history_count += 1;
+            }
+        }
+        self.reports.sync_info = format!("Ready! Drained {} historical triggers. Click 'Start Capture' to begin.", history_count);
+        Ok(())
+    }
+
+    fn open_camera(&mut self, options: &Options) -> Result<Box<dyn CaptureBackend>, Box<dyn std::error::Error>> {
+        match options.backend.as_str() {
+            "nokhwa" => Ok(Box::new(NokhwaBackend::open(self.camera_index, self.width, self.height)?)),
+            #[cfg(target_os = "linux")]
+            "v4l2" => {
+                // Fewer queued buffers mean less delay between exposure and dequeue
+                let tuning = sync_capture::tuning::BufferTuning::plan(self.camera_fps, options.target_latency_ms, options.v4l2_buffers, options.v4l2_memory);
+                self.status.line("buffer_tuning", format_args!("V4L2 buffer tuning: {}", tuning), &[("tuning", tuning.to_string().into())]);
+                let backend = sync_capture::V4l2Backend::open(self.camera_index, self.width, self.height, self.camera_fps, tuning)?;
+                Ok(Box::new(backend))
+            }
+            "pattern" => {
+                // Its own subscriber, so the camera sees the triggers independently of the matcher
+                let node = create_node(&options.node.child("pattern"))?;
+                let subscriber = TriggerSubscriber::create_for(&node, &options.trigger_service(self.camera_index))?;
+                // Historical triggers were exposed before the camera existed
+                while subscriber.receive()?.is_some() {}
+                // Canaries expose no frame
+                let source: test_pattern::TriggerSource = Box::new(move || {
+                    let Some((trigger, header)) = subscriber.receive()? else {
+                        return Ok(None);
+                    };
+                    let (trigger, _) = clock::trigger_to_realtime(trigger, header)?;
+                    Ok((!canary::is_canary(trigger.frame_id)).then_some((trigger.frame_id, trigger.hw_timestamp_ns)))
+                });
+                let delay = Duration::from_secs_f64(options.pattern_delay_ms.max(0.0) / 1000.0);
+                let jitter = Duration::from_secs_f64(options.pattern_jitter_ms.max(0.0) / 1000.0);
+                self.pattern_check = true;
+                Ok(Box::new(TestPatternBackend::new(source, self.width, self.height, delay, jitter)))
+            }
+            #[cfg(feature = "gstreamer")]
+            "gstreamer" => {
+                let description = options.gst_pipeline.as_deref().ok_or("--backend gstreamer needs --gst-pipeline <description>")?;
+                // Serial and bus path selectors work for pipelines too
+                let description = description.replace("{device}", &format!("/dev/video{}", self.camera_index));
+                Ok(Box::new(sync_capture::GstreamerBackend::open(&description)?))
+            }
+            other => Err(format!("unknown capture backend '{}'", other).into()),
+        }
+    }
+
+    fn capture_frame(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(event) = self.realtime_guard.observe(clock::realtime_now_ns(), clock::monotonic_now_ns()) {
+            self.report_clock_event(&event)?;
+        }
+        if let Some(camera) = &mut self.camera {
+            if let Some(strobe) = &self.strobe {
+                while let Some(command) = strobe.receive()? {
+                    self.strobe_log.push(command);
+                }
+            }
+            if let Some(pose) = &self.pose {
+                while let Some(sample) = pose.receive()? {
+                    self.pose_track.push(sample);
+                }
+            }
+            if let Some(encoder) = &self.encoder {
+                while let Some(sample) = encoder.receive()? {
+                    self.encoder_track.push(sample);
+                }
+            }
+
+            // Receive new triggers; clock events and policy changes are reported once the camera is done
+            let mut clock_events = Vec::new();
+            let reported_policy = self.clock_policy;
+            if let Some(subscriber) = &self.subscriber {
+                while let Some((trigger, header)) = subscriber.receive()? {
+                    let received_ns = clock::realtime_now_ns();
+                    if let Some(verifier) = &self.verifier {
+                        let signature = verifier.verify(&trigger, &header);
+                        if signature != SignatureStatus::Valid {
+                            self.status.line(
+                                "trigger_rejected",
+                                format_args!("WARNING: Rejected trigger id={} (signature {})", trigger.frame_id, signature),
+                                &[("trigger_id", trigger.frame_id.into()), ("signature", signature.to_string().into())],
+                            );
+                            self.event_log.event("trigger_rejected", true, &[("trigger_id", trigger.frame_id.to_string()), ("signature", signature.to_string())])?;
+                            continue;
+                        }
+                    }
+                    // Matched in CLOCK_REALTIME like the frames, whatever the publisher stamped them in
+                    if self.published_domain != Some(header.clock_domain) {
+                        self.published_domain = Some(header.clock_domain);
+                        let label = ClockDomain::label(header.clock_domain);
+                        let converted = header.clock_domain != ClockDomain::Realtime as u8;
+                        self.status.line(
+                            "clock_domain",
+                            format_args!("Triggers published in clock domain {}{}", label, if converted { ", converted to CLOCK_REALTIME for matching" } else { "" }),
+                            &[("domain", label.as_str().into()), ("converted", converted.into())],
+                        );
+                    }
+                    self.clock_policy = DowngradePolicy::from_header(header.clock_policy);
+                    let (trigger, header) = clock::trigger_to_realtime(trigger, header)?;
+                    let CameraTrigger { frame_id: trigger_id, hw_timestamp_ns: hw_ts, publish_timestamp_ns: pub_ts, .. } = trigger;
+                    if let Some((feedback, consumer_name)) = &self.feedback {
+                        if feedback::is_sampled(trigger_id) {
+                            feedback.publish(ReceiveFeedback::new(consumer_name, &trigger, received_ns))?;
+                        }
+                    }
+                    // Canaries prove the chain alive, they are acknowledged and never matched
+                    if canary::is_canary(trigger_id) {
+                        continue;
+                    }
+                    if let Some(gap) = self.trigger_gaps.on_trigger(trigger_id) {
+                        self.status.line(
+                            "trigger_gap",
+                            format_args!("WARNING: {} ({} missing so far)", gap, self.trigger_gaps.missing_triggers),
+                            &[
+                                ("after", gap.after.into()),
+                                ("before", gap.before.into()),
+                                ("missing", gap.missing.into()),
+                                ("missing_total", self.trigger_gaps.missing_triggers.into()),
+                            ],
+                        );
+                        self.event_log.event("trigger_gap", true, &[("after", gap.after.to_string()), ("before", gap.before.to_string()), ("missing", gap.missing.to_string())])?;
+                        self.reports.drops_report = Self::drops_summary(&self.drops, &self.trigger_gaps);
+                    }
+                    // Triggers are taken between frames, so this includes the wait for the camera
+                    self.latency.record_wakeup(pub_ts, received_ns);
+                    clock_events.extend(self.trigger_guard.observe(hw_ts, pub_ts));
+                    self.stall.on_trigger();
+                    let trace = TraceId::of(&trigger, &header);
+                    self.tracer.trigger_received(trace, hw_ts);
+                    self.status.line(
+                        "trigger",
+                        format_args!("Received trigger: id={}, hw_ts={}, ipc_delay={}ns, trace={}", trigger_id, hw_ts, pub_ts.saturating_sub(hw_ts), trace),
+                        &[
+                            ("trigger_id", trigger_id.into()),
+                            ("hw_ts", hw_ts.into()),
+                            ("ipc_delay_ns", pub_ts.saturating_sub(hw_ts).into()),
+                            ("trace", trace.to_string().into()),
+                        ],
+                    );
+                    self.trigger_rate.on_trigger(trigger_id, hw_ts);
+                    let queued = self.pending_triggers.on_trigger(trigger, header);
+                    self.received_triggers += 1;
+                    if let (Some(mapping), Some(recorder)) = (self.clock_mapper.on_trigger(pub_ts, clock::realtime_now_ns()), &mut self.recorder) {
+                        recorder.record_clock(&mapping)?;
+                    }
+                    // Matched or not, so drops show in the recording
+                    if let Some(mcap) = &self.mcap {
+                        if mcap.offer(RecordJob::Trigger(trigger))?.is_some() {
+                            self.unrecorded_messages += 1;
+                        }
+                    }
+                    if let Some(glass_to_glass) = &mut self.glass_to_glass {
+                        glass_to_glass.on_trigger(trigger_id, hw_ts);
+                    }
+
+                    // Pending triggers are limited (last 100)
+                    match queued {
+                        Queued::Added => {}
+                        Queued::Duplicate => self.status.line(
+                            "trigger_duplicate",
+                            format_args!("WARNING: Dropped duplicate trigger id={}", trigger_id),
+                            &[("trigger_id", trigger_id.into())],
+                        ),
+                        Queued::Evicted(CameraTrigger { frame_id: old_trigger_id, .. }) => {
+                            self.dropped_triggers += 1;
+                            let high = priority::is_priority(self.priority, old_trigger_id);
+                            self.status.line(
+                                "trigger_dropped",
+                                format_args!("WARNING: Dropped old {}trigger id={} (V4L2 too slow)", if high { "priority " } else { "" }, old_trigger_id),
+                                &[("trigger_id", old_trigger_id.into()), ("priority", high.into())],
+                            );
+                            self.event_log.event("trigger_dropped", true, &[("trigger_id", old_trigger_id.to_string()), ("priority", high.to_string())])?;
+                        }
+                    }
+                }
+            }
+
+            // Capture frame; without one the caller checks for a stall
+            let frame = camera.frame();
+            let pool = camera.pool_stats();
+            let queued_buffers = camera.queued_buffers();
+            let frame_timestamp = Self::frame_timestamp(self.frame_timestamp, camera.as_ref());
+            let v4l2_timestamp_ns = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos() as u64;
+            for event in &clock_events {
+                self.report_clock_event(event)?;
+            }
+            if self.clock_policy != reported_policy {
+                self.report_clock_policy()?;
+            }
+            let frame = match frame {
+                Ok(frame) => frame,
+                Err(e) if sync_capture::is_timeout(&*e) => return Ok(()),
+                // With stall recovery, a failing stream is restarted like a silent one
+                Err(e) if self.stall.config().restart => {
+                    self.status.line("capture_error", format_args!("WARNING: Capture error: {}", e), &[("error", e.to_string().into())]);
+                    std::thread::sleep(self.stall.frame_timeout());
+                    return Ok(());
+                }
+                Err(e) => return Err(e),
+            };
+            match self.stall.on_frame(clock::monotonic_now_ns()) {
+                FrameAction::Process => {}
+                FrameAction::WarmUp => return Ok(()),
+                FrameAction::Resumed(resumed) => self.report_resumed(&resumed)?,
+            }
+            self.check_frame_consumers(pool)?;
+            // Finished by the caller, so early returns are timed too
+            self.loop_deadline.start();
+            self.update_trigger_rate()?;
+            self.update_ui_budget()?;
+            self.tracer.frame_dequeued(v4l2_timestamp_ns);
+            self.diagnostics.on_frame(v4l2_timestamp_ns);
+            if let Some(lost) = self.drops.on_frame(frame.sequence, frame.driver_error) {
+                self.status.line(
+                    "sequence_gap",
+                    format_args!("WARNING: Driver sequence gap, {} frames lost before sequence {}", lost, frame.sequence.unwrap_or_default()),
+                    &[("lost", lost.into()), ("sequence", frame.sequence.into())],
+                );
+                self.event_log.event("sequence_gap", true, &[("lost", lost.to_string()), ("sequence", frame.sequence.unwrap_or_default().to_string())])?;
+            }
+            if frame.driver_error {
+                self.status.line(
+                    "corrupted_frame",
+                    format_args!("WARNING: Driver flagged frame {} as corrupted", frame.sequence.unwrap_or_default()),
+                    &[("sequence", frame.sequence.into())],
+                );
+                self.event_log.event("corrupted_frame", true, &[("sequence", frame.sequence.unwrap_or_default().to_string())])?;
+            }
+            if self.drops.has_transport_loss() || self.trigger_gaps.has_loss() {
+                self.reports.drops_report = Self::drops_summary(&self.drops, &self.trigger_gaps);
+            }
+            // Driver buffer timestamp (monotonic) expressed in the trigger clock domain, and the time the frame is
+            // matched on: that one (V4L2 buffer timestamp, GStreamer PTS) unless `--frame-timestamp dequeue`
+            let realtime_minus_monotonic_ns = clock::realtime_minus_monotonic_ns();
+            let driver_timestamp_ns = clock::driver_to_realtime_ns(frame.driver_timestamp_ns, self.previous_driver_ns, realtime_minus_monotonic_ns);
+            let match_ns = frame_timestamp.match_ns(frame.driver_timestamp_ns, self.previous_driver_ns, v4l2_timestamp_ns, realtime_minus_monotonic_ns);
+            self.previous_driver_ns = frame.driver_timestamp_ns.filter(|&ns| ns != 0).or(self.previous_driver_ns);
+
+            // Frame skipping, more of it while degraded
+            self.trigger_count += 1;
+            let skip_ratio = self.skip_ratio * self.degradation.skip_factor();
+            let should_process = self.trigger_count.is_multiple_of(skip_ratio);
+            if !should_process && self.trigger_count.is_multiple_of(self.skip_ratio) {
+                self.degradation.degraded.skipped += 1;
+            }
+
+            if should_process {
+                // Synchronize with trigger
+                let matched_hw_ts = self.sync_frame_with_trigger(&frame, match_ns, v4l2_timestamp_ns, driver_timestamp_ns)?;
+                if let Some(glass_to_glass) = &mut self.glass_to_glass {
+                    glass_to_glass.on_frame(glass_to_glass::mean_luma(&frame.rgb), matched_hw_ts);
+                }
+                if let Some(display_clock) = &mut self.display_clock {
+                    display_clock.on_frame(&frame.rgb, frame.width, frame.height, matched_hw_ts);
+                    self.reports.display_clock_report = display_clock.report(self.status.display_unit());
+                }
+
+                // Converted for display by the UI thread when it repaints; the UI keeps the last one while degraded
+                if self.degradation.shows_preview() {
+                    if self.window.is_some() {
+                        self.preview.publish(Some((frame, matched_hw_ts, v4l2_timestamp_ns)));
+                        self.request_repaint();
+                    }
+                } else {
+                    self.degradation.degraded.previews += 1;
+                }
+            } else {
+                self.status.line(
+                    "skipped",
+                    format_args!("SKIPPED: Frame {} skipped (output FPS: {}fps, processing every {}th trigger)", self.trigger_count, self.output_fps, skip_ratio),
+                    &[("frame", self.trigger_count.into()), ("output_fps", self.output_fps.into()), ("skip_ratio", skip_ratio.into())],
+                );
+            }
+            self.check_pipeline_depth(queued_buffers, driver_timestamp_ns, v4l2_timestamp_ns)?;
+            self.update_degradation()?;
+        }
+        Ok(())
+    }
+
+    // Returns the hardware timestamp of the matched trigger, if any
+    fn sync_frame_with_trigger(&mut self, frame: &CapturedFrame, match_ns: u64, v4l2_timestamp_ns: u64, driver_timestamp_ns: Option<u64>) -> Result<Option<u64>, Box<dyn std::error::Error>> {
+        // Not matched until the publisher's trigger clock recovers; the pending triggers age out meanwhile
+        if self.clock_policy == Some(DowngradePolicy::Pause) {
+            self.paused_frames += 1;
+            self.trigger_gaps.on_frame(None);
+            self.reports.sync_info = format!("PAUSED: publisher's trigger clock is downgraded, {} frames not matched", self.paused_frames);
+            return Ok(None);
+        }
+        self.processed_frames += 1;
+        self.tracer.match_started();
+        self.match_deadline.start();
+        let clock_disturbed = self.realtime_guard.is_disturbed() || self.trigger_guard.is_disturbed();
+        self.pending_triggers.set_learning_frozen(clock_disturbed || self.clock_policy.is_some());
+        self.pending_triggers.set_tolerance_factor(if self.clock_policy == Some(DowngradePolicy::Widen) { clock_quality::WIDEN_FACTOR } else { 1.0 });
+        // Within a burst the frame after a match takes the next pulse, if it is pending and within tolerance
+        let tolerance_ms = self.pending_triggers.tolerance_ms();
+        let association = self.pending_triggers.on_frame(match_ns);
+        if association.relearned {
+            self.status.line(
+                "matcher_relearn",
+                format_args!("WARNING: no match for {} frames in a row, discarding the learned latency of camera {}", pipeline::RELEARN_AFTER, self.camera_selector),
+                &[("stream", self.camera_selector.to_string().into()), ("unmatched", pipeline::RELEARN_AFTER.into())],
+            );
+            self.event_log.event("matcher_relearn", true, &[("stream", self.camera_selector.to_string())])?;
+        }
+
+        if let Some((trigger, header, best_score)) = association.matched {
+            let CameraTrigger { frame_id: trigger_id, hw_timestamp_ns: hw_ts, .. } = trigger;
+            let trace = TraceId::of(&trigger, &header);
+            self.tracer.match_decided(Some(trace), best_score);
+            if let Some(missed) = self.match_deadline.finish() {
+                self.report_missed_deadline(missed)?;
+            }
+            for finding in &association.findings {
+                self.status.line(
+                    "match_audit",
+                    format_args!("AUDIT: frame at {}ns: {}", match_ns, finding),
+                    &[("frame_ns", match_ns.into()), ("finding", finding.as_str().into())],
+                );
+                self.event_log.event("match_audit", true, &[("finding", finding.clone())])?;
+            }
+
+            // Cleanup old triggers
+            let removed_old_count = association.cleaned.len();
+            for CameraTrigger { frame_id: old_trigger_id, .. } in &association.cleaned {
+                self.status.line(
+                    "trigger_cleanup",
+                    format_args!("CLEANUP: Removed old trigger id={} (too old for future frames)", old_trigger_id),
+                    &[("trigger_id", (*old_trigger_id).into())],
+                );
+            }
+
+            self.check_pattern(frame, match_ns, Some(trigger_id))?;
+
+            let FrameLatency { trigger_type, total_latency_ms, v4l2_delay_ms } = FrameLatency::of(&trigger, match_ns, v4l2_timestamp_ns);
+            self.diagnostics.on_match(Some(total_latency_ms));
+            self.drops.on_match(true);
+            if let Some(lost) = self.trigger_gaps.on_frame(Some(trigger_id)) {
+                self.status.line(
+                    "frames_lost",
+                    format_args!("WARNING: trigger_id={} matched {} frames after the previous match, {} frames lost in between", trigger_id, lost + 1, lost),
+                    &[("trigger_id", trigger_id.into()), ("lost", lost.into()), ("lost_total", self.trigger_gaps.lost_frames.into())],
+                );
+                self.event_log.event("frames_lost", true, &[("trigger_id", trigger_id.to_string()), ("lost", lost.to_string())])?;
+            }
+            let mut frame_info = String::new();
+            if self.burst.is_burst() {
+                frame_info.push_str(&format!(", burst={}", self.burst.position(trigger_id)));
+            }
+            let timestamp_source = TimestampSource::label(header.timestamp_source);
+            frame_info.push_str(&format!(", src={}, trace={}", timestamp_source, trace));
+            let mut metadata = vec![("timestamp_source".to_string(), timestamp_source), ("trace".to_string(), trace.to_string())];
+            let priority = priority::is_priority(self.priority, trigger_id);
+            if priority {
+                frame_info.push_str(", priority=high");
+                metadata.push(("priority".to_string(), "high".to_string()));
+            }
+            // Under overload normal frames go unrecorded, then undelivered (see `sync_core::degrade`)
+            let delivered = self.degradation.delivers(priority);
+            let recorded = delivered && self.degradation.records(priority);
+            if !delivered {
+                self.degradation.degraded.shed += 1;
+            } else if !recorded {
+                self.degradation.degraded.recordings += 1;
+            }
+            if !recorded {
+                let level = self.degradation.level();
+                frame_info.push_str(&format!(", degraded={}", level));
+                metadata.push(("degraded".to_string(), level.to_string()));
+            }
+            if let Some(command) = self.strobe_log.get(trigger_id) {
+                frame_info.push_str(&format!(", strobe={}", command));
+                metadata.push(("strobe".to_string(), command.to_string()));
+            }
+            if let Some(pose) = self.pose_track.at(hw_ts) {
+                frame_info.push_str(&format!(", {}", pose));
+                metadata.extend(pose.metadata());
+            }
+            if let Some(position) = self.encoder_track.position(hw_ts) {
+                frame_info.push_str(&format!(", encoder={:.3}", position));
+                metadata.push(("encoder".to_string(), format!("{:.3}", position)));
+            }
+            let timecode = self.timecode.map(|clock| clock.timecode(Nanos(hw_ts)));
+            if let Some(timecode) = timecode {
+                frame_info.push_str(&format!(", tc={}", timecode));
+                metadata.push(("timecode".to_string(), timecode.metadata_value()));
+            }
+            if clock_disturbed {
+                frame_info.push_str(", clock=disturbed");
+                metadata.push(("clock".to_string(), "disturbed".to_string()));
+            }
+            // The frame on the publisher's PTP time, for publishers reading a PHC (`--clock ptp:<dev>`)
+            if let Some(network_ns) = header.network_time_ns(match_ns) {
+                frame_info.push_str(&format!(", phc_offset={}ns", header.phc_offset_ns));
+                metadata.push(("phc_offset_ns".to_string(), header.phc_offset_ns.to_string()));
+                metadata.push(("network_time_ns".to_string(), network_ns.to_string()));
+            }
+            if let Some(policy) = self.clock_policy {
+                frame_info.push_str(&format!(", clock_policy={}", policy));
+                metadata.push(("clock_policy".to_string(), policy.to_string()));
+            }
+            let confidence = MatchQuality {
+                score_ms: best_score,
+                tolerance_ms,
+                clock_disturbed,
+                clock_downgraded: self.clock_policy.is_some(),
+                findings: association.findings.len(),
+            }
+            .confidence();
+            frame_info.push_str(&format!(", confidence={:.2}", confidence));
+            metadata.push(("confidence".to_string(), format!("{:.2}", confidence)));
+
+            self.reports.sync_info = format!("SYNCED [{}]: trigger_id={}, latency={:.1}, score={:.1}{}",
+                                   trigger_type, trigger_id, DisplayMs(total_latency_ms, self.status.display_unit()), DisplayMs(best_score, self.status.display_unit()), frame_info);
+
+            let signature = if self.verifier.is_some() { SignatureStatus::Valid } else { SignatureStatus::Unsigned };
+
+            let mut fields = vec![
+                ("trigger_type", trigger_type.into()),
+                ("trigger_id", trigger_id.into()),
+                ("hw_exposure_ts", hw_ts.into()),
+                ("v4l2_ts", v4l2_timestamp_ns.into()),
+                ("total_latency_ms", total_latency_ms.into()),
+                ("v4l2_delay_ms", v4l2_delay_ms.into()),
+                ("score_ms", best_score.into()),
+                ("cleaned", removed_old_count.into()),
+                ("frame_size", frame.raw_len.into()),
+                ("signature", signature.to_string().into()),
+            ];
+            if self.burst.is_burst() {
+                fields.push(("burst", self.burst.position(trigger_id).to_string().into()));
+            }
+            fields.extend(metadata.iter().map(|(key, value)| (key.as_str(), value.as_str().into())));
+            self.status.line(
+                "synced",
+                format_args!(
+                    "SYNCED [{}]: trigger_id={}, hw_exposure_ts={}, v4l2_ts={}, total_latency={:.1}, v4l2_delay={:.1}, score={:.1}, cleaned={}, frame_size={}bytes, sig={}{}",
+                    trigger_type,
+                    trigger_id,
+                    hw_ts,
+                    v4l2_timestamp_ns,
+                    DisplayMs(total_latency_ms, self.status.display_unit()),
+                    DisplayMs(v4l2_delay_ms, self.status.display_unit()),
+                    DisplayMs(best_score, self.status.display_unit()),
+                    removed_old_count,
+                    frame.raw_len,
+                    signature,
+                    frame_info
+                ),
+                &fields,
+            );
+
+            // Latencies across a clock step are garbage, the frame is only recorded (marked as disturbed)
+            if clock_disturbed {
+                self.disturbed_matches += 1;
+            } else {
+                self.latency.record(&FrameTiming {
+                    trigger_ns: hw_ts,
+                    driver_ns: driver_timestamp_ns,
+                    dequeue_ns: v4l2_timestamp_ns,
+                    match_ns: clock::realtime_now_ns(),
+                });
+                if let Some((group_latency, group, name)) = &self.group_latency {
+                    if feedback::is_sampled(trigger_id) {
+                        group_latency.publish(GroupLatency::new(group, name, trigger_id, match_ns as i64 - hw_ts as i64))?;
+                    }
+                }
+                if self.latency.count().is_multiple_of(100) {
+                    self.reports.latency_report = self.latency.report(self.status.display_unit());
+                    self.status.latency(&self.latency);
+                    self.status.stages(self.pending_triggers.stats());
+                    self.status.latency_filter(&self.pending_triggers);
+                    self.status.line(
+                        "pipeline_depth",
+                        format_args!("{}", self.depth),
+                        &[
+                            ("total", self.depth.total().into()),
+                            ("limit", self.depth.limit().into()),
+                            ("max", self.depth.max_total().into()),
+                            ("exceeded", self.depth.exceeded_count().into()),
+                            ("stages", self.depth.stages().iter().map(StageDepth::to_string).collect::<Vec<_>>().join(", ").into()),
+                        ],
+                    );
+                    self.report_ui_budget();
+                    self.report_degradation_level()?;
+                    if let Some((run_summary, runs_dir)) = &mut self.run_summary {
+                        run_summary.update(self.processed_frames, self.latency.count(), &self.latency);
+                        run_summary.write(runs_dir)?;
+                    }
+                    // Skipped while the recording is behind; the next report follows
+                    if let Some(mcap) = &self.mcap {
+                        let _ = mcap.offer(RecordJob::Latency(hw_ts, self.latency.snapshot()))?;
+                    }
+                }
+            }
+
+            // Consumers of the loopback device see the exposure time, in the V4L2 clock (monotonic)
+            #[cfg(target_os = "linux")]
+            if let Some(loopback) = self.loopback.as_mut().filter(|_| delivered) {
+                let exposure_monotonic_ns = (hw_ts as i64 - clock::realtime_minus_monotonic_ns()).max(0) as u64;
+                if !loopback.write(&frame.rgb, exposure_monotonic_ns)? {
+                    self.status.line(
+                        "loopback_dropped",
+                        format_args!("WARNING: Loopback reader too slow, dropped frame of trigger id={} ({} dropped, {} written)", trigger_id, loopback.dropped(), loopback.written()),
+                        &[("trigger_id", trigger_id.into()), ("dropped", loopback.dropped().into()), ("written", loopback.written().into())],
+                    );
+                }
+            }
+
+            #[cfg(feature = "rtsp")]
+            if let Some(rtsp) = self.rtsp.as_mut().filter(|_| delivered) {
+                // Whole frames of the output fps unless a timecode rate is configured
+                let timecode = timecode.unwrap_or_else(|| Timecode::at(Nanos(hw_ts), self.output_fps));
+                rtsp.push(&frame.rgb, (timecode.hours, timecode.minutes, timecode.seconds, timecode.frames))?;
+            }
+
+            // The publisher drops frames it is behind on, like the frame service does for slow subscribers; priority frames wait
+            if let Some(frame_publisher) = self.frame_publisher.as_ref().filter(|_| delivered) {
+                let header = FrameHeader {
+                    timestamp_ns: hw_ts,
+                    source_timestamp_ns: match_ns,
+                    sequence: frame.sequence.map_or(self.processed_frames, u64::from),
+                    trigger_id,
+                    width: frame.width,
+                    height: frame.height,
+                    fourcc: *b"RGB3",
+                    flags: FLAG_MATCHED,
+                    trace_origin: trace.origin,
+                };
+                if priority {
+                    frame_publisher.push((header, frame.rgb.clone()))?;
+                } else if frame_publisher.offer((header, frame.rgb.clone()))?.is_some() {
+                    self.unpublished_frames += 1;
+                    self.status.line(
+                        "frame_unpublished",
+                        format_args!("WARNING: Frame publisher behind, dropped frame of trigger id={} (trace {}, {} dropped)", trigger_id, trace, self.unpublished_frames),
+                        &[("trigger_id", trigger_id.into()), ("trace", trace.to_string().into()), ("dropped", self.unpublished_frames.into())],
+                    );
+                }
+            }
+
+            if let Some(mcap) = self.mcap.as_ref().filter(|_| recorded) {
+                let matched = RecordedMatch {
+                    trigger,
+                    frame_ns: match_ns,
+                    latency_ms: total_latency_ms,
+                    score_ms: best_score,
+                    confidence,
+                };
+                let job = RecordJob::Frame(matched, frame.width, frame.height, frame.rgb.clone());
+                if priority {
+                    mcap.push(job)?;
+                } else if mcap.offer(job)?.is_some() {
+                    self.unrecorded_messages += 1;
+                    self.status.line(
+                        "mcap_dropped",
+                        format_args!("WARNING: MCAP recording behind, dropped frame of trigger id={} ({} messages dropped)", trigger_id, self.unrecorded_messages),
+                        &[("trigger_id", trigger_id.into()), ("dropped", self.unrecorded_messages.into())],
+                    );
+                }
+            }
+
+            if let Some(recorder) = &mut self.recorder {
+                let action = recorder.retention_action(confidence);
+                if let Some(target) = recorder.retain(action) {
+                    // Written by the recorder thread; in place when it is behind, so no frame is lost. Left out while degraded
+                    match &self.frame_writer {
+                        _ if !recorded => {}
+                        Some(frame_writer) => {
+                            let item = (target.dir().to_path_buf(), trigger_id, frame.width, frame.height, frame.rgb.clone());
+                            if let Some((_, trigger_id, width, height, rgb)) = frame_writer.offer(item)? {
+                                target.record_frame(trigger_id, width, height, &rgb)?;
+                            }
+                        }
+                        None => target.record_frame(trigger_id, frame.width, frame.height, &frame.rgb)?,
+                    }
+                    target.record(&MatchRecord {
+                        trigger,
+                        v4l2_timestamp_ns: match_ns,
+                        total_latency_ms,
+                        score_ms: best_score,
+                        signature_status: signature,
+                        signature: header,
+                        metadata,
+                    })?;
+                }
+                if action != RetentionAction::Keep {
+                    let retained = recorder.retained();
+                    let min_confidence = recorder.retention().map_or(0.0, |policy| policy.min_confidence);
+                    self.reports.retention_report = retained.to_string();
+                    self.status.line(
+                        "frame_retention",
+                        format_args!("Frame of trigger id={} {} (confidence {:.2} below {}), {}", trigger_id, action, confidence, min_confidence, retained),
+                        &[
+                            ("trigger_id", trigger_id.into()),
+                            ("action", action.to_string().into()),
+                            ("confidence", confidence.into()),
+                            ("min_confidence", min_confidence.into()),
+                            ("kept", retained.kept.into()),
+                            ("quarantined", retained.quarantined.into()),
+                            ("discarded", retained.discarded.into()),
+                        ],
+                    );
+                }
+            }
+            Ok(Some(hw_ts))
+        } else {
+            self.reports.sync_info = format!("WARNING: No matching trigger within {:.1}ms (frame at {}ns)", tolerance_ms, match_ns);
+            self.tracer.match_decided(None, 0.0);
+            if let Some(missed) = self.match_deadline.finish() {
+                self.report_missed_deadline(missed)?;
+            }
+            if association.dropped_by == Some(Stage::Dedupe) {
+                self.status.line(
+                    "duplicate_frame",
+                    format_args!("WARNING: V4L2 frame at {}ns repeats the previous frame's timestamp", match_ns),
+                    &[("frame_ns", match_ns.into())],
+                );
+            } else {
+                self.status.line(
+                    "unmatched_frame",
+                    format_args!("WARNING: V4L2 frame at {}ns - no matching trigger within {:.1}ms tolerance", match_ns, tolerance_ms),
+                    &[("frame_ns", match_ns.into()), ("tolerance_ms", tolerance_ms.into())],
+                );
+            }
+            self.event_log.event("unmatched_frame", true, &[("frame_ns", match_ns.to_string())])?;
+            self.check_pattern(frame, match_ns, None)?;
+            self.diagnostics.on_match(None);
+            self.drops.on_match(false);
+            self.trigger_gaps.on_frame(None);
+            self.run_diagnosis()?;
+            Ok(None)
+        }
+    }
+
+    // Test-pattern camera: compares the trigger the frame was rendered for with the matched one
+    fn check_pattern(&mut self, frame: &CapturedFrame, frame_ns: u64, matched: Option<u64>) -> std::io::Result<()> {
+        if !self.pattern_check {
+            return Ok(());
+        }
+        let expected = test_pattern::decode(&frame.rgb, frame.width, frame.height).map(|(trigger_id, _)| trigger_id);
+        if expected.is_some() && expected == matched {
+            return Ok(());
+        }
+        self.pattern_mismatches += 1;
+        let id = |id: Option<u64>| id.map_or("-".to_string(), |id| id.to_string());
+        self.status.line(
+            "pattern_mismatch",
+            format_args!(
+                "MISMATCH: frame at {}ns shows trigger {} but was matched to {} ({} mismatches)",
+                frame_ns,
+                id(expected),
+                id(matched),
+                self.pattern_mismatches
+            ),
+            &[
+                ("frame_ns", frame_ns.into()),
+                ("expected", expected.into()),
+                ("matched", matched.into()),
+                ("mismatches", self.pattern_mismatches.into()),
+            ],
+        );
+        self.event_log.event("pattern_mismatch", true, &[("expected", id(expected)), ("matched", id(matched))])
+    }
+
+    // Makes sporadic stalls visible: log, telemetry and event export
+    // A clock step or the end of a disturbance, also on telemetry and in the event log
+    fn report_clock_event(&mut self, event: &ClockEvent) -> Result<(), Box<dyn std::error::Error>> {
+        self.status.line(
+            "clock_event",
+            format_args!("{}{}", if event.is_step() { "WARNING: " } else { "" }, event),
+            &[
+                ("clock", event.clock.into()),
+                ("event", event.kind.name().into()),
+                ("step_ns", event.step_ns.into()),
+                ("clock_ns", event.clock_ns.into()),
+                ("disturbed_ns", event.disturbed_ns.into()),
+                ("steps", event.steps.into()),
+            ],
+        );
+        if let Some(telemetry) = &self.telemetry {
+            telemetry.publish("clock_event", &event.to_string())?;
+        }
+        self.event_log.event(
+            "clock_event",
+            event.is_step(),
+            &[
+                ("clock", event.clock.to_string()),
+                ("event", event.kind.name().to_string()),
+                ("step_ns", event.step_ns.to_string()),
+                ("disturbed_ns", event.disturbed_ns.to_string()),
+            ],
+        )?;
+        Ok(())
+    }
+
+    // The publisher's trigger clock was downgraded or recovered, with the frames left unmatched since the last change
+    fn report_clock_policy(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let (policy, paused) = (self.clock_policy, std::mem::take(&mut self.paused_frames));
+        let text = match policy {
+            Some(policy) => format!("WARNING: publisher's trigger clock downgraded, policy {}: {}", policy, policy.describe()),
+            None => format!("Publisher's trigger clock synchronized again, matching as usual ({} frames paused)", paused),
+        };
+        self.status.line("clock_policy", format_args!("{}", text), &[("policy", policy.map(|policy| policy.to_string()).into()), ("paused_frames", paused.into())]);
+        self.event_log.event(
+            "clock_policy",
+            policy.is_some(),
+            &[("policy", policy.map_or_else(|| "none".to_string(), |policy| policy.to_string())), ("paused_frames", paused.to_string())],
+        )?;
+        Ok(())
+    }
+
+    // Reports a stall and, with --stall-restart, reopens the stream until it is back or the attempts are used up
+    fn check_stall(&mut self, options: &Options) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(stall) = self.stall.check(clock::monotonic_now_ns()) else {
+            return Ok(());
+        };
+        self.report_stall(&stall)?;
+        if !self.stall.config().restart {
+            return Ok(());
+        }
+        loop {
+            if self.stall.exhausted() {
+                return Err(format!("camera {} still stalled after {} restarts", self.camera_selector, self.stall.config().max_restarts).into());
+            }
+            let result = self.restart_camera(options);
+            self.stall.restarted(clock::monotonic_now_ns());
+            let Err(e) = result else {
+                return Ok(());
+            };
+            self.status.line(
+                "camera_restart_failed",
+                format_args!("WARNING: Could not reopen camera {}: {}", self.camera_selector, e),
+                &[("camera", self.camera_selector.to_string().into()), ("error", e.to_string().into())],
+            );
+            self.event_log.event("camera_restart_failed", true, &[("camera", self.camera_selector.to_string()), ("error", e.to_string())])?;
+            std::thread::sleep(self.stall.window());
+        }
+    }
+
+    // Closes the stream, power-cycles the camera if due, and reopens it wherever it enumerated again
+    fn restart_camera(&mut self, options: &Options) -> Result<(), Box<dyn std::error::Error>> {
+        let bus_path = device::usb_bus_path(self.camera_index);
+        self.camera = None;
+        if self.stall.should_power_cycle() {
+            match bus_path.as_deref().and_then(|bus_path| self.stall.config().power_cycle_command(bus_path)) {
+                Some(command) => {
+                    let exit = std::process::Command::new("sh").arg("-c").arg(&command).status()?;
+                    self.status.line(
+                        "camera_power_cycle",
+                        format_args!("Power-cycled camera {}: {} ({})", self.camera_selector, command, exit),
+                        &[("camera", self.camera_selector.to_string().into()), ("command", command.as_str().into()), ("success", exit.success().into())],
+                    );
+                }
+                None => self.status.line(
+                    "camera_power_cycle",
+                    format_args!("WARNING: camera {} has no USB bus path, not power-cycled", self.camera_selector),
+                    &[("camera", self.camera_selector.to_string().into()), ("success", false.into())],
+                ),
+            }
+        }
+        self.camera_index = self.camera_selector.resolve()?;
+        let mut camera = self.open_camera(options)?;
+        camera.set_frame_timeout(self.stall.frame_timeout());
+        self.reports.stream_info = camera.describe();
+        self.camera = Some(camera);
+        self.drops.restart();
+        self.trigger_gaps.restart();
+        self.status.line(
+            "camera_restarted",
+            format_args!("Camera {} reopened as /dev/video{}: {}", self.camera_selector, self.camera_index, self.reports.stream_info),
+            &[("camera", self.camera_selector.to_string().into()), ("index", self.camera_index.into()), ("stream", self.reports.stream_info.as_str().into())],
+        );
+        self.event_log.event("camera_restarted", false, &[("camera", self.camera_selector.to_string()), ("index", self.camera_index.to_string())])?;
+        Ok(())
+    }
+
+    // Frames in flight from the driver queue to the last consumer; reported when the depth goes over its limit and back
+    fn check_pipeline_depth(&mut self, queued_buffers: Option<u32>, driver_timestamp_ns: Option<u64>, v4l2_timestamp_ns: u64) -> Result<(), Box<dyn std::error::Error>> {
+        let mut stages = Vec::with_capacity(5);
+        if let (Some(buffers), Some(driver_ns)) = (queued_buffers, driver_timestamp_ns) {
+            let frame_interval_ns = 1_000_000_000 / self.camera_fps.max(1) as u64;
+            // Estimated from the age of the dequeued frame; one buffer is always being filled
+            stages.push(StageDepth {
+                name: "driver",
+                depth: depth::driver_backlog(v4l2_timestamp_ns, driver_ns, frame_interval_ns),
+                capacity: buffers.saturating_sub(1).max(1) as usize,
+            });
+        }
+        // A newer preview replaces the unread one, so it never fills: the UI holds at most one more
+        stages.push(StageDepth {
+            name: "preview",
+            depth: self.preview.is_unread() as usize,
+            capacity: 2,
+        });
+        if let Some(sink) = &self.frame_writer {
+            stages.push(StageDepth {
+                name: "recorder",
+                depth: sink.depth(),
+                capacity: sink.capacity(),
+            });
+        }
+        if let Some(sink) = &self.frame_publisher {
+            stages.push(StageDepth {
+                name: "publisher",
+                depth: sink.depth(),
+                capacity: sink.capacity(),
+            });
+        }
+        if let Some(sink) = &self.mcap {
+            stages.push(StageDepth {
+                name: "mcap",
+                depth: sink.depth(),
+                capacity: sink.capacity(),
+            });
+        }
+        let change = self.depth.observe(&stages);
+        self.reports.depth_report = self.depth.to_string();
+        let Some(change) = change else {
+            return Ok(());
+        };
+        let kind = if change.exceeded { "pipeline_depth_exceeded" } else { "pipeline_depth_ok" };
+        let stages = change.stages.iter().map(StageDepth::to_string).collect::<Vec<_>>().join(", ");
+        self.status.line(
+            kind,
+            format_args!("{}{}", if change.exceeded { "WARNING: " } else { "" }, change),
+            &[("total", change.total.into()), ("limit", change.limit.into()), ("stages", stages.as_str().into())],
+        );
+        if let Some(telemetry) = &self.telemetry {
+            telemetry.publish(kind, &change.to_string())?;
+        }
+        self.event_log.event(kind, change.exceeded, &[("total", change.total.to_string()), ("limit", change.limit.to_string()), ("stages", stages)])?;
+        Ok(())
+    }
+
+    // UI time per frame with the paint statistics, while there is a preview
+    fn report_ui_budget(&mut self) {
+        if self.ui_budget.paint.count() == 0 {
+            return;
+        }
+        self.reports.ui_report = self.ui_budget.report(self.status.display_unit());
+        self.status.line(
+            "ui_budget",
+            format_args!("{}", self.reports.ui_report),
+            &[
+                ("convert_p95_ms", self.ui_budget.convert.percentile(95.0).into()),
+                ("upload_p95_ms", self.ui_budget.upload.percentile(95.0).into()),
+                ("paint_p50_ms", self.ui_budget.paint.percentile(50.0).into()),
+                ("paint_p95_ms", self.ui_budget.paint.percentile(95.0).into()),
+                ("share_pct", self.ui_budget.share_pct().into()),
+                ("frame_interval_ms", self.ui_budget.frame_interval_ms().into()),
+                ("paints", self.ui_budget.paint.count().into()),
+                ("warnings", self.ui_budget.warnings.into()),
+            ],
+        );
+    }
+
+    // Steps down the degradation ladder while frames are overloaded and back up once they are not; changes are reported and published
+    fn update_degradation(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let deadline_missed = std::mem::take(&mut self.deadline_missed);
+        let overload = if self.depth.is_exceeded() {
+            Some("pipeline depth")
+        } else if deadline_missed {
+            Some("missed deadline")
+        } else {
+            None
+        };
+        let change = self.degradation.observe(overload);
+        self.reports.degradation_report = if self.degradation.changes() > 0 { self.degradation.to_string() } else { String::new() };
+        let Some(change) = change else {
+            return Ok(());
+        };
+        self.trigger_gaps.set_frame_step((self.skip_ratio * self.degradation.skip_factor()) as u64);
+        self.report_degradation(&change)?;
+        // The UI shows the new level even without preview frames
+        self.repaint_now();
+        Ok(())
+    }
+
+    // The current level with the latency report, so late monitors see it without waiting for a change
+    fn report_degradation_level(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.degradation.config().max == Level::Full {
+            return Ok(());
+        }
+        let degraded = self.degradation.degraded;
+        self.status.line(
+            "degradation_level",
+            format_args!("{}", self.degradation),
+            &[
+                ("level", self.degradation.level().index().into()),
+                ("name", self.degradation.level().name().into()),
+                ("max", self.degradation.config().max.name().into()),
+                ("changes", self.degradation.changes().into()),
+                ("previews", degraded.previews.into()),
+                ("recordings", degraded.recordings.into()),
+                ("skipped", degraded.skipped.into()),
+                ("shed", degraded.shed.into()),
+            ],
+        );
+        if let Some(telemetry) = &self.telemetry {
+            telemetry.publish("degradation", &self.degradation.to_string())?;
+        }
+        Ok(())
+    }
+
+    fn report_degradation(&mut self, change: &LevelChange) -> Result<(), Box<dyn std::error::Error>> {
+        let cause = change.cause.unwrap_or("");
+        self.status.line(
+            "degradation",
+            format_args!("{}{}", if change.is_escalation() { "WARNING: " } else { "" }, change),
+            &[
+                ("level", change.to.index().into()),
+                ("name", change.to.name().into()),
+                ("previous", change.from.name().into()),
+                ("cause", change.cause.into()),
+                ("frames", change.frames.into()),
+            ],
+        );
+        if let Some(telemetry) = &self.telemetry {
+            telemetry.publish("degradation", &format!("{}\n{}", self.degradation, change))?;
+        }
+        self.event_log.event(
+            "degradation",
+            change.is_escalation(),
+            &[("level", change.to.index().to_string()), ("name", change.to.name().to_string()), ("previous", change.from.name().to_string()), ("cause", cause.to_string())],
+        )?;
+        Ok(())
+    }
+
+    // Reports failures of the recorder and publisher threads, and buffers held so long that the pool grows
+    fn check_frame_consumers(&mut self, pool: Option<PoolStats>) -> Result<(), Box<dyn std::error::Error>> {
+        let mut failures = Vec::new();
+        if let Some(sink) = &self.frame_writer {
+            failures.extend(sink.failures().into_iter().map(|error| (sink.name().to_string(), error)));
+        }
+        if let Some(sink) = &self.frame_publisher {
+            failures.extend(sink.failures().into_iter().map(|error| (sink.name().to_string(), error)));
+        }
+        if let Some(sink) = &self.mcap {
+            failures.extend(sink.failures().into_iter().map(|error| (sink.name().to_string(), error)));
+        }
+        for (sink, error) in failures {
+            self.status.line("frame_sink_failed", format_args!("WARNING: {} failed: {}", sink, error), &[("sink", sink.as_str().into()), ("error", error.as_str().into())]);
+            self.event_log.event("frame_sink_failed", true, &[("sink", sink), ("error", error)])?;
+        }
+        let Some(pool) = pool.filter(|pool| pool.allocated > pool.capacity as u64 && pool.allocated > self.pool_allocated) else {
+            return Ok(());
+        };
+        self.pool_allocated = pool.allocated;
+        self.status.line(
+            "frame_pool",
+            format_args!("WARNING: Frame pool grew to {} buffers, consumers hold frames too long ({})", pool.allocated, pool),
+            &[
+                ("capacity", pool.capacity.into()),
+                ("in_use", pool.in_use.into()),
+                ("free", pool.free.into()),
+                ("allocated", pool.allocated.into()),
+                ("reused", pool.reused.into()),
+            ],
+        );
+        Ok(())
+    }
+
+    fn report_stall(&mut self, stall: &Stall) -> Result<(), Box<dyn std::error::Error>> {
+        self.reports.sync_info = stall.to_string();
+        self.status.line(
+            "camera_stall",
+            format_args!("WARNING: {}", stall),
+            &[
+                ("camera", self.camera_selector.to_string().into()),
+                ("silent_ns", stall.silent_ns.into()),
+                ("missed_triggers", stall.missed_triggers.into()),
+                ("attempt", stall.attempt.into()),
+                ("restart", self.stall.config().restart.into()),
+            ],
+        );
+        if let Some(telemetry) = &self.telemetry {
+            telemetry.publish("camera_stall", &stall.to_string())?;
+        }
+        self.event_log.event(
+            "camera_stall",
+            true,
+            &[("camera", self.camera_selector.to_string()), ("silent_ns", stall.silent_ns.to_string()), ("missed_triggers", stall.missed_triggers.to_string())],
+        )?;
+        Ok(())
+    }
+
+    fn report_resumed(&mut self, resumed: &Resumed) -> Result<(), Box<dyn std::error::Error>> {
+        self.reports.sync_info = resumed.to_string();
+        self.status.line(
+            "camera_resumed",
+            format_args!("{}", resumed),
+            &[
+                ("camera", self.camera_selector.to_string().into()),
+                ("downtime_ns", resumed.downtime_ns.into()),
+                ("restarts", resumed.restarts.into()),
+                ("missed_triggers", resumed.missed_triggers.into()),
+            ],
+        );
+        if let Some(telemetry) = &self.telemetry {
+            telemetry.publish("camera_resumed", &resumed.to_string())?;
+        }
+        self.event_log.event("camera_resumed", false, &[("camera", self.camera_selector.to_string()), ("downtime_ns", resumed.downtime_ns.to_string())])?;
+        Ok(())
+    }
+
+    fn report_missed_deadline(&mut self, missed: MissedDeadline) -> Result<(), Box<dyn std::error::Error>> {
+        self.deadline_missed = true;
+        self.status.line(
+            "deadline_missed",
+            format_args!("{}", missed.summary(self.status.display_unit())),
+            &[
+                ("section", missed.section.into()),
+                ("thread", missed.thread.as_str().into()),
+                ("duration_ns", missed.duration_ns.into()),
+                ("budget_ns", missed.budget_ns.into()),
+                ("missed", missed.missed.into()),
+            ],
+        );
+        if let Some(telemetry) = &self.telemetry {
+            telemetry.publish("deadline_missed", &missed.summary(self.status.display_unit()))?;
+        }
+        self.event_log.event(
+            "deadline_missed",
+            true,
+            &[
+                ("section", missed.section.to_string()),
+                ("thread", missed.thread.clone()),
+                ("duration_ns", missed.duration_ns.to_string()),
+                ("budget_ns", missed.budget_ns.to_string()),
+            ],
+        )?;
+        self.reports.deadline_report = format!("Deadlines: {} | {}", self.loop_deadline.summary(self.status.display_unit()), self.match_deadline.summary(self.status.display_unit()));
+        Ok(())
+    }
+
+    // Driver drop counters, followed by the trigger id gaps once there are any; takes the
+    // fields so the capture loop can call it while the camera is borrowed
+    fn drops_summary(drops: &DropCounters, trigger_gaps: &TriggerGaps) -> String {
+        if trigger_gaps.has_loss() {
+            format!("{} | {}", drops, trigger_gaps)
+        } else {
+            drops.to_string()
+        }
+    }
+
+    // Explains growing numbers of unmatched frames
+    fn run_diagnosis(&mut self) -> std::io::Result<()> {
+        // Tells transport loss apart from association failures
+        self.reports.drops_report = Self::drops_summary(&self.drops, &self.trigger_gaps);
+        if let Some(diagnosis) = self.diagnostics.check(self.trigger_rate.estimate().as_ref()) {
+            self.reports.diagnosis_report = diagnosis.to_string();
+            let causes: Vec<String> = diagnosis.causes.iter().map(ToString::to_string).collect();
+            self.status.line(
+                "diagnosis",
+                format_args!("{}", self.reports.diagnosis_report),
+                &[("unmatched_pct", diagnosis.unmatched_pct.into()), ("causes", causes.join("; ").into())],
+            );
+            let drops = &self.drops;
+            self.status.line(
+                "drops",
+                format_args!("{}", self.reports.drops_report),
+                &[
+                    ("frames", drops.frames.into()),
+                    ("sequence_gaps", drops.sequence_gaps.into()),
+                    ("lost_frames", drops.lost_frames.into()),
+                    ("error_frames", drops.error_frames.into()),
+                    ("matched", drops.matched.into()),
+                    ("unmatched_transport", drops.unmatched_transport.into()),
+                    ("unmatched_association", drops.unmatched_association.into()),
+                    ("missing_triggers", self.trigger_gaps.missing_triggers.into()),
+                    ("trigger_gaps", self.trigger_gaps.gaps.into()),
+                    ("frames_lost_between_matches", self.trigger_gaps.lost_frames.into()),
+                ],
+            );
+            self.event_log.event("diagnosis", true, &[("report", self.reports.diagnosis_report.clone()), ("drops", self.reports.drops_report.clone())])?;
+        }
+        Ok(())
+    }
+
+    // Logs when the preview starts or stops eating into the frame budget
+    fn update_ui_budget(&mut self) -> std::io::Result<()> {
+        let warning = self.ui_budget.check(self.status.display_unit());
+        if warning.is_some() != self.reports.ui_warning.is_some() {
+            match &warning {
+                Some(warning) => {
+                    self.status.line(
+                        "ui_budget_exceeded",
+                        format_args!("WARNING: {}", warning),
+                        &[("share_pct", self.ui_budget.share_pct().into()), ("ui_ms", self.ui_budget.ui_ms_per_frame().into()), ("frame_interval_ms", self.ui_budget.frame_interval_ms().into())],
+                    );
+                    self.event_log.event("ui_budget_exceeded", true, &[("detail", warning.clone())])?;
+                }
+                None => {
+                    self.status.line(
+                        "ui_budget_ok",
+                        format_args!("Preview UI back within its share of the frame budget"),
+                        &[("share_pct", self.ui_budget.share_pct().into())],
+                    );
+                    self.event_log.event("ui_budget_ok", false, &[])?;
+                }
+            }
+        }
+        self.reports.ui_warning = warning;
+        Ok(())
+    }
+
+    fn update_trigger_rate(&mut self) -> std::io::Result<()> {
+        if let Some(estimate) = self.trigger_rate.estimate() {
+            self.reports.rate_info = estimate.summary(self.status.display_unit());
+        }
+        let warning = self.trigger_rate.check(self.camera_fps as f64, self.rate_tolerance_pct);
+        // Log only when a mismatch appears or clears
+        if warning.is_some() != self.reports.rate_warning.is_some() {
+            match &warning {
+                Some(warning) => {
+                    self.status.line("rate_mismatch", format_args!("WARNING: {}", warning), &[("detail", warning.as_str().into())]);
+                    self.event_log.event("rate_mismatch", true, &[("detail", warning.clone())])?;
+                }
+                None => {
+                    self.status.line(
+                        "rate_ok",
+                        format_args!("Trigger rate back within {:.1}% of {}fps", self.rate_tolerance_pct, self.camera_fps),
+                        &[("tolerance_pct", self.rate_tolerance_pct.into()), ("expected_fps", self.camera_fps.into())],
+                    );
+                    self.event_log.event("rate_ok", false, &[])?;
+                }
+            }
+        }
+        self.reports.rate_warning = warning;
+        Ok(())
+    }
+
+
+    // The camera selector names the stream, so cameras can share one state file
+    fn restore_matcher_state(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(path) = &self.matcher_state else {
+            return Ok(());
+        };
+        let stream = self.camera_selector.to_string();
+        self.matcher_saved_ns = clock::realtime_now_ns();
+        match matcher_state::restore(path, &stream, &mut self.pending_triggers, self.matcher_saved_ns)? {
+            Some(snapshot) => self.status.line(
+                "matcher_state",
+                format_args!(
+                    "Restored matcher state of camera {} from {}: latency {:.1}ms, tolerance {:.1}ms",
+                    stream,
+                    path.display(),
+                    snapshot.latency_ms().unwrap_or_default(),
+                    self.pending_triggers.tolerance_ms()
+                ),
+                &[
+                    ("stream", stream.as_str().into()),
+                    ("restored", true.into()),
+                    ("latency_ms", snapshot.latency_ms().into()),
+                    ("tolerance_ms", self.pending_triggers.tolerance_ms().into()),
+                    ("offsets", snapshot.offsets_ns.len().into()),
+                ],
+            ),
+            None => self.status.line(
+                "matcher_state",
+                format_args!("No matcher state of camera {} in {}, learning from the first matches", stream, path.display()),
+                &[("stream", stream.as_str().into()), ("restored", false.into()), ("tolerance_ms", self.pending_triggers.tolerance_ms().into())],
+            ),
+        }
+        Ok(())
+    }
+
+    // A failed save only costs the warm restart, capture goes on
+    fn save_matcher_state(&mut self, now_ns: u64) {
+        let Some(path) = &self.matcher_state else {
+            return;
+        };
+        self.matcher_saved_ns = now_ns;
+        if let Err(e) = MatcherState::save(path, &self.camera_selector.to_string(), self.pending_triggers.snapshot(), now_ns) {
+            self.status.line(
+                "matcher_state_failed",
+                format_args!("WARNING: could not save the matcher state to {}: {}", path.display(), e),
+                &[("path", path.display().to_string().into()), ("error", e.to_string().into())],
+            );
+        }
+    }
+
+    // Counters of the run so far, for the batch report and the stats snapshot
+    fn results(&self) -> BatchResults {
+        BatchResults {
+            triggers: self.received_triggers,
+            frames: self.processed_frames,
+            matched: self.latency.count() + self.disturbed_matches,
+            drops: self.dropped_triggers + self.trigger_gaps.missing_triggers + self.drops.lost_frames,
+            deadline_misses: self.loop_deadline.counts().1 + self.match_deadline.counts().1,
+            pattern_mismatches: self.pattern_mismatches,
+        }
+    }
+
+    // Ends a `--duration` batch run: final report and the pass/fail exit code, None without a batch run
+    fn finish_batch(&mut self) -> Option<i32> {
+        let batch = self.batch.take()?;
+        self.flush_outputs();
+        match batch.finish(&self.results(), &self.latency) {
+            Ok(code) => Some(code),
+            Err(e) => {
+                eprintln!("Error: could not write the batch report: {}", e);
+                Some(batch::EXIT_FAIL)
+            }
+        }
+    }
+
+    // SIGINT or SIGTERM without a window: a batch run reports what it has, otherwise the outputs are flushed
+    fn finish_headless(&mut self) -> i32 {
+        if let Some(code) = self.finish_batch() {
+            return code;
+        }
+        self.flush_outputs();
+        self.status.line(
+            "shutdown",
+            format_args!("Stopped after {} frames, {} matched", self.processed_frames, self.drops.matched),
+            &[("frames", self.processed_frames.into()), ("matched", self.drops.matched.into())],
+        );
+        0
+    }
+
+    // Flushes the session records, the MCAP recording and the matcher state, and removes the stats snapshot before exiting
+    fn flush_outputs(&mut self) {
+        self.recorder = None;
+        self.mcap = None;
+        self.shared_stats = None;
+        self.save_matcher_state(clock::realtime_now_ns());
+        self.glass_to_glass = None;
+        self.display_clock = None;
+        if let Some((run_summary, runs_dir)) = &mut self.run_summary {
+            run_summary.update(self.processed_frames, self.latency.count(), &self.latency);
+            if let Err(e) = run_summary.write(runs_dir) {
+                eprintln!("Error: could not write the run summary: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn throttle(line: &str) -> RepaintThrottle {
+        let line = format!("v4l2_capture {}", line);
+        RepaintThrottle::from_args(&Args::parse(line.split_whitespace().map(str::to_string), SWITCHES))
+    }
+
+    #[test]
+    fn repaints_follow_the_preview_rate() {
+        let mut repaint = throttle("--preview-fps 20");
+        assert_eq!(repaint.interval, Duration::from_millis(50));
+        let start = Instant::now();
+        assert_eq!(repaint.delay(start, false), Duration::ZERO);
+        assert_eq!(repaint.delay(start + Duration::from_millis(20), false), Duration::from_millis(30));
+        // Glass-to-glass repaints after every frame
+        assert_eq!(repaint.delay(start + Duration::from_millis(30), true), Duration::ZERO);
+        assert_eq!(repaint.delay(start + Duration::from_millis(80), false), Duration::ZERO);
+
+        let mut every_frame = throttle("--preview-fps 0");
+        assert_eq!(every_frame.interval, Duration::ZERO);
+        assert_eq!(every_frame.delay(Instant::now(), false), Duration::ZERO);
+        assert_eq!(throttle("").interval, Duration::from_secs_f64(1.0 / 30.0));
+    }
+
+    #[test]
+    fn frames_are_published_next_to_the_matched_trigger_stream() {
+        let options = |line: &str| {
+            let line = format!("v4l2_capture {}", line);
+            Options::from_args(&Args::parse(line.split_whitespace().map(str::to_string), SWITCHES), "0").unwrap()
+        };
+        assert_eq!(options("").frames_service(3), None);
+        assert_eq!(options("--publish-frames auto").frames_service(3).as_deref(), Some("Camera/Frames/cam3"));
+        assert_eq!(options("--publish-frames auto --trigger-camera 1").frames_service(3).as_deref(), Some("Camera/Frames/cam1"));
+        assert_eq!(options("--publish-frames Lab/Frames").frames_service(3).as_deref(), Some("Lab/Frames"));
+    }
+
+    #[test]
+    fn driver_matched_frames_take_their_time_from_the_driver() {
+        const MS: u64 = 1_000_000;
+        // Exposure at 1000ms, published 0.2ms later; the driver stamped the frame at 1004ms (monotonic 4ms)
+        // and the worker dequeued it at 1010ms
+        let trigger = CameraTrigger::new(7, 1_000 * MS, 1_000 * MS + MS / 5);
+        let (driver_ns, dequeue_ns, realtime_minus_monotonic_ns) = (Some(4 * MS), 1_010 * MS, 1_000 * MS as i64);
+        let frame_ns = FrameTimestamp::Driver.match_ns(driver_ns, None, dequeue_ns, realtime_minus_monotonic_ns);
+        assert_eq!(frame_ns, 1_004 * MS);
+        let latency = FrameLatency::of(&trigger, frame_ns, dequeue_ns);
+        assert_eq!((latency.trigger_type, latency.total_latency_ms), ("PAST", 4.0));
+        // The V4L2 delay stays the dequeue time after the publish
+        assert_eq!(latency.v4l2_delay_ms, 9.8);
+
+        // A trigger between the driver and the dequeue time lies in the frame's future
+        let later = CameraTrigger::new(8, 1_006 * MS, 1_006 * MS);
+        assert_eq!(FrameLatency::of(&later, frame_ns, dequeue_ns).trigger_type, "FUTURE");
+        assert_eq!(FrameLatency::of(&later, frame_ns, dequeue_ns).total_latency_ms, -2.0);
+        // Dequeue mode keeps the dequeue time, the same trigger is then in the past
+        let dequeue_frame_ns = FrameTimestamp::Dequeue.match_ns(driver_ns, None, dequeue_ns, realtime_minus_monotonic_ns);
+        assert_eq!(FrameLatency::of(&later, dequeue_frame_ns, dequeue_ns), FrameLatency { trigger_type: "PAST", total_latency_ms: 4.0, v4l2_delay_ms: 4.0 });
+    }
+}
@@ -0,0 +1,59 @@
+// The `Motion/Encoder` service.
+//
+// An encoder counter publishes its accumulated tick count, timestamped in the
+// trigger clock domain; camera processes interpolate it at the trigger
+// timestamps of their frames.
+
+use iceoryx2::port::publisher::Publisher;
+use iceoryx2::port::subscriber::Subscriber;
+use iceoryx2::prelude::*;
+use sync_core::encoder::EncoderSample;
+
+pub const ENCODER_SERVICE: &str = "Motion/Encoder";
+
+type EncoderService = iceoryx2::service::port_factory::publish_subscribe::PortFactory<ipc::Service, EncoderSample, ()>;
+
+fn open_service(node: &Node<ipc::Service>) -> Result<EncoderService, Box<dyn std::error::Error>> {
+    let service = node
+        .service_builder(&ENCODER_SERVICE.try_into()?)
+        .publish_subscribe::<EncoderSample>()
+        .enable_safe_overflow(true)
+        // Encoder counters report at kHz rates, drained once per camera loop
+        .subscriber_max_buffer_size(1024)
+        .max_subscribers(8)
+        .max_publishers(1)
+        .open_or_create()?;
+    Ok(service)
+}
+
+pub struct EncoderPublisher {
+    publisher: Publisher<ipc::Service, EncoderSample, ()>,
+}
+
+impl EncoderPublisher {
+    pub fn create(node: &Node<ipc::Service>) -> Result<Self, Box<dyn std::error::Error>> {
+        let publisher = open_service(node)?.publisher_builder().create()?;
+        Ok(Self { publisher })
+    }
+
+    pub fn publish(&self, sample: EncoderSample) -> Result<(), Box<dyn std::error::Error>> {
+        self.publisher.send_copy(sample)?;
+        Ok(())
+    }
+}
+
+pub struct EncoderSubscriber {
+    subscriber: Subscriber<ipc::Service, EncoderSample, ()>,
+}
+
+impl EncoderSubscriber {
+    pub fn create(node: &Node<ipc::Service>) -> Result<Self, Box<dyn std::error::Error>> {
+        let subscriber = open_service(node)?.subscriber_builder().create()?;
+        Ok(Self { subscriber })
+    }
+
+    /// Returns the next encoder sample, if any.
+    pub fn receive(&self) -> Result<Option<EncoderSample>, Box<dyn std::error::Error>> {
+        Ok(self.subscriber.receive()?.map(|sample| *sample))
+    }
+}
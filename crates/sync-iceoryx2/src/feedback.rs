@@ -0,0 +1,62 @@
+// The `Camera/Feedback` service.
+//
+// Trigger consumers publish a `ReceiveFeedback` for every canary and sampled
+// trigger they receive; the trigger publisher subscribes, reports which
+// consumers got each canary through and keeps their delivery latency (see
+// `sync_core::canary` and `sync_core::feedback`).
+
+use iceoryx2::port::publisher::Publisher;
+use iceoryx2::port::subscriber::Subscriber;
+use iceoryx2::prelude::*;
+use sync_core::feedback::ReceiveFeedback;
+
+pub const FEEDBACK_SERVICE: &str = "Camera/Feedback";
+
+type FeedbackService = iceoryx2::service::port_factory::publish_subscribe::PortFactory<ipc::Service, ReceiveFeedback, ()>;
+
+fn open_service(node: &Node<ipc::Service>) -> Result<FeedbackService, Box<dyn std::error::Error>> {
+    let service = node
+        .service_builder(&FEEDBACK_SERVICE.try_into()?)
+        .publish_subscribe::<ReceiveFeedback>()
+        .enable_safe_overflow(true)
+        // Old feedback is past every deadline
+        .history_size(0)
+        .subscriber_max_buffer_size(64)
+        // Every trigger consumer reports
+        .max_publishers(16)
+        .max_subscribers(2)
+        .open_or_create()?;
+    Ok(service)
+}
+
+pub struct FeedbackPublisher {
+    publisher: Publisher<ipc::Service, ReceiveFeedback, ()>,
+}
+
+impl FeedbackPublisher {
+    pub fn create(node: &Node<ipc::Service>) -> Result<Self, Box<dyn std::error::Error>> {
+        let publisher = open_service(node)?.publisher_builder().create()?;
+        Ok(Self { publisher })
+    }
+
+    pub fn publish(&self, feedback: ReceiveFeedback) -> Result<(), Box<dyn std::error::Error>> {
+        self.publisher.send_copy(feedback)?;
+        Ok(())
+    }
+}
+
+pub struct FeedbackSubscriber {
+    subscriber: Subscriber<ipc::Service, ReceiveFeedback, ()>,
+}
+
+impl FeedbackSubscriber {
+    pub fn create(node: &Node<ipc::Service>) -> Result<Self, Box<dyn std::error::Error>> {
+        let subscriber = open_service(node)?.subscriber_builder().create()?;
+        Ok(Self { subscriber })
+    }
+
+    /// Returns the next feedback, if any.
+    pub fn receive(&self) -> Result<Option<ReceiveFeedback>, Box<dyn std::error::Error>> {
+        Ok(self.subscriber.receive()?.map(|sample| *sample))
+    }
+}
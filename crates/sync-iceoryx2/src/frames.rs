@@ -0,0 +1,105 @@
+// Frame stream services.
+//
+// A frame stream carries raw frame bytes as a slice payload with a
+// `FrameHeader` user header (timestamp, size, FourCC, matched trigger).
+// Pipelines that cannot be modified publish their unsynchronized frames on
+// one (`Camera/Frames/raw` by default); `retime_proxy` republishes them with
+// trigger timestamps on another (`Camera/Frames/synced`). `v4l2_capture
+// --publish-frames auto` publishes a camera's matched frames on a stream of
+// its own, `Camera/Frames/cam<N>` next to its trigger stream `Camera/Sync/cam<N>`.
+
+use iceoryx2::port::publisher::Publisher;
+use iceoryx2::port::subscriber::Subscriber;
+use iceoryx2::prelude::*;
+use sync_core::retime::FrameHeader;
+use sync_core::sensor;
+
+pub const RAW_FRAMES_SERVICE: &str = "Camera/Frames/raw";
+pub const SYNCED_FRAMES_SERVICE: &str = "Camera/Frames/synced";
+
+/// Matched frames of camera `camera_index`, e.g. `Camera/Frames/cam0`.
+pub fn camera_service(camera_index: u32) -> String {
+    format!("Camera/Frames/{}", sensor::camera_name(camera_index))
+}
+
+/// Stream `--publish-frames <value>` publishes camera `camera_index`'s matched frames on: with
+/// `auto` the one of the camera whose triggers are matched (`trigger_camera`, else its own),
+/// otherwise the service named.
+pub fn publish_service(value: &str, camera_index: u32, trigger_camera: Option<u32>) -> String {
+    match value {
+        "auto" => camera_service(trigger_camera.unwrap_or(camera_index)),
+        service_name => service_name.to_string(),
+    }
+}
+
+/// Initial slice length: one 1080p RGB frame, grown on demand.
+const INITIAL_FRAME_LEN: usize = 1920 * 1080 * 3;
+
+type FrameService = iceoryx2::service::port_factory::publish_subscribe::PortFactory<ipc::Service, [u8], FrameHeader>;
+
+fn open_service(node: &Node<ipc::Service>, service_name: &str) -> Result<FrameService, Box<dyn std::error::Error>> {
+    let service = node
+        .service_builder(&service_name.try_into()?)
+        .publish_subscribe::<[u8]>()
+        .user_header::<FrameHeader>()
+        // Slow consumers lose old frames rather than stalling the source
+        .enable_safe_overflow(true)
+        .subscriber_max_buffer_size(4)
+        .history_size(0)
+        .max_publishers(1)
+        .max_subscribers(4)
+        .open_or_create()?;
+    Ok(service)
+}
+
+pub struct FramePublisher {
+    publisher: Publisher<ipc::Service, [u8], FrameHeader>,
+}
+
+impl FramePublisher {
+    pub fn create(node: &Node<ipc::Service>, service_name: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let publisher = open_service(node, service_name)?
+            .publisher_builder()
+            .initial_max_slice_len(INITIAL_FRAME_LEN)
+            .allocation_strategy(AllocationStrategy::PowerOfTwo)
+            .create()?;
+        Ok(Self { publisher })
+    }
+
+    pub fn publish(&self, header: FrameHeader, data: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        let mut sample = self.publisher.loan_slice_uninit(data.len())?;
+        *sample.user_header_mut() = header;
+        sample.write_from_slice(data).send()?;
+        Ok(())
+    }
+}
+
+pub struct FrameSubscriber {
+    subscriber: Subscriber<ipc::Service, [u8], FrameHeader>,
+}
+
+impl FrameSubscriber {
+    pub fn create(node: &Node<ipc::Service>, service_name: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let subscriber = open_service(node, service_name)?.subscriber_builder().create()?;
+        Ok(Self { subscriber })
+    }
+
+    /// Hands the next frame to `f` without copying it; `None` if there is none.
+    pub fn receive<R>(&self, f: impl FnOnce(&FrameHeader, &[u8]) -> R) -> Result<Option<R>, Box<dyn std::error::Error>> {
+        Ok(self.subscriber.receive()?.map(|sample| f(sample.user_header(), sample.payload())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn auto_publishes_on_the_stream_of_the_matched_camera() {
+        assert_eq!(camera_service(0), "Camera/Frames/cam0");
+        assert_eq!(publish_service("auto", 2, None), "Camera/Frames/cam2");
+        // Matched against another camera's triggers: its frames go next to that trigger stream
+        assert_eq!(publish_service("auto", 2, Some(1)), "Camera/Frames/cam1");
+        assert_eq!(publish_service(SYNCED_FRAMES_SERVICE, 2, Some(1)), "Camera/Frames/synced");
+    }
+}
@@ -0,0 +1,84 @@
+// The `Camera/SyncHistory` request-response service.
+//
+// The trigger publisher serves its trigger ring (`sync_core::history`): a
+// client sends a realtime timestamp T in ns and receives one response with
+// all triggers since T that are still in the ring. Consumers that attach
+// mid-run use it to backfill beyond the trigger service's history.
+
+use iceoryx2::port::client::Client;
+use iceoryx2::port::server::Server;
+use iceoryx2::prelude::*;
+use std::time::{Duration, Instant};
+use sync_core::history::{HistoryEntry, TriggerHistory, DEFAULT_CAPACITY};
+use sync_core::units::Nanos;
+
+pub const HISTORY_SERVICE: &str = "Camera/SyncHistory";
+
+type HistoryService = iceoryx2::service::port_factory::request_response::PortFactory<ipc::Service, u64, (), [HistoryEntry], ()>;
+
+fn open_service(node: &Node<ipc::Service>) -> Result<HistoryService, Box<dyn std::error::Error>> {
+    let service = node
+        .service_builder(&HISTORY_SERVICE.try_into()?)
+        .request_response::<u64, [HistoryEntry]>()
+        // The trigger publisher
+        .max_servers(1)
+        // Analysis tools and late camera processes
+        .max_clients(8)
+        .open_or_create()?;
+    Ok(service)
+}
+
+pub struct TriggerHistoryServer {
+    server: Server<ipc::Service, u64, (), [HistoryEntry], ()>,
+}
+
+impl TriggerHistoryServer {
+    pub fn create(node: &Node<ipc::Service>) -> Result<Self, Box<dyn std::error::Error>> {
+        let server = open_service(node)?
+            .server_builder()
+            .initial_max_slice_len(DEFAULT_CAPACITY)
+            // Rings larger than the default grow the response segment on demand
+            .allocation_strategy(AllocationStrategy::PowerOfTwo)
+            .create()?;
+        Ok(Self { server })
+    }
+
+    /// Answers all pending queries from `history`; returns how many were answered.
+    pub fn serve(&self, history: &TriggerHistory) -> Result<usize, Box<dyn std::error::Error>> {
+        let mut answered = 0;
+        while let Some(request) = self.server.receive()? {
+            let entries: Vec<HistoryEntry> = history.since(Nanos(*request.payload())).copied().collect();
+            let response = request.loan_slice_uninit(entries.len())?;
+            response.write_from_slice(&entries).send()?;
+            answered += 1;
+        }
+        Ok(answered)
+    }
+}
+
+pub struct TriggerHistoryClient {
+    client: Client<ipc::Service, u64, (), [HistoryEntry], ()>,
+}
+
+impl TriggerHistoryClient {
+    pub fn create(node: &Node<ipc::Service>) -> Result<Self, Box<dyn std::error::Error>> {
+        let client = open_service(node)?.client_builder().create()?;
+        Ok(Self { client })
+    }
+
+    /// All triggers since `since` (compared with their hardware timestamp) still in the publisher's ring.
+    pub fn query(&self, since: Nanos, timeout: Duration) -> Result<Vec<HistoryEntry>, Box<dyn std::error::Error>> {
+        let pending = self.client.send_copy(since.0)?;
+        // The publisher answers between triggers
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(response) = pending.receive()? {
+                return Ok(response.payload().to_vec());
+            }
+            if Instant::now() > deadline {
+                return Err("no answer from the trigger history service (is the publisher running?)".into());
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
@@ -0,0 +1,26 @@
+// iceoryx2 services connecting the processes of a camera rig.
+//
+// `Camera/Sync` carries the hardware triggers (with their signature header),
+// `Camera/SyncHistory` answers queries for older triggers, `Lighting/Strobe`
+// the lighting state per trigger, `Motion/Pose` pan-tilt poses,
+// `Motion/Encoder` encoder tick counts, `Camera/Telemetry` low-rate status and
+// metadata, `Camera/Frames/*` frame streams for re-timestamping,
+// `Latency/Photodiode` light edges for glass-to-glass measurements,
+// `Video/Reference` video reference edges for genlock, `Camera/Feedback` the
+// receive times consumers report for canary and sampled triggers and
+// `Camera/GroupLatency` the frame latencies of grouped cameras. Every process
+// creates its node and opens the services through this crate so node names,
+// rig namespaces and QoS settings always agree.
+
+pub mod encoder;
+pub mod feedback;
+pub mod frames;
+pub mod history;
+pub mod node;
+pub mod photodiode;
+pub mod pose;
+pub mod reference;
+pub mod skew;
+pub mod strobe;
+pub mod telemetry;
+pub mod trigger;
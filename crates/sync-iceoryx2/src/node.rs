@@ -0,0 +1,110 @@
+// iceoryx2 nodes named and isolated by rig.
+//
+// `create_node` replaces `NodeBuilder::new().create()` in every process: the
+// node gets the deterministic name of its `NodeIdentity` (see
+// `sync_core::namespace`) and, outside the default rig, the rig's resource
+// prefix. `list_nodes`, `list_services` and `remove_stale_nodes` back the
+// `rig_nodes` tool: a process that crashed leaves its node behind as dead,
+// together with its ports and the services nobody else holds, and removing
+// the dead node releases them. Only nodes of the rig are touched.
+
+use iceoryx2::node::NodeView;
+use iceoryx2::prelude::*;
+use iceoryx2::service::Service;
+use sync_core::namespace::NodeIdentity;
+
+/// A node of the rig found in shared memory.
+#[derive(Debug, Clone)]
+pub struct RigNode {
+    /// `None` if the node's details could not be read.
+    pub name: Option<String>,
+    pub alive: bool,
+}
+
+/// The global iceoryx2 config with the rig's resource prefix.
+pub fn config(identity: &NodeIdentity) -> Result<Config, Box<dyn std::error::Error>> {
+    let mut config = Config::global_config().clone();
+    if let Some(prefix) = identity.resource_prefix() {
+        config.global.prefix = FileName::new(prefix.as_bytes()).map_err(|e| format!("invalid resource prefix {}: {:?}", prefix, e))?;
+    }
+    Ok(config)
+}
+
+/// Creates the process's node under its rig name.
+pub fn create_node(identity: &NodeIdentity) -> Result<Node<ipc::Service>, Box<dyn std::error::Error>> {
+    let name = NodeName::new(identity.name()).map_err(|e| format!("invalid node name {}: {:?}", identity.name(), e))?;
+    let node = NodeBuilder::new().name(&name).config(&config(identity)?).create::<ipc::Service>()?;
+    Ok(node)
+}
+
+// A node whose details are unreadable belongs to the rig only if the rig has its own prefix
+fn owned(identity: &NodeIdentity, name: &Option<String>) -> bool {
+    match name {
+        Some(name) => identity.owns(name),
+        None => identity.resource_prefix().is_some(),
+    }
+}
+
+fn node_name<V: NodeView>(view: &V) -> Option<String> {
+    view.details().as_ref().map(|details| details.name().as_str().to_string())
+}
+
+/// The rig's nodes, alive and dead.
+pub fn list_nodes(identity: &NodeIdentity) -> Result<Vec<RigNode>, Box<dyn std::error::Error>> {
+    let mut nodes = Vec::new();
+    Node::<ipc::Service>::list(&config(identity)?, |state| {
+        let node = match &state {
+            NodeState::Alive(view) => RigNode { name: node_name(view), alive: true },
+            NodeState::Dead(view) => RigNode { name: node_name(view), alive: false },
+            NodeState::Inaccessible(_) | NodeState::Undefined(_) => return CallbackProgression::Continue,
+        };
+        if owned(identity, &node.name) {
+            nodes.push(node);
+        }
+        CallbackProgression::Continue
+    })?;
+    Ok(nodes)
+}
+
+/// Names of the services under the rig's resource prefix (with the default rig, all services of the host).
+pub fn list_services(identity: &NodeIdentity) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut services = Vec::new();
+    ipc::Service::list(&config(identity)?, |service| {
+        services.push(service.static_details.name().as_str().to_string());
+        CallbackProgression::Continue
+    })?;
+    services.sort();
+    Ok(services)
+}
+
+/// The name of a dead node and whether it was removed (`false` if another process cleaned it up first).
+pub type RemovedNode = (Option<String>, bool);
+
+/// Removes the rig's dead nodes with their ports and stale services.
+pub fn remove_stale_nodes(identity: &NodeIdentity) -> Result<Vec<RemovedNode>, Box<dyn std::error::Error>> {
+    let mut removed = Vec::new();
+    let mut failure = None;
+    Node::<ipc::Service>::list(&config(identity)?, |state| {
+        let NodeState::Dead(view) = state else {
+            return CallbackProgression::Continue;
+        };
+        let name = node_name(&view);
+        if !owned(identity, &name) {
+            return CallbackProgression::Continue;
+        }
+        match view.remove_stale_resources() {
+            Ok(done) => {
+                removed.push((name, done));
+                CallbackProgression::Continue
+            }
+            Err(e) => {
+                failure = Some(format!("could not remove node {}: {:?}", name.as_deref().unwrap_or("<unknown>"), e));
+                CallbackProgression::Stop
+            }
+        }
+    })?;
+    match failure {
+        Some(failure) => Err(failure.into()),
+        None => Ok(removed),
+    }
+}
@@ -0,0 +1,52 @@
+// The `Latency/Photodiode` service.
+//
+// A photodiode taped to the glass-to-glass marker publishes the timestamp
+// (trigger clock domain, ns) of every light edge it sees.
+
+use iceoryx2::port::publisher::Publisher;
+use iceoryx2::port::subscriber::Subscriber;
+use iceoryx2::prelude::*;
+
+pub const PHOTODIODE_SERVICE: &str = "Latency/Photodiode";
+
+type PhotodiodeService = iceoryx2::service::port_factory::publish_subscribe::PortFactory<ipc::Service, u64, ()>;
+
+fn open_service(node: &Node<ipc::Service>) -> Result<PhotodiodeService, Box<dyn std::error::Error>> {
+    let service = node
+        .service_builder(&PHOTODIODE_SERVICE.try_into()?)
+        .publish_subscribe::<u64>()
+        .open_or_create()?;
+    Ok(service)
+}
+
+pub struct PhotodiodePublisher {
+    publisher: Publisher<ipc::Service, u64, ()>,
+}
+
+impl PhotodiodePublisher {
+    pub fn create(node: &Node<ipc::Service>) -> Result<Self, Box<dyn std::error::Error>> {
+        let publisher = open_service(node)?.publisher_builder().create()?;
+        Ok(Self { publisher })
+    }
+
+    pub fn publish(&self, edge_ns: u64) -> Result<(), Box<dyn std::error::Error>> {
+        self.publisher.send_copy(edge_ns)?;
+        Ok(())
+    }
+}
+
+pub struct PhotodiodeSubscriber {
+    subscriber: Subscriber<ipc::Service, u64, ()>,
+}
+
+impl PhotodiodeSubscriber {
+    pub fn create(node: &Node<ipc::Service>) -> Result<Self, Box<dyn std::error::Error>> {
+        let subscriber = open_service(node)?.subscriber_builder().create()?;
+        Ok(Self { subscriber })
+    }
+
+    /// Returns the timestamp of the next light edge, if any.
+    pub fn receive(&self) -> Result<Option<u64>, Box<dyn std::error::Error>> {
+        Ok(self.subscriber.receive()?.map(|sample| *sample))
+    }
+}
@@ -0,0 +1,58 @@
+// The `Motion/Pose` service.
+//
+// A pan-tilt unit (or its controller) publishes timestamped pose samples;
+// camera processes interpolate them at the trigger timestamps of their frames.
+
+use iceoryx2::port::publisher::Publisher;
+use iceoryx2::port::subscriber::Subscriber;
+use iceoryx2::prelude::*;
+use sync_core::pose::PoseSample;
+
+pub const POSE_SERVICE: &str = "Motion/Pose";
+
+type PoseService = iceoryx2::service::port_factory::publish_subscribe::PortFactory<ipc::Service, PoseSample, ()>;
+
+fn open_service(node: &Node<ipc::Service>) -> Result<PoseService, Box<dyn std::error::Error>> {
+    let service = node
+        .service_builder(&POSE_SERVICE.try_into()?)
+        .publish_subscribe::<PoseSample>()
+        .enable_safe_overflow(true)
+        // Servo controllers report far faster than the camera frame rate
+        .subscriber_max_buffer_size(256)
+        .max_subscribers(8)
+        .max_publishers(1)
+        .open_or_create()?;
+    Ok(service)
+}
+
+pub struct PosePublisher {
+    publisher: Publisher<ipc::Service, PoseSample, ()>,
+}
+
+impl PosePublisher {
+    pub fn create(node: &Node<ipc::Service>) -> Result<Self, Box<dyn std::error::Error>> {
+        let publisher = open_service(node)?.publisher_builder().create()?;
+        Ok(Self { publisher })
+    }
+
+    pub fn publish(&self, sample: PoseSample) -> Result<(), Box<dyn std::error::Error>> {
+        self.publisher.send_copy(sample)?;
+        Ok(())
+    }
+}
+
+pub struct PoseSubscriber {
+    subscriber: Subscriber<ipc::Service, PoseSample, ()>,
+}
+
+impl PoseSubscriber {
+    pub fn create(node: &Node<ipc::Service>) -> Result<Self, Box<dyn std::error::Error>> {
+        let subscriber = open_service(node)?.subscriber_builder().create()?;
+        Ok(Self { subscriber })
+    }
+
+    /// Returns the next pose sample, if any.
+    pub fn receive(&self) -> Result<Option<PoseSample>, Box<dyn std::error::Error>> {
+        Ok(self.subscriber.receive()?.map(|sample| *sample))
+    }
+}
@@ -0,0 +1,57 @@
+// The `Video/Reference` service.
+//
+// A video reference (house sync, a capture card's vsync) publishes the
+// timestamp (trigger clock domain, ns) of every reference edge, for the
+// publisher's genlock phase measurement.
+
+use iceoryx2::port::publisher::Publisher;
+use iceoryx2::port::subscriber::Subscriber;
+use iceoryx2::prelude::*;
+
+pub const REFERENCE_SERVICE: &str = "Video/Reference";
+
+type ReferenceService = iceoryx2::service::port_factory::publish_subscribe::PortFactory<ipc::Service, u64, ()>;
+
+fn open_service(node: &Node<ipc::Service>) -> Result<ReferenceService, Box<dyn std::error::Error>> {
+    let service = node
+        .service_builder(&REFERENCE_SERVICE.try_into()?)
+        .publish_subscribe::<u64>()
+        .enable_safe_overflow(true)
+        // About a second of edges at 60Hz, the publisher drains them once per trigger
+        .subscriber_max_buffer_size(64)
+        .max_publishers(1)
+        .open_or_create()?;
+    Ok(service)
+}
+
+pub struct ReferencePublisher {
+    publisher: Publisher<ipc::Service, u64, ()>,
+}
+
+impl ReferencePublisher {
+    pub fn create(node: &Node<ipc::Service>) -> Result<Self, Box<dyn std::error::Error>> {
+        let publisher = open_service(node)?.publisher_builder().create()?;
+        Ok(Self { publisher })
+    }
+
+    pub fn publish(&self, edge_ns: u64) -> Result<(), Box<dyn std::error::Error>> {
+        self.publisher.send_copy(edge_ns)?;
+        Ok(())
+    }
+}
+
+pub struct ReferenceSubscriber {
+    subscriber: Subscriber<ipc::Service, u64, ()>,
+}
+
+impl ReferenceSubscriber {
+    pub fn create(node: &Node<ipc::Service>) -> Result<Self, Box<dyn std::error::Error>> {
+        let subscriber = open_service(node)?.subscriber_builder().create()?;
+        Ok(Self { subscriber })
+    }
+
+    /// Returns the timestamp of the next reference edge, if any.
+    pub fn receive(&self) -> Result<Option<u64>, Box<dyn std::error::Error>> {
+        Ok(self.subscriber.receive()?.map(|sample| *sample))
+    }
+}
@@ -0,0 +1,60 @@
+// The `Camera/GroupLatency` service.
+//
+// Cameras started with `--camera-group` publish a `GroupLatency` for every
+// matched frame of a sampled trigger; the trigger publisher subscribes and
+// compares the cameras of each group (see `sync_core::skew`).
+
+use iceoryx2::port::publisher::Publisher;
+use iceoryx2::port::subscriber::Subscriber;
+use iceoryx2::prelude::*;
+use sync_core::skew::GroupLatency;
+
+pub const GROUP_LATENCY_SERVICE: &str = "Camera/GroupLatency";
+
+type GroupLatencyService = iceoryx2::service::port_factory::publish_subscribe::PortFactory<ipc::Service, GroupLatency, ()>;
+
+fn open_service(node: &Node<ipc::Service>) -> Result<GroupLatencyService, Box<dyn std::error::Error>> {
+    let service = node
+        .service_builder(&GROUP_LATENCY_SERVICE.try_into()?)
+        .publish_subscribe::<GroupLatency>()
+        .enable_safe_overflow(true)
+        .history_size(0)
+        .subscriber_max_buffer_size(64)
+        // Every grouped camera reports
+        .max_publishers(16)
+        .max_subscribers(2)
+        .open_or_create()?;
+    Ok(service)
+}
+
+pub struct GroupLatencyPublisher {
+    publisher: Publisher<ipc::Service, GroupLatency, ()>,
+}
+
+impl GroupLatencyPublisher {
+    pub fn create(node: &Node<ipc::Service>) -> Result<Self, Box<dyn std::error::Error>> {
+        let publisher = open_service(node)?.publisher_builder().create()?;
+        Ok(Self { publisher })
+    }
+
+    pub fn publish(&self, latency: GroupLatency) -> Result<(), Box<dyn std::error::Error>> {
+        self.publisher.send_copy(latency)?;
+        Ok(())
+    }
+}
+
+pub struct GroupLatencySubscriber {
+    subscriber: Subscriber<ipc::Service, GroupLatency, ()>,
+}
+
+impl GroupLatencySubscriber {
+    pub fn create(node: &Node<ipc::Service>) -> Result<Self, Box<dyn std::error::Error>> {
+        let subscriber = open_service(node)?.subscriber_builder().create()?;
+        Ok(Self { subscriber })
+    }
+
+    /// Returns the next latency sample, if any.
+    pub fn receive(&self) -> Result<Option<GroupLatency>, Box<dyn std::error::Error>> {
+        Ok(self.subscriber.receive()?.map(|sample| *sample))
+    }
+}
@@ -0,0 +1,61 @@
+// The `Lighting/Strobe` service.
+//
+// The trigger publisher sends the strobe command of every trigger right
+// before the trigger itself. Lighting controllers and camera processes
+// subscribe; the history lets a late camera process pick up the commands of
+// triggers it still has pending.
+
+use iceoryx2::port::publisher::Publisher;
+use iceoryx2::port::subscriber::Subscriber;
+use iceoryx2::prelude::*;
+use sync_core::lighting::StrobeCommand;
+
+pub const STROBE_SERVICE: &str = "Lighting/Strobe";
+
+type StrobeService = iceoryx2::service::port_factory::publish_subscribe::PortFactory<ipc::Service, StrobeCommand, ()>;
+
+fn open_service(node: &Node<ipc::Service>) -> Result<StrobeService, Box<dyn std::error::Error>> {
+    let service = node
+        .service_builder(&STROBE_SERVICE.try_into()?)
+        .publish_subscribe::<StrobeCommand>()
+        .enable_safe_overflow(true)
+        .history_size(10)
+        .subscriber_max_buffer_size(20)
+        // Lighting controllers and camera processes
+        .max_subscribers(8)
+        .max_publishers(1)
+        .open_or_create()?;
+    Ok(service)
+}
+
+pub struct StrobePublisher {
+    publisher: Publisher<ipc::Service, StrobeCommand, ()>,
+}
+
+impl StrobePublisher {
+    pub fn create(node: &Node<ipc::Service>) -> Result<Self, Box<dyn std::error::Error>> {
+        let publisher = open_service(node)?.publisher_builder().create()?;
+        Ok(Self { publisher })
+    }
+
+    pub fn publish(&self, command: StrobeCommand) -> Result<(), Box<dyn std::error::Error>> {
+        self.publisher.send_copy(command)?;
+        Ok(())
+    }
+}
+
+pub struct StrobeSubscriber {
+    subscriber: Subscriber<ipc::Service, StrobeCommand, ()>,
+}
+
+impl StrobeSubscriber {
+    pub fn create(node: &Node<ipc::Service>) -> Result<Self, Box<dyn std::error::Error>> {
+        let subscriber = open_service(node)?.subscriber_builder().create()?;
+        Ok(Self { subscriber })
+    }
+
+    /// Returns the next strobe command, if any.
+    pub fn receive(&self) -> Result<Option<StrobeCommand>, Box<dyn std::error::Error>> {
+        Ok(self.subscriber.receive()?.map(|sample| *sample))
+    }
+}
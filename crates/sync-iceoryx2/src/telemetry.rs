@@ -0,0 +1,73 @@
+// Telemetry channel shared by all processes of a rig.
+//
+// Telemetry messages are UTF-8 text published as byte slices on the
+// `Camera/Telemetry` service. The first line names the message kind
+// (e.g. `manifest`), the rest is the body. Trigger and frame timing stay on
+// their own services; telemetry is for low-rate status and metadata.
+
+use iceoryx2::port::publisher::Publisher;
+use iceoryx2::port::subscriber::Subscriber;
+use iceoryx2::prelude::*;
+
+pub const TELEMETRY_SERVICE: &str = "Camera/Telemetry";
+
+fn open_service(
+    node: &Node<ipc::Service>,
+) -> Result<iceoryx2::service::port_factory::publish_subscribe::PortFactory<ipc::Service, [u8], ()>, Box<dyn std::error::Error>> {
+    let service = node
+        .service_builder(&TELEMETRY_SERVICE.try_into()?)
+        .publish_subscribe::<[u8]>()
+        // Keep recent messages (e.g. session manifests) for late monitors
+        .history_size(16)
+        .subscriber_max_buffer_size(64)
+        .enable_safe_overflow(true)
+        // Every process of a rig may publish telemetry
+        .max_publishers(16)
+        .max_subscribers(8)
+        .open_or_create()?;
+    Ok(service)
+}
+
+pub struct TelemetryPublisher {
+    publisher: Publisher<ipc::Service, [u8], ()>,
+}
+
+impl TelemetryPublisher {
+    pub fn create(node: &Node<ipc::Service>) -> Result<Self, Box<dyn std::error::Error>> {
+        let publisher = open_service(node)?
+            .publisher_builder()
+            .initial_max_slice_len(4096)
+            .allocation_strategy(AllocationStrategy::PowerOfTwo)
+            .create()?;
+        Ok(Self { publisher })
+    }
+
+    pub fn publish(&self, kind: &str, body: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let message = format!("{}\n{}", kind, body);
+        let sample = self.publisher.loan_slice_uninit(message.len())?;
+        let sample = sample.write_from_slice(message.as_bytes());
+        sample.send()?;
+        Ok(())
+    }
+}
+
+pub struct TelemetrySubscriber {
+    subscriber: Subscriber<ipc::Service, [u8], ()>,
+}
+
+impl TelemetrySubscriber {
+    pub fn create(node: &Node<ipc::Service>) -> Result<Self, Box<dyn std::error::Error>> {
+        let subscriber = open_service(node)?.subscriber_builder().create()?;
+        Ok(Self { subscriber })
+    }
+
+    /// Returns the next `(kind, body)` message, if any.
+    pub fn receive(&self) -> Result<Option<(String, String)>, Box<dyn std::error::Error>> {
+        let Some(sample) = self.subscriber.receive()? else {
+            return Ok(None);
+        };
+        let message = String::from_utf8_lossy(sample.payload());
+        let (kind, body) = message.split_once('\n').unwrap_or((&message, ""));
+        Ok(Some((kind.to_string(), body.to_string())))
+    }
+}
@@ -0,0 +1,341 @@
+// The `Camera/Sync` trigger service.
+//
+// One publisher sends a `CameraTrigger` for every hardware trigger (fixed
+// layout and versioned, see `sync_core::layout`; a payload of another
+// version is an error), with the optional ed25519 signature in the
+// user header. Camera processes subscribe and match their frames against the
+// received triggers. What happens to a trigger for a subscriber with a full
+// buffer is set by the publisher (see `sync_core::delivery`). At kHz rates the
+// publisher sends batches of triggers on a second service next to it (see
+// `sync_core::trigger_batch`), which subscribers unpack into single triggers.
+// After every sample the publisher notifies an event service of the same
+// name, so subscribers block in a WaitSet until a trigger arrives instead of
+// polling (`TriggerSubscriber::wait`).
+// The ports implement the transport traits of `sync_core::transport`, which
+// has an in-memory mock of them.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt::Debug;
+use std::time::{Duration, Instant};
+
+use iceoryx2::port::listener::Listener;
+use iceoryx2::port::notifier::Notifier;
+use iceoryx2::port::publisher::Publisher;
+use iceoryx2::port::subscriber::Subscriber;
+use iceoryx2::port::LoanError;
+use iceoryx2::prelude::*;
+use iceoryx2::waitset::{WaitSet, WaitSetBuilder, WaitSetRunResult};
+use sync_core::delivery::{DeliveryCounts, UnableToDeliver};
+use sync_core::loans::{self, LoanStats};
+use sync_core::sensor;
+use sync_core::signing::TriggerSignature;
+use sync_core::transport::{self, TriggerSink, TriggerSource};
+use sync_core::trigger_batch::TriggerBatch;
+use sync_core::CameraTrigger;
+
+pub const TRIGGER_SERVICE: &str = "Camera/Sync";
+
+/// Trigger service of a fanned-out sensor (see `sync_core::sensor`), e.g. `Camera/Sync/radar0`.
+pub fn sensor_service(sensor_name: &str) -> String {
+    format!("{}/{}", TRIGGER_SERVICE, sensor_name)
+}
+
+/// Trigger service of camera `camera_index` in a multi-camera fan-out, e.g. `Camera/Sync/cam0`.
+pub fn camera_service(camera_index: u32) -> String {
+    sensor_service(&sensor::camera_name(camera_index))
+}
+
+/// Trigger service of an independent trigger channel (see `sync_core::channel`), e.g. `Camera/Sync/channel/groupb`.
+pub fn channel_service(channel_name: &str) -> String {
+    format!("{}/channel/{}", TRIGGER_SERVICE, channel_name)
+}
+
+/// Batched triggers of a trigger service, e.g. `Camera/Sync/batched`.
+pub fn batched_service(service_name: &str) -> String {
+    format!("{}/batched", service_name)
+}
+
+type TriggerService<Payload> = iceoryx2::service::port_factory::publish_subscribe::PortFactory<ipc::Service, Payload, TriggerSignature>;
+type NotificationService = iceoryx2::service::port_factory::event::PortFactory<ipc::Service>;
+
+// QoS settings optimized for camera sync, for single (`CameraTrigger`) and
+// batched triggers. Subscribers pass `None` and take the overflow behavior the
+// publisher created the service with.
+fn open_service<Payload: Debug + ZeroCopySend + 'static>(
+    node: &Node<ipc::Service>,
+    service_name: &str,
+    safe_overflow: Option<bool>,
+) -> Result<TriggerService<Payload>, Box<dyn std::error::Error>> {
+    let mut builder = node
+        .service_builder(&service_name.try_into()?)
+        .publish_subscribe::<Payload>()
+        .user_header::<TriggerSignature>();
+    // Safe overflow (the default) keeps trigger bursts from blocking the publisher
+    if let Some(safe_overflow) = safe_overflow {
+        builder = builder.enable_safe_overflow(safe_overflow);
+    }
+    let service = builder
+        // Store recent triggers for late V4L2 frames
+        .history_size(transport::HISTORY_SIZE)
+        // Buffer for trigger bursts
+        .subscriber_max_buffer_size(transport::SUBSCRIBER_BUFFER_SIZE)
+        // Allow multiple camera processes
+        .max_subscribers(transport::MAX_SUBSCRIBERS)
+        // Single trigger publisher
+        .max_publishers(1)
+        .open_or_create()?;
+    Ok(service)
+}
+
+// New-sample notifications of a trigger service: an event service of the same name, which the
+// publish-subscribe service does not clash with
+fn open_notifications(node: &Node<ipc::Service>, service_name: &str) -> Result<NotificationService, Box<dyn std::error::Error>> {
+    Ok(node.service_builder(&service_name.try_into()?).event().open_or_create()?)
+}
+
+// Loans a sample and sends it; the subscribers it reached, `None` if the loan pool was exhausted
+fn send<Payload: Debug + ZeroCopySend + 'static>(
+    publisher: &Publisher<ipc::Service, Payload, TriggerSignature>,
+    loans: &RefCell<LoanStats>,
+    payload: Payload,
+    header: TriggerSignature,
+) -> Result<Option<usize>, Box<dyn std::error::Error>> {
+    let started = Instant::now();
+    let mut sample = match publisher.loan_uninit() {
+        Ok(sample) => sample,
+        Err(LoanError::ExceedsMaxLoans | LoanError::OutOfMemory) => {
+            loans.borrow_mut().record_exhausted();
+            return Ok(None);
+        }
+        Err(e) => return Err(e.into()),
+    };
+    loans.borrow_mut().record_loan(started.elapsed().as_nanos() as f64 / 1e6);
+    *sample.user_header_mut() = header;
+    Ok(Some(sample.write_payload(payload).send()?))
+}
+
+pub struct TriggerPublisher {
+    // Kept for the subscriber counts, which the recipients of a sample are compared against
+    service: TriggerService<CameraTrigger>,
+    batch_service: TriggerService<TriggerBatch>,
+    publisher: Publisher<ipc::Service, CameraTrigger, TriggerSignature>,
+    batch_publisher: Publisher<ipc::Service, TriggerBatch, TriggerSignature>,
+    // Wakes the subscribers after every sample, single or batched
+    notifier: Notifier<ipc::Service>,
+    delivery: RefCell<DeliveryCounts>,
+    loans: RefCell<LoanStats>,
+    max_loaned_samples: usize,
+}
+
+impl TriggerPublisher {
+    pub fn create(node: &Node<ipc::Service>) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::create_for(node, TRIGGER_SERVICE)
+    }
+
+    /// Publishes on another service with the trigger QoS (e.g. an isolated self-test or a sensor fan-out).
+    pub fn create_for(node: &Node<ipc::Service>, service_name: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::create_with(node, service_name, UnableToDeliver::default(), loans::DEFAULT_MAX_LOANS)
+    }
+
+    /// Like `create_for`, with the handling of subscribers whose buffer is full and the loan pool size.
+    pub fn create_with(
+        node: &Node<ipc::Service>,
+        service_name: &str,
+        unable_to_deliver: UnableToDeliver,
+        max_loaned_samples: usize,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let strategy = match unable_to_deliver {
+            UnableToDeliver::Block => UnableToDeliverStrategy::Block,
+            UnableToDeliver::Overflow | UnableToDeliver::Discard => UnableToDeliverStrategy::DiscardSample,
+        };
+        let service = open_service::<CameraTrigger>(node, service_name, Some(unable_to_deliver.safe_overflow()))?;
+        let publisher = service
+            .publisher_builder()
+            .max_loaned_samples(max_loaned_samples) // Handle trigger bursts
+            .unable_to_deliver_strategy(strategy)
+            .create()?;
+        let batch_service = open_service::<TriggerBatch>(node, &batched_service(service_name), Some(unable_to_deliver.safe_overflow()))?;
+        let batch_publisher = batch_service
+            .publisher_builder()
+            .max_loaned_samples(max_loaned_samples)
+            .unable_to_deliver_strategy(strategy)
+            .create()?;
+        let notifier = open_notifications(node, service_name)?.notifier_builder().create()?;
+        Ok(Self {
+            service,
+            batch_service,
+            publisher,
+            batch_publisher,
+            notifier,
+            delivery: RefCell::default(),
+            loans: RefCell::default(),
+            max_loaned_samples,
+        })
+    }
+
+    /// Publishes a trigger; `false` if the loan pool was exhausted and the trigger was dropped.
+    pub fn publish(&self, trigger: CameraTrigger, signature: TriggerSignature) -> Result<bool, Box<dyn std::error::Error>> {
+        let Some(recipients) = send(&self.publisher, &self.loans, trigger, signature)? else {
+            return Ok(false);
+        };
+        self.notifier.notify()?;
+        let mut delivery = self.delivery.borrow_mut();
+        delivery.record_published();
+        // A full buffer without safe overflow takes nothing (discard); blocking sends reach everyone
+        delivery.record_sent(self.service.dynamic_config().number_of_subscribers(), recipients);
+        Ok(true)
+    }
+
+    /// Publishes a batch of triggers in one sample, see `publish`; `header` only carries the timestamp source.
+    pub fn publish_batch(&self, batch: &TriggerBatch, header: TriggerSignature) -> Result<bool, Box<dyn std::error::Error>> {
+        let Some(recipients) = send(&self.batch_publisher, &self.loans, *batch, header)? else {
+            return Ok(false);
+        };
+        self.notifier.notify()?;
+        let mut delivery = self.delivery.borrow_mut();
+        delivery.published += batch.len() as u64;
+        let subscribers = self.batch_service.dynamic_config().number_of_subscribers();
+        delivery.record_undelivered(subscribers.saturating_sub(recipients) as u64 * batch.len() as u64);
+        Ok(true)
+    }
+
+    pub fn max_loaned_samples(&self) -> usize {
+        self.max_loaned_samples
+    }
+
+    /// Loans so far, with the exhaustion events.
+    pub fn loans(&self) -> LoanStats {
+        self.loans.borrow().clone()
+    }
+
+    /// Triggers published so far and the ones subscribers could not take.
+    pub fn delivery(&self) -> DeliveryCounts {
+        self.delivery.borrow().clone()
+    }
+}
+
+impl TriggerSink for TriggerPublisher {
+    fn publish(&self, trigger: CameraTrigger, signature: TriggerSignature) -> Result<bool, Box<dyn Error>> {
+        TriggerPublisher::publish(self, trigger, signature)
+    }
+
+    fn publish_batch(&self, batch: &TriggerBatch, header: TriggerSignature) -> Result<bool, Box<dyn Error>> {
+        TriggerPublisher::publish_batch(self, batch, header)
+    }
+
+    fn delivery(&self) -> DeliveryCounts {
+        TriggerPublisher::delivery(self)
+    }
+}
+
+pub struct TriggerSubscriber {
+    subscriber: Subscriber<ipc::Service, CameraTrigger, TriggerSignature>,
+    batch_subscriber: Subscriber<ipc::Service, TriggerBatch, TriggerSignature>,
+    // The publisher's new-sample notifications, waited for in `waitset`
+    listener: Listener<ipc::Service>,
+    waitset: WaitSet<ipc::Service>,
+    // Triggers of a received batch not returned yet
+    unpacked: RefCell<VecDeque<(CameraTrigger, TriggerSignature)>>,
+}
+
+impl TriggerSubscriber {
+    pub fn create(node: &Node<ipc::Service>) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::create_for(node, TRIGGER_SERVICE)
+    }
+
+    pub fn create_for(node: &Node<ipc::Service>, service_name: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let subscriber = open_service::<CameraTrigger>(node, service_name, None)?.subscriber_builder().create()?;
+        let batch_subscriber = open_service::<TriggerBatch>(node, &batched_service(service_name), None)?.subscriber_builder().create()?;
+        let listener = open_notifications(node, service_name)?.listener_builder().create()?;
+        let waitset = WaitSetBuilder::new().create::<ipc::Service>()?;
+        Ok(Self {
+            subscriber,
+            batch_subscriber,
+            listener,
+            waitset,
+            unpacked: RefCell::default(),
+        })
+    }
+
+    /// Blocks until the publisher sends a trigger or `timeout` passed, once `receive` returned `None`;
+    /// `false` on timeout. Triggers that arrived since the last `receive` return right away.
+    pub fn wait(&self, timeout: Duration) -> Result<bool, Box<dyn std::error::Error>> {
+        if !self.unpacked.borrow().is_empty() || self.subscriber.has_samples()? || self.batch_subscriber.has_samples()? {
+            return Ok(true);
+        }
+        // The guard borrows the listener, so it is attached per wait
+        let guard = self.waitset.attach_notification(&self.listener)?;
+        let mut notified = false;
+        let result = self.waitset.wait_and_process_once_with_timeout(
+            |_| {
+                notified = true;
+                CallbackProgression::Stop
+            },
+            timeout,
+        )?;
+        drop(guard);
+        if matches!(result, WaitSetRunResult::TerminationRequest) {
+            return Err("termination requested".into());
+        }
+        // Consumed, so the next wait blocks until the next sample
+        self.listener.try_wait_all(|_| {})?;
+        Ok(notified)
+    }
+
+    /// Returns the next trigger and its signature header, if any; batches are returned trigger by trigger.
+    pub fn receive(&self) -> Result<Option<(CameraTrigger, TriggerSignature)>, Box<dyn std::error::Error>> {
+        let mut unpacked = self.unpacked.borrow_mut();
+        if unpacked.is_empty() {
+            if let Some(sample) = self.batch_subscriber.receive()? {
+                sample.first.check_version()?;
+                let header = *sample.user_header();
+                unpacked.extend(sample.triggers().map(|trigger| (trigger, header)));
+            }
+        }
+        if let Some(trigger) = unpacked.pop_front() {
+            return Ok(Some(trigger));
+        }
+        let Some(sample) = self.subscriber.receive()? else {
+            return Ok(None);
+        };
+        sample.check_version()?;
+        Ok(Some((*sample, *sample.user_header())))
+    }
+}
+
+impl TriggerSource for TriggerSubscriber {
+    fn receive(&self) -> Result<Option<(CameraTrigger, TriggerSignature)>, Box<dyn Error>> {
+        TriggerSubscriber::receive(self)
+    }
+
+    fn wait(&self, timeout: Duration) -> Result<bool, Box<dyn Error>> {
+        TriggerSubscriber::wait(self, timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[ignore = "needs shared memory; waking and timeouts are tested on the mock transport"]
+    fn triggers_reach_a_subscriber_in_order() {
+        let node = NodeBuilder::new().create::<ipc::Service>().unwrap();
+        let service_name = format!("Camera/Test/trigger_{}", std::process::id());
+        let publisher = TriggerPublisher::create_for(&node, &service_name).unwrap();
+        let subscriber = TriggerSubscriber::create_for(&node, &service_name).unwrap();
+        assert_eq!(subscriber.receive().unwrap().map(|(trigger, _)| trigger.frame_id), None);
+
+        for frame_id in 1..=3 {
+            assert!(publisher.publish(CameraTrigger::new(frame_id, frame_id * 1_000, frame_id * 1_000 + 5), TriggerSignature::default()).unwrap());
+        }
+        assert!(subscriber.wait(Duration::from_secs(1)).unwrap());
+        let received: Vec<_> = std::iter::from_fn(|| subscriber.receive().unwrap())
+            .map(|(trigger, header)| (trigger.frame_id, trigger.hw_timestamp_ns, header.signed))
+            .collect();
+        assert_eq!(received, [(1, 1_000, false), (2, 2_000, false), (3, 3_000, false)]);
+        assert_eq!(publisher.delivery().published, 3);
+    }
+}
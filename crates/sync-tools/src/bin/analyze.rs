@@ -0,0 +1,191 @@
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+use sync_core::analyze::{self, DEFAULT_BINS, DEFAULT_BUCKET_NS};
+use sync_core::burst::BurstPattern;
+use sync_core::cli::Args;
+use sync_core::fixture::Fixture;
+use sync_core::matching;
+use sync_core::merge::HostSession;
+use sync_core::offset_sweep::{self, OffsetSweep, SweepRange};
+use sync_core::stats::StatsSummary;
+use sync_core::status::{Status, Value};
+use sync_core::units::{DisplayMs, Unit};
+
+// Routine checks of recorded sessions (see `sync_core::analyze`): latency
+// histograms, drop timelines and per-camera skew rendered to SVG or PNG, CSV
+// extracts of the frames, and static offset sweeps that replay the sessions
+// to find a fixed pipeline delay (see `sync_core::offset_sweep`).
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::from_env(&[]);
+    let status = Status::from_args(&args, "analyze")?;
+    let session_dirs: Vec<String> = (1..).map_while(|i| args.positional::<String>(i)).collect();
+    let command = args.positional::<String>(0).unwrap_or_default();
+    if !matches!(command.as_str(), "latency" | "drops" | "skew" | "csv" | "sweep") || session_dirs.is_empty() {
+        eprintln!(
+            "Usage: {} latency|drops|skew|csv|sweep <session_dir>... [--out <file>] [--bins <n>] [--bucket-s <s>] [--range <from_ms>:<to_ms>:<step_ms>] [--tolerance-ms <ms>] [--status-format text|json] [--display-unit ms|us|ns]",
+            args.program()
+        );
+        eprintln!("Charts are written to --out (<command>.svg by default, PNG for a .png file), csv to --out or stdout.");
+        std::process::exit(2);
+    }
+    let sessions = session_dirs
+        .iter()
+        .map(|dir| HostSession::load(Path::new(dir)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let out = args.value("out").map(str::to_string).unwrap_or_else(|| format!("{}.svg", command));
+
+    let chart = match command.as_str() {
+        "latency" => {
+            for session in &sessions {
+                let latencies: Vec<f64> = session.records.iter().map(|record| record.total_latency_ms).collect();
+                let summary = analyze::summarize(&latencies);
+                status.line(
+                    "latency_histogram",
+                    format_args!("{}: latency {}", session.name, summary_text(&summary, status.display_unit())),
+                    &summary_fields(&session.name, &summary),
+                );
+            }
+            analyze::latency_histogram(&sessions, args.value_as("bins").unwrap_or(DEFAULT_BINS))
+        }
+        "drops" => {
+            let bucket_ns = args.value_as::<f64>("bucket-s").map_or(DEFAULT_BUCKET_NS, |s| (s * 1e9).max(1e6) as u64);
+            let timelines: Vec<_> = sessions.iter().map(|session| analyze::drop_timeline(session, bucket_ns)).collect();
+            for timeline in &timelines {
+                status.line(
+                    "drop_timeline",
+                    format_args!(
+                        "{}: {} recorded, {} dropped in {} gaps (every {} trigger ids), longest gap {} triggers at hw_ts {}",
+                        timeline.name,
+                        timeline.recorded,
+                        timeline.dropped,
+                        timeline.gaps,
+                        timeline.stride,
+                        timeline.longest_gap,
+                        timeline.longest_gap_ns
+                    ),
+                    &[
+                        ("session", timeline.name.as_str().into()),
+                        ("recorded", timeline.recorded.into()),
+                        ("dropped", timeline.dropped.into()),
+                        ("gaps", timeline.gaps.into()),
+                        ("stride", timeline.stride.into()),
+                        ("longest_gap", timeline.longest_gap.into()),
+                        ("longest_gap_ns", timeline.longest_gap_ns.into()),
+                    ],
+                );
+            }
+            analyze::drops_chart(&timelines, bucket_ns)
+        }
+        "skew" => {
+            if sessions.len() < 2 {
+                eprintln!("Error: skew compares cameras, pass at least two sessions");
+                std::process::exit(2);
+            }
+            let skews = analyze::session_skew(&sessions);
+            for skew in &skews {
+                status.line(
+                    "session_skew",
+                    format_args!("{}: skew {}", skew.name, summary_text(&skew.summary, status.display_unit())),
+                    &summary_fields(&skew.name, &skew.summary),
+                );
+            }
+            analyze::skew_chart(&skews)
+        }
+        "sweep" => {
+            let range = args.value("range").map_or(Ok(offset_sweep::DEFAULT_RANGE), str::parse::<SweepRange>)?;
+            let tolerance_ms = args.value_as::<f64>("tolerance-ms").unwrap_or(matching::DEFAULT_TOLERANCE_MS);
+            let sweeps: Vec<_> = sessions
+                .iter()
+                .map(|session| OffsetSweep::run(&session.name, &Fixture::from_records(&session.records, tolerance_ms, BurstPattern::default()), &range))
+                .collect();
+            for sweep in &sweeps {
+                report_sweep(sweep, &range, &status);
+            }
+            offset_sweep::sweep_chart(&sweeps)
+        }
+        _ => {
+            let rows = match args.value("out") {
+                Some(path) => analyze::write_csv(&sessions, &mut BufWriter::new(File::create(path)?))?,
+                None => analyze::write_csv(&sessions, &mut io::stdout().lock())?,
+            };
+            // Stdout carries the CSV
+            if let Some(path) = args.value("out") {
+                status.line("csv_written", format_args!("{} frames written to {}", rows, path), &[("path", path.into()), ("rows", rows.into())]);
+            }
+            return Ok(());
+        }
+    };
+    chart.write(Path::new(&out))?;
+    status.line("chart_written", format_args!("{} written to {}", chart.title, out), &[("chart", command.as_str().into()), ("path", out.as_str().into())]);
+    Ok(())
+}
+
+// Every offset of a session's sweep, then its optimum
+fn report_sweep(sweep: &OffsetSweep, range: &SweepRange, status: &Status) {
+    for point in &sweep.points {
+        status.line(
+            "offset_sweep",
+            format_args!("{}: {}", sweep.name, point.summary(status.display_unit())),
+            &[
+                ("session", sweep.name.as_str().into()),
+                ("offset_ms", point.offset_ms.into()),
+                ("frames", point.frames.into()),
+                ("matched", point.matched.into()),
+                ("match_pct", (point.match_rate() * 100.0).into()),
+                ("residual_p50_ms", point.residual.p50_ms.into()),
+                ("residual_std_ms", point.residual.std_ms.into()),
+                ("abs_residual_p50_ms", point.abs_residual_p50_ms.into()),
+            ],
+        );
+    }
+    match sweep.optimum() {
+        Some(best) => status.line(
+            "offset_optimum",
+            format_args!(
+                "{}: best static offset {:+.3}ms, {:.1}% matched, median |residual| {:.3}",
+                sweep.name,
+                best.offset_ms,
+                best.match_rate() * 100.0,
+                DisplayMs(best.abs_residual_p50_ms, status.display_unit())
+            ),
+            &[
+                ("session", sweep.name.as_str().into()),
+                ("offset_ms", best.offset_ms.into()),
+                ("match_pct", (best.match_rate() * 100.0).into()),
+                ("abs_residual_p50_ms", best.abs_residual_p50_ms.into()),
+            ],
+        ),
+        None => status.line(
+            "offset_optimum",
+            format_args!("WARNING: {}: no frame matched at any offset of {}", sweep.name, range),
+            &[("session", sweep.name.as_str().into()), ("offset_ms", None::<f64>.into())],
+        ),
+    }
+}
+
+fn summary_text(summary: &StatsSummary, unit: Unit) -> String {
+    format!(
+        "n={} mean={:.3} std={:.3} p50={:.3} p95={:.3} min={:.3} max={:.3}",
+        summary.count,
+        DisplayMs(summary.mean_ms, unit),
+        DisplayMs(summary.std_ms, unit),
+        DisplayMs(summary.p50_ms, unit),
+        DisplayMs(summary.p95_ms, unit),
+        DisplayMs(summary.min_ms, unit),
+        DisplayMs(summary.max_ms, unit)
+    )
+}
+
+fn summary_fields(session: &str, summary: &StatsSummary) -> [(&'static str, Value); 8] {
+    [
+        ("session", session.into()),
+        ("count", summary.count.into()),
+        ("mean_ms", summary.mean_ms.into()),
+        ("std_ms", summary.std_ms.into()),
+        ("p50_ms", summary.p50_ms.into()),
+        ("p95_ms", summary.p95_ms.into()),
+        ("min_ms", summary.min_ms.into()),
+        ("max_ms", summary.max_ms.into()),
+    ]
+}
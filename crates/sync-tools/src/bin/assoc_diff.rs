@@ -0,0 +1,58 @@
+use std::fs;
+use sync_core::assoc_diff::{AssociationDiff, AssociationTrace, Change};
+use sync_core::cli::Args;
+use sync_core::status::Status;
+
+/// Width of the ASCII timeline strip.
+const TIMELINE_WIDTH: usize = 100;
+
+// Compares two frame-to-trigger association traces of the same session
+// (see `sync_core::assoc_diff`); exits with 1 if any frame's trigger changed.
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::from_env(&["all"]);
+    let status = Status::from_args(&args, "assoc_diff")?;
+    let (Some(before), Some(after)) = (args.positional::<String>(0), args.positional::<String>(1)) else {
+        println!("Usage: {} <before> <after> [--svg <file>] [--all] [--status-format text|json] [--display-unit ms|us|ns]", args.program());
+        println!("A trace is a session directory, a fixture file, or replay:<fixture> for the current matcher's pairs.");
+        std::process::exit(2);
+    };
+
+    let before = AssociationTrace::load(&before)?;
+    let after = AssociationTrace::load(&after)?;
+    let diff = AssociationDiff::new(&before, &after);
+
+    // Changed frames only, unless `--all`
+    let id = |id: Option<u64>| id.map_or("-".to_string(), |id| id.to_string());
+    for frame in diff.frames.iter().filter(|frame| args.flag("all") || frame.change != Change::Unchanged) {
+        status.line(
+            "association",
+            format_args!("{:>10} frame {}ns: {} -> {}", frame.change.name(), frame.frame_ns, id(frame.before), id(frame.after)),
+            &[
+                ("frame_ns", frame.frame_ns.into()),
+                ("before", frame.before.into()),
+                ("after", frame.after.into()),
+                ("change", frame.change.name().into()),
+            ],
+        );
+    }
+    status.text(format_args!("[{}]", diff.timeline(TIMELINE_WIDTH)));
+    status.line(
+        "association_diff",
+        format_args!("{} -> {}: {}", before.name, after.name, diff.summary()),
+        &[
+            ("before", before.name.as_str().into()),
+            ("after", after.name.as_str().into()),
+            ("frames", diff.frames.len().into()),
+            ("unchanged", diff.count(Change::Unchanged).into()),
+            ("reassigned", diff.count(Change::Reassigned).into()),
+            ("gained", diff.count(Change::Gained).into()),
+            ("lost", diff.count(Change::Lost).into()),
+        ],
+    );
+
+    if let Some(path) = args.value("svg") {
+        fs::write(path, diff.svg(&before, &after))?;
+        status.text(format_args!("Timeline written to {}", path));
+    }
+    std::process::exit(if diff.changed().next().is_some() { 1 } else { 0 });
+}
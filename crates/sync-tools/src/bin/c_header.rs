@@ -0,0 +1,6 @@
+// Prints the C header of the shared-memory payloads (see `sync_core::layout`):
+//
+//   cargo run --bin c_header > crates/sync-core/include/camera_sync.h
+fn main() {
+    print!("{}", sync_core::layout::c_header());
+}
@@ -0,0 +1,3 @@
+fn main() -> Result<std::process::ExitCode, Box<dyn std::error::Error>> {
+    sync_gui::run_headless()
+}
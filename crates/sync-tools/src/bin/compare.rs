@@ -0,0 +1,18 @@
+use std::path::Path;
+use sync_core::cli::Args;
+use sync_core::runs::{self, RunSummary};
+
+// Diffs the summary statistics of two runs (files or config hash directories of `--runs-dir`).
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::from_env(&[]);
+    let (Some(baseline), Some(candidate)) = (args.positional::<String>(0), args.positional::<String>(1)) else {
+        println!("Usage: {} <baseline_run> <candidate_run>", args.program());
+        println!("A run is a summary file or a config hash directory (its latest run is used).");
+        std::process::exit(2);
+    };
+
+    let baseline = RunSummary::read(Path::new(&baseline))?;
+    let candidate = RunSummary::read(Path::new(&candidate))?;
+    println!("{}", runs::compare(&baseline, &candidate));
+    Ok(())
+}
@@ -0,0 +1,69 @@
+use std::path::Path;
+use sync_core::cli::Args;
+use sync_core::export::{self, DatasetFormat};
+use sync_core::leap;
+use sync_core::status::Status;
+
+// Converts recorded sessions (one per camera) into a EuRoC or KITTI style
+// dataset stamped with the trigger hardware timestamps.
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::from_env(&[]);
+    let status = Status::from_args(&args, "export_dataset")?;
+    let session_dirs: Vec<String> = (1..).map_while(|i| args.positional::<String>(i)).collect();
+    let (Some(out_dir), false) = (args.positional::<String>(0), session_dirs.is_empty()) else {
+        eprintln!("Usage: {} <out_dir> <session_dir>... [--format euroc|kitti] [--leap-seconds <file>] [--status-format text|json] [--display-unit ms|us|ns]", args.program());
+        std::process::exit(2);
+    };
+    let format = match args.value("format").unwrap_or("euroc").parse::<DatasetFormat>() {
+        Ok(format) => format,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(2);
+        }
+    };
+
+    // The sensor rate is taken over the TAI span of each session
+    status.leap_seconds(leap::install_from_args(&args)?);
+
+    for (index, session_dir) in session_dirs.iter().enumerate() {
+        let camera = export::export_session(format, Path::new(session_dir), index, Path::new(&out_dir))?;
+        if camera.images == 0 && camera.missing_images == 0 {
+            status.line(
+                "no_frames",
+                format_args!("WARNING: {} has no saved frames (record with --save-frames), exported timestamps only", camera.session),
+                &[("session", camera.session.as_str().into())],
+            );
+        }
+        if camera.missing_images > 0 {
+            status.line(
+                "missing_images",
+                format_args!("WARNING: {} is missing {} frame images, skipped", camera.session, camera.missing_images),
+                &[("session", camera.session.as_str().into()), ("missing_images", camera.missing_images.into())],
+            );
+        }
+        status.line(
+            "camera_exported",
+            format_args!(
+                "{} -> {}: {} frames, {} images{}",
+                camera.session,
+                camera.camera,
+                camera.frames,
+                camera.images,
+                if camera.calibrated { ", sensor.yaml" } else { "" }
+            ),
+            &[
+                ("session", camera.session.as_str().into()),
+                ("camera", camera.camera.as_str().into()),
+                ("frames", camera.frames.into()),
+                ("images", camera.images.into()),
+                ("calibrated", camera.calibrated.into()),
+            ],
+        );
+    }
+    status.line(
+        "exported",
+        format_args!("Exported {} cameras as {} into {}", session_dirs.len(), format, out_dir),
+        &[("cameras", session_dirs.len().into()), ("format", format.to_string().into()), ("out_dir", out_dir.as_str().into())],
+    );
+    Ok(())
+}
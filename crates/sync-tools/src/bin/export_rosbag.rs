@@ -0,0 +1,64 @@
+use std::path::Path;
+use sync_core::cli::Args;
+use sync_core::rosbag;
+use sync_core::status::Status;
+
+// Writes recorded sessions (one per camera) as a ROS 2 bag with images,
+// camera infos and trigger messages stamped with the trigger hardware
+// timestamps, for replay with `ros2 bag play`.
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::from_env(&[]);
+    let status = Status::from_args(&args, "export_rosbag")?;
+    let session_dirs: Vec<String> = (1..).map_while(|i| args.positional::<String>(i)).collect();
+    let (Some(bag_dir), false) = (args.positional::<String>(0), session_dirs.is_empty()) else {
+        eprintln!("Usage: {} <bag_dir> <session_dir>... [--status-format text|json] [--display-unit ms|us|ns]", args.program());
+        std::process::exit(2);
+    };
+
+    let sessions: Vec<&Path> = session_dirs.iter().map(Path::new).collect();
+    let summary = rosbag::export_bag(&sessions, Path::new(&bag_dir))?;
+
+    for camera in &summary.cameras {
+        if camera.images == 0 {
+            status.line(
+                "no_frames",
+                format_args!("WARNING: {} has no saved frames (record with --save-frames), exported trigger messages only", camera.session),
+                &[("session", camera.session.as_str().into())],
+            );
+        }
+        status.line(
+            "camera_exported",
+            format_args!(
+                "{} -> {}: {} triggers, {} images{}",
+                camera.session,
+                camera.namespace,
+                camera.triggers,
+                camera.images,
+                if camera.calibrated { ", with intrinsics" } else { "" }
+            ),
+            &[
+                ("session", camera.session.as_str().into()),
+                ("namespace", camera.namespace.as_str().into()),
+                ("triggers", camera.triggers.into()),
+                ("images", camera.images.into()),
+                ("calibrated", camera.calibrated.into()),
+            ],
+        );
+    }
+    status.line(
+        "exported",
+        format_args!(
+            "Wrote {} messages over {:.3}s into {}",
+            summary.messages,
+            (summary.end_ns - summary.start_ns) as f64 / 1e9,
+            bag_dir
+        ),
+        &[
+            ("messages", summary.messages.into()),
+            ("start_ns", summary.start_ns.into()),
+            ("end_ns", summary.end_ns.into()),
+            ("bag_dir", bag_dir.as_str().into()),
+        ],
+    );
+    Ok(())
+}
@@ -0,0 +1,42 @@
+use std::fs;
+use std::path::Path;
+use sync_core::burst::BurstPattern;
+use sync_core::cli::Args;
+use sync_core::fixture::Fixture;
+use sync_core::matching;
+use sync_core::session;
+
+// Converts a recorded session into a regression fixture for `cargo test`
+// (see `sync_core::fixture`).
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::from_env(&[]);
+    let (Some(session_dir), Some(output)) = (args.positional::<String>(0), args.positional::<String>(1)) else {
+        println!("Usage: {} <session_dir> <output.fixture> [--burst-size <k>] [--tolerance-ms <ms>]", args.program());
+        println!("Fixtures in crates/sync-core/tests/fixtures/ run with `cargo test`.");
+        std::process::exit(2);
+    };
+    let burst = BurstPattern::new(args.value_as::<u32>("burst-size").unwrap_or(1));
+    let tolerance_ms = args.value_as::<f64>("tolerance-ms").unwrap_or(matching::DEFAULT_TOLERANCE_MS);
+
+    let records = session::read_records(Path::new(&session_dir))?;
+    if records.is_empty() {
+        return Err(format!("{}: no records", session_dir).into());
+    }
+    let fixture = Fixture::from_records(&records, tolerance_ms, burst);
+
+    // Frames of a session recorded with another matcher (or with lost records) replay differently
+    if let Err(differences) = fixture.check() {
+        println!("WARNING: the current matcher does not reproduce this session: {}", differences);
+    }
+
+    let text = format!(
+        "# Recorded session {} ({} triggers, {} frames)\n{}",
+        Path::new(&session_dir).file_name().unwrap_or_default().to_string_lossy(),
+        fixture.triggers.len(),
+        fixture.frames.len(),
+        fixture
+    );
+    fs::write(&output, text)?;
+    println!("Fixture written to {}", output);
+    Ok(())
+}
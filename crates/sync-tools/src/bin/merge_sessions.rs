@@ -0,0 +1,78 @@
+use std::path::Path;
+use sync_core::cli::Args;
+use sync_core::merge::{self, HostSession, MERGED_FILE};
+use sync_core::status::Status;
+
+// Aligns session recordings of several hosts into one timeline using their
+// recorded clock mappings and writes the merged dataset with residual
+// alignment error estimates.
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::from_env(&[]);
+    let status = Status::from_args(&args, "merge_sessions")?;
+    let session_dirs: Vec<String> = (1..).map_while(|i| args.positional::<String>(i)).collect();
+    let (Some(out_dir), false) = (args.positional::<String>(0), session_dirs.is_empty()) else {
+        eprintln!("Usage: {} <out_dir> <session_dir>... [--status-format text|json] [--display-unit ms|us|ns]", args.program());
+        std::process::exit(2);
+    };
+
+    let sessions = session_dirs
+        .iter()
+        .map(|dir| HostSession::load(Path::new(dir)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let merged = merge::merge(&sessions);
+    merged.write(Path::new(&out_dir))?;
+
+    for host in &merged.hosts {
+        if host.mappings == 0 {
+            status.line(
+                "no_clock_mapping",
+                format_args!("WARNING: {} has no clock mappings, merged with offset 0", host.name),
+                &[("host", host.name.as_str().into())],
+            );
+        }
+        status.line(
+            "host",
+            format_args!(
+                "{}: {} records, {} mappings, offset {:.3}ms (uncertainty <= {:.3}ms), median latency {:.2}ms, residual p50={:.3}ms p95={:.3}ms",
+                host.name,
+                host.records,
+                host.mappings,
+                host.mean_offset_ns / 1e6,
+                host.max_uncertainty_ns as f64 / 1e6,
+                host.median_latency_ms,
+                host.residual.percentile(50.0),
+                host.residual.percentile(95.0)
+            ),
+            &[
+                ("host", host.name.as_str().into()),
+                ("records", host.records.into()),
+                ("mappings", host.mappings.into()),
+                ("mean_offset_ns", host.mean_offset_ns.into()),
+                ("max_uncertainty_ns", host.max_uncertainty_ns.into()),
+                ("median_latency_ms", host.median_latency_ms.into()),
+                ("residual_p50_ms", host.residual.percentile(50.0).into()),
+                ("residual_p95_ms", host.residual.percentile(95.0).into()),
+            ],
+        );
+    }
+    status.line(
+        "merged",
+        format_args!(
+            "Merged {} records into {}; cross-host residual spread over {} shared triggers: p50={:.3}ms p95={:.3}ms max={:.3}ms",
+            merged.records.len(),
+            Path::new(&out_dir).join(MERGED_FILE).display(),
+            merged.cross_host.count(),
+            merged.cross_host.percentile(50.0),
+            merged.cross_host.percentile(95.0),
+            merged.cross_host.max()
+        ),
+        &[
+            ("records", merged.records.len().into()),
+            ("shared_triggers", merged.cross_host.count().into()),
+            ("cross_host_p50_ms", merged.cross_host.percentile(50.0).into()),
+            ("cross_host_p95_ms", merged.cross_host.percentile(95.0).into()),
+            ("cross_host_max_ms", merged.cross_host.max().into()),
+        ],
+    );
+    Ok(())
+}
@@ -0,0 +1,909 @@
+use std::cell::Cell;
+use std::path::Path;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use sync_core::batch::{self, BatchResults, BatchRun};
+use sync_core::burst::BurstPattern;
+use sync_core::canary::{self, CanaryMonitor, CanaryReport};
+use sync_core::channel::{ChannelSchedule, TriggerChannel};
+use sync_core::cli::Args;
+use sync_core::clock::{self, ClockDomain, DomainConverter};
+use sync_core::clock_guard::{ClockEvent, ClockGuard, ClockGuardConfig};
+use sync_core::clock_quality::{ClockQualityMonitor, DowngradePolicy, QualityChange};
+use sync_core::delivery::UnableToDeliver;
+use sync_core::feedback::{ConsumerDelivery, DeliveryMonitor};
+use sync_core::genlock::{Genlock, GenlockConfig};
+use sync_core::history::{self, TriggerHistory};
+use sync_core::latency::LatencyBreakdown;
+use sync_core::leap;
+use sync_core::lighting::StrobePattern;
+use sync_core::loans;
+use sync_core::namespace::NodeIdentity;
+use sync_core::priority::PriorityPattern;
+use sync_core::sensor::{self, SensorProfile};
+use sync_core::signing::{TriggerSignature, TriggerSigner};
+use sync_core::skew::{self, CameraSkew, SkewMonitor};
+use sync_core::stats::LatencyStats;
+use sync_core::status::Status;
+use sync_core::timestamping::{self, TimestampSource};
+use sync_core::trace::{TraceId, Tracer};
+use sync_core::trigger_batch::{self, BatchSelector, TriggerBatch, TriggerBatcher};
+use sync_core::units::Nanos;
+use sync_core::CameraTrigger;
+use sync_iceoryx2::feedback::{FeedbackSubscriber, FEEDBACK_SERVICE};
+use sync_iceoryx2::history::TriggerHistoryServer;
+use sync_iceoryx2::node::create_node;
+use sync_iceoryx2::reference::{ReferenceSubscriber, REFERENCE_SERVICE};
+use sync_iceoryx2::skew::GroupLatencySubscriber;
+use sync_iceoryx2::strobe::StrobePublisher;
+use sync_iceoryx2::telemetry::TelemetryPublisher;
+use sync_iceoryx2::trigger::{self, TriggerPublisher};
+
+/// Delivery counts are published on telemetry every this many triggers.
+const DELIVERY_REPORT_EVERY: u64 = 100;
+
+/// The phase against the video reference is reported every this many triggers.
+const GENLOCK_REPORT_EVERY: u64 = 100;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Parse command line arguments
+    let args = Args::from_env(&["aligned", "genlock", "genlock-steer"]);
+    let status = Status::from_args(&args, "publisher")?;
+
+    // Stop after a fixed duration and report the published triggers (`--duration 60s`)
+    let batch = BatchRun::from_args(&args, "publisher").unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        std::process::exit(batch::EXIT_USAGE);
+    });
+    let trigger_interval_ms = args.positional::<u64>(0).unwrap_or(33); // Default: 33ms (30 FPS)
+
+    // Optional ed25519 signing of every trigger (signature travels in the user header)
+    let signer = match args.value("sign-key") {
+        Some(path) => Some(TriggerSigner::from_key_file(Path::new(path))?),
+        None => None,
+    };
+
+    // Burst mode: K pulses per interval, `--burst-gap-ms` apart (multi-exposure captures)
+    let burst_size = args.value_as::<u32>("burst-size").unwrap_or(1).max(1);
+    let burst_gap_ms = args.value_as::<f64>("burst-gap-ms").unwrap_or(1.0);
+    // Triggers consumers shed last, logged here; consumers started with the same setting tag them (see `sync_core::priority`)
+    let priority = PriorityPattern::from_args(&args, BurstPattern::new(burst_size));
+
+    // Lighting state cycled per trigger, e.g. `0:1.0,1:1.0,off`
+    let strobe_pattern = match args.value("strobe-pattern") {
+        Some(pattern) => Some(pattern.parse::<StrobePattern>()?),
+        None => None,
+    };
+
+    // Non-camera sensors with their own trigger-in pins, each on its own divided/offset trigger stream
+    let mut sensors = args
+        .values("sensor")
+        .into_iter()
+        .map(|profile| profile.parse::<SensorProfile>())
+        .collect::<Result<Vec<_>, _>>()?;
+    // Multi-camera rigs: camera N on `Camera/Sync/camN` with its own offset, e.g. `--cameras 4 --camera-offsets-ms 0,0,0.5,0.5`
+    if let Some(cameras) = args.value_as::<u32>("cameras") {
+        sensors.extend(sensor::camera_fan_out(cameras, args.value("camera-offsets-ms"))?);
+    }
+    if let Some(sensor) = sensors.iter().enumerate().find_map(|(index, sensor)| sensors[..index].iter().any(|other| other.name == sensor.name).then_some(sensor)) {
+        return Err(format!("sensor {} given twice", sensor.name).into());
+    }
+
+    // Independent trigger channels for camera groups at other rates, each with its own schedule, trigger ids and service
+    let channels = args
+        .values("channel")
+        .into_iter()
+        .map(|spec| spec.parse::<TriggerChannel>())
+        .collect::<Result<Vec<_>, _>>()?;
+    if let Some(channel) = channels.iter().enumerate().find_map(|(index, channel)| channels[..index].iter().any(|other| other.name == channel.name).then_some(channel)) {
+        return Err(format!("trigger channel {} given twice", channel.name).into());
+    }
+
+    // Tracepoints for perfetto/LTTng timelines (`ftrace`, `lttng` or a file)
+    let mut tracer = match args.value("trace") {
+        Some(target) => Tracer::open(target)?,
+        None => Tracer::default(),
+    };
+
+    // Place triggers on the realtime grid `k * interval + offset`, e.g. to interleave with another rig.
+    // `--aligned` alone fires on the interval boundaries of UTC, so rigs on PTP-synced hosts line up without coordination
+    let phase_offset_ms = args.value_as::<f64>("phase-offset-ms").or(args.flag("aligned").then_some(0.0));
+
+    // Where the trigger timestamps come from: `system`, `phc:/dev/ptp0`, `mcu:<udp addr>`, `hte:<chip>:<line>` or `timer:<hz>`.
+    // External sources (MCU, GPIO edges, timer pulse trains) set the trigger pace, the interval and grid options are ignored
+    // PHC and timer sources count TAI, converted to UTC with the leap second table (`--leap-seconds <leap-seconds.list>`)
+    // `--clock ptp:/dev/ptp0` is short for `--timestamp-source phc:/dev/ptp0 --clock-domain ptp`
+    status.leap_seconds(leap::install_from_args(&args)?);
+    let clock_spec = args.value("clock").map(timestamping::clock_spec).transpose()?;
+    if clock_spec.is_some() && (args.value("timestamp-source").is_some() || args.value("clock-domain").is_some()) {
+        return Err("--clock sets the timestamp source and clock domain, drop --timestamp-source and --clock-domain".into());
+    }
+    let timestamp_source = clock_spec.as_ref().map_or(args.value("timestamp-source").unwrap_or("system"), |(spec, _)| spec.as_str());
+    let mut timestamper = timestamping::open(timestamp_source)?;
+    let source = timestamper.source();
+    let external = timestamper.is_external();
+
+    // Clock the published timestamps count in (`realtime`, `monotonic`, `tai` or `ptp`), carried in the trigger header.
+    // Monotonic triggers do not move when NTP steps the clock; subscribers convert them back (see `sync_core::clock`)
+    let clock_domain = match clock_spec {
+        Some((_, domain)) => domain,
+        None => args.value("clock-domain").unwrap_or("realtime").parse::<ClockDomain>()?,
+    };
+
+    // Synchronization state of the trigger clock (`--clock-quality kernel|file:<path>`) and what consumers do while it
+    // is downgraded (`--on-clock-downgrade flag|widen|pause`), sent with every trigger (see `sync_core::clock_quality`)
+    let mut clock_quality = ClockQualityMonitor::from_args(&args)?;
+    // Header value of the policy in force, shared with the channel thread
+    let clock_policy = Arc::new(AtomicU8::new(0));
+
+    // Phase against the edges of a video reference on `Video/Reference` (`--genlock`), optionally steering
+    // the triggers onto it (`--genlock-steer --genlock-phase-ms 2`); external sources pace themselves and are only measured
+    let mut genlock = GenlockConfig::from_args(&args).map(|config| Genlock::new(config, trigger_interval_ms.max(1) * 1_000_000));
+    let steering = genlock.as_ref().is_some_and(|genlock| genlock.config().steer) && !external;
+
+    // Sample loan pool, by default large enough for a whole burst
+    let max_loaned_samples = args
+        .value_as::<usize>("max-loaned-samples")
+        .unwrap_or(loans::DEFAULT_MAX_LOANS.max(loans::recommended_max_loans(burst_size)));
+
+    // Subscribers with a full buffer: overflow (replace their oldest trigger), discard or block
+    let unable_to_deliver = args.value("unable-to-deliver").unwrap_or("overflow").parse::<UnableToDeliver>()?;
+
+    // kHz trigger rates: above `--batch-above-hz` (0 disables), up to `--batch-max` triggers go out as one sample,
+    // delayed by at most `--batch-max-delay-ms`. Signed triggers need their own header and are never batched
+    let batch_above_hz = args.value_as::<f64>("batch-above-hz").unwrap_or(1000.0);
+    let batch_max = args.value_as::<usize>("batch-max").unwrap_or(trigger_batch::MAX_BATCH).clamp(2, trigger_batch::MAX_BATCH);
+    let batch_max_delay_ms = args.value_as::<f64>("batch-max-delay-ms").unwrap_or(1.0);
+    let mut selector = (batch_above_hz > 0.0 && signer.is_none()).then(|| BatchSelector::new(batch_above_hz));
+    let mut batcher = TriggerBatcher::new(batch_max, batch_max_delay_ms);
+
+    // Canary triggers every `--canary-interval-ms`, each consumer must acknowledge them within `--canary-deadline-ms`;
+    // `--canary-consumers` names consumers that count as missing before their first acknowledgement
+    let canary_deadline_ms = args.value_as::<u64>("canary-deadline-ms").unwrap_or(500);
+    let mut canaries = args.value_as::<u64>("canary-interval-ms").map(|interval_ms| {
+        let expected = args.values("canary-consumers").into_iter().flat_map(|list| list.split(',')).map(|name| name.trim().to_string());
+        CanaryMonitor::new(interval_ms * 1_000_000, canary_deadline_ms * 1_000_000, expected.filter(|name| !name.is_empty()))
+    });
+
+    // Published triggers kept for "all triggers since T" queries of late-joining consumers
+    let mut history = TriggerHistory::new(args.value_as::<usize>("history-ring").unwrap_or(history::DEFAULT_CAPACITY));
+
+    status.text(format_args!("Camera trigger publisher started with interval: {}ms", trigger_interval_ms));
+    status.text(format_args!("Usage: {} [trigger_interval_ms] [--sign-key <key_file>] [--timestamp-source system|phc:<dev>|mcu:<addr>|hte:<chip>:<line>|timer:<hz>[:<dev>]|irq:<irq>[:<bpf dir>]] [--clock-domain realtime|monotonic|tai|ptp] [--clock ptp:<dev>] [--clock-quality kernel|file:<path> [--on-clock-downgrade flag|widen|pause] [--clock-max-error-us <us>]] [--aligned] [--phase-offset-ms <ms>] [--genlock [--genlock-steer] [--genlock-phase-ms <ms>] [--genlock-max-step-us <us>] [--genlock-tolerance-us <us>]] [--burst-size <k> --burst-gap-ms <ms>] [--priority-every <n>] [--strobe-pattern <steps>] [--sensor <profile>]... [--cameras <n> [--camera-offsets-ms <ms,...>]] [--channel <spec>]... [--canary-interval-ms <ms> [--canary-deadline-ms <ms>] [--canary-consumers <name,...>]] [--skew-threshold-ms <ms>] [--clock-max-step-ms <ms>] [--clock-settle-ms <ms>] [--history-ring <n>] [--unable-to-deliver overflow|discard|block] [--max-loaned-samples <n>] [--batch-above-hz <hz>] [--batch-max <n>] [--batch-max-delay-ms <ms>] [--leap-seconds <file>] [--trace ftrace|lttng|<file>] [--status-format text|json] [--display-unit ms|us|ns] [--duration <time> [--report <file>]]", args.program()));
+    status.text(format_args!(
+        "Trigger timestamps from {} (source {}, rank {}, ~{}ns precision)",
+        timestamper.describe(),
+        source,
+        source.rank(),
+        source.precision_ns()
+    ));
+    if clock_domain != ClockDomain::Realtime {
+        status.text(format_args!("Trigger timestamps published in {} (clock domain {})", clock_domain.describe(), clock_domain));
+    }
+    if let Some(monitor) = &clock_quality {
+        status.text(format_args!("Trigger clock quality read from {}; while downgraded, policy {}: {}", monitor.source(), monitor.policy(), monitor.policy().describe()));
+    }
+    if burst_size > 1 {
+        status.text(format_args!("Burst mode: {} pulses per trigger, {}ms apart", burst_size, burst_gap_ms));
+    }
+    if let Some(warning) = loans::sizing_warning(max_loaned_samples, burst_size) {
+        status.text(format_args!("WARNING: {}", warning));
+    }
+    if let Some(genlock) = &genlock {
+        let config = genlock.config();
+        if steering {
+            status.text(format_args!(
+                "Genlock: steering triggers onto phase {}ms of the reference on {} (max step {}us, tolerance {}us){}",
+                config.target_phase_ns as f64 / 1e6,
+                REFERENCE_SERVICE,
+                config.max_step_ns as f64 / 1e3,
+                config.tolerance_ns as f64 / 1e3,
+                if phase_offset_ms.is_some() { ", realtime grid options ignored" } else { "" }
+            ));
+        } else {
+            status.text(format_args!("Genlock: measuring the trigger phase against the reference on {}", REFERENCE_SERVICE));
+            if config.steer {
+                status.text(format_args!("WARNING: {} sets the trigger pace, --genlock-steer ignored", timestamper.describe()));
+            }
+        }
+    } else if let Some(offset) = phase_offset_ms {
+        status.text(format_args!("Triggers aligned to the realtime grid with phase offset {}ms", offset));
+    }
+    for sensor in &sensors {
+        status.text(format_args!("Sensor fan-out on {}: {}", trigger::sensor_service(&sensor.name), sensor));
+    }
+    for channel in &channels {
+        status.text(format_args!("Trigger channel on {}: {} ({}Hz, own trigger ids)", trigger::channel_service(&channel.name), channel, channel.rate()));
+    }
+    if let Some(signer) = &signer {
+        status.text(format_args!("Signing triggers, public key: {}", signer.public_key_hex()));
+        if batch_above_hz > 0.0 {
+            status.text(format_args!("Signed triggers are not batched, --batch-above-hz ignored"));
+        }
+    }
+    if selector.is_some() {
+        status.text(format_args!(
+            "Batching triggers above {}Hz on {}: up to {} per sample, at most {}ms delay",
+            batch_above_hz,
+            trigger::batched_service(trigger::TRIGGER_SERVICE),
+            batch_max,
+            batch_max_delay_ms
+        ));
+    }
+    if let Some(canaries) = &canaries {
+        status.text(format_args!(
+            "Canary triggers every {}ms, acknowledged on {} within {}ms{}",
+            args.value("canary-interval-ms").unwrap_or_default(),
+            FEEDBACK_SERVICE,
+            canary_deadline_ms,
+            if canaries.consumers().is_empty() { String::new() } else { format!(" by {}", canaries.consumers().join(", ")) }
+        ));
+    }
+    status.text(format_args!("Publishing hardware timestamps for multiple cameras..."));
+
+    let identity = NodeIdentity::from_args(&args, "publisher", None)?;
+    let node = create_node(&identity)?;
+
+    // Trigger service with QoS settings optimized for camera sync
+    let publisher = TriggerPublisher::create_with(&node, trigger::TRIGGER_SERVICE, unable_to_deliver, max_loaned_samples)?;
+    let telemetry = TelemetryPublisher::create(&node)?;
+    let sensor_publishers = sensors
+        .iter()
+        .map(|sensor| TriggerPublisher::create_for(&node, &trigger::sensor_service(&sensor.name)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let strobe = match &strobe_pattern {
+        Some(_) => Some(StrobePublisher::create(&node)?),
+        None => None,
+    };
+    let history_server = TriggerHistoryServer::create(&node)?;
+    let reference = match &genlock {
+        Some(_) => Some(ReferenceSubscriber::create(&node)?),
+        None => None,
+    };
+    // Receive times of canaries and sampled triggers reported by the consumers
+    let feedback = FeedbackSubscriber::create(&node)?;
+    let mut delivery_monitor = DeliveryMonitor::default();
+    // Frame latencies of the cameras started with `--camera-group`, compared within each group (see `sync_core::skew`)
+    let group_latency = GroupLatencySubscriber::create(&node)?;
+    let mut skew_monitor = SkewMonitor::new(args.value_as::<f64>("skew-threshold-ms").unwrap_or(skew::DEFAULT_SKEW_THRESHOLD_MS));
+
+    // The channels fire on a thread and node of their own: iceoryx2 ports stay on the thread that created them
+    let channel_count = channels.len();
+    let mut channel_thread = if channels.is_empty() {
+        None
+    } else {
+        let schedule = ChannelSchedule::new(channels, clock::realtime_now_ns());
+        let (identity, sign_key, status, clock_policy) = (identity.child("channels"), args.value("sign-key").map(str::to_string), status.clone(), clock_policy.clone());
+        Some(std::thread::Builder::new().name("trigger channels".to_string()).spawn(move || {
+            run_channels(schedule, &identity, sign_key.as_deref(), clock_domain, &clock_policy, unable_to_deliver, &status).map_err(|e| e.to_string())
+        })?)
+    };
+
+    let mut global_trigger_id = 0;
+    // Sent with every trigger, so consumers can tell this instance's trigger ids apart (see `sync_core::trace`)
+    let trace_origin = TraceId::new_origin();
+    status.line(
+        "start",
+        format_args!("Camera trigger publisher started. Publishing hardware timestamps for multiple cameras..."),
+        &[
+            ("interval_ms", trigger_interval_ms.into()),
+            ("burst_size", burst_size.into()),
+            ("priority_every", priority.map(|pattern| pattern.every).into()),
+            ("phase_offset_ms", phase_offset_ms.into()),
+            ("genlock", genlock.as_ref().map(|_| if steering { "steer" } else { "measure" }).into()),
+            ("sensors", sensors.len().into()),
+            ("channels", channel_count.into()),
+            ("public_key", signer.as_ref().map(|signer| signer.public_key_hex()).into()),
+            ("trace_origin", format!("{:08x}", trace_origin).into()),
+            ("timestamp_source", source.to_string().into()),
+            ("timestamp_source_rank", (source.rank() as u64).into()),
+            ("timestamp_precision_ns", source.precision_ns().into()),
+            ("clock_domain", clock_domain.to_string().into()),
+            ("clock_quality", clock_quality.as_ref().map(|monitor| monitor.source().to_string()).into()),
+            ("on_clock_downgrade", clock_quality.as_ref().map(|monitor| monitor.policy().to_string()).into()),
+            ("history_ring", history.capacity().into()),
+            ("canary_interval_ms", args.value_as::<u64>("canary-interval-ms").into()),
+            ("skew_threshold_ms", skew_monitor.threshold_ms().into()),
+            ("unable_to_deliver", unable_to_deliver.to_string().into()),
+            ("max_loaned_samples", max_loaned_samples.into()),
+            ("batch_above_hz", selector.as_ref().map_or(0.0, |_| batch_above_hz).into()),
+            ("batch_max", batch_max.into()),
+            ("batch_max_delay_ms", batch_max_delay_ms.into()),
+        ],
+    );
+
+    // How late the triggers fire after their grid points (aligned mode)
+    let mut wake_lateness = LatencyStats::default();
+
+    // Steps of CLOCK_REALTIME (publish times, grid and genlock slots) against CLOCK_MONOTONIC (see `sync_core::clock_guard`)
+    let mut realtime_guard = ClockGuard::new("realtime", ClockGuardConfig::from_args(&args));
+
+    // Undelivered triggers and missed timer pulses at the last delivery report
+    let mut reported_undelivered = 0;
+    let mut reported_missed = 0;
+
+    // PHC minus CLOCK_REALTIME at the last timestamp, 0 unless the source reads a PTP hardware clock
+    let phc_offset_ns = Cell::new(0);
+
+    // Signature (if signing) plus the timestamp source, clock domain, clock policy, trace origin and PHC offset, carried in the user header
+    let header = |trigger: &CameraTrigger| TriggerSignature {
+        timestamp_source: source as u8,
+        clock_domain: clock_domain as u8,
+        clock_policy: clock_policy.load(Ordering::Relaxed),
+        trace_origin,
+        phc_offset_ns: phc_offset_ns.get(),
+        ..signer.as_ref().map_or_else(TriggerSignature::default, |signer| signer.sign(trigger))
+    };
+    // Batches are never signed
+    let mut batch_header = TriggerSignature {
+        timestamp_source: source as u8,
+        clock_domain: clock_domain as u8,
+        trace_origin,
+        ..TriggerSignature::default()
+    };
+
+    // Whether triggers currently go out batched
+    let mut batching = false;
+
+    // Triggers measured against the video reference
+    let mut genlock_triggers = 0u64;
+
+    loop {
+        if batch.as_ref().is_some_and(BatchRun::is_over) {
+            break;
+        }
+        history_server.serve(&history)?;
+
+        // The channels only stop on an error, which ends the publisher like one on the main trigger line
+        if let Some(thread) = channel_thread.take_if(|thread| thread.is_finished()) {
+            let error = thread.join().map_or_else(|_| "thread panicked".to_string(), |result| result.err().unwrap_or_default());
+            return Err(format!("trigger channels: {}", error).into());
+        }
+
+        if let Some(event) = realtime_guard.observe(clock::realtime_now_ns(), clock::monotonic_now_ns()) {
+            report_clock_event(&event, &telemetry, &status)?;
+        }
+        // Lateness and phase measured across a clock step are garbage, the genlock loop holds its estimates
+        let clock_disturbed = realtime_guard.is_disturbed();
+
+        // A downgrade or recovery of the trigger clock goes out with the next trigger
+        if let Some(change) = clock_quality.as_mut().and_then(|monitor| monitor.poll(clock::monotonic_now_ns())) {
+            report_clock_quality(&change, &telemetry, &status)?;
+            clock_policy.store(DowngradePolicy::to_header(change.active), Ordering::Relaxed);
+            batch_header.clock_policy = DowngradePolicy::to_header(change.active);
+        }
+
+        // A batch whose first trigger waited long enough goes out even if the triggers stopped
+        if let Some(ready) = batcher.due(clock::realtime_now_ns()) {
+            send_batch(&publisher, &ready, batch_header, &mut history, &status)?;
+        }
+
+        while let Some(received) = feedback.receive()? {
+            if canary::is_canary(received.trigger_id) {
+                if let Some(canaries) = &mut canaries {
+                    canaries.on_ack(&received);
+                }
+            } else if let Some(consumer) = delivery_monitor.on_feedback(&received) {
+                report_delivery_change(consumer, &status);
+            }
+        }
+        while let Some(sample) = group_latency.receive()? {
+            if let Some(camera) = skew_monitor.on_sample(&sample, clock::monotonic_now_ns()) {
+                report_skew_change(camera, &telemetry, &status)?;
+            }
+        }
+
+        // Canaries go out in order with the triggers, but never into the history
+        if let Some(canaries) = &mut canaries {
+            let now_ns = clock::realtime_now_ns();
+            for report in canaries.expired(now_ns) {
+                report_canary(&report, &telemetry, &status)?;
+            }
+            if let Some(canary) = canaries.due(now_ns) {
+                let canary = DomainConverter::sample().convert_trigger(canary, ClockDomain::Realtime, clock_domain);
+                if let Some(pending) = batcher.flush() {
+                    send_batch(&publisher, &pending, batch_header, &mut history, &status)?;
+                }
+                publisher.publish(canary, header(&canary))?;
+                for sensor_publisher in &sensor_publishers {
+                    sensor_publisher.publish(canary, header(&canary))?;
+                }
+            }
+        }
+
+        if let (Some(genlock), Some(reference)) = (&mut genlock, &reference) {
+            while let Some(edge_ns) = reference.receive()? {
+                genlock.on_reference(edge_ns);
+            }
+        }
+
+        // Wait for the next grid point (or genlock slot) with an absolute timer; the timestamp below stays the actual trigger time
+        let mut grid_ns = None;
+        if let Some(genlock) = genlock.as_mut().filter(|_| steering) {
+            let next = genlock.next_trigger_ns(clock::realtime_now_ns());
+            clock::sleep_until_realtime_ns(next);
+            grid_ns = Some(next);
+        } else if let Some(offset) = phase_offset_ms.filter(|_| !external) {
+            let period_ns = trigger_interval_ms.max(1) * 1_000_000;
+            let next = clock::next_grid_ns(clock::realtime_now_ns(), period_ns, (offset * 1e6) as i64);
+            clock::sleep_until_realtime_ns(next);
+            grid_ns = Some(next);
+        }
+
+        // One pulse per interval, or a burst of closely spaced pulses with consecutive ids
+        for pulse in 0..burst_size {
+            if pulse > 0 && !external {
+                std::thread::sleep(Duration::from_secs_f64(burst_gap_ms / 1000.0));
+            }
+
+            // Simulate hardware trigger interrupt (shared by all cameras)
+            global_trigger_id += 1;
+
+            // Lighting is set up before the exposure it applies to
+            if let (Some(strobe), Some(pattern)) = (&strobe, &strobe_pattern) {
+                strobe.publish(pattern.command(global_trigger_id))?;
+            }
+
+            // Capture hardware timestamp (actual exposure time - same for all cameras)
+            let Nanos(hardware_timestamp_ns) = timestamper.next()?;
+            if let Some(offset_ns) = timestamper.clock_offset_ns() {
+                phc_offset_ns.set(offset_ns);
+                batch_header.phc_offset_ns = offset_ns;
+            }
+
+            if let Some(grid_ns) = grid_ns.filter(|_| pulse == 0 && !clock_disturbed) {
+                wake_lateness.add(hardware_timestamp_ns.saturating_sub(grid_ns) as f64 / 1e6);
+                if wake_lateness.count().is_multiple_of(100) {
+                    status.line(
+                        "alignment",
+                        format_args!("ALIGNMENT: trigger lateness after grid point {}", wake_lateness.summary(status.display_unit())),
+                        &[
+                            ("count", wake_lateness.count().into()),
+                            ("p50_ms", wake_lateness.percentile(50.0).into()),
+                            ("p95_ms", wake_lateness.percentile(95.0).into()),
+                            ("max_ms", wake_lateness.max().into()),
+                        ],
+                    );
+                }
+            }
+
+            // A burst is one exposure slot, its first pulse carries the phase
+            if let Some(genlock) = genlock.as_mut().filter(|_| pulse == 0 && !clock_disturbed) {
+                report_genlock(genlock, hardware_timestamp_ns, &mut genlock_triggers, &telemetry, &status)?;
+            }
+
+            // Publish immediately via Iceoryx2
+            let publish_timestamp_ns = SystemTime::now()
+                .duration_since(UNIX_EPOCH)?
+                .as_nanos() as u64;
+
+            // Timestamps are taken in CLOCK_REALTIME and published in `--clock-domain`
+            let trigger = DomainConverter::sample().convert_trigger(CameraTrigger::new(global_trigger_id, hardware_timestamp_ns, publish_timestamp_ns), ClockDomain::Realtime, clock_domain);
+
+            // Switch between single and batched triggers on the observed rate
+            let batched = selector.as_mut().is_some_and(|selector| selector.observe(hardware_timestamp_ns));
+            if batched != batching {
+                batching = batched;
+                let rate_hz = selector.as_ref().map_or(0.0, BatchSelector::rate_hz);
+                status.line(
+                    "batching",
+                    format_args!("Trigger rate {:.0}Hz, {}", rate_hz, if batched { "batching triggers" } else { "back to single triggers" }),
+                    &[("batched", batched.into()), ("rate_hz", rate_hz.into())],
+                );
+            }
+            if batched {
+                if let Some(ready) = batcher.due(publish_timestamp_ns) {
+                    send_batch(&publisher, &ready, batch_header, &mut history, &status)?;
+                }
+                if let Some(ready) = batcher.push(trigger, publish_timestamp_ns) {
+                    send_batch(&publisher, &ready, batch_header, &mut history, &status)?;
+                }
+            } else if let Some(pending) = batcher.flush() {
+                // Keep the triggers in order
+                send_batch(&publisher, &pending, batch_header, &mut history, &status)?;
+            }
+
+            // Batched triggers go out with their batch
+            let trigger_header = header(&trigger);
+            if !batched {
+                if !publisher.publish(trigger, trigger_header)? {
+                    // The loan pool is the bottleneck: this trigger never reaches the cameras
+                    status.line(
+                        "loan_exhausted",
+                        format_args!(
+                            "WARNING: loan pool of {} samples exhausted, trigger {} (pulse {} of {}) dropped; {}",
+                            max_loaned_samples,
+                            global_trigger_id,
+                            pulse + 1,
+                            burst_size,
+                            publisher.loans().summary()
+                        ),
+                        &[
+                            ("trigger_id", global_trigger_id.into()),
+                            ("pulse", pulse.into()),
+                            ("burst_size", burst_size.into()),
+                            ("max_loaned_samples", max_loaned_samples.into()),
+                            ("exhausted", publisher.loans().exhausted.into()),
+                        ],
+                    );
+                    continue;
+                }
+                history.push(trigger, trigger_header);
+            }
+            let trace = TraceId::new(trace_origin, global_trigger_id);
+            tracer.trigger_published(trace, hardware_timestamp_ns);
+
+            // Derived trigger streams, signed separately since their timestamps differ
+            for (sensor, sensor_publisher) in sensors.iter().zip(&sensor_publishers) {
+                if let Some(sensor_trigger) = sensor.fan_out(trigger) {
+                    sensor_publisher.publish(sensor_trigger, header(&sensor_trigger))?;
+                }
+            }
+
+            let high = priority.map(|pattern| pattern.is_priority(global_trigger_id));
+            let mut fields = vec![
+                ("trigger_id", global_trigger_id.into()),
+                ("trace", trace.to_string().into()),
+                ("hw_ts", hardware_timestamp_ns.into()),
+                ("ipc_latency_ns", publish_timestamp_ns.saturating_sub(hardware_timestamp_ns).into()),
+            ];
+            if let Some(high) = high {
+                fields.push(("priority", high.into()));
+            }
+            if let Some(offset_ns) = timestamper.clock_offset_ns() {
+                fields.push(("phc_offset_ns", offset_ns.into()));
+            }
+            status.line(
+                "trigger",
+                format_args!(
+                    "Published trigger: id={}, hw_ts={}, ipc_latency={}ns, trace={}{}",
+                    global_trigger_id,
+                    hardware_timestamp_ns,
+                    publish_timestamp_ns.saturating_sub(hardware_timestamp_ns),
+                    trace,
+                    if high == Some(true) { ", priority=high" } else { "" }
+                ),
+                &fields,
+            );
+
+            // Trigger loss to slow consumers, visible to the whole rig
+            if global_trigger_id.is_multiple_of(DELIVERY_REPORT_EVERY) {
+                let delivery = publisher.delivery();
+                telemetry.publish("delivery", &delivery.to_string())?;
+                telemetry.publish("loans", &publisher.loans().summary())?;
+                for consumer in delivery_monitor.consumers() {
+                    report_delivery_latency(consumer, &telemetry, &status)?;
+                }
+                for camera in skew_monitor.cameras() {
+                    report_group_latency(camera, &telemetry, &status)?;
+                }
+                let undelivered = delivery.undelivered;
+                if undelivered > reported_undelivered {
+                    status.line(
+                        "undelivered",
+                        format_args!("WARNING: {} ({})", delivery.summary(), unable_to_deliver),
+                        &[
+                            ("published", delivery.published.into()),
+                            ("undelivered", undelivered.into()),
+                            ("strategy", unable_to_deliver.to_string().into()),
+                        ],
+                    );
+                }
+                reported_undelivered = undelivered;
+
+                // Timer pulses and trigger interrupts that fired but were never read back never reach anyone
+                let missed = timestamper.missed();
+                if missed > reported_missed {
+                    status.line(
+                        "missed_pulses",
+                        format_args!("WARNING: {} pulses fired without being published ({})", missed, timestamper.describe()),
+                        &[("missed", missed.into()), ("published", global_trigger_id.into())],
+                    );
+                }
+                reported_missed = missed;
+            }
+        }
+
+        // Simulate configurable trigger rate (grid aligned and steered triggers wait at the top of the loop)
+        if phase_offset_ms.is_none() && !steering && !external {
+            std::thread::sleep(Duration::from_millis(trigger_interval_ms));
+        }
+    }
+
+    // Only reached when a `--duration` batch run is over
+    if let Some(pending) = batcher.flush() {
+        send_batch(&publisher, &pending, batch_header, &mut history, &status)?;
+    }
+    let Some(batch) = batch else {
+        return Ok(());
+    };
+    let results = BatchResults {
+        triggers: global_trigger_id,
+        ..Default::default()
+    };
+    std::process::exit(batch.finish(&results, &LatencyBreakdown::default())?);
+}
+
+// Fires the trigger channels, each on its own grid and service with its own trigger ids. Channel triggers
+// are stamped with the system clock when their grid point is reached; bursts, canaries, batching and the
+// trigger history belong to the main trigger line. Only returns on errors
+fn run_channels(
+    mut schedule: ChannelSchedule,
+    identity: &NodeIdentity,
+    sign_key: Option<&str>,
+    clock_domain: ClockDomain,
+    clock_policy: &AtomicU8,
+    unable_to_deliver: UnableToDeliver,
+    status: &Status,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let signer = match sign_key {
+        Some(path) => Some(TriggerSigner::from_key_file(Path::new(path))?),
+        None => None,
+    };
+    let node = create_node(identity)?;
+    let publishers = schedule
+        .channels()
+        .iter()
+        .map(|channel| TriggerPublisher::create_with(&node, &trigger::channel_service(&channel.name), unable_to_deliver, loans::DEFAULT_MAX_LOANS))
+        .collect::<Result<Vec<_>, _>>()?;
+    // Trigger ids repeat across channels, so each channel is a trace origin of its own
+    let trace_origins: Vec<u32> = publishers.iter().map(|_| TraceId::new_origin()).collect();
+
+    while let Some((index, due_ns)) = schedule.next() {
+        clock::sleep_until_realtime_ns(due_ns);
+        let hardware_timestamp_ns = clock::realtime_now_ns();
+        let trigger = schedule.fire(index, hardware_timestamp_ns, clock::realtime_now_ns());
+        let trigger = DomainConverter::sample().convert_trigger(trigger, ClockDomain::Realtime, clock_domain);
+        let header = TriggerSignature {
+            timestamp_source: TimestampSource::System as u8,
+            clock_domain: clock_domain as u8,
+            clock_policy: clock_policy.load(Ordering::Relaxed),
+            trace_origin: trace_origins[index],
+            ..signer.as_ref().map_or_else(TriggerSignature::default, |signer| signer.sign(&trigger))
+        };
+        let channel = schedule.channels()[index].name.as_str();
+        let trigger_id = trigger.frame_id;
+        if !publishers[index].publish(trigger, header)? {
+            status.line(
+                "loan_exhausted",
+                format_args!("WARNING: loan pool of channel {} exhausted, trigger {} dropped; {}", channel, trigger_id, publishers[index].loans().summary()),
+                &[
+                    ("channel", channel.into()),
+                    ("trigger_id", trigger_id.into()),
+                    ("max_loaned_samples", publishers[index].max_loaned_samples().into()),
+                    ("exhausted", publishers[index].loans().exhausted.into()),
+                ],
+            );
+            continue;
+        }
+        let trace = TraceId::new(trace_origins[index], trigger_id);
+        status.line(
+            "trigger",
+            format_args!("Published trigger: channel={}, id={}, hw_ts={}, trace={}", channel, trigger_id, hardware_timestamp_ns, trace),
+            &[
+                ("channel", channel.into()),
+                ("trigger_id", trigger_id.into()),
+                ("trace", trace.to_string().into()),
+                ("hw_ts", hardware_timestamp_ns.into()),
+            ],
+        );
+    }
+    Ok(())
+}
+
+// Measures the phase of a trigger against the video reference, reports lock changes and the periodic phase statistics
+fn report_genlock(
+    genlock: &mut Genlock,
+    trigger_ns: u64,
+    measured: &mut u64,
+    telemetry: &TelemetryPublisher,
+    status: &Status,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(sample) = genlock.on_trigger(trigger_ns) {
+        if let Some(locked) = sample.lock_changed {
+            let event = if locked { "GENLOCK: locked to the reference" } else { "WARNING: genlock lost the reference" };
+            status.line(
+                "genlock_lock",
+                format_args!(
+                    "{} (phase {:.1}us, error {:.1}us)",
+                    event,
+                    sample.phase_ns / 1e3,
+                    sample.error_ns / 1e3
+                ),
+                &[("locked", locked.into()), ("phase_us", (sample.phase_ns / 1e3).into()), ("error_us", (sample.error_ns / 1e3).into())],
+            );
+        }
+    }
+    *measured += 1;
+    if measured.is_multiple_of(GENLOCK_REPORT_EVERY) {
+        let report = genlock.report();
+        telemetry.publish("genlock", &report.summary())?;
+        status.line(
+            "genlock",
+            format_args!("GENLOCK: {}", report.summary()),
+            &[
+                ("reference_hz", report.reference_hz.into()),
+                ("triggers", report.triggers.into()),
+                ("phase_us", report.phase_us.into()),
+                ("jitter_us", report.jitter_us.into()),
+                ("error_us", report.error_us.into()),
+                ("drift_us_per_s", report.drift_us_per_s.into()),
+                ("locked", report.locked.into()),
+            ],
+        );
+    }
+    Ok(())
+}
+
+// A step of the publisher's clock or the end of the disturbance, also on telemetry
+fn report_clock_event(event: &ClockEvent, telemetry: &TelemetryPublisher, status: &Status) -> Result<(), Box<dyn std::error::Error>> {
+    status.line(
+        "clock_event",
+        format_args!("{}{}", if event.is_step() { "WARNING: " } else { "" }, event),
+        &[
+            ("clock", event.clock.into()),
+            ("event", event.kind.name().into()),
+            ("step_ns", event.step_ns.into()),
+            ("clock_ns", event.clock_ns.into()),
+            ("disturbed_ns", event.disturbed_ns.into()),
+            ("steps", event.steps.into()),
+        ],
+    );
+    telemetry.publish("clock_event", &event.to_string())?;
+    Ok(())
+}
+
+// Downgrades and recoveries of the trigger clock, also on telemetry
+fn report_clock_quality(change: &QualityChange, telemetry: &TelemetryPublisher, status: &Status) -> Result<(), Box<dyn std::error::Error>> {
+    status.line(
+        "clock_quality",
+        format_args!("{}{}", if change.is_downgrade() { "WARNING: " } else { "" }, change),
+        &[
+            ("quality", change.quality.to_string().into()),
+            ("detail", change.detail.as_str().into()),
+            ("policy", change.active.map(|policy| policy.to_string()).into()),
+            ("downgraded_ns", change.downgraded_ns.into()),
+        ],
+    );
+    telemetry.publish("clock_quality", &change.to_string())?;
+    Ok(())
+}
+
+// One line per canary: healthy when every known consumer acknowledged it in time
+fn report_canary(report: &CanaryReport, telemetry: &TelemetryPublisher, status: &Status) -> Result<(), Box<dyn std::error::Error>> {
+    telemetry.publish("canary", &report.to_string())?;
+    status.line(
+        "canary",
+        format_args!("{}CANARY: {}", if report.is_healthy() { "" } else { "WARNING: " }, report),
+        &[
+            ("sequence", report.sequence.into()),
+            ("healthy", report.is_healthy().into()),
+            ("acknowledged", report.acknowledged_list().into()),
+            ("missing", report.missing.join(", ").into()),
+            ("max_latency_ms", report.max_latency_ns().map(|ns| ns as f64 / 1e6).into()),
+        ],
+    );
+    Ok(())
+}
+
+// Delivery latency of one consumer from its receive-time feedback
+fn report_delivery_latency(consumer: &ConsumerDelivery, telemetry: &TelemetryPublisher, status: &Status) -> Result<(), Box<dyn std::error::Error>> {
+    let summary = consumer.summary(status.display_unit());
+    telemetry.publish("delivery_latency", &summary)?;
+    status.line(
+        "delivery_latency",
+        format_args!("DELIVERY LATENCY: {}", summary),
+        &[
+            ("consumer", consumer.consumer.as_str().into()),
+            ("count", consumer.latency.count().into()),
+            ("p50_ms", consumer.latency.percentile(50.0).into()),
+            ("p95_ms", consumer.latency.percentile(95.0).into()),
+            ("max_ms", consumer.latency.max().into()),
+            ("baseline_p95_ms", consumer.baseline_p95_ms.into()),
+            ("degraded", consumer.degraded.into()),
+        ],
+    );
+    Ok(())
+}
+
+// A consumer whose recent delivery latency left (or came back to) its baseline
+fn report_delivery_change(consumer: &ConsumerDelivery, status: &Status) {
+    let (kind, event) = if consumer.degraded {
+        ("delivery_degraded", "WARNING: delivery latency degraded")
+    } else {
+        ("delivery_recovered", "Delivery latency recovered")
+    };
+    status.line(
+        kind,
+        format_args!("{} for {}: recent p95 {:.2}ms, baseline {:.2}ms", event, consumer.consumer, consumer.recent_p95_ms(), consumer.baseline_p95_ms.unwrap_or_default()),
+        &[
+            ("consumer", consumer.consumer.as_str().into()),
+            ("recent_p95_ms", consumer.recent_p95_ms().into()),
+            ("baseline_p95_ms", consumer.baseline_p95_ms.into()),
+        ],
+    );
+}
+
+// Latency of one grouped camera and its offset against the group
+fn report_group_latency(camera: &CameraSkew, telemetry: &TelemetryPublisher, status: &Status) -> Result<(), Box<dyn std::error::Error>> {
+    telemetry.publish("group_latency", &camera.to_string())?;
+    status.line(
+        "group_latency",
+        format_args!("GROUP LATENCY: {}", camera),
+        &[
+            ("group", camera.group.as_str().into()),
+            ("camera", camera.camera.as_str().into()),
+            ("latency_ms", camera.latency_ms().into()),
+            ("baseline_ms", camera.baseline_ms.into()),
+            ("skew_ms", camera.skew_ms.into()),
+            ("peers", camera.peers.into()),
+            ("diverged", camera.diverged.into()),
+        ],
+    );
+    Ok(())
+}
+
+// A camera whose latency moved away from (or back to) the rest of its group
+fn report_skew_change(camera: &CameraSkew, telemetry: &TelemetryPublisher, status: &Status) -> Result<(), Box<dyn std::error::Error>> {
+    telemetry.publish("latency_skew", &camera.to_string())?;
+    let (kind, prefix, relation) = if camera.diverged {
+        ("latency_skew", "WARNING: ", "diverged from")
+    } else {
+        ("latency_skew_recovered", "", "back with")
+    };
+    status.line(
+        kind,
+        format_args!(
+            "{}LATENCY SKEW: {} {} group {}: skew {:+.2}ms against {} cameras, latency {:.2}ms (baseline {:.2}ms)",
+            prefix,
+            camera.camera,
+            relation,
+            camera.group,
+            camera.skew_ms.unwrap_or_default(),
+            camera.peers,
+            camera.latency_ms().unwrap_or_default(),
+            camera.baseline_ms.unwrap_or_default()
+        ),
+        &[
+            ("group", camera.group.as_str().into()),
+            ("camera", camera.camera.as_str().into()),
+            ("skew_ms", camera.skew_ms.into()),
+            ("latency_ms", camera.latency_ms().into()),
+            ("baseline_ms", camera.baseline_ms.into()),
+            ("peers", camera.peers.into()),
+        ],
+    );
+    Ok(())
+}
+
+// Publishes a batch and keeps its triggers for history queries, or reports the dropped batch
+fn send_batch(
+    publisher: &TriggerPublisher,
+    batch: &TriggerBatch,
+    header: TriggerSignature,
+    history: &mut TriggerHistory,
+    status: &Status,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if publisher.publish_batch(batch, header)? {
+        batch.triggers().for_each(|trigger| history.push(trigger, header));
+        return Ok(());
+    }
+    status.line(
+        "loan_exhausted",
+        format_args!(
+            "WARNING: loan pool of {} samples exhausted, batch of {} triggers from {} dropped; {}",
+            publisher.max_loaned_samples(),
+            batch.len(),
+            batch.first.frame_id,
+            publisher.loans().summary()
+        ),
+        &[
+            ("trigger_id", batch.first.frame_id.into()),
+            ("batch", batch.len().into()),
+            ("max_loaned_samples", publisher.max_loaned_samples().into()),
+            ("exhausted", publisher.loans().exhausted.into()),
+        ],
+    );
+    Ok(())
+}
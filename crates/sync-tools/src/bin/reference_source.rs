@@ -0,0 +1,55 @@
+use std::process;
+use sync_core::cli::Args;
+use sync_core::namespace::NodeIdentity;
+use sync_core::status::Status;
+use sync_core::timestamping;
+use sync_core::units::Nanos;
+use sync_iceoryx2::node::create_node;
+use sync_iceoryx2::reference::{ReferencePublisher, REFERENCE_SERVICE};
+
+/// Edge counts are reported every this many edges.
+const REPORT_EVERY: u64 = 300;
+
+// Publishes the edges of a video reference for the publisher's genlock.
+//
+// Edges come from an external timestamp source (see `sync_core::timestamping`):
+// the vsync output of a sync separator on black burst, wired to a GPIO line
+// latched by the timestamp engine (`hte:/dev/gpiochip0:7`), or a timer pulse
+// train standing in for house sync (`timer:29.97`). Every edge goes out on
+// `Video/Reference`; capture cards with their own vsync timestamps publish
+// there directly with `ReferencePublisher`.
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::from_env(&[]);
+    let status = Status::from_args(&args, "reference_source")?;
+    let Some(spec) = args.positional::<String>(0) else {
+        eprintln!("Usage: {} <hte:<chip>:<line>[:<edge>] | timer:<hz>[:<phc>] | mcu:<addr>> [--status-format text|json] [--display-unit ms|us|ns]", args.program());
+        process::exit(2);
+    };
+    let mut source = timestamping::open(&spec)?;
+    if !source.is_external() {
+        eprintln!("Error: {} stamps the time it is read, not reference edges", source.describe());
+        process::exit(2);
+    }
+
+    let node = create_node(&NodeIdentity::from_args(&args, "reference_source", None)?)?;
+    let publisher = ReferencePublisher::create(&node)?;
+    status.line(
+        "start",
+        format_args!("Publishing reference edges from {} on {}", source.describe(), REFERENCE_SERVICE),
+        &[("source", source.describe().into()), ("service", REFERENCE_SERVICE.into())],
+    );
+
+    let mut edges = 0u64;
+    loop {
+        let Nanos(edge_ns) = source.next()?;
+        publisher.publish(edge_ns)?;
+        edges += 1;
+        if edges.is_multiple_of(REPORT_EVERY) {
+            status.line(
+                "reference",
+                format_args!("REFERENCE: {} edges published, {} missed", edges, source.missed()),
+                &[("edges", edges.into()), ("missed", source.missed().into())],
+            );
+        }
+    }
+}
@@ -0,0 +1,240 @@
+use std::process;
+use std::time::Duration;
+use sync_core::burst::BurstPattern;
+use sync_core::canary;
+use sync_core::cli::Args;
+use sync_core::clock;
+use sync_core::clock_guard::{ClockEvent, ClockGuard, ClockGuardConfig};
+use sync_core::feedback::{self, ReceiveFeedback};
+use sync_core::namespace::NodeIdentity;
+use sync_core::pipeline::{self, MatchConfig, MatchPipeline, Stage};
+use sync_core::reload::{ConfigReload, ConfigWatcher};
+use sync_core::stats::LatencyStats;
+use sync_core::status::Status;
+use sync_core::CameraTrigger;
+use sync_iceoryx2::feedback::FeedbackPublisher;
+use sync_iceoryx2::frames::{FramePublisher, FrameSubscriber, RAW_FRAMES_SERVICE, SYNCED_FRAMES_SERVICE};
+use sync_iceoryx2::node::create_node;
+use sync_iceoryx2::trigger::TriggerSubscriber;
+
+/// Correction statistics are reported every this many frames.
+const REPORT_EVERY: u64 = 100;
+
+/// Options that never take a value.
+const SWITCHES: &[&str] = &["drop-unmatched"];
+
+/// Options a changed `--config` file (or SIGHUP) applies while running; the others need a restart.
+const LIVE_OPTIONS: &[&str] = &["match-tolerance-ms", "tolerance-ms", "future-penalty", "drop-unmatched"];
+
+// Retrofits trigger sync onto a frame pipeline that cannot be modified: reads
+// its unsynchronized frame stream, matches every frame against the trigger
+// service and republishes it with the trigger's hw_ts (see `sync_core::retime`).
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::from_env(SWITCHES);
+    let status = Status::from_args(&args, "retime_proxy")?;
+    status.text(format_args!(
+        "Usage: {} [--input <service>] [--output <service>] [--source-clock realtime|monotonic] [--match-tolerance-ms <ms>] [--future-penalty <factor>] [--max-pending-triggers <n>] [--burst-size <k>] [--match-stages <stage,...>] [--drop-unmatched] [--clock-max-step-ms <ms>] [--clock-settle-ms <ms>] [--consumer-name <name>] [--config <file.toml>] [--status-format text|json] [--display-unit ms|us|ns]",
+        args.program()
+    ));
+    let input = args.value("input").unwrap_or(RAW_FRAMES_SERVICE).to_string();
+    let output = args.value("output").unwrap_or(SYNCED_FRAMES_SERVICE).to_string();
+    // Sources stamping with CLOCK_MONOTONIC (e.g. GStreamer buffers, V4L2) are mapped to realtime
+    let monotonic_source = match args.value("source-clock").unwrap_or("realtime") {
+        "realtime" => false,
+        "monotonic" => true,
+        other => {
+            eprintln!("Error: unknown source clock '{}' (expected realtime or monotonic)", other);
+            process::exit(2);
+        }
+    };
+    // `--match-tolerance-ms`, `--future-penalty` and `--max-pending-triggers` (see `sync_core::pipeline::MatchConfig`)
+    let match_config = match MatchConfig::from_args(&args) {
+        Ok(config) => config,
+        Err(error) => {
+            eprintln!("Error: {}", error);
+            process::exit(2);
+        }
+    };
+    let mut tolerance_ms = match_config.tolerance_ms;
+    let burst = BurstPattern::new(args.value_as::<u32>("burst-size").unwrap_or(1));
+    let mut drop_unmatched = args.flag("drop-unmatched");
+    // Matcher chain, e.g. `dedupe,drift,associate,audit` (see `sync_core::pipeline`)
+    let stages = match args.value("match-stages").map(pipeline::parse_stages).unwrap_or(Ok(vec![Stage::Associate])) {
+        Ok(stages) => stages,
+        Err(error) => {
+            eprintln!("Error: {}", error);
+            process::exit(2);
+        }
+    };
+    let stage_names: Vec<&str> = stages.iter().map(Stage::name).collect();
+
+    let node = create_node(&NodeIdentity::from_args(&args, "retime_proxy", None)?)?;
+    let triggers = TriggerSubscriber::create(&node)?;
+    // Receive times of canary and sampled triggers are reported under this name (see `sync_core::feedback`)
+    let feedback = FeedbackPublisher::create(&node)?;
+    let consumer_name = args.value("consumer-name").unwrap_or("retime_proxy").to_string();
+    let frames = FrameSubscriber::create(&node, &input)?;
+    let publisher = FramePublisher::create(&node, &output)?;
+    status.line(
+        "start",
+        format_args!("Re-timestamping frames from {} to {} ({} source clock, tolerance {}ms)", input, output, if monotonic_source { "monotonic" } else { "realtime" }, tolerance_ms),
+        &[
+            ("input", input.as_str().into()),
+            ("output", output.as_str().into()),
+            ("monotonic_source", monotonic_source.into()),
+            ("tolerance_ms", tolerance_ms.into()),
+            ("future_penalty", match_config.future_penalty.into()),
+            ("max_pending_triggers", match_config.max_pending.into()),
+            ("burst_size", burst.size.into()),
+            ("match_stages", stage_names.join(",").into()),
+        ],
+    );
+
+    // Triggers carry their trace origin into the republished headers
+    let mut retimer: MatchPipeline<u32> = MatchPipeline::with_stages(&stages, tolerance_ms, burst);
+    retimer.configure(&match_config);
+    // Source timestamp minus trigger hw_ts of the matched frames, ms
+    let mut correction = LatencyStats::default();
+    let (mut frame_count, mut matched_count) = (0u64, 0u64);
+    // CLOCK_REALTIME against CLOCK_MONOTONIC and the trigger timestamps against their publish times (see `sync_core::clock_guard`)
+    let clock_guard_config = ClockGuardConfig::from_args(&args);
+    let mut realtime_guard = ClockGuard::new("realtime", clock_guard_config);
+    let mut trigger_guard = ClockGuard::new("trigger", clock_guard_config);
+    // Safe-to-change options follow the `--config` file and SIGHUP (see `sync_core::reload`)
+    let mut config_watcher = ConfigWatcher::from_args(&args, SWITCHES, LIVE_OPTIONS);
+    loop {
+        if let Some(reload) = config_watcher.as_mut().and_then(ConfigWatcher::poll) {
+            let path = config_watcher.as_ref().map(|watcher| watcher.path().display().to_string()).unwrap_or_default();
+            // An invalid value leaves everything as it was
+            let reload = reload.and_then(|reload| {
+                let match_config = MatchConfig::from_args(&reload.args)?;
+                tolerance_ms = match_config.tolerance_ms;
+                retimer.set_tolerance_ms(tolerance_ms);
+                retimer.set_future_penalty(match_config.future_penalty);
+                drop_unmatched = reload.args.flag("drop-unmatched");
+                Ok(reload)
+            });
+            report_config_reload(&path, &reload, &status);
+        }
+        if let Some(event) = realtime_guard.observe(clock::realtime_now_ns(), clock::monotonic_now_ns()) {
+            report_clock_event(&event, &status);
+        }
+        while let Some((trigger, header)) = triggers.receive()? {
+            // Frames are retimed in CLOCK_REALTIME, whatever the publisher stamped the triggers in
+            let (trigger, header) = clock::trigger_to_realtime(trigger, header)?;
+            if feedback::is_sampled(trigger.frame_id) {
+                feedback.publish(ReceiveFeedback::new(&consumer_name, &trigger, clock::realtime_now_ns()))?;
+            }
+            // Canaries are never matched
+            if canary::is_canary(trigger.frame_id) {
+                continue;
+            }
+            if let Some(event) = trigger_guard.observe(trigger.hw_timestamp_ns, trigger.publish_timestamp_ns) {
+                report_clock_event(&event, &status);
+            }
+            retimer.on_trigger(trigger, header.trace_origin);
+        }
+
+        let mut idle = true;
+        // Refreshed per batch, so clock steps are followed
+        let realtime_minus_monotonic_ns = clock::realtime_minus_monotonic_ns();
+        // Frames are still retimed while a clock is disturbed, but neither learned from nor measured
+        let clock_disturbed = realtime_guard.is_disturbed() || trigger_guard.is_disturbed();
+        retimer.set_learning_frozen(clock_disturbed);
+        while let Some(result) = frames.receive(|header, data| {
+            let frame_ns = if monotonic_source {
+                clock::monotonic_to_realtime_ns(header.timestamp_ns, realtime_minus_monotonic_ns)
+            } else {
+                header.timestamp_ns
+            };
+            let frame = retimer.on_frame(frame_ns);
+            let matched = frame.matched.as_ref().map(|(trigger, trace_origin, _)| (*trigger, *trace_origin));
+            if let Some((CameraTrigger { hw_timestamp_ns: hw_ts, .. }, _)) = matched.filter(|_| !clock_disturbed) {
+                correction.add((frame_ns as i64 - hw_ts as i64) as f64 / 1e6);
+            }
+            // Duplicates never reach the output
+            if frame.dropped_by == Some(Stage::Dedupe) || (matched.is_none() && drop_unmatched) {
+                return Ok(frame);
+            }
+            publisher.publish(header.retimed(matched), data).map(|_| frame)
+        })? {
+            idle = false;
+            frame_count += 1;
+            let frame = result?;
+            match frame.dropped_by {
+                None => matched_count += 1,
+                Some(Stage::Dedupe) => status.line(
+                    "duplicate_frame",
+                    format_args!("WARNING: frame {} repeats the previous frame's timestamp, dropped", frame_count),
+                    &[("frame", frame_count.into())],
+                ),
+                Some(_) => status.line(
+                    "unmatched_frame",
+                    format_args!("WARNING: no trigger within {}ms of frame {}{}", tolerance_ms, frame_count, if drop_unmatched { ", dropped" } else { "" }),
+                    &[("frame", frame_count.into()), ("dropped", drop_unmatched.into())],
+                ),
+            }
+            for finding in &frame.findings {
+                status.line(
+                    "match_audit",
+                    format_args!("AUDIT: frame {}: {}", frame_count, finding),
+                    &[("frame", frame_count.into()), ("finding", finding.as_str().into())],
+                );
+            }
+            if frame_count.is_multiple_of(REPORT_EVERY) {
+                status.line(
+                    "retime_stats",
+                    format_args!(
+                        "RETIMED {}/{} frames; source minus trigger time: {}",
+                        matched_count,
+                        frame_count,
+                        correction.summary(status.display_unit())
+                    ),
+                    &[
+                        ("frames", frame_count.into()),
+                        ("matched", matched_count.into()),
+                        ("correction_p50_ms", correction.percentile(50.0).into()),
+                        ("correction_p95_ms", correction.percentile(95.0).into()),
+                        ("correction_min_ms", correction.min().into()),
+                        ("correction_max_ms", correction.max().into()),
+                        ("pending_triggers", retimer.pending().into()),
+                    ],
+                );
+                status.stages(retimer.stats());
+                status.latency_filter(&retimer);
+            }
+        }
+        // Frames are not notified: triggers end the wait early, frames are picked up within 500us
+        if idle {
+            triggers.wait(Duration::from_micros(500))?;
+        }
+    }
+}
+
+// Reports what a config reload applied and what waits for a restart, or why it failed
+fn report_config_reload(path: &str, reload: &Result<ConfigReload, String>, status: &Status) {
+    match reload {
+        Ok(reload) => status.line(
+            "config_reload",
+            format_args!("Config {} reloaded: {}", path, reload),
+            &[("path", path.into()), ("applied", reload.applied.join(",").into()), ("restart_required", reload.restart_required.join(",").into())],
+        ),
+        Err(e) => status.line("config_reload_failed", format_args!("WARNING: config {} not reloaded: {}", path, e), &[("path", path.into()), ("error", e.as_str().into())]),
+    }
+}
+
+// A clock step or the end of a disturbance
+fn report_clock_event(event: &ClockEvent, status: &Status) {
+    status.line(
+        "clock_event",
+        format_args!("{}{}", if event.is_step() { "WARNING: " } else { "" }, event),
+        &[
+            ("clock", event.clock.into()),
+            ("event", event.kind.name().into()),
+            ("step_ns", event.step_ns.into()),
+            ("clock_ns", event.clock_ns.into()),
+            ("disturbed_ns", event.disturbed_ns.into()),
+            ("steps", event.steps.into()),
+        ],
+    );
+}
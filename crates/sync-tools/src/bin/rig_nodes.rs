@@ -0,0 +1,87 @@
+use std::process;
+use sync_core::cli::Args;
+use sync_core::namespace::NodeIdentity;
+use sync_core::status::Status;
+use sync_iceoryx2::node::{self, RigNode};
+
+// Lists the iceoryx2 nodes and services of a rig, or removes what crashed
+// processes left behind.
+//
+// `list` prints the rig's nodes (alive or dead) and the services under its
+// resource prefix. `cleanup` removes the rig's dead nodes; iceoryx2 releases
+// their ports and the services no live process holds with them. Nodes of
+// other rigs and of other iceoryx2 applications are never touched (see
+// `sync_core::namespace`). `--dry-run` only lists what would be removed.
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::from_env(&["dry-run"]);
+    let status = Status::from_args(&args, "rig_nodes")?;
+    let mode = args.positional::<String>(0).unwrap_or_default();
+    let identity = NodeIdentity::from_args(&args, "rig_nodes", None)?;
+    status.text(format_args!("Usage: {} list | cleanup [--dry-run] [--rig-id <id>] [--status-format text|json] [--display-unit ms|us|ns]", args.program()));
+
+    match mode.as_str() {
+        "list" => list(&identity, &status),
+        "cleanup" => cleanup(&identity, args.flag("dry-run"), &status),
+        other => {
+            eprintln!("Error: unknown mode '{}' (expected list or cleanup)", other);
+            process::exit(2);
+        }
+    }
+}
+
+fn display_name(node: &RigNode) -> &str {
+    node.name.as_deref().unwrap_or("<unreadable>")
+}
+
+fn list(identity: &NodeIdentity, status: &Status) -> Result<(), Box<dyn std::error::Error>> {
+    let nodes = node::list_nodes(identity)?;
+    for node in &nodes {
+        let state = if node.alive { "alive" } else { "dead" };
+        status.line("rig_node", format_args!("  node {} ({})", display_name(node), state), &[("node", display_name(node).into()), ("alive", node.alive.into())]);
+    }
+    let services = node::list_services(identity)?;
+    for service in &services {
+        status.line("rig_service", format_args!("  service {}", service), &[("service", service.as_str().into())]);
+    }
+    let dead = nodes.iter().filter(|node| !node.alive).count();
+    status.line(
+        "rig_nodes",
+        format_args!("Rig {}: {} nodes ({} dead), {} services", identity.rig(), nodes.len(), dead, services.len()),
+        &[("rig", identity.rig().into()), ("alive", (nodes.len() - dead).into()), ("dead", dead.into()), ("services", services.len().into())],
+    );
+    if dead > 0 {
+        status.text(format_args!("Dead nodes are left by crashed processes; remove them with `cleanup`"));
+    }
+    Ok(())
+}
+
+fn cleanup(identity: &NodeIdentity, dry_run: bool, status: &Status) -> Result<(), Box<dyn std::error::Error>> {
+    let services_before = node::list_services(identity)?.len();
+    let removed: Vec<(String, bool)> = if dry_run {
+        node::list_nodes(identity)?.iter().filter(|node| !node.alive).map(|node| (display_name(node).to_string(), false)).collect()
+    } else {
+        node::remove_stale_nodes(identity)?.into_iter().map(|(name, removed)| (name.unwrap_or_else(|| "<unreadable>".to_string()), removed)).collect()
+    };
+    for (name, removed) in &removed {
+        let action = match (dry_run, removed) {
+            (true, _) => "would remove",
+            (false, true) => "removed",
+            (false, false) => "already gone",
+        };
+        status.line("rig_cleanup", format_args!("  {} dead node {}", action, name), &[("node", name.as_str().into()), ("removed", (*removed).into()), ("dry_run", dry_run.into())]);
+    }
+    let services_after = node::list_services(identity)?.len();
+    status.line(
+        "rig_cleanup_done",
+        format_args!(
+            "Rig {}: {} dead nodes {}, services {} -> {}",
+            identity.rig(),
+            removed.len(),
+            if dry_run { "found (dry run)" } else { "cleaned up" },
+            services_before,
+            services_after
+        ),
+        &[("rig", identity.rig().into()), ("dead", removed.len().into()), ("dry_run", dry_run.into()), ("services_before", services_before.into()), ("services_after", services_after.into())],
+    );
+    Ok(())
+}
@@ -0,0 +1,87 @@
+use std::time::Duration;
+use sync_core::cli::Args;
+use sync_core::namespace::NodeIdentity;
+use sync_core::ros2_bridge::{self, DEFAULT_FRAME_ID, DEFAULT_TOPIC};
+use sync_core::status::Status;
+use sync_iceoryx2::frames::{FrameSubscriber, SYNCED_FRAMES_SERVICE};
+use sync_iceoryx2::node::create_node;
+use zenoh::Wait;
+
+/// Bridge statistics are reported every this many frames.
+const REPORT_EVERY: u64 = 100;
+
+// Republishes the matched frames of a frame stream to ROS 2 as
+// `sensor_msgs/msg/Image`, stamped with the trigger's hw_ts (see
+// `sync_core::ros2_bridge`). The images go out over Zenoh; a
+// `zenoh-bridge-ros2dds` on the ROS 2 side delivers them to the subscribers
+// of the topic.
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::from_env(&[]);
+    let status = Status::from_args(&args, "ros2_bridge")?;
+    status.text(format_args!(
+        "Usage: {} [--input <service>] [--topic <key>] [--frame-id <id>] [--zenoh-config <file.json5>] [--status-format text|json]",
+        args.program()
+    ));
+    let input = args.value("input").unwrap_or(SYNCED_FRAMES_SERVICE).to_string();
+    let topic = args.value("topic").unwrap_or(DEFAULT_TOPIC).trim_start_matches('/').to_string();
+    let frame_id = args.value("frame-id").unwrap_or(DEFAULT_FRAME_ID).to_string();
+    // Peer mode with multicast scouting unless configured otherwise
+    let config = match args.value("zenoh-config") {
+        Some(path) => zenoh::Config::from_file(path)?,
+        None => zenoh::Config::default(),
+    };
+
+    let node = create_node(&NodeIdentity::from_args(&args, "ros2_bridge", None)?)?;
+    let frames = FrameSubscriber::create(&node, &input)?;
+    let session = zenoh::open(config).wait()?;
+    let publisher = session.declare_publisher(topic.clone()).wait()?;
+    status.line(
+        "ros2_bridge",
+        format_args!("Republishing matched frames from {} as sensor_msgs/msg/Image on /{} (frame_id {})", input, topic, frame_id),
+        &[("input", input.as_str().into()), ("topic", format!("/{}", topic).into()), ("frame_id", frame_id.as_str().into())],
+    );
+
+    let (mut frame_count, mut published, mut unmatched, mut rejected) = (0u64, 0u64, 0u64, 0u64);
+    loop {
+        let mut idle = true;
+        while let Some(message) = frames.receive(|header, data| {
+            // Without a trigger there is no hw_ts to stamp
+            if !header.is_matched() {
+                return None;
+            }
+            Some((header.trigger_id, ros2_bridge::image_message(header, &frame_id, data)))
+        })? {
+            idle = false;
+            frame_count += 1;
+            match message {
+                None => unmatched += 1,
+                Some((_, Ok(image))) => {
+                    publisher.put(image).wait()?;
+                    published += 1;
+                }
+                Some((trigger_id, Err(error))) => {
+                    rejected += 1;
+                    // Every frame of a stream has the same format: reported once
+                    if rejected == 1 {
+                        status.line(
+                            "ros2_rejected",
+                            format_args!("WARNING: frame of trigger {} not republished: {}", trigger_id, error),
+                            &[("trigger_id", trigger_id.into()), ("error", error.as_str().into())],
+                        );
+                    }
+                }
+            }
+            if frame_count.is_multiple_of(REPORT_EVERY) {
+                status.line(
+                    "ros2_bridge_stats",
+                    format_args!("ROS2 {}/{} frames published, {} unmatched, {} rejected", published, frame_count, unmatched, rejected),
+                    &[("frames", frame_count.into()), ("published", published.into()), ("unmatched", unmatched.into()), ("rejected", rejected.into())],
+                );
+            }
+        }
+        // Frames are not notified: picked up within 500us
+        if idle {
+            std::thread::sleep(Duration::from_micros(500));
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use std::process;
+use std::time::Duration;
+use sync_core::cli::Args;
+use sync_core::namespace::NodeIdentity;
+use sync_core::readiness::{self, ReadinessReport};
+use sync_core::status::Status;
+use sync_core::transport::{self, RoundTrips};
+use sync_core::units::Unit;
+use sync_iceoryx2::node::create_node;
+use sync_iceoryx2::trigger::{TriggerPublisher, TriggerSubscriber};
+
+/// Round trips measured for the IPC latency baseline.
+const ROUND_TRIPS: u64 = 1000;
+/// Baseline p95 above this points at a misconfigured or overloaded host.
+const MAX_IPC_P95_MS: f64 = 1.0;
+
+// Validates the local IPC path for field bring-up: shared memory setup,
+// trigger delivery and integrity, baseline IPC latency and clock sanity.
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::from_env(&[]);
+    let status = Status::from_args(&args, "selftest")?;
+    let round_trips = args.value_as::<u64>("round-trips").unwrap_or(ROUND_TRIPS);
+    let identity = NodeIdentity::from_args(&args, "selftest", None)?;
+    status.text(format_args!("Usage: {} [--round-trips <n>] [--rig-id <id>] [--status-format text|json] [--display-unit ms|us|ns]", args.program()));
+    status.text(format_args!("Running self-test..."));
+
+    let mut report = ReadinessReport::default();
+    readiness::check_clocks(&mut report);
+    if let Err(e) = check_ipc(&mut report, &identity, round_trips, status.display_unit()) {
+        report.check("ipc", false, e.to_string());
+    }
+    report.print(&status);
+
+    if !report.is_ready() {
+        process::exit(1);
+    }
+    Ok(())
+}
+
+fn check_ipc(report: &mut ReadinessReport, identity: &NodeIdentity, round_trips: u64, unit: Unit) -> Result<(), Box<dyn std::error::Error>> {
+    let node = create_node(identity)?;
+    // A private service, so a running rig is not disturbed
+    let service_name = format!("Camera/SelfTest/{}", process::id());
+    let publisher = TriggerPublisher::create_for(&node, &service_name)?;
+    let subscriber = TriggerSubscriber::create_for(&node, &service_name)?;
+    report.check("shared memory setup", true, format!("service {}", service_name));
+
+    let RoundTrips { lost, corrupted, latency, .. } = transport::round_trips(&publisher, &subscriber, round_trips, Duration::from_millis(100))?;
+
+    report.check("trigger delivery", lost == 0, format!("{} of {} lost", lost, round_trips));
+    report.check("payload integrity", corrupted == 0, format!("{} of {} corrupted", corrupted, round_trips));
+    report.check(
+        "baseline IPC latency",
+        latency.count() > 0 && latency.percentile(95.0) <= MAX_IPC_P95_MS,
+        latency.summary(unit),
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sync_core::readiness::Outcome;
+
+    #[test]
+    fn triggers_go_through_the_local_ipc_path() {
+        let identity = NodeIdentity::from_args(&Args::parse(["selftest".to_string()], &[]), "selftest", Some("test")).unwrap();
+        let mut report = ReadinessReport::default();
+        check_ipc(&mut report, &identity, 20, Unit::Us).unwrap();
+        // The latency baseline depends on the host, delivery and integrity do not
+        let outcomes: Vec<_> = report.checks().iter().map(|check| (check.name.as_str(), check.outcome)).collect();
+        assert_eq!(
+            outcomes[..3],
+            [("shared memory setup", Outcome::Pass), ("trigger delivery", Outcome::Pass), ("payload integrity", Outcome::Pass)]
+        );
+        assert_eq!(report.checks()[1].detail, "0 of 20 lost");
+        assert_eq!(outcomes[3].0, "baseline IPC latency");
+    }
+}
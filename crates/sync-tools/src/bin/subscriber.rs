@@ -0,0 +1,1078 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use sync_core::batch::{self, BatchResults, BatchRun};
+use sync_core::burst::BurstPattern;
+use sync_core::canary;
+use sync_core::channel::TriggerChannel;
+use sync_core::cli::Args;
+use sync_core::clock::{self, ClockDomain};
+use sync_core::clock_guard::{ClockEvent, ClockGuard, ClockGuardConfig};
+use sync_core::clock_quality::{self, DowngradePolicy};
+use sync_core::clockmap::ClockMapper;
+use sync_core::deadline::{DeadlineMonitor, MissedDeadline};
+use sync_core::diagnosis::MatchDiagnostics;
+use sync_core::drops::{TriggerGap, TriggerGaps};
+use sync_core::encoder::EncoderTrack;
+use sync_core::eventlog::EventLog;
+use sync_core::feedback::{self, ReceiveFeedback};
+use sync_core::intrinsics::CameraIntrinsics;
+use sync_core::latency::{FrameTiming, LatencyBreakdown};
+use sync_core::leap;
+use sync_core::lighting::StrobeLog;
+use sync_core::manifest::SessionManifest;
+use sync_core::matcher_state::{self, MatcherState, SAVE_INTERVAL_NS};
+use sync_core::namespace::NodeIdentity;
+use sync_core::pipeline::{self, MatchConfig, MatchPipeline, Queued, Stage};
+use sync_core::pose::PoseTrack;
+use sync_core::priority::{self, PriorityPattern};
+use sync_core::rate::TriggerRateEstimator;
+use sync_core::reload::{ConfigReload, ConfigWatcher};
+use sync_core::retention::{MatchQuality, RetentionAction, RetentionPolicy};
+use sync_core::runs::RunSummary;
+use sync_core::sensor::SensorProfile;
+use sync_core::session::{MatchRecord, SessionRecorder};
+use sync_core::shared_stats::{ProcessStats, StatsPublisher};
+use sync_core::signing::{SignatureStatus, TriggerSignature, TriggerVerifier};
+use sync_core::skew::GroupLatency;
+use sync_core::status::Status;
+use sync_core::timecode::TimecodeClock;
+use sync_core::timestamping::TimestampSource;
+use sync_core::trace::{TraceId, Tracer};
+use sync_core::units::{DisplayMs, Nanos, TimestampFormat};
+use sync_core::CameraTrigger;
+use sync_iceoryx2::encoder::EncoderSubscriber;
+use sync_iceoryx2::feedback::FeedbackPublisher;
+use sync_iceoryx2::node::create_node;
+use sync_iceoryx2::pose::PoseSubscriber;
+use sync_iceoryx2::skew::GroupLatencyPublisher;
+use sync_iceoryx2::strobe::StrobeSubscriber;
+use sync_iceoryx2::telemetry::TelemetryPublisher;
+use sync_iceoryx2::trigger::{self, TriggerSubscriber};
+
+/// Options that never take a value.
+const SWITCHES: &[&str] = &["adaptive-tolerance", "quarantine", "shared-stats"];
+
+/// Options a changed `--config` file (or SIGHUP) applies while running; the others need a restart.
+const LIVE_OPTIONS: &[&str] = &["match-tolerance-ms", "tolerance-ms", "future-penalty", "adaptive-tolerance", "rate-tolerance-pct", "loop-deadline-ms", "match-deadline-ms", "event-log", "session-dir"];
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Parse command line arguments: subscriber [v4l2_delay_ms] [output_fps]
+    let args = Args::from_env(SWITCHES);
+
+    // Human text or line-delimited JSON with stable keys (`--status-format json`)
+    let status = Status::from_args(&args, "subscriber")?;
+
+    // Unit and epoch of the printed timestamps (`--timestamp-unit us --timestamp-epoch gps`), ns since the Unix epoch by default.
+    // TAI and GPS count the leap seconds of the table (`--leap-seconds <leap-seconds.list>`, bundled by default)
+    status.leap_seconds(leap::install_from_args(&args)?);
+    let timestamps = TimestampFormat::from_args(&args)?;
+
+    // SMPTE timecode of each matched frame, recorded in its metadata (`--timecode-rate 29.97 --timecode-epoch tai`)
+    let timecode_clock = TimecodeClock::from_args(&args)?;
+
+    // Fixed-duration run with a final report and pass/fail exit code (`--duration 60s`)
+    let batch = BatchRun::from_args(&args, "subscriber").unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        std::process::exit(batch::EXIT_USAGE);
+    });
+
+    // Match the frames of a fanned-out sensor (radar, ToF) against its own trigger stream
+    let sensor = match args.value("sensor") {
+        Some(profile) => Some(profile.parse::<SensorProfile>()?),
+        None => None,
+    };
+    // Or against an independent trigger channel of the publisher, with the channel's rate
+    let channel = match args.value("channel") {
+        Some(spec) => Some(spec.parse::<TriggerChannel>()?),
+        None => None,
+    };
+    if sensor.is_some() && channel.is_some() {
+        return Err("--sensor and --channel select different trigger streams, give one of them".into());
+    }
+    // Names the node instance, the matcher state and the receive-time feedback
+    let stream_name = sensor.as_ref().map(|sensor| sensor.name.as_str()).or(channel.as_ref().map(|channel| channel.name.as_str()));
+    let v4l2_delay_ms = args
+        .positional::<u64>(0)
+        .or_else(|| sensor.as_ref().map(|sensor| sensor.latency_ms.round() as u64))
+        .unwrap_or(150);
+    let output_fps = args.positional::<u32>(1).unwrap_or(30); // Default: process all frames (30fps input = 30fps output)
+
+    // Only accept triggers signed by this public key (tamper-evident sync results)
+    let verifier = match args.value("verify-key") {
+        Some(path) => Some(TriggerVerifier::from_key_file(Path::new(path))?),
+        None => None,
+    };
+
+    // Optional session recording of every synchronized frame, or of those above --min-confidence (see `sync_core::retention`)
+    let retention = RetentionPolicy::from_args(&args)?;
+    let mut recorder = match args.value("session-dir") {
+        Some(dir) => Some(create_recorder(Path::new(dir), retention)?),
+        None => None,
+    };
+
+    // Sync events with monotonic timestamps for correlation with dmesg/journald (`journald` or a file)
+    let mut event_log = match args.value("event-log") {
+        Some(target) => EventLog::open(target, "subscriber")?,
+        None => EventLog::default(),
+    };
+
+    // Tracepoints for perfetto/LTTng timelines (`ftrace`, `lttng` or a file)
+    let mut tracer = match args.value("trace") {
+        Some(target) => Tracer::open(target)?,
+        None => Tracer::default(),
+    };
+
+    // Calculate frame skip ratio
+    let input_fps = 30u32; // Assuming 30fps input triggers
+    let input_fps = sensor.as_ref().map_or(input_fps, |sensor| sensor.rate(input_fps));
+    let input_fps = channel.as_ref().map_or(input_fps, TriggerChannel::rate);
+
+    // Soft deadlines: frame processing within one trigger interval, matching within 1ms
+    let mut loop_deadline = DeadlineMonitor::new(
+        "frame processing",
+        args.value_as::<f64>("loop-deadline-ms").unwrap_or(1000.0 / input_fps as f64),
+    );
+    let mut match_deadline = DeadlineMonitor::new("matching", args.value_as::<f64>("match-deadline-ms").unwrap_or(1.0));
+    let skip_ratio = if output_fps >= input_fps {
+        1 // No skipping if output FPS >= input FPS
+    } else {
+        (input_fps as f32 / output_fps as f32).round() as u32
+    };
+
+    // Pulses per logical capture, as configured on the publisher (--burst-size)
+    let burst = BurstPattern::new(args.value_as::<u32>("burst-size").unwrap_or(1));
+    // Triggers shed last under overload, as configured on the publisher (--priority-every, see `sync_core::priority`)
+    let priority = PriorityPattern::from_args(&args, burst);
+
+    // Matcher chain, e.g. `dedupe,drift,associate,audit` (see `sync_core::pipeline`)
+    let stages = match args.value("match-stages") {
+        Some(list) => pipeline::parse_stages(list)?,
+        None => vec![Stage::Associate],
+    };
+    let stage_names: Vec<&str> = stages.iter().map(Stage::name).collect();
+
+    // Triggers further than this from a frame never match it (`--match-tolerance-ms`), the penalty on triggers after
+    // the frame (`--future-penalty`) and the pending trigger queue (`--max-pending-triggers`)
+    let match_config = MatchConfig::from_args(&args)?;
+
+    // Narrow the match tolerance to the learned latency, saved across restarts in `--matcher-state <file>`
+    let adaptive_tolerance = args.flag("adaptive-tolerance");
+    let matcher_state = args.value("matcher-state").map(PathBuf::from);
+    let matcher_stream = stream_name.unwrap_or("camera").to_string();
+
+    // CLOCK_REALTIME against CLOCK_MONOTONIC and the trigger timestamps against their publish times (see `sync_core::clock_guard`)
+    let clock_guard_config = ClockGuardConfig::from_args(&args);
+    let mut realtime_guard = ClockGuard::new("realtime", clock_guard_config);
+    let mut trigger_guard = ClockGuard::new("trigger", clock_guard_config);
+    let mut disturbed_matches = 0u64;
+
+    // Warn when the observed trigger rate is off the expected input rate by more than this
+    let mut rate_tolerance_pct = args.value_as::<f64>("rate-tolerance-pct").unwrap_or(10.0);
+
+    // Encoder ticks per unit of travel (e.g. per mm), frames are tagged with the position in units
+    let encoder_ticks_per_unit = args.value_as::<f64>("encoder-ticks-per-unit").unwrap_or(1.0);
+
+    status.text(format_args!("Camera sync subscriber started:"));
+    status.text(format_args!("  V4L2 delay: {}ms", v4l2_delay_ms));
+    status.text(format_args!("  Input triggers: {}fps (33ms intervals)", input_fps));
+    status.text(format_args!("  Output FPS: {}fps (process every {}th trigger)", output_fps, skip_ratio));
+    if let Some(sensor) = &sensor {
+        status.text(format_args!("  Sensor: {} ({})", sensor.name, sensor));
+    }
+    if let Some(channel) = &channel {
+        status.text(format_args!("  Trigger channel: {} ({})", channel.name, channel));
+    }
+    status.text(format_args!("  Signature check: {}", if verifier.is_some() { "enabled" } else { "disabled" }));
+    if let Some(recorder) = &recorder {
+        status.text(format_args!("  Session records: {}", recorder.dir().display()));
+        if let Some(policy) = recorder.retention() {
+            status.text(format_args!("  Retention: {}", policy));
+        }
+    }
+    status.text(format_args!("Usage: {} [v4l2_delay_ms] [output_fps] [--verify-key <public_key_file>] [--session-dir <dir> [--min-confidence <0..1> [--quarantine]]] [--calibration <file>]... [--camera-info <file.yaml>] [--rate-tolerance-pct <pct>] [--match-tolerance-ms <ms>] [--future-penalty <factor>] [--max-pending-triggers <n>] [--runs-dir <dir>] [--burst-size <k>] [--priority-every <n>] [--match-stages <stage,...>] [--adaptive-tolerance] [--matcher-state <file>] [--clock-max-step-ms <ms>] [--clock-settle-ms <ms>] [--consumer-name <name>] [--camera-group <name>] [--encoder-ticks-per-unit <n>] [--sensor <profile>] [--channel <spec>] [--event-log journald|<file>] [--trace ftrace|lttng|<file>] [--loop-deadline-ms <ms>] [--match-deadline-ms <ms>] [--shared-stats [--shared-stats-dir <dir>]] [--status-format text|json] [--display-unit ms|us|ns] [--config <file.toml>] [--timestamp-unit ns|us|ms] [--timestamp-epoch unix|tai|gps] [--leap-seconds <file>] [--timecode-rate <fps|29.97|59.94> [--timecode-epoch unix|tai|gps]] [--duration <time> [--report <file>] [--min-match-pct <pct>] [--max-p95-ms <ms>] [--max-drops <n>] [--max-deadline-misses <n>]]", args.program()));
+    status.text(format_args!("Synchronizing hardware timestamps with V4L2 frames..."));
+
+    let identity = NodeIdentity::from_args(&args, "subscriber", stream_name)?;
+    let node = create_node(&identity)?;
+
+    // Open the same trigger service, the sensor's derived one or the channel's
+    let subscriber = match (&sensor, &channel) {
+        (Some(sensor), _) => TriggerSubscriber::create_for(&node, &trigger::sensor_service(&sensor.name))?,
+        (None, Some(channel)) => TriggerSubscriber::create_for(&node, &trigger::channel_service(&channel.name))?,
+        (None, None) => TriggerSubscriber::create(&node)?,
+    };
+
+    // Receive times of canary and sampled triggers are reported under this name (`--consumer-name`), see `sync_core::feedback`
+    let feedback = FeedbackPublisher::create(&node)?;
+    let consumer_name = args.value("consumer-name").or(stream_name).unwrap_or("subscriber").to_string();
+    // Frame latencies under the same name, compared with the other cameras of `--camera-group` by the publisher (see `sync_core::skew`)
+    let group_latency = match args.value("camera-group") {
+        Some(group) => Some((GroupLatencyPublisher::create(&node)?, group.to_string())),
+        None => None,
+    };
+
+    // Lighting state per trigger, recorded with each matched frame
+    let strobe = StrobeSubscriber::create(&node)?;
+    let mut strobe_log = StrobeLog::default();
+
+    // Pan-tilt poses, interpolated at the trigger timestamp of each matched frame
+    let pose = PoseSubscriber::create(&node)?;
+    let mut pose_track = PoseTrack::default();
+
+    // Encoder position, interpolated at the trigger timestamp of each matched frame
+    let encoder = EncoderSubscriber::create(&node)?;
+    let mut encoder_track = EncoderTrack::new(encoder_ticks_per_unit);
+
+    // Session manifest: effective configuration, software version and calibration hashes
+    let mut manifest = SessionManifest::new("subscriber");
+    manifest
+        .config("v4l2_delay_ms", v4l2_delay_ms)
+        .config("input_fps", input_fps)
+        .config("output_fps", output_fps)
+        .config("skip_ratio", skip_ratio)
+        .config("rate_tolerance_pct", rate_tolerance_pct)
+        .config("burst_size", burst.size)
+        .config("priority_every", priority.map(|pattern| pattern.every.to_string()).unwrap_or_default())
+        .config("match_stages", stage_names.join(","))
+        .config("tolerance_ms", match_config.tolerance_ms)
+        .config("future_penalty", match_config.future_penalty)
+        .config("max_pending_triggers", match_config.max_pending)
+        .config("adaptive_tolerance", adaptive_tolerance)
+        .config("matcher_state", args.value("matcher-state").unwrap_or(""))
+        .config("encoder_ticks_per_unit", encoder_ticks_per_unit)
+        .config("timecode_rate", timecode_clock.map(|clock| clock.rate.to_string()).unwrap_or_default())
+        .config("timecode_epoch", timecode_clock.map(|clock| clock.epoch.to_string()).unwrap_or_default())
+        .config("sensor", sensor.as_ref().map(ToString::to_string).unwrap_or_default())
+        .config("channel", channel.as_ref().map(ToString::to_string).unwrap_or_default())
+        .config("camera_group", args.value("camera-group").unwrap_or(""))
+        .config("verify_key", args.value("verify-key").unwrap_or(""))
+        .config("timestamp_unit", timestamps.unit)
+        .config("timestamp_epoch", timestamps.epoch)
+        .config("session_dir", args.value("session-dir").unwrap_or(""))
+        .config("min_confidence", retention.map(|policy| policy.min_confidence.to_string()).unwrap_or_default())
+        .config("quarantine", retention.is_some_and(|policy| policy.quarantine));
+    for path in args.values("calibration") {
+        manifest.calibration_file(Path::new(path))?;
+    }
+    // Intrinsics (ROS camera_calibration YAML), recorded and published with the session
+    let intrinsics = match args.value("camera-info") {
+        Some(path) => {
+            manifest.calibration_file(Path::new(path))?;
+            Some(CameraIntrinsics::load(Path::new(path))?)
+        }
+        None => None,
+    };
+    if let Some(recorder) = &recorder {
+        manifest.write(recorder.dir())?;
+    }
+    let telemetry = TelemetryPublisher::create(&node)?;
+    telemetry.publish("manifest", &manifest.to_string())?;
+    if let Some(intrinsics) = &intrinsics {
+        if let Some(recorder) = &recorder {
+            intrinsics.write(recorder.dir())?;
+        }
+        telemetry.publish("camera_info", &intrinsics.to_string())?;
+    }
+    status.line(
+        "start",
+        format_args!("Session manifest published (config_hash={})", manifest.config_hash()),
+        &[
+            ("config_hash", manifest.config_hash().into()),
+            ("v4l2_delay_ms", v4l2_delay_ms.into()),
+            ("input_fps", input_fps.into()),
+            ("output_fps", output_fps.into()),
+            ("skip_ratio", skip_ratio.into()),
+            ("sensor", sensor.as_ref().map(|sensor| sensor.name.clone()).into()),
+            ("channel", channel.as_ref().map(|channel| channel.name.clone()).into()),
+            ("camera_group", args.value("camera-group").into()),
+            ("signature_check", verifier.is_some().into()),
+            ("timestamp_unit", timestamps.unit.to_string().into()),
+            ("timestamp_epoch", timestamps.epoch.to_string().into()),
+        ],
+    );
+    event_log.event("start", false, &[("config_hash", manifest.config_hash())])?;
+
+    // Summary statistics of this run, kept per config hash for `compare`
+    let runs_dir = args.value("runs-dir").map(PathBuf::from);
+    let mut run_summary = RunSummary::new(&manifest);
+    let mut processed_frames = 0u64;
+    let mut received_triggers = 0u64;
+    let mut dropped_triggers = 0u64;
+
+    // Counters and latency in a memory-mapped file for local tools (`--shared-stats`, see `sync_core::shared_stats`)
+    let mut shared_stats = StatsPublisher::from_args(&args, &identity)?;
+    let started_ns = clock::realtime_now_ns();
+    if let Some(shared_stats) = &shared_stats {
+        let path = shared_stats.path().display().to_string();
+        status.line("shared_stats", format_args!("Stats snapshot published at {}", path), &[("path", path.as_str().into())]);
+    }
+
+    // Triggers missing from the received ids and frames lost between matches (a sensor's ids step by its divider)
+    let mut trigger_gaps = TriggerGaps::new(sensor.as_ref().map_or(1, |sensor| sensor.divider as u64), skip_ratio as u64);
+
+    status.text(format_args!("Camera sync subscriber started. Synchronizing hardware timestamps with V4L2 frames..."));
+
+    // Buffer for pending triggers waiting for V4L2 frames (with their signature header)
+    let mut pending_triggers: MatchPipeline<TriggerSignature> = MatchPipeline::with_stages(&stages, match_config.tolerance_ms, burst);
+    pending_triggers.configure(&match_config);
+    pending_triggers.set_adaptive_tolerance(adaptive_tolerance);
+    pending_triggers.set_priority(priority);
+    if let Some(path) = &matcher_state {
+        match matcher_state::restore(path, &matcher_stream, &mut pending_triggers, clock::realtime_now_ns())? {
+            Some(snapshot) => status.line(
+                "matcher_state",
+                format_args!(
+                    "Restored matcher state of {} from {}: latency {:.1}ms, tolerance {:.1}ms",
+                    matcher_stream,
+                    path.display(),
+                    snapshot.latency_ms().unwrap_or_default(),
+                    pending_triggers.tolerance_ms()
+                ),
+                &[
+                    ("stream", matcher_stream.as_str().into()),
+                    ("restored", true.into()),
+                    ("latency_ms", snapshot.latency_ms().into()),
+                    ("tolerance_ms", pending_triggers.tolerance_ms().into()),
+                    ("offsets", snapshot.offsets_ns.len().into()),
+                ],
+            ),
+            None => status.line(
+                "matcher_state",
+                format_args!("No matcher state of {} in {}, learning from the first matches", matcher_stream, path.display()),
+                &[("stream", matcher_stream.as_str().into()), ("restored", false.into()), ("tolerance_ms", pending_triggers.tolerance_ms().into())],
+            ),
+        }
+    }
+    let mut matcher_saved_ns = clock::realtime_now_ns();
+
+    // Frame skipping for output FPS control
+    let mut trigger_count = 0u32;
+
+    // Trigger rate/phase observed from the received triggers
+    let mut trigger_rate = TriggerRateEstimator::default();
+
+    // Offset of the local clock from the publisher's, recorded for multi-host merges
+    let mut clock_mapper = ClockMapper::default();
+    let mut rate_mismatch = false;
+
+    // Diagnosis of unmatched frames (each simulated frame stands for skip_ratio camera frames)
+    let mut diagnostics = MatchDiagnostics::new(skip_ratio);
+
+    // Per-stage latency statistics (no driver timestamps in the simulation)
+    let mut latency = LatencyBreakdown::default();
+
+    // Clock domain of the received triggers, reported when it changes
+    let mut published_domain = None;
+
+    // What the publisher asks for while its trigger clock is downgraded, and the frames left unmatched under `pause`
+    let mut clock_policy: Option<DowngradePolicy> = None;
+    let mut paused_frames = 0u64;
+
+    // Drain historical triggers at the beginning (if any)
+    status.text(format_args!("Draining historical triggers..."));
+    let mut history_count = 0usize;
+    while let Some((trigger, header)) = subscriber.receive()? {
+        if !is_trusted(&verifier, &trigger, &header, &status) || canary::is_canary(trigger.frame_id) {
+            continue;
+        }
+        if published_domain != Some(header.clock_domain) {
+            published_domain = Some(header.clock_domain);
+            report_clock_domain(header.clock_domain, &status);
+        }
+        let (trigger, header) = clock::trigger_to_realtime(trigger, header)?;
+        let CameraTrigger { frame_id: trigger_id, hw_timestamp_ns: hw_ts, .. } = trigger;
+        if let Some(gap) = trigger_gaps.on_trigger(trigger_id) {
+            report_trigger_gap(&gap, &trigger_gaps, &mut event_log, &status)?;
+        }
+        status.line(
+            "historical_trigger",
+            format_args!("Historical trigger: id={}, hw_ts={}", trigger_id, timestamps.present(Nanos(hw_ts))),
+            &[("trigger_id", trigger_id.into()), ("hw_ts", timestamps.present(Nanos(hw_ts)).into())],
+        );
+        trigger_rate.on_trigger(trigger_id, hw_ts);
+        pending_triggers.on_trigger(trigger, header);
+        history_count += 1;
+        received_triggers += 1;
+    }
+    status.line(
+        "history_drained",
+        format_args!("Drained {} historical triggers. Starting real-time sync...", history_count),
+        &[("triggers", history_count.into())],
+    );
+
+    // Safe-to-change options follow the `--config` file and SIGHUP (see `sync_core::reload`)
+    let mut config_watcher = ConfigWatcher::from_args(&args, SWITCHES, LIVE_OPTIONS);
+
+    loop {
+        if batch.as_ref().is_some_and(BatchRun::is_over) {
+            break;
+        }
+
+        if let Some(reload) = config_watcher.as_mut().and_then(ConfigWatcher::poll) {
+            let path = config_watcher.as_ref().map(|watcher| watcher.path().display().to_string()).unwrap_or_default();
+            let reload = reload.and_then(|reload| {
+                let args = &reload.args;
+                // Checked and opened first: an invalid value or a failed open leaves everything as it was
+                let match_config = MatchConfig::from_args(args)?;
+                if reload.is_applied("event-log") {
+                    event_log = match args.value("event-log") {
+                        Some(target) => EventLog::open(target, "subscriber").map_err(|e| format!("could not open event log {}: {}", target, e))?,
+                        None => EventLog::default(),
+                    };
+                }
+                if reload.is_applied("session-dir") {
+                    recorder = match args.value("session-dir") {
+                        Some(dir) => {
+                            let new = create_recorder(Path::new(dir), retention).map_err(|e| format!("could not create session {}: {}", dir, e))?;
+                            manifest.write(new.dir()).map_err(|e| e.to_string())?;
+                            if let Some(intrinsics) = &intrinsics {
+                                intrinsics.write(new.dir()).map_err(|e| e.to_string())?;
+                            }
+                            Some(new)
+                        }
+                        None => None,
+                    };
+                }
+                pending_triggers.set_tolerance_ms(match_config.tolerance_ms);
+                pending_triggers.set_future_penalty(match_config.future_penalty);
+                pending_triggers.set_adaptive_tolerance(args.flag("adaptive-tolerance"));
+                rate_tolerance_pct = args.value_as::<f64>("rate-tolerance-pct").unwrap_or(10.0);
+                loop_deadline.set_budget_ms(args.value_as::<f64>("loop-deadline-ms").unwrap_or(1000.0 / input_fps as f64));
+                match_deadline.set_budget_ms(args.value_as::<f64>("match-deadline-ms").unwrap_or(1.0));
+                Ok(reload)
+            });
+            report_config_reload(&path, &reload, &telemetry, &mut event_log, &status)?;
+        }
+
+        if let Some(event) = realtime_guard.observe(clock::realtime_now_ns(), clock::monotonic_now_ns()) {
+            report_clock_event(&event, &telemetry, &mut event_log, &status)?;
+        }
+
+        while let Some(command) = strobe.receive()? {
+            strobe_log.push(command);
+        }
+        while let Some(sample) = pose.receive()? {
+            pose_track.push(sample);
+        }
+        while let Some(sample) = encoder.receive()? {
+            encoder_track.push(sample);
+        }
+
+        // Receive new triggers
+        while let Some((trigger, header)) = subscriber.receive()? {
+            // Taken before the signature check, which is not part of the delivery
+            let received_ns = clock::realtime_now_ns();
+            if !is_trusted(&verifier, &trigger, &header, &status) {
+                continue;
+            }
+            // Matched in CLOCK_REALTIME like the frames, whatever the publisher stamped them in
+            if published_domain != Some(header.clock_domain) {
+                published_domain = Some(header.clock_domain);
+                report_clock_domain(header.clock_domain, &status);
+            }
+            if DowngradePolicy::from_header(header.clock_policy) != clock_policy {
+                clock_policy = DowngradePolicy::from_header(header.clock_policy);
+                report_clock_policy(clock_policy, paused_frames, &mut event_log, &status)?;
+                paused_frames = 0;
+            }
+            let (trigger, header) = clock::trigger_to_realtime(trigger, header)?;
+            let CameraTrigger { frame_id: trigger_id, hw_timestamp_ns: hw_ts, publish_timestamp_ns: pub_ts, .. } = trigger;
+            if feedback::is_sampled(trigger_id) {
+                feedback.publish(ReceiveFeedback::new(&consumer_name, &trigger, received_ns))?;
+            }
+            // Canaries prove the chain alive, they are acknowledged and never matched
+            if canary::is_canary(trigger_id) {
+                continue;
+            }
+            if let Some(gap) = trigger_gaps.on_trigger(trigger_id) {
+                report_trigger_gap(&gap, &trigger_gaps, &mut event_log, &status)?;
+            }
+            latency.record_wakeup(pub_ts, received_ns);
+            if let Some(event) = trigger_guard.observe(hw_ts, pub_ts) {
+                report_clock_event(&event, &telemetry, &mut event_log, &status)?;
+            }
+            let trace = TraceId::of(&trigger, &header);
+            tracer.trigger_received(trace, hw_ts);
+            status.line(
+                "trigger",
+                format_args!(
+                    "Received trigger: id={}, hw_ts={}, ipc_delay={}ns, trace={}",
+                    trigger_id,
+                    timestamps.present(Nanos(hw_ts)),
+                    pub_ts.saturating_sub(hw_ts),
+                    trace
+                ),
+                &[
+                    ("trigger_id", trigger_id.into()),
+                    ("hw_ts", timestamps.present(Nanos(hw_ts)).into()),
+                    ("ipc_delay_ns", pub_ts.saturating_sub(hw_ts).into()),
+                    ("trace", trace.to_string().into()),
+                ],
+            );
+
+            trigger_rate.on_trigger(trigger_id, hw_ts);
+            let queued = pending_triggers.on_trigger(trigger, header);
+            received_triggers += 1;
+            if let (Some(mapping), Some(recorder)) = (clock_mapper.on_trigger(pub_ts, clock::realtime_now_ns()), &mut recorder) {
+                recorder.record_clock(&mapping)?;
+            }
+
+            // Pending triggers are limited to avoid memory issues (keep last 100)
+            match queued {
+                Queued::Added => {}
+                Queued::Duplicate => status.line(
+                    "trigger_duplicate",
+                    format_args!("WARNING: Dropped duplicate trigger id={}", trigger_id),
+                    &[("trigger_id", trigger_id.into())],
+                ),
+                Queued::Evicted(CameraTrigger { frame_id: old_trigger_id, .. }) => {
+                    dropped_triggers += 1;
+                    let high = priority::is_priority(priority, old_trigger_id);
+                    status.line(
+                        "trigger_dropped",
+                        format_args!("WARNING: Dropped old {}trigger id={} (V4L2 too slow)", if high { "priority " } else { "" }, old_trigger_id),
+                        &[("trigger_id", old_trigger_id.into()), ("priority", high.into())],
+                    );
+                    event_log.event("trigger_dropped", true, &[("trigger_id", old_trigger_id.to_string()), ("priority", high.to_string())])?;
+                }
+            }
+        }
+
+        // Report rate mismatches once when they appear and once when they clear
+        let warning = trigger_rate.check(input_fps as f64, rate_tolerance_pct);
+        if warning.is_some() != rate_mismatch {
+            match &warning {
+                Some(warning) => {
+                    status.line("rate_mismatch", format_args!("WARNING: {}", warning), &[("detail", warning.as_str().into())]);
+                    event_log.event("rate_mismatch", true, &[("detail", warning.clone())])?;
+                }
+                None => {
+                    status.line(
+                        "rate_ok",
+                        format_args!("Trigger rate back within {:.1}% of {}fps", rate_tolerance_pct, input_fps),
+                        &[("tolerance_pct", rate_tolerance_pct.into()), ("expected_fps", input_fps.into())],
+                    );
+                    event_log.event("rate_ok", false, &[])?;
+                }
+            }
+            rate_mismatch = warning.is_some();
+        }
+
+        // Simulate V4L2 frame capture (slower than triggers)
+        // In real code, this would be your V4L2 capture loop
+        if pending_triggers.pending() > 0 {
+            // Frame skipping for output FPS control
+            trigger_count += 1;
+            let should_process = trigger_count.is_multiple_of(skip_ratio);
+
+            if should_process && clock_policy == Some(DowngradePolicy::Pause) {
+                // Not matched until the publisher's trigger clock recovers; the pending triggers age out meanwhile
+                paused_frames += 1;
+                trigger_gaps.on_frame(None);
+            } else if should_process {
+                // Simulate V4L2 processing delay (configurable via command line)
+                std::thread::sleep(Duration::from_millis(v4l2_delay_ms));
+
+                // Simulate receiving a frame from V4L2
+                let v4l2_timestamp_ns = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)?
+                    .as_nanos() as u64;
+                tracer.frame_dequeued(v4l2_timestamp_ns);
+                loop_deadline.start();
+                diagnostics.on_frame(v4l2_timestamp_ns);
+                processed_frames += 1;
+
+                // Find the best matching trigger based on timestamp proximity
+                // IMPROVED: Handle case where V4L2 delay > trigger interval
+                // Prefer past triggers (hw_ts < v4l2_ts) but allow future triggers as fallback
+                // Within a burst the frame after a match takes the next pulse, if it is pending and within tolerance
+                // The configured stages (--match-stages) run around this association
+                tracer.match_started();
+                match_deadline.start();
+                let clock_disturbed = realtime_guard.is_disturbed() || trigger_guard.is_disturbed();
+                pending_triggers.set_learning_frozen(clock_disturbed || clock_policy.is_some());
+                pending_triggers.set_tolerance_factor(if clock_policy == Some(DowngradePolicy::Widen) { clock_quality::WIDEN_FACTOR } else { 1.0 });
+                let tolerance_ms = pending_triggers.tolerance_ms();
+                let frame = pending_triggers.on_frame(v4l2_timestamp_ns);
+                if frame.relearned {
+                    status.line(
+                        "matcher_relearn",
+                        format_args!("WARNING: no match for {} frames in a row, discarding the learned latency of {}", pipeline::RELEARN_AFTER, matcher_stream),
+                        &[("stream", matcher_stream.as_str().into()), ("unmatched", pipeline::RELEARN_AFTER.into())],
+                    );
+                    event_log.event("matcher_relearn", true, &[("stream", matcher_stream.clone())])?;
+                }
+
+                if let Some((trigger, header, best_score)) = frame.matched {
+                    let CameraTrigger { frame_id: trigger_id, hw_timestamp_ns: hw_ts, publish_timestamp_ns: pub_ts, .. } = trigger;
+                    let trace = TraceId::of(&trigger, &header);
+                    tracer.match_decided(Some(trace), best_score);
+                    if let Some(missed) = match_deadline.finish() {
+                        report_missed_deadline(&missed, &telemetry, &mut event_log, &status)?;
+                    }
+                    for finding in &frame.findings {
+                        status.line(
+                            "match_audit",
+                            format_args!("AUDIT: frame at {}ns: {}", v4l2_timestamp_ns, finding),
+                            &[("frame_ns", v4l2_timestamp_ns.into()), ("finding", finding.as_str().into())],
+                        );
+                        event_log.event("match_audit", true, &[("finding", finding.clone())])?;
+                    }
+
+                    // OPTIMIZATION: All triggers older than the matched one left the queue with it
+                    // These will never be useful for future frames since they're too old
+                    let removed_old_count = frame.cleaned.len();
+                    for CameraTrigger { frame_id: old_trigger_id, .. } in &frame.cleaned {
+                        status.line(
+                            "trigger_cleanup",
+                            format_args!("CLEANUP: Removed old trigger id={} (too old for future frames)", old_trigger_id),
+                            &[("trigger_id", (*old_trigger_id).into())],
+                        );
+                    }
+
+                    // Calculate synchronization metrics
+                    let total_latency_ms = (v4l2_timestamp_ns as i64 - hw_ts as i64) as f64 / 1_000_000.0;
+                    let v4l2_delay_ms = (v4l2_timestamp_ns as i64 - pub_ts as i64) as f64 / 1_000_000.0;
+                    let trigger_type = if hw_ts < v4l2_timestamp_ns { "PAST" } else { "FUTURE" };
+                    let mut frame_info = String::new();
+                    if burst.is_burst() {
+                        frame_info.push_str(&format!(", burst={}", burst.position(trigger_id)));
+                    }
+                    // Recorded with every frame, so a dataset shows how far its timestamps can be trusted
+                    let timestamp_source = TimestampSource::label(header.timestamp_source);
+                    frame_info.push_str(&format!(", src={}, trace={}", timestamp_source, trace));
+                    let mut metadata = vec![("timestamp_source".to_string(), timestamp_source), ("trace".to_string(), trace.to_string())];
+                    if priority::is_priority(priority, trigger_id) {
+                        frame_info.push_str(", priority=high");
+                        metadata.push(("priority".to_string(), "high".to_string()));
+                    }
+                    if let Some(command) = strobe_log.get(trigger_id) {
+                        frame_info.push_str(&format!(", strobe={}", command));
+                        metadata.push(("strobe".to_string(), command.to_string()));
+                    }
+                    if let Some(pose) = pose_track.at(hw_ts) {
+                        frame_info.push_str(&format!(", {}", pose));
+                        metadata.extend(pose.metadata());
+                    }
+                    if let Some(position) = encoder_track.position(hw_ts) {
+                        frame_info.push_str(&format!(", encoder={:.3}", position));
+                        metadata.push(("encoder".to_string(), format!("{:.3}", position)));
+                    }
+                    if let Some(clock) = &timecode_clock {
+                        let timecode = clock.timecode(Nanos(hw_ts));
+                        frame_info.push_str(&format!(", tc={}", timecode));
+                        metadata.push(("timecode".to_string(), timecode.metadata_value()));
+                    }
+                    if clock_disturbed {
+                        frame_info.push_str(", clock=disturbed");
+                        metadata.push(("clock".to_string(), "disturbed".to_string()));
+                    }
+                    if let Some(policy) = clock_policy {
+                        frame_info.push_str(&format!(", clock_policy={}", policy));
+                        metadata.push(("clock_policy".to_string(), policy.to_string()));
+                    }
+                    let confidence = MatchQuality {
+                        score_ms: best_score,
+                        tolerance_ms,
+                        clock_disturbed,
+                        clock_downgraded: clock_policy.is_some(),
+                        findings: frame.findings.len(),
+                    }
+                    .confidence();
+                    frame_info.push_str(&format!(", confidence={:.2}", confidence));
+                    metadata.push(("confidence".to_string(), format!("{:.2}", confidence)));
+                    diagnostics.on_match(Some(total_latency_ms));
+                    if let Some(lost) = trigger_gaps.on_frame(Some(trigger_id)) {
+                        status.line(
+                            "frames_lost",
+                            format_args!("WARNING: trigger_id={} matched {} frames after the previous match, {} frames lost in between", trigger_id, lost + 1, lost),
+                            &[("trigger_id", trigger_id.into()), ("lost", lost.into()), ("lost_total", trigger_gaps.lost_frames.into())],
+                        );
+                        event_log.event("frames_lost", true, &[("trigger_id", trigger_id.to_string()), ("lost", lost.to_string())])?;
+                    }
+
+                    let signature = if verifier.is_some() { SignatureStatus::Valid } else { SignatureStatus::Unsigned };
+
+                    let (hw_exposure_ts, v4l2_ts) = (timestamps.present(Nanos(hw_ts)), timestamps.present(Nanos(v4l2_timestamp_ns)));
+                    let mut fields = vec![
+                        ("trigger_type", trigger_type.into()),
+                        ("trigger_id", trigger_id.into()),
+                        ("hw_exposure_ts", hw_exposure_ts.into()),
+                        ("v4l2_ts", v4l2_ts.into()),
+                        ("total_latency_ms", total_latency_ms.into()),
+                        ("v4l2_delay_ms", v4l2_delay_ms.into()),
+                        ("score_ms", best_score.into()),
+                        ("cleaned", removed_old_count.into()),
+                        ("signature", signature.to_string().into()),
+                    ];
+                    if burst.is_burst() {
+                        fields.push(("burst", burst.position(trigger_id).to_string().into()));
+                    }
+                    fields.extend(metadata.iter().map(|(key, value)| (key.as_str(), value.as_str().into())));
+                    status.line(
+                        "synced",
+                        format_args!(
+                            "SYNCED [{}]: trigger_id={}, hw_exposure_ts={}, v4l2_ts={}, total_latency={:.1}, v4l2_delay={:.1}, score={:.1}, cleaned={}, sig={}{}",
+                            trigger_type,
+                            trigger_id,
+                            hw_exposure_ts,
+                            v4l2_ts,
+                            DisplayMs(total_latency_ms, status.display_unit()),
+                            DisplayMs(v4l2_delay_ms, status.display_unit()),
+                            DisplayMs(best_score, status.display_unit()),
+                            removed_old_count,
+                            signature,
+                            frame_info
+                        ),
+                        &fields,
+                    );
+
+                    // Latencies across a clock step are garbage, the frame is only recorded (marked as disturbed)
+                    if clock_disturbed {
+                        disturbed_matches += 1;
+                    } else {
+                        latency.record(&FrameTiming {
+                            trigger_ns: hw_ts,
+                            driver_ns: None,
+                            dequeue_ns: v4l2_timestamp_ns,
+                            match_ns: clock::realtime_now_ns(),
+                        });
+                        if let Some((group_latency, group)) = &group_latency {
+                            if feedback::is_sampled(trigger_id) {
+                                group_latency.publish(GroupLatency::new(group, &consumer_name, trigger_id, v4l2_timestamp_ns as i64 - hw_ts as i64))?;
+                            }
+                        }
+                        if latency.count().is_multiple_of(100) {
+                            status.latency(&latency);
+                            status.deadlines(&[&loop_deadline, &match_deadline]);
+                            status.stages(pending_triggers.stats());
+                            status.latency_filter(&pending_triggers);
+                            if trigger_gaps.has_loss() {
+                                status.line(
+                                    "trigger_gaps",
+                                    format_args!("{}", trigger_gaps),
+                                    &[
+                                        ("received", trigger_gaps.received.into()),
+                                        ("gaps", trigger_gaps.gaps.into()),
+                                        ("missing_triggers", trigger_gaps.missing_triggers.into()),
+                                        ("restarts", trigger_gaps.restarts.into()),
+                                        ("lost_frames", trigger_gaps.lost_frames.into()),
+                                    ],
+                                );
+                            }
+                            if let Some(estimate) = trigger_rate.estimate() {
+                                status.line(
+                                    "trigger_rate",
+                                    format_args!("TRIGGER RATE: {}", estimate.summary(status.display_unit())),
+                                    &[
+                                        ("rate_hz", estimate.rate_hz.into()),
+                                        ("period_ns", estimate.period_ns.into()),
+                                        ("phase_ns", estimate.phase_ns.into()),
+                                        ("jitter_ns", estimate.jitter_ns.into()),
+                                        ("triggers", estimate.triggers.into()),
+                                    ],
+                                );
+                            }
+                            if let Some(runs_dir) = &runs_dir {
+                                run_summary.update(processed_frames, latency.count(), &latency);
+                                run_summary.write(runs_dir)?;
+                            }
+                        }
+                    }
+
+                    if let Some(recorder) = &mut recorder {
+                        let action = recorder.retention_action(confidence);
+                        if let Some(target) = recorder.retain(action) {
+                            target.record(&MatchRecord {
+                                trigger,
+                                v4l2_timestamp_ns,
+                                total_latency_ms,
+                                score_ms: best_score,
+                                signature_status: signature,
+                                signature: header,
+                                metadata,
+                            })?;
+                        }
+                        if action != RetentionAction::Keep {
+                            report_frame_retention(trigger_id, confidence, action, recorder, &status);
+                        }
+                    }
+
+                    // Process the synchronized frame here
+                    // Your frame processing code would go here
+
+                } else {
+                    // No suitable trigger found within tolerance
+                    tracer.match_decided(None, 0.0);
+                    if let Some(missed) = match_deadline.finish() {
+                        report_missed_deadline(&missed, &telemetry, &mut event_log, &status)?;
+                    }
+                    if frame.dropped_by == Some(Stage::Dedupe) {
+                        status.line(
+                            "duplicate_frame",
+                            format_args!("WARNING: V4L2 frame at {}ns repeats the previous frame's timestamp", v4l2_timestamp_ns),
+                            &[("frame_ns", v4l2_timestamp_ns.into())],
+                        );
+                    } else {
+                        status.line(
+                            "unmatched_frame",
+                            format_args!("WARNING: V4L2 frame at {}ns - no matching trigger within {:.1}ms tolerance", v4l2_timestamp_ns, tolerance_ms),
+                            &[("frame_ns", v4l2_timestamp_ns.into()), ("tolerance_ms", tolerance_ms.into())],
+                        );
+                        trigger_gaps.on_frame(None);
+                    }
+                    diagnostics.on_match(None);
+                    event_log.event("unmatched_frame", true, &[("frame_ns", v4l2_timestamp_ns.to_string())])?;
+                }
+
+                // Explain growing numbers of unmatched frames
+                if let Some(diagnosis) = diagnostics.check(trigger_rate.estimate().as_ref()) {
+                    let causes: Vec<String> = diagnosis.causes.iter().map(ToString::to_string).collect();
+                    status.line(
+                        "diagnosis",
+                        format_args!("{}", diagnosis),
+                        &[("unmatched_pct", diagnosis.unmatched_pct.into()), ("causes", causes.join("; ").into())],
+                    );
+                    event_log.event("diagnosis", true, &[("report", diagnosis.to_string())])?;
+                }
+                if let Some(missed) = loop_deadline.finish() {
+                    report_missed_deadline(&missed, &telemetry, &mut event_log, &status)?;
+                }
+            } else {
+                // Frame skipped for output FPS control
+                status.line(
+                    "skipped",
+                    format_args!("SKIPPED: Frame {} skipped (output FPS: {}fps, processing every {}th trigger)", trigger_count, output_fps, skip_ratio),
+                    &[("frame", trigger_count.into()), ("output_fps", output_fps.into()), ("skip_ratio", skip_ratio.into())],
+                );
+            }
+        }
+
+        // Keep the learned state for a warm restart
+        if let Some(path) = &matcher_state {
+            let now_ns = clock::realtime_now_ns();
+            if now_ns.saturating_sub(matcher_saved_ns) >= SAVE_INTERVAL_NS {
+                save_matcher_state(path, &matcher_stream, &pending_triggers, now_ns, &status);
+                matcher_saved_ns = now_ns;
+            }
+        }
+
+        if let Some(shared_stats) = &mut shared_stats {
+            shared_stats.update(clock::monotonic_now_ns(), || {
+                let results = BatchResults {
+                    triggers: received_triggers,
+                    frames: processed_frames,
+                    matched: latency.count() + disturbed_matches,
+                    drops: dropped_triggers + trigger_gaps.missing_triggers,
+                    deadline_misses: loop_deadline.counts().1 + match_deadline.counts().1,
+                    ..Default::default()
+                };
+                ProcessStats {
+                    pending_triggers: pending_triggers.pending() as u64,
+                    ..ProcessStats::new(started_ns, clock::realtime_now_ns(), &results, &latency)
+                }
+            });
+        }
+
+        // Sleep until the publisher notifies the next trigger; the timeout keeps the side channels, clock guard and reloads going
+        subscriber.wait(Duration::from_millis(10))?;
+    }
+
+    // Only reached when a `--duration` batch run is over
+    let Some(batch) = batch else {
+        return Ok(());
+    };
+    drop((recorder, shared_stats));
+    if let Some(path) = &matcher_state {
+        save_matcher_state(path, &matcher_stream, &pending_triggers, clock::realtime_now_ns(), &status);
+    }
+    if let Some(runs_dir) = &runs_dir {
+        run_summary.update(processed_frames, latency.count(), &latency);
+        run_summary.write(runs_dir)?;
+    }
+    let results = BatchResults {
+        triggers: received_triggers,
+        frames: processed_frames,
+        matched: latency.count() + disturbed_matches,
+        drops: dropped_triggers + trigger_gaps.missing_triggers,
+        deadline_misses: loop_deadline.counts().1 + match_deadline.counts().1,
+        ..Default::default()
+    };
+    std::process::exit(batch.finish(&results, &latency)?);
+}
+
+// Session with the retention policy applied, also when --session-dir is reloaded
+fn create_recorder(dir: &Path, retention: Option<RetentionPolicy>) -> Result<SessionRecorder, Box<dyn std::error::Error>> {
+    let recorder = SessionRecorder::create(dir)?;
+    match retention {
+        Some(policy) => recorder.with_retention(policy),
+        None => Ok(recorder),
+    }
+}
+
+// A frame the retention policy kept out of the session, with the running counts
+fn report_frame_retention(trigger_id: u64, confidence: f64, action: RetentionAction, recorder: &SessionRecorder, status: &Status) {
+    let retained = recorder.retained();
+    let min_confidence = recorder.retention().map_or(0.0, |policy| policy.min_confidence);
+    status.line(
+        "frame_retention",
+        format_args!("Frame of trigger id={} {} (confidence {:.2} below {}), {}", trigger_id, action, confidence, min_confidence, retained),
+        &[
+            ("trigger_id", trigger_id.into()),
+            ("action", action.to_string().into()),
+            ("confidence", confidence.into()),
+            ("min_confidence", min_confidence.into()),
+            ("kept", retained.kept.into()),
+            ("quarantined", retained.quarantined.into()),
+            ("discarded", retained.discarded.into()),
+        ],
+    );
+}
+
+// Triggers the publisher sent but this subscriber never received, counted towards the batch drops
+fn report_trigger_gap(gap: &TriggerGap, gaps: &TriggerGaps, event_log: &mut EventLog, status: &Status) -> Result<(), Box<dyn std::error::Error>> {
+    status.line(
+        "trigger_gap",
+        format_args!("WARNING: {} ({} missing so far)", gap, gaps.missing_triggers),
+        &[
+            ("after", gap.after.into()),
+            ("before", gap.before.into()),
+            ("missing", gap.missing.into()),
+            ("missing_total", gaps.missing_triggers.into()),
+        ],
+    );
+    event_log.event("trigger_gap", true, &[("after", gap.after.to_string()), ("before", gap.before.to_string()), ("missing", gap.missing.to_string())])?;
+    Ok(())
+}
+
+// With a verifier configured, only triggers carrying a valid signature are used for matching.
+fn is_trusted(verifier: &Option<TriggerVerifier>, trigger: &CameraTrigger, header: &TriggerSignature, status: &Status) -> bool {
+    let Some(verifier) = verifier else {
+        return true;
+    };
+    let signature = verifier.verify(trigger, header);
+    if signature != SignatureStatus::Valid {
+        status.line(
+            "trigger_rejected",
+            format_args!("WARNING: Rejected trigger id={} (signature {})", trigger.frame_id, signature),
+            &[("trigger_id", trigger.frame_id.into()), ("signature", signature.to_string().into())],
+        );
+    }
+    signature == SignatureStatus::Valid
+}
+
+// The clock the publisher stamps triggers in; anything but CLOCK_REALTIME is converted on receipt
+fn report_clock_domain(domain: u8, status: &Status) {
+    let label = ClockDomain::label(domain);
+    let converted = domain != ClockDomain::Realtime as u8;
+    status.line(
+        "clock_domain",
+        format_args!("Triggers published in clock domain {}{}", label, if converted { ", converted to CLOCK_REALTIME for matching" } else { "" }),
+        &[("domain", label.as_str().into()), ("converted", converted.into())],
+    );
+}
+
+// The publisher's trigger clock was downgraded or recovered; `paused` frames were left unmatched since the last change
+fn report_clock_policy(policy: Option<DowngradePolicy>, paused: u64, event_log: &mut EventLog, status: &Status) -> Result<(), Box<dyn std::error::Error>> {
+    let text = match policy {
+        Some(policy) => format!("WARNING: publisher's trigger clock downgraded, policy {}: {}", policy, policy.describe()),
+        None => format!("Publisher's trigger clock synchronized again, matching as usual ({} frames paused)", paused),
+    };
+    status.line("clock_policy", format_args!("{}", text), &[("policy", policy.map(|policy| policy.to_string()).into()), ("paused_frames", paused.into())]);
+    event_log.event(
+        "clock_policy",
+        policy.is_some(),
+        &[("policy", policy.map_or_else(|| "none".to_string(), |policy| policy.to_string())), ("paused_frames", paused.to_string())],
+    )?;
+    Ok(())
+}
+
+// A failed save only costs the warm restart, the run goes on
+fn save_matcher_state(path: &Path, stream: &str, pipeline: &MatchPipeline<TriggerSignature>, now_ns: u64, status: &Status) {
+    if let Err(e) = MatcherState::save(path, stream, pipeline.snapshot(), now_ns) {
+        status.line(
+            "matcher_state_failed",
+            format_args!("WARNING: could not save the matcher state to {}: {}", path.display(), e),
+            &[("path", path.display().to_string().into()), ("error", e.to_string().into())],
+        );
+    }
+}
+
+// A clock step or the end of a disturbance, also on telemetry and in the event log
+fn report_clock_event(event: &ClockEvent, telemetry: &TelemetryPublisher, event_log: &mut EventLog, status: &Status) -> Result<(), Box<dyn std::error::Error>> {
+    status.line(
+        "clock_event",
+        format_args!("{}{}", if event.is_step() { "WARNING: " } else { "" }, event),
+        &[
+            ("clock", event.clock.into()),
+            ("event", event.kind.name().into()),
+            ("step_ns", event.step_ns.into()),
+            ("clock_ns", event.clock_ns.into()),
+            ("disturbed_ns", event.disturbed_ns.into()),
+            ("steps", event.steps.into()),
+        ],
+    );
+    telemetry.publish("clock_event", &event.to_string())?;
+    event_log.event(
+        "clock_event",
+        event.is_step(),
+        &[
+            ("clock", event.clock.to_string()),
+            ("event", event.kind.name().to_string()),
+            ("step_ns", event.step_ns.to_string()),
+            ("disturbed_ns", event.disturbed_ns.to_string()),
+        ],
+    )?;
+    Ok(())
+}
+
+// Reports what a config reload applied and what waits for a restart, or why it failed
+fn report_config_reload(path: &str, reload: &Result<ConfigReload, String>, telemetry: &TelemetryPublisher, event_log: &mut EventLog, status: &Status) -> Result<(), Box<dyn std::error::Error>> {
+    match reload {
+        Ok(reload) => {
+            status.line(
+                "config_reload",
+                format_args!("Config {} reloaded: {}", path, reload),
+                &[("path", path.into()), ("applied", reload.applied.join(",").into()), ("restart_required", reload.restart_required.join(",").into())],
+            );
+            telemetry.publish("config_reload", &format!("{}: {}", path, reload))?;
+            event_log.event(
+                "config_reload",
+                !reload.restart_required.is_empty(),
+                &[("path", path.to_string()), ("applied", reload.applied.join(",")), ("restart_required", reload.restart_required.join(","))],
+            )?;
+        }
+        Err(e) => {
+            status.line("config_reload_failed", format_args!("WARNING: config {} not reloaded: {}", path, e), &[("path", path.into()), ("error", e.as_str().into())]);
+            event_log.event("config_reload_failed", true, &[("path", path.to_string()), ("error", e.clone())])?;
+        }
+    }
+    Ok(())
+}
+
+// Makes sporadic stalls visible: log, telemetry and event export
+fn report_missed_deadline(missed: &MissedDeadline, telemetry: &TelemetryPublisher, event_log: &mut EventLog, status: &Status) -> Result<(), Box<dyn std::error::Error>> {
+    status.line(
+        "deadline_missed",
+        format_args!("{}", missed.summary(status.display_unit())),
+        &[
+            ("section", missed.section.into()),
+            ("thread", missed.thread.as_str().into()),
+            ("duration_ns", missed.duration_ns.into()),
+            ("budget_ns", missed.budget_ns.into()),
+            ("missed", missed.missed.into()),
+        ],
+    );
+    telemetry.publish("deadline_missed", &missed.summary(status.display_unit()))?;
+    event_log.event(
+        "deadline_missed",
+        true,
+        &[
+            ("section", missed.section.to_string()),
+            ("thread", missed.thread.clone()),
+            ("duration_ns", missed.duration_ns.to_string()),
+            ("budget_ns", missed.budget_ns.to_string()),
+        ],
+    )?;
+    Ok(())
+}
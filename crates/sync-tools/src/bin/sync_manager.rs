@@ -0,0 +1,216 @@
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::{self, Child, Command, ExitStatus, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::{Duration, Instant};
+use sync_core::cli::Args;
+use sync_core::status::Status;
+use sync_core::supervise::{self, Backoff, ProcessSpec, RigConfig};
+
+/// Exits, restarts and output are checked this often.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+// A supervised process and when it was started, or when it is due to restart
+struct Supervised {
+    spec: ProcessSpec,
+    child: Option<Child>,
+    started: Instant,
+    restart_at: Option<Instant>,
+    backoff: Backoff,
+    restarts: u64,
+}
+
+// Runs the processes of a rig as one unit (see `sync_core::supervise`): starts
+// them in order, prints their output prefixed with their names, restarts the
+// ones that exit and stops all of them on SIGINT or SIGTERM.
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::from_env(&[]);
+    let status = Status::from_args(&args, "sync_manager")?;
+    let Some(path) = args.positional::<String>(0) else {
+        println!("Usage: {} <rig.toml> [--status-format text|json]", args.program());
+        process::exit(2);
+    };
+    let config = match RigConfig::load(Path::new(&path)) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error: could not load rig config {}: {}", path, e);
+            process::exit(2);
+        }
+    };
+    supervise::install_shutdown_handler();
+    let names: Vec<String> = config.processes.iter().map(|process| process.name.clone()).collect();
+    status.line(
+        "rig_start",
+        format_args!("Starting {} processes of rig {} from {}", config.processes.len(), config.rig_id.as_deref().unwrap_or("default"), path),
+        &[("rig", config.rig_id.as_deref().into()), ("processes", config.processes.len().into()), ("config", path.as_str().into())],
+    );
+
+    // Output lines of all processes, by process index
+    let (output, lines) = mpsc::channel();
+    let mut supervised = Vec::with_capacity(config.processes.len());
+    for (index, spec) in config.processes.iter().enumerate() {
+        let mut process = Supervised {
+            spec: spec.clone(),
+            child: None,
+            started: Instant::now(),
+            restart_at: None,
+            backoff: Backoff::new(config.restart_delay, config.max_restart_delay),
+            restarts: 0,
+        };
+        start(&mut process, index, &config, &output, &status);
+        supervised.push(process);
+    }
+
+    while !supervise::shutdown_requested() {
+        print_output(&lines, &names, &status);
+        let now = Instant::now();
+        for (index, process) in supervised.iter_mut().enumerate() {
+            if let Some(child) = &mut process.child {
+                if let Some(exit) = child.try_wait()? {
+                    process.child = None;
+                    on_exit(process, exit, now, &status);
+                }
+            }
+            if process.restart_at.is_some_and(|at| now >= at) {
+                process.restarts += 1;
+                start(process, index, &config, &output, &status);
+            }
+        }
+        // Nothing left to run or restart
+        if supervised.iter().all(|process| process.child.is_none() && process.restart_at.is_none()) {
+            print_output(&lines, &names, &status);
+            status.line("rig_stopped", format_args!("All processes exited"), &[]);
+            return Ok(());
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+
+    status.line("rig_shutdown", format_args!("Shutting down, stopping processes in reverse start order"), &[]);
+    for process in supervised.iter_mut().rev() {
+        let Some(mut child) = process.child.take() else {
+            continue;
+        };
+        let (exit, killed) = stop(&mut child, config.shutdown_timeout)?;
+        print_output(&lines, &names, &status);
+        status.line(
+            "process_stopped",
+            format_args!("[{}] stopped ({}{})", process.spec.name, exit, if killed { ", killed after the shutdown timeout" } else { "" }),
+            &[("process", process.spec.name.as_str().into()), ("exit_code", exit.code().map(i64::from).into()), ("killed", killed.into())],
+        );
+    }
+    print_output(&lines, &names, &status);
+    Ok(())
+}
+
+// Spawns the process with its output piped into `output`; a failed spawn is retried like an exit
+fn start(process: &mut Supervised, index: usize, config: &RigConfig, output: &Sender<(usize, String)>, status: &Status) {
+    process.restart_at = None;
+    process.started = Instant::now();
+    let program = resolve(&process.spec.program);
+    let spawned = Command::new(&program)
+        .args(process.spec.command_args(config.rig_id.as_deref()))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+    match spawned {
+        Ok(mut child) => {
+            forward(child.stdout.take(), index, output.clone());
+            forward(child.stderr.take(), index, output.clone());
+            status.line(
+                "process_started",
+                format_args!("[{}] started {} (pid {}, restarts {})", process.spec.name, program.display(), child.id(), process.restarts),
+                &[
+                    ("process", process.spec.name.as_str().into()),
+                    ("program", program.display().to_string().into()),
+                    ("pid", child.id().into()),
+                    ("restarts", process.restarts.into()),
+                ],
+            );
+            process.child = Some(child);
+        }
+        Err(e) => {
+            status.line(
+                "process_failed",
+                format_args!("WARNING: [{}] could not start {}: {}", process.spec.name, program.display(), e),
+                &[("process", process.spec.name.as_str().into()), ("program", program.display().to_string().into()), ("error", e.to_string().into())],
+            );
+            schedule_restart(process, false, Instant::now(), status);
+        }
+    }
+}
+
+fn on_exit(process: &mut Supervised, exit: ExitStatus, now: Instant, status: &Status) {
+    let ran_s = now.duration_since(process.started).as_secs_f64();
+    status.line(
+        "process_exited",
+        format_args!("{}[{}] exited after {:.1}s ({})", if exit.success() { "" } else { "WARNING: " }, process.spec.name, ran_s, exit),
+        &[("process", process.spec.name.as_str().into()), ("exit_code", exit.code().map(i64::from).into()), ("success", exit.success().into()), ("ran_s", ran_s.into())],
+    );
+    schedule_restart(process, exit.success(), now, status);
+}
+
+// Sets the restart time as the process's policy and backoff say
+fn schedule_restart(process: &mut Supervised, success: bool, now: Instant, status: &Status) {
+    if !process.spec.restart.restarts(success) {
+        status.line(
+            "process_done",
+            format_args!("[{}] not restarted (restart = {})", process.spec.name, process.spec.restart),
+            &[("process", process.spec.name.as_str().into()), ("restart", process.spec.restart.name().into())],
+        );
+        return;
+    }
+    let delay = process.backoff.on_exit(now.duration_since(process.started));
+    process.restart_at = Some(now + delay);
+    status.line(
+        "process_restart",
+        format_args!("[{}] restarting in {}ms ({} exits in a row)", process.spec.name, delay.as_millis(), process.backoff.crashes),
+        &[("process", process.spec.name.as_str().into()), ("delay_ms", (delay.as_millis() as u64).into()), ("crashes", process.backoff.crashes.into())],
+    );
+}
+
+// SIGTERM, then SIGKILL after `timeout`; returns the exit status and whether it had to be killed
+fn stop(child: &mut Child, timeout: Duration) -> std::io::Result<(ExitStatus, bool)> {
+    // Already gone if it failed
+    let _ = supervise::terminate(child.id());
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+        if let Some(exit) = child.try_wait()? {
+            return Ok((exit, false));
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+    child.kill()?;
+    Ok((child.wait()?, true))
+}
+
+// A bare program name is looked up next to this binary first, then in PATH
+fn resolve(program: &str) -> PathBuf {
+    if program.contains('/') {
+        return PathBuf::from(program);
+    }
+    let sibling = std::env::current_exe().ok().and_then(|exe| exe.parent().map(|dir| dir.join(program)));
+    sibling.filter(|path| path.is_file()).unwrap_or_else(|| PathBuf::from(program))
+}
+
+// Reads lines of a child's stdout or stderr on a thread of its own until the pipe closes
+fn forward(pipe: Option<impl Read + Send + 'static>, index: usize, output: Sender<(usize, String)>) {
+    let Some(pipe) = pipe else {
+        return;
+    };
+    std::thread::spawn(move || {
+        for line in BufReader::new(pipe).lines().map_while(Result::ok) {
+            if output.send((index, line)).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+// Prints the lines received so far, prefixed with their process's name
+fn print_output(lines: &Receiver<(usize, String)>, names: &[String], status: &Status) {
+    while let Ok((index, line)) = lines.try_recv() {
+        let name = names[index].as_str();
+        status.line("process_output", format_args!("[{}] {}", name, line), &[("process", name.into()), ("line", line.as_str().into())]);
+    }
+}
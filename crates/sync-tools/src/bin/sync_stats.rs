@@ -0,0 +1,103 @@
+use std::path::Path;
+use std::process;
+use std::time::Duration;
+use sync_core::cli::Args;
+use sync_core::clock;
+use sync_core::namespace::NodeIdentity;
+use sync_core::shared_stats::{self, Snapshot, DEFAULT_DIR};
+use sync_core::status::Status;
+use sync_core::units::DisplayMs;
+
+// Prints the stats snapshots the processes of a rig publish with
+// `--shared-stats` (see `sync_core::shared_stats`), without an iceoryx2 node.
+//
+// `list` prints every snapshot once, `watch` again every `--interval-ms`.
+// Snapshots of processes that are gone are shown as dead; the next process
+// of the rig that publishes one removes them.
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::from_env(&[]);
+    let status = Status::from_args(&args, "sync_stats")?;
+    let mode = args.positional::<String>(0).unwrap_or_default();
+    let identity = NodeIdentity::from_args(&args, "sync_stats", None)?;
+    let rig_dir = shared_stats::rig_dir(Path::new(args.value("shared-stats-dir").unwrap_or(DEFAULT_DIR)), &identity);
+    status.text(format_args!(
+        "Usage: {} list | watch [--interval-ms <ms>] [--rig-id <id>] [--shared-stats-dir <dir>] [--status-format text|json] [--display-unit ms|us|ns]",
+        args.program()
+    ));
+
+    match mode.as_str() {
+        "list" => list(&identity, &rig_dir, &status),
+        "watch" => {
+            let interval = Duration::from_millis(args.value_as::<u64>("interval-ms").unwrap_or(1000));
+            loop {
+                list(&identity, &rig_dir, &status)?;
+                std::thread::sleep(interval);
+            }
+        }
+        other => {
+            eprintln!("Error: unknown mode '{}' (expected list or watch)", other);
+            process::exit(2);
+        }
+    }
+}
+
+fn list(identity: &NodeIdentity, rig_dir: &Path, status: &Status) -> Result<(), Box<dyn std::error::Error>> {
+    // No process of the rig published a snapshot yet
+    let snapshots = if rig_dir.exists() { shared_stats::list(rig_dir)? } else { Vec::new() };
+    let now_ns = clock::realtime_now_ns();
+    for snapshot in &snapshots {
+        print_snapshot(snapshot, now_ns, status);
+    }
+    let dead = snapshots.iter().filter(|snapshot| !snapshot.alive).count();
+    status.line(
+        "stats_snapshots",
+        format_args!("Rig {}: {} stats snapshots ({} dead) in {}", identity.rig(), snapshots.len(), dead, rig_dir.display()),
+        &[("rig", identity.rig().into()), ("alive", (snapshots.len() - dead).into()), ("dead", dead.into()), ("dir", rig_dir.display().to_string().into())],
+    );
+    Ok(())
+}
+
+fn print_snapshot(snapshot: &Snapshot, now_ns: u64, status: &Status) {
+    let stats = &snapshot.stats;
+    let state = if snapshot.alive { "alive" } else { "dead" };
+    let age_s = now_ns.saturating_sub(stats.updated_ns) as f64 / 1e9;
+    let uptime_s = stats.updated_ns.saturating_sub(stats.started_ns) as f64 / 1e9;
+    status.line(
+        "process_stats",
+        format_args!(
+            "  {} (pid {}, {}) up {:.0}s, updated {:.1}s ago: triggers={} frames={} matched={} ({:.1}%) drops={} deadline_misses={} pending={} latency p50={:.2} p95={:.2} max={:.2}",
+            snapshot.node_name,
+            snapshot.pid,
+            state,
+            uptime_s,
+            age_s,
+            stats.triggers,
+            stats.frames,
+            stats.matched,
+            stats.match_rate() * 100.0,
+            stats.drops,
+            stats.deadline_misses,
+            stats.pending_triggers,
+            DisplayMs(stats.latency_p50_ms, status.display_unit()),
+            DisplayMs(stats.latency_p95_ms, status.display_unit()),
+            DisplayMs(stats.latency_max_ms, status.display_unit())
+        ),
+        &[
+            ("node", snapshot.node_name.as_str().into()),
+            ("pid", snapshot.pid.into()),
+            ("alive", snapshot.alive.into()),
+            ("uptime_s", uptime_s.into()),
+            ("age_s", age_s.into()),
+            ("triggers", stats.triggers.into()),
+            ("frames", stats.frames.into()),
+            ("matched", stats.matched.into()),
+            ("match_rate", stats.match_rate().into()),
+            ("drops", stats.drops.into()),
+            ("deadline_misses", stats.deadline_misses.into()),
+            ("pending_triggers", stats.pending_triggers.into()),
+            ("latency_p50_ms", stats.latency_p50_ms.into()),
+            ("latency_p95_ms", stats.latency_p95_ms.into()),
+            ("latency_max_ms", stats.latency_max_ms.into()),
+        ],
+    );
+}
@@ -0,0 +1,23 @@
+use std::time::Duration;
+use sync_core::cli::Args;
+use sync_core::namespace::NodeIdentity;
+use sync_iceoryx2::node::create_node;
+use sync_iceoryx2::telemetry::TelemetrySubscriber;
+
+// Prints every message published on the telemetry channel (session manifests, status, ...).
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::from_env(&[]);
+    let node = create_node(&NodeIdentity::from_args(&args, "telemetry_monitor", None)?)?;
+    let telemetry = TelemetrySubscriber::create(&node)?;
+
+    println!("Telemetry monitor started. Waiting for messages...");
+
+    loop {
+        while let Some((kind, body)) = telemetry.receive()? {
+            println!("=== {} ===", kind);
+            println!("{}", body);
+        }
+
+        std::thread::sleep(Duration::from_millis(100));
+    }
+}
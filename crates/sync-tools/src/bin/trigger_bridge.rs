@@ -0,0 +1,509 @@
+use iceoryx2::prelude::*;
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::path::Path;
+use std::process;
+use std::time::Duration;
+use sync_core::canary;
+use sync_core::cli::Args;
+use sync_core::clock::{self, ClockDomain};
+use sync_core::federation::{FederationTable, LossCounter, ProbeClient, RelayState, RigHealth};
+use sync_core::feedback::{self, ReceiveFeedback};
+use sync_core::leap;
+use sync_core::namespace::NodeIdentity;
+use sync_core::netstamp::{StampMode, StampedSocket};
+use sync_core::signing::{TriggerSignature, TriggerSigner};
+use sync_core::stats::LatencyStats;
+use sync_core::status::Status;
+use sync_core::wire::{ProbeMessage, TriggerMessage, WireMessage, MAX_MESSAGE_LEN};
+use sync_core::CameraTrigger;
+use sync_iceoryx2::feedback::FeedbackPublisher;
+use sync_iceoryx2::node::create_node;
+use sync_iceoryx2::telemetry::TelemetryPublisher;
+use sync_iceoryx2::trigger::{TriggerPublisher, TriggerSubscriber};
+
+/// One-way delay statistics are reported every this many received triggers.
+const REPORT_EVERY: u64 = 100;
+/// Federation health is reported this often.
+const FEDERATION_REPORT_NS: u64 = 5_000_000_000;
+/// Relays probe their upstream this often by default.
+const PROBE_INTERVAL_MS: u64 = 250;
+/// Relays with a larger offset uncertainty count as unhealthy by default.
+const MAX_UNCERTAINTY_US: f64 = 250.0;
+
+// Forwards triggers between hosts over UDP in the wire encoding.
+//
+// `send` subscribes to the local trigger service and sends every trigger to
+// the remote bridges, `recv` republishes the received triggers on its host.
+// `relay` does the same for one rig of a federation (see
+// `sync_core::federation`): it measures the clock offset to its upstream
+// bridge with probes, republishes the triggers re-timestamped into the local
+// clock and forwards them to the relays below it. Datagrams are timestamped
+// with SO_TIMESTAMPING (NIC, kernel or user space, whichever is available);
+// each message carries the send time of the one before, and the receiver
+// reports the one-way delay (meaningful when both hosts' clocks are
+// PTP-synchronized).
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::from_env(&[]);
+    let status = Status::from_args(&args, "trigger_bridge")?;
+    let mode = args.positional::<String>(0).unwrap_or_default();
+    let Some(address) = args.positional::<String>(1) else {
+        eprintln!("Usage: {} send <remote addr>[,<remote addr>...] [--bind <addr>] [--interface <nic>] [--consumer-name <name>] | recv <bind addr> [--interface <nic>] | relay <bind addr> [--forward <addr>]... [--rig <name>] [--probe-interval-ms <ms>] [--max-uncertainty-us <us>] [--sign-key <key_file>] [--interface <nic>] [--leap-seconds <file>] [--status-format text|json] [--display-unit ms|us|ns]", args.program());
+        process::exit(2);
+    };
+    let interface = args.value("interface");
+    // NIC hardware stamps count TAI
+    status.leap_seconds(leap::install_from_args(&args)?);
+
+    let node = create_node(&NodeIdentity::from_args(&args, "trigger_bridge", Some(mode.as_str()))?)?;
+    match mode.as_str() {
+        "send" => send(&node, &address, args.value("bind").unwrap_or("0.0.0.0:0"), interface, args.value("consumer-name").unwrap_or("trigger_bridge"), &status),
+        "recv" => recv(&node, &address, interface, &status),
+        "relay" => relay(&node, &address, &args, interface, &status),
+        other => {
+            eprintln!("Error: unknown mode '{}' (expected send, recv or relay)", other);
+            process::exit(2);
+        }
+    }
+}
+
+/// Resolves `host:port` addresses, several per entry if comma-separated.
+fn resolve<'a>(addresses: impl IntoIterator<Item = &'a str>) -> Result<Vec<SocketAddr>, Box<dyn std::error::Error>> {
+    let mut resolved = Vec::new();
+    for address in addresses.into_iter().flat_map(|entry| entry.split(',')).filter(|address| !address.is_empty()) {
+        let found = address.to_socket_addrs()?.next().ok_or_else(|| format!("cannot resolve '{}'", address))?;
+        resolved.push(found);
+    }
+    Ok(resolved)
+}
+
+/// A bridge below this one, with the send time of the last trigger sent to it.
+struct Link {
+    remote: SocketAddr,
+    last_tx: Option<(u64, u64)>,
+}
+
+/// Sends a trigger over every link and reports when TX timestamps fall back.
+fn forward(
+    socket: &StampedSocket,
+    links: &mut [Link],
+    trigger: CameraTrigger,
+    signature: TriggerSignature,
+    fallbacks: &mut u64,
+    status: &Status,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for link in links {
+        let message = TriggerMessage {
+            trigger,
+            signature,
+            link_tx: link.last_tx,
+        };
+        let (tx_ns, tx_mode) = socket.send_to(&message.encode(), link.remote)?;
+        // The send time travels with the next message
+        link.last_tx = Some((trigger.frame_id, tx_ns));
+        if tx_mode != socket.mode() {
+            *fallbacks += 1;
+            if fallbacks.is_power_of_two() {
+                status.line(
+                    "tx_stamp_fallback",
+                    format_args!("TX STAMP: {} of the sent triggers fell back to {} timestamps", fallbacks, tx_mode),
+                    &[("count", (*fallbacks).into()), ("timestamps", tx_mode.to_string().into())],
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+fn reject(error: String, rejected: &mut u64, status: &Status) {
+    *rejected += 1;
+    status.line(
+        "bridge_rejected",
+        format_args!("REJECTED: malformed datagram ({}), {} so far", error, rejected),
+        &[("error", error.as_str().into()), ("count", (*rejected).into())],
+    );
+}
+
+/// Answers a relay's probe; the reply's send time travels with the next reply.
+fn answer_probe(socket: &StampedSocket, federation: &mut FederationTable, from: SocketAddr, probe: &ProbeMessage, rx_ns: u64) -> io::Result<()> {
+    let reply = federation.on_probe(from, probe, rx_ns);
+    let (tx_ns, _) = socket.send_to(&reply.encode(), from)?;
+    federation.on_reply_sent(from, reply.sequence, tx_ns);
+    Ok(())
+}
+
+/// Reports the relays directly below this bridge.
+fn report_relays(federation: &FederationTable, now_ns: u64, status: &Status) {
+    for relay in federation.relays() {
+        let state = relay.state(now_ns);
+        let Some(health) = &relay.health else {
+            status.line(
+                "federation_relay",
+                format_args!("RELAY {}: no probes yet", relay.address),
+                &[("address", relay.address.to_string().into()), ("state", state.to_string().into())],
+            );
+            continue;
+        };
+        let offset = match (health.offset_ns, health.uncertainty_ns) {
+            (Some(offset_ns), Some(uncertainty_ns)) => format!("offset {:+.1}us ±{:.1}us", offset_ns as f64 / 1e3, uncertainty_ns as f64 / 1e3),
+            _ => "offset not measured yet".to_string(),
+        };
+        let silent_s = relay.last_probe_ns.map(|last| now_ns.saturating_sub(last) as f64 / 1e9);
+        status.line(
+            "federation_relay",
+            format_args!(
+                "{}RELAY {} ({}, hop {}): {}, {}, {} triggers, {} lost, {} of {} rigs unhealthy",
+                if state == RelayState::Lost { "WARNING: " } else { "" },
+                relay.address,
+                health.rig,
+                health.hops,
+                state,
+                offset,
+                health.received,
+                health.lost,
+                health.unhealthy,
+                health.rigs
+            ),
+            &[
+                ("address", relay.address.to_string().into()),
+                ("rig", health.rig.as_str().into()),
+                ("state", state.to_string().into()),
+                ("hops", health.hops.into()),
+                ("offset_us", health.offset_ns.map(|ns| ns as f64 / 1e3).into()),
+                ("uncertainty_us", health.uncertainty_ns.map(|ns| ns as f64 / 1e3).into()),
+                ("received", health.received.into()),
+                ("lost", health.lost.into()),
+                ("rigs", health.rigs.into()),
+                ("unhealthy", health.unhealthy.into()),
+                ("silent_s", silent_s.into()),
+            ],
+        );
+    }
+}
+
+fn send(node: &Node<ipc::Service>, remotes: &str, bind: &str, interface: Option<&str>, consumer_name: &str, status: &Status) -> Result<(), Box<dyn std::error::Error>> {
+    let subscriber = TriggerSubscriber::create(node)?;
+    // Canaries of the local publisher end here, the remote rigs have their own
+    let feedback = FeedbackPublisher::create(node)?;
+    let telemetry = TelemetryPublisher::create(node)?;
+    let socket = StampedSocket::bind(bind, interface)?;
+    // Probes from relays are answered between triggers
+    socket.set_nonblocking(true)?;
+    let mut links: Vec<Link> = resolve([remotes])?.into_iter().map(|remote| Link { remote, last_tx: None }).collect();
+    let remotes = links.iter().map(|link| link.remote.to_string()).collect::<Vec<_>>().join(", ");
+    status.line(
+        "start",
+        format_args!("Trigger bridge sending to {} with {} timestamps", remotes, socket.mode()),
+        &[("mode", "send".into()), ("remote", remotes.as_str().into()), ("timestamps", socket.mode().to_string().into())],
+    );
+
+    let mut federation = FederationTable::new(0, links.iter().map(|link| link.remote));
+    let mut buffer = [0u8; MAX_MESSAGE_LEN];
+    let mut fallbacks = 0u64;
+    let mut rejected = 0u64;
+    let mut converted = 0u64;
+    let mut next_report_ns = clock::realtime_now_ns() + FEDERATION_REPORT_NS;
+    loop {
+        while let Some((trigger, signature)) = subscriber.receive()? {
+            // CLOCK_MONOTONIC means nothing on the other hosts: such triggers go out in CLOCK_REALTIME,
+            // unsigned since the signature covers the original timestamps
+            let (trigger, signature) = if ClockDomain::from_u8(signature.clock_domain).is_some_and(ClockDomain::is_host_local) {
+                if converted == 0 {
+                    status.line(
+                        "bridge_converted",
+                        format_args!("WARNING: triggers in {} are sent converted to CLOCK_REALTIME and unsigned", ClockDomain::label(signature.clock_domain)),
+                        &[("domain", ClockDomain::label(signature.clock_domain).into())],
+                    );
+                }
+                converted += 1;
+                let (trigger, header) = clock::trigger_to_realtime(trigger, signature)?;
+                let header = TriggerSignature {
+                    timestamp_source: header.timestamp_source,
+                    clock_domain: header.clock_domain,
+                    clock_policy: header.clock_policy,
+                    trace_origin: header.trace_origin,
+                    phc_offset_ns: header.phc_offset_ns,
+                    ..TriggerSignature::default()
+                };
+                (trigger, header)
+            } else {
+                (trigger, signature)
+            };
+            if feedback::is_sampled(trigger.frame_id) {
+                feedback.publish(ReceiveFeedback::new(consumer_name, &trigger, clock::realtime_now_ns()))?;
+            }
+            if canary::is_canary(trigger.frame_id) {
+                continue;
+            }
+            forward(&socket, &mut links, trigger, signature, &mut fallbacks, status)?;
+        }
+        loop {
+            let (len, from, rx_ns, _) = match socket.recv_from(&mut buffer) {
+                Ok(received) => received,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e.into()),
+            };
+            match WireMessage::decode(&buffer[..len]) {
+                Ok(WireMessage::Probe(probe)) => answer_probe(&socket, &mut federation, from, &probe, rx_ns)?,
+                Ok(_) => {}
+                Err(e) => reject(e, &mut rejected, status),
+            }
+        }
+
+        let now_ns = clock::realtime_now_ns();
+        // Plain `recv` bridges never probe; the report starts with the first relay
+        if now_ns >= next_report_ns && federation.relays().iter().any(|relay| relay.health.is_some()) {
+            next_report_ns = now_ns + FEDERATION_REPORT_NS;
+            report_relays(&federation, now_ns, status);
+            let (rigs, unhealthy) = federation.subtree(now_ns);
+            status.line(
+                "federation",
+                format_args!("FEDERATION: {} rigs below the master, {} unhealthy", rigs, unhealthy),
+                &[("rigs", rigs.into()), ("unhealthy", unhealthy.into())],
+            );
+            telemetry.publish("federation", &format!("{} rigs, {} unhealthy", rigs, unhealthy))?;
+        }
+        // Triggers end the wait right away, probes are answered within 100us
+        subscriber.wait(Duration::from_micros(100))?;
+    }
+}
+
+fn recv(node: &Node<ipc::Service>, bind: &str, interface: Option<&str>, status: &Status) -> Result<(), Box<dyn std::error::Error>> {
+    let publisher = TriggerPublisher::create(node)?;
+    let socket = StampedSocket::bind(bind, interface)?;
+    status.line(
+        "start",
+        format_args!("Trigger bridge receiving on {} with {} timestamps", socket.local_addr()?, socket.mode()),
+        &[("mode", "recv".into()), ("bind", socket.local_addr()?.to_string().into()), ("timestamps", socket.mode().to_string().into())],
+    );
+
+    let mut buffer = [0u8; MAX_MESSAGE_LEN];
+    // Receive time of the last message, matched against the send time the next one carries
+    let mut last_rx: Option<(u64, u64, StampMode)> = None;
+    let mut delay = LatencyStats::default();
+    let mut received = 0u64;
+    let mut rejected = 0u64;
+    loop {
+        let (len, rx_ns, rx_mode) = socket.recv(&mut buffer)?;
+        let message = match TriggerMessage::decode(&buffer[..len]) {
+            Ok(message) => message,
+            Err(e) => {
+                reject(e, &mut rejected, status);
+                continue;
+            }
+        };
+        publisher.publish(message.trigger, message.signature)?;
+        received += 1;
+
+        if let (Some((tx_frame_id, tx_ns)), Some((rx_frame_id, last_rx_ns, last_rx_mode))) = (message.link_tx, last_rx) {
+            if tx_frame_id == rx_frame_id {
+                delay.add((last_rx_ns as i64 - tx_ns as i64) as f64 / 1e6);
+                if delay.count().is_multiple_of(REPORT_EVERY) {
+                    status.line(
+                        "bridge_delay",
+                        format_args!("ONE-WAY DELAY ({} rx timestamps): {}", last_rx_mode, delay.summary(status.display_unit())),
+                        &[
+                            ("count", delay.count().into()),
+                            ("p50_ms", delay.percentile(50.0).into()),
+                            ("p95_ms", delay.percentile(95.0).into()),
+                            ("min_ms", delay.min().into()),
+                            ("max_ms", delay.max().into()),
+                            ("rx_timestamps", last_rx_mode.to_string().into()),
+                            ("received", received.into()),
+                        ],
+                    );
+                }
+            }
+        }
+        last_rx = Some((message.trigger.frame_id, rx_ns, rx_mode));
+    }
+}
+
+fn relay(node: &Node<ipc::Service>, bind: &str, args: &Args, interface: Option<&str>, status: &Status) -> Result<(), Box<dyn std::error::Error>> {
+    let publisher = TriggerPublisher::create(node)?;
+    let telemetry = TelemetryPublisher::create(node)?;
+    let signer = match args.value("sign-key") {
+        Some(path) => Some(TriggerSigner::from_key_file(Path::new(path))?),
+        None => None,
+    };
+    let probe_interval_ns = args.value_as::<u64>("probe-interval-ms").unwrap_or(PROBE_INTERVAL_MS).max(1) * 1_000_000;
+    let max_uncertainty_ns = (args.value_as::<f64>("max-uncertainty-us").unwrap_or(MAX_UNCERTAINTY_US) * 1e3) as u64;
+    let socket = StampedSocket::bind(bind, interface)?;
+    // Wakes up for probes and reports while no trigger arrives
+    socket.set_read_timeout(Some(Duration::from_millis(10)))?;
+    let rig = args.value("rig").map_or_else(|| socket.local_addr().map(|addr| addr.to_string()), |rig| Ok(rig.to_string()))?;
+    let mut links: Vec<Link> = resolve(args.values("forward"))?.into_iter().map(|remote| Link { remote, last_tx: None }).collect();
+    let forwards = links.iter().map(|link| link.remote.to_string()).collect::<Vec<_>>().join(", ");
+    status.line(
+        "start",
+        format_args!(
+            "Trigger bridge relaying rig '{}' on {} with {} timestamps{}",
+            rig,
+            socket.local_addr()?,
+            socket.mode(),
+            if links.is_empty() { String::new() } else { format!(", forwarding to {}", forwards) }
+        ),
+        &[
+            ("mode", "relay".into()),
+            ("rig", rig.as_str().into()),
+            ("bind", socket.local_addr()?.to_string().into()),
+            ("forward", forwards.as_str().into()),
+            ("timestamps", socket.mode().to_string().into()),
+        ],
+    );
+
+    let mut federation = FederationTable::new(1, links.iter().map(|link| link.remote));
+    let mut client = ProbeClient::default();
+    let mut loss = LossCounter::default();
+    let mut buffer = [0u8; MAX_MESSAGE_LEN];
+    // Learned from the first trigger; probes go where the triggers come from
+    let mut upstream: Option<SocketAddr> = None;
+    let mut next_probe_ns = 0u64;
+    let mut next_report_ns = clock::realtime_now_ns() + FEDERATION_REPORT_NS;
+    let mut lost_at_last_probe = 0u64;
+    let mut unsynced = 0u64;
+    let mut unsigned = 0u64;
+    let mut fallbacks = 0u64;
+    let mut rejected = 0u64;
+    loop {
+        let now_ns = clock::realtime_now_ns();
+        // Until an offset is measured, the next probe goes out as soon as the last one is answered
+        let probe_due = now_ns >= next_probe_ns || (client.estimate().is_none() && !client.awaiting_reply());
+        if let (Some(upstream), true) = (upstream, probe_due) {
+            next_probe_ns = now_ns + probe_interval_ns;
+            let estimate = client.estimate().filter(|estimate| now_ns.saturating_sub(estimate.updated_ns) < 4 * probe_interval_ns);
+            let healthy = estimate.is_some_and(|estimate| estimate.uncertainty_ns <= max_uncertainty_ns) && loss.lost == lost_at_last_probe;
+            lost_at_last_probe = loss.lost;
+            let (rigs, unhealthy) = federation.subtree(now_ns);
+            let sequence = client.next_sequence();
+            let probe = ProbeMessage {
+                sequence,
+                health: RigHealth {
+                    rig: rig.clone(),
+                    hops: client.hops().unwrap_or(1),
+                    received: loss.received,
+                    lost: loss.lost,
+                    offset_ns: estimate.map(|estimate| estimate.offset_ns),
+                    uncertainty_ns: estimate.map(|estimate| estimate.uncertainty_ns),
+                    rigs: rigs + 1,
+                    unhealthy: unhealthy + !healthy as u32,
+                },
+            };
+            let (tx_ns, _) = socket.send_to(&probe.encode(), upstream)?;
+            client.on_sent(sequence, tx_ns);
+        }
+
+        if now_ns >= next_report_ns {
+            next_report_ns = now_ns + FEDERATION_REPORT_NS;
+            report_relays(&federation, now_ns, status);
+            let (rigs, unhealthy) = federation.subtree(now_ns);
+            let hops = client.hops().unwrap_or(1);
+            let estimate = client.estimate();
+            let offset = estimate.map_or_else(|| "offset not measured yet".to_string(), |estimate| format!("offset {}", estimate.summary()));
+            status.line(
+                "federation",
+                format_args!(
+                    "FEDERATION rig '{}' (hop {}): {}, {} triggers, {} lost, {} dropped before the offset was known, {} rigs below, {} unhealthy",
+                    rig, hops, offset, loss.received, loss.lost, unsynced, rigs, unhealthy
+                ),
+                &[
+                    ("rig", rig.as_str().into()),
+                    ("hops", hops.into()),
+                    ("offset_us", estimate.map(|estimate| estimate.offset_ns as f64 / 1e3).into()),
+                    ("uncertainty_us", estimate.map(|estimate| estimate.uncertainty_ns as f64 / 1e3).into()),
+                    ("round_trip_us", estimate.map(|estimate| estimate.round_trip_ns as f64 / 1e3).into()),
+                    ("received", loss.received.into()),
+                    ("lost", loss.lost.into()),
+                    ("unsynced", unsynced.into()),
+                    ("rigs", rigs.into()),
+                    ("unhealthy", unhealthy.into()),
+                ],
+            );
+            telemetry.publish("federation", &format!("rig {} hop {}: {}, {} lost, {} rigs below, {} unhealthy", rig, hops, offset, loss.lost, rigs, unhealthy))?;
+        }
+
+        let (len, from, rx_ns, _) = match socket.recv_from(&mut buffer) {
+            Ok(received) => received,
+            Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => continue,
+            Err(e) => return Err(e.into()),
+        };
+        match WireMessage::decode(&buffer[..len]) {
+            Ok(WireMessage::Trigger(message)) => {
+                if upstream != Some(from) {
+                    status.line(
+                        "federation_upstream",
+                        format_args!("Receiving triggers from {}", from),
+                        &[("address", from.to_string().into())],
+                    );
+                    upstream = Some(from);
+                }
+                loss.on_trigger(message.trigger.frame_id);
+                // Triggers in an unknown clock would stamp frames wrongly; drop them until the first offset
+                let Some(estimate) = client.estimate() else {
+                    unsynced += 1;
+                    continue;
+                };
+                let trigger = CameraTrigger {
+                    hw_timestamp_ns: estimate.to_local(message.trigger.hw_timestamp_ns),
+                    publish_timestamp_ns: estimate.to_local(message.trigger.publish_timestamp_ns),
+                    ..message.trigger
+                };
+                // The upstream signature covers the original timestamps; the trace origin stays, the upstream PHC offset
+                // does not apply to the local clock
+                let signature = match (&signer, message.signature.signed) {
+                    (Some(signer), _) => TriggerSignature {
+                        timestamp_source: message.signature.timestamp_source,
+                        clock_domain: message.signature.clock_domain,
+                        clock_policy: message.signature.clock_policy,
+                        trace_origin: message.signature.trace_origin,
+                        ..signer.sign(&trigger)
+                    },
+                    (None, true) => {
+                        unsigned += 1;
+                        if unsigned == 1 {
+                            status.line(
+                                "federation_unsigned",
+                                format_args!("WARNING: re-timestamped triggers are republished unsigned; pass --sign-key to sign them"),
+                                &[],
+                            );
+                        }
+                        TriggerSignature {
+                            timestamp_source: message.signature.timestamp_source,
+                            clock_domain: message.signature.clock_domain,
+                            clock_policy: message.signature.clock_policy,
+                            trace_origin: message.signature.trace_origin,
+                            ..TriggerSignature::default()
+                        }
+                    }
+                    (None, false) => TriggerSignature {
+                        phc_offset_ns: 0,
+                        ..message.signature
+                    },
+                };
+                publisher.publish(trigger, signature)?;
+                forward(&socket, &mut links, trigger, signature, &mut fallbacks, status)?;
+            }
+            Ok(WireMessage::Probe(probe)) => answer_probe(&socket, &mut federation, from, &probe, rx_ns)?,
+            Ok(WireMessage::ProbeReply(reply)) => {
+                let first = client.estimate().is_none();
+                if let Some(estimate) = client.on_reply(&reply, rx_ns) {
+                    if let Some(hops) = client.hops() {
+                        federation.set_hops(hops);
+                    }
+                    if first {
+                        status.line(
+                            "federation_offset",
+                            format_args!("Upstream clock offset measured: {}", estimate.summary()),
+                            &[
+                                ("offset_us", (estimate.offset_ns as f64 / 1e3).into()),
+                                ("uncertainty_us", (estimate.uncertainty_ns as f64 / 1e3).into()),
+                                ("round_trip_us", (estimate.round_trip_ns as f64 / 1e3).into()),
+                            ],
+                        );
+                    }
+                }
+            }
+            Err(e) => reject(e, &mut rejected, status),
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use std::time::Duration;
+use sync_core::batch::parse_duration;
+use sync_core::cli::Args;
+use sync_core::clock::{self, ClockDomain};
+use sync_core::leap;
+use sync_core::namespace::NodeIdentity;
+use sync_core::status::Status;
+use sync_core::timestamping::TimestampSource;
+use sync_core::trace::TraceId;
+use sync_core::units::{Nanos, TimestampFormat};
+use sync_core::CameraTrigger;
+use sync_iceoryx2::history::TriggerHistoryClient;
+use sync_iceoryx2::node::create_node;
+
+// Fetches the triggers published since a point in time from the running
+// publisher's trigger ring, e.g. to backfill an analysis attached mid-run.
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::from_env(&[]);
+    let status = Status::from_args(&args, "trigger_history")?;
+    status.text(format_args!(
+        "Usage: {} [--since <timestamp> | --last <time>] [--timeout-ms <ms>] [--timestamp-unit ns|us|ms] [--timestamp-epoch unix|tai|gps] [--leap-seconds <file>] [--status-format text|json] [--display-unit ms|us|ns]",
+        args.program()
+    ));
+
+    // `--since` and the printed timestamps are in this unit and epoch, ns since the Unix epoch by default
+    let timestamps = TimestampFormat::from_args(&args)?;
+    status.leap_seconds(leap::install_from_args(&args)?);
+
+    let since = match (args.value_as::<u64>("since"), args.value("last")) {
+        (Some(since), _) => timestamps.parse(since),
+        (None, Some(last)) => match parse_duration(last) {
+            Ok(last) => Nanos::now().saturating_sub(last),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(2);
+            }
+        },
+        // Everything still in the ring
+        (None, None) => Nanos(0),
+    };
+    let timeout = Duration::from_millis(args.value_as::<u64>("timeout-ms").unwrap_or(1000));
+
+    let node = create_node(&NodeIdentity::from_args(&args, "trigger_history", None)?)?;
+    let client = TriggerHistoryClient::create(&node)?;
+    let entries = client.query(since, timeout)?;
+
+    for entry in &entries {
+        // Shown in CLOCK_REALTIME like `--since`, whatever the publisher stamped them in
+        let (CameraTrigger { frame_id: trigger_id, hw_timestamp_ns: hw_ns, publish_timestamp_ns: pub_ns, .. }, _) = clock::trigger_to_realtime(entry.trigger, entry.header)?;
+        let (hw_ts, pub_ts) = (timestamps.present(Nanos(hw_ns)), timestamps.present(Nanos(pub_ns)));
+        let timestamp_source = TimestampSource::label(entry.header.timestamp_source);
+        let clock_domain = ClockDomain::label(entry.header.clock_domain);
+        let trace = TraceId::new(entry.header.trace_origin, trigger_id);
+        status.line(
+            "historical_trigger",
+            format_args!(
+                "Historical trigger: id={}, hw_ts={}, pub_ts={}, src={}, clock={}, key_id={:016x}, trace={}",
+                trigger_id, hw_ts, pub_ts, timestamp_source, clock_domain, entry.header.key_id, trace
+            ),
+            &[
+                ("trigger_id", trigger_id.into()),
+                ("trace", trace.to_string().into()),
+                ("hw_ts", hw_ts.into()),
+                ("pub_ts", pub_ts.into()),
+                ("timestamp_source", timestamp_source.as_str().into()),
+                ("clock_domain", clock_domain.as_str().into()),
+                ("key_id", entry.header.key_id.into()),
+                ("signature", entry.header.signature_hex().into()),
+            ],
+        );
+    }
+    status.line(
+        "history_drained",
+        format_args!("Fetched {} triggers since {} ({})", entries.len(), timestamps.present(since), timestamps.describe()),
+        &[
+            ("triggers", entries.len().into()),
+            ("since_ns", since.0.into()),
+            ("timestamp_unit", timestamps.unit.to_string().into()),
+            ("timestamp_epoch", timestamps.epoch.to_string().into()),
+        ],
+    );
+    Ok(())
+}
@@ -0,0 +1,37 @@
+use sync_core::cli::Args;
+use sync_core::session::read_records;
+use sync_core::signing::{SignatureStatus, TriggerVerifier};
+use std::path::Path;
+
+// Re-checks every signed record of a session against the publisher's public key,
+// proving that the recorded trigger timestamps were not altered afterwards.
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::from_env(&[]);
+    let (Some(session_dir), Some(key_file)) = (args.positional::<String>(0), args.value("verify-key")) else {
+        println!("Usage: {} <session_dir> --verify-key <public_key_file>", args.program());
+        std::process::exit(2);
+    };
+
+    let verifier = TriggerVerifier::from_key_file(Path::new(key_file))?;
+    let records = read_records(Path::new(&session_dir))?;
+
+    let mut valid = 0;
+    let mut unsigned = 0;
+    let mut invalid = 0;
+    for record in &records {
+        match verifier.verify(&record.trigger, &record.signature) {
+            SignatureStatus::Valid => valid += 1,
+            SignatureStatus::Unsigned => unsigned += 1,
+            SignatureStatus::Invalid => {
+                invalid += 1;
+                println!("INVALID: trigger_id={}, hw_ts={}", record.trigger.frame_id, record.trigger.hw_timestamp_ns);
+            }
+        }
+    }
+
+    println!("Verified {} records: valid={}, unsigned={}, invalid={}", records.len(), valid, unsigned, invalid);
+    if invalid > 0 || unsigned > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}